@@ -0,0 +1,6 @@
+#![no_main]
+
+use implbox_macros_core_fuzz::{check_decls, Input};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: Input| check_decls(&input));
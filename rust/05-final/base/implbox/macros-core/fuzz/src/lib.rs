@@ -0,0 +1,237 @@
+//! Structured input for the implbox macro fuzz targets. Raw bytes
+//! would almost never parse as a function, so the fuzzer generates the
+//! pieces of a signature and of the attribute arguments, and these are
+//! rendered as source text. The checks assert that expansion never
+//! panics and that whatever it produces is syntactically valid.
+
+use arbitrary::Arbitrary;
+use proc_macro2::TokenStream;
+use quote::quote;
+
+#[derive(Arbitrary, Debug)]
+pub enum Name {
+    New(u8),
+    Other(u8),
+    BareNew,
+}
+
+#[derive(Arbitrary, Debug)]
+pub enum Bound {
+    Trait(u8),
+    Generic(u8, Box<Ty>),
+    Send,
+    Static,
+}
+
+#[derive(Arbitrary, Debug)]
+pub enum Generic {
+    Type(Vec<Bound>),
+    Lifetime,
+    Const,
+}
+
+#[derive(Arbitrary, Debug)]
+pub enum Pat {
+    Ident,
+    Mut,
+    Wild,
+    Tuple,
+}
+
+#[derive(Arbitrary, Debug, Clone)]
+pub enum Ty {
+    Int,
+    Str,
+    Param(u8),
+    Ref(Box<Ty>),
+    Tuple(Vec<Ty>),
+    Path(u8, Vec<Ty>),
+}
+
+#[derive(Arbitrary, Debug)]
+pub enum Ret {
+    Default,
+    Impl(Vec<Bound>),
+    Concrete(Ty),
+    ResultImpl(Ty),
+}
+
+#[derive(Arbitrary, Debug)]
+pub enum Receiver {
+    Ref,
+    Value,
+}
+
+#[derive(Arbitrary, Debug)]
+pub struct Signature {
+    is_async: bool,
+    is_const: bool,
+    is_unsafe: bool,
+    name: Name,
+    generics: Vec<Generic>,
+    receiver: Option<Receiver>,
+    args: Vec<(Pat, Ty)>,
+    ret: Ret,
+    where_clause: Option<(u8, Vec<Bound>)>,
+}
+
+#[derive(Arbitrary, Debug)]
+pub struct Input {
+    pub sig: Signature,
+    pub attr_args: Vec<(u8, Vec<Ty>)>,
+}
+
+fn join<T>(items: &[T], sep: &str, f: impl FnMut(&T) -> String) -> String {
+    items.iter().map(f).collect::<Vec<_>>().join(sep)
+}
+
+impl Bound {
+    fn render(&self) -> String {
+        match self {
+            Bound::Trait(n) => format!("Trait{n}"),
+            Bound::Generic(n, t) => format!("Trait{n}<{}>", t.render()),
+            Bound::Send => "Send".to_string(),
+            Bound::Static => "'static".to_string(),
+        }
+    }
+}
+
+fn render_bounds(first: &str, rest: &[Bound]) -> String {
+    let mut s = first.to_string();
+    for b in rest {
+        s += " + ";
+        s += &b.render();
+    }
+    s
+}
+
+impl Ty {
+    fn render(&self) -> String {
+        match self {
+            Ty::Int => "i32".to_string(),
+            Ty::Str => "&str".to_string(),
+            Ty::Param(n) => format!("T{}", n % 4),
+            Ty::Ref(t) => format!("&{}", t.render()),
+            Ty::Tuple(ts) => match ts.len() {
+                0 => "()".to_string(),
+                1 => format!("({},)", ts[0].render()),
+                _ => format!("({})", join(ts, ", ", Ty::render)),
+            },
+            Ty::Path(n, ts) if ts.is_empty() => format!("Type{n}"),
+            Ty::Path(n, ts) => format!("Type{n}<{}>", join(ts, ", ", Ty::render)),
+        }
+    }
+}
+
+impl Signature {
+    pub fn render(&self) -> String {
+        let mut s = String::new();
+        if self.is_const {
+            s += "const ";
+        }
+        if self.is_async {
+            s += "async ";
+        }
+        if self.is_unsafe {
+            s += "unsafe ";
+        }
+        s += "fn ";
+        s += &match self.name {
+            Name::New(n) => format!("new_thing{n}"),
+            Name::Other(n) => format!("thing{n}"),
+            Name::BareNew => "new_".to_string(),
+        };
+        if !self.generics.is_empty() {
+            let mut i = 0;
+            let params = join(&self.generics, ", ", |g| {
+                i += 1;
+                match g {
+                    Generic::Type(bounds) if bounds.is_empty() => format!("T{i}"),
+                    Generic::Type(bounds) => {
+                        format!("T{i}: {}", join(bounds, " + ", Bound::render))
+                    }
+                    Generic::Lifetime => format!("'a{i}"),
+                    Generic::Const => format!("const N{i}: usize"),
+                }
+            });
+            s += &format!("<{params}>");
+        }
+        let mut args = Vec::new();
+        match self.receiver {
+            Some(Receiver::Ref) => args.push("&self".to_string()),
+            Some(Receiver::Value) => args.push("self".to_string()),
+            None => {}
+        }
+        for (i, (pat, ty)) in self.args.iter().enumerate() {
+            let pat = match pat {
+                Pat::Ident => format!("a{i}"),
+                Pat::Mut => format!("mut a{i}"),
+                Pat::Wild => "_".to_string(),
+                Pat::Tuple => format!("(a{i}, b{i})"),
+            };
+            args.push(format!("{pat}: {}", ty.render()));
+        }
+        s += &format!("({})", args.join(", "));
+        match &self.ret {
+            Ret::Default => {}
+            Ret::Impl(bounds) => s += &format!(" -> impl {}", render_bounds("Thing", bounds)),
+            Ret::Concrete(t) => s += &format!(" -> {}", t.render()),
+            Ret::ResultImpl(e) => s += &format!(" -> Result<impl Thing, {}>", e.render()),
+        }
+        if let Some((n, bounds)) = &self.where_clause {
+            s += &format!(" where T{}: {}", n % 4, render_bounds("Other", bounds));
+        }
+        s
+    }
+}
+
+impl Input {
+    pub fn render_attr_args(&self) -> String {
+        join(&self.attr_args, ", ", |(n, ts)| {
+            Ty::Path(*n, ts.clone()).render()
+        })
+    }
+}
+
+fn tokens(src: &str) -> Option<TokenStream> {
+    src.parse().ok()
+}
+
+fn check_error(err: syn::Error) {
+    let out = err.into_compile_error();
+    syn::parse2::<syn::File>(out).expect("compile error is not a valid item");
+}
+
+/// Expand `implbox_decls` on a trait method declaration.
+pub fn check_decls(input: &Input) {
+    let Some(item) = tokens(&format!("{};", input.sig.render())) else {
+        return;
+    };
+    let Some(args) = tokens(&input.render_attr_args()) else {
+        return;
+    };
+    match implbox_macros_core::implbox_decls(args, item) {
+        Ok(out) => {
+            syn::parse2::<syn::ItemTrait>(quote! { trait Fuzz { #out } })
+                .expect("implbox_decls produced invalid code");
+        }
+        Err(e) => check_error(e),
+    }
+}
+
+/// Expand `implbox_impls` on a trait method implementation.
+pub fn check_impls(input: &Input) {
+    let Some(item) = tokens(&format!("{} {{ todo!() }}", input.sig.render())) else {
+        return;
+    };
+    let Some(args) = tokens(&input.render_attr_args()) else {
+        return;
+    };
+    match implbox_macros_core::implbox_impls(args, item) {
+        Ok(out) => {
+            syn::parse2::<syn::ItemImpl>(quote! { impl Fuzz for Thing { #out } })
+                .expect("implbox_impls produced invalid code");
+        }
+        Err(e) => check_error(e),
+    }
+}
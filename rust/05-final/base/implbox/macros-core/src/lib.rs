@@ -0,0 +1,1809 @@
+//! This is the implementation of the `implbox_decls` and
+//! `implbox_impls` attribute macros from `implbox-macros`. A
+//! proc-macro crate can only export macros, so the expansion logic is
+//! kept here, operating on [proc_macro2::TokenStream], where it can be
+//! called from tests and fuzz targets. Expansion never panics. Invalid
+//! input results in an error that the macro crate turns into a
+//! `compile_error!`.
+
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::{format_ident, quote, ToTokens};
+use std::path::PathBuf;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::token::Comma;
+use syn::Token;
+use syn::{
+    parse, FnArg, GenericArgument, GenericParam, Generics, Ident, ImplItemFn, ItemTrait, Lifetime,
+    LifetimeParam, LitStr, PathArguments, ReturnType, Signature, TraitItem, TraitItemFn, Type,
+    TypeImplTrait, TypeParamBound, TypePath, Visibility,
+};
+
+/// A parameter of the attribute macros: a type, a flag, or
+/// `name = "thing"`
+enum AttrArg {
+    Type(TypePath),
+    Name(LitStr),
+}
+
+impl Parse for AttrArg {
+    fn parse(input: ParseStream) -> parse::Result<Self> {
+        if input.peek(Ident) && input.peek2(Token![=]) {
+            let key: Ident = input.parse()?;
+            if key != "name" {
+                return Err(syn::Error::new_spanned(
+                    key,
+                    "the only named parameter is `name`",
+                ));
+            }
+            input.parse::<Token![=]>()?;
+            Ok(AttrArg::Name(input.parse()?))
+        } else {
+            Ok(AttrArg::Type(input.parse()?))
+        }
+    }
+}
+
+/// The parameters of an attribute macro: the types and flags, in
+/// order, and the name, if given
+struct Attrs {
+    types: Vec<TypePath>,
+    name: Option<LitStr>,
+}
+
+impl Parse for Attrs {
+    fn parse(input: ParseStream) -> parse::Result<Self> {
+        let mut attrs = Attrs {
+            types: Vec::new(),
+            name: None,
+        };
+        for arg in Punctuated::<AttrArg, Comma>::parse_terminated(input)? {
+            match arg {
+                AttrArg::Type(t) => attrs.types.push(t),
+                AttrArg::Name(n) if attrs.name.is_some() => {
+                    return Err(syn::Error::new_spanned(n, "`name` is given more than once"));
+                }
+                AttrArg::Name(n) => attrs.name = Some(n),
+            }
+        }
+        Ok(attrs)
+    }
+}
+
+pub fn implbox_decls(args: TokenStream, input: TokenStream) -> syn::Result<TokenStream> {
+    let item_decl: TraitItemFn = syn::parse2(input)?;
+    let attr: Attrs = syn::parse2(args)?;
+    let generic_type = match attr.types.as_slice() {
+        [generic_type] => generic_type,
+        [] => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "implbox_decls requires the shadow type, such as #[implbox_decls(ThingBox)]",
+            ))
+        }
+        [_, extra, ..] => {
+            return Err(syn::Error::new_spanned(
+                extra,
+                "implbox_decls takes only the shadow type and optionally a name",
+            ))
+        }
+    };
+    check_signature(&item_decl.sig, "implbox_decls")?;
+    let base = suffix(&item_decl.sig.ident, attr.name.as_ref(), "implbox_decls")?;
+    let decls = decl_items(item_decl.sig.clone(), generic_type, &base, "implbox_decls")?;
+    Ok(quote! {
+        #item_decl
+        #decls
+    })
+}
+
+/// The names of the functions generated for a constructor, which all
+/// end with the same suffix
+struct Names {
+    box_fn: Ident,
+    unbox_fn: Ident,
+    try_unbox_fn: Ident,
+    unbox_ref_fn: Ident,
+    unbox_mut_fn: Ident,
+    take_fn: Ident,
+    replace_fn: Ident,
+    box_shared_fn: Ident,
+    box_in_fn: Ident,
+    box_pinned_fn: Ident,
+    unbox_pinned_fn: Ident,
+    drop_fn: Ident,
+}
+
+impl Names {
+    fn new(base: &str) -> Self {
+        Self {
+            box_fn: format_ident!("box_{}", base),
+            unbox_fn: format_ident!("unbox_{}", base),
+            try_unbox_fn: format_ident!("try_unbox_{}", base),
+            unbox_ref_fn: format_ident!("unbox_ref_{}", base),
+            unbox_mut_fn: format_ident!("unbox_mut_{}", base),
+            take_fn: format_ident!("take_{}", base),
+            replace_fn: format_ident!("replace_{}", base),
+            box_shared_fn: format_ident!("box_shared_{}", base),
+            box_in_fn: format_ident!("box_in_{}", base),
+            box_pinned_fn: format_ident!("box_pinned_{}", base),
+            unbox_pinned_fn: format_ident!("unbox_pinned_{}", base),
+            drop_fn: format_ident!("drop_{}", base),
+        }
+    }
+}
+
+/// What the generated functions for a constructor need from its
+/// signature, which is the same for their declarations and their
+/// implementations
+struct Parts {
+    ident: Ident,
+    generics: Generics,
+    constness: Option<Token![const]>,
+    asyncness: Option<Token![async]>,
+    unsafety: Option<Token![unsafe]>,
+    inputs: Punctuated<FnArg, Comma>,
+    error: Option<Type>,
+    borrow_generics: Generics,
+    borrow: Option<Lifetime>,
+    ref_lifetime: Lifetime,
+    output: ReturnType,
+    try_output: ReturnType,
+    mut_output: ReturnType,
+    pinned_output: ReturnType,
+    take_output: ReturnType,
+    boxed: TokenStream,
+    shared: TokenStream,
+    names: Names,
+}
+
+impl Parts {
+    fn new(sig: Signature, generic_type: &TypePath, base: &str) -> syn::Result<Self> {
+        let (output, error) = split_result(sig.output);
+        let (borrow_generics, borrow) = borrow_generics(&sig.generics);
+        let pinned_output = create_pinned_output(&output, &borrow)?;
+        let mut_output = create_mut_output(&output, &borrow)?;
+        let take_output = output.clone();
+        let output = create_box_output(output, &borrow)?;
+        let ref_lifetime = borrow
+            .clone()
+            .unwrap_or_else(|| Lifetime::new("'_", Span::call_site()));
+        Ok(Self {
+            ident: sig.ident,
+            generics: sig.generics,
+            constness: sig.constness,
+            asyncness: sig.asyncness,
+            unsafety: sig.unsafety,
+            inputs: sig.inputs,
+            boxed: fallible(quote! { ImplBox<#generic_type> }, &error),
+            shared: fallible(quote! { ::implbox::ImplBoxShared<#generic_type> }, &error),
+            error,
+            borrow_generics,
+            borrow,
+            ref_lifetime,
+            try_output: create_try_output(&output),
+            output,
+            mut_output,
+            pinned_output,
+            take_output,
+            names: Names::new(base),
+        })
+    }
+
+    fn docs(&self, macro_name: &str, generic_type: &TypePath, base: &str, item: &str) -> Docs {
+        Docs::new(
+            macro_name,
+            &self.ident,
+            &self.unsafety,
+            &self.error,
+            generic_type,
+            base,
+            item,
+        )
+    }
+}
+
+/// Declarations of the generated functions for the constructor with
+/// signature `sig`, documented as coming from `macro_name`
+fn decl_items(
+    sig: Signature,
+    generic_type: &TypePath,
+    base: &str,
+    macro_name: &str,
+) -> syn::Result<TokenStream> {
+    let parts = Parts::new(sig, generic_type, base)?;
+    let item = format!(
+        "the item returned by the implementation's `{}`",
+        parts.ident
+    );
+    let Docs {
+        box_doc,
+        box_shared_doc,
+        box_in_doc,
+        box_pinned_doc,
+        unbox_doc,
+        try_unbox_doc,
+        unbox_mut_doc,
+        unbox_pinned_doc,
+        unbox_ref_doc,
+        take_doc,
+        replace_doc,
+        drop_doc,
+    } = parts.docs(macro_name, generic_type, base, &item);
+    let Parts {
+        generics,
+        constness,
+        asyncness,
+        unsafety,
+        inputs,
+        borrow_generics,
+        borrow,
+        ref_lifetime,
+        output,
+        try_output,
+        mut_output,
+        pinned_output,
+        take_output,
+        boxed,
+        shared,
+        names:
+            Names {
+                box_fn,
+                unbox_fn,
+                try_unbox_fn,
+                unbox_ref_fn,
+                unbox_mut_fn,
+                take_fn,
+                replace_fn,
+                box_shared_fn,
+                box_in_fn,
+                box_pinned_fn,
+                unbox_pinned_fn,
+                drop_fn,
+            },
+        ..
+    } = parts;
+    let where_clause = &generics.where_clause;
+    let replace_inputs = with_arg(
+        &inputs,
+        syn::parse_quote! { l: &mut ImplBox<#generic_type> },
+    );
+    let box_in_inputs = with_arg(
+        &inputs,
+        syn::parse_quote! { allocator: ::implbox::Allocator },
+    );
+
+    // `pub`, `default`, `const`, `async`, `unsafe`, `extern`
+    Ok(quote! {
+        #box_doc
+        #constness #asyncness #unsafety fn #box_fn #generics (#inputs) -> #boxed #where_clause;
+        #box_shared_doc
+        #asyncness #unsafety fn #box_shared_fn #generics (#inputs) -> #shared #where_clause;
+        #box_in_doc
+        #asyncness #unsafety fn #box_in_fn #generics (#box_in_inputs) -> #boxed #where_clause;
+        #box_pinned_doc
+        #constness #asyncness #unsafety fn #box_pinned_fn #generics (#inputs) -> #boxed #where_clause;
+        #unbox_doc
+        fn #unbox_fn #borrow_generics (l: &#borrow ImplBox<#generic_type>) #output #where_clause;
+        #try_unbox_doc
+        fn #try_unbox_fn #borrow_generics (l: &#borrow ImplBox<#generic_type>) #try_output #where_clause;
+        #unbox_mut_doc
+        fn #unbox_mut_fn #borrow_generics (l: &#borrow mut ImplBox<#generic_type>) #mut_output #where_clause;
+        #unbox_pinned_doc
+        fn #unbox_pinned_fn #borrow_generics (l: &#borrow mut ImplBox<#generic_type>) #pinned_output #where_clause;
+        #unbox_ref_doc
+        fn #unbox_ref_fn #borrow_generics (l: ::implbox::ImplBoxRef<#ref_lifetime, #generic_type>) #output #where_clause;
+        #take_doc
+        fn #take_fn #generics(l: ImplBox<#generic_type>) #take_output #where_clause;
+        #replace_doc
+        #asyncness #unsafety fn #replace_fn #generics (#replace_inputs) -> #boxed #where_clause;
+        #drop_doc
+        fn #drop_fn #generics (p: *const ()) #where_clause;
+    })
+}
+
+pub fn implbox_impls(args: TokenStream, input: TokenStream) -> syn::Result<TokenStream> {
+    let item_impl: ImplItemFn = syn::parse2(input)?;
+    let attr: Attrs = syn::parse2(args)?;
+    let mut iter = attr.types.iter();
+    let (generic_type, concrete_path) = match (iter.next(), iter.next()) {
+        (Some(generic_type), Some(concrete_path)) => (generic_type, concrete_path),
+        (Some(generic_type), None) => {
+            return Err(syn::Error::new_spanned(
+                generic_type,
+                "implbox_impls requires the concrete type after the shadow type, such as #[implbox_impls(ThingBox, Thing)]",
+            ))
+        }
+        _ => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "implbox_impls requires the shadow type and the concrete type, such as #[implbox_impls(ThingBox, Thing)]",
+            ))
+        }
+    };
+    // Recording the concrete type's id requires it to be 'static, and
+    // serialization requires serde traits, so these are opt-in.
+    let mut set_flags = TokenStream::new();
+    for flag in iter {
+        if flag.path.is_ident("downcast") {
+            set_flags.extend(quote! {
+                let b = unsafe { b.set_item_id(::core::any::TypeId::of::<#concrete_path>()) };
+            });
+        } else if flag.path.is_ident("serde") {
+            set_flags.extend(quote! {
+                let b = unsafe { b.set_serde_hooks(::implbox::SerdeHooks::of::<#concrete_path>()) };
+            });
+        } else {
+            return Err(syn::Error::new_spanned(
+                flag,
+                "implbox_impls flags must be `downcast` or `serde`",
+            ));
+        }
+    }
+    check_signature(&item_impl.sig, "implbox_impls")?;
+    let orig = item_impl.clone();
+    let ident = &item_impl.sig.ident;
+    let base = suffix(ident, attr.name.as_ref(), "implbox_impls")?;
+    let item = format!(
+        "the `{}` returned by `{}`",
+        type_string(concrete_path),
+        ident
+    );
+    let parts = Parts::new(item_impl.sig, generic_type, &base)?;
+    let docs = parts.docs("implbox_impls", generic_type, &base, &item);
+    let probe = quote! {
+        let probe = &::implbox::__private::Probe::<#concrete_path>::new();
+    };
+    let items = impl_items(parts, generic_type, probe, set_flags, docs);
+    Ok(quote! {
+        #orig
+        #items
+    })
+}
+
+pub fn implbox_trait(args: TokenStream, input: TokenStream) -> syn::Result<TokenStream> {
+    let handles = if args.is_empty() {
+        false
+    } else {
+        let flag: Ident = syn::parse2(args.clone()).map_err(|_| {
+            syn::Error::new_spanned(&args, "implbox_trait takes only the `handles` flag")
+        })?;
+        if flag != "handles" {
+            return Err(syn::Error::new_spanned(
+                flag,
+                "implbox_trait takes only the `handles` flag",
+            ));
+        }
+        true
+    };
+    let item_trait: ItemTrait = syn::parse2(input)?;
+    if !item_trait.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &item_trait.generics,
+            "implbox_trait does not support generic traits; use implbox_decls and implbox_impls",
+        ));
+    }
+    let vis = &item_trait.vis;
+    let trait_ident = &item_trait.ident;
+    let helper = format_ident!("{}ImplBox", trait_ident);
+
+    let mut shadows = TokenStream::new();
+    let mut decls = TokenStream::new();
+    let mut impls = TokenStream::new();
+    let mut handle_types = TokenStream::new();
+    for item in &item_trait.items {
+        let TraitItem::Fn(f) = item else {
+            continue;
+        };
+        let ReturnType::Type(_, t) = split_result(f.sig.output.clone()).0 else {
+            continue;
+        };
+        let Type::ImplTrait(ret) = *t else {
+            continue;
+        };
+        let name = f.sig.ident.to_string();
+        let base = match name.strip_prefix("new_") {
+            Some(base) if !base.is_empty() => base,
+            _ => &name,
+        };
+        let shadow = format_ident!("{}Box", camel_case(base));
+        shadows.extend(shadow_type(vis, &shadow, base, &f.sig.generics, &ret));
+        let (_, g_type, _) = f.sig.generics.split_for_impl();
+        let generic_type: TypePath = syn::parse_quote! { #shadow #g_type };
+        decls.extend(decl_items(
+            f.sig.clone(),
+            &generic_type,
+            base,
+            "implbox_trait",
+        )?);
+        impls.extend(trait_impl_items(f.sig.clone(), &generic_type, base)?);
+        if handles {
+            let handle = format_ident!("{}Handle", camel_case(base));
+            handle_types.extend(handle_type(
+                vis,
+                trait_ident,
+                &helper,
+                &handle,
+                base,
+                &f.sig,
+                &generic_type,
+            )?);
+        }
+    }
+    if decls.is_empty() {
+        return Err(syn::Error::new_spanned(
+            trait_ident,
+            "implbox_trait requires a function that returns impl Trait",
+        ));
+    }
+
+    let helper_doc = format!(
+        " Generated by implbox_trait -- box functions for the constructors of [{trait_ident}]"
+    );
+    Ok(quote! {
+        #item_trait
+        #shadows
+        #[doc = #helper_doc]
+        #vis trait #helper {
+            #decls
+        }
+
+        impl<X: #trait_ident + 'static> #helper for X {
+            #impls
+        }
+
+        #handle_types
+    })
+}
+
+/// Return the handle type for a constructor generated by
+/// `implbox_trait` with the `handles` flag. It wraps a box created by a
+/// particular implementation `X` of the trait, so it can't be passed
+/// to another implementation's unbox functions.
+fn handle_type(
+    vis: &Visibility,
+    trait_ident: &Ident,
+    helper: &Ident,
+    handle: &Ident,
+    base: &str,
+    sig: &Signature,
+    generic_type: &TypePath,
+) -> syn::Result<TokenStream> {
+    if let Some(FnArg::Receiver(r)) = sig.inputs.first() {
+        return Err(syn::Error::new_spanned(
+            r,
+            "implbox_trait can't generate a handle for a constructor that takes self",
+        ));
+    }
+    let (output, error) = split_result(sig.output.clone());
+    let get_output = create_box_output(output.clone(), &None)?;
+    let mut_output = create_mut_output(&output, &None)?;
+    let asyncness = &sig.asyncness;
+    let unsafety = &sig.unsafety;
+    let where_clause = &sig.generics.where_clause;
+    let call_fish = call_turbofish(&sig.generics);
+    let box_fn = format_ident!("box_{}", base);
+    let (box_inputs, params) = forward_inputs(&sig.inputs);
+    // The handle can only have been created by `X`, so it reads the
+    // item directly. Going through the helper trait would return a type
+    // that captures the borrow of the handle.
+    let probe = probe_stmt(
+        quote! { <X as #trait_ident> },
+        &sig.ident,
+        &call_fish,
+        params.len(),
+        asyncness,
+        unsafety,
+        &error,
+    );
+    let (create, _) = create_item(
+        quote! { <X as #helper>::#box_fn #call_fish(#(#params),*) },
+        asyncness,
+        &error,
+    );
+    let ret = fallible(quote! { Self }, &error);
+    let wrapped = ok(quote! { Self(b, ::core::marker::PhantomData) }, &error);
+
+    // The implementation goes after any lifetimes.
+    let mut generics = sig.generics.clone();
+    let position = generics.lifetimes().count();
+    generics
+        .params
+        .insert(position, syn::parse_quote! { X: #trait_ident + 'static });
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let mut bare = generics.clone();
+    bare.where_clause = None;
+    for p in bare.params.iter_mut() {
+        match p {
+            GenericParam::Type(t) => {
+                t.colon_token = None;
+                t.bounds.clear();
+                t.eq_token = None;
+                t.default = None;
+            }
+            GenericParam::Lifetime(l) => {
+                l.colon_token = None;
+                l.bounds.clear();
+            }
+            GenericParam::Const(c) => {
+                c.eq_token = None;
+                c.default = None;
+            }
+        }
+    }
+
+    let doc = format!(
+        " Generated by implbox_trait -- a box created by `X::box_{base}`, which can only be unboxed by `X`"
+    );
+    let new_doc = format!(" Create the item with `X::{}` and box it", sig.ident);
+    Ok(quote! {
+        #[doc = #doc]
+        #vis struct #handle #bare(ImplBox<#generic_type>, ::core::marker::PhantomData<fn() -> X>);
+
+        impl #impl_generics #handle #ty_generics #where_clause {
+            #[doc = #new_doc]
+            pub #asyncness #unsafety fn new(#box_inputs) -> #ret {
+                let b = #create;
+                #wrapped
+            }
+
+            /// Borrow the item
+            pub fn get(&self) #get_output {
+                #probe
+                self.0.with(::core::any::TypeId::of::<X>(), |p| unsafe { probe.cast(p) })
+            }
+
+            /// Borrow the item mutably
+            pub fn get_mut(&mut self) #mut_output {
+                #probe
+                self.0.with_mut(::core::any::TypeId::of::<X>(), |p| unsafe { probe.cast_mut(p) })
+            }
+
+            /// Consume the handle and take back ownership of the item
+            pub fn take(self) #output {
+                #probe
+                unsafe { probe.take(self.0, ::core::any::TypeId::of::<X>()) }
+            }
+
+            /// Return the box
+            pub fn into_inner(self) -> ImplBox<#generic_type> {
+                self.0
+            }
+        }
+    })
+}
+
+/// Return the shadow type for a constructor generated by
+/// `implbox_trait`. It has the constructor's generic parameters, and
+/// it is `Send` and `Sync` only if the constructor's return type says
+/// so, since that is all that is known about the concrete type.
+fn shadow_type(
+    vis: &Visibility,
+    shadow: &Ident,
+    base: &str,
+    generics: &Generics,
+    ret: &TypeImplTrait,
+) -> TokenStream {
+    let has_bound = |name: &str| {
+        ret.bounds.iter().any(|b| match b {
+            TypeParamBound::Trait(t) => t.path.segments.last().is_some_and(|s| s.ident == name),
+            _ => false,
+        })
+    };
+    let marker = match (has_bound("Send"), has_bound("Sync")) {
+        (true, true) => quote! { () },
+        (true, false) => quote! { ::core::cell::Cell<()> },
+        (false, _) => quote! { *const () },
+    };
+    let mut params = Vec::new();
+    let mut phantoms = Vec::new();
+    for p in &generics.params {
+        match p {
+            GenericParam::Type(t) => {
+                let ident = &t.ident;
+                params.push(quote! { #ident });
+                phantoms.push(quote! { #ident });
+            }
+            GenericParam::Lifetime(l) => {
+                let lifetime = &l.lifetime;
+                params.push(quote! { #lifetime });
+                phantoms.push(quote! { &#lifetime () });
+            }
+            GenericParam::Const(c) => {
+                let ident = &c.ident;
+                let ty = &c.ty;
+                params.push(quote! { const #ident: #ty });
+            }
+        }
+    }
+    let params = if params.is_empty() {
+        TokenStream::new()
+    } else {
+        quote! { <#(#params),*> }
+    };
+    let doc =
+        format!(" Generated by implbox_trait -- the shadow type for boxes created by `box_{base}`");
+    quote! {
+        #[doc = #doc]
+        #vis struct #shadow #params(::core::marker::PhantomData<(#marker, fn() -> (#(#phantoms,)*))>);
+    }
+}
+
+/// Implementations of the functions declared by [decl_items] for any
+/// implementation of a trait annotated with `implbox_trait`. These
+/// can't name the concrete type, so they get a probe for it from a
+/// closure that calls the constructor but is never run.
+fn trait_impl_items(
+    sig: Signature,
+    generic_type: &TypePath,
+    base: &str,
+) -> syn::Result<TokenStream> {
+    for arg in &sig.inputs {
+        if let FnArg::Typed(t) = arg {
+            if let Type::ImplTrait(_) = &*t.ty {
+                return Err(syn::Error::new_spanned(
+                    arg,
+                    "implbox_trait does not support impl Trait arguments; use implbox_decls and implbox_impls",
+                ));
+            }
+        }
+    }
+    let parts = Parts::new(sig, generic_type, base)?;
+    let probe = probe_stmt(
+        quote! { Self },
+        &parts.ident,
+        &call_turbofish(&parts.generics),
+        parts.inputs.len(),
+        &parts.asyncness,
+        &parts.unsafety,
+        &parts.error,
+    );
+    // The declarations in the helper trait carry the docs.
+    Ok(impl_items(
+        parts,
+        generic_type,
+        probe,
+        TokenStream::new(),
+        Docs::default(),
+    ))
+}
+
+/// Implementations of the functions declared by [decl_items]. `probe`
+/// is the statement that binds `probe` to the `Probe` for the concrete
+/// type, which `implbox_impls` is given and `implbox_trait` has to
+/// infer, and `set_flags` adjusts the new box `b` before it is returned.
+fn impl_items(
+    parts: Parts,
+    generic_type: &TypePath,
+    probe: TokenStream,
+    set_flags: TokenStream,
+    docs: Docs,
+) -> TokenStream {
+    let Docs {
+        box_doc,
+        box_shared_doc,
+        box_in_doc,
+        box_pinned_doc,
+        unbox_doc,
+        try_unbox_doc,
+        unbox_mut_doc,
+        unbox_pinned_doc,
+        unbox_ref_doc,
+        take_doc,
+        replace_doc,
+        drop_doc,
+    } = docs;
+    let Parts {
+        ident,
+        generics,
+        constness,
+        asyncness,
+        unsafety,
+        inputs,
+        error,
+        borrow_generics,
+        borrow,
+        ref_lifetime,
+        output,
+        try_output,
+        mut_output,
+        pinned_output,
+        take_output,
+        boxed,
+        shared,
+        names:
+            Names {
+                box_fn,
+                unbox_fn,
+                try_unbox_fn,
+                unbox_ref_fn,
+                unbox_mut_fn,
+                take_fn,
+                replace_fn,
+                box_shared_fn,
+                box_in_fn,
+                box_pinned_fn,
+                unbox_pinned_fn,
+                drop_fn,
+            },
+    } = parts;
+    let where_clause = &generics.where_clause;
+    let call_fish = call_turbofish(&generics);
+
+    let (box_inputs, params) = forward_inputs(&inputs);
+    let replace_inputs = with_arg(
+        &box_inputs,
+        syn::parse_quote! { l: &mut ImplBox<#generic_type> },
+    );
+    let box_in_inputs = with_arg(
+        &box_inputs,
+        syn::parse_quote! { allocator: ::implbox::Allocator },
+    );
+    let (create, ok_b) = create_item(
+        quote! { Self::#ident #call_fish(#(#params),*) },
+        &asyncness,
+        &error,
+    );
+    // The body of the box functions given an expression that creates
+    // the ImplBox from `item`
+    let box_body = |new: TokenStream| {
+        quote! {
+            let item = #create;
+            #[allow(unused_imports)]
+            use ::implbox::__private::{CloneNo as _, CloneYes as _, SendNo as _, SendYes as _};
+            #probe
+            let b = #new
+                .set_clone_fn(probe.clone_fn())
+                .set_type_name(probe.type_name())
+                .set_send_check(probe.is_send());
+            #set_flags
+            #ok_b
+        }
+    };
+    let box_new = box_body(quote! {
+        ImplBox::new(::core::any::TypeId::of::<Self>(), Self::#drop_fn #call_fish, item)
+    });
+    let box_in = box_body(quote! {
+        ImplBox::new_in(::core::any::TypeId::of::<Self>(), Self::#drop_fn #call_fish, item, allocator)
+    });
+
+    let mut box_call = quote! { Self::#box_fn #call_fish(#(#params),*) };
+    if asyncness.is_some() {
+        box_call = quote! { #box_call.await };
+    }
+    if unsafety.is_some() {
+        box_call = quote! { unsafe { #box_call } };
+    }
+    let box_shared = shared_body(box_call, &error);
+    let replaced = ok(
+        quote! { unsafe { l.replace(::core::any::TypeId::of::<Self>(), item) } },
+        &error,
+    );
+
+    // `pub`, `default`, `const`, `async`, `unsafe`, `extern`
+    quote! {
+        #box_doc
+        #constness #asyncness #unsafety fn #box_fn #generics (#box_inputs) -> #boxed #where_clause {
+            #box_new
+        }
+
+        #box_in_doc
+        #asyncness #unsafety fn #box_in_fn #generics (#box_in_inputs) -> #boxed #where_clause {
+            #box_in
+        }
+
+        #box_shared_doc
+        #asyncness #unsafety fn #box_shared_fn #generics (#box_inputs) -> #shared #where_clause {
+            #box_shared
+        }
+
+        #box_pinned_doc
+        #constness #asyncness #unsafety fn #box_pinned_fn #generics (#box_inputs) -> #boxed #where_clause {
+            let item = #create;
+            #[allow(unused_imports)]
+            use ::implbox::__private::{SendNo as _, SendYes as _};
+            #probe
+            let b = ImplBox::box_pinned(::core::any::TypeId::of::<Self>(), Self::#drop_fn #call_fish, item)
+                .set_type_name(probe.type_name())
+                .set_send_check(probe.is_send());
+            #set_flags
+            #ok_b
+        }
+
+        #unbox_pinned_doc
+        fn #unbox_pinned_fn #borrow_generics (l: &#borrow mut ImplBox<#generic_type>) #pinned_output #where_clause {
+            #probe
+            // The box was created pinned, so the item is on the heap and
+            // is never moved until it is dropped in place.
+            l.with_pinned(::core::any::TypeId::of::<Self>(), |p| unsafe {
+                ::core::pin::Pin::new_unchecked(probe.cast_mut(p))
+            })
+        }
+
+        #unbox_doc
+        fn #unbox_fn #borrow_generics (l: &#borrow ImplBox<#generic_type>) #output #where_clause {
+            #probe
+            l.with(::core::any::TypeId::of::<Self>(), |p| unsafe { probe.cast(p) })
+        }
+
+        #try_unbox_doc
+        fn #try_unbox_fn #borrow_generics (l: &#borrow ImplBox<#generic_type>) #try_output #where_clause {
+            #probe
+            l.try_with(::core::any::TypeId::of::<Self>(), |p| unsafe { probe.cast(p) })
+        }
+
+        #unbox_mut_doc
+        fn #unbox_mut_fn #borrow_generics (l: &#borrow mut ImplBox<#generic_type>) #mut_output #where_clause {
+            #probe
+            l.with_mut(::core::any::TypeId::of::<Self>(), |p| unsafe { probe.cast_mut(p) })
+        }
+
+        #unbox_ref_doc
+        fn #unbox_ref_fn #borrow_generics (l: ::implbox::ImplBoxRef<#ref_lifetime, #generic_type>) #output #where_clause {
+            #probe
+            l.with(::core::any::TypeId::of::<Self>(), |p| unsafe { probe.cast(p) })
+        }
+
+        #take_doc
+        fn #take_fn #generics (l: ImplBox<#generic_type>) #take_output #where_clause {
+            #probe
+            unsafe { probe.take(l, ::core::any::TypeId::of::<Self>()) }
+        }
+
+        #replace_doc
+        #asyncness #unsafety fn #replace_fn #generics (#replace_inputs) -> #boxed #where_clause {
+            let item = #create;
+            #replaced
+        }
+
+        #drop_doc
+        // Only the box calls this, with the pointer to its own item.
+        #[allow(clippy::not_unsafe_ptr_arg_deref)]
+        fn #drop_fn #generics (p: *const ()) #where_clause {
+            #probe
+            unsafe { probe.drop_in_place(p) };
+        }
+    }
+}
+
+/// Return the statement that binds `probe` to the probe for the item
+/// created by `implementor`'s constructor. The constructor is never
+/// called; the closure is only there so the compiler infers its type.
+fn probe_stmt(
+    implementor: TokenStream,
+    ident: &Ident,
+    call_fish: &TokenStream,
+    n_params: usize,
+    asyncness: &Option<Token![async]>,
+    unsafety: &Option<Token![unsafe]>,
+    error: &Option<Type>,
+) -> TokenStream {
+    let unreachable = (0..n_params).map(|_| quote! { ::core::unreachable!() });
+    let mut hint = quote! { #implementor::#ident #call_fish(#(#unreachable),*) };
+    if unsafety.is_some() {
+        hint = quote! { unsafe { #hint } };
+    }
+    let mut probe_of = if asyncness.is_some() {
+        quote! { probe_of_output(|| #hint) }
+    } else {
+        quote! { probe_of(|| #hint) }
+    };
+    if error.is_some() {
+        probe_of = quote! { #probe_of.ok() };
+    }
+    quote! {
+        #[allow(unreachable_code)]
+        let probe = &::implbox::__private::#probe_of;
+    }
+}
+
+/// Doc comments for the functions generated for one constructor. They
+/// say what each function does, what the box holds, and what the caller
+/// has to guarantee, since the generated code is otherwise invisible.
+#[derive(Default)]
+struct Docs {
+    box_doc: TokenStream,
+    box_shared_doc: TokenStream,
+    box_in_doc: TokenStream,
+    box_pinned_doc: TokenStream,
+    unbox_doc: TokenStream,
+    try_unbox_doc: TokenStream,
+    unbox_mut_doc: TokenStream,
+    unbox_pinned_doc: TokenStream,
+    unbox_ref_doc: TokenStream,
+    take_doc: TokenStream,
+    replace_doc: TokenStream,
+    drop_doc: TokenStream,
+}
+
+impl Docs {
+    /// `ctor` is the constructor, and `item` describes what it returns,
+    /// naming the concrete type if the macro knows it.
+    fn new(
+        macro_name: &str,
+        ctor: &Ident,
+        unsafety: &Option<Token![unsafe]>,
+        error: &Option<Type>,
+        generic_type: &TypePath,
+        base: &str,
+        item: &str,
+    ) -> Self {
+        let shadow = type_string(generic_type);
+        let by = format!("Generated by {macro_name} --");
+        let holds = format!(
+            "The box holds {item}. It records the implementation that created it, and only that implementation can unbox it."
+        );
+        let mut create = Vec::new();
+        if error.is_some() {
+            create.push(format!("If `{ctor}` fails, its error is returned."));
+        }
+        if unsafety.is_some() {
+            create.push("# Safety".to_string());
+            create.push(format!(
+                "The caller must uphold the safety requirements of `{ctor}`."
+            ));
+        }
+        let box_doc = |first: String| {
+            let mut lines = vec![first, holds.clone()];
+            lines.extend(create.iter().cloned());
+            doc_attrs(&lines)
+        };
+        let panics = |first: String, when: &str| {
+            doc_attrs(&[
+                first,
+                "# Panics".to_string(),
+                format!("If the box was created by a different implementation{when}."),
+            ])
+        };
+        Docs {
+            box_doc: box_doc(format!(
+                "{by} call `{ctor}` and box the result as `ImplBox<{shadow}>`."
+            )),
+            box_shared_doc: box_doc(format!(
+                "{by} call `{ctor}` and box the result with shared ownership as `ImplBoxShared<{shadow}>`."
+            )),
+            box_in_doc: box_doc(format!(
+                "{by} call `{ctor}` and box the result as `ImplBox<{shadow}>`, using `allocator` if it doesn't fit inline."
+            )),
+            box_pinned_doc: box_doc(format!(
+                "{by} call `{ctor}` and box the result as `ImplBox<{shadow}>` on the heap, where it will never move. Unbox it with `unbox_pinned_{base}`."
+            )),
+            unbox_doc: panics(format!("{by} borrow {item} from a box created by `box_{base}`."), ""),
+            try_unbox_doc: doc_attrs(&[format!(
+                "{by} borrow {item} from a box created by `box_{base}`, or return an error if the box was created by a different implementation."
+            )]),
+            unbox_mut_doc: panics(
+                format!("{by} mutably borrow {item} from a box created by `box_{base}`."),
+                " or is pinned",
+            ),
+            unbox_pinned_doc: panics(
+                format!("{by} borrow {item} from a box created by `box_pinned_{base}`."),
+                " or is not pinned",
+            ),
+            unbox_ref_doc: panics(
+                format!("{by} borrow {item} from an `ImplBoxRef` to a box created by `box_{base}`."),
+                "",
+            ),
+            take_doc: panics(
+                format!("{by} consume a box created by `box_{base}` and take back ownership of {item}."),
+                " or is pinned",
+            ),
+            replace_doc: {
+                let mut lines = vec![
+                    format!("{by} call `{ctor}` and put the result in `l`, returning the old item in its own box."),
+                    holds.clone(),
+                ];
+                lines.extend(create.iter().cloned());
+                lines.push("# Panics".to_string());
+                lines.push("If `l` was created by a different implementation.".to_string());
+                doc_attrs(&lines)
+            },
+            drop_doc: doc_attrs(&[
+                format!("{by} drop {item} in place. This is called by boxes created by this implementation when they are dropped."),
+                "# Safety".to_string(),
+                format!("This is not marked unsafe so that it can be stored in the box, but `p` must point to the item of an `ImplBox<{shadow}>` created by this implementation, and the item must not be used again. Don't call it directly."),
+            ]),
+        }
+    }
+}
+
+/// Return `#[doc]` attributes for `paragraphs`, separated by blank lines
+fn doc_attrs(paragraphs: &[String]) -> TokenStream {
+    let mut result = TokenStream::new();
+    for (i, p) in paragraphs.iter().enumerate() {
+        if i > 0 {
+            result.extend(quote! { #[doc = ""] });
+        }
+        let line = format!(" {p}");
+        result.extend(quote! { #[doc = #line] });
+    }
+    result
+}
+
+/// Return `t` as it would be written in source, for use in docs
+fn type_string(t: &impl ToTokens) -> String {
+    let mut s = t.to_token_stream().to_string();
+    for (from, to) in [
+        (" :: ", "::"),
+        (":: ", "::"),
+        (" <", "<"),
+        ("< ", "<"),
+        (" >", ">"),
+        (" ,", ","),
+    ] {
+        s = s.replace(from, to);
+    }
+    s
+}
+
+/// Turn `snake_case` into `CamelCase`.
+fn camel_case(name: &str) -> String {
+    name.split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// Return the turbofish for calling the original function from a
+/// generated one. Type parameters that appear only in the return type
+/// can't be inferred, so they are passed explicitly. Lifetimes are left
+/// to inference since they may be late-bound.
+fn call_turbofish(generics: &Generics) -> TokenStream {
+    let call_params: Vec<TokenStream> = generics
+        .params
+        .iter()
+        .filter_map(|p| match p {
+            GenericParam::Type(t) => Some(t.ident.to_token_stream()),
+            GenericParam::Const(c) => Some(c.ident.to_token_stream()),
+            GenericParam::Lifetime(_) => None,
+        })
+        .collect();
+    if call_params.is_empty() {
+        TokenStream::new()
+    } else {
+        quote! { ::<#(#call_params),*> }
+    }
+}
+
+/// Return the inputs for a generated function that forwards its
+/// arguments to the original function, along with the expressions to
+/// forward. Arguments may be arbitrary patterns, which can't be
+/// forwarded as expressions, so each one is given a plain name.
+fn forward_inputs(
+    inputs: &Punctuated<FnArg, Comma>,
+) -> (Punctuated<FnArg, Comma>, Vec<TokenStream>) {
+    let mut box_inputs = Punctuated::new();
+    let mut params = Vec::new();
+    for (i, arg) in inputs.iter().enumerate() {
+        match arg {
+            FnArg::Receiver(r) => {
+                box_inputs.push(arg.clone());
+                params.push(r.self_token.to_token_stream());
+            }
+            FnArg::Typed(t) => {
+                let name = format_ident!("arg{}", i);
+                let ty = &t.ty;
+                box_inputs.push(syn::parse_quote! { #name: #ty });
+                params.push(name.to_token_stream());
+            }
+        }
+    }
+    (box_inputs, params)
+}
+
+/// Add `arg` to `inputs` after the receiver, if any.
+fn with_arg(inputs: &Punctuated<FnArg, Comma>, arg: FnArg) -> Punctuated<FnArg, Comma> {
+    let mut result = Punctuated::new();
+    let mut rest = inputs.iter().peekable();
+    if let Some(r @ FnArg::Receiver(_)) = rest.peek() {
+        result.push((*r).clone());
+        rest.next();
+    }
+    result.push(arg);
+    result.extend(rest.cloned());
+    result
+}
+
+/// Return the suffix of the generated function names, which is `name`
+/// if given and otherwise the part of the constructor's name after
+/// `new_`.
+fn suffix(ident: &Ident, name: Option<&LitStr>, macro_name: &str) -> syn::Result<String> {
+    if let Some(name) = name {
+        let value = name.value();
+        if value.is_empty() || syn::parse_str::<Ident>(&format!("box_{value}")).is_err() {
+            return Err(syn::Error::new_spanned(
+                name,
+                "name must be usable in an identifier, such as \"thing\"",
+            ));
+        }
+        return Ok(value);
+    }
+    match ident.to_string().strip_prefix("new_") {
+        Some(base) if !base.is_empty() => Ok(base.to_string()),
+        _ => Err(syn::Error::new_spanned(
+            ident,
+            format!(
+                "function for {macro_name} must be named new_something, such as new_thing, or the name must be given, such as name = \"thing\""
+            ),
+        )),
+    }
+}
+
+/// Check that `sig` is a constructor that the macros can handle. The
+/// error points at the part of the signature that is the problem.
+fn check_signature(sig: &Signature, macro_name: &str) -> syn::Result<()> {
+    if let Some(variadic) = &sig.variadic {
+        return Err(syn::Error::new_spanned(
+            variadic,
+            format!("{macro_name} does not support variadic functions"),
+        ));
+    }
+    match split_result(sig.output.clone()).0 {
+        ReturnType::Default => {
+            return Err(syn::Error::new(
+                sig.paren_token.span.close(),
+                "expected a return type of `impl Trait` or `Result<impl Trait, E>` after this",
+            ))
+        }
+        output => {
+            impl_type(&output)?;
+        }
+    }
+    // The generated functions call the original with a turbofish,
+    // which isn't allowed if it has impl Trait arguments.
+    if !call_turbofish(&sig.generics).is_empty() {
+        for arg in &sig.inputs {
+            if let FnArg::Typed(t) = arg {
+                if let Type::ImplTrait(_) = &*t.ty {
+                    return Err(syn::Error::new_spanned(
+                        &t.ty,
+                        format!("{macro_name} does not support impl Trait arguments in a generic function; use a named type parameter"),
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Return the `impl` type from the original return type, parenthesized
+/// if necessary so that a reference to it can be taken.
+fn impl_type(orig: &ReturnType) -> syn::Result<Type> {
+    match orig {
+        ReturnType::Type(_, t) => match &**t {
+            // `&impl A + B` is ambiguous, so parenthesize multiple bounds.
+            Type::ImplTrait(t) if t.bounds.len() > 1 => Ok(syn::parse_quote! { (#t) }),
+            Type::ImplTrait(t) => Ok(Type::ImplTrait(t.clone())),
+            _ => Err(syn::Error::new_spanned(
+                t,
+                "return type must be `impl Trait` or `Result<impl Trait, E>`",
+            )),
+        },
+        ReturnType::Default => Err(syn::Error::new(
+            Span::call_site(),
+            "return type must be `impl Trait` or `Result<impl Trait, E>`",
+        )),
+    }
+}
+
+/// Split `-> Result<impl Thing, E>` into `-> impl Thing` and `E`. Any
+/// other return type is returned unchanged with no error type.
+fn split_result(output: ReturnType) -> (ReturnType, Option<Type>) {
+    let ReturnType::Type(arrow, t) = &output else {
+        return (output, None);
+    };
+    let Type::Path(TypePath { qself: None, path }) = &**t else {
+        return (output, None);
+    };
+    let Some(last) = path.segments.last().filter(|s| s.ident == "Result") else {
+        return (output, None);
+    };
+    let PathArguments::AngleBracketed(args) = &last.arguments else {
+        return (output, None);
+    };
+    let mut args = args.args.iter();
+    match (args.next(), args.next(), args.next()) {
+        (
+            Some(GenericArgument::Type(item @ Type::ImplTrait(_))),
+            Some(GenericArgument::Type(error)),
+            None,
+        ) => (
+            ReturnType::Type(*arrow, Box::new(item.clone())),
+            Some(error.clone()),
+        ),
+        _ => (output, None),
+    }
+}
+
+/// Return `t`, or `Result<t, E>` if the constructor is fallible.
+fn fallible(t: TokenStream, error: &Option<Type>) -> TokenStream {
+    match error {
+        Some(e) => quote! { ::core::result::Result<#t, #e> },
+        None => t,
+    }
+}
+
+/// Return `value`, wrapped in `Ok` if the constructor is fallible.
+fn ok(value: TokenStream, error: &Option<Type>) -> TokenStream {
+    match error {
+        Some(_) => quote! { ::core::result::Result::Ok(#value) },
+        None => value,
+    }
+}
+
+/// Given a call to the constructor, return the expression that creates
+/// the item, which awaits it if it is async and propagates its error if
+/// it is fallible, and the expression that returns the box `b`.
+fn create_item(
+    call: TokenStream,
+    asyncness: &Option<Token![async]>,
+    error: &Option<Type>,
+) -> (TokenStream, TokenStream) {
+    let mut create = call;
+    if asyncness.is_some() {
+        create = quote! { #create.await };
+    }
+    if error.is_some() {
+        create = quote! { #create? };
+    }
+    (create, ok(quote! { b }, error))
+}
+
+/// The body of the box_shared function given a call to the box
+/// function
+fn shared_body(box_call: TokenStream, error: &Option<Type>) -> TokenStream {
+    match error {
+        Some(_) => quote! {
+            ::core::result::Result::map(#box_call, ::implbox::ImplBoxShared::new)
+        },
+        None => quote! { ::implbox::ImplBoxShared::new(#box_call) },
+    }
+}
+
+/// Return the generics for a function that borrows the box, along
+/// with the lifetime of the borrow. If the constructor has lifetime
+/// parameters, the lifetime of the borrow can't be elided, so it is
+/// added as a parameter.
+fn borrow_generics(generics: &Generics) -> (Generics, Option<Lifetime>) {
+    if generics.lifetimes().next().is_none() {
+        return (generics.clone(), None);
+    }
+    let lifetime = Lifetime::new("'__implbox", Span::call_site());
+    let mut result = generics.clone();
+    result.params.insert(
+        0,
+        GenericParam::Lifetime(LifetimeParam::new(lifetime.clone())),
+    );
+    (result, Some(lifetime))
+}
+
+/// Turn `-> impl Thing` into `-> &'borrow impl Thing`.
+fn create_box_output(orig: ReturnType, borrow: &Option<Lifetime>) -> syn::Result<ReturnType> {
+    let t = impl_type(&orig)?;
+    Ok(syn::parse_quote! { -> &#borrow #t })
+}
+
+/// Turn `-> impl Thing` into `-> &'borrow mut impl Thing`.
+fn create_mut_output(orig: &ReturnType, borrow: &Option<Lifetime>) -> syn::Result<ReturnType> {
+    let t = impl_type(orig)?;
+    Ok(syn::parse_quote! { -> &#borrow mut #t })
+}
+
+/// Turn `-> impl Thing` into `-> Pin<&'borrow mut impl Thing>`.
+fn create_pinned_output(orig: &ReturnType, borrow: &Option<Lifetime>) -> syn::Result<ReturnType> {
+    let t = impl_type(orig)?;
+    Ok(syn::parse_quote! { -> ::core::pin::Pin<&#borrow mut #t> })
+}
+
+/// Turn `-> &impl Thing` into `-> Result<&impl Thing, ImplBoxError>`.
+fn create_try_output(output: &ReturnType) -> ReturnType {
+    match output {
+        ReturnType::Type(arr, t) => ReturnType::Type(
+            *arr,
+            Box::new(syn::parse_quote! { ::core::result::Result<#t, ::implbox::ImplBoxError> }),
+        ),
+        // create_box_output never returns this.
+        ReturnType::Default => output.clone(),
+    }
+}
+
+/// Remove the `debug_expand` flag from the parameters of any of the
+/// attribute macros. Return the remaining parameters and whether the
+/// flag was given.
+pub fn take_debug_expand(args: TokenStream) -> (TokenStream, bool) {
+    let mut tokens: Vec<TokenTree> = args.into_iter().collect();
+    let is_comma =
+        |t: Option<&TokenTree>| matches!(t, Some(TokenTree::Punct(p)) if p.as_char() == ',');
+    // Types may contain commas between angle brackets, so only an
+    // identifier that is a parameter by itself is the flag.
+    let found = (0..tokens.len()).find(|&i| {
+        matches!(&tokens[i], TokenTree::Ident(id) if id == "debug_expand")
+            && (i == 0 || is_comma(tokens.get(i - 1)))
+            && (i + 1 == tokens.len() || is_comma(tokens.get(i + 1)))
+    });
+    let Some(i) = found else {
+        return (tokens.into_iter().collect(), false);
+    };
+    // Remove the comma that separates it from the other parameters.
+    if i + 1 < tokens.len() {
+        tokens.drain(i..i + 2);
+    } else if i > 0 {
+        tokens.drain(i - 1..i + 1);
+    } else {
+        tokens.remove(i);
+    }
+    (tokens.into_iter().collect(), true)
+}
+
+/// Write `output`, which `macro_name` generated from `input`, to a file
+/// so the generated code can be inspected, and return its path. The
+/// file is `implbox-expand/{macro_name}-{name}.rs` in `OUT_DIR` if the
+/// crate has a build script and in the temporary directory otherwise,
+/// where `name` is the name of the annotated function or trait. It is
+/// not formatted; run `rustfmt` on it to read it.
+pub fn dump_expansion(
+    macro_name: &str,
+    input: &TokenStream,
+    output: &TokenStream,
+) -> std::io::Result<PathBuf> {
+    let mut tokens = input.clone().into_iter();
+    let mut name = String::from("unknown");
+    while let Some(t) = tokens.next() {
+        if matches!(&t, TokenTree::Ident(i) if i == "fn" || i == "trait") {
+            if let Some(TokenTree::Ident(i)) = tokens.next() {
+                name = i.to_string();
+            }
+            break;
+        }
+    }
+    let dir = match std::env::var_os("OUT_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => std::env::temp_dir(),
+    }
+    .join("implbox-expand");
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{macro_name}-{name}.rs"));
+    std::fs::write(
+        &path,
+        format!("// Generated by #[{macro_name}] on {name}\n{output}\n"),
+    )?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(tokens: TokenStream) -> syn::ItemImpl {
+        syn::parse2(quote! { impl X for Y { #tokens } }).unwrap()
+    }
+
+    #[test]
+    fn test_multiple_args() {
+        let out = implbox_impls(
+            quote! { ThingBox<T>, Thing<T> },
+            quote! {
+                fn new_thing<T>(a: T, (b, c): (i32, i32), mut d: String) -> impl Thing<T> {
+                    Thing::new(a, b, c, d)
+                }
+            },
+        )
+        .unwrap();
+        let item = items(out);
+        let syn::ImplItem::Fn(box_fn) = &item.items[1] else {
+            panic!("expected box function");
+        };
+        assert_eq!(box_fn.sig.ident, "box_thing");
+        assert_eq!(box_fn.sig.inputs.len(), 3);
+        let body = box_fn.block.to_token_stream().to_string();
+        assert!(body.contains("Self :: new_thing :: < T > (arg0 , arg1 , arg2)"));
+    }
+
+    #[test]
+    fn test_multiple_bounds() {
+        let out = implbox_decls(
+            quote! { ThingBox },
+            quote! { fn new_thing() -> impl Thing + Send; },
+        )
+        .unwrap();
+        assert!(out.to_string().contains("-> & (impl Thing + Send) ;"));
+    }
+
+    #[test]
+    fn test_try_unbox() {
+        let out = implbox_decls(
+            quote! { ThingBox },
+            quote! { fn new_thing() -> impl Thing; },
+        )
+        .unwrap()
+        .to_string();
+        assert!(out.contains("fn take_thing (l : ImplBox < ThingBox >) -> impl Thing ;"));
+        assert!(out.contains(
+            "fn try_unbox_thing (l : & ImplBox < ThingBox >) -> :: core :: result :: Result < & impl Thing , :: implbox :: ImplBoxError > ;"
+        ));
+    }
+
+    #[test]
+    fn test_unbox_mut() {
+        let out = implbox_decls(
+            quote! { ThingBox },
+            quote! { fn new_thing() -> impl Thing + Send; },
+        )
+        .unwrap()
+        .to_string();
+        assert!(out.contains(
+            "fn unbox_mut_thing (l : & mut ImplBox < ThingBox >) -> & mut (impl Thing + Send) ;"
+        ));
+    }
+
+    #[test]
+    fn test_fallible() {
+        let out = implbox_decls(
+            quote! { ThingBox },
+            quote! { fn new_thing() -> Result<impl Thing, Error>; },
+        )
+        .unwrap()
+        .to_string();
+        assert!(out.contains(
+            "fn box_thing () -> :: core :: result :: Result < ImplBox < ThingBox > , Error > ;"
+        ));
+        assert!(out.contains("fn unbox_thing (l : & ImplBox < ThingBox >) -> & impl Thing ;"));
+        assert!(out.contains("fn take_thing (l : ImplBox < ThingBox >) -> impl Thing ;"));
+        let out = implbox_impls(
+            quote! { ThingBox, Thing },
+            quote! { fn new_thing() -> Result<impl Thing, Error> { Ok(Thing) } },
+        )
+        .unwrap()
+        .to_string();
+        assert!(out.contains("let item = Self :: new_thing () ? ;"));
+    }
+
+    #[test]
+    fn test_docs() {
+        let out = implbox_decls(
+            quote! { ThingBox<T> },
+            quote! { unsafe fn new_thing<T>(t: T) -> Result<impl Thing, Error>; },
+        )
+        .unwrap()
+        .to_string();
+        assert!(out.contains(
+            "# [doc = \" Generated by implbox_decls -- call `new_thing` and box the result as `ImplBox<ThingBox<T>>`.\"]"
+        ));
+        assert!(out.contains("# [doc = \" If `new_thing` fails, its error is returned.\"]"));
+        assert!(out.contains(
+            "# [doc = \" The caller must uphold the safety requirements of `new_thing`.\"]"
+        ));
+        assert!(out.contains(
+            "# [doc = \" If the box was created by a different implementation or is pinned.\"]"
+        ));
+        let out = implbox_impls(
+            quote! { ThingBox<T>, Thing<T> },
+            quote! { fn new_thing<T>(t: T) -> impl Thing { Thing(t) } },
+        )
+        .unwrap()
+        .to_string();
+        assert!(out.contains(
+            "# [doc = \" Generated by implbox_impls -- borrow the `Thing<T>` returned by `new_thing` from a box created by `box_thing`.\"]"
+        ));
+    }
+
+    #[test]
+    fn test_name() {
+        let out = implbox_decls(
+            quote! { LockBox<T>, name = "lock" },
+            quote! { fn create<T>(item: T) -> impl Lock<T>; },
+        )
+        .unwrap()
+        .to_string();
+        assert!(out.contains("fn box_lock < T > (item : T) -> ImplBox < LockBox < T > > ;"));
+        assert!(out.contains("fn drop_lock < T > (p : * const ()) ;"));
+        let out = implbox_impls(
+            quote! { LockBox<T>, Wrapper<T>, downcast, name = "lock" },
+            quote! { fn create<T>(item: T) -> impl Lock<T> { Wrapper(item) } },
+        )
+        .unwrap()
+        .to_string();
+        assert!(out.contains("fn unbox_lock < T >"));
+        assert!(out.contains("set_item_id"));
+    }
+
+    #[test]
+    fn test_lifetimes() {
+        let out = implbox_decls(
+            quote! { ViewBox<'a, N> },
+            quote! { fn new_view<'a, const N: usize>(data: &'a [u8; N]) -> impl View<'a>; },
+        )
+        .unwrap()
+        .to_string();
+        assert!(out.contains(
+            "fn unbox_view < '__implbox , 'a , const N : usize > (l : & '__implbox ImplBox < ViewBox < 'a , N > >) -> & '__implbox impl View < 'a > ;"
+        ));
+        assert!(out.contains(
+            "fn unbox_ref_view < '__implbox , 'a , const N : usize > (l : :: implbox :: ImplBoxRef < '__implbox , ViewBox < 'a , N > >)"
+        ));
+        let out = implbox_impls(
+            quote! { ViewBox<'a, N>, Slice<'a> },
+            quote! { fn new_view<'a, const N: usize>(data: &'a [u8; N]) -> impl View<'a> { Slice(data) } },
+        )
+        .unwrap()
+        .to_string();
+        // Lifetimes are inferred since they may be late-bound.
+        assert!(out.contains("Self :: drop_view :: < N >"));
+    }
+
+    #[test]
+    fn test_where_clause() {
+        let out = implbox_decls(
+            quote! { ThingBox<K, V> },
+            quote! { fn new_thing<K, V>() -> impl Thing<K, V> where K: Eq; },
+        )
+        .unwrap()
+        .to_string();
+        assert!(out.contains(
+            "fn unbox_thing < K , V > (l : & ImplBox < ThingBox < K , V > >) -> & impl Thing < K , V > where K : Eq ;"
+        ));
+        let out = implbox_impls(
+            quote! { ThingBox<K, V>, Thing<K, V> },
+            quote! {
+                fn new_thing<'a, K, V>() -> impl Thing<K, V> where K: Eq {
+                    Thing::new()
+                }
+            },
+        )
+        .unwrap();
+        let item = items(out);
+        let syn::ImplItem::Fn(box_fn) = &item.items[1] else {
+            panic!("expected box function");
+        };
+        assert!(box_fn.sig.generics.where_clause.is_some());
+        let body = box_fn.block.to_token_stream().to_string();
+        assert!(body.contains("Self :: new_thing :: < K , V > ()"));
+    }
+
+    #[test]
+    fn test_replace() {
+        let out = implbox_decls(
+            quote! { ThingBox },
+            quote! { fn new_thing(&self, a: i32) -> impl Thing; },
+        )
+        .unwrap()
+        .to_string();
+        assert!(out.contains(
+            "fn replace_thing (& self , l : & mut ImplBox < ThingBox > , a : i32) -> ImplBox < ThingBox > ;"
+        ));
+    }
+
+    #[test]
+    fn test_qualifier_order() {
+        let out = implbox_decls(
+            quote! { ThingBox },
+            quote! { const async unsafe fn new_thing() -> impl Thing; },
+        )
+        .unwrap();
+        assert!(out
+            .to_string()
+            .contains("const async unsafe fn box_thing ()"));
+        // Arc::new is not const, so box_shared can't be.
+        assert!(out
+            .to_string()
+            .contains("] async unsafe fn box_shared_thing ()"));
+        let out = implbox_impls(
+            quote! { ThingBox, Thing },
+            quote! { async unsafe fn new_thing() -> impl Thing { Thing } },
+        )
+        .unwrap();
+        assert!(out
+            .to_string()
+            .contains("ImplBoxShared :: new (unsafe { Self :: box_thing () . await })"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = implbox_decls(quote! { ThingBox }, quote! { fn thing() -> impl Thing; })
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "function for implbox_decls must be named new_something, such as new_thing, or the name must be given, such as name = \"thing\""
+        );
+        let err = implbox_decls(quote! { ThingBox }, quote! { fn new_thing() -> Thing; })
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "return type must be `impl Trait` or `Result<impl Trait, E>`"
+        );
+        let err = implbox_decls(quote! { ThingBox }, quote! { fn new_thing(); })
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "expected a return type of `impl Trait` or `Result<impl Trait, E>` after this"
+        );
+        let err = implbox_decls(quote! {}, quote! { fn new_thing() -> impl Thing; })
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "implbox_decls requires the shadow type, such as #[implbox_decls(ThingBox)]"
+        );
+        let err = implbox_decls(
+            quote! { ThingBox, Thing },
+            quote! { fn new_thing() -> impl Thing; },
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "implbox_decls takes only the shadow type and optionally a name"
+        );
+        let err = implbox_decls(
+            quote! { ThingBox, name = "a-b" },
+            quote! { fn thing() -> impl Thing; },
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "name must be usable in an identifier, such as \"thing\""
+        );
+        let err = implbox_decls(
+            quote! { ThingBox, title = "thing" },
+            quote! { fn thing() -> impl Thing; },
+        )
+        .err()
+        .unwrap();
+        assert_eq!(err.to_string(), "the only named parameter is `name`");
+        let err = implbox_decls(
+            quote! { ThingBox<T> },
+            quote! { fn new_thing<T>(t: T, s: impl Into<String>) -> impl Thing<T>; },
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "implbox_decls does not support impl Trait arguments in a generic function; use a named type parameter"
+        );
+        let err = implbox_impls(
+            quote! { ThingBox },
+            quote! { fn new_thing() -> impl Thing { Thing } },
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "implbox_impls requires the concrete type after the shadow type, such as #[implbox_impls(ThingBox, Thing)]"
+        );
+        let err = implbox_impls(
+            quote! { ThingBox, Thing, clone },
+            quote! { fn new_thing() -> impl Thing { Thing } },
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "implbox_impls flags must be `downcast` or `serde`"
+        );
+    }
+
+    #[test]
+    fn test_trait() {
+        let out = implbox_trait(
+            TokenStream::new(),
+            quote! {
+                pub trait Maker {
+                    fn new_some_thing<K>(k: K) -> impl Thing<K> + Send;
+                    fn other() -> usize;
+                }
+            },
+        )
+        .unwrap()
+        .to_string();
+        assert!(out.contains(
+            "pub struct SomeThingBox < K > (:: core :: marker :: PhantomData < (:: core :: cell :: Cell < () > , fn () -> (K ,)) >) ;"
+        ));
+        assert!(out.contains("pub trait MakerImplBox {"));
+        assert!(out.contains("impl < X : Maker + 'static > MakerImplBox for X {"));
+        assert!(out.contains("fn unbox_some_thing < K > (l : & ImplBox < SomeThingBox < K > >)"));
+        assert!(!out.contains("box_other"));
+
+        let err = implbox_trait(
+            TokenStream::new(),
+            quote! { trait Maker<T> { fn new_thing() -> impl Thing; } },
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "implbox_trait does not support generic traits; use implbox_decls and implbox_impls"
+        );
+        let err = implbox_trait(
+            TokenStream::new(),
+            quote! { trait Maker { fn new_thing(t: impl Into<String>) -> impl Thing; } },
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "implbox_trait does not support impl Trait arguments; use implbox_decls and implbox_impls"
+        );
+        let err = implbox_trait(TokenStream::new(), quote! { trait Maker { fn thing(); } })
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "implbox_trait requires a function that returns impl Trait"
+        );
+    }
+
+    #[test]
+    fn test_handles() {
+        let out = implbox_trait(
+            quote! { handles },
+            quote! {
+                pub trait Maker {
+                    fn new_thing<'a, K>(k: &'a K) -> Result<impl Thing<K>, String>;
+                }
+            },
+        )
+        .unwrap()
+        .to_string();
+        assert!(out.contains(
+            "pub struct ThingHandle < 'a , X , K > (ImplBox < ThingBox < 'a , K > > , :: core :: marker :: PhantomData < fn () -> X >) ;"
+        ));
+        assert!(out.contains("impl < 'a , X : Maker + 'static , K > ThingHandle < 'a , X , K > {"));
+        assert!(out.contains(
+            "pub fn new (arg0 : & 'a K) -> :: core :: result :: Result < Self , String > {"
+        ));
+        assert!(out.contains("< X as MakerImplBox > :: box_thing :: < K > (arg0) ?"));
+
+        let err = implbox_trait(quote! { handles, boxes }, quote! { trait Maker {} })
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "implbox_trait takes only the `handles` flag"
+        );
+        let err = implbox_trait(
+            quote! { handles },
+            quote! { trait Maker { fn new_thing(&self) -> impl Thing; } },
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "implbox_trait can't generate a handle for a constructor that takes self"
+        );
+    }
+
+    #[test]
+    fn test_debug_expand() {
+        let (args, found) =
+            take_debug_expand(quote! { LockBox<K, V>, debug_expand, name = "lock" });
+        assert!(found);
+        assert_eq!(args.to_string(), "LockBox < K , V > , name = \"lock\"");
+        let (args, found) = take_debug_expand(quote! { handles, debug_expand });
+        assert!(found);
+        assert_eq!(args.to_string(), "handles");
+        let (args, found) = take_debug_expand(quote! { debug_expand });
+        assert!(found);
+        assert!(args.is_empty());
+        let (args, found) = take_debug_expand(quote! { ThingBox<debug_expand> });
+        assert!(!found);
+        assert_eq!(args.to_string(), "ThingBox < debug_expand >");
+
+        let input = quote! { fn new_expanded_thing() -> impl Thing; };
+        let output = implbox_decls(quote! { ThingBox }, input.clone()).unwrap();
+        let path = dump_expansion("implbox_decls", &input, &output).unwrap();
+        assert!(path.ends_with("implbox-expand/implbox_decls-new_expanded_thing.rs"));
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.starts_with("// Generated by #[implbox_decls] on new_expanded_thing\n"));
+        assert!(contents.contains("fn drop_expanded_thing (p : * const ()) ;"));
+        std::fs::remove_file(path).unwrap();
+    }
+}
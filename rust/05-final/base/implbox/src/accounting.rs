@@ -0,0 +1,32 @@
+//! Counts of live [ImplBox]es, keyed by the name of the generic
+//! "shadow" type. This is enabled by the `accounting` feature. Since
+//! each kind of boxed item should have its own shadow type, these
+//! counts show what kinds of items are alive, which helps track down
+//! leaks in long-running processes. Counts are process-wide.
+//!
+//! [ImplBox]: crate::ImplBox
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+static LIVE: Mutex<BTreeMap<&'static str, usize>> = Mutex::new(BTreeMap::new());
+
+pub(crate) fn created(name: &'static str) {
+    *LIVE.lock().unwrap().entry(name).or_default() += 1;
+}
+
+pub(crate) fn dropped(name: &'static str) {
+    let mut live = LIVE.lock().unwrap();
+    if let Some(count) = live.get_mut(name) {
+        *count -= 1;
+        if *count == 0 {
+            live.remove(name);
+        }
+    }
+}
+
+/// Return the number of live boxes for each shadow type that has any.
+/// Names come from [std::any::type_name], so they are suitable for
+/// display but their exact format is not guaranteed.
+pub fn live_boxes() -> BTreeMap<&'static str, usize> {
+    LIVE.lock().unwrap().clone()
+}
@@ -0,0 +1,55 @@
+//! Allocators for [ImplBox] items that are too large to be stored
+//! inline. By default, the global allocator is used. An embedded or
+//! FFI consumer can supply its own [Allocator] to place items in an
+//! arena or in memory owned by foreign code. This is a pair of function
+//! pointers rather than the unstable `core::alloc::Allocator` trait so
+//! that it works on stable Rust and doesn't add a type parameter to
+//! [ImplBox].
+//!
+//! [ImplBox]: crate::ImplBox
+use core::alloc::Layout;
+
+/// Functions that allocate and free the heap storage of an
+/// [ImplBox](crate::ImplBox). The box keeps a copy and frees its
+/// storage with the same allocator that allocated it.
+#[derive(Debug, Clone, Copy)]
+pub struct Allocator {
+    /// Allocate memory for `layout`, returning null on failure. This is
+    /// never called with a zero-sized layout.
+    pub alloc: unsafe fn(Layout) -> *mut u8,
+    /// Free memory returned by `alloc` for the same layout
+    pub dealloc: unsafe fn(*mut u8, Layout),
+}
+
+impl Allocator {
+    /// The global allocator
+    pub const GLOBAL: Allocator = Allocator {
+        alloc: alloc::alloc::alloc,
+        dealloc: alloc::alloc::dealloc,
+    };
+
+    pub(crate) fn allocate(&self, layout: Layout) -> *mut u8 {
+        if layout.size() == 0 {
+            return layout.align() as *mut u8;
+        }
+        let p = unsafe { (self.alloc)(layout) };
+        if p.is_null() {
+            alloc::alloc::handle_alloc_error(layout);
+        }
+        p
+    }
+
+    /// # Safety
+    /// `p` must have come from [Allocator::allocate] with `layout`.
+    pub(crate) unsafe fn deallocate(&self, p: *mut u8, layout: Layout) {
+        if layout.size() != 0 {
+            (self.dealloc)(p, layout);
+        }
+    }
+}
+
+impl Default for Allocator {
+    fn default() -> Self {
+        Self::GLOBAL
+    }
+}
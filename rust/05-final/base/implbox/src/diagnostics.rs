@@ -0,0 +1,64 @@
+//! Counts of live [ImplBox]es, keyed by the [TypeId] each box was
+//! created with. This is enabled by the `diagnostics` feature. The id
+//! is that of the type whose generated functions created the box, so
+//! these counts answer whether everything created by a particular
+//! implementation has been dropped. Use it at shutdown to check for
+//! leaked handles. For counts by kind of item, see the `accounting`
+//! feature. Counts are process-wide.
+//!
+//! [ImplBox]: crate::ImplBox
+use std::any::TypeId;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// The number of live boxes created with a particular id
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LiveCount {
+    /// The name of the concrete type most recently stored in a box
+    /// with this id, or `unknown` if none was recorded
+    pub type_name: &'static str,
+    pub count: usize,
+}
+
+static LIVE: Mutex<BTreeMap<TypeId, LiveCount>> = Mutex::new(BTreeMap::new());
+static TOTAL: AtomicUsize = AtomicUsize::new(0);
+
+pub(crate) fn created(id: TypeId) {
+    TOTAL.fetch_add(1, Ordering::Relaxed);
+    LIVE.lock()
+        .unwrap()
+        .entry(id)
+        .or_insert(LiveCount {
+            type_name: "unknown",
+            count: 0,
+        })
+        .count += 1;
+}
+
+pub(crate) fn named(id: TypeId, type_name: &'static str) {
+    if let Some(live) = LIVE.lock().unwrap().get_mut(&id) {
+        live.type_name = type_name;
+    }
+}
+
+pub(crate) fn dropped(id: TypeId) {
+    TOTAL.fetch_sub(1, Ordering::Relaxed);
+    let mut live = LIVE.lock().unwrap();
+    if let Some(entry) = live.get_mut(&id) {
+        entry.count -= 1;
+        if entry.count == 0 {
+            live.remove(&id);
+        }
+    }
+}
+
+/// Return the number of live boxes for each id that has any.
+pub fn live_counts() -> BTreeMap<TypeId, LiveCount> {
+    LIVE.lock().unwrap().clone()
+}
+
+/// Return the total number of live boxes. This doesn't take a lock.
+pub fn live_total() -> usize {
+    TOTAL.load(Ordering::Relaxed)
+}
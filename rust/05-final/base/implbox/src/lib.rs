@@ -29,10 +29,16 @@
 //! - In the Trait's declaration, declare a method whose name starts
 //!   with `new_` and that returns an opaque type, e.g. `new_thing()
 //!   -> impl Thing`. It can take any additional arguments that may be
-//!   required.
+//!   required. If creating the thing can fail, it may instead return
+//!   `Result<impl Thing, E>`. The generated box and replace functions
+//!   then return `Result` and propagate the error.
+//!   If the function can't be named that way, such as when wrapping
+//!   an existing trait, give the name to both macros instead, e.g.
+//!   `#[implbox_decls(ThingBox, name = "thing")]`.
 //! - Annotate the declaration with `#[implbox_decl]`. If your
 //!   function is called `new_thing`, this will create `box_thing`,
-//!   `unbox_thing`, and `drop_thing`.
+//!   `unbox_thing`, `try_unbox_thing`, `unbox_mut_thing`,
+//!   `unbox_ref_thing`, `take_thing`, and `drop_thing`, among others.
 //! - In the implementation of `ThingMaker` for some concrete type,
 //!   annotate the implementation of `new_thing` with
 //!   `#[implbox_impls]`.
@@ -42,10 +48,74 @@
 //!   - To get the `&impl Thing`, call the associated
 //!     `unbox_new_thing` method with a reference to the `ImplBox`.
 //!     This returns a reference to the thing. It is useful to create
-//!     a separate method that does this.
+//!     a separate method that does this. `unbox_thing` panics if the
+//!     `ImplBox` was created by a different type. `try_unbox_thing`
+//!     returns an [ImplBoxError] instead.
+//!   - To call methods of `Thing` that take `&mut self`, call
+//!     `unbox_mut_thing` with a mutable reference to the `ImplBox`.
+//!     This returns `&mut impl Thing`.
+//!   - If the concrete type implements `Clone`, the `ImplBox` can be
+//!     copied with [ImplBox::try_clone].
+//!   - To share the `ImplBox` between several owners, call
+//!     `box_shared_thing` instead of `box_thing`. This returns an
+//!     [ImplBoxShared], which can be cloned and passed to
+//!     `unbox_thing`.
+//!   - To lend the thing without giving up ownership, for example
+//!     through a struct field, call [ImplBox::to_ref]. This returns
+//!     an [ImplBoxRef], which holds no destroy function and borrows
+//!     from the box. Pass it to `unbox_ref_thing` to get the
+//!     `&impl Thing` back. An implementation that has the concrete
+//!     value can also create one with [ImplBoxRef::new].
+//!   - To get the original value back, call `take_thing`, which
+//!     consumes the `ImplBox` and returns the `impl Thing` by value.
+//!   - To swap in a new thing of the same concrete type without
+//!     rebuilding whatever holds the box, call `replace_thing` with a
+//!     mutable reference to the box and the arguments for
+//!     `new_thing`. It returns a box holding the old thing.
+//!   - To store the thing in memory from a custom [Allocator] if it
+//!     is too large to be stored inline, call `box_in_thing` with the
+//!     allocator and the arguments for `new_thing`.
+//!   - If the thing must not move, such as a self-referential future,
+//!     call `box_pinned_thing` instead of `box_thing`, and call
+//!     `unbox_pinned_thing` with a mutable reference to the box to
+//!     get a `Pin<&mut impl Thing>`. `take_thing` panics on a pinned
+//!     box.
+//!   - If the concrete type is `'static`, pass `downcast` as a third
+//!     argument to `#[implbox_impls]`. Code that knows the concrete
+//!     type can then get it back with [ImplBox::downcast_ref], for
+//!     operations that aren't part of `Thing`.
+//!   - With the `serde` feature, pass `serde` to `#[implbox_impls]`
+//!     to make the box serializable and restorable. See
+//!     `serde_hooks`.
 //!   - You never call `drop_thing` -- it is called automatically when
 //!     the `ImplBox` is dropped.
 //!
+//! To skip the per-function annotations, put `#[implbox_trait]` on
+//! `ThingMaker` itself. For each associated function that returns an
+//! impl type, it creates a shadow type named after the function, such
+//! as `ThingBox` for `new_thing`, and declares the same functions in a
+//! helper trait, `ThingMakerImplBox`, that is implemented for every
+//! `'static` implementation of `ThingMaker`. Implementations of
+//! `ThingMaker` only write `new_thing`. The generated code can't see
+//! the concrete type, so it knows only what the return type declares:
+//! the box can be cloned only if it says `Clone`, and the shadow type
+//! is `Send` or `Sync` only if it says so. Generic traits and
+//! constructors with impl type arguments aren't supported.
+//!
+//! With `#[implbox_trait(handles)]`, it also creates a handle type for
+//! each constructor, such as `ThingHandle<X>`, which wraps the box
+//! along with the implementation `X` that created it. A struct that
+//! stores a `ThingHandle<X>` can call `get`, `get_mut`, and `take` on
+//! it without repeating `X::unbox_thing` everywhere, and the type
+//! system ensures it is never unboxed by a different implementation.
+//!
+//! To see the code that any of the macros generates, such as when a
+//! box isn't `Send` as expected, add the `debug_expand` flag, e.g.
+//! `#[implbox_impls(ThingBox, Thing, debug_expand)]`. The expansion is
+//! written to `implbox-expand/implbox_impls-new_thing.rs` in `OUT_DIR`
+//! if the crate has a build script and in the temporary directory
+//! otherwise. Remove the flag when you are done.
+//!
 //! The [ImplBox] type has a generic type parameter. There is no
 //! specifically defined relationship between that type and the type
 //! the [ImplBox] is proxying. The type can never be the exact type
@@ -82,6 +152,21 @@
 //!   supplement these compile-time checks and would be sufficient if
 //!   the compile-time helper types were used incorrectly.
 //!
+//! # Storage
+//!
+//! An `ImplBox<T, N>` stores items of up to `N` machine words inline,
+//! avoiding a heap allocation. `N` defaults to [INLINE_WORDS]. Items
+//! that are larger, or that need more than word alignment, are boxed.
+//! The generated functions always use the default capacity, so
+//! [ImplBox::is_inline] is the only place the difference is visible.
+//!
+//! # no_std
+//!
+//! With the default `std` feature disabled, this crate only requires
+//! `core` and `alloc`, and so does the code generated by the macros.
+//! Only [ImplBoxError]'s implementation of `std::error::Error` and the
+//! `accounting` and `diagnostics` features need `std`.
+//!
 //! # Example
 //! ```
 //! use implbox::ImplBox;
@@ -187,40 +272,855 @@
 //! assert_eq!(r.food().prep(), "baked");
 //! ```
 
-use std::any::TypeId;
-use std::marker::PhantomData;
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 
-unsafe impl<T: Send> Send for ImplBox<T> {}
-unsafe impl<T: Sync> Sync for ImplBox<T> {}
-pub struct ImplBox<T> {
+#[cfg(not(loom))]
+use alloc::sync::Arc;
+use core::alloc::Layout;
+use core::any::TypeId;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::{self, MaybeUninit};
+use core::ops::Deref;
+#[cfg(doc)]
+use core::pin::Pin;
+use core::ptr;
+#[cfg(not(loom))]
+use core::sync::atomic::{AtomicBool, Ordering};
+// With `--cfg loom`, the shared count and the poisoned flag are loom's,
+// so runtime-loom's tests check how boxes are shared between threads.
+// Boxes can then only be created inside `loom::model`.
+#[cfg(loom)]
+use loom::sync::atomic::{AtomicBool, Ordering};
+#[cfg(loom)]
+use loom::sync::Arc;
+
+#[cfg(feature = "accounting")]
+pub mod accounting;
+pub mod allocator;
+#[cfg(feature = "diagnostics")]
+pub mod diagnostics;
+pub use allocator::Allocator;
+#[cfg(feature = "serde")]
+pub mod serde_hooks;
+#[cfg(feature = "diagnostics")]
+pub use diagnostics::{live_counts, live_total, LiveCount};
+#[cfg(feature = "serde")]
+pub use serde_hooks::SerdeHooks;
+
+/// Errors from the fallible accessors of [ImplBox]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ImplBoxError {
+    /// The box was accessed with the id of a type other than the one
+    /// that created it, which means it was passed to the wrong unbox
+    /// function.
+    IdMismatch,
+    /// Pinned access was requested for a box that was not created with
+    /// [ImplBox::box_pinned].
+    NotPinned,
+    /// Mutable access was requested for a box that was created with
+    /// [ImplBox::box_pinned], which would allow the item to be moved.
+    Pinned,
+    /// A function called with the stored item panicked, so the item
+    /// may be in an inconsistent state. See [ImplBox::is_poisoned].
+    Poisoned,
+}
+
+impl fmt::Display for ImplBoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImplBoxError::IdMismatch => write!(
+                f,
+                "id mismatch: ImplBox was unboxed by a different type than the one that created it"
+            ),
+            ImplBoxError::NotPinned => write!(f, "ImplBox was not created pinned"),
+            ImplBoxError::Pinned => write!(f, "ImplBox was created pinned"),
+            ImplBoxError::Poisoned => write!(f, "ImplBox is poisoned"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ImplBoxError {}
+
+/// The default number of machine words of inline storage in an
+/// [ImplBox]
+pub const INLINE_WORDS: usize = 3;
+
+/// Copies the item at the first pointer into uninitialized memory at
+/// the second. See [ImplBox::set_clone_fn].
+pub type CloneFn = unsafe fn(*const (), *mut ());
+
+enum Storage<const N: usize> {
+    Heap(*mut u8),
+    Inline([MaybeUninit<usize>; N]),
+}
+
+unsafe impl<T: Send, const N: usize> Send for ImplBox<T, N> {}
+unsafe impl<T: Sync, const N: usize> Sync for ImplBox<T, N> {}
+/// An owned item of a type that is not known where the box is used.
+/// Items that fit in `N` machine words and are no more strictly
+/// aligned than a word are stored inline. Larger items, and items
+/// created with [ImplBox::box_pinned], are stored on the heap.
+pub struct ImplBox<T, const N: usize = INLINE_WORDS> {
     id: TypeId,
-    ptr: *const (),
+    storage: Storage<N>,
+    layout: Layout,
+    pinned: bool,
+    item_id: Option<TypeId>,
+    #[cfg(feature = "serde")]
+    serde: Option<SerdeHooks>,
+    poisoned: AtomicBool,
+    /// The thread that created a box whose item is not `Send`
+    #[cfg(all(debug_assertions, feature = "std"))]
+    origin: Option<std::thread::ThreadId>,
     destroy: fn(*const ()),
+    clone: Option<CloneFn>,
+    type_name: &'static str,
+    allocator: Allocator,
     _t: PhantomData<T>,
 }
-impl<T> ImplBox<T> {
-    pub fn new(id: TypeId, destroy: fn(*const ()), ptr: *const ()) -> Self {
+impl<T, const N: usize> ImplBox<T, N> {
+    /// Store `item`. `id` identifies the type that is allowed to
+    /// access the item, and `destroy` is called with a pointer to the
+    /// item to drop it in place. The box frees the storage itself.
+    pub fn new<C>(id: TypeId, destroy: fn(*const ()), item: C) -> Self {
+        Self::new_in(id, destroy, item, Allocator::GLOBAL)
+    }
+
+    /// Like [ImplBox::new], but if `item` doesn't fit inline, store it
+    /// in memory from `allocator`.
+    pub fn new_in<C>(id: TypeId, destroy: fn(*const ()), item: C, allocator: Allocator) -> Self {
+        let layout = Layout::new::<C>();
+        let storage = if Self::fits(layout) {
+            let mut buf = [MaybeUninit::uninit(); N];
+            unsafe { ptr::write(buf.as_mut_ptr() as *mut C, item) };
+            Storage::Inline(buf)
+        } else {
+            Storage::Heap(Self::allocate(&allocator, item))
+        };
+        Self::from_parts(id, storage, layout, destroy, allocator)
+    }
+
+    fn allocate<C>(allocator: &Allocator, item: C) -> *mut u8 {
+        let p = allocator.allocate(Layout::new::<C>());
+        unsafe { ptr::write(p as *mut C, item) };
+        p
+    }
+
+    /// Store `item` on the heap and never move it again. Use this for
+    /// items, such as self-referential futures, that must stay put
+    /// once they are accessed through [Pin]. A pinned box can be
+    /// accessed mutably with [ImplBox::with_pinned], and it can't be
+    /// consumed with [ImplBox::into_value].
+    pub fn box_pinned<C>(id: TypeId, destroy: fn(*const ()), item: C) -> Self {
+        Self::box_pinned_in(id, destroy, item, Allocator::GLOBAL)
+    }
+
+    /// Like [ImplBox::box_pinned], but store `item` in memory from
+    /// `allocator`.
+    pub fn box_pinned_in<C>(
+        id: TypeId,
+        destroy: fn(*const ()),
+        item: C,
+        allocator: Allocator,
+    ) -> Self {
+        let storage = Storage::Heap(Self::allocate(&allocator, item));
+        let mut b = Self::from_parts(id, storage, Layout::new::<C>(), destroy, allocator);
+        b.pinned = true;
+        b
+    }
+
+    fn from_parts(
+        id: TypeId,
+        storage: Storage<N>,
+        layout: Layout,
+        destroy: fn(*const ()),
+        allocator: Allocator,
+    ) -> Self {
+        #[cfg(feature = "accounting")]
+        accounting::created(core::any::type_name::<T>());
+        #[cfg(feature = "diagnostics")]
+        diagnostics::created(id);
         Self {
             id,
-            ptr,
+            storage,
+            layout,
+            pinned: false,
+            item_id: None,
+            #[cfg(feature = "serde")]
+            serde: None,
+            poisoned: AtomicBool::new(false),
+            #[cfg(all(debug_assertions, feature = "std"))]
+            origin: None,
             destroy,
+            clone: None,
+            type_name: "unknown",
+            allocator,
             _t: Default::default(),
         }
     }
 
+    fn fits(layout: Layout) -> bool {
+        layout.size() <= N * mem::size_of::<usize>() && layout.align() <= mem::align_of::<usize>()
+    }
+
+    fn ptr(&self) -> *const () {
+        match &self.storage {
+            Storage::Heap(p) => *p as *const (),
+            Storage::Inline(buf) => buf.as_ptr() as *const (),
+        }
+    }
+
+    fn ptr_mut(&mut self) -> *mut () {
+        match &mut self.storage {
+            Storage::Heap(p) => *p as *mut (),
+            Storage::Inline(buf) => buf.as_mut_ptr() as *mut (),
+        }
+    }
+
+    /// Whether the item is stored inline rather than on the heap
+    pub fn is_inline(&self) -> bool {
+        matches!(self.storage, Storage::Inline(_))
+    }
+
+    /// Whether the box was created with [ImplBox::box_pinned]
+    pub fn is_pinned(&self) -> bool {
+        self.pinned
+    }
+
+    /// Record the [TypeId] of the concrete type of the item so that it
+    /// can be recovered with [ImplBox::downcast_ref]. The generated box
+    /// functions set this when `#[implbox_impls]` is given `downcast`.
+    /// It is not set by default since it requires a `'static` type.
+    ///
+    /// # Safety
+    /// `item_id` must be the id of the type of the stored item.
+    pub unsafe fn set_item_id(mut self, item_id: TypeId) -> Self {
+        self.item_id = Some(item_id);
+        self
+    }
+
+    /// Set the functions used to serialize and restore the item. The
+    /// generated box functions set these when `#[implbox_impls]` is
+    /// given `serde`.
+    ///
+    /// # Safety
+    /// `hooks` must be for the type of the stored item.
+    #[cfg(feature = "serde")]
+    pub unsafe fn set_serde_hooks(mut self, hooks: SerdeHooks) -> Self {
+        self.serde = Some(hooks);
+        self
+    }
+
+    /// Replace the stored item with one read from `deserializer`. On
+    /// error, the old item is kept. This also clears poisoning, since
+    /// the old item is gone. Fails if the box has no [SerdeHooks].
+    #[cfg(feature = "serde")]
+    pub fn restore<'de, D: serde::Deserializer<'de>>(
+        &mut self,
+        deserializer: D,
+    ) -> Result<(), D::Error> {
+        use serde::de::Error;
+        self.check_thread();
+        let Some(hooks) = self.serde else {
+            return Err(D::Error::custom(format_args!(
+                "ImplBox holding {} can't be restored",
+                self.type_name
+            )));
+        };
+        let mut de = <dyn erased_serde::Deserializer>::erase(deserializer);
+        unsafe { (hooks.deserialize)(self.ptr_mut(), &mut de) }.map_err(D::Error::custom)?;
+        self.clear_poison();
+        Ok(())
+    }
+
+    /// Whether the stored item is a `C`. This is false if the item's
+    /// type was not recorded.
+    pub fn is<C: 'static>(&self) -> bool {
+        self.item_id == Some(TypeId::of::<C>())
+    }
+
+    /// Return a reference to the stored item if it is a `C`. This
+    /// bypasses the trait, so it is for operations specific to one
+    /// implementation. Returns `None` if the item's type was not
+    /// recorded or the box is poisoned.
+    pub fn downcast_ref<C: 'static>(&self) -> Option<&C> {
+        self.check_thread();
+        if !self.is::<C>() || self.is_poisoned() {
+            return None;
+        }
+        // The id check ensures that the item is a C.
+        unsafe { (self.ptr() as *const C).as_ref() }
+    }
+
+    /// Whether a function called by [ImplBox::with] or a related
+    /// method panicked. Once a box is poisoned, those methods fail with
+    /// [ImplBoxError::Poisoned] until [ImplBox::clear_poison] is called
+    /// or the item is replaced with [ImplBox::replace].
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::Relaxed)
+    }
+
+    /// Poison the box as if a function called by [ImplBox::with] had
+    /// panicked. A generated unbox function returns a reference that
+    /// outlives its call, so a panic while the item is used through it
+    /// can't poison the box. A caller that may leave the item in an
+    /// inconsistent state, such as one holding a lock's write guard,
+    /// calls this if it unwinds.
+    pub fn poison(&self) {
+        self.poisoned.store(true, Ordering::Relaxed);
+    }
+
+    /// Allow access to the item again after a panic. Only do this if
+    /// the item is known to be usable.
+    pub fn clear_poison(&self) {
+        self.poisoned.store(false, Ordering::Relaxed);
+    }
+
+    /// Record whether the concrete type of the item is `Send`. The
+    /// generated box functions set this. In builds with debug
+    /// assertions and the `std` feature, a box whose item is not
+    /// `Send` remembers the thread that created it and panics if it is
+    /// accessed from another thread. That can only happen if the box's
+    /// shadow type is `Send` when it shouldn't be. Otherwise, this does
+    /// nothing.
+    ///
+    /// The generated functions can only tell that a type is `Send` from
+    /// the bounds in scope where the box is created. If the concrete
+    /// type is generic, add a `Send` bound there to avoid a false
+    /// report.
+    #[cfg_attr(not(all(debug_assertions, feature = "std")), allow(unused_mut))]
+    pub fn set_send_check(mut self, is_send: bool) -> Self {
+        #[cfg(all(debug_assertions, feature = "std"))]
+        if !is_send {
+            self.origin = Some(std::thread::current().id());
+        }
+        #[cfg(not(all(debug_assertions, feature = "std")))]
+        let _ = is_send;
+        self
+    }
+
+    fn is_send(&self) -> bool {
+        #[cfg(all(debug_assertions, feature = "std"))]
+        return self.origin.is_none();
+        #[cfg(not(all(debug_assertions, feature = "std")))]
+        true
+    }
+
+    fn check_thread(&self) {
+        #[cfg(all(debug_assertions, feature = "std"))]
+        if let Some(origin) = self.origin {
+            if origin != std::thread::current().id() {
+                panic!(
+                    "ImplBox holding {}, which is not Send, was used from a thread other than \
+                     the one that created it; its shadow type {} must not be Send",
+                    self.type_name,
+                    core::any::type_name::<T>()
+                );
+            }
+        }
+    }
+
+    fn check(&self, id: TypeId) -> Result<(), ImplBoxError> {
+        self.check_thread();
+        if self.id != id {
+            Err(ImplBoxError::IdMismatch)
+        } else if self.is_poisoned() {
+            Err(ImplBoxError::Poisoned)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Record the name of the concrete type for diagnostics. The
+    /// generated box functions set this from [core::any::type_name].
+    pub fn set_type_name(mut self, type_name: &'static str) -> Self {
+        #[cfg(feature = "diagnostics")]
+        diagnostics::named(self.id, type_name);
+        self.type_name = type_name;
+        self
+    }
+
+    /// The name of the concrete type of the stored item, or `unknown`
+    /// if it was not recorded
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Set the function used by [ImplBox::try_clone] to copy the
+    /// stored item. It must write a clone of the item into memory with
+    /// the item's layout. The generated box functions set this when
+    /// the concrete type is known to implement [Clone].
+    pub fn set_clone_fn(mut self, clone: Option<CloneFn>) -> Self {
+        self.clone = clone;
+        self
+    }
+
+    pub fn is_cloneable(&self) -> bool {
+        self.clone.is_some()
+    }
+
+    /// Return a box holding a clone of the stored item, or `None` if
+    /// the box has no clone function or is poisoned.
+    pub fn try_clone(&self) -> Option<Self> {
+        if self.is_poisoned() {
+            return None;
+        }
+        let clone = self.clone?;
+        let storage = match self.storage {
+            Storage::Inline(_) => {
+                let mut buf = [MaybeUninit::uninit(); N];
+                unsafe { clone(self.ptr(), buf.as_mut_ptr() as *mut ()) };
+                Storage::Inline(buf)
+            }
+            Storage::Heap(_) => {
+                let p = self.allocator.allocate(self.layout);
+                unsafe { clone(self.ptr(), p as *mut ()) };
+                Storage::Heap(p)
+            }
+        };
+        let mut b = Self::from_parts(self.id, storage, self.layout, self.destroy, self.allocator)
+            .set_clone_fn(self.clone)
+            .set_type_name(self.type_name)
+            .set_send_check(self.is_send());
+        b.pinned = self.pinned;
+        b.item_id = self.item_id;
+        #[cfg(feature = "serde")]
+        {
+            b.serde = self.serde;
+        }
+        Some(b)
+    }
+
+    /// Borrow the stored item without giving up ownership
+    pub fn to_ref(&self) -> ImplBoxRef<'_, T> {
+        ImplBoxRef {
+            id: self.id,
+            ptr: self.ptr(),
+            type_name: self.type_name,
+            _t: PhantomData,
+        }
+    }
+
+    /// Call `f` with the stored pointer. Panics if `id` is not the id
+    /// the box was created with. See [ImplBox::try_with].
+    pub fn with<F, Ret>(&self, id: TypeId, f: F) -> Ret
+    where
+        F: FnOnce(*const ()) -> Ret,
+    {
+        self.try_with(id, f)
+            .unwrap_or_else(|e| panic!("{e}; box holds {}", self.type_name))
+    }
+
+    /// Call `f` with a mutable pointer to the stored item. Panics if
+    /// `id` is not the id the box was created with or if the box was
+    /// created with [ImplBox::box_pinned], since a mutable reference
+    /// would allow a pinned item to be moved. See
+    /// [ImplBox::try_with_mut].
+    pub fn with_mut<F, Ret>(&mut self, id: TypeId, f: F) -> Ret
+    where
+        F: FnOnce(*mut ()) -> Ret,
+    {
+        let type_name = self.type_name;
+        self.try_with_mut(id, f)
+            .unwrap_or_else(|e| panic!("{e}; box holds {type_name}"))
+    }
+
+    /// Like [ImplBox::with_mut], but return an error instead of
+    /// panicking.
+    pub fn try_with_mut<F, Ret>(&mut self, id: TypeId, f: F) -> Result<Ret, ImplBoxError>
+    where
+        F: FnOnce(*mut ()) -> Ret,
+    {
+        self.check(id)?;
+        if self.pinned {
+            return Err(ImplBoxError::Pinned);
+        }
+        let p = self.ptr_mut();
+        Ok(PoisonOnUnwind::call(&self.poisoned, || f(p)))
+    }
+
+    /// Call `f` with a mutable pointer to the stored item, which may
+    /// be wrapped in [Pin]. Panics if `id` is not the id the box was
+    /// created with or if the box was not created with
+    /// [ImplBox::box_pinned].
+    pub fn with_pinned<F, Ret>(&mut self, id: TypeId, f: F) -> Ret
+    where
+        F: FnOnce(*mut ()) -> Ret,
+    {
+        let type_name = self.type_name;
+        self.try_with_pinned(id, f)
+            .unwrap_or_else(|e| panic!("{e}; box holds {type_name}"))
+    }
+
+    /// Like [ImplBox::with_pinned], but return an error instead of
+    /// panicking.
+    pub fn try_with_pinned<F, Ret>(&mut self, id: TypeId, f: F) -> Result<Ret, ImplBoxError>
+    where
+        F: FnOnce(*mut ()) -> Ret,
+    {
+        self.check(id)?;
+        if !self.pinned {
+            return Err(ImplBoxError::NotPinned);
+        }
+        let p = self.ptr_mut();
+        Ok(PoisonOnUnwind::call(&self.poisoned, || f(p)))
+    }
+
+    /// Replace the stored item with `item` and return a box holding the
+    /// old one. The new item is stored the same way as the old one,
+    /// with the same id and clone function. The old item is not moved
+    /// if the box is pinned. Panics if `id` is not the id the box was
+    /// created with.
+    ///
+    /// # Safety
+    /// `C` must be the type of the stored item.
+    pub unsafe fn replace<C>(&mut self, id: TypeId, item: C) -> Self {
+        if self.id != id {
+            panic!("{}; box holds {}", ImplBoxError::IdMismatch, self.type_name);
+        }
+        let new = if self.pinned {
+            Self::box_pinned_in(id, self.destroy, item, self.allocator)
+        } else {
+            Self::new_in(id, self.destroy, item, self.allocator)
+        };
+        let mut new = new
+            .set_clone_fn(self.clone)
+            .set_type_name(self.type_name)
+            .set_send_check(self.is_send());
+        new.item_id = self.item_id;
+        #[cfg(feature = "serde")]
+        {
+            new.serde = self.serde;
+        }
+        mem::replace(self, new)
+    }
+
+    /// Consume the box and move the stored item out of it. Panics if
+    /// `id` is not the id the box was created with or if the box is
+    /// pinned, since moving a pinned item is not allowed.
+    ///
+    /// # Safety
+    /// `C` must be the type of the stored item.
+    pub unsafe fn into_value<C>(self, id: TypeId) -> C {
+        assert!(
+            !self.pinned,
+            "can't move out of a pinned ImplBox; box holds {}",
+            self.type_name
+        );
+        let item = self.with(id, |p| ptr::read(p as *const C));
+        if let Storage::Heap(p) = self.storage {
+            self.allocator.deallocate(p, self.layout);
+        }
+        #[cfg(feature = "accounting")]
+        accounting::dropped(core::any::type_name::<T>());
+        #[cfg(feature = "diagnostics")]
+        diagnostics::dropped(self.id);
+        mem::forget(self);
+        item
+    }
+
+    /// Call `f` with the stored pointer if `id` is the id the box was
+    /// created with and the box is not poisoned. Otherwise, return an
+    /// error without calling `f`. If `f` panics, the box is poisoned.
+    pub fn try_with<F, Ret>(&self, id: TypeId, f: F) -> Result<Ret, ImplBoxError>
+    where
+        F: FnOnce(*const ()) -> Ret,
+    {
+        self.check(id)?;
+        Ok(PoisonOnUnwind::call(&self.poisoned, || f(self.ptr())))
+    }
+}
+
+/// Sets a poisoned flag if dropped during unwinding. This works without
+/// `std`, which is needed for `catch_unwind`.
+struct PoisonOnUnwind<'a>(&'a AtomicBool);
+
+impl PoisonOnUnwind<'_> {
+    fn call<Ret>(poisoned: &AtomicBool, f: impl FnOnce() -> Ret) -> Ret {
+        let guard = PoisonOnUnwind(poisoned);
+        let ret = f();
+        mem::forget(guard);
+        ret
+    }
+}
+
+impl Drop for PoisonOnUnwind<'_> {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Serialize the stored item. This fails if the box has no
+/// [SerdeHooks] or is poisoned.
+#[cfg(feature = "serde")]
+impl<T, const N: usize> serde::Serialize for ImplBox<T, N> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::Error;
+        self.check_thread();
+        let Some(hooks) = self.serde else {
+            return Err(S::Error::custom(format_args!(
+                "ImplBox holding {} can't be serialized",
+                self.type_name
+            )));
+        };
+        if self.is_poisoned() {
+            return Err(S::Error::custom(ImplBoxError::Poisoned));
+        }
+        let item = unsafe { &*(hooks.serialize)(self.ptr()) };
+        erased_serde::serialize(item, serializer)
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for ImplBox<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ImplBox")
+            .field("shadow", &core::any::type_name::<T>())
+            .field("concrete", &self.type_name)
+            .field("inline", &self.is_inline())
+            .field("cloneable", &self.is_cloneable())
+            .finish()
+    }
+}
+
+unsafe impl<T: Sync> Send for ImplBoxRef<'_, T> {}
+unsafe impl<T: Sync> Sync for ImplBoxRef<'_, T> {}
+/// A borrowed item of a type that is not known where the reference is
+/// used. Unlike [ImplBox], this doesn't own the item, so it has no
+/// destroy function, and it can be copied freely for as long as the
+/// borrow lasts. The same id check applies when it is unboxed.
+pub struct ImplBoxRef<'a, T> {
+    id: TypeId,
+    ptr: *const (),
+    type_name: &'static str,
+    _t: PhantomData<&'a T>,
+}
+
+impl<'a, T> ImplBoxRef<'a, T> {
+    /// Borrow `item`. `id` identifies the type that is allowed to
+    /// access the item.
+    pub fn new<C>(id: TypeId, item: &'a C) -> Self {
+        Self {
+            id,
+            ptr: item as *const C as *const (),
+            type_name: core::any::type_name::<C>(),
+            _t: PhantomData,
+        }
+    }
+
+    /// The name of the concrete type of the borrowed item, or
+    /// `unknown` if it was not recorded
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Call `f` with the borrowed pointer. Panics if `id` is not the
+    /// id the reference was created with.
     pub fn with<F, Ret>(&self, id: TypeId, f: F) -> Ret
+    where
+        F: FnOnce(*const ()) -> Ret,
+    {
+        self.try_with(id, f)
+            .unwrap_or_else(|e| panic!("{e}; box holds {}", self.type_name))
+    }
+
+    /// Call `f` with the borrowed pointer if `id` is the id the
+    /// reference was created with. Otherwise, return an error without
+    /// calling `f`.
+    pub fn try_with<F, Ret>(&self, id: TypeId, f: F) -> Result<Ret, ImplBoxError>
     where
         F: FnOnce(*const ()) -> Ret,
     {
         if self.id == id {
-            f(self.ptr)
+            Ok(f(self.ptr))
         } else {
-            panic!("id mismatch");
+            Err(ImplBoxError::IdMismatch)
         }
     }
 }
-impl<T> Drop for ImplBox<T> {
+
+impl<T> Clone for ImplBoxRef<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ImplBoxRef<'_, T> {}
+
+impl<T> fmt::Debug for ImplBoxRef<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ImplBoxRef")
+            .field("shadow", &core::any::type_name::<T>())
+            .field("concrete", &self.type_name)
+            .finish()
+    }
+}
+
+/// An [ImplBox] with shared ownership. Cloning it creates another
+/// handle to the same item, which is destroyed when the last handle is
+/// dropped. It dereferences to the [ImplBox], so it can be passed to
+/// the generated unbox functions. Create one with the generated
+/// `box_shared_thing` function or from an existing [ImplBox].
+pub struct ImplBoxShared<T, const N: usize = INLINE_WORDS>(Arc<ImplBox<T, N>>);
+
+impl<T, const N: usize> ImplBoxShared<T, N> {
+    pub fn new(b: ImplBox<T, N>) -> Self {
+        Self(Arc::new(b))
+    }
+}
+
+impl<T, const N: usize> Clone for ImplBoxShared<T, N> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T, const N: usize> Deref for ImplBoxShared<T, N> {
+    type Target = ImplBox<T, N>;
+
+    fn deref(&self) -> &ImplBox<T, N> {
+        &self.0
+    }
+}
+
+impl<T, const N: usize> From<ImplBox<T, N>> for ImplBoxShared<T, N> {
+    fn from(b: ImplBox<T, N>) -> Self {
+        Self::new(b)
+    }
+}
+
+/// Support for generated code. The generated box functions need to
+/// know whether the concrete type implements [Clone], which a macro
+/// can't see. [Probe] uses autoref-based specialization: for a
+/// concrete type that implements [Clone], method resolution finds
+/// [CloneYes] on `Probe<C>` before it tries [CloneNo] on `&Probe<C>`.
+/// In generic code, the choice is made based on the declared bounds.
+/// Code generated by `implbox_trait` can't name the concrete type, so
+/// it gets a [Probe] from `probe_of`, which infers the type from a
+/// closure that is never called, and uses it in place of the type.
+#[doc(hidden)]
+pub mod __private {
+    use crate::{CloneFn, ImplBox};
+    use core::any::TypeId;
+    use core::future::Future;
+    use core::marker::PhantomData;
+    use core::ptr;
+
+    pub struct Probe<C>(PhantomData<C>);
+
+    impl<C> Probe<C> {
+        #[allow(clippy::new_without_default)]
+        pub fn new() -> Self {
+            Self(PhantomData)
+        }
+
+        pub fn type_name(&self) -> &'static str {
+            core::any::type_name::<C>()
+        }
+
+        /// # Safety
+        /// `p` must point to a valid `C` that outlives `'a`.
+        pub unsafe fn cast<'a>(&self, p: *const ()) -> &'a C {
+            &*(p as *const C)
+        }
+
+        /// # Safety
+        /// `p` must point to a valid `C` that outlives `'a` and is not
+        /// otherwise borrowed.
+        pub unsafe fn cast_mut<'a>(&self, p: *mut ()) -> &'a mut C {
+            &mut *(p as *mut C)
+        }
+
+        /// # Safety
+        /// `C` must be the type of the item stored in `b`.
+        pub unsafe fn take<T>(&self, b: ImplBox<T>, id: TypeId) -> C {
+            b.into_value(id)
+        }
+
+        /// # Safety
+        /// `p` must point to a valid `C` that is not used again.
+        pub unsafe fn drop_in_place(&self, p: *const ()) {
+            ptr::drop_in_place(p as *mut C);
+        }
+    }
+
+    impl<C, E> Probe<Result<C, E>> {
+        /// Return a probe for the type of a successful result
+        pub fn ok(&self) -> Probe<C> {
+            Probe::new()
+        }
+    }
+
+    /// Return a probe for the type returned by `f`, which is not called
+    pub fn probe_of<C>(_f: impl FnOnce() -> C) -> Probe<C> {
+        Probe::new()
+    }
+
+    /// Return a probe for the output of the future returned by `f`,
+    /// which is not called
+    pub fn probe_of_output<F: Future>(_f: impl FnOnce() -> F) -> Probe<F::Output> {
+        Probe::new()
+    }
+
+    unsafe fn clone_shim<C: Clone>(src: *const (), dst: *mut ()) {
+        let item = &*(src as *const C);
+        ptr::write(dst as *mut C, item.clone());
+    }
+
+    pub trait CloneYes {
+        fn clone_fn(&self) -> Option<CloneFn>;
+    }
+
+    impl<C: Clone> CloneYes for Probe<C> {
+        fn clone_fn(&self) -> Option<CloneFn> {
+            Some(clone_shim::<C>)
+        }
+    }
+
+    pub trait CloneNo {
+        fn clone_fn(&self) -> Option<CloneFn>;
+    }
+
+    impl<C> CloneNo for &Probe<C> {
+        fn clone_fn(&self) -> Option<CloneFn> {
+            None
+        }
+    }
+
+    pub trait SendYes {
+        fn is_send(&self) -> bool;
+    }
+
+    impl<C: Send> SendYes for Probe<C> {
+        fn is_send(&self) -> bool {
+            true
+        }
+    }
+
+    pub trait SendNo {
+        fn is_send(&self) -> bool;
+    }
+
+    impl<C> SendNo for &Probe<C> {
+        fn is_send(&self) -> bool {
+            false
+        }
+    }
+}
+
+impl<T, const N: usize> Drop for ImplBox<T, N> {
     fn drop(&mut self) {
-        (self.destroy)(self.ptr);
+        #[cfg(feature = "accounting")]
+        accounting::dropped(core::any::type_name::<T>());
+        #[cfg(feature = "diagnostics")]
+        diagnostics::dropped(self.id);
+        (self.destroy)(self.ptr());
+        if let Storage::Heap(p) = self.storage {
+            unsafe { self.allocator.deallocate(p, self.layout) };
+        }
     }
 }
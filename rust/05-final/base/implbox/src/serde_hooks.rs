@@ -0,0 +1,46 @@
+//! Serialization of boxed items. This is enabled by the `serde`
+//! feature. The concrete type is only known where the box is created,
+//! so functions that serialize and deserialize it are captured there as
+//! [SerdeHooks]. The generated box functions capture them when
+//! `#[implbox_impls]` is given the `serde` flag. A box with hooks
+//! implements [Serialize], and [ImplBox::restore] replaces its item
+//! with a deserialized one.
+//!
+//! [ImplBox]: crate::ImplBox
+//! [ImplBox::restore]: crate::ImplBox::restore
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Functions that serialize and deserialize an item of a particular
+/// concrete type through a type-erased pointer
+#[derive(Clone, Copy)]
+pub struct SerdeHooks {
+    pub(crate) serialize: unsafe fn(*const ()) -> *const dyn erased_serde::Serialize,
+    pub(crate) deserialize: for<'de> unsafe fn(
+        *mut (),
+        &mut dyn erased_serde::Deserializer<'de>,
+    ) -> Result<(), erased_serde::Error>,
+}
+
+unsafe fn serialize<C: Serialize + 'static>(p: *const ()) -> *const dyn erased_serde::Serialize {
+    p as *const C as *const dyn erased_serde::Serialize
+}
+
+unsafe fn deserialize<C: DeserializeOwned>(
+    p: *mut (),
+    de: &mut dyn erased_serde::Deserializer<'_>,
+) -> Result<(), erased_serde::Error> {
+    let item: C = erased_serde::deserialize(de)?;
+    *(p as *mut C) = item;
+    Ok(())
+}
+
+impl SerdeHooks {
+    /// Hooks for items of type `C`
+    pub fn of<C: Serialize + DeserializeOwned + 'static>() -> Self {
+        Self {
+            serialize: serialize::<C>,
+            deserialize: deserialize::<C>,
+        }
+    }
+}
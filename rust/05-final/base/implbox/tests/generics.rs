@@ -0,0 +1,86 @@
+//! Exercise the macros with constructors that have several generic
+//! parameters and where-clauses.
+
+use implbox::ImplBox;
+use implbox_macros::{implbox_decls, implbox_impls};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+trait Cache<K, V> {
+    fn put(&mut self, k: K, v: V);
+    fn get(&self, k: &K) -> Option<&V>;
+    fn cap(&self) -> usize;
+}
+
+struct MapCache<K, V> {
+    cap: usize,
+    items: HashMap<K, V>,
+}
+
+impl<K: Hash + Eq, V: Clone> Cache<K, V> for MapCache<K, V> {
+    fn put(&mut self, k: K, v: V) {
+        if self.items.len() < self.cap {
+            self.items.insert(k, v);
+        }
+    }
+
+    fn get(&self, k: &K) -> Option<&V> {
+        self.items.get(k)
+    }
+
+    fn cap(&self) -> usize {
+        self.cap
+    }
+}
+
+struct CacheBox<K, V>(PhantomData<(K, V)>);
+
+trait CacheMaker {
+    #[implbox_decls(CacheBox<K, V>)]
+    fn new_cache<K: Hash + Eq, V: Clone>(cap: usize) -> impl Cache<K, V>;
+
+    #[implbox_decls(CacheBox<K, V>)]
+    fn new_where_cache<K, V>(cap: usize) -> impl Cache<K, V>
+    where
+        K: Hash + Eq,
+        V: Clone;
+}
+
+struct Maker;
+impl CacheMaker for Maker {
+    #[implbox_impls(CacheBox<K, V>, MapCache<K, V>)]
+    fn new_cache<K: Hash + Eq, V: Clone>(cap: usize) -> impl Cache<K, V> {
+        MapCache {
+            cap,
+            items: HashMap::new(),
+        }
+    }
+
+    #[implbox_impls(CacheBox<K, V>, MapCache<K, V>)]
+    fn new_where_cache<K, V>(cap: usize) -> impl Cache<K, V>
+    where
+        K: Hash + Eq,
+        V: Clone,
+    {
+        MapCache {
+            cap,
+            items: HashMap::new(),
+        }
+    }
+}
+
+#[test]
+fn test_generics() {
+    let b: ImplBox<CacheBox<String, i32>> = Maker::box_cache(2);
+    assert_eq!(Maker::unbox_cache(&b).cap(), 2);
+    let b2 = b.try_clone();
+    assert!(b2.is_none());
+    let mut c = Maker::take_cache::<String, i32>(b);
+    assert_eq!(c.get(&"x".to_string()), None);
+    c.put("x".to_string(), 1);
+    assert_eq!(c.get(&"x".to_string()), Some(&1));
+
+    let b: ImplBox<CacheBox<&str, i32>> = Maker::box_where_cache(1);
+    assert_eq!(Maker::unbox_where_cache(&b).cap(), 1);
+}
@@ -0,0 +1,149 @@
+//! Exercise the shadow types and helper trait generated by
+//! implbox_trait.
+
+use implbox::{ImplBox, ImplBoxError};
+use implbox_macros::implbox_trait;
+
+trait Counter {
+    fn incr(&mut self) -> usize;
+    fn get(&self) -> usize;
+}
+
+#[derive(Clone)]
+struct Simple(usize);
+impl Counter for Simple {
+    fn incr(&mut self) -> usize {
+        self.0 += 1;
+        self.0
+    }
+
+    fn get(&self) -> usize {
+        self.0
+    }
+}
+
+trait Labeled<T> {
+    fn label(&self) -> &T;
+}
+
+struct Label<T>(T);
+impl<T> Labeled<T> for Label<T> {
+    fn label(&self) -> &T {
+        &self.0
+    }
+}
+
+#[implbox_trait(handles)]
+trait Maker {
+    fn new_counter(start: usize) -> impl Counter + Clone + Send + Sync;
+    fn new_label<T: Clone>(label: &T) -> impl Labeled<T>;
+    fn new_checked(start: usize) -> Result<impl Counter, String>;
+    fn version() -> usize;
+}
+
+struct M1;
+impl Maker for M1 {
+    fn new_counter(start: usize) -> impl Counter + Clone + Send + Sync {
+        Simple(start)
+    }
+
+    fn new_label<T: Clone>(label: &T) -> impl Labeled<T> {
+        Label(label.clone())
+    }
+
+    fn new_checked(start: usize) -> Result<impl Counter, String> {
+        match start {
+            0 => Err("zero".to_string()),
+            _ => Ok(Simple(start)),
+        }
+    }
+
+    fn version() -> usize {
+        1
+    }
+}
+
+struct M2;
+impl Maker for M2 {
+    fn new_counter(start: usize) -> impl Counter + Clone + Send + Sync {
+        Simple(start * 2)
+    }
+
+    fn new_label<T: Clone>(label: &T) -> impl Labeled<T> {
+        Label(label.clone())
+    }
+
+    fn new_checked(_start: usize) -> Result<impl Counter, String> {
+        Err::<Simple, _>("unsupported".to_string())
+    }
+
+    fn version() -> usize {
+        2
+    }
+}
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn test_trait() {
+    assert_send_sync::<ImplBox<CounterBox>>();
+    assert_eq!(M1::version() + M2::version(), 3);
+
+    let mut b = M1::box_counter(1);
+    assert!(b.type_name().ends_with("Simple"));
+    assert_eq!(M1::unbox_mut_counter(&mut b).incr(), 2);
+    let b2 = b.try_clone().unwrap();
+    assert_eq!(M1::unbox_counter(&b2).get(), 2);
+    assert_eq!(
+        M2::try_unbox_counter(&b).err().unwrap(),
+        ImplBoxError::IdMismatch
+    );
+    assert_eq!(M1::take_counter(b).get(), 2);
+    assert_eq!(M2::unbox_ref_counter(M2::box_counter(2).to_ref()).get(), 4);
+
+    let mut old = M2::box_counter(1);
+    let older = M2::replace_counter(&mut old, 5);
+    assert_eq!(M2::unbox_counter(&old).get(), 10);
+    assert_eq!(M2::unbox_counter(&older).get(), 2);
+
+    let mut p = M1::box_pinned_counter(3);
+    assert_eq!(M1::unbox_pinned_counter(&mut p).get(), 3);
+
+    // Cloning depends on the declared bounds, not the concrete type.
+    let l: ImplBox<LabelBox<String>> = M1::box_label(&"potato".to_string());
+    assert!(!l.is_cloneable());
+    assert_eq!(M1::unbox_label(&l).label(), "potato");
+    assert_eq!(M1::take_label(l).label(), "potato");
+
+    let b = M1::box_checked(2).unwrap();
+    assert_eq!(M1::unbox_checked(&b).get(), 2);
+    assert_eq!(M1::box_checked(0).err().unwrap(), "zero");
+    assert_eq!(M2::box_checked(2).err().unwrap(), "unsupported");
+}
+
+/// Something that holds a counter without knowing its type
+struct Holder<M: Maker + 'static> {
+    counter: CounterHandle<M>,
+}
+
+#[test]
+fn test_handles() {
+    let mut h = Holder {
+        counter: CounterHandle::<M1>::new(1),
+    };
+    assert_eq!(h.counter.get_mut().incr(), 2);
+    assert_eq!(h.counter.get().get(), 2);
+    let h2: Holder<M2> = Holder {
+        counter: CounterHandle::new(1),
+    };
+    assert_eq!(h2.counter.get().get(), 2);
+    assert_eq!(h.counter.take().get(), 2);
+
+    let l = LabelHandle::<M1, String>::new(&"potato".to_string());
+    assert_eq!(l.get().label(), "potato");
+    let b = l.into_inner();
+    assert_eq!(M1::unbox_label(&b).label(), "potato");
+
+    assert_eq!(CheckedHandle::<M1>::new(3).unwrap().get().get(), 3);
+    assert!(CheckedHandle::<M1>::new(0).is_err());
+}
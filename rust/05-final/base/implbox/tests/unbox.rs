@@ -0,0 +1,587 @@
+//! Exercise the generated unbox functions with boxes created by the
+//! right and the wrong implementation.
+
+use implbox::{Allocator, ImplBox, ImplBoxError, ImplBoxRef};
+use implbox_macros::{implbox_decls, implbox_impls};
+use std::marker::{PhantomData, PhantomPinned};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+trait Named {
+    fn name(&self) -> String;
+}
+
+#[derive(Clone)]
+struct Fixed(&'static str);
+impl Named for Fixed {
+    fn name(&self) -> String {
+        self.0.to_string()
+    }
+}
+
+struct NamedBox(PhantomData<()>);
+
+trait Namer {
+    #[implbox_decls(NamedBox)]
+    fn new_named(name: &'static str) -> impl Named;
+}
+
+struct A;
+impl Namer for A {
+    #[implbox_impls(NamedBox, Fixed)]
+    fn new_named(name: &'static str) -> impl Named {
+        Fixed(name)
+    }
+}
+
+struct B;
+impl Namer for B {
+    #[implbox_impls(NamedBox, Fixed)]
+    fn new_named(name: &'static str) -> impl Named {
+        Fixed(name)
+    }
+}
+
+#[test]
+fn test_try_unbox() {
+    let b = A::box_named("potato");
+    assert_eq!(A::unbox_named(&b).name(), "potato");
+    assert_eq!(A::try_unbox_named(&b).unwrap().name(), "potato");
+    assert_eq!(
+        B::try_unbox_named(&b).err().unwrap(),
+        ImplBoxError::IdMismatch
+    );
+}
+
+struct Unique;
+impl Named for Unique {
+    fn name(&self) -> String {
+        "unique".to_string()
+    }
+}
+
+struct C;
+impl Namer for C {
+    #[implbox_impls(NamedBox, Unique)]
+    fn new_named(_name: &'static str) -> impl Named {
+        Unique
+    }
+}
+
+#[test]
+fn test_clone() {
+    let b = A::box_named("potato");
+    assert!(b.is_cloneable());
+    let b2 = b.try_clone().unwrap();
+    drop(b);
+    assert_eq!(A::unbox_named(&b2).name(), "potato");
+
+    let b = C::box_named("potato");
+    assert!(!b.is_cloneable());
+    assert!(b.try_clone().is_none());
+}
+
+/// Too big to be stored inline. Counts how many times it is dropped.
+#[derive(Clone)]
+struct Large([u64; 8]);
+static LARGE_DROPS: AtomicUsize = AtomicUsize::new(0);
+impl Named for Large {
+    fn name(&self) -> String {
+        format!("large {}", self.0[7])
+    }
+}
+impl Drop for Large {
+    fn drop(&mut self) {
+        LARGE_DROPS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+struct D;
+impl Namer for D {
+    #[implbox_impls(NamedBox, Large)]
+    fn new_named(_name: &'static str) -> impl Named {
+        Large([7; 8])
+    }
+}
+
+#[test]
+fn test_storage() {
+    let b = A::box_named("potato");
+    assert!(b.is_inline());
+    let b = ImplBox::<NamedBox, 0>::new(std::any::TypeId::of::<A>(), |_| (), 5u8);
+    assert!(!b.is_inline());
+    let b = ImplBox::<NamedBox, 0>::new(std::any::TypeId::of::<A>(), |_| (), ());
+    assert!(b.is_inline());
+
+    // Each Large is dropped exactly once whether it is dropped in the
+    // box, cloned, or taken out of the box.
+    let b = D::box_named("");
+    assert!(!b.is_inline());
+    let b2 = b.try_clone().unwrap();
+    assert!(!b2.is_inline());
+    drop(b);
+    assert_eq!(LARGE_DROPS.load(Ordering::Relaxed), 1);
+    assert_eq!(D::unbox_named(&b2).name(), "large 7");
+    let large = D::take_named(b2);
+    assert_eq!(LARGE_DROPS.load(Ordering::Relaxed), 1);
+    assert_eq!(large.name(), "large 7");
+    drop(large);
+    assert_eq!(LARGE_DROPS.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn test_replace() {
+    let mut b = A::box_named("potato");
+    let old = A::replace_named(&mut b, "carrot");
+    assert_eq!(A::unbox_named(&b).name(), "carrot");
+    assert!(b.is_cloneable());
+    assert_eq!(A::take_named(old).name(), "potato");
+}
+
+#[test]
+fn test_poison() {
+    let mut b = A::box_named("potato");
+    let id = std::any::TypeId::of::<A>();
+    let r = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        b.with(id, |_| panic!("torn"));
+    }));
+    assert!(r.is_err());
+    assert!(b.is_poisoned());
+    assert_eq!(
+        A::try_unbox_named(&b).err().unwrap(),
+        ImplBoxError::Poisoned
+    );
+    assert!(b.try_clone().is_none());
+    b.clear_poison();
+    assert_eq!(A::unbox_named(&b).name(), "potato");
+
+    // Replacing the item also clears the poison.
+    let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        b.with(id, |_| panic!("torn"));
+    }));
+    drop(A::replace_named(&mut b, "carrot"));
+    assert!(!b.is_poisoned());
+    assert_eq!(A::unbox_named(&b).name(), "carrot");
+
+    // Poisoning by hand works the same way.
+    b.poison();
+    assert_eq!(
+        A::try_unbox_named(&b).err().unwrap(),
+        ImplBoxError::Poisoned
+    );
+    b.clear_poison();
+    assert_eq!(A::unbox_named(&b).name(), "carrot");
+}
+
+struct F;
+impl Namer for F {
+    #[implbox_impls(NamedBox, Fixed, downcast)]
+    fn new_named(name: &'static str) -> impl Named {
+        Fixed(name)
+    }
+}
+
+#[test]
+fn test_downcast() {
+    let b = F::box_named("potato");
+    assert!(b.is::<Fixed>());
+    assert_eq!(b.downcast_ref::<Fixed>().unwrap().0, "potato");
+    assert!(b.downcast_ref::<Unique>().is_none());
+    assert!(b.try_clone().unwrap().is::<Fixed>());
+
+    // Without `downcast`, the type is not recorded.
+    let b = A::box_named("potato");
+    assert!(!b.is::<Fixed>());
+    assert!(b.downcast_ref::<Fixed>().is_none());
+}
+
+/// Bytes currently allocated by the counting allocator
+static ARENA_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe fn arena_alloc(layout: std::alloc::Layout) -> *mut u8 {
+    ARENA_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+    std::alloc::alloc(layout)
+}
+
+unsafe fn arena_dealloc(p: *mut u8, layout: std::alloc::Layout) {
+    ARENA_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+    std::alloc::dealloc(p, layout)
+}
+
+const ARENA: Allocator = Allocator {
+    alloc: arena_alloc,
+    dealloc: arena_dealloc,
+};
+
+/// Like Large, but without the drop counter
+#[derive(Clone)]
+struct Big([u64; 8]);
+impl Named for Big {
+    fn name(&self) -> String {
+        format!("big {}", self.0[7])
+    }
+}
+
+struct G;
+impl Namer for G {
+    #[implbox_impls(NamedBox, Big)]
+    fn new_named(_name: &'static str) -> impl Named {
+        Big([7; 8])
+    }
+}
+
+#[test]
+fn test_allocator() {
+    // Small items don't use the allocator.
+    let b = A::box_in_named(ARENA, "potato");
+    assert!(b.is_inline());
+    assert_eq!(ARENA_BYTES.load(Ordering::Relaxed), 0);
+
+    let mut b = G::box_in_named(ARENA, "");
+    let size = std::mem::size_of::<Big>();
+    assert_eq!(ARENA_BYTES.load(Ordering::Relaxed), size);
+    let b2 = b.try_clone().unwrap();
+    assert_eq!(ARENA_BYTES.load(Ordering::Relaxed), 2 * size);
+    let old = G::replace_named(&mut b, "");
+    assert_eq!(ARENA_BYTES.load(Ordering::Relaxed), 3 * size);
+    drop(old);
+    drop(b2);
+    assert_eq!(G::take_named(b).name(), "big 7");
+    assert_eq!(ARENA_BYTES.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+fn test_take() {
+    let b = A::box_named("potato");
+    let named = A::take_named(b);
+    assert_eq!(named.name(), "potato");
+}
+
+/// Lends a name without owning it
+struct Request<'a> {
+    named: ImplBoxRef<'a, NamedBox>,
+}
+
+fn greet(r: Request) -> String {
+    format!("hello {}", A::unbox_ref_named(r.named).name())
+}
+
+#[test]
+fn test_ref() {
+    let b = A::box_named("potato");
+    let r = b.to_ref();
+    assert_eq!(greet(Request { named: r }), "hello potato");
+    assert_eq!(greet(Request { named: r }), "hello potato");
+    assert_eq!(r.type_name(), "unbox::Fixed");
+    assert!(r.try_with(std::any::TypeId::of::<B>(), |_| ()).is_err());
+    // The box is still usable and is dropped normally.
+    assert_eq!(A::unbox_named(&b).name(), "potato");
+
+    let fixed = Fixed("carrot");
+    let r = ImplBoxRef::<NamedBox>::new(std::any::TypeId::of::<A>(), &fixed);
+    assert_eq!(greet(Request { named: r }), "hello carrot");
+}
+
+#[test]
+fn test_shared() {
+    let b = A::box_shared_named("potato");
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let b = b.clone();
+            std::thread::spawn(move || A::unbox_named(&b).name())
+        })
+        .collect();
+    for h in handles {
+        assert_eq!(h.join().unwrap(), "potato");
+    }
+}
+
+#[test]
+fn test_debug() {
+    let b = A::box_named("potato");
+    assert_eq!(b.type_name(), "unbox::Fixed");
+    assert_eq!(
+        format!("{b:?}"),
+        r#"ImplBox { shadow: "unbox::NamedBox", concrete: "unbox::Fixed", inline: true, cloneable: true }"#
+    );
+}
+
+#[test]
+#[should_panic(expected = "box holds unbox::Fixed")]
+fn test_unbox_mismatch() {
+    let b = A::box_named("potato");
+    B::unbox_named(&b);
+}
+
+/// Remembers its own address and checks that it hasn't moved
+trait Anchored {
+    fn check(self: Pin<&mut Self>) -> usize;
+}
+
+struct Anchor {
+    addr: usize,
+    checks: usize,
+    _pin: PhantomPinned,
+}
+impl Anchored for Anchor {
+    fn check(self: Pin<&mut Self>) -> usize {
+        let this = unsafe { self.get_unchecked_mut() };
+        let addr = this as *const Anchor as usize;
+        if this.addr == 0 {
+            this.addr = addr;
+        }
+        assert_eq!(this.addr, addr);
+        this.checks += 1;
+        this.checks
+    }
+}
+
+struct AnchorBox(PhantomData<()>);
+
+trait AnchorMaker {
+    #[implbox_decls(AnchorBox)]
+    fn new_anchor() -> impl Anchored;
+}
+
+impl AnchorMaker for A {
+    #[implbox_impls(AnchorBox, Anchor)]
+    fn new_anchor() -> impl Anchored {
+        Anchor {
+            addr: 0,
+            checks: 0,
+            _pin: PhantomPinned,
+        }
+    }
+}
+
+#[test]
+fn test_pinned() {
+    let mut b = A::box_pinned_anchor();
+    assert!(b.is_pinned());
+    assert_eq!(A::unbox_pinned_anchor(&mut b).check(), 1);
+    // Moving the box doesn't move the item.
+    let mut boxes = [b];
+    assert_eq!(A::unbox_pinned_anchor(&mut boxes[0]).check(), 2);
+    // Replacing the item leaves the old one where it was.
+    let mut old = A::replace_anchor(&mut boxes[0]);
+    assert!(boxes[0].is_pinned());
+    assert_eq!(A::unbox_pinned_anchor(&mut boxes[0]).check(), 1);
+    assert_eq!(A::unbox_pinned_anchor(&mut old).check(), 3);
+
+    let mut b = A::box_anchor();
+    assert_eq!(
+        b.try_with_pinned(std::any::TypeId::of::<A>(), |_| ())
+            .err()
+            .unwrap(),
+        ImplBoxError::NotPinned
+    );
+}
+
+trait Renamed {
+    #[implbox_decls(NamedBox, name = "label")]
+    fn make(name: &'static str) -> impl Named;
+}
+
+impl Renamed for A {
+    #[implbox_impls(NamedBox, Fixed, name = "label")]
+    fn make(name: &'static str) -> impl Named {
+        Fixed(name)
+    }
+}
+
+#[test]
+fn test_name() {
+    let b = A::box_label("potato");
+    assert_eq!(A::unbox_label(&b).name(), "potato");
+    assert_eq!(A::take_label(b).name(), "potato");
+}
+
+trait Fallible {
+    #[implbox_decls(NamedBox)]
+    fn new_parsed(s: &'static str) -> Result<impl Named, String>;
+}
+
+impl Fallible for A {
+    #[implbox_impls(NamedBox, Fixed)]
+    fn new_parsed(s: &'static str) -> Result<impl Named, String> {
+        if s.is_empty() {
+            Err("empty".to_string())
+        } else {
+            Ok(Fixed(s))
+        }
+    }
+}
+
+#[test]
+fn test_fallible() {
+    let mut b = A::box_parsed("potato").unwrap();
+    assert_eq!(A::unbox_parsed(&b).name(), "potato");
+    assert_eq!(A::box_parsed("").err().unwrap(), "empty");
+    assert_eq!(A::box_shared_parsed("").err().unwrap(), "empty");
+    assert!(A::replace_parsed(&mut b, "").is_err());
+    let old = A::replace_parsed(&mut b, "carrot").unwrap();
+    assert_eq!(A::take_parsed(old).name(), "potato");
+    assert_eq!(A::unbox_parsed(&b).name(), "carrot");
+}
+
+trait Tally {
+    fn add(&mut self, n: usize) -> usize;
+}
+
+struct Total(usize);
+impl Tally for Total {
+    fn add(&mut self, n: usize) -> usize {
+        self.0 += n;
+        self.0
+    }
+}
+
+struct TallyBox(PhantomData<()>);
+
+trait TallyMaker {
+    #[implbox_decls(TallyBox)]
+    fn new_tally(start: usize) -> impl Tally;
+}
+
+impl TallyMaker for A {
+    #[implbox_impls(TallyBox, Total)]
+    fn new_tally(start: usize) -> impl Tally {
+        Total(start)
+    }
+}
+
+#[test]
+fn test_unbox_mut() {
+    let mut b = A::box_tally(1);
+    assert_eq!(A::unbox_mut_tally(&mut b).add(2), 3);
+    assert_eq!(A::unbox_mut_tally(&mut b).add(4), 7);
+    assert_eq!(A::take_tally(b).add(0), 7);
+
+    let mut b = A::box_pinned_tally(1);
+    assert_eq!(
+        b.try_with_mut(std::any::TypeId::of::<A>(), |_| ())
+            .err()
+            .unwrap(),
+        ImplBoxError::Pinned
+    );
+}
+
+#[test]
+#[should_panic(expected = "can't move out of a pinned ImplBox")]
+fn test_take_pinned() {
+    let b = A::box_pinned_named("potato");
+    A::take_named(b);
+}
+
+#[cfg(feature = "diagnostics")]
+#[test]
+fn test_live_counts() {
+    // Only this test creates boxes with E's id.
+    struct E;
+    impl Namer for E {
+        #[implbox_impls(NamedBox, Fixed)]
+        fn new_named(name: &'static str) -> impl Named {
+            Fixed(name)
+        }
+    }
+    let id = std::any::TypeId::of::<E>();
+    assert!(!implbox::live_counts().contains_key(&id));
+    let b = E::box_named("potato");
+    let b2 = b.try_clone().unwrap();
+    let live = implbox::live_counts()[&id];
+    assert_eq!(live.count, 2);
+    assert_eq!(live.type_name, "unbox::Fixed");
+    assert!(implbox::live_total() >= 2);
+    drop(b);
+    assert_eq!(implbox::live_counts()[&id].count, 1);
+    E::take_named(b2);
+    assert!(!implbox::live_counts().contains_key(&id));
+}
+
+/// Not Send, but boxed with a Send shadow type by mistake
+struct Local(std::rc::Rc<str>);
+impl Named for Local {
+    fn name(&self) -> String {
+        self.0.to_string()
+    }
+}
+
+struct L;
+impl Namer for L {
+    #[implbox_impls(NamedBox, Local)]
+    fn new_named(name: &'static str) -> impl Named {
+        Local(name.into())
+    }
+}
+
+#[cfg(debug_assertions)]
+#[test]
+fn test_send_check() {
+    let b = L::box_named("potato");
+    assert_eq!(L::unbox_named(&b).name(), "potato");
+    let err = std::thread::spawn(move || {
+        L::unbox_named(&b);
+    })
+    .join()
+    .unwrap_err();
+    let msg = err.downcast_ref::<String>().unwrap();
+    assert!(msg.contains("unbox::Local, which is not Send, was used from a thread"));
+
+    // Send items can be used anywhere.
+    let b = A::box_named("potato");
+    let name = std::thread::spawn(move || A::unbox_named(&b).name());
+    assert_eq!(name.join().unwrap(), "potato");
+}
+
+#[cfg(feature = "serde")]
+mod serde_tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    struct Counter {
+        name: String,
+        count: u32,
+    }
+    impl Named for Counter {
+        fn name(&self) -> String {
+            format!("{} {}", self.name, self.count)
+        }
+    }
+
+    struct S;
+    impl Namer for S {
+        #[implbox_impls(NamedBox, Counter, serde)]
+        fn new_named(name: &'static str) -> impl Named {
+            Counter {
+                name: name.to_string(),
+                count: 1,
+            }
+        }
+    }
+
+    #[test]
+    fn test_serde() {
+        let b = S::box_named("potato");
+        let json = serde_json::to_string(&b).unwrap();
+        assert_eq!(json, r#"{"name":"potato","count":1}"#);
+
+        let mut b = S::box_named("carrot");
+        b.restore(&mut serde_json::Deserializer::from_str(&json))
+            .unwrap();
+        assert_eq!(S::unbox_named(&b).name(), "potato 1");
+        // A failed restore keeps the old item.
+        assert!(b
+            .restore(&mut serde_json::Deserializer::from_str("{}"))
+            .is_err());
+        assert_eq!(S::unbox_named(&b).name(), "potato 1");
+
+        // Without the flag, there are no hooks.
+        let b = A::box_named("potato");
+        let err = serde_json::to_string(&b).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "ImplBox holding unbox::Fixed can't be serialized"
+        );
+    }
+}
@@ -0,0 +1,59 @@
+//! Exercise the macros with constructors that have lifetime and const
+//! generic parameters.
+
+use implbox::ImplBox;
+use implbox_macros::{implbox_decls, implbox_impls, implbox_trait};
+use std::marker::PhantomData;
+
+trait View<'a> {
+    fn bytes(&self) -> &'a [u8];
+}
+
+struct Slice<'a>(&'a [u8]);
+impl<'a> View<'a> for Slice<'a> {
+    fn bytes(&self) -> &'a [u8] {
+        self.0
+    }
+}
+
+struct ViewBox<'a, const N: usize>(PhantomData<&'a [u8; N]>);
+
+trait ViewMaker {
+    #[implbox_decls(ViewBox<'a, N>)]
+    fn new_view<'a, const N: usize>(data: &'a [u8; N]) -> impl View<'a>;
+}
+
+struct Maker;
+impl ViewMaker for Maker {
+    #[implbox_impls(ViewBox<'a, N>, Slice<'a>)]
+    fn new_view<'a, const N: usize>(data: &'a [u8; N]) -> impl View<'a> {
+        Slice(data)
+    }
+}
+
+#[implbox_trait]
+trait Window {
+    fn new_window<'a, const N: usize>(data: &'a [u8; N], start: usize) -> impl View<'a>;
+}
+
+impl Window for Maker {
+    fn new_window<'a, const N: usize>(data: &'a [u8; N], start: usize) -> impl View<'a> {
+        Slice(&data[start..])
+    }
+}
+
+#[test]
+fn test_views() {
+    let data = [1u8, 2, 3];
+    let mut b: ImplBox<ViewBox<'_, 3>> = Maker::box_view(&data);
+    assert_eq!(Maker::unbox_view(&b).bytes(), &[1, 2, 3]);
+    let other = [4u8, 5, 6];
+    let old = Maker::replace_view(&mut b, &other);
+    assert_eq!(Maker::take_view(old).bytes(), &[1, 2, 3]);
+    assert_eq!(Maker::unbox_ref_view(b.to_ref()).bytes(), &[4, 5, 6]);
+    assert_eq!(Maker::take_view(b).bytes(), &[4, 5, 6]);
+
+    let mut w: ImplBox<WindowBox<'_, 3>> = Maker::box_window(&data, 1);
+    assert_eq!(Maker::unbox_window(&w).bytes(), &[2, 3]);
+    assert_eq!(Maker::unbox_mut_window(&mut w).bytes(), &[2, 3]);
+}
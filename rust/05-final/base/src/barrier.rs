@@ -0,0 +1,74 @@
+use crate::sync::Mutex;
+use alloc::vec::Vec;
+use core::future::{poll_fn, Future};
+use core::task::{Poll, Waker};
+
+/// A rendezvous point for a fixed number of tasks. Each call to
+/// [AsyncBarrier::wait] waits until `n` tasks are waiting, and then they
+/// all continue. The barrier can be used again for the next group.
+/// Dropping a waiting future still counts it as having arrived.
+pub trait AsyncBarrier {
+    /// Create a barrier for `n` tasks. A barrier for zero tasks acts
+    /// like one for one task.
+    fn new(n: usize) -> Self;
+    /// Wait for the rest of the group. Exactly one task in each group gets
+    /// `true`, which is useful when one of them has to clean up.
+    fn wait(&self) -> impl Future<Output = bool> + Send;
+}
+
+struct BarrierState {
+    arrived: usize,
+    /// Incremented each time a group is released
+    generation: u64,
+    wakers: Vec<Waker>,
+}
+
+/// An [AsyncBarrier] that doesn't depend on an executor, and works
+/// without std. Runtimes that don't have their own barrier can use it.
+pub struct StdBarrier {
+    n: usize,
+    state: Mutex<BarrierState>,
+}
+
+impl AsyncBarrier for StdBarrier {
+    fn new(n: usize) -> Self {
+        Self {
+            n: n.max(1),
+            state: Mutex::new(BarrierState {
+                arrived: 0,
+                generation: 0,
+                wakers: Vec::new(),
+            }),
+        }
+    }
+
+    async fn wait(&self) -> bool {
+        let generation = {
+            let mut state = self.state.lock();
+            state.arrived += 1;
+            if state.arrived == self.n {
+                state.arrived = 0;
+                state.generation += 1;
+                for waker in state.wakers.drain(..) {
+                    waker.wake();
+                }
+                return true;
+            }
+            state.generation
+        };
+        poll_fn(|cx| {
+            let mut state = self.state.lock();
+            if state.generation != generation {
+                return Poll::Ready(false);
+            }
+            if !state.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                state.wakers.push(cx.waker().clone());
+            }
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+/// This is an empty structure that we use as the generic type for ImplBox.
+pub struct BarrierBox;
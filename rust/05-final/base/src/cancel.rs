@@ -0,0 +1,115 @@
+use crate::sync::Mutex;
+use crate::{AsyncNotify, StdNotify};
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::future::{poll_fn, Future};
+use core::pin::pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::Poll;
+
+/// A way to tell tasks to stop what they are doing, like a Go
+/// `context.Context` without the values or deadline. Tokens form a tree:
+/// cancelling a token cancels every token derived from it with
+/// [CancelToken::child], but not its parent. Clones of a token share its
+/// state. Tokens are created with [crate::Runtime::new_cancel_token], and
+/// [run_until_cancelled] abandons a future when its token is cancelled.
+pub trait CancelToken: Send + Sync {
+    fn cancel(&self);
+    fn is_cancelled(&self) -> bool;
+    /// Wait until the token is cancelled.
+    fn cancelled(&self) -> impl Future<Output = ()> + Send;
+    /// Return a new token that is cancelled when this one is.
+    fn child(&self) -> Self
+    where
+        Self: Sized;
+}
+
+/// Run `future` until it finishes, or return `None` if `token` is
+/// cancelled first. If the token is already cancelled, `future` is never
+/// polled. This is a function rather than a method of [CancelToken] so
+/// that the returned future is `Send` whenever `future` is, which a trait
+/// method can't express.
+pub async fn run_until_cancelled<F: Future>(
+    token: &impl CancelToken,
+    future: F,
+) -> Option<F::Output> {
+    if token.is_cancelled() {
+        return None;
+    }
+    let mut future = pin!(future);
+    let mut cancelled = pin!(token.cancelled());
+    poll_fn(|cx| {
+        if let Poll::Ready(output) = future.as_mut().poll(cx) {
+            return Poll::Ready(Some(output));
+        }
+        cancelled.as_mut().poll(cx).map(|()| None)
+    })
+    .await
+}
+
+#[derive(Default)]
+struct Node {
+    cancelled: AtomicBool,
+    notify: StdNotify,
+    /// Only weak references are kept so that dropping a child frees it.
+    children: Mutex<Vec<Weak<Node>>>,
+}
+
+impl Node {
+    fn cancel(&self) {
+        // Setting the flag while holding the lock on the children ensures
+        // that a child is either in the list or is created cancelled.
+        let children = {
+            let mut children = self.children.lock();
+            if self.cancelled.swap(true, Ordering::AcqRel) {
+                return;
+            }
+            core::mem::take(&mut *children)
+        };
+        self.notify.notify_waiters();
+        for child in children.iter().filter_map(Weak::upgrade) {
+            child.cancel();
+        }
+    }
+}
+
+/// A [CancelToken] that doesn't depend on an executor, and works without
+/// std. Runtimes that don't have their own can return it from
+/// [crate::Runtime::new_cancel_token].
+#[derive(Clone, Default)]
+pub struct StdCancelToken(Arc<Node>);
+
+impl CancelToken for StdCancelToken {
+    fn cancel(&self) {
+        self.0.cancel()
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::Acquire)
+    }
+
+    async fn cancelled(&self) {
+        // The notified future is registered as soon as it is created, so
+        // the flag has to be checked after creating it.
+        let notified = self.0.notify.notified();
+        if self.is_cancelled() {
+            return;
+        }
+        notified.await;
+    }
+
+    fn child(&self) -> Self {
+        let child = Arc::new(Node::default());
+        let mut children = self.0.children.lock();
+        if self.is_cancelled() {
+            child.cancelled.store(true, Ordering::Release);
+        } else {
+            children.retain(|c| c.strong_count() > 0);
+            children.push(Arc::downgrade(&child));
+        }
+        StdCancelToken(child)
+    }
+}
+
+/// This is an empty structure that we use as the generic type for ImplBox.
+pub struct CancelTokenBox;
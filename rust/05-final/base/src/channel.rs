@@ -0,0 +1,274 @@
+use crate::sync::Mutex;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::future::{poll_fn, Future};
+use core::marker::PhantomData;
+use core::task::{Poll, Waker};
+#[cfg(feature = "std")]
+use std::error::Error;
+
+/// Why [AsyncChannel::send] or [AsyncChannel::try_send] failed. Each
+/// variant holds the item that wasn't sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SendError<T> {
+    /// The channel is bounded and has no room. Only
+    /// [AsyncChannel::try_send] returns this.
+    Full(T),
+    Closed(T),
+}
+
+impl<T> SendError<T> {
+    /// Return the item that wasn't sent.
+    pub fn into_inner(self) -> T {
+        match self {
+            SendError::Full(item) | SendError::Closed(item) => item,
+        }
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::Full(_) => write!(f, "channel is full"),
+            SendError::Closed(_) => write!(f, "channel is closed"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: fmt::Debug> Error for SendError<T> {}
+
+/// Why [AsyncChannel::try_recv] returned no item
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// Nothing is available now, but more may be sent.
+    Empty,
+    /// The channel is closed, and every item has been received.
+    Closed,
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => write!(f, "channel is empty"),
+            TryRecvError::Closed => write!(f, "channel is closed"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for TryRecvError {}
+
+/// A multi-producer, single-consumer queue. Producers share the channel,
+/// usually through an [implbox::ImplBoxShared], and call
+/// [AsyncChannel::send]. Items are received in the order they were sent.
+/// If more than one task receives, they take turns. After
+/// [AsyncChannel::close], sending fails, and receiving returns the items
+/// that were already sent and then `None`. None of the functions may
+/// block the thread.
+pub trait AsyncChannel<T> {
+    /// Create a channel that holds at most `capacity` items, or any number
+    /// if `capacity` is `None`.
+    fn new(capacity: Option<usize>) -> Self;
+    /// Send `item`, waiting for room if the channel is full.
+    fn send(&self, item: T) -> impl Future<Output = Result<(), SendError<T>>> + Send;
+    /// Send `item` if there is room without waiting.
+    fn try_send(&self, item: T) -> Result<(), SendError<T>>;
+    /// Wait for the next item. Return `None` if the channel is closed and
+    /// empty.
+    fn recv(&self) -> impl Future<Output = Option<T>> + Send;
+    /// Return the next item if one is available without waiting. This
+    /// returns [TryRecvError::Empty] if another task is waiting in
+    /// [AsyncChannel::recv], since the next item will go to that task.
+    fn try_recv(&self) -> Result<T, TryRecvError>;
+    /// Stop accepting items. Items that were already sent can still be
+    /// received.
+    fn close(&self);
+    fn is_closed(&self) -> bool;
+}
+
+/// This is an empty structure that we use as the generic type for ImplBox.
+/// A channel can be shared by any number of tasks as long as its items
+/// can be sent between threads, which is the same as for a mutex.
+pub struct ChannelBox<T>(PhantomData<Mutex<T>>);
+
+/// Why [BroadcastReceiver::recv] or [WatchReceiver::changed] returned no
+/// item
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecvError {
+    /// The receiver fell so far behind that the given number of items
+    /// were dropped before it received them. It can keep receiving from
+    /// the oldest item that is still kept.
+    Lagged(u64),
+    /// The sending side was dropped, and every item has been received.
+    Closed,
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecvError::Lagged(n) => write!(f, "receiver lagged by {n} items"),
+            RecvError::Closed => write!(f, "channel is closed"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for RecvError {}
+
+/// A channel that delivers every item to every receiver. Receivers are
+/// created with [crate::Locker::new_broadcast_receiver] and only see items
+/// sent after they were created. The channel keeps the most recent
+/// `capacity` items for receivers that haven't received them yet. A
+/// receiver that falls further behind loses the oldest ones, which it
+/// learns from [RecvError::Lagged]. `capacity` must be at least 1.
+/// Receivers see that the channel is closed once it is dropped.
+pub trait Broadcast<T: Clone> {
+    fn new(capacity: usize) -> Self;
+    /// Send `item` to every receiver, and return the number of receivers.
+    /// Sending never waits, and sending with no receivers is not an error.
+    fn send(&self, item: T) -> usize;
+    fn receiver_count(&self) -> usize;
+}
+
+pub trait BroadcastReceiver<T: Clone> {
+    /// Wait for the next item.
+    fn recv(&mut self) -> impl Future<Output = Result<T, RecvError>> + Send;
+    /// Return the next item, or `None` if there isn't one yet, without
+    /// waiting.
+    fn try_recv(&mut self) -> Result<Option<T>, RecvError>;
+}
+
+/// A value that many receivers can watch for changes. Unlike
+/// [Broadcast], receivers don't see every value, only the latest one.
+/// Receivers are created with [crate::Locker::new_watch_receiver].
+/// Receivers see that the watch is closed once it is dropped.
+pub trait Watch<T: Clone> {
+    fn new(initial: T) -> Self;
+    /// Replace the value and notify every receiver. This never waits.
+    fn send(&self, value: T);
+    /// Return a copy of the current value.
+    fn get(&self) -> T;
+    fn receiver_count(&self) -> usize;
+}
+
+pub trait WatchReceiver<T: Clone> {
+    /// Wait until the value is different from the last one this receiver
+    /// saw, and mark the new value as seen. Only [RecvError::Closed] is
+    /// returned, once the watch is dropped and the latest value was seen.
+    fn changed(&mut self) -> impl Future<Output = Result<(), RecvError>> + Send;
+    /// Return a copy of the current value, and mark it as seen.
+    fn get(&mut self) -> T;
+}
+
+struct WatchState<T> {
+    value: T,
+    /// Incremented by each send
+    version: u64,
+    closed: bool,
+    receivers: usize,
+    wakers: Vec<Waker>,
+}
+
+/// A [Watch] that doesn't depend on an executor, and works without std.
+/// Runtimes that don't have their own watch channel can use it.
+/// Receivers are created with [StdWatch::subscribe].
+pub struct StdWatch<T>(Arc<Mutex<WatchState<T>>>);
+
+impl<T> StdWatch<T> {
+    pub fn subscribe(&self) -> StdWatchReceiver<T> {
+        let mut state = self.0.lock();
+        state.receivers += 1;
+        StdWatchReceiver {
+            seen: state.version,
+            state: self.0.clone(),
+        }
+    }
+}
+
+impl<T> Drop for StdWatch<T> {
+    fn drop(&mut self) {
+        let mut state = self.0.lock();
+        state.closed = true;
+        for waker in state.wakers.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+impl<T: Clone> Watch<T> for StdWatch<T> {
+    fn new(initial: T) -> Self {
+        StdWatch(Arc::new(Mutex::new(WatchState {
+            value: initial,
+            version: 0,
+            closed: false,
+            receivers: 0,
+            wakers: Vec::new(),
+        })))
+    }
+
+    fn send(&self, value: T) {
+        let mut state = self.0.lock();
+        state.value = value;
+        state.version += 1;
+        for waker in state.wakers.drain(..) {
+            waker.wake();
+        }
+    }
+
+    fn get(&self) -> T {
+        self.0.lock().value.clone()
+    }
+
+    fn receiver_count(&self) -> usize {
+        self.0.lock().receivers
+    }
+}
+
+pub struct StdWatchReceiver<T> {
+    state: Arc<Mutex<WatchState<T>>>,
+    /// The version of the value this receiver last saw
+    seen: u64,
+}
+
+impl<T> Drop for StdWatchReceiver<T> {
+    fn drop(&mut self) {
+        self.state.lock().receivers -= 1;
+    }
+}
+
+impl<T: Clone + Send> WatchReceiver<T> for StdWatchReceiver<T> {
+    async fn changed(&mut self) -> Result<(), RecvError> {
+        poll_fn(|cx| {
+            let mut state = self.state.lock();
+            if state.version != self.seen {
+                self.seen = state.version;
+                return Poll::Ready(Ok(()));
+            }
+            if state.closed {
+                return Poll::Ready(Err(RecvError::Closed));
+            }
+            if !state.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                state.wakers.push(cx.waker().clone());
+            }
+            Poll::Pending
+        })
+        .await
+    }
+
+    fn get(&mut self) -> T {
+        let state = self.state.lock();
+        self.seen = state.version;
+        state.value.clone()
+    }
+}
+
+/// This is an empty structure that we use as the generic type for ImplBox.
+pub struct BroadcastBox<T>(PhantomData<T>);
+/// This is an empty structure that we use as the generic type for ImplBox.
+pub struct BroadcastReceiverBox<T>(PhantomData<T>);
+/// This is an empty structure that we use as the generic type for ImplBox.
+pub struct WatchBox<T>(PhantomData<T>);
+/// This is an empty structure that we use as the generic type for ImplBox.
+pub struct WatchReceiverBox<T>(PhantomData<T>);
@@ -0,0 +1,190 @@
+use crate::Elapsed;
+use std::future::{poll_fn, Future};
+use std::pin::pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+/// A source of the current time and of timers. Code that reads the time
+/// and waits through a clock instead of using [Instant::now] and the
+/// runtime's timers directly can be tested with virtual time, so tests of
+/// retries, backoff, and rate limits are fast and deterministic. Get the
+/// runtime's clock with [crate::Runtime::clock].
+pub trait Clock: Clone + Send + Sync + 'static {
+    fn now(&self) -> Instant;
+    /// Wait until `duration` has passed on this clock.
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send;
+}
+
+/// A [Clock] whose time can be moved by tests
+pub trait TestClock: Clock {
+    /// Move the clock forward by `duration`, finishing every sleep that
+    /// ends by then.
+    fn advance(&self, duration: Duration) -> impl Future<Output = ()> + Send;
+}
+
+/// A [Clock] that uses the system time and, for sleeping, the same timer
+/// as the default [crate::Runtime::timeout], so it works with any
+/// executor
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StdClock;
+
+impl Clock for StdClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send {
+        let mut timer = ParkTimer::new(duration);
+        poll_fn(move |cx| timer.poll(cx))
+    }
+}
+
+struct VirtualTime {
+    now: Instant,
+    sleepers: Vec<(Instant, Waker)>,
+}
+
+/// A [TestClock] that works with any executor. It starts at the time it
+/// is created and only moves when [TestClock::advance] is called. Clones
+/// share the same time. Tasks whose sleeps finish are woken by `advance`
+/// but run when their executor gets to them.
+#[derive(Clone)]
+pub struct VirtualClock(Arc<Mutex<VirtualTime>>);
+
+impl VirtualClock {
+    pub fn new() -> Self {
+        Self(Arc::new(Mutex::new(VirtualTime {
+            now: Instant::now(),
+            sleepers: Vec::new(),
+        })))
+    }
+}
+
+impl Default for VirtualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Instant {
+        self.0.lock().unwrap().now
+    }
+
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send {
+        let deadline = self.now() + duration;
+        let time = self.0.clone();
+        poll_fn(move |cx| {
+            let mut time = time.lock().unwrap();
+            if time.now >= deadline {
+                return Poll::Ready(());
+            }
+            // Don't pile up wakers if this is polled again before it is
+            // due.
+            time.sleepers
+                .retain(|(d, w)| *d != deadline || !w.will_wake(cx.waker()));
+            time.sleepers.push((deadline, cx.waker().clone()));
+            Poll::Pending
+        })
+    }
+}
+
+impl TestClock for VirtualClock {
+    fn advance(&self, duration: Duration) -> impl Future<Output = ()> + Send {
+        let time = self.0.clone();
+        async move {
+            let due: Vec<Waker> = {
+                let mut time = time.lock().unwrap();
+                time.now += duration;
+                let now = time.now;
+                let (due, waiting) = time.sleepers.drain(..).partition(|(d, _)| *d <= now);
+                time.sleepers = waiting;
+                due.into_iter().map(|(_, w)| w).collect()
+            };
+            for w in due {
+                w.wake();
+            }
+        }
+    }
+}
+
+struct TimerState {
+    waker: Mutex<Waker>,
+    cancelled: AtomicBool,
+}
+
+/// A timer that doesn't need a runtime. The first time it is polled, it
+/// starts a thread that parks until the deadline and then wakes the task.
+/// Dropping the timer unparks the thread so it can exit early.
+struct ParkTimer {
+    deadline: Instant,
+    thread: Option<(Arc<TimerState>, Thread)>,
+}
+
+impl ParkTimer {
+    fn new(duration: Duration) -> Self {
+        Self {
+            deadline: Instant::now() + duration,
+            thread: None,
+        }
+    }
+
+    fn poll(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if Instant::now() >= self.deadline {
+            return Poll::Ready(());
+        }
+        match &self.thread {
+            Some((state, _)) => cx.waker().clone_into(&mut state.waker.lock().unwrap()),
+            None => {
+                let state = Arc::new(TimerState {
+                    waker: Mutex::new(cx.waker().clone()),
+                    cancelled: AtomicBool::new(false),
+                });
+                let deadline = self.deadline;
+                let s = state.clone();
+                let handle = thread::spawn(move || {
+                    // park_timeout may return early, either spuriously or
+                    // because the timer was dropped.
+                    while !s.cancelled.load(Ordering::Acquire) {
+                        let now = Instant::now();
+                        if now >= deadline {
+                            s.waker.lock().unwrap().wake_by_ref();
+                            return;
+                        }
+                        thread::park_timeout(deadline - now);
+                    }
+                });
+                self.thread = Some((state, handle.thread().clone()));
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for ParkTimer {
+    fn drop(&mut self) {
+        if let Some((state, thread)) = &self.thread {
+            state.cancelled.store(true, Ordering::Release);
+            thread.unpark();
+        }
+    }
+}
+
+/// The default [crate::Runtime::timeout], which works with any executor
+pub(crate) async fn park_timeout<F: Future>(
+    duration: Duration,
+    future: F,
+) -> Result<F::Output, Elapsed> {
+    let mut future = pin!(future);
+    let mut timer = ParkTimer::new(duration);
+    poll_fn(|cx| {
+        if let Poll::Ready(output) = future.as_mut().poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        timer.poll(cx).map(|()| Err(Elapsed))
+    })
+    .await
+}
@@ -0,0 +1,58 @@
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake};
+use std::thread::{self, Thread};
+use std::time::Duration;
+
+/// Something that runs futures to completion from code that isn't async,
+/// such as a blocking wrapper around code that is generic over
+/// [crate::Runtime]. Create one with [crate::Runtime::new_executor].
+pub trait Executor: Send + Sync {
+    /// Run `future` on the current thread until it finishes. This must not
+    /// be called from a future that an executor is running.
+    fn block_on<F: Future>(&self, future: F) -> F::Output;
+    /// Stop the executor, giving work it is still running up to `timeout`
+    /// to stop. Tasks are dropped at their next await rather than run to
+    /// completion, so code that needs to finish something should wait for
+    /// it before calling this. The default does nothing, which suits
+    /// executors that don't run anything in the background.
+    fn shutdown(self, _timeout: Duration)
+    where
+        Self: Sized,
+    {
+    }
+}
+
+/// An [Executor] that works without a runtime. It polls the future on the
+/// current thread and parks the thread until the future is woken. It
+/// doesn't run spawned tasks or drive timers or I/O, so it only suits
+/// futures that other threads wake, such as those from [crate::StdBarrier].
+pub struct StdExecutor;
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+impl Executor for StdExecutor {
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        let mut future = pin!(future);
+        let waker = Arc::new(ThreadWaker(thread::current())).into();
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            // This may return spuriously, which only costs another poll.
+            thread::park();
+        }
+    }
+}
@@ -0,0 +1,1242 @@
+//! Fault injection for testing. A [Scenario] declares what should go
+//! wrong: a rate of failed requests, added latency, a dropped
+//! connection on a particular request, delayed and reordered lock
+//! wakeups, spurious timeouts, and lost channel messages. The same
+//! scenario can be applied to requests with a [FaultLayer], to any
+//! [Transport] with [FaultTransport], and to any [Runtime] with
+//! [FaultRuntime], so a failure scenario written once can run against
+//! every transport and runtime implementation. [ChaosRuntime] applies a
+//! bit of everything.
+//!
+//! Everything here is deterministic given the scenario's seed, so a
+//! failing scenario can be replayed exactly, as long as the tasks
+//! involved run in the same order. Nothing here depends on a particular
+//! async runtime. Delays are implemented by a helper thread that wakes
+//! the waiting task.
+use crate::{
+    AsyncBarrier, AsyncChannel, AsyncFile, AsyncMutex, AsyncNotify, AsyncRwLock, AsyncSemaphore,
+    AsyncTcpListener, AsyncTcpStream, AsyncUdpSocket, BarrierBox, BoxFuture, Broadcast,
+    BroadcastBox, BroadcastReceiver, BroadcastReceiverBox, CancelToken, CancelTokenBox, ChannelBox,
+    DynPermit, DynReadGuard, DynUpgradableReadGuard, DynWriteGuard, Elapsed, FileBox, JoinError,
+    JoinHandle, JoinHandleBox, LocalBoxFuture, LockBox, LockPolicy, Locker, MutexBox, NotifyBox,
+    PolicyLockBox, RecvError, Runtime, SemaphoreBox, SendError, TcpListenerBox, TcpStreamBox,
+    TryRecvError, UdpSocketBox, Watch, WatchBox, WatchReceiver, WatchReceiverBox,
+};
+use crate::{AsyncStream, ChannelStream, Method, StreamBox, Transport};
+use implbox::{ImplBox, ImplBoxShared};
+use implbox_macros::implbox_impls;
+use std::any::TypeId;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::future::Future;
+use std::io;
+use std::marker::PhantomData;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+/// How long to delay an operation
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Latency {
+    #[default]
+    None,
+    Fixed(Duration),
+    /// Uniformly distributed between the two durations
+    Uniform(Duration, Duration),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Scenario {
+    /// Seed for the random number generator used to sample error
+    /// rates and latencies
+    pub seed: u64,
+    /// Fraction of requests, from 0.0 to 1.0, that fail with
+    /// [Fault::Injected]
+    pub error_rate: f64,
+    /// Added to every request
+    pub latency: Latency,
+    /// Fail the request with this 1-based number with [Fault::Dropped]
+    pub drop_on: Option<u64>,
+    /// Added before every lock acquisition by a [FaultRuntime]
+    pub wakeup_delay: Latency,
+    /// Fraction of lock acquisitions by a [FaultRuntime], from 0.0 to
+    /// 1.0, that first let other tasks run a few times, so a task that
+    /// asked for the lock later may get it first
+    pub reorder_rate: f64,
+    /// Fraction of timeouts by a [FaultRuntime], from 0.0 to 1.0, that
+    /// fail with [Elapsed] right away, whether or not the future would
+    /// have finished in time
+    pub spurious_timeout_rate: f64,
+    /// Fraction of messages sent on a [FaultRuntime]'s channels, from
+    /// 0.0 to 1.0, that are discarded even though sending succeeds
+    pub lost_message_rate: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    Injected,
+    Dropped,
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fault::Injected => write!(f, "injected fault"),
+            Fault::Dropped => write!(f, "connection dropped"),
+        }
+    }
+}
+
+impl Error for Fault {}
+
+/// A small xorshift generator. Fault injection doesn't need good
+/// randomness, just reproducible randomness.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // xorshift gets stuck at zero.
+        Self(seed | 1)
+    }
+
+    /// Return a value in `[0, 1)`.
+    fn next(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Return true with probability `rate`.
+    fn chance(&mut self, rate: f64) -> bool {
+        rate > 0.0 && self.next() < rate
+    }
+
+    fn latency(&mut self, latency: &Latency) -> Duration {
+        match latency {
+            Latency::None => Duration::ZERO,
+            Latency::Fixed(d) => *d,
+            Latency::Uniform(lo, hi) => *lo + hi.saturating_sub(*lo).mul_f64(self.next()),
+        }
+    }
+}
+
+/// A future that completes after a delay. Another thread sleeps and
+/// then wakes the task, so this works with any executor.
+struct Delay {
+    until: Instant,
+    /// The waker from the latest poll, which the thread wakes. This is
+    /// `None` until the thread is started.
+    waker: Option<Arc<Mutex<Waker>>>,
+}
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let now = Instant::now();
+        if now >= self.until {
+            return Poll::Ready(());
+        }
+        match &self.waker {
+            // The task may have moved, so wake wherever it was polled
+            // from last.
+            Some(waker) => waker.lock().unwrap().clone_from(cx.waker()),
+            None => {
+                let waker = Arc::new(Mutex::new(cx.waker().clone()));
+                let remaining = self.until - now;
+                let thread_waker = waker.clone();
+                std::thread::spawn(move || {
+                    std::thread::sleep(remaining);
+                    thread_waker.lock().unwrap().wake_by_ref();
+                });
+                self.waker = Some(waker);
+            }
+        }
+        Poll::Pending
+    }
+}
+
+pub(crate) async fn delay(d: Duration) {
+    if !d.is_zero() {
+        Delay {
+            until: Instant::now() + d,
+            waker: None,
+        }
+        .await
+    }
+}
+
+/// Let other tasks run `n` times before continuing. Each time, the task
+/// wakes itself and returns to the executor.
+async fn yield_times(mut n: usize) {
+    std::future::poll_fn(|cx| {
+        if n == 0 {
+            return Poll::Ready(());
+        }
+        n -= 1;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    })
+    .await
+}
+
+/// Apply a [Scenario] to requests. Call [FaultLayer::inject] before
+/// each request is sent. It waits for the scenario's latency and then
+/// decides whether the request fails.
+pub struct FaultLayer {
+    scenario: Scenario,
+    count: AtomicU64,
+    rng: Mutex<Rng>,
+}
+
+impl FaultLayer {
+    pub fn new(scenario: Scenario) -> Self {
+        Self {
+            rng: Mutex::new(Rng::new(scenario.seed)),
+            count: AtomicU64::new(0),
+            scenario,
+        }
+    }
+
+    pub fn scenario(&self) -> &Scenario {
+        &self.scenario
+    }
+
+    /// The number of requests seen so far
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    pub async fn inject(&self) -> Result<(), Fault> {
+        let n = self.count.fetch_add(1, Ordering::Relaxed) + 1;
+        let (latency, fail) = {
+            let mut rng = self.rng.lock().unwrap();
+            (
+                rng.latency(&self.scenario.latency),
+                rng.next() < self.scenario.error_rate,
+            )
+        };
+        delay(latency).await;
+        if self.scenario.drop_on == Some(n) {
+            Err(Fault::Dropped)
+        } else if fail {
+            Err(Fault::Injected)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// A [Transport] that applies a [FaultLayer] to each request before
+/// passing it to the transport it wraps, so that a scenario can be run
+/// against any transport, such as one that talks to a real server. An
+/// injected fault fails the request without sending it. Pings and
+/// streamed requests count as requests, so a scenario's faults apply to
+/// them as well.
+pub struct FaultTransport<T> {
+    inner: T,
+    faults: FaultLayer,
+}
+
+impl<T: Transport> FaultTransport<T> {
+    pub fn new(inner: T, scenario: Scenario) -> Self {
+        Self {
+            inner,
+            faults: FaultLayer::new(scenario),
+        }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn faults(&self) -> &FaultLayer {
+        &self.faults
+    }
+}
+
+impl<T: Transport + Sync> Transport for FaultTransport<T> {
+    async fn send(&self, path: &str) -> Result<String, Box<dyn Error + Sync + Send>> {
+        self.faults.inject().await?;
+        self.inner.send(path).await
+    }
+
+    async fn send_with(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&str>,
+    ) -> Result<String, Box<dyn Error + Sync + Send>> {
+        self.faults.inject().await?;
+        self.inner.send_with(method, path, body).await
+    }
+
+    #[allow(clippy::type_complexity)]
+    async fn stream(
+        &self,
+        path: String,
+    ) -> Result<
+        impl AsyncStream<Result<Vec<u8>, Box<dyn Error + Sync + Send>>> + Send + 'static,
+        Box<dyn Error + Sync + Send>,
+    > {
+        self.faults.inject().await?;
+        self.inner.stream(path).await
+    }
+
+    async fn ping(&self) -> Result<(), Box<dyn Error + Sync + Send>> {
+        self.faults.inject().await?;
+        self.inner.ping().await
+    }
+}
+
+/// Supplies the scenario for a [FaultRuntime]. Runtimes are types, not
+/// values, so the scenario is attached to a type as well.
+pub trait Faults {
+    fn scenario() -> Scenario;
+}
+
+/// A [Runtime] that wraps another runtime and injects the scenario's
+/// runtime faults: every lock acquisition is delayed by `wakeup_delay`
+/// and may be reordered, timeouts may expire early, and channel messages
+/// may be lost. This shakes out code that only works because locks are
+/// usually acquired immediately and in order, or that doesn't handle
+/// timeouts and lost messages the way it should.
+pub struct FaultRuntime<R, F>(PhantomData<(R, F)>);
+
+/// A [FaultRuntime] with [Chaos], which injects a little of every
+/// runtime fault. Use `ChaosRuntime<R, F>` with your own [Faults] to
+/// choose how much.
+pub type ChaosRuntime<R, F = Chaos> = FaultRuntime<R, F>;
+
+/// The scenario for [ChaosRuntime]: short wakeup delays, with a quarter
+/// of lock acquisitions reordered, 5% of timeouts expiring early, and 1%
+/// of channel messages lost. The seed is taken from the `CHAOS_SEED`
+/// environment variable, or is 0 if it isn't set or isn't a number, so a
+/// failing run can be replayed.
+pub struct Chaos;
+
+impl Faults for Chaos {
+    fn scenario() -> Scenario {
+        Scenario {
+            seed: std::env::var("CHAOS_SEED")
+                .ok()
+                .and_then(|seed| seed.parse().ok())
+                .unwrap_or(0),
+            wakeup_delay: Latency::Uniform(Duration::ZERO, Duration::from_millis(1)),
+            reorder_rate: 0.25,
+            spurious_timeout_rate: 0.05,
+            lost_message_rate: 0.01,
+            ..Default::default()
+        }
+    }
+}
+
+/// The runtime faults for one object created by a [FaultRuntime]. Each
+/// has its own random number generator, so what happens to one object
+/// doesn't depend on how others are used.
+struct Injector {
+    delay: Latency,
+    reorder_rate: f64,
+    spurious_timeout_rate: f64,
+    lost_message_rate: f64,
+    rng: Mutex<Rng>,
+}
+
+impl Injector {
+    fn new(scenario: Scenario) -> Self {
+        Self {
+            delay: scenario.wakeup_delay,
+            reorder_rate: scenario.reorder_rate,
+            spurious_timeout_rate: scenario.spurious_timeout_rate,
+            lost_message_rate: scenario.lost_message_rate,
+            rng: Mutex::new(Rng::new(scenario.seed)),
+        }
+    }
+
+    /// Wait before a lock acquisition or other wakeup.
+    fn wait(&self) -> impl Future<Output = ()> + Send {
+        let (d, yields) = {
+            let mut rng = self.rng.lock().unwrap();
+            let d = rng.latency(&self.delay);
+            let yields = if rng.chance(self.reorder_rate) {
+                1 + (rng.next() * 4.0) as usize
+            } else {
+                0
+            };
+            (d, yields)
+        };
+        async move {
+            delay(d).await;
+            yield_times(yields).await;
+        }
+    }
+
+    fn spurious_timeout(&self) -> bool {
+        self.rng.lock().unwrap().chance(self.spurious_timeout_rate)
+    }
+
+    fn lose_message(&self) -> bool {
+        self.rng.lock().unwrap().chance(self.lost_message_rate)
+    }
+}
+
+/// One generator per [Faults] type for [FaultRuntime::timeout], which
+/// has no object to keep it in
+static TIMEOUT_RNGS: Mutex<BTreeMap<TypeId, Rng>> = Mutex::new(BTreeMap::new());
+
+fn spurious_timeout<F: Faults + 'static>() -> bool {
+    let scenario = F::scenario();
+    if scenario.spurious_timeout_rate <= 0.0 {
+        return false;
+    }
+    TIMEOUT_RNGS
+        .lock()
+        .unwrap()
+        .entry(TypeId::of::<F>())
+        .or_insert_with(|| Rng::new(scenario.seed))
+        .chance(scenario.spurious_timeout_rate)
+}
+
+/// A lock from the inner runtime, stored in an [ImplBox] since its
+/// type can't be named
+pub struct FaultLock<T, R> {
+    inner: ImplBox<PolicyLockBox<T>>,
+    faults: Injector,
+    _r: PhantomData<fn() -> R>,
+}
+
+impl<T: Sync + Send + 'static, R: Runtime> FaultLock<T, R> {
+    fn with_faults<F: Faults>(policy: LockPolicy, item: T) -> Self {
+        Self {
+            inner: R::box_lock_with(policy, item),
+            faults: Injector::new(F::scenario()),
+            _r: PhantomData,
+        }
+    }
+}
+
+impl<T: Sync + Send + 'static, R: Runtime> AsyncRwLock<T> for FaultLock<T, R> {
+    // The inner lock's guards can't be named since it is boxed.
+    type ReadGuard<'a>
+        = DynReadGuard<'a, T>
+    where
+        Self: 'a;
+    type WriteGuard<'a>
+        = DynWriteGuard<'a, T>
+    where
+        Self: 'a;
+    type UpgradableReadGuard<'a>
+        = DynUpgradableReadGuard<'a, T>
+    where
+        Self: 'a;
+    type OwnedReadGuard = DynReadGuard<'static, T>;
+    type OwnedWriteGuard = DynWriteGuard<'static, T>;
+
+    fn new(item: T) -> Self {
+        Self {
+            inner: R::box_lock_with(LockPolicy::default(), item),
+            faults: Injector::new(Scenario::default()),
+            _r: PhantomData,
+        }
+    }
+
+    async fn read(&self) -> DynReadGuard<'_, T> {
+        self.faults.wait().await;
+        DynReadGuard::new(R::unbox_lock_with(&self.inner).read().await)
+    }
+
+    async fn write(&self) -> DynWriteGuard<'_, T> {
+        self.faults.wait().await;
+        DynWriteGuard::new(R::unbox_lock_with(&self.inner).write().await)
+    }
+
+    async fn upgradable_read(&self) -> DynUpgradableReadGuard<'_, T> {
+        self.faults.wait().await;
+        DynUpgradableReadGuard::new(R::unbox_lock_with(&self.inner).upgradable_read().await)
+    }
+
+    async fn read_owned(&self) -> DynReadGuard<'static, T> {
+        self.faults.wait().await;
+        DynReadGuard::new(R::unbox_lock_with(&self.inner).read_owned().await)
+    }
+
+    async fn write_owned(&self) -> DynWriteGuard<'static, T> {
+        self.faults.wait().await;
+        DynWriteGuard::new(R::unbox_lock_with(&self.inner).write_owned().await)
+    }
+
+    // There are no delays since they need a runtime.
+    fn blocking_read(&self) -> DynReadGuard<'_, T> {
+        DynReadGuard::new(R::unbox_lock_with(&self.inner).blocking_read())
+    }
+
+    fn blocking_write(&self) -> DynWriteGuard<'_, T> {
+        DynWriteGuard::new(R::unbox_lock_with(&self.inner).blocking_write())
+    }
+
+    fn try_read(&self) -> Option<DynReadGuard<'_, T>> {
+        R::unbox_lock_with(&self.inner)
+            .try_read()
+            .map(DynReadGuard::new)
+    }
+
+    // The injected delay counts toward the timeout.
+    async fn read_timeout(&self, duration: Duration) -> Result<DynReadGuard<'_, T>, Elapsed> {
+        if self.faults.spurious_timeout() {
+            return Err(Elapsed);
+        }
+        R::timeout(duration, self.read()).await
+    }
+
+    async fn write_timeout(&self, duration: Duration) -> Result<DynWriteGuard<'_, T>, Elapsed> {
+        if self.faults.spurious_timeout() {
+            return Err(Elapsed);
+        }
+        R::timeout(duration, self.write()).await
+    }
+
+    fn try_write(&self) -> Option<DynWriteGuard<'_, T>> {
+        R::unbox_lock_with(&self.inner)
+            .try_write()
+            .map(DynWriteGuard::new)
+    }
+}
+
+/// A mutex from the inner runtime, stored in an [ImplBox] since its
+/// type can't be named
+pub struct FaultMutex<T, R> {
+    inner: ImplBox<MutexBox<T>>,
+    faults: Injector,
+    _r: PhantomData<fn() -> R>,
+}
+
+impl<T: Sync + Send + 'static, R: Runtime> FaultMutex<T, R> {
+    fn with_faults<F: Faults>(item: T) -> Self {
+        Self {
+            inner: R::box_mutex(item),
+            faults: Injector::new(F::scenario()),
+            _r: PhantomData,
+        }
+    }
+}
+
+impl<T: Sync + Send + 'static, R: Runtime> AsyncMutex<T> for FaultMutex<T, R> {
+    type Guard<'a>
+        = DynWriteGuard<'a, T>
+    where
+        Self: 'a;
+
+    fn new(item: T) -> Self {
+        Self {
+            inner: R::box_mutex(item),
+            faults: Injector::new(Scenario::default()),
+            _r: PhantomData,
+        }
+    }
+
+    async fn lock(&self) -> DynWriteGuard<'_, T> {
+        self.faults.wait().await;
+        DynWriteGuard::new(R::unbox_mutex(&self.inner).lock().await)
+    }
+}
+
+/// A semaphore from the inner runtime, stored in an [ImplBox] since its
+/// type can't be named
+pub struct FaultSemaphore<R> {
+    inner: ImplBox<SemaphoreBox>,
+    faults: Injector,
+    _r: PhantomData<fn() -> R>,
+}
+
+impl<R: Runtime> FaultSemaphore<R> {
+    fn with_faults<F: Faults>(permits: usize) -> Self {
+        Self {
+            inner: R::box_semaphore(permits),
+            faults: Injector::new(F::scenario()),
+            _r: PhantomData,
+        }
+    }
+}
+
+impl<R: Runtime> AsyncSemaphore for FaultSemaphore<R> {
+    type Permit<'a>
+        = DynPermit<'a>
+    where
+        Self: 'a;
+
+    fn new(permits: usize) -> Self {
+        Self {
+            inner: R::box_semaphore(permits),
+            faults: Injector::new(Scenario::default()),
+            _r: PhantomData,
+        }
+    }
+
+    async fn acquire(&self) -> DynPermit<'_> {
+        self.faults.wait().await;
+        DynPermit::new(R::unbox_semaphore(&self.inner).acquire().await)
+    }
+
+    fn try_acquire(&self) -> Option<DynPermit<'_>> {
+        R::unbox_semaphore(&self.inner)
+            .try_acquire()
+            .map(DynPermit::new)
+    }
+
+    fn permits(&self) -> usize {
+        R::unbox_semaphore(&self.inner).permits()
+    }
+}
+
+/// A notify from the inner runtime. Waiting is delayed after the inner
+/// wait finishes.
+pub struct FaultNotify<R> {
+    inner: ImplBox<NotifyBox>,
+    faults: Injector,
+    _r: PhantomData<fn() -> R>,
+}
+
+impl<R: Runtime> FaultNotify<R> {
+    fn with_faults<F: Faults>() -> Self {
+        Self {
+            inner: R::box_notify(),
+            faults: Injector::new(F::scenario()),
+            _r: PhantomData,
+        }
+    }
+}
+
+impl<R: Runtime> AsyncNotify for FaultNotify<R> {
+    fn new() -> Self {
+        Self {
+            inner: R::box_notify(),
+            faults: Injector::new(Scenario::default()),
+            _r: PhantomData,
+        }
+    }
+
+    fn notify_one(&self) {
+        R::unbox_notify(&self.inner).notify_one()
+    }
+
+    fn notify_waiters(&self) {
+        R::unbox_notify(&self.inner).notify_waiters()
+    }
+
+    fn notified(&self) -> impl Future<Output = ()> + Send + '_ {
+        // The inner future must be created now so that it starts waiting.
+        let notified = R::unbox_notify(&self.inner).notified();
+        async move {
+            notified.await;
+            self.faults.wait().await;
+        }
+    }
+}
+
+/// A barrier from the inner runtime. Leaving the barrier is delayed.
+pub struct FaultBarrier<R> {
+    inner: ImplBox<BarrierBox>,
+    faults: Injector,
+    _r: PhantomData<fn() -> R>,
+}
+
+impl<R: Runtime> FaultBarrier<R> {
+    fn with_faults<F: Faults>(n: usize) -> Self {
+        Self {
+            inner: R::box_barrier(n),
+            faults: Injector::new(F::scenario()),
+            _r: PhantomData,
+        }
+    }
+}
+
+impl<R: Runtime> AsyncBarrier for FaultBarrier<R> {
+    fn new(n: usize) -> Self {
+        Self {
+            inner: R::box_barrier(n),
+            faults: Injector::new(Scenario::default()),
+            _r: PhantomData,
+        }
+    }
+
+    async fn wait(&self) -> bool {
+        let leader = R::unbox_barrier(&self.inner).wait().await;
+        self.faults.wait().await;
+        leader
+    }
+}
+
+/// A channel from the inner runtime, stored in an [ImplBox] since its
+/// type can't be named. Only receiving is delayed, since that is where a
+/// task waits to be woken. A lost message is discarded without waiting
+/// for room, and sending succeeds unless the channel is closed.
+pub struct FaultChannel<T, R> {
+    inner: ImplBox<ChannelBox<T>>,
+    faults: Injector,
+    _r: PhantomData<fn() -> R>,
+}
+
+impl<T: Send + 'static, R: Runtime> FaultChannel<T, R> {
+    fn with_faults<F: Faults>(capacity: Option<usize>) -> Self {
+        Self {
+            inner: R::box_channel(capacity),
+            faults: Injector::new(F::scenario()),
+            _r: PhantomData,
+        }
+    }
+
+    fn inner(&self) -> &(impl AsyncChannel<T> + '_) {
+        R::unbox_channel(&self.inner)
+    }
+
+    fn lose(&self, item: T) -> Result<(), SendError<T>> {
+        if self.inner().is_closed() {
+            Err(SendError::Closed(item))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<T: Send + 'static, R: Runtime> AsyncChannel<T> for FaultChannel<T, R> {
+    fn new(capacity: Option<usize>) -> Self {
+        Self {
+            inner: R::box_channel(capacity),
+            faults: Injector::new(Scenario::default()),
+            _r: PhantomData,
+        }
+    }
+
+    async fn send(&self, item: T) -> Result<(), SendError<T>> {
+        if self.faults.lose_message() {
+            return self.lose(item);
+        }
+        self.inner().send(item).await
+    }
+
+    fn try_send(&self, item: T) -> Result<(), SendError<T>> {
+        if self.faults.lose_message() {
+            return self.lose(item);
+        }
+        self.inner().try_send(item)
+    }
+
+    async fn recv(&self) -> Option<T> {
+        self.faults.wait().await;
+        self.inner().recv().await
+    }
+
+    fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.inner().try_recv()
+    }
+
+    fn close(&self) {
+        self.inner().close()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.inner().is_closed()
+    }
+}
+
+/// A broadcast channel from the inner runtime. Sending never waits, so
+/// only its receivers are delayed.
+pub struct FaultBroadcast<T, R> {
+    inner: ImplBox<BroadcastBox<T>>,
+    _r: PhantomData<fn() -> R>,
+}
+
+impl<T: Clone + Sync + Send + 'static, R: Runtime> Broadcast<T> for FaultBroadcast<T, R> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            inner: R::box_broadcast(capacity),
+            _r: PhantomData,
+        }
+    }
+
+    fn send(&self, item: T) -> usize {
+        R::unbox_broadcast(&self.inner).send(item)
+    }
+
+    fn receiver_count(&self) -> usize {
+        R::unbox_broadcast(&self.inner).receiver_count()
+    }
+}
+
+pub struct FaultBroadcastReceiver<T, R> {
+    inner: ImplBox<BroadcastReceiverBox<T>>,
+    faults: Injector,
+    _r: PhantomData<fn() -> R>,
+}
+
+impl<T: Clone + Sync + Send + 'static, R: Runtime> FaultBroadcastReceiver<T, R> {
+    fn with_faults<F: Faults>(broadcast: &FaultBroadcast<T, R>) -> Self {
+        Self {
+            inner: R::box_broadcast_receiver(&broadcast.inner),
+            faults: Injector::new(F::scenario()),
+            _r: PhantomData,
+        }
+    }
+}
+
+impl<T: Clone + Sync + Send + 'static, R: Runtime> BroadcastReceiver<T>
+    for FaultBroadcastReceiver<T, R>
+{
+    async fn recv(&mut self) -> Result<T, RecvError> {
+        self.faults.wait().await;
+        R::unbox_mut_broadcast_receiver(&mut self.inner)
+            .recv()
+            .await
+    }
+
+    fn try_recv(&mut self) -> Result<Option<T>, RecvError> {
+        R::unbox_mut_broadcast_receiver(&mut self.inner).try_recv()
+    }
+}
+
+/// A watch from the inner runtime. As with [FaultBroadcast], only its
+/// receivers are delayed.
+pub struct FaultWatch<T, R> {
+    inner: ImplBox<WatchBox<T>>,
+    _r: PhantomData<fn() -> R>,
+}
+
+impl<T: Clone + Sync + Send + 'static, R: Runtime> Watch<T> for FaultWatch<T, R> {
+    fn new(initial: T) -> Self {
+        Self {
+            inner: R::box_watch(initial),
+            _r: PhantomData,
+        }
+    }
+
+    fn send(&self, value: T) {
+        R::unbox_watch(&self.inner).send(value)
+    }
+
+    fn get(&self) -> T {
+        R::unbox_watch(&self.inner).get()
+    }
+
+    fn receiver_count(&self) -> usize {
+        R::unbox_watch(&self.inner).receiver_count()
+    }
+}
+
+pub struct FaultWatchReceiver<T, R> {
+    inner: ImplBox<WatchReceiverBox<T>>,
+    faults: Injector,
+    _r: PhantomData<fn() -> R>,
+}
+
+impl<T: Clone + Sync + Send + 'static, R: Runtime> FaultWatchReceiver<T, R> {
+    fn with_faults<F: Faults>(watch: &FaultWatch<T, R>) -> Self {
+        Self {
+            inner: R::box_watch_receiver(&watch.inner),
+            faults: Injector::new(F::scenario()),
+            _r: PhantomData,
+        }
+    }
+}
+
+impl<T: Clone + Sync + Send + 'static, R: Runtime> WatchReceiver<T> for FaultWatchReceiver<T, R> {
+    async fn changed(&mut self) -> Result<(), RecvError> {
+        self.faults.wait().await;
+        R::unbox_mut_watch_receiver(&mut self.inner).changed().await
+    }
+
+    fn get(&mut self) -> T {
+        R::unbox_mut_watch_receiver(&mut self.inner).get()
+    }
+}
+
+/// A task spawned on the inner runtime. Joining is delayed, since that is
+/// where a task waits to be woken. `local` says whether the handle came
+/// from `spawn_local`, since the inner runtime's handles from `spawn` and
+/// `spawn_local` are boxed separately.
+pub struct FaultJoinHandle<T, R> {
+    inner: ImplBox<JoinHandleBox<T>>,
+    local: bool,
+    faults: Injector,
+    _r: PhantomData<fn() -> R>,
+}
+
+impl<T: Send + 'static, R: Runtime> JoinHandle<T> for FaultJoinHandle<T, R> {
+    async fn join(&mut self) -> Result<T, JoinError> {
+        self.faults.wait().await;
+        if self.local {
+            R::unbox_mut_local_task(&mut self.inner).join().await
+        } else {
+            R::unbox_mut_task(&mut self.inner).join().await
+        }
+    }
+
+    fn abort(&self) {
+        if self.local {
+            R::unbox_local_task(&self.inner).abort()
+        } else {
+            R::unbox_task(&self.inner).abort()
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        if self.local {
+            R::unbox_local_task(&self.inner).is_finished()
+        } else {
+            R::unbox_task(&self.inner).is_finished()
+        }
+    }
+}
+
+/// A cancel token from the inner runtime. Waiting for cancellation is
+/// delayed like any other wakeup.
+pub struct FaultCancelToken<R> {
+    inner: ImplBox<CancelTokenBox>,
+    faults: Arc<Injector>,
+    _r: PhantomData<fn() -> R>,
+}
+
+impl<R: Runtime> Clone for FaultCancelToken<R> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.try_clone().expect("cancel tokens can be cloned"),
+            faults: self.faults.clone(),
+            _r: PhantomData,
+        }
+    }
+}
+
+impl<R: Runtime> CancelToken for FaultCancelToken<R> {
+    fn cancel(&self) {
+        R::unbox_cancel_token(&self.inner).cancel()
+    }
+
+    fn is_cancelled(&self) -> bool {
+        R::unbox_cancel_token(&self.inner).is_cancelled()
+    }
+
+    async fn cancelled(&self) {
+        R::unbox_cancel_token(&self.inner).cancelled().await;
+        self.faults.wait().await;
+    }
+
+    fn child(&self) -> Self {
+        Self {
+            inner: R::box_cancel_token(Some(&self.inner)),
+            faults: self.faults.clone(),
+            _r: PhantomData,
+        }
+    }
+}
+
+/// A TCP stream whose reads are delayed. `S` is a [BoxedTcpStream] for a
+/// connection or the inner listener's stream for an accepted one.
+pub struct FaultTcpStream<S> {
+    inner: S,
+    faults: Injector,
+}
+
+impl<S: AsyncTcpStream + Send> AsyncTcpStream for FaultTcpStream<S> {
+    async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.faults.wait().await;
+        self.inner.read(buf).await
+    }
+
+    async fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf).await
+    }
+
+    async fn shutdown(&mut self) -> io::Result<()> {
+        self.inner.shutdown().await
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+}
+
+/// A TCP stream from the inner runtime, stored in an [ImplBox] since its
+/// type can't be named
+pub struct BoxedTcpStream<R> {
+    inner: ImplBox<TcpStreamBox>,
+    _r: PhantomData<fn() -> R>,
+}
+
+impl<R: Runtime> AsyncTcpStream for BoxedTcpStream<R> {
+    async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        R::unbox_mut_tcp_stream(&mut self.inner).read(buf).await
+    }
+
+    async fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        R::unbox_mut_tcp_stream(&mut self.inner).write(buf).await
+    }
+
+    async fn shutdown(&mut self) -> io::Result<()> {
+        R::unbox_mut_tcp_stream(&mut self.inner).shutdown().await
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        R::unbox_tcp_stream(&self.inner).local_addr()
+    }
+
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        R::unbox_tcp_stream(&self.inner).peer_addr()
+    }
+}
+
+/// A TCP listener from the inner runtime. Each accepted stream gets its
+/// own delays from the scenario.
+pub struct FaultTcpListener<R> {
+    inner: ImplBox<TcpListenerBox>,
+    scenario: Scenario,
+    _r: PhantomData<fn() -> R>,
+}
+
+impl<R: Runtime> AsyncTcpListener for FaultTcpListener<R> {
+    async fn accept(
+        &self,
+    ) -> io::Result<(impl AsyncTcpStream + Send + Sync + 'static, SocketAddr)> {
+        let (stream, addr) = R::unbox_tcp_listener(&self.inner).accept().await?;
+        let stream = FaultTcpStream {
+            inner: stream,
+            faults: Injector::new(self.scenario.clone()),
+        };
+        Ok((stream, addr))
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        R::unbox_tcp_listener(&self.inner).local_addr()
+    }
+}
+
+/// A UDP socket whose receives are delayed
+pub struct FaultUdpSocket<R> {
+    inner: ImplBox<UdpSocketBox>,
+    faults: Injector,
+    _r: PhantomData<fn() -> R>,
+}
+
+impl<R: Runtime> AsyncUdpSocket for FaultUdpSocket<R> {
+    async fn send_to(&self, buf: &[u8], target: SocketAddr) -> io::Result<usize> {
+        R::unbox_udp_socket(&self.inner).send_to(buf, target).await
+    }
+
+    async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.faults.wait().await;
+        R::unbox_udp_socket(&self.inner).recv_from(buf).await
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        R::unbox_udp_socket(&self.inner).local_addr()
+    }
+}
+
+/// A file whose reads are delayed
+pub struct FaultFile<R> {
+    inner: ImplBox<FileBox>,
+    faults: Injector,
+    _r: PhantomData<fn() -> R>,
+}
+
+impl<R: Runtime> AsyncFile for FaultFile<R> {
+    async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.faults.wait().await;
+        R::unbox_mut_file(&mut self.inner).read(buf).await
+    }
+
+    async fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        R::unbox_mut_file(&mut self.inner).write(buf).await
+    }
+
+    async fn sync(&mut self) -> io::Result<()> {
+        R::unbox_mut_file(&mut self.inner).sync().await
+    }
+}
+
+impl<R: Runtime + 'static, F: Faults + 'static> Locker for FaultRuntime<R, F> {
+    #[implbox_impls(LockBox<T>, FaultLock<T, R>, downcast)]
+    fn new_lock<T: Sync + Send + 'static>(item: T) -> impl AsyncRwLock<T> {
+        FaultLock::<T, R>::with_faults::<F>(LockPolicy::default(), item)
+    }
+
+    #[implbox_impls(PolicyLockBox<T>, FaultLock<T, R>, downcast)]
+    fn new_lock_with<T: Sync + Send + 'static>(policy: LockPolicy, item: T) -> impl AsyncRwLock<T> {
+        FaultLock::<T, R>::with_faults::<F>(policy, item)
+    }
+
+    #[implbox_impls(MutexBox<T>, FaultMutex<T, R>, downcast)]
+    fn new_mutex<T: Sync + Send + 'static>(item: T) -> impl AsyncMutex<T> {
+        FaultMutex::<T, R>::with_faults::<F>(item)
+    }
+
+    #[implbox_impls(SemaphoreBox, FaultSemaphore<R>, downcast)]
+    fn new_semaphore(permits: usize) -> impl AsyncSemaphore {
+        FaultSemaphore::<R>::with_faults::<F>(permits)
+    }
+
+    #[implbox_impls(NotifyBox, FaultNotify<R>, downcast)]
+    fn new_notify() -> impl AsyncNotify {
+        FaultNotify::<R>::with_faults::<F>()
+    }
+
+    #[implbox_impls(BarrierBox, FaultBarrier<R>, downcast)]
+    fn new_barrier(n: usize) -> impl AsyncBarrier {
+        FaultBarrier::<R>::with_faults::<F>(n)
+    }
+
+    #[implbox_impls(ChannelBox<T>, FaultChannel<T, R>, downcast)]
+    fn new_channel<T: Send + 'static>(capacity: Option<usize>) -> impl AsyncChannel<T> {
+        FaultChannel::<T, R>::with_faults::<F>(capacity)
+    }
+
+    #[implbox_impls(BroadcastBox<T>, FaultBroadcast<T, R>, downcast)]
+    fn new_broadcast<T: Clone + Sync + Send + 'static>(capacity: usize) -> impl Broadcast<T> {
+        FaultBroadcast::<T, R>::new(capacity)
+    }
+
+    #[implbox_impls(BroadcastReceiverBox<T>, FaultBroadcastReceiver<T, R>, downcast)]
+    fn new_broadcast_receiver<T: Clone + Sync + Send + 'static>(
+        broadcast: &ImplBox<BroadcastBox<T>>,
+    ) -> impl BroadcastReceiver<T> {
+        let broadcast = broadcast
+            .downcast_ref::<FaultBroadcast<T, R>>()
+            .expect("broadcast was not created by this FaultRuntime");
+        FaultBroadcastReceiver::<T, R>::with_faults::<F>(broadcast)
+    }
+
+    #[implbox_impls(WatchBox<T>, FaultWatch<T, R>, downcast)]
+    fn new_watch<T: Clone + Sync + Send + 'static>(initial: T) -> impl Watch<T> {
+        FaultWatch::<T, R>::new(initial)
+    }
+
+    #[implbox_impls(WatchReceiverBox<T>, FaultWatchReceiver<T, R>, downcast)]
+    fn new_watch_receiver<T: Clone + Sync + Send + 'static>(
+        watch: &ImplBox<WatchBox<T>>,
+    ) -> impl WatchReceiver<T> {
+        let watch = watch
+            .downcast_ref::<FaultWatch<T, R>>()
+            .expect("watch was not created by this FaultRuntime");
+        FaultWatchReceiver::<T, R>::with_faults::<F>(watch)
+    }
+
+    /// The stream receives from the [FaultChannel], so it gets that
+    /// channel's delays.
+    #[implbox_impls(StreamBox<T>, ChannelStream<T, FaultRuntime<R, F>>, downcast)]
+    fn new_stream<T: Send + 'static>(channel: ImplBoxShared<ChannelBox<T>>) -> impl AsyncStream<T> {
+        ChannelStream::<T, Self>::new(channel)
+    }
+}
+
+impl<R: Runtime + 'static, F: Faults + 'static> Runtime for FaultRuntime<R, F> {
+    type Executor = R::Executor;
+    type Clock = R::Clock;
+
+    fn new_executor() -> io::Result<Self::Executor> {
+        R::new_executor()
+    }
+
+    fn clock() -> R::Clock {
+        R::clock()
+    }
+
+    #[implbox_impls(JoinHandleBox<T>, FaultJoinHandle<T, R>, downcast, name = "task")]
+    fn spawn<T: Send + 'static>(future: BoxFuture<'static, T>) -> impl JoinHandle<T> {
+        FaultJoinHandle::<T, R> {
+            inner: R::box_task(future),
+            local: false,
+            faults: Injector::new(F::scenario()),
+            _r: PhantomData,
+        }
+    }
+
+    #[implbox_impls(JoinHandleBox<T>, FaultJoinHandle<T, R>, downcast, name = "local_task")]
+    fn spawn_local<T: Send + 'static>(future: LocalBoxFuture<'static, T>) -> impl JoinHandle<T> {
+        FaultJoinHandle::<T, R> {
+            inner: R::box_local_task(future),
+            local: true,
+            faults: Injector::new(F::scenario()),
+            _r: PhantomData,
+        }
+    }
+
+    #[implbox_impls(CancelTokenBox, FaultCancelToken<R>, downcast)]
+    fn new_cancel_token(parent: Option<&ImplBox<CancelTokenBox>>) -> impl CancelToken + Clone {
+        match parent {
+            Some(parent) => parent
+                .downcast_ref::<FaultCancelToken<R>>()
+                .expect("cancel token was not created by this FaultRuntime")
+                .child(),
+            None => FaultCancelToken::<R> {
+                inner: R::box_cancel_token(None),
+                faults: Arc::new(Injector::new(F::scenario())),
+                _r: PhantomData,
+            },
+        }
+    }
+
+    #[implbox_impls(TcpStreamBox, FaultTcpStream<BoxedTcpStream<R>>, downcast, name = "tcp_stream")]
+    async fn connect_tcp(addr: SocketAddr) -> Result<impl AsyncTcpStream, io::Error> {
+        let inner = BoxedTcpStream::<R> {
+            inner: R::box_tcp_stream(addr).await?,
+            _r: PhantomData,
+        };
+        Ok(FaultTcpStream {
+            inner,
+            faults: Injector::new(F::scenario()),
+        })
+    }
+
+    #[implbox_impls(TcpListenerBox, FaultTcpListener<R>, downcast, name = "tcp_listener")]
+    async fn bind_tcp(addr: SocketAddr) -> Result<impl AsyncTcpListener, io::Error> {
+        Ok(FaultTcpListener::<R> {
+            inner: R::box_tcp_listener(addr).await?,
+            scenario: F::scenario(),
+            _r: PhantomData,
+        })
+    }
+
+    #[implbox_impls(UdpSocketBox, FaultUdpSocket<R>, downcast, name = "udp_socket")]
+    async fn bind_udp(addr: SocketAddr) -> Result<impl AsyncUdpSocket, io::Error> {
+        Ok(FaultUdpSocket::<R> {
+            inner: R::box_udp_socket(addr).await?,
+            faults: Injector::new(F::scenario()),
+            _r: PhantomData,
+        })
+    }
+
+    #[implbox_impls(FileBox, FaultFile<R>, downcast, name = "file")]
+    async fn open_file(path: &Path, options: &OpenOptions) -> Result<impl AsyncFile, io::Error> {
+        Ok(FaultFile::<R> {
+            inner: R::box_file(path, options).await?,
+            faults: Injector::new(F::scenario()),
+            _r: PhantomData,
+        })
+    }
+
+    fn timeout<Fut: Future + Send>(
+        duration: Duration,
+        future: Fut,
+    ) -> impl Future<Output = Result<Fut::Output, Elapsed>> + Send {
+        let spurious = spurious_timeout::<F>();
+        async move {
+            if spurious {
+                return Err(Elapsed);
+            }
+            R::timeout(duration, future).await
+        }
+    }
+
+    fn shutdown_signal() -> impl Future<Output = io::Result<()>> + Send {
+        R::shutdown_signal()
+    }
+}
@@ -0,0 +1,47 @@
+use std::fs::{File, OpenOptions};
+use std::future::Future;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// An open file, created with [crate::Runtime::open_file]. Reads and
+/// writes continue from where the last one left off.
+pub trait AsyncFile {
+    /// Read into `buf` and return the number of bytes read, which is zero
+    /// at the end of the file.
+    fn read(&mut self, buf: &mut [u8]) -> impl Future<Output = io::Result<usize>> + Send;
+    /// Write from `buf` and return the number of bytes written, which may
+    /// be less than the length of `buf`.
+    fn write(&mut self, buf: &[u8]) -> impl Future<Output = io::Result<usize>> + Send;
+    /// Wait until everything written so far, including metadata, has
+    /// reached the disk.
+    fn sync(&mut self) -> impl Future<Output = io::Result<()>> + Send;
+}
+
+/// An [AsyncFile] that works with any executor. Each call does its I/O
+/// on the calling thread and blocks it until the system call returns,
+/// which is usually brief for local files. Runtimes without async file
+/// I/O can return this from [crate::Runtime::open_file].
+pub struct StdFile(File);
+
+impl StdFile {
+    pub fn open(path: &Path, options: &OpenOptions) -> io::Result<Self> {
+        Ok(StdFile(options.open(path)?))
+    }
+}
+
+impl AsyncFile for StdFile {
+    async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+
+    async fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    async fn sync(&mut self) -> io::Result<()> {
+        self.0.sync_all()
+    }
+}
+
+/// This is an empty structure that we use as the generic type for ImplBox.
+pub struct FileBox;
@@ -0,0 +1,144 @@
+use alloc::boxed::Box;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::ptr::NonNull;
+
+/// A read guard narrowed to part of the locked item, so code can hand out
+/// access to one field without exposing the rest. It works with the guard
+/// of any [crate::AsyncRwLock], including the ones that can't be named.
+/// The original guard is kept, in a box so that the part doesn't move,
+/// until this guard is dropped.
+pub struct MappedReadGuard<'a, U: ?Sized> {
+    part: NonNull<U>,
+    guard: NonNull<dyn Sync + Send + 'a>,
+    _u: PhantomData<&'a U>,
+}
+
+// SAFETY: The original guard is `Sync` and `Send`, and this only gives out
+// shared references to the part.
+unsafe impl<U: ?Sized + Sync> Send for MappedReadGuard<'_, U> {}
+unsafe impl<U: ?Sized + Sync> Sync for MappedReadGuard<'_, U> {}
+
+impl<'a, U: ?Sized> MappedReadGuard<'a, U> {
+    /// Narrow `guard` to the part of its item that `f` returns.
+    pub fn map<G>(guard: G, f: impl FnOnce(&G::Target) -> &U) -> Self
+    where
+        G: Deref + Sync + Send + 'a,
+    {
+        match Self::try_map(guard, |t| Some(f(t))) {
+            Ok(mapped) => mapped,
+            Err(_) => unreachable!(),
+        }
+    }
+
+    /// Narrow `guard` to the part of its item that `f` returns, or give
+    /// `guard` back if `f` returns `None`.
+    pub fn try_map<G>(guard: G, f: impl FnOnce(&G::Target) -> Option<&U>) -> Result<Self, G>
+    where
+        G: Deref + Sync + Send + 'a,
+    {
+        // Every reference to the part is derived from this raw pointer, so
+        // moving the mapped guard doesn't invalidate it.
+        let raw = Box::into_raw(Box::new(guard));
+        // SAFETY: `raw` came from a box that is only freed below or in drop.
+        match f(unsafe { &**raw }) {
+            Some(part) => Ok(Self {
+                part: NonNull::from(part),
+                // SAFETY: `raw` is not null.
+                guard: unsafe { NonNull::new_unchecked(raw) },
+                _u: PhantomData,
+            }),
+            // SAFETY: Nothing borrows from `raw` anymore.
+            None => Err(*unsafe { Box::from_raw(raw) }),
+        }
+    }
+}
+
+impl<U: ?Sized> Deref for MappedReadGuard<'_, U> {
+    type Target = U;
+
+    fn deref(&self) -> &U {
+        // SAFETY: The part stays valid while the original guard exists.
+        unsafe { self.part.as_ref() }
+    }
+}
+
+impl<U: ?Sized> Drop for MappedReadGuard<'_, U> {
+    fn drop(&mut self) {
+        // SAFETY: This is the box created in try_map, and the part is not
+        // used again.
+        drop(unsafe { Box::from_raw(self.guard.as_ptr()) });
+    }
+}
+
+/// A write guard narrowed to part of the locked item. See
+/// [MappedReadGuard].
+pub struct MappedWriteGuard<'a, U: ?Sized> {
+    part: NonNull<U>,
+    guard: NonNull<dyn Sync + Send + 'a>,
+    _u: PhantomData<&'a mut U>,
+}
+
+// SAFETY: The original guard is `Sync` and `Send`. Like `&mut U`, this
+// can move to another thread if `U` can, and can be shared if `U` can.
+unsafe impl<U: ?Sized + Send> Send for MappedWriteGuard<'_, U> {}
+unsafe impl<U: ?Sized + Sync> Sync for MappedWriteGuard<'_, U> {}
+
+impl<'a, U: ?Sized> MappedWriteGuard<'a, U> {
+    /// Narrow `guard` to the part of its item that `f` returns.
+    pub fn map<G>(guard: G, f: impl FnOnce(&mut G::Target) -> &mut U) -> Self
+    where
+        G: DerefMut + Sync + Send + 'a,
+    {
+        match Self::try_map(guard, |t| Some(f(t))) {
+            Ok(mapped) => mapped,
+            Err(_) => unreachable!(),
+        }
+    }
+
+    /// Narrow `guard` to the part of its item that `f` returns, or give
+    /// `guard` back if `f` returns `None`.
+    pub fn try_map<G>(guard: G, f: impl FnOnce(&mut G::Target) -> Option<&mut U>) -> Result<Self, G>
+    where
+        G: DerefMut + Sync + Send + 'a,
+    {
+        // See MappedReadGuard::try_map.
+        let raw = Box::into_raw(Box::new(guard));
+        // SAFETY: `raw` came from a box that is only freed below or in drop.
+        match f(unsafe { &mut **raw }) {
+            Some(part) => Ok(Self {
+                part: NonNull::from(part),
+                // SAFETY: `raw` is not null.
+                guard: unsafe { NonNull::new_unchecked(raw) },
+                _u: PhantomData,
+            }),
+            // SAFETY: Nothing borrows from `raw` anymore.
+            None => Err(*unsafe { Box::from_raw(raw) }),
+        }
+    }
+}
+
+impl<U: ?Sized> Deref for MappedWriteGuard<'_, U> {
+    type Target = U;
+
+    fn deref(&self) -> &U {
+        // SAFETY: The part stays valid while the original guard exists.
+        unsafe { self.part.as_ref() }
+    }
+}
+
+impl<U: ?Sized> DerefMut for MappedWriteGuard<'_, U> {
+    fn deref_mut(&mut self) -> &mut U {
+        // SAFETY: As for deref, and `&mut self` makes this the only
+        // reference.
+        unsafe { self.part.as_mut() }
+    }
+}
+
+impl<U: ?Sized> Drop for MappedWriteGuard<'_, U> {
+    fn drop(&mut self) {
+        // SAFETY: This is the box created in try_map, and the part is not
+        // used again.
+        drop(unsafe { Box::from_raw(self.guard.as_ptr()) });
+    }
+}
@@ -1,2 +1,49 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+extern crate alloc;
+
+mod barrier;
+mod cancel;
+mod channel;
+#[cfg(feature = "std")]
+mod clock;
+#[cfg(feature = "std")]
+mod executor;
+#[cfg(feature = "std")]
+mod fs;
+mod guard;
+#[cfg(feature = "std")]
+mod net;
+mod notify;
 mod runtime;
+#[cfg(feature = "std")]
+mod signal;
+mod stream;
+mod sync;
+mod task;
+mod time;
+#[cfg(feature = "std")]
+mod transport;
+pub use barrier::*;
+pub use cancel::*;
+pub use channel::*;
+#[cfg(feature = "std")]
+pub use clock::{Clock, StdClock, TestClock, VirtualClock};
+#[cfg(feature = "std")]
+pub use executor::*;
+#[cfg(feature = "std")]
+pub use fs::*;
+pub use guard::*;
+#[cfg(feature = "std")]
+pub use net::*;
+pub use notify::*;
 pub use runtime::*;
+#[cfg(feature = "std")]
+pub use signal::std_shutdown_signal;
+pub use stream::*;
+pub use task::*;
+pub use time::Elapsed;
+#[cfg(feature = "std")]
+pub use transport::*;
+#[cfg(feature = "std")]
+pub mod fault;
+pub mod trace;
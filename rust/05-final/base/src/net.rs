@@ -0,0 +1,54 @@
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+
+/// A TCP connection, created with [crate::Runtime::connect_tcp] or
+/// accepted by an [AsyncTcpListener]
+pub trait AsyncTcpStream {
+    /// Read into `buf` and return the number of bytes read, which is zero
+    /// once the peer has shut down its side of the connection.
+    fn read(&mut self, buf: &mut [u8]) -> impl Future<Output = io::Result<usize>> + Send;
+    /// Write from `buf` and return the number of bytes written, which may
+    /// be less than the length of `buf`.
+    fn write(&mut self, buf: &[u8]) -> impl Future<Output = io::Result<usize>> + Send;
+    /// Shut down the writing side of the connection. The peer sees the end
+    /// of the stream once it has read everything written before this.
+    fn shutdown(&mut self) -> impl Future<Output = io::Result<()>> + Send;
+    fn local_addr(&self) -> io::Result<SocketAddr>;
+    fn peer_addr(&self) -> io::Result<SocketAddr>;
+}
+
+/// A TCP listener, created with [crate::Runtime::bind_tcp]
+pub trait AsyncTcpListener {
+    /// Wait for a connection and return it with the peer's address.
+    fn accept(
+        &self,
+    ) -> impl Future<Output = io::Result<(impl AsyncTcpStream + Send + Sync + 'static, SocketAddr)>> + Send;
+    fn local_addr(&self) -> io::Result<SocketAddr>;
+}
+
+/// A UDP socket, created with [crate::Runtime::bind_udp]. Each datagram
+/// is sent or received whole.
+pub trait AsyncUdpSocket {
+    /// Send `buf` as one datagram to `target`, and return the number of
+    /// bytes sent.
+    fn send_to(
+        &self,
+        buf: &[u8],
+        target: SocketAddr,
+    ) -> impl Future<Output = io::Result<usize>> + Send;
+    /// Wait for a datagram, copy it into `buf`, and return its length and
+    /// sender. If the datagram doesn't fit, the rest of it is discarded.
+    fn recv_from(
+        &self,
+        buf: &mut [u8],
+    ) -> impl Future<Output = io::Result<(usize, SocketAddr)>> + Send;
+    fn local_addr(&self) -> io::Result<SocketAddr>;
+}
+
+/// This is an empty structure that we use as the generic type for ImplBox.
+pub struct TcpStreamBox;
+/// This is an empty structure that we use as the generic type for ImplBox.
+pub struct TcpListenerBox;
+/// This is an empty structure that we use as the generic type for ImplBox.
+pub struct UdpSocketBox;
@@ -0,0 +1,133 @@
+use crate::sync::Mutex;
+use crate::AsyncNotify;
+use alloc::vec::Vec;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+
+/// How a waiter was woken. A waiter woken by
+/// [AsyncNotify::notify_one] that is dropped before it finishes passes
+/// the notification on, so it isn't lost.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Woken {
+    No,
+    One,
+    All,
+}
+
+struct Waiter {
+    id: u64,
+    woken: Woken,
+    waker: Option<Waker>,
+}
+
+#[derive(Default)]
+struct NotifyState {
+    permit: bool,
+    next_id: u64,
+    /// In the order they started waiting
+    waiters: Vec<Waiter>,
+}
+
+impl NotifyState {
+    fn notify_one(&mut self) {
+        match self.waiters.iter_mut().find(|w| w.woken == Woken::No) {
+            Some(waiter) => {
+                waiter.woken = Woken::One;
+                if let Some(waker) = waiter.waker.take() {
+                    waker.wake();
+                }
+            }
+            None => self.permit = true,
+        }
+    }
+}
+
+/// An [AsyncNotify] that doesn't depend on an executor, and works without
+/// std. Runtimes that don't have their own notify can use it.
+#[derive(Default)]
+pub struct StdNotify {
+    state: Mutex<NotifyState>,
+}
+
+/// The future returned by [StdNotify::notified]. It is registered as a
+/// waiter when it is created.
+pub struct StdNotified<'a> {
+    notify: &'a StdNotify,
+    id: u64,
+    done: bool,
+}
+
+impl Future for StdNotified<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.done {
+            return Poll::Ready(());
+        }
+        let mut state = self.notify.state.lock();
+        let i = state
+            .waiters
+            .iter()
+            .position(|w| w.id == self.id)
+            .expect("waiter is registered until it finishes");
+        if state.waiters[i].woken == Woken::No && !core::mem::take(&mut state.permit) {
+            state.waiters[i].waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        state.waiters.remove(i);
+        drop(state);
+        self.done = true;
+        Poll::Ready(())
+    }
+}
+
+impl Drop for StdNotified<'_> {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+        let mut state = self.notify.state.lock();
+        if let Some(i) = state.waiters.iter().position(|w| w.id == self.id) {
+            if state.waiters.remove(i).woken == Woken::One {
+                state.notify_one();
+            }
+        }
+    }
+}
+
+impl AsyncNotify for StdNotify {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn notify_one(&self) {
+        self.state.lock().notify_one();
+    }
+
+    fn notify_waiters(&self) {
+        let mut state = self.state.lock();
+        for waiter in state.waiters.iter_mut().filter(|w| w.woken == Woken::No) {
+            waiter.woken = Woken::All;
+            if let Some(waker) = waiter.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    fn notified(&self) -> impl Future<Output = ()> + Send + '_ {
+        let mut state = self.state.lock();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.waiters.push(Waiter {
+            id,
+            woken: Woken::No,
+            waker: None,
+        });
+        StdNotified {
+            notify: self,
+            id,
+            done: false,
+        }
+    }
+}
@@ -1,28 +1,511 @@
-use implbox::ImplBox;
+use crate::{
+    AsyncBarrier, AsyncChannel, AsyncStream, BarrierBox, Broadcast, BroadcastBox,
+    BroadcastReceiver, BroadcastReceiverBox, ChannelBox, Elapsed, StreamBox, Watch, WatchBox,
+    WatchReceiver, WatchReceiverBox,
+};
+#[cfg(feature = "std")]
+use crate::{
+    AsyncFile, AsyncTcpListener, AsyncTcpStream, AsyncUdpSocket, CancelToken, CancelTokenBox,
+    Clock, Executor, FileBox, JoinHandle, JoinHandleBox, TcpListenerBox, TcpStreamBox,
+    UdpSocketBox,
+};
+use alloc::boxed::Box;
+use core::future::Future;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::pin::Pin;
+use core::time::Duration;
+use implbox::{ImplBox, ImplBoxShared};
 use implbox_macros::implbox_decls;
-use std::marker::PhantomData;
-use std::ops::{Deref, DerefMut};
+#[cfg(feature = "std")]
+use std::{fs::OpenOptions, io, net::SocketAddr, path::Path};
 
-pub trait Runtime: Locker {}
+/// Everything a component needs from an async runtime. Code that is
+/// generic over the runtime spawns background work with
+/// [Runtime::spawn] and stores the handle, boxed with `box_task`, instead
+/// of referring to a particular runtime's task API. The future is boxed
+/// so that the handle's type depends only on the output, which is what
+/// makes it possible to unbox the handle.
+///
+/// The networking constructors are async, so the functions generated for
+/// them are async functions in a public trait, which can't promise that
+/// their futures are `Send`. They are `Send` whenever the
+/// implementation's are, which is checked where a concrete runtime is
+/// used.
+///
+/// This needs std. Without it, only [Locker] is available.
+#[cfg(feature = "std")]
+#[allow(async_fn_in_trait)]
+pub trait Runtime: Locker {
+    /// The executor that [Runtime::new_executor] creates
+    type Executor: Executor;
+    /// The clock that [Runtime::clock] returns
+    type Clock: Clock;
+
+    /// Create an executor for running this runtime's futures from code
+    /// that isn't async.
+    fn new_executor() -> io::Result<Self::Executor>;
+    /// Return a clock that follows this runtime's time. Code that takes
+    /// its clock as a parameter instead can be given a [crate::VirtualClock]
+    /// in tests.
+    fn clock() -> Self::Clock;
+    /// Start running `future` in the background.
+    #[implbox_decls(JoinHandleBox<T>, name = "task")]
+    fn spawn<T: Send + 'static>(future: BoxFuture<'static, T>) -> impl JoinHandle<T>;
+    /// Start running a future that can't be sent between threads on the
+    /// current thread. The runtime may require this to be called from a
+    /// particular context; for tokio, that is inside a `LocalSet`.
+    #[implbox_decls(JoinHandleBox<T>, name = "local_task")]
+    fn spawn_local<T: Send + 'static>(future: LocalBoxFuture<'static, T>) -> impl JoinHandle<T>;
+    /// Create a token that is cancelled only by calling
+    /// [CancelToken::cancel], or, if `parent` is given, a child of that
+    /// token, which must have been created by the same implementation.
+    /// Boxed tokens can be cloned with [ImplBox::try_clone].
+    #[implbox_decls(CancelTokenBox)]
+    fn new_cancel_token(parent: Option<&ImplBox<CancelTokenBox>>) -> impl CancelToken + Clone;
+    /// Connect to `addr`.
+    #[implbox_decls(TcpStreamBox, name = "tcp_stream")]
+    async fn connect_tcp(addr: SocketAddr) -> Result<impl AsyncTcpStream, io::Error>;
+    /// Listen for connections on `addr`. Use port 0 to have the system
+    /// choose a port, and find it with [AsyncTcpListener::local_addr].
+    #[implbox_decls(TcpListenerBox, name = "tcp_listener")]
+    async fn bind_tcp(addr: SocketAddr) -> Result<impl AsyncTcpListener, io::Error>;
+    /// Create a UDP socket bound to `addr`. As with [Runtime::bind_tcp],
+    /// port 0 lets the system choose.
+    #[implbox_decls(UdpSocketBox, name = "udp_socket")]
+    async fn bind_udp(addr: SocketAddr) -> Result<impl AsyncUdpSocket, io::Error>;
+    /// Open the file at `path` as `options` says. A runtime without async
+    /// file I/O can return a [crate::StdFile].
+    #[implbox_decls(FileBox, name = "file")]
+    async fn open_file(path: &Path, options: &OpenOptions) -> Result<impl AsyncFile, io::Error>;
+    /// Wait for `future` for at most `duration`. If it doesn't finish in
+    /// time, it is dropped and [Elapsed] is returned. The default
+    /// implementation works with any executor but starts a thread for each
+    /// call that has to wait, so runtimes with their own timers should
+    /// override it.
+    fn timeout<F: Future + Send>(
+        duration: Duration,
+        future: F,
+    ) -> impl Future<Output = Result<F::Output, Elapsed>> + Send {
+        crate::clock::park_timeout(duration, future)
+    }
+    /// Wait until the process is asked to stop, by ctrl-c (SIGINT) or, on
+    /// unix, SIGTERM, so a long-running program can shut down cleanly.
+    /// Once this has been called, those signals no longer stop the process
+    /// on their own. The default implementation works with any executor
+    /// but uses a thread to wait and, on platforms other than unix,
+    /// returns an error, so runtimes that handle signals should override
+    /// it.
+    fn shutdown_signal() -> impl Future<Output = io::Result<()>> + Send {
+        crate::signal::std_shutdown_signal()
+    }
+}
 
 /// The [AsyncRwLock::read] and [AsyncRwLock::write] functions must return
 /// actual async-aware lock guards that maintain the lock until they are out of
 /// scope. They must not block the thread while holding the lock.
+///
+/// The guard types are named so that code generic over the lock can store
+/// guards in struct fields and put bounds on them. An implementation that
+/// can't name its guards, such as one that wraps a lock in an [ImplBox], can
+/// use [DynReadGuard] and [DynWriteGuard].
+///
+/// The owned guards don't borrow the lock, so they can be moved into
+/// spawned tasks. Each keeps the locked item alive until it is dropped,
+/// even if the lock itself is dropped first, so implementations keep the
+/// item behind an `Arc` or similar.
 pub trait AsyncRwLock<T> {
+    type ReadGuard<'a>: Deref<Target = T> + Sync + Send + 'a
+    where
+        Self: 'a;
+    type WriteGuard<'a>: DerefMut<Target = T> + Sync + Send + 'a
+    where
+        Self: 'a;
+    type UpgradableReadGuard<'a>: Upgradable<'a, T>
+    where
+        Self: 'a;
+    type OwnedReadGuard: Deref<Target = T> + Sync + Send + 'static;
+    type OwnedWriteGuard: DerefMut<Target = T> + Sync + Send + 'static;
+
     fn new(item: T) -> Self;
-    fn read(
+    fn read(&self) -> impl Future<Output = Self::ReadGuard<'_>> + Send;
+    fn write(&self) -> impl Future<Output = Self::WriteGuard<'_>> + Send;
+    /// Lock for reading in a way that can later be upgraded to writing with
+    /// [Upgradable::upgrade]. Only one upgradable guard exists at a time,
+    /// and no writer gets the lock while it does, so nothing read through
+    /// the guard changes before the upgrade. Plain readers can still share
+    /// the lock.
+    fn upgradable_read(&self) -> impl Future<Output = Self::UpgradableReadGuard<'_>> + Send;
+    /// Lock for reading and return a guard that doesn't borrow the lock.
+    fn read_owned(&self) -> impl Future<Output = Self::OwnedReadGuard> + Send;
+    /// Lock for writing and return a guard that doesn't borrow the lock.
+    fn write_owned(&self) -> impl Future<Output = Self::OwnedWriteGuard> + Send;
+    /// Lock for reading from code that isn't async, such as a `Drop` impl,
+    /// by blocking the thread. Implementations may panic if this is called
+    /// from async code. Not every implementation can do this, so the
+    /// default panics.
+    fn blocking_read(&self) -> Self::ReadGuard<'_> {
+        unimplemented!("blocking_read is not supported by this lock")
+    }
+    /// Lock for writing by blocking the thread. See
+    /// [AsyncRwLock::blocking_read].
+    fn blocking_write(&self) -> Self::WriteGuard<'_> {
+        unimplemented!("blocking_write is not supported by this lock")
+    }
+    /// Lock for reading if that is possible without waiting.
+    fn try_read(&self) -> Option<Self::ReadGuard<'_>>;
+    /// Lock for writing if that is possible without waiting.
+    fn try_write(&self) -> Option<Self::WriteGuard<'_>>;
+    /// Lock for reading, giving up with [Elapsed] after `duration`. The
+    /// default implementation uses the same timer as the default
+    /// [Runtime::timeout], so implementations with their own timers
+    /// should override it. Without std, there is no default.
+    #[cfg(feature = "std")]
+    fn read_timeout(
+        &self,
+        duration: Duration,
+    ) -> impl Future<Output = Result<Self::ReadGuard<'_>, Elapsed>> + Send {
+        crate::clock::park_timeout(duration, self.read())
+    }
+    #[cfg(not(feature = "std"))]
+    fn read_timeout(
+        &self,
+        duration: Duration,
+    ) -> impl Future<Output = Result<Self::ReadGuard<'_>, Elapsed>> + Send;
+    /// Lock for writing, giving up with [Elapsed] after `duration`. See
+    /// [AsyncRwLock::read_timeout].
+    #[cfg(feature = "std")]
+    fn write_timeout(
         &self,
-    ) -> impl std::future::Future<Output = impl Deref<Target = T> + Sync + Send> + Send;
-    fn write(
+        duration: Duration,
+    ) -> impl Future<Output = Result<Self::WriteGuard<'_>, Elapsed>> + Send {
+        crate::clock::park_timeout(duration, self.write())
+    }
+    #[cfg(not(feature = "std"))]
+    fn write_timeout(
         &self,
-    ) -> impl std::future::Future<Output = impl DerefMut<Target = T> + Sync + Send> + Send;
+        duration: Duration,
+    ) -> impl Future<Output = Result<Self::WriteGuard<'_>, Elapsed>> + Send;
+}
+
+/// A lock that only allows exclusive access. Use it instead of
+/// [AsyncRwLock] when every access modifies the item, since it doesn't
+/// have to keep track of readers. As with [AsyncRwLock::write],
+/// [AsyncMutex::lock] must return an actual async-aware guard that
+/// maintains the lock until it is out of scope.
+pub trait AsyncMutex<T> {
+    type Guard<'a>: DerefMut<Target = T> + Sync + Send + 'a
+    where
+        Self: 'a;
+
+    fn new(item: T) -> Self;
+    fn lock(&self) -> impl Future<Output = Self::Guard<'_>> + Send;
+}
+
+/// A counting semaphore. [AsyncSemaphore::acquire] waits until a permit
+/// is available without blocking the thread. The permit is returned to
+/// the semaphore when it is dropped.
+pub trait AsyncSemaphore {
+    type Permit<'a>: Sync + Send + 'a
+    where
+        Self: 'a;
+
+    fn new(permits: usize) -> Self;
+    fn acquire(&self) -> impl Future<Output = Self::Permit<'_>> + Send;
+    /// Return a permit if one is available without waiting.
+    fn try_acquire(&self) -> Option<Self::Permit<'_>>;
+    /// Return the number of permits that are currently available.
+    fn permits(&self) -> usize;
+}
+
+/// Wakes tasks that are waiting for something to happen, such as new
+/// work being queued, so they don't have to poll. If no task is waiting,
+/// [AsyncNotify::notify_one] stores a single permit, and the next wait
+/// finishes immediately. [AsyncNotify::notify_waiters] wakes every task
+/// that is waiting and stores nothing. The future returned by
+/// [AsyncNotify::notified] starts waiting when it is created, not when
+/// it is first polled, so create it before checking for work to avoid
+/// missing a notification that arrives in between.
+pub trait AsyncNotify {
+    fn new() -> Self;
+    fn notify_one(&self);
+    fn notify_waiters(&self);
+    fn notified(&self) -> impl Future<Output = ()> + Send + '_;
+}
+
+/// A boxed future, as returned by [DynAsyncRwLock] and passed to
+/// [Runtime::spawn]
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+/// A boxed future that can't be sent between threads, as passed to
+/// [Runtime::spawn_local]
+pub type LocalBoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
+
+/// The guard returned by [DynAsyncRwLock::read], which holds the
+/// boxed guard of the underlying lock
+pub struct DynReadGuard<'a, T>(Box<dyn Deref<Target = T> + Sync + Send + 'a>);
+
+impl<'a, T> DynReadGuard<'a, T> {
+    pub fn new(guard: impl Deref<Target = T> + Sync + Send + 'a) -> Self {
+        Self(Box::new(guard))
+    }
+}
+
+impl<T> Deref for DynReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+/// The guard returned by [DynAsyncRwLock::write], which holds the
+/// boxed guard of the underlying lock
+pub struct DynWriteGuard<'a, T>(Box<dyn DerefMut<Target = T> + Sync + Send + 'a>);
+
+impl<'a, T> DynWriteGuard<'a, T> {
+    pub fn new(guard: impl DerefMut<Target = T> + Sync + Send + 'a) -> Self {
+        Self(Box::new(guard))
+    }
+}
+
+impl<T> Deref for DynWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for DynWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+/// The guard returned by [AsyncRwLock::upgradable_read]
+pub trait Upgradable<'a, T>: Deref<Target = T> + Sync + Send + 'a {
+    type WriteGuard: DerefMut<Target = T> + Sync + Send + 'a;
+
+    /// Wait for the other readers to finish, and then lock for writing.
+    fn upgrade(self) -> impl Future<Output = Self::WriteGuard> + Send;
+}
+
+/// [Upgradable] with the upgrade boxed so that it can be a trait object
+trait DynUpgradable<'a, T>: Deref<Target = T> + Sync + Send + 'a {
+    fn upgrade(self: Box<Self>) -> BoxFuture<'a, DynWriteGuard<'a, T>>;
+}
+
+impl<'a, T, G: Upgradable<'a, T>> DynUpgradable<'a, T> for G {
+    fn upgrade(self: Box<Self>) -> BoxFuture<'a, DynWriteGuard<'a, T>> {
+        Box::pin(async move { DynWriteGuard::new(Upgradable::upgrade(*self).await) })
+    }
+}
+
+/// A boxed upgradable guard, for an [AsyncRwLock] implementation that
+/// can't name the guard type of the lock it wraps
+pub struct DynUpgradableReadGuard<'a, T>(Box<dyn DynUpgradable<'a, T, Target = T>>);
+
+impl<'a, T> DynUpgradableReadGuard<'a, T> {
+    pub fn new(guard: impl Upgradable<'a, T>) -> Self {
+        Self(Box::new(guard))
+    }
+}
+
+impl<T> Deref for DynUpgradableReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<'a, T: 'a> Upgradable<'a, T> for DynUpgradableReadGuard<'a, T> {
+    type WriteGuard = DynWriteGuard<'a, T>;
+
+    fn upgrade(self) -> impl Future<Output = DynWriteGuard<'a, T>> + Send {
+        self.0.upgrade()
+    }
+}
+
+/// A boxed semaphore permit, for an [AsyncSemaphore] implementation that
+/// can't name the permit type of the semaphore it wraps
+pub struct DynPermit<'a> {
+    // Only held so the permit is released when this is dropped
+    _permit: Box<dyn Sync + Send + 'a>,
+}
+
+impl<'a> DynPermit<'a> {
+    pub fn new(permit: impl Sync + Send + 'a) -> Self {
+        Self {
+            _permit: Box::new(permit),
+        }
+    }
+}
+
+/// A version of [AsyncRwLock] that can be used as a trait object.
+/// [AsyncRwLock] returns `impl` types, so `dyn AsyncRwLock<T>` isn't
+/// possible, which is why [ImplBox] exists. If you would rather pay
+/// for an allocation on each lock operation than use [ImplBox], store
+/// a `Box<dyn DynAsyncRwLock<T>>` instead. Every [AsyncRwLock] that is
+/// `Sync` implements this trait, so any lock can be converted with
+/// `Box::new(lock)`. If both traits are in scope, calling `read` or
+/// `write` on a concrete lock is ambiguous, so only import this one
+/// where you use trait objects.
+pub trait DynAsyncRwLock<T> {
+    fn read<'a>(&'a self) -> BoxFuture<'a, DynReadGuard<'a, T>>
+    where
+        T: 'a;
+    fn write<'a>(&'a self) -> BoxFuture<'a, DynWriteGuard<'a, T>>
+    where
+        T: 'a;
+    fn try_read<'a>(&'a self) -> Option<DynReadGuard<'a, T>>
+    where
+        T: 'a;
+    fn try_write<'a>(&'a self) -> Option<DynWriteGuard<'a, T>>
+    where
+        T: 'a;
+}
+
+impl<T, L> DynAsyncRwLock<T> for L
+where
+    L: AsyncRwLock<T> + Sync,
+{
+    fn read<'a>(&'a self) -> BoxFuture<'a, DynReadGuard<'a, T>>
+    where
+        T: 'a,
+    {
+        Box::pin(async move { DynReadGuard::new(AsyncRwLock::read(self).await) })
+    }
+
+    fn write<'a>(&'a self) -> BoxFuture<'a, DynWriteGuard<'a, T>>
+    where
+        T: 'a,
+    {
+        Box::pin(async move { DynWriteGuard::new(AsyncRwLock::write(self).await) })
+    }
+
+    fn try_read<'a>(&'a self) -> Option<DynReadGuard<'a, T>>
+    where
+        T: 'a,
+    {
+        AsyncRwLock::try_read(self).map(DynReadGuard::new)
+    }
+
+    fn try_write<'a>(&'a self) -> Option<DynWriteGuard<'a, T>>
+    where
+        T: 'a,
+    {
+        AsyncRwLock::try_write(self).map(DynWriteGuard::new)
+    }
+}
+
+/// Which side a lock lets in first when readers and writers are both
+/// waiting
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LockPreference {
+    /// A waiting writer keeps readers that arrive after it from getting
+    /// the lock, so readers can't starve writers.
+    #[default]
+    Writers,
+    /// Readers share the lock whenever no writer holds it, even if a
+    /// writer is waiting, so a steady stream of readers can starve
+    /// writers.
+    Readers,
+}
+
+/// How a lock created by [Locker::new_lock_with] schedules readers and
+/// writers. Not every lock can do everything, so this is a request, and
+/// each implementation documents which parts it honors. The default
+/// policy gives the same lock as [Locker::new_lock].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LockPolicy {
+    pub preference: LockPreference,
+    /// The most readers that can hold the lock at once. Further readers
+    /// wait, as if a writer held it. `None` leaves the limit to the
+    /// implementation.
+    pub max_readers: Option<u32>,
 }
 
 /// This is an empty structure that we use as the generic type for ImplBox.
 pub struct LockBox<T>(PhantomData<T>);
-/// This trait glues ImplBox to AsyncRwLock and enables creation of AsyncRwLocks
-/// of any type.
+/// This is an empty structure that we use as the generic type for ImplBox
+/// for locks created by [Locker::new_lock_with].
+pub struct PolicyLockBox<T>(PhantomData<T>);
+/// This is an empty structure that we use as the generic type for ImplBox.
+pub struct MutexBox<T>(PhantomData<T>);
+/// This is an empty structure that we use as the generic type for ImplBox.
+pub struct SemaphoreBox;
+/// This is an empty structure that we use as the generic type for ImplBox.
+pub struct NotifyBox;
+/// This trait glues ImplBox to the synchronization primitives and enables
+/// creation of them for items of any type.
+///
+/// Receivers for a [Broadcast] or [Watch] are created from the box that
+/// holds it, so they can be stored separately. Implementations find their
+/// own type with [ImplBox::downcast_ref], so `new_broadcast` and
+/// `new_watch` must be given the `downcast` flag, and the box must have
+/// been created by the same implementation. The receiver returned by
+/// `new_broadcast_receiver` or `new_watch_receiver` borrows the box it
+/// was created from, so use `box_broadcast_receiver` or
+/// `box_watch_receiver` for a receiver that outlives that borrow.
+///
+/// A stream is created from a shared channel, which producers keep
+/// sending to. Implementations that have nothing better can return a
+/// [crate::ChannelStream].
 pub trait Locker {
     #[implbox_decls(LockBox<T>)]
-    fn new_lock<T: Sync + Send>(item: T) -> impl AsyncRwLock<T>;
+    fn new_lock<T: Sync + Send + 'static>(item: T) -> impl AsyncRwLock<T>;
+    /// Create a lock that follows `policy` as far as the implementation
+    /// can. It may be a different type from the lock that `new_lock`
+    /// returns, so it has its own shadow type, [PolicyLockBox].
+    #[implbox_decls(PolicyLockBox<T>)]
+    fn new_lock_with<T: Sync + Send + 'static>(policy: LockPolicy, item: T) -> impl AsyncRwLock<T>;
+    #[implbox_decls(MutexBox<T>)]
+    fn new_mutex<T: Sync + Send + 'static>(item: T) -> impl AsyncMutex<T>;
+    #[implbox_decls(SemaphoreBox)]
+    fn new_semaphore(permits: usize) -> impl AsyncSemaphore;
+    #[implbox_decls(NotifyBox)]
+    fn new_notify() -> impl AsyncNotify;
+    #[implbox_decls(BarrierBox)]
+    fn new_barrier(n: usize) -> impl AsyncBarrier;
+    #[implbox_decls(ChannelBox<T>)]
+    fn new_channel<T: Send + 'static>(capacity: Option<usize>) -> impl AsyncChannel<T>;
+    #[implbox_decls(BroadcastBox<T>)]
+    fn new_broadcast<T: Clone + Sync + Send + 'static>(capacity: usize) -> impl Broadcast<T>;
+    #[implbox_decls(BroadcastReceiverBox<T>)]
+    fn new_broadcast_receiver<T: Clone + Sync + Send + 'static>(
+        broadcast: &ImplBox<BroadcastBox<T>>,
+    ) -> impl BroadcastReceiver<T>;
+    #[implbox_decls(WatchBox<T>)]
+    fn new_watch<T: Clone + Sync + Send + 'static>(initial: T) -> impl Watch<T>;
+    #[implbox_decls(WatchReceiverBox<T>)]
+    fn new_watch_receiver<T: Clone + Sync + Send + 'static>(
+        watch: &ImplBox<WatchBox<T>>,
+    ) -> impl WatchReceiver<T>;
+    #[implbox_decls(StreamBox<T>)]
+    fn new_stream<T: Send + 'static>(channel: ImplBoxShared<ChannelBox<T>>) -> impl AsyncStream<T>;
+}
+
+/// Return the number of live boxed locks and mutexes of all types in the
+/// process.
+#[cfg(feature = "accounting")]
+pub fn live_locks() -> usize {
+    count_locks(&implbox::accounting::live_boxes())
+}
+
+/// Return the number of boxed locks and mutexes in `boxes`, a snapshot
+/// from [implbox::accounting::live_boxes]. Use this to count locks in
+/// the same snapshot as other boxes.
+#[cfg(feature = "accounting")]
+pub fn count_locks(boxes: &std::collections::BTreeMap<&'static str, usize>) -> usize {
+    let prefixes = [
+        concat!(module_path!(), "::LockBox<"),
+        concat!(module_path!(), "::PolicyLockBox<"),
+        concat!(module_path!(), "::MutexBox<"),
+    ];
+    boxes
+        .iter()
+        .filter(|(name, _)| prefixes.iter().any(|prefix| name.starts_with(prefix)))
+        .map(|(_, count)| count)
+        .sum()
 }
@@ -0,0 +1,83 @@
+#[cfg(unix)]
+use signal_hook::consts::{SIGINT, SIGTERM};
+#[cfg(unix)]
+use signal_hook::iterator::{Handle, Signals};
+#[cfg(unix)]
+use std::future::poll_fn;
+use std::future::Future;
+use std::io;
+#[cfg(unix)]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(unix)]
+use std::sync::{Arc, Mutex};
+#[cfg(unix)]
+use std::task::{Poll, Waker};
+#[cfg(unix)]
+use std::thread;
+
+#[cfg(unix)]
+struct SignalState {
+    waker: Mutex<Option<Waker>>,
+    received: AtomicBool,
+}
+
+/// Stops the waiting thread when the future is dropped
+#[cfg(unix)]
+struct Unregister(Handle);
+
+#[cfg(unix)]
+impl Drop for Unregister {
+    fn drop(&mut self) {
+        self.0.close();
+    }
+}
+
+/// The default [crate::Runtime::shutdown_signal], which works with any
+/// executor. The first time it is polled, it starts a thread that waits
+/// for SIGINT or SIGTERM and then wakes the task. Dropping the future
+/// stops the thread.
+#[cfg(unix)]
+pub fn std_shutdown_signal() -> impl Future<Output = io::Result<()>> + Send {
+    let mut started: Option<(Arc<SignalState>, Unregister)> = None;
+    poll_fn(move |cx| {
+        let state = match &started {
+            Some((state, _)) => state,
+            None => {
+                let mut signals = Signals::new([SIGINT, SIGTERM])?;
+                let state = Arc::new(SignalState {
+                    waker: Mutex::new(None),
+                    received: AtomicBool::new(false),
+                });
+                let s = state.clone();
+                let handle = signals.handle();
+                thread::spawn(move || {
+                    // This ends without a signal if the handle is closed.
+                    if signals.forever().next().is_some() {
+                        s.received.store(true, Ordering::Release);
+                        if let Some(w) = s.waker.lock().unwrap().take() {
+                            w.wake();
+                        }
+                    }
+                });
+                &started.insert((state, Unregister(handle))).0
+            }
+        };
+        // Store the waker before checking so a signal in between isn't
+        // missed.
+        *state.waker.lock().unwrap() = Some(cx.waker().clone());
+        if state.received.load(Ordering::Acquire) {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    })
+}
+
+/// Without unix signals, there is nothing to wait for without a runtime.
+#[cfg(not(unix))]
+pub fn std_shutdown_signal() -> impl Future<Output = io::Result<()>> + Send {
+    std::future::ready(Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "shutdown signals need a runtime on this platform",
+    )))
+}
@@ -0,0 +1,98 @@
+use crate::sync::Mutex;
+use crate::{AsyncChannel, BoxFuture, ChannelBox, Locker};
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use core::future::Future;
+use core::marker::PhantomData;
+use implbox::ImplBoxShared;
+
+/// A sequence of items that arrive over time, like an async iterator.
+/// APIs that produce results incrementally return one of these instead
+/// of a particular runtime's stream type. Streams are created with
+/// [crate::Locker::new_stream], or with [IterStream] for items that are
+/// already available.
+pub trait AsyncStream<T> {
+    /// Wait for the next item. Return `None` once the stream has ended,
+    /// after which every call returns `None`.
+    fn next(&mut self) -> impl Future<Output = Option<T>> + Send;
+}
+
+/// A version of [AsyncStream] that can be used as a trait object, like
+/// [crate::DynAsyncRwLock] for locks. Every [AsyncStream] that is
+/// `Send` implements this trait, so any stream can be converted with
+/// `Box::new(stream)`, and the box is itself an [AsyncStream]. This
+/// lets a stream whose type can't be named, such as one returned by a
+/// trait method, be stored in a struct. If both traits are in scope,
+/// calling `next` on a box is ambiguous, so name the trait, as in
+/// `DynAsyncStream::next(&mut *stream)`.
+pub trait DynAsyncStream<T> {
+    fn next<'a>(&'a mut self) -> BoxFuture<'a, Option<T>>
+    where
+        T: 'a;
+}
+
+impl<T, S> DynAsyncStream<T> for S
+where
+    S: AsyncStream<T> + Send,
+{
+    fn next<'a>(&'a mut self) -> BoxFuture<'a, Option<T>>
+    where
+        T: 'a,
+    {
+        Box::pin(AsyncStream::next(self))
+    }
+}
+
+impl<T> AsyncStream<T> for Box<dyn DynAsyncStream<T> + Send + '_> {
+    fn next(&mut self) -> impl Future<Output = Option<T>> + Send {
+        DynAsyncStream::next(&mut **self)
+    }
+}
+
+/// A stream of the items received from a channel. It ends when the
+/// channel is closed and empty. It takes turns with any other receivers
+/// of the channel, so it only sees every item if it is the only one.
+/// This only uses [AsyncChannel], so any [Locker] can return it from
+/// [Locker::new_stream].
+pub struct ChannelStream<T, L> {
+    channel: ImplBoxShared<ChannelBox<T>>,
+    _l: PhantomData<fn() -> L>,
+}
+
+impl<T: Send + 'static, L: Locker> ChannelStream<T, L> {
+    /// `channel` must have been created by `L`.
+    pub fn new(channel: ImplBoxShared<ChannelBox<T>>) -> Self {
+        Self {
+            channel,
+            _l: PhantomData,
+        }
+    }
+}
+
+impl<T: Send + 'static, L: Locker> AsyncStream<T> for ChannelStream<T, L> {
+    fn next(&mut self) -> impl Future<Output = Option<T>> + Send {
+        L::unbox_channel(&self.channel).recv()
+    }
+}
+
+/// A stream of items that are already available, for tests and for
+/// responses that arrive all at once. Every call to
+/// [AsyncStream::next] is ready immediately.
+pub struct IterStream<T>(VecDeque<T>);
+
+impl<T> IterStream<T> {
+    pub fn new(items: impl IntoIterator<Item = T>) -> Self {
+        Self(items.into_iter().collect())
+    }
+}
+
+impl<T: Send> AsyncStream<T> for IterStream<T> {
+    async fn next(&mut self) -> Option<T> {
+        self.0.pop_front()
+    }
+}
+
+/// This is an empty structure that we use as the generic type for ImplBox.
+/// A stream only moves items out, so like a mutex, it can be shared
+/// between threads as long as the items can be sent.
+pub struct StreamBox<T>(PhantomData<Mutex<T>>);
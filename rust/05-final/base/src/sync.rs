@@ -0,0 +1,94 @@
+//! The mutex that holds the state of the executor-independent
+//! primitives, such as [crate::StdNotify] and [crate::StdWatch]. With
+//! std, it is std's Mutex. Without std, it holds a critical section
+//! while it is locked, so the program must provide a `critical-section`
+//! implementation, as embassy programs do. The state is only locked
+//! briefly and never across an await point, so either way is cheap.
+
+#[cfg(not(feature = "std"))]
+use core::cell::{RefCell, RefMut};
+#[cfg(not(feature = "std"))]
+use core::ops::{Deref, DerefMut};
+
+#[cfg(feature = "std")]
+#[derive(Default)]
+pub(crate) struct Mutex<T>(std::sync::Mutex<T>);
+
+#[cfg(feature = "std")]
+impl<T> Mutex<T> {
+    pub(crate) fn new(item: T) -> Self {
+        Self(std::sync::Mutex::new(item))
+    }
+
+    /// Lock the mutex. A panic while it was locked poisons it, and the
+    /// state may be inconsistent, so this panics too.
+    pub(crate) fn lock(&self) -> std::sync::MutexGuard<'_, T> {
+        self.0.lock().unwrap()
+    }
+}
+
+#[cfg(not(feature = "std"))]
+#[derive(Default)]
+pub(crate) struct Mutex<T>(RefCell<T>);
+
+// SAFETY: The item is only reached through a guard, which holds a
+// critical section, so no other thread or interrupt can reach it at the
+// same time.
+#[cfg(not(feature = "std"))]
+unsafe impl<T: Send> Sync for Mutex<T> {}
+
+#[cfg(not(feature = "std"))]
+impl<T> Mutex<T> {
+    pub(crate) fn new(item: T) -> Self {
+        Self(RefCell::new(item))
+    }
+
+    /// Lock the mutex. Locking it again before the guard is dropped
+    /// panics rather than deadlocking.
+    pub(crate) fn lock(&self) -> MutexGuard<'_, T> {
+        // SAFETY: The section is released when the guard is dropped.
+        // Guards can't be sent to another thread, and they are only held
+        // briefly, so sections are released in the reverse of the order
+        // they were acquired.
+        let section = Section(unsafe { critical_section::acquire() });
+        MutexGuard {
+            item: self.0.borrow_mut(),
+            _section: section,
+        }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+struct Section(critical_section::RestoreState);
+
+#[cfg(not(feature = "std"))]
+impl Drop for Section {
+    fn drop(&mut self) {
+        // SAFETY: The state came from the matching acquire.
+        unsafe { critical_section::release(self.0) }
+    }
+}
+
+/// Fields are dropped in order, so the item is released before the
+/// critical section is.
+#[cfg(not(feature = "std"))]
+pub(crate) struct MutexGuard<'a, T> {
+    item: RefMut<'a, T>,
+    _section: Section,
+}
+
+#[cfg(not(feature = "std"))]
+impl<T> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.item
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<T> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.item
+    }
+}
@@ -0,0 +1,161 @@
+use crate::sync::Mutex;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt;
+use core::future::{poll_fn, Future};
+use core::marker::PhantomData;
+use core::task::Poll;
+#[cfg(feature = "std")]
+use std::{
+    error::Error,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::atomic::{AtomicBool, Ordering},
+    sync::Arc,
+    task::{Context, Waker},
+};
+
+/// Why [JoinHandle::join] returned no output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinError {
+    /// The task was stopped with [JoinHandle::abort] before it finished.
+    Cancelled,
+    Panicked,
+}
+
+impl fmt::Display for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JoinError::Cancelled => write!(f, "task was cancelled"),
+            JoinError::Panicked => write!(f, "task panicked"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for JoinError {}
+
+/// A handle to a task started with [crate::Runtime::spawn] or
+/// [crate::Runtime::spawn_local]. Dropping the handle detaches the task;
+/// it keeps running, and its output is discarded.
+pub trait JoinHandle<T> {
+    /// Wait for the task to finish and return its output. Once this has
+    /// returned, calling it again is a bug and may panic.
+    fn join(&mut self) -> impl Future<Output = Result<T, JoinError>> + Send;
+    /// Ask the task to stop. It stops at its next await point, if it
+    /// hasn't finished already, and [JoinHandle::join] then returns
+    /// [JoinError::Cancelled].
+    fn abort(&self);
+    fn is_finished(&self) -> bool;
+}
+
+#[cfg(feature = "std")]
+#[derive(Default)]
+struct AbortState {
+    aborted: AtomicBool,
+    finished: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+}
+
+/// Controls a task wrapped by [abortable]
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub struct AbortHandle(Arc<AbortState>);
+
+#[cfg(feature = "std")]
+impl AbortHandle {
+    /// Stop the task at its next await point. See [JoinHandle::abort].
+    pub fn abort(&self) {
+        self.0.aborted.store(true, Ordering::Release);
+        if let Some(waker) = self.0.waker.lock().take() {
+            waker.wake();
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.0.finished.load(Ordering::Acquire)
+    }
+}
+
+/// The future returned by [abortable]
+#[cfg(feature = "std")]
+pub struct Abortable<F> {
+    future: F,
+    state: Arc<AbortState>,
+}
+
+#[cfg(feature = "std")]
+impl<F: Future + Unpin> Future for Abortable<F> {
+    type Output = Result<F::Output, JoinError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        if this.state.finished.load(Ordering::Acquire) {
+            panic!("Abortable polled after it finished");
+        }
+        *this.state.waker.lock() = Some(cx.waker().clone());
+        let result = if this.state.aborted.load(Ordering::Acquire) {
+            Err(JoinError::Cancelled)
+        } else {
+            match panic::catch_unwind(AssertUnwindSafe(|| Pin::new(&mut this.future).poll(cx))) {
+                Ok(Poll::Pending) => return Poll::Pending,
+                Ok(Poll::Ready(output)) => Ok(output),
+                Err(_) => Err(JoinError::Panicked),
+            }
+        };
+        this.state.finished.store(true, Ordering::Release);
+        Poll::Ready(result)
+    }
+}
+
+/// Wrap `future` so that it can be stopped with the returned
+/// [AbortHandle] and so that a panic is returned as
+/// [JoinError::Panicked] instead of unwinding. Runtimes whose task
+/// handles can't be aborted through a shared reference, or that don't
+/// report panics, spawn the wrapped future to implement [JoinHandle].
+/// This needs std to catch the panic.
+#[cfg(feature = "std")]
+pub fn abortable<F: Future + Unpin>(future: F) -> (Abortable<F>, AbortHandle) {
+    let state = Arc::new(AbortState::default());
+    let handle = AbortHandle(state.clone());
+    (Abortable { future, state }, handle)
+}
+
+/// Run `futures` concurrently on the current task and return their
+/// outputs in the same order. Unlike [crate::Runtime::spawn], the
+/// futures may borrow from the caller. Every future that hasn't
+/// finished is polled on each wakeup, so this is meant for a modest
+/// number of futures, such as a batch of requests.
+pub async fn join_all<F: Future>(futures: impl IntoIterator<Item = F>) -> Vec<F::Output> {
+    let mut futures: Vec<_> = futures.into_iter().map(|f| Some(Box::pin(f))).collect();
+    let mut outputs: Vec<Option<F::Output>> = futures.iter().map(|_| None).collect();
+    poll_fn(|cx| {
+        let mut pending = false;
+        for (slot, output) in futures.iter_mut().zip(outputs.iter_mut()) {
+            if let Some(future) = slot {
+                match future.as_mut().poll(cx) {
+                    Poll::Ready(value) => {
+                        *output = Some(value);
+                        *slot = None;
+                    }
+                    Poll::Pending => pending = true,
+                }
+            }
+        }
+        if pending {
+            return Poll::Pending;
+        }
+        Poll::Ready(
+            outputs
+                .iter_mut()
+                .map(|o| o.take().expect("finished"))
+                .collect(),
+        )
+    })
+    .await
+}
+
+/// This is an empty structure that we use as the generic type for ImplBox.
+/// A handle only moves the output out of the task, so like a mutex, it
+/// can be shared between threads as long as the output can be sent.
+pub struct JoinHandleBox<T>(PhantomData<Mutex<T>>);
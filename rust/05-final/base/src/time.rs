@@ -0,0 +1,17 @@
+use core::fmt;
+#[cfg(feature = "std")]
+use std::error::Error;
+
+/// The error returned by [crate::Runtime::timeout] when the future
+/// doesn't finish in time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed;
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "deadline has elapsed")
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for Elapsed {}
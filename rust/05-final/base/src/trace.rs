@@ -0,0 +1,77 @@
+//! These macros are used by the crates in this workspace to emit
+//! tracing spans and events. With the `tracing` feature, they expand
+//! to calls into the tracing crate. Without it, they expand to nothing
+//! (or to the wrapped future) and their arguments are not evaluated,
+//! so instrumentation has no cost when it is disabled. Each crate that
+//! uses them has a `tracing` feature that enables this one, so they
+//! are always enabled or disabled together.
+//!
+//! Span names have the form `component.operation`, such as
+//! `lock.write` or `controller.request`.
+
+#[doc(hidden)]
+#[cfg(feature = "tracing")]
+pub use tracing as __tracing;
+
+/// Returned by [trace_span] when tracing is disabled.
+#[doc(hidden)]
+pub struct NoSpan;
+
+/// Wrap a future in a debug-level span:
+/// `trace_future!(fut, "name", field = value, ...)`.
+#[cfg(feature = "tracing")]
+#[macro_export]
+macro_rules! trace_future {
+    ($fut:expr, $($span:tt)+) => {
+        $crate::trace::__tracing::Instrument::instrument(
+            $fut,
+            $crate::trace::__tracing::debug_span!($($span)+),
+        )
+    };
+}
+
+/// Wrap a future in a debug-level span:
+/// `trace_future!(fut, "name", field = value, ...)`.
+#[cfg(not(feature = "tracing"))]
+#[macro_export]
+macro_rules! trace_future {
+    ($fut:expr, $($span:tt)+) => {
+        $fut
+    };
+}
+
+/// Enter a debug-level span until the returned guard is dropped:
+/// `let _span = trace_span!("name", field = value, ...)`.
+#[cfg(feature = "tracing")]
+#[macro_export]
+macro_rules! trace_span {
+    ($($span:tt)+) => {
+        $crate::trace::__tracing::debug_span!($($span)+).entered()
+    };
+}
+
+/// Enter a debug-level span until the returned guard is dropped:
+/// `let _span = trace_span!("name", field = value, ...)`.
+#[cfg(not(feature = "tracing"))]
+#[macro_export]
+macro_rules! trace_span {
+    ($($span:tt)+) => {
+        $crate::trace::NoSpan
+    };
+}
+
+/// Emit a debug-level event with the same syntax as `tracing::debug!`.
+#[cfg(feature = "tracing")]
+#[macro_export]
+macro_rules! trace_event {
+    ($($event:tt)+) => {
+        $crate::trace::__tracing::debug!($($event)+)
+    };
+}
+
+/// Emit a debug-level event with the same syntax as `tracing::debug!`.
+#[cfg(not(feature = "tracing"))]
+#[macro_export]
+macro_rules! trace_event {
+    ($($event:tt)+) => {};
+}
@@ -0,0 +1,322 @@
+use crate::{AsyncStream, IterStream};
+use implbox::ImplBox;
+use implbox_macros::{implbox_decls, implbox_impls};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// What a request sent with [Transport::send_with] does, as in HTTP
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "UPPERCASE"))]
+pub enum Method {
+    #[default]
+    Get,
+    Post,
+    Put,
+    Delete,
+}
+
+impl Method {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Method::Get => "GET",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Delete => "DELETE",
+        }
+    }
+
+    /// Whether sending a request twice has the same effect as sending it
+    /// once, as in HTTP, where this is true of all but [Method::Post].
+    pub fn is_idempotent(self) -> bool {
+        self != Method::Post
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Sends requests to a server. A request is a path, relative to
+/// wherever the transport was configured to send, and the response is
+/// the body the server returned. An implementation returns an error for
+/// a response that indicates failure, such as an HTTP error status.
+pub trait Transport {
+    /// Send a request for `path` and return the body of the response.
+    fn send(
+        &self,
+        path: &str,
+    ) -> impl Future<Output = Result<String, Box<dyn Error + Sync + Send>>> + Send;
+
+    /// Send a request for `path` with `method` and `body`, and return
+    /// the body of the response. The default implementation, for
+    /// transports that only fetch, sends a [Method::Get] without a body
+    /// with [Transport::send] and fails for anything else.
+    fn send_with(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&str>,
+    ) -> impl Future<Output = Result<String, Box<dyn Error + Sync + Send>>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            match (method, body) {
+                (Method::Get, None) => self.send(path).await,
+                (Method::Get, Some(_)) => Err("requests with a body aren't supported".into()),
+                _ => Err(format!("{method} requests aren't supported").into()),
+            }
+        }
+    }
+
+    /// Send a request for `path` and return the body of the response as
+    /// a stream of chunks, so it can be read as it arrives. Chunks are
+    /// split wherever the transport receives them, which may be in the
+    /// middle of a UTF-8 character. An error after the response has
+    /// started is returned as the last item of the stream. The default
+    /// implementation returns the whole body from [Transport::send] as
+    /// a single chunk.
+    #[allow(clippy::type_complexity)]
+    fn stream(
+        &self,
+        path: String,
+    ) -> impl Future<
+        Output = Result<
+            impl AsyncStream<Result<Vec<u8>, Box<dyn Error + Sync + Send>>> + Send + 'static,
+            Box<dyn Error + Sync + Send>,
+        >,
+    > + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let body = self.send(&path).await?;
+            Ok(IterStream::new([Ok(body.into_bytes())]))
+        }
+    }
+
+    /// Check that the server can be reached, as cheaply as the transport
+    /// can, for health checks. The default implementation, for
+    /// transports that don't connect to anything, always succeeds.
+    fn ping(&self) -> impl Future<Output = Result<(), Box<dyn Error + Sync + Send>>> + Send {
+        async { Ok(()) }
+    }
+}
+
+/// This is an empty structure that we use as the generic type for ImplBox.
+pub struct TransportBox;
+
+/// Settings that every transport's configuration accepts, so that a
+/// component can point whichever transport it is given at a server.
+/// A transport that doesn't need a setting ignores it, which is what
+/// the default implementations do. The default configuration is what
+/// the transport uses when nothing is set.
+pub trait TransportConfig: Default {
+    /// Send requests to paths under `base_url`. Return an error if the
+    /// transport can't use the URL.
+    fn set_base_url(&mut self, base_url: &str) -> Result<(), Box<dyn Error + Sync + Send>> {
+        let _ = base_url;
+        Ok(())
+    }
+
+    /// Identify the client to the server as `user_agent`.
+    fn set_user_agent(&mut self, user_agent: &str) {
+        let _ = user_agent;
+    }
+}
+
+impl TransportConfig for () {}
+
+/// This trait glues ImplBox to a [Transport] so that a component that
+/// is generic over the connector can store the transport it creates.
+/// Whatever the transport needs to know, such as the server's address,
+/// is given to [Connector::new_transport] as the connector's `Config`.
+pub trait Connector {
+    type Config: TransportConfig;
+
+    #[implbox_decls(TransportBox)]
+    fn new_transport(config: Self::Config) -> impl Transport + Sync + Send;
+}
+
+/// A [Transport] that sends nothing and returns each path it is given
+/// as the response. It needs no configuration and is what a client
+/// uses when it isn't connected to a server, such as in examples.
+#[derive(Debug, Default, Clone)]
+pub struct LoopbackTransport;
+
+/// Every method is answered the same way, and bodies are ignored.
+impl Transport for LoopbackTransport {
+    async fn send(&self, path: &str) -> Result<String, Box<dyn Error + Sync + Send>> {
+        Ok(path.to_string())
+    }
+
+    async fn send_with(
+        &self,
+        _method: Method,
+        path: &str,
+        _body: Option<&str>,
+    ) -> Result<String, Box<dyn Error + Sync + Send>> {
+        self.send(path).await
+    }
+}
+
+impl Connector for LoopbackTransport {
+    type Config = ();
+
+    #[implbox_impls(TransportBox, LoopbackTransport, downcast)]
+    fn new_transport(_config: ()) -> impl Transport + Sync + Send {
+        LoopbackTransport
+    }
+}
+
+struct Scripted {
+    latency: Duration,
+    result: Result<String, Box<dyn Error + Sync + Send>>,
+}
+
+#[derive(Default)]
+struct MockState {
+    script: VecDeque<Scripted>,
+    requests: Vec<String>,
+    bodies: Vec<String>,
+    base_url: Option<String>,
+    user_agent: Option<String>,
+    ping_fails: bool,
+    pings: usize,
+}
+
+/// A [Transport] for tests that records every request and answers with
+/// scripted responses, in order, after a scripted latency. Once the
+/// script runs out, it answers like [LoopbackTransport]. Clones share
+/// the script and the record, so a test keeps one clone and gives
+/// another to the component under test as its [Connector::Config].
+/// The [TransportConfig] settings are recorded too.
+#[derive(Clone, Default)]
+pub struct MockTransport(Arc<Mutex<MockState>>);
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Answer a request with `result` after waiting for `latency`. The
+    /// wait uses a thread, so it works with any executor.
+    pub fn push(&self, latency: Duration, result: Result<String, Box<dyn Error + Sync + Send>>) {
+        let mut state = self.0.lock().unwrap();
+        state.script.push_back(Scripted { latency, result });
+    }
+
+    /// Answer a request with `body` right away.
+    pub fn respond(&self, body: &str) {
+        self.push(Duration::ZERO, Ok(body.to_string()));
+    }
+
+    /// Fail a request with `error` right away.
+    pub fn fail(&self, error: impl Into<Box<dyn Error + Sync + Send>>) {
+        self.push(Duration::ZERO, Err(error.into()));
+    }
+
+    /// Return the paths of the requests received so far, in order,
+    /// including any that are still waiting for their latency.
+    pub fn requests(&self) -> Vec<String> {
+        self.0.lock().unwrap().requests.clone()
+    }
+
+    /// Fail every [Transport::ping] from now on if `fail` is true. Pings
+    /// don't use the script.
+    pub fn fail_pings(&self, fail: bool) {
+        self.0.lock().unwrap().ping_fails = fail;
+    }
+
+    /// The number of pings received so far
+    pub fn pings(&self) -> usize {
+        self.0.lock().unwrap().pings
+    }
+
+    /// Return the bodies of the requests received so far that had one,
+    /// in order.
+    pub fn bodies(&self) -> Vec<String> {
+        self.0.lock().unwrap().bodies.clone()
+    }
+
+    /// The number of scripted responses that haven't been used
+    pub fn remaining(&self) -> usize {
+        self.0.lock().unwrap().script.len()
+    }
+
+    pub fn base_url(&self) -> Option<String> {
+        self.0.lock().unwrap().base_url.clone()
+    }
+
+    pub fn user_agent(&self) -> Option<String> {
+        self.0.lock().unwrap().user_agent.clone()
+    }
+}
+
+impl TransportConfig for MockTransport {
+    fn set_base_url(&mut self, base_url: &str) -> Result<(), Box<dyn Error + Sync + Send>> {
+        self.0.lock().unwrap().base_url = Some(base_url.to_string());
+        Ok(())
+    }
+
+    fn set_user_agent(&mut self, user_agent: &str) {
+        self.0.lock().unwrap().user_agent = Some(user_agent.to_string());
+    }
+}
+
+impl Transport for MockTransport {
+    async fn send(&self, path: &str) -> Result<String, Box<dyn Error + Sync + Send>> {
+        let scripted = {
+            let mut state = self.0.lock().unwrap();
+            state.requests.push(path.to_string());
+            state.script.pop_front()
+        };
+        match scripted {
+            Some(Scripted { latency, result }) => {
+                crate::fault::delay(latency).await;
+                result
+            }
+            None => Ok(path.to_string()),
+        }
+    }
+
+    /// Requests of every method are answered from the same script, and
+    /// their bodies are recorded for [MockTransport::bodies].
+    async fn send_with(
+        &self,
+        _method: Method,
+        path: &str,
+        body: Option<&str>,
+    ) -> Result<String, Box<dyn Error + Sync + Send>> {
+        if let Some(body) = body {
+            let mut state = self.0.lock().unwrap();
+            state.bodies.push(body.to_string());
+        }
+        self.send(path).await
+    }
+
+    async fn ping(&self) -> Result<(), Box<dyn Error + Sync + Send>> {
+        let mut state = self.0.lock().unwrap();
+        state.pings += 1;
+        if state.ping_fails {
+            return Err("ping failed".into());
+        }
+        Ok(())
+    }
+}
+
+impl Connector for MockTransport {
+    type Config = MockTransport;
+
+    #[implbox_impls(TransportBox, MockTransport, downcast)]
+    fn new_transport(config: MockTransport) -> impl Transport + Sync + Send {
+        config
+    }
+}
@@ -0,0 +1,93 @@
+//! These benchmarks quantify the cost of each layer of abstraction in
+//! this workspace by measuring the same operation with and without the
+//! layer:
+//! - `implbox`: getting `&impl AsyncRwLock` out of an `ImplBox` vs.
+//!   holding a reference to the concrete lock
+//! - `rwlock`: acquiring a lock through the [AsyncRwLock] trait vs.
+//!   using `tokio::sync::RwLock` directly
+//! - `dispatch`: calling through the blocking device wrapper vs.
+//!   calling the [Controller] directly on a runtime
+//!
+//! Run with `cargo bench -p benches`.
+
+use base::{AsyncRwLock, Locker};
+use controller::Controller;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use runtime_tokio::rwlock::TokioLockWrapper;
+use runtime_tokio::TokioRuntime;
+
+fn current_thread() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+}
+
+fn implbox(c: &mut Criterion) {
+    let mut g = c.benchmark_group("implbox");
+    let boxed = TokioRuntime::box_lock(1);
+    g.bench_function("unbox", |b| {
+        b.iter(|| black_box(TokioRuntime::unbox_lock(black_box(&boxed))))
+    });
+    let direct = TokioLockWrapper::new(1);
+    g.bench_function("direct", |b| b.iter(|| black_box(black_box(&direct))));
+    g.finish();
+}
+
+fn rwlock(c: &mut Criterion) {
+    let rt = current_thread();
+    let mut g = c.benchmark_group("rwlock");
+    let boxed = TokioRuntime::box_lock(1);
+    g.bench_function("boxed_trait_write", |b| {
+        b.to_async(&rt).iter(|| async {
+            let mut lock = TokioRuntime::unbox_lock(&boxed).write().await;
+            *lock += 1;
+        })
+    });
+    let wrapper = TokioLockWrapper::new(1);
+    g.bench_function("trait_write", |b| {
+        b.to_async(&rt).iter(|| async {
+            let mut lock = wrapper.write().await;
+            *lock += 1;
+        })
+    });
+    let tokio_lock = tokio::sync::RwLock::new(1);
+    g.bench_function("tokio_write", |b| {
+        b.to_async(&rt).iter(|| async {
+            let mut lock = tokio_lock.write().await;
+            *lock += 1;
+        })
+    });
+    g.bench_function("trait_read", |b| {
+        b.to_async(&rt)
+            .iter(|| async { black_box(*wrapper.read().await) })
+    });
+    g.bench_function("tokio_read", |b| {
+        b.to_async(&rt)
+            .iter(|| async { black_box(*tokio_lock.read().await) })
+    });
+    g.finish();
+}
+
+fn dispatch(c: &mut Criterion) {
+    let mut g = c.benchmark_group("dispatch");
+    device::init();
+    g.bench_function("device_one", |b| {
+        b.iter(|| device::one(black_box(5)).unwrap())
+    });
+    g.bench_function("device_two", |b| {
+        b.iter(|| device::two(black_box("potato")).unwrap())
+    });
+    let rt = current_thread();
+    let controller = Controller::<TokioRuntime>::new();
+    g.bench_function("controller_one", |b| {
+        b.iter(|| rt.block_on(controller.one(black_box(5))).unwrap())
+    });
+    g.bench_function("controller_two", |b| {
+        b.iter(|| rt.block_on(controller.two(black_box("potato"))).unwrap())
+    });
+    g.finish();
+}
+
+criterion_group!(benches, implbox, rwlock, dispatch);
+criterion_main!(benches);
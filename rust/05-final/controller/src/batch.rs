@@ -0,0 +1,45 @@
+//! Sending many requests in one call with [Controller::batch]. This
+//! saves callers of the blocking device wrapper a round trip through
+//! the runtime for every request.
+use crate::error::ControllerError;
+use crate::Controller;
+use base::{AsyncSemaphore, Connector, Runtime};
+
+/// A request for [Controller::batch]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchRequest {
+    /// See [Controller::one].
+    One(i32),
+    /// See [Controller::two].
+    Two(String),
+}
+
+/// The response to a [BatchRequest] of the same variant
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchResponse {
+    One(i32),
+    Two(String),
+}
+
+impl<RuntimeT: Runtime, TransportT: Connector> Controller<RuntimeT, TransportT> {
+    /// Send `requests` concurrently, with at most the controller's batch
+    /// concurrency in progress at once, and return their results in the
+    /// same order. Each request is handled as if it had been sent on its
+    /// own: it gets its own sequence number, and one failing doesn't stop
+    /// the others. See [crate::builder::ControllerBuilder::batch_concurrency].
+    pub async fn batch(
+        &self,
+        requests: Vec<BatchRequest>,
+    ) -> Vec<Result<BatchResponse, ControllerError>> {
+        let semaphore = RuntimeT::box_semaphore(self.batch_concurrency);
+        let semaphore = RuntimeT::unbox_semaphore(&semaphore);
+        base::join_all(requests.into_iter().map(|request| async move {
+            let _permit = semaphore.acquire().await;
+            match request {
+                BatchRequest::One(val) => self.one(val).await.map(BatchResponse::One),
+                BatchRequest::Two(val) => self.two(&val).await.map(BatchResponse::Two),
+            }
+        }))
+        .await
+    }
+}
@@ -0,0 +1,212 @@
+//! Configuration for a [Controller]. Start with [Controller::builder],
+//! set what differs from the defaults, and call
+//! [ControllerBuilder::build]:
+//!
+//! ```
+//! use controller::Controller;
+//! use runtime_tokio::TokioRuntime;
+//! use std::time::Duration;
+//!
+//! let c = Controller::<TokioRuntime>::builder()
+//!     .timeout(Duration::from_secs(5))
+//!     .max_retries(2)
+//!     .initial_seq(100)
+//!     .build()
+//!     .unwrap();
+//! ```
+//!
+//! The base URL and user agent are given to the transport through
+//! [TransportConfig]. Transports that don't send anywhere, such as the
+//! default [base::LoopbackTransport], ignore them.
+use crate::error::ControllerError;
+use crate::layer::Layer;
+use crate::Controller;
+use base::{Connector, Runtime, TransportConfig};
+use std::marker::PhantomData;
+use std::time::Duration;
+
+/// What a request does when the controller already has as many
+/// requests in flight as [ControllerBuilder::max_in_flight] allows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverloadPolicy {
+    /// Wait for another request to finish. This counts toward the
+    /// request's timeout.
+    #[default]
+    Wait,
+    /// Fail with [ControllerError::Overloaded].
+    FailFast,
+}
+
+/// Settings for a [Controller], created with [Controller::builder]
+pub struct ControllerBuilder<RuntimeT, TransportT: Connector> {
+    pub(crate) config: TransportT::Config,
+    base_url: Option<String>,
+    user_agent: Option<String>,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) max_retries: u32,
+    pub(crate) initial_seq: i32,
+    pub(crate) batch_concurrency: usize,
+    pub(crate) layers: Vec<Box<dyn Layer>>,
+    pub(crate) rate_limit: Option<(f64, u32)>,
+    pub(crate) max_in_flight: Option<(usize, OverloadPolicy)>,
+    pub(crate) history: usize,
+    pub(crate) metrics: bool,
+    _r: PhantomData<fn() -> RuntimeT>,
+}
+
+impl<RuntimeT: Runtime, TransportT: Connector> Default for ControllerBuilder<RuntimeT, TransportT> {
+    fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
+impl<RuntimeT: Runtime, TransportT: Connector> ControllerBuilder<RuntimeT, TransportT> {
+    pub(crate) fn new(config: TransportT::Config) -> Self {
+        Self {
+            config,
+            base_url: None,
+            user_agent: None,
+            timeout: None,
+            max_retries: 0,
+            initial_seq: 1,
+            batch_concurrency: 8,
+            layers: Vec::new(),
+            rate_limit: None,
+            max_in_flight: None,
+            history: 0,
+            metrics: false,
+            _r: Default::default(),
+        }
+    }
+
+    /// Create the transport from `config` instead of the default
+    /// configuration. A base URL or user agent set on the builder is
+    /// applied to it.
+    pub fn transport(mut self, config: TransportT::Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Send requests to paths under `base_url`. See
+    /// [TransportConfig::set_base_url].
+    pub fn base_url(mut self, base_url: &str) -> Self {
+        self.base_url = Some(base_url.to_string());
+        self
+    }
+
+    /// Identify the client as `user_agent`. See
+    /// [TransportConfig::set_user_agent].
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    /// Fail a request with [ControllerError::Timeout] if it doesn't
+    /// finish within `timeout`, including retries and waiting for other
+    /// requests. By default, requests wait as long as it takes.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Send a request up to `max_retries` more times if the transport
+    /// fails. Retries reuse the request's sequence number. Requests that
+    /// aren't idempotent, such as [Method::Post], aren't retried, since
+    /// the server may have acted on them before failing. The default is
+    /// 0.
+    ///
+    /// [Method::Post]: crate::endpoint::Method::Post
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Give the first request sequence number `seq` instead of 1. This
+    /// also applies after [Controller::reset].
+    pub fn initial_seq(mut self, seq: i32) -> Self {
+        self.initial_seq = seq;
+        self
+    }
+
+    /// Let [Controller::batch] have up to `n` requests in progress at
+    /// once. The default is 8.
+    pub fn batch_concurrency(mut self, n: usize) -> Self {
+        self.batch_concurrency = n;
+        self
+    }
+
+    /// Pass every request through `layer`, after the layers that were
+    /// added before it. See [Layer].
+    pub fn layer(mut self, layer: impl Layer + 'static) -> Self {
+        self.layers.push(Box::new(layer));
+        self
+    }
+
+    /// Send at most `per_second` requests per second on average, with
+    /// bursts of up to `burst` requests. Requests over the limit wait,
+    /// which counts toward their timeout. See
+    /// [crate::rate_limit::RateLimiter]. By default, there is no limit.
+    pub fn rate_limit(mut self, per_second: f64, burst: u32) -> Self {
+        self.rate_limit = Some((per_second, burst));
+        self
+    }
+
+    /// Allow at most `max` requests in flight at once, from all callers
+    /// together, including those waiting for other requests or for the
+    /// rate limit. `policy` says what happens to the rest. A stream
+    /// counts only while it is being opened. By default, there is no
+    /// limit.
+    pub fn max_in_flight(mut self, max: usize, policy: OverloadPolicy) -> Self {
+        self.max_in_flight = Some((max, policy));
+        self
+    }
+
+    /// Keep the last `n` requests for [Controller::recent_requests]. The
+    /// default is 0, which keeps none. Each entry is timestamped with
+    /// the runtime's clock, which not every target has.
+    pub fn history(mut self, n: usize) -> Self {
+        self.history = n;
+        self
+    }
+
+    /// Count requests, errors, and latency for [Controller::metrics].
+    /// Like the history, this uses the runtime's clock. By default,
+    /// nothing is counted.
+    pub fn metrics(mut self) -> Self {
+        self.metrics = true;
+        self
+    }
+
+    /// Create the controller. This fails with
+    /// [ControllerError::InvalidInput] if the transport can't use the
+    /// base URL, if the batch concurrency or maximum in flight is 0, or
+    /// if the rate limit isn't positive or has a burst of 0.
+    pub fn build(mut self) -> Result<Controller<RuntimeT, TransportT>, ControllerError> {
+        if self.batch_concurrency == 0 {
+            return Err(ControllerError::InvalidInput(
+                "batch concurrency must not be 0".to_string(),
+            ));
+        }
+        if let Some((per_second, burst)) = self.rate_limit {
+            if !(per_second > 0.0 && per_second.is_finite()) || burst == 0 {
+                return Err(ControllerError::InvalidInput(format!(
+                    "invalid rate limit: {per_second} per second with burst {burst}"
+                )));
+            }
+        }
+        if let Some((0, _)) = self.max_in_flight {
+            return Err(ControllerError::InvalidInput(
+                "max in flight must not be 0".to_string(),
+            ));
+        }
+        if let Some(base_url) = &self.base_url {
+            self.config
+                .set_base_url(base_url)
+                .map_err(|e| ControllerError::InvalidInput(e.to_string()))?;
+        }
+        if let Some(user_agent) = &self.user_agent {
+            self.config.set_user_agent(user_agent);
+        }
+        Ok(Controller::from_builder(self))
+    }
+}
@@ -0,0 +1,88 @@
+//! Shutting down a [Controller] with [Controller::close], so that the
+//! requests it has started aren't cut off when it is dropped.
+use crate::error::ControllerError;
+use crate::event::Event;
+use crate::Controller;
+use base::{AsyncNotify, Connector, Runtime, StdNotify};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// The requests in progress, so that closing can wait for them
+#[derive(Default)]
+pub(crate) struct Activity {
+    closed: AtomicBool,
+    active: AtomicUsize,
+    idle: StdNotify,
+}
+
+/// Held for as long as a request is in progress
+pub(crate) struct Active<'a>(&'a Activity);
+
+impl Drop for Active<'_> {
+    fn drop(&mut self) {
+        if self.0.active.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
+
+impl Activity {
+    /// Count a request as in progress until the returned guard is
+    /// dropped, or fail if the controller is closed. The request is
+    /// counted before checking so that [Controller::close] either sees
+    /// it or it sees that the controller is closed.
+    pub(crate) fn enter(&self) -> Result<Active<'_>, ControllerError> {
+        self.active.fetch_add(1, Ordering::SeqCst);
+        let active = Active(self);
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(ControllerError::Closed);
+        }
+        Ok(active)
+    }
+
+    /// The number of requests in progress
+    pub(crate) fn in_progress(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    /// Wait until no request is in progress.
+    async fn idle(&self) {
+        loop {
+            // The waiter is registered before checking, so a request
+            // that finishes in between still wakes it.
+            let notified = self.idle.notified();
+            if self.active.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+impl<RuntimeT: Runtime, TransportT: Connector> Controller<RuntimeT, TransportT> {
+    /// Stop accepting requests, wait up to `timeout` for the ones in
+    /// progress to finish, and then flush the logger. Requests made from
+    /// now on fail with [ControllerError::Closed], but responses that
+    /// are already streaming can still be read. If the requests don't
+    /// finish in time, this fails with [ControllerError::Timeout] and
+    /// leaves them running, and it can be called again to keep waiting.
+    /// The controller doesn't run background tasks, so once this
+    /// succeeds, nothing is lost by dropping it.
+    pub async fn close(&self, timeout: Duration) -> Result<(), ControllerError> {
+        if !self.activity.closed.swap(true, Ordering::SeqCst) {
+            self.emit(|| Event::Closed);
+        }
+        RuntimeT::timeout(timeout, self.activity.idle())
+            .await
+            .map_err(|_| ControllerError::Timeout)?;
+        if let Some(logger) = &self.logger {
+            logger.flush();
+        }
+        Ok(())
+    }
+
+    /// Whether [Controller::close] has been called
+    pub fn is_closed(&self) -> bool {
+        self.activity.closed.load(Ordering::SeqCst)
+    }
+}
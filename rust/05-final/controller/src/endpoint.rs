@@ -0,0 +1,219 @@
+//! Typed requests. An [Endpoint] builds the [Request] for a call and
+//! turns the [Response] into what the caller wants, so that each of a
+//! server's endpoints is described once instead of as a format string
+//! at every call site. Send one with [Controller::call]:
+//!
+//! ```
+//! use controller::endpoint::{Endpoint, Request, Response};
+//! use controller::error::ControllerError;
+//! use controller::Controller;
+//! use runtime_tokio::TokioRuntime;
+//!
+//! struct Rename<'a> {
+//!     id: u32,
+//!     name: &'a str,
+//! }
+//!
+//! impl Endpoint for Rename<'_> {
+//!     type Output = String;
+//!
+//!     fn request(&self) -> Result<Request, ControllerError> {
+//!         Ok(Request::put("items")
+//!             .param("id", self.id)
+//!             .body(self.name))
+//!     }
+//!
+//!     fn response(&self, response: Response) -> Result<String, ControllerError> {
+//!         Ok(response.body)
+//!     }
+//! }
+//!
+//! # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+//! let c = Controller::<TokioRuntime>::new();
+//! let body = c.call(&Rename { id: 7, name: "salad" }).await.unwrap();
+//! assert_eq!(body, "items?id=7&seq=1");
+//! # });
+//! ```
+//!
+//! With the `serde` feature, [Request] and [Response] can be
+//! serialized, for example to record calls or replay them.
+//!
+//! [Controller::call]: crate::Controller::call
+use crate::error::ControllerError;
+pub use base::Method;
+use std::fmt::Write;
+
+/// A request, before the controller adds its sequence number
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Request {
+    /// If the transport fails, the request is only sent again if this
+    /// is idempotent. See [Method::is_idempotent].
+    pub method: Method,
+    /// The path, relative to the transport's base. A query string here
+    /// is sent as is, so parameters should go in `params` instead.
+    pub path: String,
+    /// Query parameters, in order. They are percent-encoded when the
+    /// request is sent.
+    pub params: Vec<(String, String)>,
+    pub body: Option<String>,
+}
+
+impl Request {
+    pub fn new(method: Method, path: &str) -> Self {
+        Self {
+            method,
+            path: path.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn get(path: &str) -> Self {
+        Self::new(Method::Get, path)
+    }
+
+    pub fn post(path: &str) -> Self {
+        Self::new(Method::Post, path)
+    }
+
+    pub fn put(path: &str) -> Self {
+        Self::new(Method::Put, path)
+    }
+
+    pub fn delete(path: &str) -> Self {
+        Self::new(Method::Delete, path)
+    }
+
+    /// Add the query parameter `name` with the text of `value`.
+    pub fn param(mut self, name: &str, value: impl ToString) -> Self {
+        self.params.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<String>) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    /// Return the path with the encoded query string, as the transport
+    /// receives it without the sequence number.
+    pub fn path_and_query(&self) -> String {
+        let mut out = self.path.clone();
+        for (i, (name, value)) in self.params.iter().enumerate() {
+            out.push(if i == 0 { '?' } else { '&' });
+            encode(&mut out, name);
+            out.push('=');
+            encode(&mut out, value);
+        }
+        out
+    }
+
+    /// Return the path and query as sent, with the controller's
+    /// sequence number `seq` added as the last parameter.
+    pub(crate) fn with_seq(&self, seq: i32) -> String {
+        let mut out = self.path_and_query();
+        out.push(if out.contains('?') { '&' } else { '?' });
+        write!(out, "seq={seq}").unwrap();
+        out
+    }
+}
+
+/// Append `s` to `out` with everything but unreserved characters
+/// percent-encoded.
+fn encode(out: &mut String, s: &str) {
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                out.push(b.into())
+            }
+            _ => write!(out, "%{b:02X}").unwrap(),
+        }
+    }
+}
+
+/// The result of a successful [Request]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Response {
+    /// The sequence number the request was sent with
+    pub seq: i32,
+    /// The body the transport returned
+    pub body: String,
+}
+
+/// A call that can be made with [crate::Controller::call]
+pub trait Endpoint {
+    /// What the call returns
+    type Output;
+
+    /// Build the request. An error here fails the call without sending
+    /// anything or using a sequence number.
+    fn request(&self) -> Result<Request, ControllerError>;
+
+    /// Convert the response to the request into the output.
+    fn response(&self, response: Response) -> Result<Self::Output, ControllerError>;
+}
+
+/// The endpoint of [crate::Controller::one]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct One(pub i32);
+
+impl Endpoint for One {
+    type Output = i32;
+
+    fn request(&self) -> Result<Request, ControllerError> {
+        if self.0 == 3 {
+            return Err(ControllerError::InvalidInput(
+                "sorry, not that one".to_string(),
+            ));
+        }
+        Ok(Request::get("one").param("val", self.0))
+    }
+
+    fn response(&self, response: Response) -> Result<i32, ControllerError> {
+        Ok(response.seq)
+    }
+}
+
+/// The endpoint of [crate::Controller::two]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Two<'a>(pub &'a str);
+
+impl Endpoint for Two<'_> {
+    type Output = String;
+
+    fn request(&self) -> Result<Request, ControllerError> {
+        Ok(Request::get("two").param("val", self.0))
+    }
+
+    fn response(&self, response: Response) -> Result<String, ControllerError> {
+        Ok(response.body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_and_query() {
+        assert_eq!(Request::get("items").path_and_query(), "items");
+        let request = Request::get("items").param("a", 1).param("b c", "x&y=é");
+        assert_eq!(request.path_and_query(), "items?a=1&b%20c=x%26y%3D%C3%A9");
+        assert_eq!(request.with_seq(3), "items?a=1&b%20c=x%26y%3D%C3%A9&seq=3");
+        assert_eq!(Request::get("items").with_seq(3), "items?seq=3");
+        assert_eq!(Request::get("items?a=1").with_seq(3), "items?a=1&seq=3");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let request = Request::post("items").param("id", 7).body("salad");
+        let json = serde_json::to_string(&request).unwrap();
+        assert_eq!(
+            json,
+            r#"{"method":"POST","path":"items","params":[["id","7"]],"body":"salad"}"#
+        );
+        assert_eq!(serde_json::from_str::<Request>(&json).unwrap(), request);
+    }
+}
@@ -0,0 +1,169 @@
+//! Errors returned by [Controller]. Its methods return a
+//! [ControllerError], whose variant says what kind of failure it was,
+//! so callers can branch on it and bindings can map it to error codes.
+//! Errors from the transport are kept as the source of
+//! [ControllerError::Transport]. The `Display` text of each variant is
+//! stable and is the same whether or not the `thiserror` feature is
+//! enabled.
+//!
+//! A [ControllerError] can be converted to a boxed error or an
+//! [anyhow::Error] with `?`. With the `anyhow` feature, [AnyhowExt]
+//! also converts boxed errors, such as those from code that mixes the
+//! controller with other libraries, to [anyhow::Error] and attaches
+//! context, keeping the source chain.
+//!
+//! [Controller]: crate::Controller
+use std::error::Error;
+#[cfg(not(feature = "thiserror"))]
+use std::fmt;
+
+#[derive(Debug)]
+#[cfg_attr(feature = "thiserror", derive(thiserror::Error))]
+#[non_exhaustive]
+pub enum ControllerError {
+    /// A panic left the controller unusable. The device wrapper returns
+    /// this until it is initialized again.
+    #[cfg_attr(feature = "thiserror", error("controller poisoned"))]
+    Poisoned,
+    /// The request was rejected before being sent.
+    #[cfg_attr(feature = "thiserror", error("{0}"))]
+    InvalidInput(String),
+    /// Sending the request failed.
+    #[cfg_attr(feature = "thiserror", error("transport error: {0}"))]
+    Transport(#[cfg_attr(feature = "thiserror", source)] Box<dyn Error + Sync + Send>),
+    /// The request's cancel token was cancelled before it finished.
+    #[cfg_attr(feature = "thiserror", error("request was cancelled"))]
+    Cancelled,
+    /// The request didn't finish within the controller's timeout.
+    #[cfg_attr(feature = "thiserror", error("request timed out"))]
+    Timeout,
+    /// The controller already had as many requests in flight as it
+    /// allows, and its overload policy is to fail fast.
+    #[cfg_attr(feature = "thiserror", error("too many requests in flight"))]
+    Overloaded,
+    /// The controller was closed with `Controller::close`.
+    #[cfg_attr(feature = "thiserror", error("controller is closed"))]
+    Closed,
+}
+
+#[cfg(not(feature = "thiserror"))]
+impl fmt::Display for ControllerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ControllerError::Poisoned => write!(f, "controller poisoned"),
+            ControllerError::InvalidInput(msg) => write!(f, "{msg}"),
+            ControllerError::Transport(e) => write!(f, "transport error: {e}"),
+            ControllerError::Cancelled => write!(f, "request was cancelled"),
+            ControllerError::Timeout => write!(f, "request timed out"),
+            ControllerError::Overloaded => write!(f, "too many requests in flight"),
+            ControllerError::Closed => write!(f, "controller is closed"),
+        }
+    }
+}
+
+#[cfg(not(feature = "thiserror"))]
+impl Error for ControllerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ControllerError::Transport(e) => Some(e.as_ref()),
+            ControllerError::Poisoned
+            | ControllerError::InvalidInput(_)
+            | ControllerError::Cancelled
+            | ControllerError::Timeout
+            | ControllerError::Overloaded
+            | ControllerError::Closed => None,
+        }
+    }
+}
+
+/// Conversion of boxed errors to [anyhow::Error]. anyhow can't convert
+/// `Box<dyn Error>` with `?` since the box doesn't implement [Error].
+/// A [ControllerError] is converted directly so that it can be
+/// recovered with [anyhow::Error::downcast_ref]. Other errors keep
+/// their text and source chain but can't be downcast.
+#[cfg(feature = "anyhow")]
+pub trait AnyhowExt<T> {
+    fn anyhow(self) -> anyhow::Result<T>;
+
+    fn context<C>(self, context: C) -> anyhow::Result<T>
+    where
+        C: std::fmt::Display + Send + Sync + 'static;
+
+    fn with_context<C, F>(self, f: F) -> anyhow::Result<T>
+    where
+        C: std::fmt::Display + Send + Sync + 'static,
+        F: FnOnce() -> C;
+}
+
+#[cfg(feature = "anyhow")]
+impl<T> AnyhowExt<T> for Result<T, Box<dyn Error + Sync + Send>> {
+    fn anyhow(self) -> anyhow::Result<T> {
+        self.map_err(|e| match e.downcast::<ControllerError>() {
+            Ok(e) => anyhow::Error::new(*e),
+            Err(e) => anyhow::Error::from_boxed(e),
+        })
+    }
+
+    fn context<C>(self, context: C) -> anyhow::Result<T>
+    where
+        C: std::fmt::Display + Send + Sync + 'static,
+    {
+        self.anyhow().map_err(|e| e.context(context))
+    }
+
+    fn with_context<C, F>(self, f: F) -> anyhow::Result<T>
+    where
+        C: std::fmt::Display + Send + Sync + 'static,
+        F: FnOnce() -> C,
+    {
+        self.anyhow().map_err(|e| e.context(f()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base::fault::Fault;
+
+    #[test]
+    fn test_display() {
+        // This text is part of the API. Check it in every feature
+        // combination.
+        let e = ControllerError::Poisoned;
+        assert_eq!(e.to_string(), "controller poisoned");
+        assert!(e.source().is_none());
+        let e = ControllerError::InvalidInput("no".to_string());
+        assert_eq!(e.to_string(), "no");
+        assert!(e.source().is_none());
+        let e = ControllerError::Transport(Box::new(Fault::Dropped));
+        assert_eq!(e.to_string(), "transport error: connection dropped");
+        assert_eq!(e.source().unwrap().to_string(), "connection dropped");
+        let e = ControllerError::Cancelled;
+        assert_eq!(e.to_string(), "request was cancelled");
+        assert!(e.source().is_none());
+        let e = ControllerError::Timeout;
+        assert_eq!(e.to_string(), "request timed out");
+        assert!(e.source().is_none());
+        let e = ControllerError::Overloaded;
+        assert_eq!(e.to_string(), "too many requests in flight");
+        assert!(e.source().is_none());
+        let e = ControllerError::Closed;
+        assert_eq!(e.to_string(), "controller is closed");
+        assert!(e.source().is_none());
+    }
+
+    #[cfg(feature = "anyhow")]
+    #[test]
+    fn test_anyhow() {
+        let r: Result<(), Box<dyn Error + Sync + Send>> =
+            Err(ControllerError::InvalidInput("no".to_string()).into());
+        let e = r.context("calling one").unwrap_err();
+        assert_eq!(format!("{e:#}"), "calling one: no");
+        assert!(e.downcast_ref::<ControllerError>().is_some());
+        // anyhow's own Context works on the typed error.
+        use anyhow::Context;
+        let r: Result<(), ControllerError> = Err(ControllerError::Timeout);
+        let e = r.context("calling two").unwrap_err();
+        assert_eq!(format!("{e:#}"), "calling two: request timed out");
+    }
+}
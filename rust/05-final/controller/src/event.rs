@@ -0,0 +1,77 @@
+//! Notifications of what a [Controller] is doing. Call
+//! [Controller::subscribe] to receive an [Event] for every request and
+//! state change from then on, instead of polling with methods such as
+//! [Controller::last_path].
+use crate::Controller;
+use base::{
+    AsyncStream, Broadcast, BroadcastReceiver, BroadcastReceiverBox, Connector, RecvError, Runtime,
+};
+use implbox::ImplBox;
+use std::marker::PhantomData;
+
+/// The number of events kept for a subscriber that hasn't received
+/// them yet. A subscriber that falls further behind misses the oldest
+/// ones.
+pub const EVENT_CAPACITY: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Event {
+    /// A request for `path` was made. It may still be waiting for other
+    /// requests, so it doesn't have a sequence number yet.
+    RequestStarted { path: String },
+    /// The request for `path` got the response for sequence number
+    /// `seq`.
+    RequestCompleted { seq: i32, path: String },
+    /// The request for `path` failed with `error`, the error's text.
+    RequestFailed { path: String, error: String },
+    /// The request state was replaced other than by a request, as by
+    /// [Controller::reset].
+    StateChanged,
+    /// [Controller::close] was called, so no more requests will be
+    /// made.
+    Closed,
+}
+
+/// The events of a controller, returned by [Controller::subscribe]. It
+/// doesn't borrow the controller, and it ends when the controller is
+/// dropped.
+pub struct EventStream<RuntimeT: Runtime> {
+    receiver: ImplBox<BroadcastReceiverBox<Event>>,
+    _r: PhantomData<fn() -> RuntimeT>,
+}
+
+impl<RuntimeT: Runtime> AsyncStream<Event> for EventStream<RuntimeT> {
+    async fn next(&mut self) -> Option<Event> {
+        loop {
+            let receiver = RuntimeT::unbox_mut_broadcast_receiver(&mut self.receiver);
+            match receiver.recv().await {
+                Ok(event) => return Some(event),
+                // Continue with the oldest event that is still kept.
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+impl<RuntimeT: Runtime, TransportT: Connector> Controller<RuntimeT, TransportT> {
+    /// Return a stream of the events that happen from now on. See
+    /// [EVENT_CAPACITY] for what happens to a subscriber that doesn't
+    /// keep up.
+    pub fn subscribe(&self) -> EventStream<RuntimeT> {
+        EventStream {
+            receiver: RuntimeT::box_broadcast_receiver(&self.events),
+            _r: PhantomData,
+        }
+    }
+
+    /// Send the event from `f` to subscribers. The event is only created
+    /// if there are any.
+    pub(crate) fn emit(&self, f: impl FnOnce() -> Event) {
+        let events = RuntimeT::unbox_broadcast(&self.events);
+        if events.receiver_count() > 0 {
+            events.send(f());
+        }
+    }
+}
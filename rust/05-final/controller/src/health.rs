@@ -0,0 +1,68 @@
+//! Liveness and readiness checks with [Controller::health], for a host
+//! process that wires them into its probes.
+use crate::Controller;
+use base::{AsyncRwLock, AsyncSemaphore, Connector, Runtime, Transport};
+
+/// The state of a controller as reported by [Controller::health]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthStatus {
+    /// Whether the transport answered a ping, with the error's text if
+    /// it didn't. See [Transport::ping].
+    pub transport: Result<(), String>,
+    /// Whether [Controller::close] has been called
+    pub closed: bool,
+    /// Whether a panic left the controller unusable. See
+    /// [Controller::is_poisoned].
+    pub poisoned: bool,
+    /// Whether a request held the request lock when this was checked
+    pub locked: bool,
+    /// Requests that have started and not finished, including those
+    /// waiting for the lock, the rate limit, or a turn to be in flight
+    pub in_progress: usize,
+    /// How many more requests may be in flight, if the number is
+    /// limited by [crate::builder::ControllerBuilder::max_in_flight]
+    pub in_flight_available: Option<usize>,
+}
+
+impl HealthStatus {
+    /// Whether the controller can make progress at all. If not, it has
+    /// to be reset or replaced.
+    pub fn is_live(&self) -> bool {
+        !self.poisoned
+    }
+
+    /// Whether the controller is live, open, and able to reach the
+    /// server, so it should be sent requests.
+    pub fn is_ready(&self) -> bool {
+        self.is_live() && !self.closed && self.transport.is_ok()
+    }
+}
+
+impl<RuntimeT: Runtime, TransportT: Connector> Controller<RuntimeT, TransportT> {
+    /// Ping the transport and report on the controller's state. The
+    /// ping doesn't go through the layers or wait for requests, and it
+    /// doesn't count as a request, but it is subject to the
+    /// controller's timeout.
+    pub async fn health(&self) -> HealthStatus {
+        let ping = self.transport().ping();
+        let transport = match self.timeout {
+            None => ping.await,
+            Some(timeout) => RuntimeT::timeout(timeout, ping)
+                .await
+                .unwrap_or_else(|e| Err(e.into())),
+        };
+        let poisoned = self.is_poisoned();
+        HealthStatus {
+            transport: transport.map_err(|e| e.to_string()),
+            closed: self.is_closed(),
+            poisoned,
+            // A poisoned lock can't be checked, and nothing holds it.
+            locked: !poisoned && self.req_data().try_read().is_none(),
+            in_progress: self.activity.in_progress(),
+            in_flight_available: self
+                .in_flight
+                .as_ref()
+                .map(|(semaphore, _)| RuntimeT::unbox_semaphore(semaphore).permits()),
+        }
+    }
+}
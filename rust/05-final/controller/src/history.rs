@@ -0,0 +1,51 @@
+//! A record of the most recent requests, for debugging. Turn it on with
+//! [ControllerBuilder::history] and read it with
+//! [Controller::recent_requests].
+//!
+//! [Controller::recent_requests]: crate::Controller::recent_requests
+//! [ControllerBuilder::history]: crate::builder::ControllerBuilder::history
+use std::collections::VecDeque;
+use std::time::Instant;
+
+/// A finished request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestEntry {
+    /// The sequence number of the request, if it succeeded
+    pub seq: Option<i32>,
+    /// The request path, including the query string
+    pub path: String,
+    /// When the request finished, on the runtime's clock
+    pub time: Instant,
+    /// The error message if the request failed
+    pub outcome: Result<(), String>,
+}
+
+/// The last `capacity` entries, oldest first
+pub(crate) struct History {
+    capacity: usize,
+    entries: VecDeque<RequestEntry>,
+}
+
+impl History {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub(crate) fn push(&mut self, entry: RequestEntry) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub(crate) fn entries(&self) -> Vec<RequestEntry> {
+        self.entries.iter().cloned().collect()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
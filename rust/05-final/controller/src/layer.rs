@@ -0,0 +1,89 @@
+//! Middleware for the requests a [Controller] sends. Add a [Layer] with
+//! [ControllerBuilder::layer] to inspect or change every path on its
+//! way to the transport and every result on its way back, for things
+//! such as authentication, metrics, or rewriting requests:
+//!
+//! ```
+//! use controller::error::ControllerError;
+//! use controller::layer::Layer;
+//! use controller::Controller;
+//! use runtime_tokio::TokioRuntime;
+//!
+//! struct Auth(String);
+//!
+//! impl Layer for Auth {
+//!     fn request(&self, path: &mut String) -> Result<(), ControllerError> {
+//!         path.push_str("&token=");
+//!         path.push_str(&self.0);
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let c = Controller::<TokioRuntime>::builder()
+//!     .layer(Auth("secret".to_string()))
+//!     .build()
+//!     .unwrap();
+//! ```
+//!
+//! Layers are stored as `Box<dyn Layer>` rather than in an
+//! [implbox::ImplBox]. An ImplBox hides a type that a [base::Runtime]
+//! returns as `impl Trait` so that it can be stored without naming the
+//! runtime's type, but layers are the caller's own types, and [Layer]
+//! is object safe, so a trait object already stores them without
+//! making the controller generic over them.
+//!
+//! [Controller]: crate::Controller
+//! [ControllerBuilder::layer]: crate::builder::ControllerBuilder::layer
+use crate::error::ControllerError;
+use std::borrow::Cow;
+
+/// A layer is called on the way out in the order in which it was
+/// added, and on the way back in the reverse order, so the first layer
+/// added is outermost. Layers are called for every attempt, including
+/// retries, on the task that sends the request, so they should not
+/// block. They don't depend on the runtime.
+///
+/// The controller's last path is the path before any layer changed it,
+/// so secrets added by a layer aren't kept.
+pub trait Layer: Send + Sync {
+    /// Inspect or change the path that is about to be sent. Returning
+    /// an error fails the request without sending it, and the layers
+    /// after this one aren't called. Only transport errors are retried,
+    /// so this isn't called again for the same request. The default
+    /// does nothing.
+    fn request(&self, path: &mut String) -> Result<(), ControllerError> {
+        let _ = path;
+        Ok(())
+    }
+
+    /// Inspect or change the result of sending `path`, which is the path
+    /// as the transport received it, or as far as it got. This is called
+    /// for every layer whose [Layer::request] succeeded. The default does
+    /// nothing.
+    fn response(&self, path: &str, result: &mut Result<String, ControllerError>) {
+        let _ = (path, result);
+    }
+}
+
+/// Pass `path` through the request side of `layers`. On failure, return
+/// the number of layers that accepted it along with the error.
+pub(crate) fn request(
+    layers: &[Box<dyn Layer>],
+    path: &mut Cow<'_, str>,
+) -> Result<(), (usize, ControllerError)> {
+    for (i, layer) in layers.iter().enumerate() {
+        layer.request(path.to_mut()).map_err(|e| (i, e))?;
+    }
+    Ok(())
+}
+
+/// Pass `result` back through the response side of `layers`.
+pub(crate) fn response(
+    layers: &[Box<dyn Layer>],
+    path: &str,
+    result: &mut Result<String, ControllerError>,
+) {
+    for layer in layers.iter().rev() {
+        layer.response(path, result);
+    }
+}
@@ -1,75 +1,635 @@
 //! This is an internal implementation of sample API. The
-//! implementation pretends to make network calls and accesses locked
+//! implementation sends requests with a [Transport] and accesses locked
 //! data. It is wrapped by a function-based API that operates a
 //! singleton.
-use base::{AsyncRwLock, LockBox, Runtime};
+//!
+//! By default, requests go to a [LoopbackTransport], which returns each
+//! path as the response, so nothing is sent. To talk to a server, name
+//! another [Connector], such as `transport_http::HttpTransport`, and
+//! create the controller with [Controller::new_with].
+use base::fault::FaultLayer;
+use base::{
+    run_until_cancelled, AsyncRwLock, AsyncSemaphore, BroadcastBox, CancelTokenBox, Clock,
+    Connector, LockBox, LoopbackTransport, MappedReadGuard, Runtime, SemaphoreBox, Transport,
+    TransportBox,
+};
+use builder::{ControllerBuilder, OverloadPolicy};
+use close::{Active, Activity};
+use endpoint::{Endpoint, Method, Request, Response};
+use error::ControllerError;
+use event::Event;
+use history::{History, RequestEntry};
 use implbox::ImplBox;
-use std::error::Error;
+use layer::Layer;
+use logger::{RequestLogger, RequestRecord};
+use metrics::Metrics;
+use rate_limit::RateLimiter;
+use std::borrow::Cow;
 use std::marker::PhantomData;
 use std::ops::DerefMut;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
-#[derive(Default)]
+pub mod batch;
+pub mod builder;
+mod close;
+pub mod endpoint;
+pub mod error;
+pub mod event;
+pub mod health;
+pub mod history;
+pub mod layer;
+pub mod logger;
+pub mod metrics;
+pub mod rate_limit;
+pub mod stream;
+
+/// A snapshot of resources in use, for tracking leaks in long-running
+/// processes. Box and lock counts are process-wide, not just for one
+/// controller.
+#[cfg(feature = "accounting")]
+#[derive(Debug, Clone, Default)]
+pub struct ResourceUsage {
+    /// Live ImplBoxes by shadow type. See [implbox::accounting].
+    pub implboxes: std::collections::BTreeMap<&'static str, usize>,
+    /// Live boxed locks of all types
+    pub locks: usize,
+    /// Live tasks on the runtime. The controller doesn't spawn tasks,
+    /// so this is only known to whatever owns the runtime.
+    pub tasks: usize,
+    /// Memory used by the controller's cached request data
+    pub cache_bytes: usize,
+}
+
+#[cfg(feature = "accounting")]
+impl ResourceUsage {
+    /// Return the process-wide counts.
+    pub fn global() -> Self {
+        // Count locks in the same snapshot so that the counts agree.
+        let implboxes = implbox::accounting::live_boxes();
+        Self {
+            locks: base::count_locks(&implboxes),
+            implboxes,
+            ..Default::default()
+        }
+    }
+}
+
+/// Poisons an [ImplBox] if it is dropped while its thread is panicking
+struct PoisonOnUnwind<'a, T>(&'a ImplBox<T>);
+
+impl<T> Drop for PoisonOnUnwind<'_, T> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            self.0.poison();
+        }
+    }
+}
+
+#[derive(Default, Clone)]
 struct ReqData {
     seq: i32,
     last_path: String,
+    /// The transport's response to `last_path`
+    response: String,
+}
+
+impl ReqData {
+    /// The state before any request, when the next request gets `seq`
+    fn initial(seq: i32) -> Self {
+        Self {
+            seq: seq.wrapping_sub(1),
+            ..Default::default()
+        }
+    }
 }
 
-pub struct Controller<RuntimeT: Runtime> {
-    req_data: ImplBox<LockBox<ReqData>>,
-    _r: PhantomData<RuntimeT>,
+/// Options for one request, for methods such as [Controller::one_with].
+/// The default options are what [Controller::one] and the other methods
+/// without options use.
+#[derive(Default, Clone, Copy)]
+pub struct RequestOptions<'a> {
+    /// Fail with [ControllerError::Cancelled] if this token is cancelled
+    /// before the request finishes. A request whose token is already
+    /// cancelled is never started. The token must have been created with
+    /// the controller's runtime.
+    pub cancel: Option<&'a ImplBox<CancelTokenBox>>,
+    /// Fail with [ControllerError::Timeout] if the request doesn't finish
+    /// within this time, including retries and waiting for other
+    /// requests. If the controller also has a timeout, the shorter one
+    /// applies.
+    pub timeout: Option<Duration>,
 }
 
-impl<RuntimeT: Runtime> Default for Controller<RuntimeT> {
-    fn default() -> Self {
-        Self {
-            req_data: RuntimeT::box_lock(Default::default()),
-            _r: Default::default(),
+impl<'a> RequestOptions<'a> {
+    pub fn with_cancel(mut self, cancel: &'a ImplBox<CancelTokenBox>) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+/// The runtime that [Controller] uses when none is named, chosen by the
+/// `rt-tokio`, `rt-async-std`, or `rt-std` feature. If more than one is
+/// enabled, the first in that order wins, so a crate that enables one
+/// can't be overridden by another crate in the same build enabling a
+/// different one. Name the runtime, as in `Controller<StdRuntime>`, to
+/// use any other.
+#[cfg(feature = "rt-tokio")]
+pub type DefaultRuntime = runtime_tokio::TokioRuntime;
+#[cfg(all(feature = "rt-async-std", not(feature = "rt-tokio")))]
+pub type DefaultRuntime = runtime_async_std::AsyncStdRuntime;
+#[cfg(all(
+    feature = "rt-std",
+    not(any(feature = "rt-tokio", feature = "rt-async-std"))
+))]
+pub type DefaultRuntime = runtime_std::StdRuntime;
+
+/// Declare [Controller], whose runtime parameter defaults to
+/// [DefaultRuntime] when there is one. A default can't be added with
+/// `cfg`, so the declaration is shared this way. The transport
+/// parameter always defaults to [LoopbackTransport].
+macro_rules! controller {
+    ($($default:tt)*) => {
+        pub struct Controller<
+            RuntimeT: Runtime $($default)*,
+            TransportT: Connector = LoopbackTransport,
+        > {
+            req_data: ImplBox<LockBox<ReqData>>,
+            transport: ImplBox<TransportBox>,
+            logger: Option<Box<dyn RequestLogger>>,
+            layers: Vec<Box<dyn Layer>>,
+            faults: Option<FaultLayer>,
+            timeout: Option<Duration>,
+            max_retries: u32,
+            initial_seq: i32,
+            batch_concurrency: usize,
+            events: ImplBox<BroadcastBox<Event>>,
+            rate_limiter: Option<RateLimiter<RuntimeT::Clock>>,
+            in_flight: Option<(ImplBox<SemaphoreBox>, OverloadPolicy)>,
+            history: Option<Mutex<History>>,
+            metrics: Option<Mutex<Metrics>>,
+            activity: Activity,
+            _r: PhantomData<fn() -> (RuntimeT, TransportT)>,
         }
+    };
+}
+
+#[cfg(any(feature = "rt-tokio", feature = "rt-async-std", feature = "rt-std"))]
+controller!(= DefaultRuntime);
+#[cfg(not(any(feature = "rt-tokio", feature = "rt-async-std", feature = "rt-std")))]
+controller!();
+
+impl<RuntimeT: Runtime, TransportT: Connector> Default for Controller<RuntimeT, TransportT> {
+    fn default() -> Self {
+        Self::from_builder(Default::default())
     }
 }
 
-impl<RuntimeT: Runtime> Controller<RuntimeT> {
+impl<RuntimeT: Runtime, TransportT: Connector> Controller<RuntimeT, TransportT> {
+    /// Create a controller. With a [DefaultRuntime], the runtime doesn't
+    /// have to be named, as in `let c: Controller = Controller::new();`.
     pub fn new() -> Self {
         Default::default()
     }
 
+    /// Create a controller that sends requests with a transport created
+    /// from `config`.
+    pub fn new_with(config: TransportT::Config) -> Self {
+        Self::from_builder(ControllerBuilder::new(config))
+    }
+
+    /// Configure a controller. See [ControllerBuilder].
+    pub fn builder() -> ControllerBuilder<RuntimeT, TransportT> {
+        Default::default()
+    }
+
+    fn from_builder(builder: ControllerBuilder<RuntimeT, TransportT>) -> Self {
+        Self {
+            req_data: RuntimeT::box_lock(ReqData::initial(builder.initial_seq)),
+            transport: TransportT::box_transport(builder.config),
+            logger: None,
+            layers: builder.layers,
+            faults: None,
+            timeout: builder.timeout,
+            max_retries: builder.max_retries,
+            initial_seq: builder.initial_seq,
+            batch_concurrency: builder.batch_concurrency,
+            events: RuntimeT::box_broadcast(event::EVENT_CAPACITY),
+            rate_limiter: builder
+                .rate_limit
+                .map(|(per_second, burst)| RateLimiter::new(RuntimeT::clock(), per_second, burst)),
+            in_flight: builder
+                .max_in_flight
+                .map(|(max, policy)| (RuntimeT::box_semaphore(max), policy)),
+            history: (builder.history > 0).then(|| Mutex::new(History::new(builder.history))),
+            metrics: builder.metrics.then(Default::default),
+            activity: Default::default(),
+            _r: Default::default(),
+        }
+    }
+
+    /// Pass every request to `logger`.
+    pub fn with_logger(mut self, logger: impl RequestLogger + 'static) -> Self {
+        self.logger = Some(Box::new(logger));
+        self
+    }
+
+    /// Inject faults into every request. This is for testing.
+    pub fn with_faults(mut self, faults: FaultLayer) -> Self {
+        self.faults = Some(faults);
+        self
+    }
+
+    #[cfg(feature = "accounting")]
+    pub async fn resource_usage(&self) -> ResourceUsage {
+        let data = self.req_data().read().await;
+        ResourceUsage {
+            cache_bytes: std::mem::size_of::<ReqData>()
+                + data.last_path.capacity()
+                + data.response.capacity(),
+            ..ResourceUsage::global()
+        }
+    }
+
+    /// Discard all request state, as if the controller had just been
+    /// created. The logger, fault layer, and subscribers are kept, and a
+    /// closed controller stays closed.
+    pub fn reset(&mut self) {
+        RuntimeT::replace_lock(&mut self.req_data, ReqData::initial(self.initial_seq));
+        if let Some(history) = &self.history {
+            history.lock().unwrap().clear();
+        }
+        self.emit(|| Event::StateChanged);
+    }
+
+    /// Whether a request panicked while updating the request state,
+    /// for example in a layer or the transport, which may have left the
+    /// state half updated. Requests then fail with
+    /// [ControllerError::Poisoned], and other methods that use the state
+    /// panic, until [Controller::reset] is called.
+    pub fn is_poisoned(&self) -> bool {
+        self.req_data.is_poisoned()
+    }
+
     fn req_data(&self) -> &(impl AsyncRwLock<ReqData> + '_) {
         RuntimeT::unbox_lock(&self.req_data)
     }
 
-    async fn request(&self, path: &str) -> Result<(), Box<dyn Error + Sync + Send>> {
-        let mut lock = self.req_data().write().await;
-        let ref_data: &mut ReqData = lock.deref_mut();
-        ref_data.seq += 1;
-        // A real implementation would make a network call here. Call await to make this
-        // non-trivially async.
-        async {
-            ref_data.last_path = format!("{path}&seq={}", ref_data.seq);
+    /// Return a guard to hold along with the write lock on the request
+    /// state. The lock itself is released normally if the request
+    /// panics, so the guard poisons the state instead.
+    fn poison_on_unwind(&self) -> PoisonOnUnwind<'_, LockBox<ReqData>> {
+        PoisonOnUnwind(&self.req_data)
+    }
+
+    fn transport(&self) -> &(impl Transport + Sync + Send + '_) {
+        TransportT::unbox_transport(&self.transport)
+    }
+
+    /// Return the time on the runtime's clock if it is needed to record
+    /// requests. See [Controller::record].
+    fn record_start(&self) -> Option<Instant> {
+        self.metrics.as_ref().map(|_| RuntimeT::clock().now())
+    }
+
+    /// Add a finished request to the history and metrics, if they are
+    /// kept. `start` is from [Controller::record_start].
+    fn record(&self, path: &str, start: Option<Instant>, result: Result<i32, &ControllerError>) {
+        if self.history.is_none() && self.metrics.is_none() {
+            return;
+        }
+        let now = RuntimeT::clock().now();
+        if let Some(history) = &self.history {
+            history.lock().unwrap().push(RequestEntry {
+                seq: result.ok(),
+                path: path.to_string(),
+                time: now,
+                outcome: result.map(|_| ()).map_err(|e| e.to_string()),
+            });
+        }
+        if let (Some(metrics), Some(start)) = (&self.metrics, start) {
+            let elapsed = now.saturating_duration_since(start);
+            metrics.lock().unwrap().add(elapsed, result);
+        }
+    }
+
+    /// Let a request for `path` start unless the controller is closed
+    /// or poisoned. A request refused here never gets to the end of the
+    /// request, so it is recorded as failing at once.
+    fn admit(&self, path: &str) -> Result<Active<'_>, ControllerError> {
+        let record_start = self.record_start();
+        let refused = match self.activity.enter() {
+            Ok(active) if !self.is_poisoned() => return Ok(active),
+            Ok(_) => ControllerError::Poisoned,
+            Err(e) => e,
+        };
+        self.record(path, record_start, Err(&refused));
+        Err(refused)
+    }
+
+    /// Return the most recent requests, oldest first. This is empty
+    /// unless the controller was built with
+    /// [ControllerBuilder::history].
+    pub fn recent_requests(&self) -> Vec<RequestEntry> {
+        match &self.history {
+            Some(history) => history.lock().unwrap().entries(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Return the controller's counters. They are all 0 unless the
+    /// controller was built with [ControllerBuilder::metrics]. They
+    /// aren't cleared by [Controller::reset].
+    pub fn metrics(&self) -> Metrics {
+        match &self.metrics {
+            Some(metrics) => metrics.lock().unwrap().clone(),
+            None => Metrics::default(),
+        }
+    }
+
+    /// Take a permit to make a request, if the number in flight is
+    /// limited. The request is in flight until the permit is dropped.
+    async fn in_flight_permit(&self) -> Result<Option<impl Sync + Send + '_>, ControllerError> {
+        let Some((semaphore, policy)) = &self.in_flight else {
+            return Ok(None);
+        };
+        let semaphore = RuntimeT::unbox_semaphore(semaphore);
+        match policy {
+            OverloadPolicy::Wait => Ok(Some(semaphore.acquire().await)),
+            OverloadPolicy::FailFast => semaphore
+                .try_acquire()
+                .map(Some)
+                .ok_or(ControllerError::Overloaded),
+        }
+    }
+
+    /// Wait until the rate limit allows another request.
+    async fn wait_for_rate_limit(&self) {
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire().await;
+        }
+    }
+
+    /// Send `path` once, through the layers and after any injected
+    /// faults.
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&str>,
+    ) -> Result<String, ControllerError> {
+        let mut path = Cow::Borrowed(path);
+        let (mut result, accepted) = match layer::request(&self.layers, &mut path) {
+            Ok(()) => (
+                self.send_unlayered(method, &path, body).await,
+                self.layers.len(),
+            ),
+            Err((accepted, e)) => (Err(e), accepted),
+        };
+        layer::response(&self.layers[..accepted], &path, &mut result);
+        result
+    }
+
+    async fn send_unlayered(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&str>,
+    ) -> Result<String, ControllerError> {
+        self.inject_faults().await?;
+        self.transport()
+            .send_with(method, path, body)
+            .await
+            .map_err(ControllerError::Transport)
+    }
+
+    /// Apply the faults the controller was built with, if any, before
+    /// anything is sent.
+    async fn inject_faults(&self) -> Result<(), ControllerError> {
+        if let Some(faults) = &self.faults {
+            faults
+                .inject()
+                .await
+                .map_err(|e| ControllerError::Transport(Box::new(e)))?;
         }
-        .await;
         Ok(())
     }
 
-    /// Send a request and return the sequence of the request.
-    pub async fn one(&self, val: i32) -> Result<i32, Box<dyn Error + Sync + Send>> {
-        if val == 3 {
-            return Err("sorry, not that one".into());
+    /// Record a finished request for `path`, send its event, and log
+    /// it. `start` and `record_start` are from when it started. A
+    /// successful result is the sequence number and the response.
+    fn finish(
+        &self,
+        path: &str,
+        start: Option<Instant>,
+        record_start: Option<Instant>,
+        result: Result<(i32, &str), &ControllerError>,
+    ) {
+        self.record(path, record_start, result.map(|(seq, _)| seq));
+        self.emit(|| match result {
+            Ok((seq, _)) => Event::RequestCompleted {
+                seq,
+                path: path.to_string(),
+            },
+            Err(e) => Event::RequestFailed {
+                path: path.to_string(),
+                error: e.to_string(),
+            },
+        });
+        if let (Some(logger), Some(start)) = (&self.logger, start) {
+            let err;
+            logger.log(&RequestRecord {
+                seq: result.ok().map(|(seq, _)| seq),
+                request: path,
+                response: match result {
+                    Ok((_, response)) => Ok(response),
+                    Err(e) => {
+                        err = e.to_string();
+                        Err(&err)
+                    }
+                },
+                elapsed: start.elapsed(),
+            });
         }
-        self.request(&format!("one?val={val}")).await?;
-        Ok(self.req_data().read().await.seq)
     }
 
-    /// Send a request and return the path of the request.
-    pub async fn two(&self, val: &str) -> Result<String, Box<dyn Error + Sync + Send>> {
-        self.request(&format!("two?val={val}")).await?;
-        Ok(self.req_data().read().await.last_path.clone())
+    /// Make a request and return a snapshot of the request data as of
+    /// the end of the request. Callers must use the snapshot rather
+    /// than reading `req_data` again since another request may have
+    /// changed it as soon as the write lock is released. A request gets
+    /// its sequence number once it has the lock, so one rejected before
+    /// then, such as by [Endpoint::request], doesn't use one, but one
+    /// that fails after that, as when the transport fails, does. If the
+    /// cancel token in `options` is cancelled first or the timeout
+    /// passes, the request is abandoned, but it may likewise have used
+    /// up a sequence number. Transport failures of idempotent
+    /// requests are retried up to the controller's `max_retries` times
+    /// with the same sequence number. See [Method::is_idempotent].
+    async fn request(
+        &self,
+        request: &Request,
+        options: &RequestOptions<'_>,
+    ) -> Result<ReqData, ControllerError> {
+        let path = &*request.path_and_query();
+        let _active = self.admit(path)?;
+        let body = request.body.as_deref();
+        // Only the logger needs the time, and std has no clock on some
+        // targets, such as wasm32-unknown-unknown.
+        let start = self.logger.as_ref().map(|_| Instant::now());
+        let record_start = self.record_start();
+        #[cfg(feature = "tracing")]
+        let traced = RuntimeT::clock().now();
+        self.emit(|| Event::RequestStarted {
+            path: path.to_string(),
+        });
+        let req = async {
+            let _permit = self.in_flight_permit().await?;
+            self.wait_for_rate_limit().await;
+            let mut lock = base::trace_future!(self.req_data().write(), "controller.lock").await;
+            let _poison = self.poison_on_unwind();
+            let ref_data: &mut ReqData = lock.deref_mut();
+            ref_data.seq = ref_data.seq.wrapping_add(1);
+            let full_path = request.with_seq(ref_data.seq);
+            let mut retries = 0;
+            ref_data.response = loop {
+                // Box each attempt so that the transport's future isn't
+                // inlined into this one, which keeps the request future
+                // small enough for loom's coroutine stacks.
+                match Box::pin(self.send(request.method, &full_path, body)).await {
+                    Ok(response) => break response,
+                    Err(ControllerError::Transport(_))
+                        if retries < self.max_retries && request.method.is_idempotent() =>
+                    {
+                        retries += 1;
+                        base::trace_event!(seq = ref_data.seq, retries, "retrying request");
+                    }
+                    Err(e) => return Err(e),
+                }
+            };
+            ref_data.last_path = full_path;
+            base::trace_event!(seq = ref_data.seq, "request complete");
+            Ok(ref_data.clone())
+        };
+        let req = base::trace_future!(req, "controller.request", path);
+        let req = async {
+            let timeout = match (self.timeout, options.timeout) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+            match timeout {
+                None => req.await,
+                Some(timeout) => RuntimeT::timeout(timeout, req)
+                    .await
+                    .unwrap_or_else(|_| Err(ControllerError::Timeout)),
+            }
+        };
+        let result: Result<ReqData, ControllerError> = match options.cancel {
+            None => req.await,
+            Some(cancel) => run_until_cancelled(RuntimeT::unbox_cancel_token(cancel), req)
+                .await
+                .unwrap_or_else(|| Err(ControllerError::Cancelled)),
+        };
+        base::trace_event!(
+            path,
+            seq = ?result.as_ref().ok().map(|data| data.seq),
+            elapsed = ?RuntimeT::clock().now().saturating_duration_since(traced),
+            error = ?result.as_ref().err().map(|e| e.to_string()),
+            "request finished"
+        );
+        self.finish(
+            path,
+            start,
+            record_start,
+            result
+                .as_ref()
+                .map(|data| (data.seq, data.response.as_str())),
+        );
+        result
+    }
+
+    /// Return the path of the most recent request, or an empty string if
+    /// there hasn't been one. Requests wait while the guard is held.
+    pub async fn last_path(&self) -> MappedReadGuard<'_, str> {
+        MappedReadGuard::map(self.req_data().read().await, |d| d.last_path.as_str())
+    }
+
+    /// Like [Controller::last_path], but blocks the thread so it can be
+    /// called from code that isn't async. See
+    /// [AsyncRwLock::blocking_read].
+    pub fn blocking_last_path(&self) -> MappedReadGuard<'_, str> {
+        MappedReadGuard::map(self.req_data().blocking_read(), |d| d.last_path.as_str())
+    }
+
+    /// Make the request described by `endpoint` and return its output.
+    /// See [endpoint].
+    pub async fn call<T: Endpoint>(&self, endpoint: &T) -> Result<T::Output, ControllerError> {
+        self.call_with(endpoint, &RequestOptions::default()).await
+    }
+
+    /// Like [Controller::call], with `options` for this request.
+    pub async fn call_with<T: Endpoint>(
+        &self,
+        endpoint: &T,
+        options: &RequestOptions<'_>,
+    ) -> Result<T::Output, ControllerError> {
+        let request = endpoint.request()?;
+        let data = self.request(&request, options).await?;
+        endpoint.response(Response {
+            seq: data.seq,
+            body: data.response,
+        })
+    }
+
+    /// Send a request and return the sequence of the request.
+    pub async fn one(&self, val: i32) -> Result<i32, ControllerError> {
+        self.one_with(val, &RequestOptions::default()).await
+    }
+
+    /// Like [Controller::one], with `options` for this request.
+    pub async fn one_with(
+        &self,
+        val: i32,
+        options: &RequestOptions<'_>,
+    ) -> Result<i32, ControllerError> {
+        self.call_with(&endpoint::One(val), options).await
+    }
+
+    /// Send a request and return the response, which, with the default
+    /// [LoopbackTransport], is the path of the request.
+    pub async fn two(&self, val: &str) -> Result<String, ControllerError> {
+        self.two_with(val, &RequestOptions::default()).await
+    }
+
+    /// Like [Controller::two], with `options` for this request.
+    pub async fn two_with(
+        &self,
+        val: &str,
+        options: &RequestOptions<'_>,
+    ) -> Result<String, ControllerError> {
+        self.call_with(&endpoint::Two(val), options).await
     }
 }
 
+#[cfg(all(test, feature = "loom"))]
+mod loom_tests;
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use base::fault::{ChaosRuntime, Fault, FaultRuntime, Faults, Latency, Scenario};
+    use base::{
+        AsyncChannel, AsyncStream, CancelToken, Elapsed, Executor, JoinHandle, Locker,
+        MockTransport, SendError, TryRecvError,
+    };
+    use proptest::prelude::*;
     use runtime_tokio::TokioRuntime;
+    use std::sync::Arc;
+    use std::time::Duration;
 
     #[tokio::test]
     async fn test_basic() {
@@ -81,4 +641,1134 @@ mod tests {
         );
         assert_eq!(c.two("potato").await.unwrap(), "two?val=potato&seq=2");
     }
+
+    #[cfg(feature = "rt-tokio")]
+    #[tokio::test]
+    async fn test_default_runtime() {
+        let c: Controller = Controller::new();
+        assert_eq!(c.one(5).await.unwrap(), 1);
+        let _: &Controller<TokioRuntime> = &c;
+        // Naming a runtime still overrides the default.
+        let c = Controller::<runtime_std::StdRuntime>::new();
+        assert_eq!(c.one(5).await.unwrap(), 1);
+    }
+
+    #[async_std::test]
+    async fn test_async_std() {
+        let c = Controller::<runtime_async_std::AsyncStdRuntime>::new();
+        assert_eq!(c.one(5).await.unwrap(), 1);
+        assert_eq!(c.two("potato").await.unwrap(), "two?val=potato&seq=2");
+        assert_eq!(&*c.last_path().await, "two?val=potato&seq=2");
+    }
+
+    #[test]
+    fn test_smol() {
+        type R = runtime_smol::SmolRuntime;
+        R::new_executor().unwrap().block_on(async {
+            let c = Controller::<R>::new();
+            assert_eq!(c.one(5).await.unwrap(), 1);
+            assert_eq!(c.two("potato").await.unwrap(), "two?val=potato&seq=2");
+        });
+    }
+
+    #[test]
+    fn test_std() {
+        type R = runtime_std::StdRuntime;
+        R::new_executor().unwrap().block_on(async {
+            let c = Controller::<R>::new();
+            assert_eq!(c.one(5).await.unwrap(), 1);
+            assert_eq!(c.two("potato").await.unwrap(), "two?val=potato&seq=2");
+            assert_eq!(&*c.blocking_last_path(), "two?val=potato&seq=2");
+        });
+    }
+
+    #[test]
+    fn test_futures() {
+        type R = runtime_futures::FuturesRuntime;
+        R::new_executor().unwrap().block_on(async {
+            let c = Controller::<R>::new();
+            assert_eq!(c.one(5).await.unwrap(), 1);
+            assert_eq!(c.two("potato").await.unwrap(), "two?val=potato&seq=2");
+        });
+    }
+
+    #[test]
+    fn test_wasm() {
+        // Requests only need the lock, so they can run natively.
+        type R = runtime_wasm::WasmRuntime;
+        base::StdExecutor.block_on(async {
+            let c = Controller::<R>::new();
+            assert_eq!(c.one(5).await.unwrap(), 1);
+            assert_eq!(&*c.blocking_last_path(), "one?val=5&seq=1");
+        });
+    }
+
+    #[test]
+    fn test_deterministic() {
+        // Which concurrent request gets which sequence number depends
+        // only on the seed.
+        type R = runtime_test::TestRuntime;
+        let seqs = |seed| {
+            runtime_test::TestExecutor::new(seed).block_on(async {
+                let c = Arc::new(Controller::<R>::new());
+                let handles: Vec<_> = (10..18)
+                    .map(|i| {
+                        let c = c.clone();
+                        R::spawn(Box::pin(async move { c.one(i).await.unwrap() }))
+                    })
+                    .collect();
+                let mut seqs = Vec::new();
+                for mut h in handles {
+                    seqs.push(h.join().await.unwrap());
+                }
+                seqs
+            })
+        };
+        let first = seqs(1);
+        assert_eq!(seqs(1), first);
+        assert!((2..10).any(|seed| seqs(seed) != first));
+    }
+
+    #[tokio::test]
+    async fn test_last_path() {
+        let c = Controller::<TokioRuntime>::new();
+        assert_eq!(&*c.last_path().await, "");
+        c.two("potato").await.unwrap();
+        assert_eq!(&*c.last_path().await, "two?val=potato&seq=1");
+    }
+
+    #[tokio::test]
+    async fn test_reset() {
+        let mut c = Controller::<TokioRuntime>::new();
+        assert_eq!(c.one(5).await.unwrap(), 1);
+        assert_eq!(c.one(5).await.unwrap(), 2);
+        c.reset();
+        assert_eq!(c.one(5).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_poison() {
+        use layer::Layer;
+
+        struct Boom;
+
+        impl Layer for Boom {
+            fn request(&self, path: &mut String) -> Result<(), ControllerError> {
+                assert!(!path.contains("boom"), "boom");
+                Ok(())
+            }
+        }
+
+        let c = Arc::new(
+            Controller::<TokioRuntime>::builder()
+                .layer(Boom)
+                .metrics()
+                .build()
+                .unwrap(),
+        );
+        assert_eq!(c.one(5).await.unwrap(), 1);
+        let c2 = c.clone();
+        let e = tokio::spawn(async move { c2.two("boom").await })
+            .await
+            .unwrap_err();
+        assert!(e.is_panic());
+        // The panic happened while the request state was locked.
+        assert!(c.is_poisoned());
+        assert!(matches!(c.one(5).await, Err(ControllerError::Poisoned)));
+        assert!(matches!(
+            c.stream("x").await,
+            Err(ControllerError::Poisoned)
+        ));
+        assert_eq!(c.metrics().errors.poisoned, 2);
+        let health = c.health().await;
+        assert!(health.poisoned);
+        assert!(!health.is_live());
+        let mut c = Arc::into_inner(c).unwrap();
+        c.reset();
+        assert!(!c.is_poisoned());
+        assert_eq!(c.one(5).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_faults() {
+        let scenario = Scenario {
+            error_rate: 0.5,
+            drop_on: Some(3),
+            ..Default::default()
+        };
+        let c = Controller::<TokioRuntime>::new().with_faults(FaultLayer::new(scenario));
+        let mut results = Vec::new();
+        for i in 0..20 {
+            results.push(c.one(i + 10).await.map_err(|e| e.to_string()));
+        }
+        assert_eq!(
+            results[2],
+            Err("transport error: connection dropped".to_string())
+        );
+        let failed = results
+            .iter()
+            .filter(|r| {
+                r.as_ref()
+                    .is_err_and(|e| e == "transport error: injected fault")
+            })
+            .count();
+        assert!(failed > 0 && failed < 19);
+        // Failed requests still use up a sequence number.
+        for (i, r) in results.iter().enumerate() {
+            if let Ok(seq) = r {
+                assert_eq!(*seq, i as i32 + 1);
+            }
+        }
+        // Streams get the same faults.
+        let scenario = Scenario {
+            error_rate: 1.0,
+            ..Default::default()
+        };
+        let c = Controller::<TokioRuntime>::new().with_faults(FaultLayer::new(scenario));
+        let e = c.stream("events").await.err().unwrap();
+        assert_eq!(e.to_string(), "transport error: injected fault");
+    }
+
+    #[tokio::test]
+    async fn test_mock_transport() {
+        let mock = MockTransport::new();
+        mock.respond("potato");
+        mock.fail(Fault::Dropped);
+        mock.push(Duration::from_secs(10), Ok("late".to_string()));
+        let c = Controller::<TokioRuntime, MockTransport>::new_with(mock.clone());
+        assert_eq!(c.two("a").await.unwrap(), "potato");
+        let e = c.two("b").await.unwrap_err();
+        let ControllerError::Transport(source) = &e else {
+            panic!("{e}");
+        };
+        assert_eq!(source.downcast_ref::<Fault>(), Some(&Fault::Dropped));
+        // A failed request isn't recorded as the last one.
+        assert_eq!(&*c.last_path().await, "two?val=a&seq=1");
+        let token = TokioRuntime::box_cancel_token(None);
+        let cancel = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            TokioRuntime::unbox_cancel_token(&token).cancel();
+        };
+        let options = RequestOptions::default().with_cancel(&token);
+        let (result, ()) = tokio::join!(c.two_with("c", &options), cancel);
+        assert_eq!(result.unwrap_err().to_string(), "request was cancelled");
+        // Once the script is used up, the path comes back.
+        assert_eq!(mock.remaining(), 0);
+        assert_eq!(c.two("d").await.unwrap(), "two?val=d&seq=4");
+        assert_eq!(
+            mock.requests(),
+            [
+                "two?val=a&seq=1",
+                "two?val=b&seq=2",
+                "two?val=c&seq=3",
+                "two?val=d&seq=4"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_builder() {
+        let mock = MockTransport::new();
+        mock.fail(Fault::Dropped);
+        mock.fail(Fault::Injected);
+        mock.respond("potato");
+        let mut c = Controller::<TokioRuntime, MockTransport>::builder()
+            .transport(mock.clone())
+            .base_url("http://localhost/api")
+            .user_agent("salad/1.0")
+            .max_retries(2)
+            .initial_seq(100)
+            .timeout(Duration::from_millis(50))
+            .build()
+            .unwrap();
+        assert_eq!(mock.base_url().as_deref(), Some("http://localhost/api"));
+        assert_eq!(mock.user_agent().as_deref(), Some("salad/1.0"));
+        // Retries resend the same request.
+        assert_eq!(c.two("a").await.unwrap(), "potato");
+        assert_eq!(mock.requests(), ["two?val=a&seq=100"; 3]);
+        mock.fail(Fault::Dropped);
+        mock.fail(Fault::Dropped);
+        mock.fail(Fault::Dropped);
+        assert_eq!(
+            c.one(1).await.unwrap_err().to_string(),
+            "transport error: connection dropped"
+        );
+        mock.push(Duration::from_secs(10), Ok("late".to_string()));
+        let e = c.one(1).await.unwrap_err();
+        assert!(matches!(e, ControllerError::Timeout));
+        c.reset();
+        assert_eq!(c.one(1).await.unwrap(), 100);
+    }
+
+    #[tokio::test]
+    async fn test_max_in_flight() {
+        use builder::OverloadPolicy;
+        let mock = MockTransport::new();
+        let c = Controller::<TokioRuntime, MockTransport>::builder()
+            .transport(mock.clone())
+            .max_in_flight(1, OverloadPolicy::FailFast)
+            .build()
+            .unwrap();
+        mock.push(Duration::from_millis(50), Ok("slow".to_string()));
+        let (a, b) = tokio::join!(c.two("a"), c.two("b"));
+        assert_eq!(a.unwrap(), "slow");
+        assert!(matches!(b.unwrap_err(), ControllerError::Overloaded));
+        // The permit is returned when the request finishes.
+        assert_eq!(c.two("c").await.unwrap(), "two?val=c&seq=2");
+        let c = Controller::<TokioRuntime, MockTransport>::builder()
+            .transport(mock.clone())
+            .max_in_flight(1, OverloadPolicy::Wait)
+            .build()
+            .unwrap();
+        mock.push(Duration::from_millis(50), Ok("slow".to_string()));
+        let (a, b) = tokio::join!(c.two("a"), c.two("b"));
+        assert_eq!(a.unwrap(), "slow");
+        assert_eq!(b.unwrap(), "two?val=b&seq=2");
+        let e = Controller::<TokioRuntime>::builder()
+            .max_in_flight(0, OverloadPolicy::Wait)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(e.to_string(), "max in flight must not be 0");
+    }
+
+    #[tokio::test]
+    async fn test_history() {
+        let mock = MockTransport::new();
+        let mut c = Controller::<TokioRuntime, MockTransport>::builder()
+            .transport(mock.clone())
+            .history(2)
+            .build()
+            .unwrap();
+        let start = TokioRuntime::clock().now();
+        c.one(5).await.unwrap();
+        c.two("a").await.unwrap();
+        mock.fail(Fault::Dropped);
+        c.two("b").await.unwrap_err();
+        let recent = c.recent_requests();
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].seq, Some(2));
+        assert_eq!(recent[0].path, "two?val=a");
+        assert_eq!(recent[0].outcome, Ok(()));
+        assert_eq!(recent[1].seq, None);
+        assert_eq!(recent[1].path, "two?val=b");
+        assert_eq!(
+            recent[1].outcome,
+            Err("transport error: connection dropped".to_string())
+        );
+        assert!(start <= recent[0].time && recent[0].time <= recent[1].time);
+        c.reset();
+        assert!(c.recent_requests().is_empty());
+        // No history is kept by default.
+        let c = Controller::<TokioRuntime>::new();
+        c.one(5).await.unwrap();
+        assert!(c.recent_requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_metrics() {
+        let mock = MockTransport::new();
+        let mut c = Controller::<TokioRuntime, MockTransport>::builder()
+            .transport(mock.clone())
+            .metrics()
+            .build()
+            .unwrap();
+        c.one(5).await.unwrap();
+        c.two("a").await.unwrap();
+        mock.fail(Fault::Dropped);
+        c.two("b").await.unwrap_err();
+        let metrics = c.metrics();
+        assert_eq!(metrics.requests, 3);
+        assert_eq!(metrics.errors.transport, 1);
+        assert_eq!(metrics.errors.total(), 1);
+        assert_eq!(metrics.latency.counts.iter().sum::<u64>(), 3);
+        assert_eq!(metrics.seq, Some(2));
+        // Counts are kept across a reset.
+        c.reset();
+        assert_eq!(c.metrics(), metrics);
+        // Requests refused after closing are counted.
+        c.close(Duration::ZERO).await.unwrap();
+        assert!(matches!(c.one(5).await, Err(ControllerError::Closed)));
+        assert!(matches!(c.stream("a").await, Err(ControllerError::Closed)));
+        let metrics = c.metrics();
+        assert_eq!(metrics.requests, 5);
+        assert_eq!(metrics.errors.closed, 2);
+        assert_eq!(metrics.errors.total(), 3);
+        // Nothing is counted by default.
+        let c = Controller::<TokioRuntime>::new();
+        c.one(5).await.unwrap();
+        assert_eq!(c.metrics(), metrics::Metrics::default());
+    }
+
+    #[tokio::test]
+    async fn test_close() {
+        let c = Controller::<TokioRuntime>::new();
+        let mut events = c.subscribe();
+        // Hold up a request so that closing has to wait for it.
+        let lock = c.req_data().write().await;
+        let (one, closed) = tokio::join!(c.one(5), async {
+            assert!(matches!(
+                c.close(Duration::from_millis(10)).await,
+                Err(ControllerError::Timeout)
+            ));
+            assert!(c.is_closed());
+            assert!(matches!(c.two("a").await, Err(ControllerError::Closed)));
+            assert!(matches!(c.stream("a").await, Err(ControllerError::Closed)));
+            drop(lock);
+            c.close(Duration::from_secs(1)).await
+        });
+        assert_eq!(one.unwrap(), 1);
+        closed.unwrap();
+        // Closing again doesn't wait or send another event.
+        c.close(Duration::ZERO).await.unwrap();
+        drop(c);
+        let mut seen = Vec::new();
+        while let Some(event) = events.next().await {
+            seen.push(event);
+        }
+        assert_eq!(
+            seen,
+            [
+                Event::RequestStarted {
+                    path: "one?val=5".to_string()
+                },
+                Event::Closed,
+                Event::RequestCompleted {
+                    seq: 1,
+                    path: "one?val=5".to_string()
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_health() {
+        let mock = MockTransport::new();
+        let c = Controller::<TokioRuntime, MockTransport>::builder()
+            .transport(mock.clone())
+            .max_in_flight(2, OverloadPolicy::Wait)
+            .build()
+            .unwrap();
+        let health = c.health().await;
+        assert_eq!(
+            health,
+            health::HealthStatus {
+                transport: Ok(()),
+                closed: false,
+                poisoned: false,
+                locked: false,
+                in_progress: 0,
+                in_flight_available: Some(2),
+            }
+        );
+        assert!(health.is_ready());
+        assert_eq!(mock.pings(), 1);
+        // A request waiting for the lock shows up.
+        let lock = c.req_data().write().await;
+        let (one, health) = tokio::join!(c.one(5), async {
+            let health = c.health().await;
+            drop(lock);
+            health
+        });
+        one.unwrap();
+        assert!(health.locked);
+        assert_eq!(health.in_progress, 1);
+        assert_eq!(health.in_flight_available, Some(1));
+        mock.fail_pings(true);
+        let health = c.health().await;
+        assert_eq!(health.transport, Err("ping failed".to_string()));
+        assert!(health.is_live() && !health.is_ready());
+        mock.fail_pings(false);
+        c.close(Duration::ZERO).await.unwrap();
+        assert!(!c.health().await.is_ready());
+        // Pings aren't requests.
+        assert_eq!(mock.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_call() {
+        struct Create<'a>(&'a str);
+
+        impl Endpoint for Create<'_> {
+            type Output = usize;
+
+            fn request(&self) -> Result<Request, ControllerError> {
+                Ok(Request::post("items").body(self.0))
+            }
+
+            fn response(&self, response: Response) -> Result<usize, ControllerError> {
+                response
+                    .body
+                    .parse()
+                    .map_err(|_| ControllerError::InvalidInput(response.body))
+            }
+        }
+
+        let mock = MockTransport::new();
+        let c = Controller::<TokioRuntime, MockTransport>::new_with(mock.clone());
+        mock.respond("12");
+        assert_eq!(c.call(&Create("salad")).await.unwrap(), 12);
+        // The response is checked after the request has used its
+        // sequence number.
+        let e = c.call(&Create("potato")).await.unwrap_err();
+        assert_eq!(e.to_string(), "items?seq=2");
+        assert_eq!(mock.bodies(), ["salad", "potato"]);
+        assert_eq!(c.two("a b").await.unwrap(), "two?val=a%20b&seq=3");
+        assert_eq!(
+            mock.requests(),
+            ["items?seq=1", "items?seq=2", "two?val=a%20b&seq=3"]
+        );
+        // Only the loopback and mock transports answer every method.
+        let c = Controller::<TokioRuntime, NoBodies>::new();
+        assert_eq!(c.one(5).await.unwrap(), 1);
+        let e = c.call(&Create("salad")).await.unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "transport error: POST requests aren't supported"
+        );
+        struct Remove;
+        impl Endpoint for Remove {
+            type Output = ();
+            fn request(&self) -> Result<Request, ControllerError> {
+                Ok(Request::delete("items").param("id", 7))
+            }
+            fn response(&self, _: Response) -> Result<(), ControllerError> {
+                Ok(())
+            }
+        }
+        let e = c.call(&Remove).await.unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "transport error: DELETE requests aren't supported"
+        );
+        // Only idempotent requests are retried.
+        let mock = MockTransport::new();
+        let c = Controller::<TokioRuntime, MockTransport>::builder()
+            .transport(mock.clone())
+            .max_retries(1)
+            .build()
+            .unwrap();
+        mock.fail("oops");
+        assert_eq!(
+            c.call(&Create("salad")).await.unwrap_err().to_string(),
+            "transport error: oops"
+        );
+        mock.fail("oops");
+        c.call(&Remove).await.unwrap();
+        assert_eq!(
+            mock.requests(),
+            ["items?seq=1", "items?id=7&seq=2", "items?id=7&seq=2"]
+        );
+    }
+
+    /// A transport that only has [Transport::send]
+    #[derive(Default)]
+    struct NoBodies;
+
+    impl Transport for NoBodies {
+        async fn send(
+            &self,
+            path: &str,
+        ) -> Result<String, Box<dyn std::error::Error + Sync + Send>> {
+            Ok(path.to_string())
+        }
+    }
+
+    impl Connector for NoBodies {
+        type Config = ();
+
+        #[implbox_macros::implbox_impls(TransportBox, NoBodies)]
+        fn new_transport(_config: ()) -> impl Transport + Sync + Send {
+            NoBodies
+        }
+    }
+
+    #[tokio::test]
+    async fn test_encoding() {
+        // two's value is a query parameter, so it is percent-encoded
+        // rather than pasted into the path, and it can't add parameters.
+        let mock = MockTransport::new();
+        let c = Controller::<TokioRuntime, MockTransport>::new_with(mock.clone());
+        assert_eq!(c.two("a b").await.unwrap(), "two?val=a%20b&seq=1");
+        assert_eq!(c.two("x&seq=9").await.unwrap(), "two?val=x%26seq%3D9&seq=2");
+        assert_eq!(
+            mock.requests(),
+            ["two?val=a%20b&seq=1", "two?val=x%26seq%3D9&seq=2"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        let c = Controller::<TokioRuntime>::builder()
+            .rate_limit(0.5, 1)
+            .timeout(Duration::from_millis(20))
+            .build()
+            .unwrap();
+        assert_eq!(c.one(5).await.unwrap(), 1);
+        // The next token is two seconds away, and waiting for it counts
+        // toward the timeout.
+        let e = c.one(5).await.unwrap_err();
+        assert!(matches!(e, ControllerError::Timeout));
+        for (per_second, burst) in [(0.0, 1), (f64::NAN, 1), (1.0, 0)] {
+            let e = Controller::<TokioRuntime>::builder()
+                .rate_limit(per_second, burst)
+                .build()
+                .err()
+                .unwrap();
+            assert!(matches!(e, ControllerError::InvalidInput(_)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch() {
+        use batch::{BatchRequest, BatchResponse};
+        let c = std::sync::Arc::new(
+            Controller::<TokioRuntime>::builder()
+                .batch_concurrency(2)
+                .build()
+                .unwrap(),
+        );
+        let requests = vec![
+            BatchRequest::One(5),
+            BatchRequest::Two("potato".to_string()),
+            BatchRequest::One(3),
+            BatchRequest::Two("salad".to_string()),
+        ];
+        // The batch can run on another thread.
+        let task = tokio::spawn({
+            let c = c.clone();
+            async move { c.batch(requests).await }
+        });
+        let results = task.await.unwrap();
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].as_ref().unwrap(), &BatchResponse::One(1));
+        assert_eq!(
+            results[1].as_ref().unwrap(),
+            &BatchResponse::Two("two?val=potato&seq=2".to_string())
+        );
+        assert_eq!(
+            results[2].as_ref().unwrap_err().to_string(),
+            "sorry, not that one"
+        );
+        assert_eq!(
+            results[3].as_ref().unwrap(),
+            &BatchResponse::Two("two?val=salad&seq=3".to_string())
+        );
+        assert!(c.batch(Vec::new()).await.is_empty());
+        let e = Controller::<TokioRuntime>::builder()
+            .batch_concurrency(0)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(e.to_string(), "batch concurrency must not be 0");
+    }
+
+    #[tokio::test]
+    async fn test_stream() {
+        let mock = MockTransport::new();
+        let c = Controller::<TokioRuntime, MockTransport>::new_with(mock.clone());
+        assert_eq!(c.one(5).await.unwrap(), 1);
+        let mut stream = c.stream("events").await.unwrap();
+        assert_eq!(&*c.last_path().await, "events?seq=2");
+        // Reading the stream doesn't hold up other requests.
+        assert_eq!(c.two("a").await.unwrap(), "two?val=a&seq=3");
+        assert_eq!(stream.next().await.unwrap().unwrap(), b"events?seq=2");
+        assert!(stream.next().await.is_none());
+        assert!(stream.next().await.is_none());
+        let mut stream = c.stream("events?since=4").await.unwrap();
+        assert_eq!(
+            stream.next().await.unwrap().unwrap(),
+            b"events?since=4&seq=4"
+        );
+        mock.fail(Fault::Dropped);
+        let e = c.stream("events").await.err().unwrap();
+        assert_eq!(e.to_string(), "transport error: connection dropped");
+        assert_eq!(&*c.last_path().await, "events?since=4&seq=4");
+    }
+
+    #[tokio::test]
+    async fn test_subscribe() {
+        use event::Event;
+        let mock = MockTransport::new();
+        let mut c = Controller::<TokioRuntime, MockTransport>::new_with(mock.clone());
+        // Nothing before subscribing is seen.
+        c.one(5).await.unwrap();
+        let mut events = c.subscribe();
+        c.two("potato").await.unwrap();
+        // Invalid input is rejected before the request is made.
+        c.one(3).await.unwrap_err();
+        mock.fail(Fault::Dropped);
+        c.one(4).await.unwrap_err();
+        c.reset();
+        assert_eq!(
+            events.next().await.unwrap(),
+            Event::RequestStarted {
+                path: "two?val=potato".to_string()
+            }
+        );
+        assert_eq!(
+            events.next().await.unwrap(),
+            Event::RequestCompleted {
+                seq: 2,
+                path: "two?val=potato".to_string()
+            }
+        );
+        assert_eq!(
+            events.next().await.unwrap(),
+            Event::RequestStarted {
+                path: "one?val=4".to_string()
+            }
+        );
+        assert_eq!(
+            events.next().await.unwrap(),
+            Event::RequestFailed {
+                path: "one?val=4".to_string(),
+                error: "transport error: connection dropped".to_string()
+            }
+        );
+        assert_eq!(events.next().await.unwrap(), Event::StateChanged);
+        // A subscriber that falls behind skips to the oldest event kept.
+        for i in 0..event::EVENT_CAPACITY {
+            c.one(i as i32 + 10).await.unwrap();
+        }
+        assert_eq!(
+            events.next().await.unwrap(),
+            Event::RequestStarted {
+                path: format!("one?val={}", event::EVENT_CAPACITY / 2 + 10)
+            }
+        );
+        drop(c);
+        let mut n = 0;
+        while events.next().await.is_some() {
+            n += 1;
+        }
+        assert_eq!(n, event::EVENT_CAPACITY - 1);
+    }
+
+    #[tokio::test]
+    async fn test_layers() {
+        use layer::Layer;
+        use std::sync::Mutex;
+
+        /// Add a parameter, and record the path sent for each response
+        struct Tag(&'static str, Arc<Mutex<Vec<String>>>);
+
+        impl Layer for Tag {
+            fn request(&self, path: &mut String) -> Result<(), ControllerError> {
+                if path.contains("forbidden") {
+                    return Err(ControllerError::InvalidInput("forbidden".to_string()));
+                }
+                path.push_str(self.0);
+                Ok(())
+            }
+
+            fn response(&self, path: &str, result: &mut Result<String, ControllerError>) {
+                self.1.lock().unwrap().push(format!("{}: {path}", self.0));
+                if let Ok(body) = result {
+                    body.push_str(self.0);
+                }
+            }
+        }
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mock = MockTransport::new();
+        let c = Controller::<TokioRuntime, MockTransport>::builder()
+            .transport(mock.clone())
+            .layer(Tag("&a", seen.clone()))
+            .layer(Tag("&b", seen.clone()))
+            .build()
+            .unwrap();
+        // The first layer is outermost.
+        assert_eq!(c.two("x").await.unwrap(), "two?val=x&seq=1&a&b&b&a");
+        assert_eq!(mock.requests(), ["two?val=x&seq=1&a&b"]);
+        assert_eq!(
+            *seen.lock().unwrap(),
+            ["&b: two?val=x&seq=1&a&b", "&a: two?val=x&seq=1&a&b"]
+        );
+        // The last path is what the controller asked for.
+        assert_eq!(&*c.last_path().await, "two?val=x&seq=1");
+        // A rejected request isn't sent.
+        seen.lock().unwrap().clear();
+        let e = c.two("forbidden").await.unwrap_err();
+        assert_eq!(e.to_string(), "forbidden");
+        assert_eq!(mock.requests().len(), 1);
+        assert!(seen.lock().unwrap().is_empty());
+        let mut stream = c.stream("events").await.unwrap();
+        assert_eq!(stream.next().await.unwrap().unwrap(), b"events?seq=3&a&b");
+        // A layer's rejection isn't retried, but a transport failure is.
+        seen.lock().unwrap().clear();
+        let c = Controller::<TokioRuntime, MockTransport>::builder()
+            .transport(mock.clone())
+            .max_retries(2)
+            .layer(Tag("&a", seen.clone()))
+            .build()
+            .unwrap();
+        assert_eq!(
+            c.two("forbidden").await.unwrap_err().to_string(),
+            "forbidden"
+        );
+        assert!(seen.lock().unwrap().is_empty());
+        mock.fail("oops");
+        assert_eq!(c.two("x").await.unwrap(), "two?val=x&seq=2&a&a");
+        assert_eq!(seen.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout() {
+        let mock = MockTransport::new();
+        let c = Controller::<TokioRuntime, MockTransport>::builder()
+            .transport(mock.clone())
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap();
+        let options = RequestOptions::default().with_timeout(Duration::from_millis(20));
+        // The shorter timeout wins.
+        mock.push(Duration::from_secs(5), Ok("late".to_string()));
+        let e = c.one_with(1, &options).await.unwrap_err();
+        assert!(matches!(e, ControllerError::Timeout));
+        // Waiting for the lock counts toward the timeout.
+        let guard = c.last_path().await;
+        let e = c.two_with("a", &options).await.unwrap_err();
+        assert!(matches!(e, ControllerError::Timeout));
+        drop(guard);
+        assert_eq!(c.two_with("b", &options).await.unwrap(), "two?val=b&seq=2");
+    }
+
+    #[tokio::test]
+    async fn test_cancel() {
+        let scenario = Scenario {
+            latency: Latency::Fixed(Duration::from_secs(10)),
+            ..Default::default()
+        };
+        let c = Controller::<TokioRuntime>::new().with_faults(FaultLayer::new(scenario));
+        let parent = TokioRuntime::box_cancel_token(None);
+        let token = TokioRuntime::box_cancel_token(Some(&parent));
+        let cancel = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            TokioRuntime::unbox_cancel_token(&parent).cancel();
+        };
+        let options = RequestOptions::default().with_cancel(&token);
+        let (result, ()) = tokio::join!(c.one_with(5, &options), cancel);
+        assert!(matches!(result.err().unwrap(), ControllerError::Cancelled));
+        // A request with a cancelled token is never started.
+        assert_eq!(
+            c.two_with("potato", &options)
+                .await
+                .err()
+                .unwrap()
+                .to_string(),
+            "request was cancelled"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fault_runtime() {
+        struct SlowLocks;
+        impl Faults for SlowLocks {
+            fn scenario() -> Scenario {
+                Scenario {
+                    wakeup_delay: Latency::Uniform(Duration::ZERO, Duration::from_millis(2)),
+                    ..Default::default()
+                }
+            }
+        }
+        let c = Arc::new(Controller::<FaultRuntime<TokioRuntime, SlowLocks>>::new());
+        let handles: Vec<_> = (0..10)
+            .map(|_| {
+                let c = c.clone();
+                tokio::spawn(async move { c.one(1).await.unwrap() })
+            })
+            .collect();
+        let mut seqs = Vec::new();
+        for h in handles {
+            seqs.push(h.await.unwrap());
+        }
+        seqs.sort();
+        assert_eq!(seqs, (1..=10).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_chaos_runtime() {
+        // Delayed and reordered lock acquisitions don't change which
+        // sequence numbers are handed out, and a cancelled request still
+        // fails.
+        type R = ChaosRuntime<TokioRuntime>;
+        let c = Arc::new(Controller::<R>::new());
+        let handles: Vec<_> = (0..10)
+            .map(|_| {
+                let c = c.clone();
+                tokio::spawn(async move { c.one(1).await.unwrap() })
+            })
+            .collect();
+        let mut seqs = Vec::new();
+        for h in handles {
+            seqs.push(h.await.unwrap());
+        }
+        seqs.sort();
+        assert_eq!(seqs, (1..=10).collect::<Vec<_>>());
+        let token = R::box_cancel_token(None);
+        R::unbox_cancel_token(&token).cancel();
+        assert_eq!(
+            c.one_with(5, &RequestOptions::default().with_cancel(&token))
+                .await
+                .err()
+                .unwrap()
+                .to_string(),
+            "request was cancelled"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_chaos_retry() {
+        struct Flaky;
+        impl Faults for Flaky {
+            fn scenario() -> Scenario {
+                Scenario {
+                    reorder_rate: 1.0,
+                    spurious_timeout_rate: 0.5,
+                    lost_message_rate: 1.0,
+                    ..Default::default()
+                }
+            }
+        }
+        type R = ChaosRuntime<TokioRuntime, Flaky>;
+        let c = Controller::<R>::new();
+        // A caller that retries after a timeout always gets through, and
+        // a request abandoned by a spurious timeout never started, so it
+        // didn't use up a sequence number.
+        let mut timeouts = 0;
+        for i in 1..=10 {
+            let seq = loop {
+                match R::timeout(Duration::from_secs(10), c.one(5)).await {
+                    Ok(result) => break result.unwrap(),
+                    Err(Elapsed) => timeouts += 1,
+                }
+            };
+            assert_eq!(seq, i);
+        }
+        assert!(timeouts > 0);
+        // Lost messages are accepted but never received.
+        let ch = R::new_channel(Some(1));
+        ch.try_send(1).unwrap();
+        ch.send(2).await.unwrap();
+        assert_eq!(ch.try_recv(), Err(TryRecvError::Empty));
+        ch.close();
+        assert_eq!(ch.send(3).await, Err(SendError::Closed(3)));
+    }
+
+    #[cfg(feature = "accounting")]
+    #[tokio::test]
+    async fn test_resource_usage() {
+        let c = Controller::<TokioRuntime>::new();
+        let before = c.resource_usage().await;
+        assert!(before.locks >= 1);
+        assert!(before.implboxes["base::runtime::LockBox<controller::ReqData>"] >= 1);
+        c.two("potato").await.unwrap();
+        let after = c.resource_usage().await;
+        assert!(after.cache_bytes > before.cache_bytes);
+    }
+
+    #[tokio::test]
+    async fn test_logger() {
+        #[derive(Default, Clone)]
+        struct Sink(Arc<std::sync::Mutex<Vec<u8>>>);
+        impl std::io::Write for Sink {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let sink = Sink::default();
+        let logger = logger::JsonLinesLogger::new(sink.clone()).redact("val");
+        let c = Controller::<TokioRuntime>::new().with_logger(logger);
+        c.one(5).await.unwrap();
+        c.two("potato").await.unwrap();
+        c.stream("events").await.unwrap();
+        let out = String::from_utf8(sink.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(
+            lines[1].contains(r#""seq":2,"request":"two?val=***","response":"two?val=***&seq=2""#)
+        );
+        assert!(lines[2].contains(r#""seq":3,"request":"events","response":"""#));
+    }
+
+    #[derive(Debug, Clone)]
+    enum Op {
+        One(i32),
+        Two(String),
+        /// A request for `two` whose transport fails
+        Fail(String),
+        /// A batch of `One` and `Two` requests
+        Batch(Vec<Op>),
+    }
+
+    impl Op {
+        fn to_batch(&self) -> batch::BatchRequest {
+            match self {
+                Op::One(val) => batch::BatchRequest::One(*val),
+                Op::Two(val) => batch::BatchRequest::Two(val.clone()),
+                _ => unreachable!("only single requests are batched"),
+            }
+        }
+    }
+
+    fn single_op() -> impl Strategy<Value = Op> {
+        // Keep values small so that the failing value 3 comes up often.
+        prop_oneof![(0..6).prop_map(Op::One), "[a-z]{1,8}".prop_map(Op::Two)]
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            4 => single_op(),
+            1 => "[a-z]{1,8}".prop_map(Op::Fail),
+            1 => prop::collection::vec(single_op(), 0..6).prop_map(Op::Batch),
+        ]
+    }
+
+    /// The number of sequence numbers `op` uses. A request rejected
+    /// before it is sent doesn't use one, but one whose transport fails
+    /// does, since the number was already in the path that was sent.
+    fn seqs_used(op: &Op) -> usize {
+        match op {
+            Op::One(3) => 0,
+            Op::Batch(ops) => ops.iter().map(seqs_used).sum(),
+            _ => 1,
+        }
+    }
+
+    /// Check the path returned by `two` for `val` and return its
+    /// sequence number.
+    fn two_seq(val: &str, path: &str) -> i32 {
+        let (p, seq) = path.split_once("&seq=").unwrap();
+        assert_eq!(p, format!("two?val={val}"));
+        seq.parse().unwrap()
+    }
+
+    /// Run `op` and return the sequence number of each successful
+    /// request along with its path, and the number of transport
+    /// failures. Rejected requests must fail with the validation error.
+    async fn run_op(
+        c: &Controller<runtime_test::TestRuntime, MockTransport>,
+        op: &Op,
+    ) -> (Vec<(i32, String)>, usize) {
+        let mut done = Vec::new();
+        let mut failed = 0;
+        let mut check = |op: &Op, result: Result<(i32, String), ControllerError>| match result {
+            Ok(ok) => done.push(ok),
+            Err(ControllerError::InvalidInput(_)) => assert!(matches!(op, Op::One(3))),
+            Err(ControllerError::Transport(_)) => failed += 1,
+            Err(e) => panic!("unexpected error: {e}"),
+        };
+        match op {
+            Op::One(val) => {
+                let result = c.one(*val).await;
+                check(
+                    op,
+                    result.map(|seq| (seq, format!("one?val={val}&seq={seq}"))),
+                );
+            }
+            Op::Two(val) | Op::Fail(val) => {
+                let result = c.two(val).await;
+                check(op, result.map(|path| (two_seq(val, &path), path)));
+            }
+            Op::Batch(ops) => {
+                let results = c.batch(ops.iter().map(Op::to_batch).collect()).await;
+                for (op, result) in ops.iter().zip(results) {
+                    let result = result.map(|response| match (op, response) {
+                        (Op::One(val), batch::BatchResponse::One(seq)) => {
+                            (seq, format!("one?val={val}&seq={seq}"))
+                        }
+                        (Op::Two(val), batch::BatchResponse::Two(path)) => {
+                            (two_seq(val, &path), path)
+                        }
+                        (op, response) => panic!("{response:?} doesn't answer {op:?}"),
+                    });
+                    check(op, result);
+                }
+            }
+        }
+        (done, failed)
+    }
+
+    proptest! {
+        #[test]
+        fn prop_sequential(seed in any::<u64>(), ops in prop::collection::vec(op(), 1..50)) {
+            runtime_test::TestExecutor::new(seed).block_on(async {
+                let mock = MockTransport::new();
+                let c = Controller::<runtime_test::TestRuntime, MockTransport>::new_with(
+                    mock.clone(),
+                );
+                let mut seq = 0;
+                let mut last_path = String::new();
+                for op in ops {
+                    if let Op::Fail(_) = op {
+                        mock.fail("injected");
+                    }
+                    let (mut done, failed) = run_op(&c, &op).await;
+                    // The requests of a batch share the sequence numbers
+                    // that follow, in no particular order.
+                    let used = seqs_used(&op);
+                    assert_eq!(done.len() + failed, used);
+                    assert_eq!(failed, matches!(op, Op::Fail(_)) as usize);
+                    done.sort();
+                    for (i, (s, _)) in done.iter().enumerate() {
+                        assert!(*s > seq && *s <= seq + used as i32, "seq {s} after {seq}");
+                        assert!(i == 0 || done[i - 1].0 < *s);
+                    }
+                    seq += used as i32;
+                    // A failed request doesn't change the last path.
+                    if let Some((_, path)) = done.pop() {
+                        last_path = path;
+                    }
+                    let data = c.req_data().read().await;
+                    assert_eq!(data.seq, seq);
+                    assert_eq!(data.last_path, last_path);
+                }
+            });
+        }
+
+        #[test]
+        fn prop_concurrent(seed in any::<u64>(), ops in prop::collection::vec(op(), 1..50)) {
+            type R = runtime_test::TestRuntime;
+            runtime_test::TestExecutor::new(seed).block_on(async {
+                let mock = MockTransport::new();
+                // Whichever requests are sent first get the failures.
+                let failures = ops.iter().filter(|op| matches!(op, Op::Fail(_))).count();
+                for _ in 0..failures {
+                    mock.fail("injected");
+                }
+                let c = Arc::new(Controller::<R, MockTransport>::new_with(mock));
+                let handles: Vec<_> = ops
+                    .iter()
+                    .cloned()
+                    .map(|op| {
+                        let c = c.clone();
+                        R::spawn(Box::pin(async move { run_op(&c, &op).await }))
+                    })
+                    .collect();
+                let mut seqs = Vec::new();
+                let mut failed = 0;
+                for mut h in handles {
+                    let (done, n) = h.join().await.unwrap();
+                    seqs.extend(done.into_iter().map(|(seq, _)| seq));
+                    failed += n;
+                }
+                // Every request that was sent got its own sequence
+                // number, whether or not its transport failed, and
+                // rejected requests didn't use any.
+                let expected = ops.iter().map(seqs_used).sum::<usize>();
+                assert_eq!(failed, failures);
+                assert_eq!(seqs.len() + failed, expected);
+                seqs.sort();
+                seqs.dedup();
+                assert_eq!(seqs.len() + failed, expected);
+                assert!(seqs.iter().all(|&seq| seq >= 1 && seq <= expected as i32));
+                assert_eq!(c.req_data().read().await.seq, expected as i32);
+            });
+        }
+    }
 }
@@ -0,0 +1,237 @@
+//! Structured logging of the requests made by a [Controller]. This is
+//! separate from tracing: it captures request and response payloads
+//! so device traffic can be saved for offline analysis. Install a
+//! [RequestLogger] with [Controller::with_logger]. [JsonLinesLogger]
+//! writes one JSON object per request to any [Write] sink.
+//!
+//! [Controller]: crate::Controller
+//! [Controller::with_logger]: crate::Controller::with_logger
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Error,
+    Info,
+}
+
+impl LogLevel {
+    fn as_str(self) -> &'static str {
+        match self {
+            LogLevel::Error => "error",
+            LogLevel::Info => "info",
+        }
+    }
+}
+
+/// Everything known about a completed request
+pub struct RequestRecord<'a> {
+    /// The sequence number assigned to the request, if it got that far
+    pub seq: Option<i32>,
+    /// The request path, including the query string
+    pub request: &'a str,
+    /// The response payload or the error message
+    pub response: Result<&'a str, &'a str>,
+    pub elapsed: Duration,
+}
+
+impl RequestRecord<'_> {
+    pub fn level(&self) -> LogLevel {
+        match self.response {
+            Ok(_) => LogLevel::Info,
+            Err(_) => LogLevel::Error,
+        }
+    }
+}
+
+/// A RequestLogger is called after every request a Controller makes.
+/// It is called on the task that made the request, so it should not
+/// block for long.
+pub trait RequestLogger: Send + Sync {
+    fn log(&self, record: &RequestRecord<'_>);
+
+    /// Write out anything buffered. This is called by
+    /// [crate::Controller::close]. The default does nothing.
+    fn flush(&self) {}
+}
+
+/// Write each request as a line of JSON:
+/// ```json
+/// {"ts_ms":1700000000000,"level":"info","seq":1,"request":"one?val=5","response":"one?val=5&seq=1","elapsed_us":12}
+/// ```
+/// Records below the configured [LogLevel] are skipped, and of the
+/// rest, only the configured fraction is written. Sampling is
+/// deterministic: with a rate of 0.25, every fourth record is written.
+/// Errors are never sampled out. The values of redacted query
+/// parameters are replaced by `***` in both request and response.
+/// Write errors are ignored since logging must not cause requests to
+/// fail.
+pub struct JsonLinesLogger<W> {
+    sink: Mutex<W>,
+    level: LogLevel,
+    redact: Vec<String>,
+    sample_rate: f64,
+    count: AtomicU64,
+}
+
+impl<W: Write + Send> JsonLinesLogger<W> {
+    /// Log all requests to `sink` without redaction.
+    pub fn new(sink: W) -> Self {
+        Self {
+            sink: Mutex::new(sink),
+            level: LogLevel::Info,
+            redact: Vec::new(),
+            sample_rate: 1.0,
+            count: AtomicU64::new(0),
+        }
+    }
+
+    pub fn level(mut self, level: LogLevel) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Replace the value of the query parameter `param` with `***`.
+    pub fn redact(mut self, param: &str) -> Self {
+        self.redact.push(param.to_string());
+        self
+    }
+
+    /// Write only the given fraction, between 0.0 and 1.0, of
+    /// successful requests.
+    pub fn sample_rate(mut self, rate: f64) -> Self {
+        self.sample_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn into_inner(self) -> W {
+        self.sink.into_inner().unwrap()
+    }
+
+    fn sampled(&self) -> bool {
+        let n = self.count.fetch_add(1, Ordering::Relaxed) as f64;
+        ((n + 1.0) * self.sample_rate).floor() > (n * self.sample_rate).floor()
+    }
+
+    fn redacted(&self, path: &str) -> String {
+        let Some((base, query)) = path.split_once('?') else {
+            return path.to_string();
+        };
+        let query: Vec<String> = query
+            .split('&')
+            .map(|param| match param.split_once('=') {
+                Some((name, _)) if self.redact.iter().any(|r| r == name) => format!("{name}=***"),
+                _ => param.to_string(),
+            })
+            .collect();
+        format!("{base}?{}", query.join("&"))
+    }
+}
+
+impl<W: Write + Send> RequestLogger for JsonLinesLogger<W> {
+    fn log(&self, record: &RequestRecord<'_>) {
+        let level = record.level();
+        if level > self.level || (level != LogLevel::Error && !self.sampled()) {
+            return;
+        }
+        let ts_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let mut line = format!("{{\"ts_ms\":{ts_ms},\"level\":\"{}\"", level.as_str());
+        if let Some(seq) = record.seq {
+            line += &format!(",\"seq\":{seq}");
+        }
+        line += &format!(
+            ",\"request\":{}",
+            json_string(&self.redacted(record.request))
+        );
+        match record.response {
+            Ok(r) => line += &format!(",\"response\":{}", json_string(&self.redacted(r))),
+            Err(e) => line += &format!(",\"error\":{}", json_string(e)),
+        }
+        line += &format!(",\"elapsed_us\":{}}}\n", record.elapsed.as_micros());
+        let _ = self.sink.lock().unwrap().write_all(line.as_bytes());
+    }
+
+    fn flush(&self) {
+        let _ = self.sink.lock().unwrap().flush();
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            '\r' => out += "\\r",
+            '\t' => out += "\\t",
+            c if (c as u32) < 0x20 => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record<'a>(seq: i32, response: Result<&'a str, &'a str>) -> RequestRecord<'a> {
+        RequestRecord {
+            seq: Some(seq),
+            request: "one?val=5&key=secret",
+            response,
+            elapsed: Duration::from_micros(12),
+        }
+    }
+
+    /// Strip the timestamp, which varies.
+    fn lines(logger: JsonLinesLogger<Vec<u8>>) -> Vec<String> {
+        String::from_utf8(logger.into_inner())
+            .unwrap()
+            .lines()
+            .map(|l| l.split_once(',').unwrap().1.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_format() {
+        let logger = JsonLinesLogger::new(Vec::new()).redact("key");
+        logger.log(&record(1, Ok("one?val=5&key=secret&seq=1")));
+        logger.log(&record(2, Err("bad \"thing\"\n")));
+        assert_eq!(
+            lines(logger),
+            [
+                r#""level":"info","seq":1,"request":"one?val=5&key=***","response":"one?val=5&key=***&seq=1","elapsed_us":12}"#,
+                r#""level":"error","seq":2,"request":"one?val=5&key=***","error":"bad \"thing\"\n","elapsed_us":12}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filtering() {
+        let logger = JsonLinesLogger::new(Vec::new()).level(LogLevel::Error);
+        logger.log(&record(1, Ok("")));
+        logger.log(&record(2, Err("oops")));
+        assert_eq!(lines(logger).len(), 1);
+
+        let logger = JsonLinesLogger::new(Vec::new()).sample_rate(0.25);
+        for seq in 1..=8 {
+            logger.log(&record(seq, Ok("")));
+        }
+        // Errors are always logged.
+        logger.log(&record(9, Err("oops")));
+        let seqs: Vec<String> = lines(logger)
+            .iter()
+            .map(|l| l.split(',').nth(1).unwrap().to_string())
+            .collect();
+        assert_eq!(seqs, [r#""seq":4"#, r#""seq":8"#, r#""seq":9"#]);
+    }
+}
@@ -0,0 +1,111 @@
+//! Model-checked tests of concurrent use of [Controller]. These use
+//! runtime-loom so that loom can explore every interleaving of lock
+//! acquisition between threads.
+use super::*;
+use base::{JoinHandle, Locker, Upgradable};
+use loom::future::block_on;
+use loom::sync::{Arc, RwLock};
+use loom::thread;
+use runtime_loom::LoomRuntime;
+
+#[test]
+fn loom_concurrent_one() {
+    loom::model(|| {
+        let c1 = Arc::new(Controller::<LoomRuntime>::new());
+        let c2 = c1.clone();
+        let h = thread::spawn(move || block_on(c2.one(1)).unwrap());
+        let s1 = block_on(c1.one(2)).unwrap();
+        let s2 = h.join().unwrap();
+        // Each call must see its own sequence number, not one assigned
+        // to the other call.
+        assert_eq!(s1 + s2, 3);
+        assert_ne!(s1, s2);
+    });
+}
+
+#[test]
+fn loom_concurrent_one_two() {
+    loom::model(|| {
+        let c1 = Arc::new(Controller::<LoomRuntime>::new());
+        let c2 = c1.clone();
+        let h = thread::spawn(move || block_on(c2.two("potato")).unwrap());
+        let seq = block_on(c1.one(5)).unwrap();
+        let path = h.join().unwrap();
+        assert_eq!(path, format!("two?val=potato&seq={}", 3 - seq));
+    });
+}
+
+#[test]
+fn loom_spawned_one() {
+    // The same as loom_concurrent_one, but the other call runs as a task
+    // spawned through the runtime.
+    loom::model(|| {
+        let c1 = Arc::new(Controller::<LoomRuntime>::new());
+        let c2 = c1.clone();
+        let mut h = LoomRuntime::box_task(Box::pin(async move { c2.one(1).await.unwrap() }));
+        let s1 = block_on(c1.one(2)).unwrap();
+        let s2 = block_on(LoomRuntime::unbox_mut_task(&mut h).join()).unwrap();
+        assert_eq!(s1 + s2, 3);
+        assert_ne!(s1, s2);
+    });
+}
+
+#[test]
+fn loom_owned_guard() {
+    // An owned guard moves to another thread and outlives the lock.
+    loom::model(|| {
+        let b = LoomRuntime::box_lock(1);
+        let lock = LoomRuntime::unbox_lock(&b);
+        let mut w = block_on(lock.write_owned());
+        let h = thread::spawn(move || {
+            *w += 1;
+        });
+        let r = block_on(lock.read_owned());
+        drop(b);
+        assert_eq!(*r, 2);
+        h.join().unwrap();
+    });
+}
+
+#[test]
+fn loom_upgrade_vs_write() {
+    // A write can't slip in between an upgradable read and its upgrade,
+    // so neither increment is lost.
+    loom::model(|| {
+        let l1 = Arc::new(LoomRuntime::new_lock(0));
+        let l2 = l1.clone();
+        let h = thread::spawn(move || {
+            *block_on(l2.write()) += 1;
+        });
+        let u = block_on(l1.upgradable_read());
+        let n = *u;
+        *block_on(u.upgrade()) = n + 1;
+        h.join().unwrap();
+        assert_eq!(*block_on(l1.read()), 2);
+    });
+}
+
+#[test]
+fn loom_init_vs_dispatch() {
+    // This models the device wrapper, which keeps the controller in a
+    // `RwLock<Option<Controller>>` that may be initialized on one thread
+    // while another thread is dispatching a call.
+    loom::model(|| {
+        let w1 = Arc::new(RwLock::new(None::<Controller<LoomRuntime>>));
+        let w2 = w1.clone();
+        let h = thread::spawn(move || {
+            *w2.write().unwrap() = Some(Controller::new());
+        });
+        let result = {
+            let lock = w1.read().unwrap();
+            lock.as_ref().map(|c| block_on(c.one(5)).unwrap())
+        };
+        h.join().unwrap();
+        // Either we were too early, or we saw a fully initialized
+        // controller.
+        if let Some(seq) = result {
+            assert_eq!(seq, 1);
+        }
+        assert!(w1.read().unwrap().is_some());
+    });
+}
@@ -0,0 +1,106 @@
+//! Counters for monitoring a [Controller]. Turn them on with
+//! [ControllerBuilder::metrics] and read them with [Controller::metrics].
+//! With the `serde` feature, a [Metrics] snapshot can be serialized for
+//! whatever monitoring system the host uses.
+//!
+//! [Controller]: crate::Controller
+//! [Controller::metrics]: crate::Controller::metrics
+//! [ControllerBuilder::metrics]: crate::builder::ControllerBuilder::metrics
+use crate::error::ControllerError;
+use std::time::Duration;
+
+/// The upper bounds, in milliseconds, of the buckets of
+/// [Histogram::counts]. The last bucket has no upper bound.
+pub const LATENCY_BUCKETS_MS: [u64; 8] = [1, 5, 10, 25, 50, 100, 500, 1000];
+
+/// A snapshot of a controller's counters. Requests that are rejected
+/// before they are started, such as [crate::Controller::one] with an
+/// invalid value, aren't counted, but those refused because the
+/// controller is closed or poisoned are.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Metrics {
+    /// Requests that finished, whether or not they succeeded
+    pub requests: u64,
+    /// Requests that failed, by kind of error
+    pub errors: ErrorCounts,
+    /// How long requests took, including waiting for other requests
+    pub latency: Histogram,
+    /// The sequence number of the most recent successful request
+    pub seq: Option<i32>,
+}
+
+/// Failed requests by [ControllerError] variant
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ErrorCounts {
+    pub invalid_input: u64,
+    pub transport: u64,
+    pub cancelled: u64,
+    pub timeout: u64,
+    pub overloaded: u64,
+    pub closed: u64,
+    pub poisoned: u64,
+}
+
+impl ErrorCounts {
+    /// The number of failed requests
+    pub fn total(&self) -> u64 {
+        self.invalid_input
+            + self.transport
+            + self.cancelled
+            + self.timeout
+            + self.overloaded
+            + self.closed
+            + self.poisoned
+    }
+}
+
+/// Request durations
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Histogram {
+    /// `counts[i]` is the number of requests that took at most
+    /// `LATENCY_BUCKETS_MS[i]` milliseconds and longer than the bucket
+    /// before. The last count is of requests that took longer than all
+    /// of the bounds.
+    pub counts: [u64; LATENCY_BUCKETS_MS.len() + 1],
+    /// The time taken by all requests together
+    pub total: Duration,
+}
+
+impl Histogram {
+    fn add(&mut self, elapsed: Duration) {
+        let bucket = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|&ms| elapsed <= Duration::from_millis(ms))
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+        self.counts[bucket] += 1;
+        self.total += elapsed;
+    }
+}
+
+impl Metrics {
+    /// Count a finished request that took `elapsed`.
+    pub(crate) fn add(&mut self, elapsed: Duration, result: Result<i32, &ControllerError>) {
+        self.requests += 1;
+        self.latency.add(elapsed);
+        let e = match result {
+            Ok(seq) => {
+                self.seq = Some(seq);
+                return;
+            }
+            Err(e) => e,
+        };
+        let errors = &mut self.errors;
+        *match e {
+            ControllerError::InvalidInput(_) => &mut errors.invalid_input,
+            ControllerError::Transport(_) => &mut errors.transport,
+            ControllerError::Cancelled => &mut errors.cancelled,
+            ControllerError::Timeout => &mut errors.timeout,
+            ControllerError::Overloaded => &mut errors.overloaded,
+            ControllerError::Closed => &mut errors.closed,
+            ControllerError::Poisoned => &mut errors.poisoned,
+        } += 1;
+    }
+}
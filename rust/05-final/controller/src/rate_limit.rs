@@ -0,0 +1,126 @@
+//! Client-side rate limiting, for servers that reject clients that send
+//! too fast. Set a limit with [ControllerBuilder::rate_limit].
+//!
+//! [ControllerBuilder::rate_limit]: crate::builder::ControllerBuilder::rate_limit
+use base::Clock;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The longest a request waits for a token. The clocks add the wait to
+/// the current time, so it has to stay far from overflowing however
+/// small `per_second` is.
+const MAX_WAIT: Duration = Duration::from_secs(24 * 60 * 60);
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// A token bucket that holds up to `burst` tokens and gains `per_second`
+/// tokens per second. Each request takes a token, waiting for one if
+/// the bucket is empty. Waiting requests reserve their tokens, so they
+/// go in the order in which they arrived. A request that is abandoned
+/// while waiting doesn't give its token back. No request waits longer
+/// than a day, even if the rate says it should. Time comes from a
+/// [Clock], so tests can use virtual time.
+pub struct RateLimiter<C: Clock> {
+    clock: C,
+    per_second: f64,
+    burst: f64,
+    bucket: Mutex<Bucket>,
+}
+
+impl<C: Clock> RateLimiter<C> {
+    /// Create a limiter that starts with a full bucket. `per_second` must
+    /// be positive and `burst` at least 1.
+    pub fn new(clock: C, per_second: f64, burst: u32) -> Self {
+        let updated = clock.now();
+        Self {
+            clock,
+            per_second,
+            burst: burst.into(),
+            bucket: Mutex::new(Bucket {
+                tokens: burst.into(),
+                updated,
+            }),
+        }
+    }
+
+    /// Wait until a token is available, and take it.
+    pub async fn acquire(&self) {
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+            let now = self.clock.now();
+            let elapsed = now.saturating_duration_since(bucket.updated);
+            bucket.tokens =
+                (bucket.tokens + elapsed.as_secs_f64() * self.per_second).min(self.burst);
+            bucket.updated = now;
+            bucket.tokens -= 1.0;
+            if bucket.tokens >= 0.0 {
+                return;
+            }
+            Duration::try_from_secs_f64(-bucket.tokens / self.per_second)
+                .map_or(MAX_WAIT, |wait| wait.min(MAX_WAIT))
+        };
+        base::trace_event!(?wait, "rate limited");
+        self.clock.sleep(wait).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base::{TestClock, VirtualClock};
+    use std::future::{poll_fn, Future};
+    use std::pin::{pin, Pin};
+    use std::task::Poll;
+
+    /// Poll `f` once and return whether it finished.
+    async fn poll_once<F: Future>(mut f: Pin<&mut F>) -> bool {
+        poll_fn(|cx| Poll::Ready(f.as_mut().poll(cx).is_ready())).await
+    }
+
+    #[tokio::test]
+    async fn test_bucket() {
+        let clock = VirtualClock::new();
+        let limiter = RateLimiter::new(clock.clone(), 10.0, 2);
+        // The bucket starts full.
+        limiter.acquire().await;
+        limiter.acquire().await;
+        let mut third = pin!(limiter.acquire());
+        assert!(!poll_once(third.as_mut()).await);
+        clock.advance(Duration::from_millis(99)).await;
+        assert!(!poll_once(third.as_mut()).await);
+        clock.advance(Duration::from_millis(2)).await;
+        assert!(poll_once(third.as_mut()).await);
+        // Waiting requests reserve tokens in order.
+        let mut fourth = pin!(limiter.acquire());
+        let mut fifth = pin!(limiter.acquire());
+        assert!(!poll_once(fourth.as_mut()).await);
+        assert!(!poll_once(fifth.as_mut()).await);
+        clock.advance(Duration::from_millis(100)).await;
+        assert!(poll_once(fourth.as_mut()).await);
+        assert!(!poll_once(fifth.as_mut()).await);
+        clock.advance(Duration::from_millis(100)).await;
+        assert!(poll_once(fifth.as_mut()).await);
+        // An idle bucket only fills up to the burst.
+        clock.advance(Duration::from_secs(60)).await;
+        assert!(poll_once(pin!(limiter.acquire())).await);
+        assert!(poll_once(pin!(limiter.acquire())).await);
+        assert!(!poll_once(pin!(limiter.acquire())).await);
+    }
+
+    #[tokio::test]
+    async fn test_tiny_rate() {
+        // The wait for a token at this rate doesn't fit in a Duration.
+        let clock = VirtualClock::new();
+        let limiter = RateLimiter::new(clock.clone(), 1e-20, 1);
+        limiter.acquire().await;
+        let mut second = pin!(limiter.acquire());
+        assert!(!poll_once(second.as_mut()).await);
+        clock.advance(MAX_WAIT - Duration::from_secs(1)).await;
+        assert!(!poll_once(second.as_mut()).await);
+        clock.advance(Duration::from_secs(1)).await;
+        assert!(poll_once(second.as_mut()).await);
+    }
+}
@@ -0,0 +1,109 @@
+//! Reading a response as it arrives with [Controller::stream], for
+//! responses that are too large to hold at once or that never end,
+//! such as server-sent events.
+use crate::endpoint::Request;
+use crate::error::ControllerError;
+use crate::event::Event;
+use crate::layer;
+use crate::Controller;
+use base::{AsyncRwLock, AsyncStream, Connector, DynAsyncStream, Runtime, Transport};
+use std::borrow::Cow;
+use std::error::Error;
+use std::ops::DerefMut;
+
+/// A piece of a response body from a [ResponseStream]
+pub type Chunk = Result<Vec<u8>, ControllerError>;
+
+/// The body of a response opened with [Controller::stream]. It doesn't
+/// borrow the controller, so it can be kept after the call that opened
+/// it. Transport errors while reading are returned as
+/// [ControllerError::Transport], after which the stream ends.
+pub struct ResponseStream {
+    inner: Box<dyn DynAsyncStream<Result<Vec<u8>, Box<dyn Error + Sync + Send>>> + Send>,
+    done: bool,
+}
+
+impl AsyncStream<Chunk> for ResponseStream {
+    async fn next(&mut self) -> Option<Chunk> {
+        if self.done {
+            return None;
+        }
+        let chunk = DynAsyncStream::next(&mut *self.inner).await;
+        self.done = !matches!(chunk, Some(Ok(_)));
+        chunk.map(|c| c.map_err(ControllerError::Transport))
+    }
+}
+
+impl<RuntimeT: Runtime, TransportT: Connector> Controller<RuntimeT, TransportT> {
+    /// Send a request for `path` and return the body of the response as
+    /// a stream of chunks. Like other requests, it is a GET [Request]
+    /// for `path`, it gets the next sequence number, which is added to
+    /// the query, and it becomes the last path once the response has
+    /// started. It is recorded, logged, and subject to injected faults
+    /// like other requests, with an empty response. Only opening the stream
+    /// counts toward the controller's timeout, and it isn't retried.
+    /// Reading it doesn't hold up other requests. The path goes through
+    /// [crate::layer::Layer::request], but since there is no whole
+    /// response, [crate::layer::Layer::response] isn't called.
+    pub async fn stream(&self, path: &str) -> Result<ResponseStream, ControllerError> {
+        let request = Request::get(path);
+        let path = &*request.path_and_query();
+        let _active = self.admit(path)?;
+        // Only the logger needs the time, and std has no clock on some
+        // targets, such as wasm32-unknown-unknown.
+        let start = self.logger.as_ref().map(|_| std::time::Instant::now());
+        let record_start = self.record_start();
+        #[cfg(feature = "tracing")]
+        let traced = base::Clock::now(&RuntimeT::clock());
+        self.emit(|| Event::RequestStarted {
+            path: path.to_string(),
+        });
+        let open = async {
+            let _permit = self.in_flight_permit().await?;
+            self.wait_for_rate_limit().await;
+            let mut lock = base::trace_future!(self.req_data().write(), "controller.lock").await;
+            let _poison = self.poison_on_unwind();
+            let ref_data = lock.deref_mut();
+            ref_data.seq = ref_data.seq.wrapping_add(1);
+            let full_path = request.with_seq(ref_data.seq);
+            let mut sent = Cow::Borrowed(full_path.as_str());
+            layer::request(&self.layers, &mut sent).map_err(|(_, e)| e)?;
+            self.inject_faults().await?;
+            let inner = self
+                .transport()
+                .stream(sent.into_owned())
+                .await
+                .map_err(ControllerError::Transport)?;
+            ref_data.last_path = full_path;
+            ref_data.response.clear();
+            Ok((
+                ref_data.seq,
+                ResponseStream {
+                    inner: Box::new(inner),
+                    done: false,
+                },
+            ))
+        };
+        let open = base::trace_future!(open, "controller.stream", path);
+        let result = match self.timeout {
+            None => open.await,
+            Some(timeout) => RuntimeT::timeout(timeout, open)
+                .await
+                .unwrap_or_else(|_| Err(ControllerError::Timeout)),
+        };
+        base::trace_event!(
+            path,
+            seq = ?result.as_ref().ok().map(|(seq, _)| *seq),
+            elapsed = ?base::Clock::now(&RuntimeT::clock()).saturating_duration_since(traced),
+            error = ?result.as_ref().err().map(|e| e.to_string()),
+            "stream opened"
+        );
+        self.finish(
+            path,
+            start,
+            record_start,
+            result.as_ref().map(|(seq, _)| (*seq, "")),
+        );
+        result.map(|(_, stream)| stream)
+    }
+}
@@ -0,0 +1,120 @@
+//! This crate re-exports the public API of the workspace so that
+//! consumers can depend on a single crate and select what they need
+//! with features:
+//! - `rt-tokio` (default): the tokio-based runtime, exported as
+//!   [runtime_tokio]
+//! - `rt-async-std`: the async-std-based runtime, exported as
+//!   `runtime_async_std`
+//! - `rt-smol`: the smol-based runtime, exported as `runtime_smol`
+//! - `rt-std`: a runtime that blocks instead of using an async
+//!   executor, exported as `runtime_std`
+//! - `rt-wasm`: the runtime for wasm32-unknown-unknown, exported as
+//!   `runtime_wasm`
+//! - `rt-embassy`: locks and channels for embassy, exported as
+//!   `runtime_embassy`. It only implements [base::Locker], so it can't
+//!   run a `Controller`.
+//! - `rt-test`: a runtime with seeded task ordering and virtual time
+//!   for reproducing races in tests, exported as `runtime_test`
+//! - `rt-parking-lot`: locks from parking_lot that block instead of
+//!   waiting asynchronously, for short, uncontended critical sections,
+//!   exported as `runtime_parking_lot`. It only implements
+//!   [base::Locker].
+//! - `rt-futures`: a runtime built only on the futures crate, running
+//!   tasks on a `LocalPool`, exported as `runtime_futures`
+//! - `http`: a transport that sends the controller's requests to an
+//!   HTTP server, exported as `transport_http`
+//! - `device` (default): the blocking device wrapper, exported as
+//!   [device]. This requires `rt-tokio`.
+//! - `once_cell`: required by the device wrapper on compilers older
+//!   than rust 1.80
+//! - `thiserror`, `anyhow`: error handling integrations. See
+//!   [controller::error] and `device::error`.
+//! - `accounting`: resource usage reporting. See
+//!   [controller::Controller::resource_usage].
+//! - `diagnostics`: counts of live boxes for leak checks. See
+//!   `implbox::diagnostics`.
+//! - `serde`: serialization of boxed items, of controller metrics, and
+//!   of typed requests and responses. See `implbox::serde_hooks`,
+//!   [controller::metrics], and [controller::endpoint::Request] and
+//!   [controller::endpoint::Response].
+//! - `metrics`: lock wait and hold times, channel, and task counts
+//!   from the tokio runtime. See `runtime_tokio::metrics`.
+//! - `test-util`: virtual time for tests with the tokio runtime. See
+//!   `runtime_tokio::time::TokioClock`.
+//! - `tracing`: tracing instrumentation in every crate. See
+//!   [base::trace].
+//!
+//! `rt-tokio`, `rt-async-std`, and `rt-std` also choose
+//! `controller::DefaultRuntime`, in that order of precedence, so that
+//! `Controller` can be used without naming a runtime.
+//!
+//! The [controller], [base], [implbox], and [implbox_macros] crates
+//! are always available, so a custom runtime can be supplied by
+//! implementing [base::Runtime] without enabling any backend. The
+//! [prelude] module collects the items most programs need.
+//!
+//! ```
+//! use device_kit::prelude::*;
+//! use device_kit::runtime_tokio::TokioRuntime;
+//!
+//! # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+//! let c = Controller::<TokioRuntime>::new();
+//! assert_eq!(c.one(5).await.unwrap(), 1);
+//! # });
+//! ```
+
+// Supported feature combinations are checked here so that an
+// unsupported combination fails with a clear message rather than
+// unresolved names deep inside a dependency.
+#[cfg(all(feature = "device", not(feature = "rt-tokio")))]
+compile_error!("the `device` feature requires the `rt-tokio` feature");
+
+pub use base;
+pub use controller;
+#[cfg(feature = "device")]
+pub use device;
+pub use implbox;
+pub use implbox_macros;
+#[cfg(feature = "rt-async-std")]
+pub use runtime_async_std;
+#[cfg(feature = "rt-embassy")]
+pub use runtime_embassy;
+#[cfg(feature = "rt-futures")]
+pub use runtime_futures;
+#[cfg(feature = "rt-parking-lot")]
+pub use runtime_parking_lot;
+#[cfg(feature = "rt-smol")]
+pub use runtime_smol;
+#[cfg(feature = "rt-std")]
+pub use runtime_std;
+#[cfg(feature = "rt-test")]
+pub use runtime_test;
+#[cfg(feature = "rt-tokio")]
+pub use runtime_tokio;
+#[cfg(feature = "rt-wasm")]
+pub use runtime_wasm;
+#[cfg(feature = "http")]
+pub use transport_http;
+
+pub mod prelude {
+    pub use base::{
+        AsyncBarrier, AsyncChannel, AsyncFile, AsyncMutex, AsyncNotify, AsyncRwLock,
+        AsyncSemaphore, AsyncStream, AsyncTcpListener, AsyncTcpStream, AsyncUdpSocket, BarrierBox,
+        Broadcast, BroadcastBox, BroadcastReceiver, BroadcastReceiverBox, CancelToken,
+        CancelTokenBox, ChannelBox, Clock, Connector, Executor, FileBox, JoinHandle, JoinHandleBox,
+        LockBox, Locker, LoopbackTransport, MappedReadGuard, MappedWriteGuard, MutexBox, NotifyBox,
+        PolicyLockBox, Runtime, SemaphoreBox, StdClock, StdExecutor, StdFile, StdNotify, StreamBox,
+        TcpListenerBox, TcpStreamBox, TestClock, Transport, TransportBox, TransportConfig,
+        UdpSocketBox, Upgradable, VirtualClock, Watch, WatchBox, WatchReceiver, WatchReceiverBox,
+    };
+    pub use controller::builder::{ControllerBuilder, OverloadPolicy};
+    pub use controller::endpoint::{Endpoint, Method, Request, Response};
+    #[cfg(feature = "anyhow")]
+    pub use controller::error::AnyhowExt;
+    pub use controller::error::ControllerError;
+    #[cfg(any(feature = "rt-tokio", feature = "rt-async-std", feature = "rt-std"))]
+    pub use controller::DefaultRuntime;
+    pub use controller::{Controller, RequestOptions};
+    pub use implbox::ImplBox;
+    pub use implbox_macros::{implbox_decls, implbox_impls, implbox_trait};
+}
@@ -0,0 +1,479 @@
+//! This is a Node.js binding for the [device] API built with napi-rs.
+//! The device functions block the calling thread while the singleton
+//! runs the request, so each call is wrapped in a [Task] that runs on
+//! the libuv thread pool and is exposed to JavaScript as a function
+//! that returns a `Promise`.
+//!
+//! Failed calls reject with a regular JavaScript `Error` whose `code`
+//! property is one of the `ERR_*` constants in this module, so callers
+//! can branch on the kind of failure without parsing messages.
+//!
+//! ```js
+//! const device = require('./device-node.node');
+//! device.init();
+//! const seq = await device.one(5);
+//! try {
+//!   await device.one(3);
+//! } catch (e) {
+//!   console.log(e.code); // ERR_DEVICE_INVALID_INPUT
+//! }
+//! ```
+//!
+//! `subscribe` calls a callback with each of the device's events, which
+//! is enough to drive an `EventEmitter`:
+//!
+//! ```js
+//! const { EventEmitter } = require('node:events');
+//! const emitter = new EventEmitter();
+//! device.subscribe((event) => emitter.emit(event.kind, event));
+//! emitter.on('requestFailed', (event) => console.log(event.error));
+//! ```
+
+use device::{ControllerError, DeviceError};
+use napi::bindgen_prelude::AsyncTask;
+use napi::threadsafe_function::{ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi::{Env, Error, JsFunction, Result, Status, Task};
+use napi_derive::napi;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// The `code` of errors caused by calling into the device before `init`.
+pub const ERR_NOT_INITIALIZED: &str = "ERR_DEVICE_NOT_INITIALIZED";
+/// The `code` of errors caused by a panic in the device. Call `init` to
+/// start over.
+pub const ERR_POISONED: &str = "ERR_DEVICE_POISONED";
+/// The `code` of errors for requests the device rejected.
+pub const ERR_INVALID_INPUT: &str = "ERR_DEVICE_INVALID_INPUT";
+/// The `code` of errors for requests that took too long.
+pub const ERR_TIMEOUT: &str = "ERR_DEVICE_TIMEOUT";
+/// The `code` of errors for requests refused because too many were in
+/// flight.
+pub const ERR_OVERLOADED: &str = "ERR_DEVICE_OVERLOADED";
+/// The `code` of errors for requests made after `close`.
+pub const ERR_CLOSED: &str = "ERR_DEVICE_CLOSED";
+/// The `code` of other errors returned by the device for a request.
+pub const ERR_REQUEST: &str = "ERR_DEVICE_REQUEST";
+
+fn error_code(e: &DeviceError) -> &'static str {
+    match e {
+        DeviceError::NotInitialized => ERR_NOT_INITIALIZED,
+        DeviceError::Controller(e) => controller_error_code(e),
+        _ => ERR_REQUEST,
+    }
+}
+
+fn controller_error_code(e: &ControllerError) -> &'static str {
+    match e {
+        ControllerError::Poisoned => ERR_POISONED,
+        ControllerError::InvalidInput(_) => ERR_INVALID_INPUT,
+        ControllerError::Timeout => ERR_TIMEOUT,
+        ControllerError::Overloaded => ERR_OVERLOADED,
+        ControllerError::Closed => ERR_CLOSED,
+        _ => ERR_REQUEST,
+    }
+}
+
+/// Convert an error from a device call into a JavaScript `Error` with a
+/// `code` property and return it as the rejection of the promise.
+fn reject<T>(env: Env, err: Error, code: &str) -> Result<T> {
+    let mut js_err = env.create_error(err)?;
+    js_err.set_named_property("code", env.create_string(code)?)?;
+    Err(Error::from(js_err.into_unknown()))
+}
+
+/// Convert an error from a device call into an [Error], and record its
+/// code for [reject], which only gets the [Error].
+fn device_error(e: DeviceError, code: &mut &'static str) -> Error {
+    *code = error_code(&e);
+    Error::new(Status::GenericFailure, e.to_string())
+}
+
+pub struct OneTask {
+    val: i32,
+    code: &'static str,
+}
+
+impl Task for OneTask {
+    type Output = i32;
+    type JsValue = i32;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        device::one(self.val).map_err(|e| device_error(e, &mut self.code))
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        Ok(output)
+    }
+
+    fn reject(&mut self, env: Env, err: Error) -> Result<Self::JsValue> {
+        reject(env, err, self.code)
+    }
+}
+
+pub struct TwoTask {
+    val: String,
+    code: &'static str,
+}
+
+impl Task for TwoTask {
+    type Output = String;
+    type JsValue = String;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        device::two(&self.val).map_err(|e| device_error(e, &mut self.code))
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        Ok(output)
+    }
+
+    fn reject(&mut self, env: Env, err: Error) -> Result<Self::JsValue> {
+        reject(env, err, self.code)
+    }
+}
+
+pub struct CloseTask {
+    timeout: Duration,
+    code: &'static str,
+}
+
+impl Task for CloseTask {
+    type Output = ();
+    type JsValue = ();
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        device::close(self.timeout).map_err(|e| device_error(e, &mut self.code))
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        Ok(output)
+    }
+
+    fn reject(&mut self, env: Env, err: Error) -> Result<Self::JsValue> {
+        reject(env, err, self.code)
+    }
+}
+
+pub struct HealthTask {
+    code: &'static str,
+}
+
+impl Task for HealthTask {
+    type Output = device::HealthStatus;
+    type JsValue = Health;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        device::health().map_err(|e| device_error(e, &mut self.code))
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        Ok(output.into())
+    }
+
+    fn reject(&mut self, env: Env, err: Error) -> Result<Self::JsValue> {
+        reject(env, err, self.code)
+    }
+}
+
+/// Initialize the device singleton. This must be called before any
+/// other function.
+#[napi]
+pub fn init() {
+    device::init();
+}
+
+/// Drop the device singleton and stop its runtime, giving background
+/// work up to `timeoutMs` milliseconds to stop. Requests that are in
+/// progress finish first. Call this before the process exits. `init`
+/// can be called again afterward.
+#[napi]
+pub fn shutdown(timeout_ms: u32) {
+    device::shutdown(Duration::from_millis(timeout_ms.into()));
+}
+
+/// Stop accepting requests and resolve once the ones in progress have
+/// finished, or reject with `ERR_DEVICE_TIMEOUT` if they take longer
+/// than `timeoutMs` milliseconds. Requests fail with
+/// `ERR_DEVICE_CLOSED` until `init` is called again.
+#[napi(ts_return_type = "Promise<void>")]
+pub fn close(timeout_ms: u32) -> AsyncTask<CloseTask> {
+    AsyncTask::new(CloseTask {
+        timeout: Duration::from_millis(timeout_ms.into()),
+        code: ERR_REQUEST,
+    })
+}
+
+/// The state of the device, returned by `health`, for liveness and
+/// readiness probes
+#[napi(object)]
+pub struct Health {
+    /// Whether the device can make progress at all
+    pub live: bool,
+    /// Whether the device is live, open, and able to reach the server
+    pub ready: bool,
+    /// Why the transport didn't answer a ping, if it didn't
+    pub transport_error: Option<String>,
+    /// Whether `close` has been called
+    pub closed: bool,
+    /// Whether a request held the request lock when this was checked
+    pub locked: bool,
+    /// Requests that have started and not finished
+    pub in_progress: u32,
+}
+
+impl From<device::HealthStatus> for Health {
+    fn from(h: device::HealthStatus) -> Self {
+        Self {
+            live: h.is_live(),
+            ready: h.is_ready(),
+            transport_error: h.transport.err(),
+            closed: h.closed,
+            locked: h.locked,
+            in_progress: h.in_progress.try_into().unwrap_or(u32::MAX),
+        }
+    }
+}
+
+/// Ping the server and report on the device. This rejects with
+/// `ERR_DEVICE_POISONED` rather than reporting a device that isn't
+/// live.
+#[napi(ts_return_type = "Promise<Health>")]
+pub fn health() -> AsyncTask<HealthTask> {
+    AsyncTask::new(HealthTask { code: ERR_REQUEST })
+}
+
+/// A snapshot of the device's counters, returned by `metrics`, for
+/// export to a monitoring system. Counts are numbers rather than
+/// BigInts, so they are exact up to 2^53.
+#[napi(object)]
+pub struct Metrics {
+    /// Requests that finished, whether or not they succeeded
+    pub requests: f64,
+    /// Failed requests by the `code` their errors had
+    pub errors: HashMap<String, f64>,
+    /// The upper bounds of the latency buckets in milliseconds. The
+    /// last bucket has no upper bound.
+    pub latency_buckets_ms: Vec<f64>,
+    /// The number of requests in each latency bucket
+    pub latency_counts: Vec<f64>,
+    /// The time taken by all requests together, in milliseconds
+    pub latency_total_ms: f64,
+    /// The sequence number of the most recent successful request
+    pub seq: Option<i32>,
+}
+
+impl From<device::Metrics> for Metrics {
+    fn from(m: device::Metrics) -> Self {
+        // Each count is keyed by the code that a call failing with its
+        // error would have, so the two can't disagree.
+        let e = &m.errors;
+        let mut errors = HashMap::new();
+        for (error, n) in [
+            (
+                ControllerError::InvalidInput(String::new()),
+                e.invalid_input,
+            ),
+            (ControllerError::Transport("".into()), e.transport),
+            (ControllerError::Cancelled, e.cancelled),
+            (ControllerError::Timeout, e.timeout),
+            (ControllerError::Overloaded, e.overloaded),
+            (ControllerError::Closed, e.closed),
+            (ControllerError::Poisoned, e.poisoned),
+        ] {
+            let code = controller_error_code(&error).to_string();
+            *errors.entry(code).or_insert(0.0) += n as f64;
+        }
+        Self {
+            requests: m.requests as f64,
+            errors,
+            latency_buckets_ms: device::LATENCY_BUCKETS_MS
+                .iter()
+                .map(|&ms| ms as f64)
+                .collect(),
+            latency_counts: m.latency.counts.iter().map(|&n| n as f64).collect(),
+            latency_total_ms: m.latency.total.as_secs_f64() * 1000.0,
+            seq: m.seq,
+        }
+    }
+}
+
+/// Return the device's counters. This doesn't block, so it returns the
+/// snapshot directly rather than a `Promise`.
+#[napi]
+pub fn metrics(env: Env) -> Result<Metrics> {
+    let mut code = ERR_REQUEST;
+    match device::metrics() {
+        Ok(m) => Ok(m.into()),
+        Err(e) => {
+            let err = device_error(e, &mut code);
+            reject(env, err, code)
+        }
+    }
+}
+
+/// An event of the device, passed to the callback given to `subscribe`
+#[napi(object)]
+pub struct DeviceEvent {
+    /// `requestStarted`, `requestCompleted`, `requestFailed`,
+    /// `stateChanged`, or `closed`
+    pub kind: String,
+    /// The path of the request, for request events
+    pub path: Option<String>,
+    /// The sequence number of a completed request
+    pub seq: Option<i32>,
+    /// The message of a failed request's error
+    pub error: Option<String>,
+}
+
+impl From<device::Event> for DeviceEvent {
+    fn from(event: device::Event) -> Self {
+        let (kind, path, seq, error) = match event {
+            device::Event::RequestStarted { path } => ("requestStarted", Some(path), None, None),
+            device::Event::RequestCompleted { seq, path } => {
+                ("requestCompleted", Some(path), Some(seq), None)
+            }
+            device::Event::RequestFailed { path, error } => {
+                ("requestFailed", Some(path), None, Some(error))
+            }
+            device::Event::StateChanged => ("stateChanged", None, None, None),
+            device::Event::Closed => ("closed", None, None, None),
+            _ => ("unknown", None, None, None),
+        };
+        Self {
+            kind: kind.to_string(),
+            path,
+            seq,
+            error,
+        }
+    }
+}
+
+/// Call `callback` on the JavaScript thread with each event of the
+/// device from now on. The events are read on a thread of their own,
+/// and they stop once `init`, `deinit`, or `shutdown` drops the device,
+/// so call this again after `init`. A subscription doesn't keep the
+/// process alive.
+#[napi(ts_args_type = "callback: (event: DeviceEvent) => void")]
+pub fn subscribe(env: Env, callback: JsFunction) -> Result<()> {
+    let mut code = ERR_REQUEST;
+    let events = match device::subscribe() {
+        Ok(events) => events,
+        Err(e) => {
+            let err = device_error(e, &mut code);
+            return reject(env, err, code);
+        }
+    };
+    let mut tsfn: ThreadsafeFunction<DeviceEvent, ErrorStrategy::Fatal> =
+        callback.create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))?;
+    tsfn.unref(&env)?;
+    std::thread::spawn(move || {
+        for event in events {
+            tsfn.call(event.into(), ThreadsafeFunctionCallMode::NonBlocking);
+        }
+    });
+    Ok(())
+}
+
+/// Send a request and resolve to the sequence of the request.
+#[napi(ts_return_type = "Promise<number>")]
+pub fn one(val: i32) -> AsyncTask<OneTask> {
+    AsyncTask::new(OneTask {
+        val,
+        code: ERR_REQUEST,
+    })
+}
+
+/// Send a request and resolve to the path of the request.
+#[napi(ts_return_type = "Promise<string>")]
+pub fn two(val: String) -> AsyncTask<TwoTask> {
+    AsyncTask::new(TwoTask {
+        val,
+        code: ERR_REQUEST,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use device::HealthStatus;
+
+    #[test]
+    fn test_error_code() {
+        assert_eq!(
+            error_code(&DeviceError::NotInitialized),
+            ERR_NOT_INITIALIZED
+        );
+        assert_eq!(error_code(&ControllerError::Poisoned.into()), ERR_POISONED);
+        assert_eq!(
+            controller_error_code(&ControllerError::Timeout),
+            ERR_TIMEOUT
+        );
+        assert_eq!(controller_error_code(&ControllerError::Closed), ERR_CLOSED);
+        assert_eq!(
+            controller_error_code(&ControllerError::InvalidInput("x".to_string())),
+            ERR_INVALID_INPUT
+        );
+        assert_eq!(
+            controller_error_code(&ControllerError::Cancelled),
+            ERR_REQUEST
+        );
+    }
+
+    #[test]
+    fn test_event() {
+        let event = DeviceEvent::from(device::Event::RequestFailed {
+            path: "one?val=3".to_string(),
+            error: "sorry".to_string(),
+        });
+        assert_eq!(event.kind, "requestFailed");
+        assert_eq!(event.path.as_deref(), Some("one?val=3"));
+        assert_eq!(event.seq, None);
+        assert_eq!(event.error.as_deref(), Some("sorry"));
+        let event = DeviceEvent::from(device::Event::RequestCompleted {
+            seq: 2,
+            path: "two?val=x".to_string(),
+        });
+        assert_eq!(
+            (event.kind.as_str(), event.seq),
+            ("requestCompleted", Some(2))
+        );
+        assert_eq!(DeviceEvent::from(device::Event::Closed).kind, "closed");
+    }
+
+    #[test]
+    fn test_health() {
+        let health = Health::from(HealthStatus {
+            transport: Err("refused".to_string()),
+            closed: false,
+            poisoned: false,
+            locked: true,
+            in_progress: 2,
+            in_flight_available: None,
+        });
+        assert!(health.live);
+        assert!(!health.ready);
+        assert_eq!(health.transport_error.as_deref(), Some("refused"));
+        assert!(health.locked);
+        assert_eq!(health.in_progress, 2);
+    }
+
+    #[test]
+    fn test_metrics() {
+        let mut m = device::Metrics {
+            requests: 5,
+            ..Default::default()
+        };
+        m.errors.timeout = 1;
+        m.errors.transport = 1;
+        m.errors.cancelled = 1;
+        m.errors.closed = 1;
+        let m = Metrics::from(m);
+        assert_eq!(m.requests, 5.0);
+        assert_eq!(m.errors[ERR_TIMEOUT], 1.0);
+        assert_eq!(m.errors[ERR_REQUEST], 2.0);
+        assert_eq!(m.errors[ERR_CLOSED], 1.0);
+        assert_eq!(m.errors[ERR_POISONED], 0.0);
+        assert!(!m.errors.contains_key(ERR_NOT_INITIALIZED));
+        assert_eq!(m.latency_buckets_ms.len() + 1, m.latency_counts.len());
+    }
+}
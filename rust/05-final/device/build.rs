@@ -0,0 +1,24 @@
+//! Detect the compiler version so the compat module can choose
+//! between std APIs and their fallbacks.
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rustc-check-cfg=cfg(has_lazy_lock)");
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let version = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|out| String::from_utf8(out.stdout).ok())
+        .unwrap_or_default();
+    // The output looks like "rustc 1.80.0 (051478957 2024-07-21)".
+    let minor = version
+        .split_whitespace()
+        .nth(1)
+        .and_then(|v| v.split('.').nth(1))
+        .and_then(|m| m.parse::<u32>().ok());
+    // If the version can't be determined, assume a recent compiler.
+    if !matches!(minor, Some(m) if m < 80) {
+        println!("cargo:rustc-cfg=has_lazy_lock");
+    }
+}
@@ -0,0 +1,39 @@
+//! This is a command-line driver for the device API. The arguments
+//! are pairs of a command and its value. The device is initialized
+//! once, and the commands run in order, printing one result per line.
+//!
+//! ```text
+//! $ cargo run -p device --example device-cli -- one 5 two potato
+//! 1
+//! two?val=potato&seq=2
+//! ```
+
+use std::error::Error;
+use std::process::ExitCode;
+
+fn run(cmd: &str, val: &str) -> Result<String, Box<dyn Error + Sync + Send>> {
+    match cmd {
+        "one" => Ok(device::one(val.parse()?)?.to_string()),
+        "two" => Ok(device::two(val)?),
+        _ => Err(format!("unknown command: {cmd}").into()),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.len() % 2 == 1 {
+        eprintln!("Usage: device-cli {{one|two}} value [{{one|two}} value ...]");
+        return ExitCode::from(2);
+    }
+    device::init();
+    for pair in args.chunks(2) {
+        match run(&pair[0], &pair[1]) {
+            Ok(out) => println!("{out}"),
+            Err(e) => {
+                eprintln!("{} {}: {e}", pair[0], pair[1]);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    ExitCode::SUCCESS
+}
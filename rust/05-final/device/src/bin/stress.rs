@@ -0,0 +1,258 @@
+//! Measure throughput and latency of the full stack. Each
+//! configuration runs a number of concurrent callers for a fixed time
+//! and reports requests per second, latency percentiles, and the error
+//! rate.
+//!
+//! ```text
+//! $ cargo run --release -p device --bin stress -- --callers 4 --duration 0.5 --latency-us 50
+//! mode        flavor   callers   requests      req/s     p50     p90     p99     max  errors
+//! device      current        4     277920   552294.9   941ns    17us    28us  16.0ms   0.00%
+//! controller  current        4       3572     7135.0   554us   579us   793us   2.4ms   0.00%
+//! controller  multi          4       3749     7488.9   510us   564us   666us   1.0ms   0.00%
+//! ```
+//!
+//! In `device` mode, each caller is an OS thread calling the blocking
+//! wrapper. In `controller` mode, each caller is a task calling a
+//! shared [Controller] directly on a tokio runtime of the given flavor.
+//! Simulated transport latency and errors, given by `--latency-us` and
+//! `--error-rate`, apply to controller mode, since the device singleton
+//! always uses a plain controller.
+//!
+//! [Controller]: controller::Controller
+
+use base::fault::{FaultLayer, Latency, Scenario};
+use controller::Controller;
+use runtime_tokio::TokioRuntime;
+use std::error::Error;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+struct Options {
+    callers: usize,
+    duration: Duration,
+    modes: Vec<&'static str>,
+    flavors: Vec<&'static str>,
+    latency: Duration,
+    error_rate: f64,
+}
+
+const USAGE: &str = "Usage: stress [--callers N] [--duration SECONDS]
+    [--mode device|controller|all] [--flavor current|multi|all]
+    [--latency-us N] [--error-rate FRACTION]";
+
+fn parse_args() -> Result<Options, Box<dyn Error + Sync + Send>> {
+    let mut opts = Options {
+        callers: 8,
+        duration: Duration::from_secs(5),
+        modes: vec!["device", "controller"],
+        flavors: vec!["current", "multi"],
+        latency: Duration::ZERO,
+        error_rate: 0.0,
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let val = args.next().ok_or(format!("{arg} requires a value"))?;
+        match arg.as_str() {
+            "--callers" => opts.callers = val.parse()?,
+            "--duration" => opts.duration = Duration::from_secs_f64(val.parse()?),
+            "--mode" => {
+                opts.modes = match val.as_str() {
+                    "device" => vec!["device"],
+                    "controller" => vec!["controller"],
+                    "all" => vec!["device", "controller"],
+                    _ => return Err(format!("unknown mode: {val}").into()),
+                }
+            }
+            "--flavor" => {
+                opts.flavors = match val.as_str() {
+                    "current" => vec!["current"],
+                    "multi" => vec!["multi"],
+                    "all" => vec!["current", "multi"],
+                    _ => return Err(format!("unknown flavor: {val}").into()),
+                }
+            }
+            "--latency-us" => opts.latency = Duration::from_micros(val.parse()?),
+            "--error-rate" => opts.error_rate = val.parse()?,
+            _ => return Err(format!("unknown option: {arg}").into()),
+        }
+    }
+    if opts.callers == 0 {
+        return Err("--callers must be at least 1".into());
+    }
+    Ok(opts)
+}
+
+/// The latencies of completed requests and the number that failed
+#[derive(Default)]
+struct Samples {
+    latencies: Vec<Duration>,
+    errors: usize,
+}
+
+impl Samples {
+    fn record<T, E>(&mut self, start: Instant, result: Result<T, E>) {
+        self.latencies.push(start.elapsed());
+        if result.is_err() {
+            self.errors += 1;
+        }
+    }
+
+    fn merge(&mut self, other: Samples) {
+        self.latencies.extend(other.latencies);
+        self.errors += other.errors;
+    }
+}
+
+fn fmt_duration(d: Duration) -> String {
+    if d < Duration::from_micros(1) {
+        format!("{}ns", d.as_nanos())
+    } else if d < Duration::from_millis(1) {
+        format!("{}us", d.as_micros())
+    } else {
+        format!("{:.1}ms", d.as_secs_f64() * 1000.0)
+    }
+}
+
+fn report(mode: &str, flavor: &str, opts: &Options, mut samples: Samples, elapsed: Duration) {
+    let n = samples.latencies.len();
+    samples.latencies.sort();
+    let pct = |p: usize| {
+        samples
+            .latencies
+            .get((n * p / 100).min(n.saturating_sub(1)))
+            .copied()
+            .unwrap_or_default()
+    };
+    println!(
+        "{mode:<11} {flavor:<8} {:>7} {n:>10} {:>10.1} {:>7} {:>7} {:>7} {:>7} {:>6.2}%",
+        opts.callers,
+        n as f64 / elapsed.as_secs_f64(),
+        fmt_duration(pct(50)),
+        fmt_duration(pct(90)),
+        fmt_duration(pct(99)),
+        fmt_duration(samples.latencies.last().copied().unwrap_or_default()),
+        if n == 0 {
+            0.0
+        } else {
+            samples.errors as f64 * 100.0 / n as f64
+        },
+    );
+}
+
+fn run_device(opts: &Options) -> (Samples, Duration) {
+    device::init();
+    let stop = Arc::new(AtomicBool::new(false));
+    let start = Instant::now();
+    let handles: Vec<_> = (0..opts.callers)
+        .map(|_| {
+            let stop = stop.clone();
+            thread::spawn(move || {
+                let mut samples = Samples::default();
+                let mut i = 0;
+                while !stop.load(Ordering::Relaxed) {
+                    i += 1;
+                    let start = Instant::now();
+                    if i % 2 == 0 {
+                        samples.record(start, device::one(1));
+                    } else {
+                        samples.record(start, device::two("stress"));
+                    }
+                }
+                samples
+            })
+        })
+        .collect();
+    thread::sleep(opts.duration);
+    stop.store(true, Ordering::Relaxed);
+    let mut samples = Samples::default();
+    for h in handles {
+        samples.merge(h.join().unwrap());
+    }
+    let elapsed = start.elapsed();
+    device::deinit();
+    (samples, elapsed)
+}
+
+fn run_controller(opts: &Options, flavor: &str) -> (Samples, Duration) {
+    let rt = match flavor {
+        "current" => tokio::runtime::Builder::new_current_thread(),
+        _ => tokio::runtime::Builder::new_multi_thread(),
+    }
+    .enable_all()
+    .build()
+    .unwrap();
+    let scenario = Scenario {
+        error_rate: opts.error_rate,
+        latency: if opts.latency.is_zero() {
+            Latency::None
+        } else {
+            Latency::Fixed(opts.latency)
+        },
+        ..Default::default()
+    };
+    let c = Arc::new(Controller::<TokioRuntime>::new().with_faults(FaultLayer::new(scenario)));
+    let duration = opts.duration;
+    let callers = opts.callers;
+    rt.block_on(async move {
+        let start = Instant::now();
+        let deadline = start + duration;
+        let handles: Vec<_> = (0..callers)
+            .map(|_| {
+                let c = c.clone();
+                tokio::spawn(async move {
+                    let mut samples = Samples::default();
+                    let mut i = 0;
+                    while Instant::now() < deadline {
+                        i += 1;
+                        let start = Instant::now();
+                        if i % 2 == 0 {
+                            samples.record(start, c.one(1).await);
+                        } else {
+                            samples.record(start, c.two("stress").await);
+                        }
+                        // Let other callers run on a current-thread
+                        // runtime even if requests never block.
+                        tokio::task::yield_now().await;
+                    }
+                    samples
+                })
+            })
+            .collect();
+        let mut samples = Samples::default();
+        for h in handles {
+            samples.merge(h.await.unwrap());
+        }
+        (samples, start.elapsed())
+    })
+}
+
+fn main() -> ExitCode {
+    let opts = match parse_args() {
+        Ok(opts) => opts,
+        Err(e) => {
+            eprintln!("stress: {e}\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    println!(
+        "{:<11} {:<8} {:>7} {:>10} {:>10} {:>7} {:>7} {:>7} {:>7} {:>7}",
+        "mode", "flavor", "callers", "requests", "req/s", "p50", "p90", "p99", "max", "errors"
+    );
+    for &mode in &opts.modes {
+        if mode == "device" {
+            // The device wrapper always uses its own current-thread
+            // runtime.
+            let (samples, elapsed) = run_device(&opts);
+            report(mode, "current", &opts, samples, elapsed);
+            continue;
+        }
+        for &flavor in &opts.flavors {
+            let (samples, elapsed) = run_controller(&opts, flavor);
+            report(mode, flavor, &opts, samples, elapsed);
+        }
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,17 @@
+//! This module provides the std APIs used by this crate that are newer
+//! than its declared minimum supported rust version, falling back to
+//! equivalents from other crates on older compilers. The build script
+//! sets `has_lazy_lock` when the compiler provides
+//! `std::sync::LazyLock` (rust 1.80). Older compilers must enable the
+//! `once_cell` feature, which is also usable on newer ones.
+
+#[cfg(feature = "once_cell")]
+pub(crate) use once_cell::sync::Lazy as LazyLock;
+
+#[cfg(all(not(feature = "once_cell"), has_lazy_lock))]
+pub(crate) use std::sync::LazyLock;
+
+#[cfg(all(not(feature = "once_cell"), not(has_lazy_lock)))]
+compile_error!(
+    "std::sync::LazyLock requires rust 1.80; enable the `once_cell` feature to use an older compiler"
+);
@@ -0,0 +1,78 @@
+//! Errors returned by the wrapper functions. A [DeviceError] is either
+//! a failure of the wrapper itself or a [ControllerError] from the
+//! singleton, which is kept as is so callers can still branch on its
+//! variant. Like [ControllerError], the `Display` text is stable and is
+//! the same whether or not the `thiserror` feature is enabled. A
+//! controller error's text is shown unchanged.
+use controller::error::ControllerError;
+#[cfg(not(feature = "thiserror"))]
+use std::{error::Error, fmt};
+
+#[derive(Debug)]
+#[cfg_attr(feature = "thiserror", derive(thiserror::Error))]
+#[non_exhaustive]
+pub enum DeviceError {
+    /// A wrapper function was called before [init] or after [deinit]
+    /// or [shutdown].
+    ///
+    /// [init]: crate::init
+    /// [deinit]: crate::deinit
+    /// [shutdown]: crate::shutdown
+    #[cfg_attr(feature = "thiserror", error("call init first"))]
+    NotInitialized,
+    /// The singleton failed. A panic that left it unusable is
+    /// [ControllerError::Poisoned], which is returned until [init] is
+    /// called again.
+    ///
+    /// [init]: crate::init
+    #[cfg_attr(feature = "thiserror", error(transparent))]
+    Controller(#[cfg_attr(feature = "thiserror", from)] ControllerError),
+}
+
+#[cfg(not(feature = "thiserror"))]
+impl From<ControllerError> for DeviceError {
+    fn from(e: ControllerError) -> Self {
+        DeviceError::Controller(e)
+    }
+}
+
+#[cfg(not(feature = "thiserror"))]
+impl fmt::Display for DeviceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviceError::NotInitialized => write!(f, "call init first"),
+            DeviceError::Controller(e) => e.fmt(f),
+        }
+    }
+}
+
+#[cfg(not(feature = "thiserror"))]
+impl Error for DeviceError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DeviceError::NotInitialized => None,
+            DeviceError::Controller(e) => e.source(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base::fault::Fault;
+    use std::error::Error;
+
+    #[test]
+    fn test_display() {
+        // This text is part of the API. Check it in every feature
+        // combination.
+        let e = DeviceError::NotInitialized;
+        assert_eq!(e.to_string(), "call init first");
+        assert!(e.source().is_none());
+        let e = DeviceError::from(ControllerError::Poisoned);
+        assert_eq!(e.to_string(), "controller poisoned");
+        let e = DeviceError::from(ControllerError::Transport(Box::new(Fault::Dropped)));
+        assert_eq!(e.to_string(), "transport error: connection dropped");
+        assert_eq!(e.source().unwrap().to_string(), "connection dropped");
+    }
+}
@@ -1,23 +1,59 @@
 //! This is a simple function-based wrapper around [Controller] that
 //! operates on a singleton. You must call [init] first, and then you
 //! can call the other functions, which call methods on the singleton.
+//! Calling [deinit] drops the singleton, and calling [shutdown] also
+//! stops the runtime. To choose how the runtime is built, for example
+//! to run spawned tasks on a pool of worker threads while many threads
+//! call into the wrapper, call [init_with_runtime] instead of [init].
 
+use base::{AsyncStream, Executor, Runtime, StdExecutor};
+use compat::LazyLock;
+pub use controller::batch::{BatchRequest, BatchResponse};
+pub use controller::error::ControllerError;
+pub use controller::event::Event;
+use controller::event::EventStream;
+pub use controller::health::HealthStatus;
+pub use controller::metrics::{Metrics, LATENCY_BUCKETS_MS};
+use controller::stream::ResponseStream;
 use controller::Controller;
-use runtime_tokio::TokioRuntime;
-use std::error::Error;
+pub use error::DeviceError;
+use hrtb::{AsyncMethod, BlockOn};
+use runtime_tokio::executor::TokioExecutor;
 use std::future::Future;
-use std::sync::{LazyLock, RwLock};
+use std::io;
+use std::sync::RwLock;
+use std::time::Duration;
 
-struct Wrapper {
-    rt: tokio::runtime::Runtime,
-    controller: RwLock<Option<Controller<TokioRuntime>>>,
+mod compat;
+pub mod error;
+
+/// The runtime behind the singleton. Nothing else here depends on which
+/// runtime it is, except [init_with_runtime], which builds a tokio
+/// runtime, and [resource_usage], which reads tokio's metrics. This
+/// crate enables controller's `rt-tokio` feature, which takes precedence
+/// over the others, so the default is always tokio.
+type DeviceRuntime = controller::DefaultRuntime;
+
+/// The executor is created by [init] and removed by [shutdown]. To avoid
+/// deadlocks, `controller` is always locked before `rt`.
+struct Wrapper<R: Runtime> {
+    rt: RwLock<Option<R::Executor>>,
+    controller: RwLock<Option<Controller<R>>>,
 }
 
-static CONTROLLER: LazyLock<Wrapper> = LazyLock::new(|| Wrapper {
-    rt: tokio::runtime::Builder::new_current_thread()
-        .enable_all()
-        .build()
-        .unwrap(),
+impl<R: Runtime> BlockOn for Wrapper<R> {
+    fn block_on<F: Future>(&self, f: F) -> F::Output {
+        // This is only called while the controller exists, and the
+        // executor is created first and removed last.
+        let rt = self.rt.read().unwrap();
+        rt.as_ref().expect("executor exists").block_on(f)
+    }
+}
+
+// The compat module only uses std's LazyLock when the compiler has it.
+#[allow(clippy::incompatible_msrv)]
+static CONTROLLER: LazyLock<Wrapper<DeviceRuntime>> = LazyLock::new(|| Wrapper {
+    rt: Default::default(),
     controller: Default::default(),
 });
 
@@ -109,63 +145,294 @@ static CONTROLLER: LazyLock<Wrapper> = LazyLock::new(|| Wrapper {
 // put a lifetime on that trait, it will apply to the whole thing.
 // Then we can use HRTB with that trait.
 //
-// The MethodCaller trait does not other than to apply the lifetime
-// associated with the trait to the controller and tie it to the
-// future. Since we need a concrete implementation, we provide a
-// trivial blanket implementation that just includes a parameter with
-// the same bounds as the associated type and then uses it as the
-// associated type. Now we can attach our HRTB to a parameter bound by
-// _this_ trait, and the lifetime will apply to the controller and the
-// future together. Effectively, this makes '2 and '3 above the same
-// as each other and distinct from '1.
-
-trait MethodCaller<'a, ArgT, ResultT>: FnOnce(&'a Controller<TokioRuntime>, ArgT) -> Self::Fut {
-    type Fut: Future<Output = Result<ResultT, Box<dyn Error + Sync + Send>>>;
-}
-impl<
-        'a,
-        ArgT,
-        ResultT,
-        FnT: FnOnce(&'a Controller<TokioRuntime>, ArgT) -> Fut,
-        Fut: Future<Output = Result<ResultT, Box<dyn Error + Sync + Send>>>,
-    > MethodCaller<'a, ArgT, ResultT> for FnT
-{
-    type Fut = Fut;
-}
+// The trait does nothing other than to apply the lifetime associated
+// with the trait to the controller and tie it to the future. Since we
+// need a concrete implementation, there is a trivial blanket
+// implementation that just includes a parameter with the same bounds
+// as the associated type and then uses it as the associated type. Now
+// we can attach our HRTB to a parameter bound by _this_ trait, and the
+// lifetime will apply to the controller and the future together.
+// Effectively, this makes '2 and '3 above the same as each other and
+// distinct from '1.
+//
+// This trait is reusable, so it lives in the hrtb crate as
+// [AsyncMethod], along with [hrtb::dispatch_blocking], which does the
+// actual call, and [hrtb::sync_facade], which generates the wrapper
+// functions.
 
 /// This is a generic dispatcher that is used by the wrapper API to
 /// call methods on the singleton. It takes a closure that takes a
 /// &[Controller] and an arg, calls the closure using the singleton,
-/// and returns the result. The [MethodCaller] trait ties the lifetime
+/// and returns the result. The [AsyncMethod] trait ties the lifetime
 /// of the controller to the lifetime of the Future.
-fn run_method<ArgT, ResultT, FnT>(
-    f: FnT,
-    arg: ArgT,
-) -> Result<ResultT, Box<dyn Error + Sync + Send>>
+fn run_method<ArgT, ResultT, FnT>(f: FnT, arg: ArgT) -> Result<ResultT, DeviceError>
 where
-    for<'a> FnT: MethodCaller<'a, ArgT, ResultT>,
+    for<'a> FnT: AsyncMethod<'a, Controller<DeviceRuntime>, ArgT, Result<ResultT, ControllerError>>,
     // Some day, one of these will work:
     // FnT: async FnOnce(&Controller, ArgT) -> Result<ResultT, Box<dyn Error + Sync + Send>>,
     // FnT: std::ops::AsyncFnOnce(&Controller, ArgT) -> Result<ResultT, Box<dyn Error + Sync + Send>>,
 {
+    let _span = base::trace_span!("device.dispatch", method = std::any::type_name::<FnT>());
+    #[cfg(feature = "tracing")]
+    let start = std::time::Instant::now();
     let lock = CONTROLLER.controller.read().unwrap();
     let Some(controller) = &*lock else {
-        return Err("call init first".into());
+        base::trace_event!("called before init");
+        return Err(DeviceError::NotInitialized);
     };
-    CONTROLLER.rt.block_on(f(controller, arg))
+    if controller.is_poisoned() {
+        base::trace_event!("controller poisoned");
+        return Err(ControllerError::Poisoned.into());
+    }
+    let result = hrtb::dispatch_blocking(&*CONTROLLER, controller, f, arg);
+    base::trace_event!(elapsed = ?start.elapsed(), ok = result.is_ok(), "dispatch finished");
+    Ok(result?)
+}
+
+/// How [init_with_runtime] builds the tokio runtime. The default is the
+/// current-thread runtime that [init] uses.
+#[derive(Debug, Clone, Default)]
+pub struct RuntimeConfig {
+    /// Use a multi-threaded runtime. Tasks spawned by the controller
+    /// then run on worker threads instead of only while a call is in
+    /// progress, and calls from different threads run in parallel.
+    pub multi_thread: bool,
+    /// The number of worker threads of a multi-threaded runtime. If
+    /// `None`, tokio's default of one per CPU is used. This must not be
+    /// 0.
+    pub worker_threads: Option<usize>,
+    /// The name of the runtime's threads. If `None`, tokio's default is
+    /// used.
+    pub thread_name: Option<String>,
+    /// The stack size, in bytes, of the runtime's threads. If `None`,
+    /// tokio's default is used.
+    pub thread_stack_size: Option<usize>,
+}
+
+impl RuntimeConfig {
+    fn build(&self) -> io::Result<TokioExecutor> {
+        let mut builder = if self.multi_thread {
+            tokio::runtime::Builder::new_multi_thread()
+        } else {
+            tokio::runtime::Builder::new_current_thread()
+        };
+        if let Some(n) = self.worker_threads {
+            // tokio panics rather than failing on 0.
+            if n == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "worker_threads must not be 0",
+                ));
+            }
+            builder.worker_threads(n);
+        }
+        if let Some(name) = &self.thread_name {
+            builder.thread_name(name);
+        }
+        if let Some(size) = self.thread_stack_size {
+            builder.thread_stack_size(size);
+        }
+        Ok(TokioExecutor::with_runtime(builder.enable_all().build()?))
+    }
+}
+
+/// The singleton counts requests so that [metrics] can report them.
+fn new_controller() -> Controller<DeviceRuntime> {
+    Controller::builder()
+        .metrics()
+        .build()
+        .expect("default settings are valid")
 }
 
+/// Create the singleton, replacing it if it exists. The runtime is only
+/// created the first time, or after [shutdown], so the runtime built by
+/// [init_with_runtime] is kept.
 pub fn init() {
+    let _span = base::trace_span!("device.init");
     let mut controller = CONTROLLER.controller.write().unwrap();
-    *controller = Some(Controller::new());
+    let mut rt = CONTROLLER.rt.write().unwrap();
+    if rt.is_none() {
+        *rt = Some(DeviceRuntime::new_executor().unwrap());
+    }
+    *controller = Some(new_controller());
+}
+
+/// Create the singleton like [init], but on a new runtime built as
+/// `config` describes. A runtime that already exists is replaced after
+/// the calls in progress finish, and the tasks still running on it are
+/// dropped, so call [shutdown] first to give them time to stop. If the
+/// runtime can't be built, nothing changes.
+pub fn init_with_runtime(config: RuntimeConfig) -> io::Result<()> {
+    let _span = base::trace_span!("device.init_with_runtime");
+    let new_rt = config.build()?;
+    let mut controller = CONTROLLER.controller.write().unwrap();
+    *controller = None;
+    let old_rt = CONTROLLER.rt.write().unwrap().replace(new_rt);
+    *controller = Some(new_controller());
+    drop(controller);
+    if let Some(rt) = old_rt {
+        rt.shutdown(Duration::ZERO);
+    }
+    Ok(())
+}
+
+/// Drop the singleton. Other functions fail until [init] is called
+/// again. With the `diagnostics` feature, `implbox::live_counts` can
+/// be checked afterward to make sure nothing created by the singleton
+/// is still alive.
+pub fn deinit() {
+    let _span = base::trace_span!("device.deinit");
+    let mut controller = CONTROLLER.controller.write().unwrap();
+    // Record what the controller last did. This is synchronous code, so
+    // it can't await the lock.
+    #[cfg(feature = "tracing")]
+    if let Some(c) = &*controller {
+        base::trace_event!(last_path = &*c.blocking_last_path(), "dropping controller");
+    }
+    *controller = None;
+}
+
+/// Drop the singleton like [deinit], and then stop the runtime, giving
+/// background tasks up to `timeout` to stop. Calls that are in progress
+/// finish first. Call this before the process exits so that nothing is
+/// cut off in the middle of an await. [init] starts a new runtime.
+pub fn shutdown(timeout: Duration) {
+    let _span = base::trace_span!("device.shutdown");
+    let mut controller = CONTROLLER.controller.write().unwrap();
+    *controller = None;
+    let rt = CONTROLLER.rt.write().unwrap().take();
+    if let Some(rt) = rt {
+        rt.shutdown(timeout);
+    }
+}
+
+/// Report resources in use. This works whether or not the singleton
+/// is initialized so that leaks remaining after [deinit] can be seen.
+#[cfg(feature = "accounting")]
+pub fn resource_usage() -> controller::ResourceUsage {
+    let lock = CONTROLLER.controller.read().unwrap();
+    let mut usage = match &*lock {
+        Some(controller) => CONTROLLER.block_on(controller.resource_usage()),
+        None => controller::ResourceUsage::global(),
+    };
+    if let Some(rt) = &*CONTROLLER.rt.read().unwrap() {
+        usage.tasks = rt.runtime().metrics().num_alive_tasks();
+    }
+    usage
 }
 
-pub fn one(val: i32) -> Result<i32, Box<dyn Error + Sync + Send>> {
-    run_method(Controller::one, val)
+/// Return the singleton's counters for export to the host's monitoring.
+/// They start over when the singleton is replaced. See
+/// [Controller::metrics].
+pub fn metrics() -> Result<Metrics, DeviceError> {
+    let lock = CONTROLLER.controller.read().unwrap();
+    let Some(controller) = &*lock else {
+        return Err(DeviceError::NotInitialized);
+    };
+    Ok(controller.metrics())
+}
+
+async fn run_health(
+    controller: &Controller<DeviceRuntime>,
+    _: (),
+) -> Result<HealthStatus, ControllerError> {
+    Ok(controller.health().await)
+}
+
+/// Check the singleton for the host's liveness and readiness probes.
+/// See [Controller::health]. A singleton that doesn't exist or is
+/// poisoned fails like any other call rather than being reported.
+pub fn health() -> Result<HealthStatus, DeviceError> {
+    run_method(run_health, ())
+}
+
+/// [Controller::batch] can't fail as a whole, but the wrapper can, so
+/// its results are wrapped for [run_method].
+async fn run_batch(
+    controller: &Controller<DeviceRuntime>,
+    requests: Vec<BatchRequest>,
+) -> Result<Vec<Result<BatchResponse, ControllerError>>, ControllerError> {
+    Ok(controller.batch(requests).await)
+}
+
+/// A response opened with [stream]. Iterating blocks until the next
+/// chunk arrives. The response can be read after the singleton is
+/// replaced, but not after [shutdown], when the next chunk is
+/// [DeviceError::NotInitialized] and the iterator ends.
+pub struct ResponseReader {
+    stream: ResponseStream,
+    done: bool,
+}
+
+impl Iterator for ResponseReader {
+    type Item = Result<Vec<u8>, DeviceError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let rt = CONTROLLER.rt.read().unwrap();
+        let Some(rt) = &*rt else {
+            self.done = true;
+            return Some(Err(DeviceError::NotInitialized));
+        };
+        rt.block_on(self.stream.next())
+            .map(|chunk| chunk.map_err(DeviceError::from))
+    }
+}
+
+async fn run_stream(
+    controller: &Controller<DeviceRuntime>,
+    path: &str,
+) -> Result<ResponseReader, ControllerError> {
+    let stream = controller.stream(path).await?;
+    Ok(ResponseReader {
+        stream,
+        done: false,
+    })
+}
+
+/// The events of the singleton, returned by [subscribe]. Iterating
+/// blocks until the next event, so read events on a thread of their
+/// own and pass them to whatever callback should handle them. Waiting
+/// doesn't use the runtime, so it doesn't hold up [shutdown]. The
+/// iterator ends once the events from before the singleton was dropped
+/// by [deinit], [init], or [shutdown] have been read.
+pub struct EventReader(EventStream<DeviceRuntime>);
+
+impl Iterator for EventReader {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        StdExecutor.block_on(self.0.next())
+    }
+}
+
+/// Receive the events of the singleton from now on. See
+/// [Controller::subscribe].
+pub fn subscribe() -> Result<EventReader, DeviceError> {
+    let lock = CONTROLLER.controller.read().unwrap();
+    let Some(controller) = &*lock else {
+        return Err(DeviceError::NotInitialized);
+    };
+    Ok(EventReader(controller.subscribe()))
 }
 
-pub fn two(val: &str) -> Result<String, Box<dyn Error + Sync + Send>> {
-    run_method(Controller::two, val)
+hrtb::sync_facade! {
+    dispatch = run_method;
+    pub fn one(val: i32) -> Result<i32, DeviceError> => Controller::one;
+    pub fn two(val: &str) -> Result<String, DeviceError> => Controller::two;
+    /// Send all of `requests` in one call. See [Controller::batch].
+    pub fn batch(
+        requests: Vec<BatchRequest>
+    ) -> Result<Vec<Result<BatchResponse, ControllerError>>, DeviceError> => run_batch;
+    /// Request `path` and read the response as it arrives. See
+    /// [Controller::stream].
+    pub fn stream(path: &str) -> Result<ResponseReader, DeviceError> => run_stream;
+    /// Stop accepting requests and wait for the ones in progress. Calls
+    /// fail with [ControllerError::Closed] until [init] is called again.
+    /// See [Controller::close].
+    pub fn close(timeout: Duration) -> Result<(), DeviceError> => Controller::close;
 }
 
 #[cfg(test)]
@@ -177,9 +444,89 @@ mod tests {
         // This is a duplication of the controller test using the
         // wrapper API.
         assert_eq!(two("quack").err().unwrap().to_string(), "call init first");
+        assert!(matches!(one(5), Err(DeviceError::NotInitialized)));
+        assert!(matches!(subscribe(), Err(DeviceError::NotInitialized)));
+        assert!(matches!(metrics(), Err(DeviceError::NotInitialized)));
+        assert!(matches!(health(), Err(DeviceError::NotInitialized)));
         init();
+        assert!(health().unwrap().is_ready());
         assert_eq!(one(5).unwrap(), 1);
         assert_eq!(one(3).err().unwrap().to_string(), "sorry, not that one");
         assert_eq!(two("potato").unwrap(), "two?val=potato&seq=2");
+        let results = batch(vec![
+            BatchRequest::One(5),
+            BatchRequest::Two("salad".to_string()),
+        ])
+        .unwrap();
+        assert_eq!(results[0].as_ref().unwrap(), &BatchResponse::One(3));
+        assert_eq!(
+            results[1].as_ref().unwrap(),
+            &BatchResponse::Two("two?val=salad&seq=4".to_string())
+        );
+        let mut reader = stream("events").unwrap();
+        deinit();
+        assert!(matches!(
+            batch(Vec::new()),
+            Err(DeviceError::NotInitialized)
+        ));
+        assert!(matches!(stream("events"), Err(DeviceError::NotInitialized)));
+        // The response outlives the singleton.
+        assert_eq!(reader.next().unwrap().unwrap(), b"events?seq=5");
+        assert!(reader.next().is_none());
+        assert_eq!(one(5).err().unwrap().to_string(), "call init first");
+        init();
+        let events = subscribe().unwrap();
+        let events = std::thread::spawn(move || events.collect::<Vec<_>>());
+        assert_eq!(one(5).unwrap(), 1);
+        shutdown(Duration::from_secs(1));
+        // Dropping the singleton ends the events.
+        assert_eq!(
+            events.join().unwrap(),
+            [
+                Event::RequestStarted {
+                    path: "one?val=5".to_string()
+                },
+                Event::RequestCompleted {
+                    seq: 1,
+                    path: "one?val=5".to_string()
+                },
+            ]
+        );
+        assert_eq!(one(5).err().unwrap().to_string(), "call init first");
+        init();
+        assert_eq!(one(5).unwrap(), 1);
+        let metrics = metrics().unwrap();
+        assert_eq!(metrics.requests, 1);
+        assert_eq!(metrics.seq, Some(1));
+        close(Duration::from_secs(1)).unwrap();
+        assert!(matches!(
+            one(5),
+            Err(DeviceError::Controller(ControllerError::Closed))
+        ));
+        assert!(!health().unwrap().is_ready());
+        init();
+        assert_eq!(one(5).unwrap(), 1);
+    }
+
+    #[cfg(feature = "accounting")]
+    #[test]
+    fn test_resource_usage() {
+        // Other tests may init or deinit concurrently, so only check
+        // what doesn't depend on that.
+        let usage = resource_usage();
+        assert_eq!(usage.tasks, 0);
+        // Not every box is a lock, but every lock is one of the boxes.
+        let lock_boxes: usize = usage
+            .implboxes
+            .iter()
+            .filter(|(name, _)| {
+                ["LockBox<", "PolicyLockBox<", "MutexBox<"]
+                    .iter()
+                    .any(|kind| name.starts_with(&format!("base::runtime::{kind}")))
+            })
+            .map(|(_, count)| count)
+            .sum();
+        assert_eq!(usage.locks, lock_boxes);
+        assert!(usage.locks <= usage.implboxes.values().sum());
     }
 }
@@ -0,0 +1,47 @@
+//! Run the device-cli example to test the whole stack from the
+//! command line.
+
+use std::process::{Command, Output};
+
+fn device_cli(args: &[&str]) -> Output {
+    // Examples are built next to the directory containing this test.
+    let mut path = std::env::current_exe().unwrap();
+    path.pop();
+    if path.ends_with("deps") {
+        path.pop();
+    }
+    path.push("examples");
+    path.push(format!("device-cli{}", std::env::consts::EXE_SUFFIX));
+    Command::new(path).args(args).output().unwrap()
+}
+
+#[test]
+fn test_commands() {
+    let out = device_cli(&["one", "5", "two", "potato", "one", "6"]);
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "1\ntwo?val=potato&seq=2\n3\n"
+    );
+}
+
+#[test]
+fn test_errors() {
+    let out = device_cli(&["one", "5", "one", "3", "one", "6"]);
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "1\n");
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "one 3: sorry, not that one\n"
+    );
+
+    let out = device_cli(&["one", "potato"]);
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "one potato: invalid digit found in string\n"
+    );
+
+    let out = device_cli(&["one"]);
+    assert_eq!(out.status.code(), Some(2));
+}
@@ -0,0 +1,50 @@
+//! Run the device wrapper on runtimes built by init_with_runtime. This
+//! is separate from the unit tests because it replaces the singleton's
+//! runtime.
+
+use device::RuntimeConfig;
+use std::io;
+use std::thread;
+
+#[test]
+fn test_init_with_runtime() {
+    let err = device::init_with_runtime(RuntimeConfig {
+        multi_thread: true,
+        worker_threads: Some(0),
+        ..Default::default()
+    })
+    .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    // A failed build leaves the device uninitialized.
+    assert_eq!(device::one(5).unwrap_err().to_string(), "call init first");
+
+    device::init_with_runtime(RuntimeConfig {
+        multi_thread: true,
+        worker_threads: Some(2),
+        thread_name: Some("device-worker".to_string()),
+        thread_stack_size: Some(1 << 20),
+    })
+    .unwrap();
+    assert_eq!(device::one(5).unwrap(), 1);
+    // Concurrent calls from many threads share the runtime.
+    let handles: Vec<_> = (0..16)
+        .map(|i| thread::spawn(move || device::two(&format!("t{i}")).unwrap()))
+        .collect();
+    let mut seqs: Vec<u32> = handles
+        .into_iter()
+        .map(|h| {
+            let path = h.join().unwrap();
+            path.rsplit_once("seq=").unwrap().1.parse().unwrap()
+        })
+        .collect();
+    seqs.sort();
+    assert_eq!(seqs, (2..18).collect::<Vec<_>>());
+
+    // Switching back to a current-thread runtime replaces the old one.
+    device::init_with_runtime(RuntimeConfig::default()).unwrap();
+    assert_eq!(device::one(5).unwrap(), 1);
+    // init keeps the runtime and replaces the controller.
+    device::init();
+    assert_eq!(device::one(5).unwrap(), 1);
+    device::shutdown(std::time::Duration::from_secs(1));
+}
@@ -0,0 +1,103 @@
+//! Call the device wrapper from many threads while other threads
+//! repeatedly initialize and tear down the singleton. The calls must
+//! either succeed or fail with an expected error, and everything must
+//! finish within a deadline. Run the long variant with
+//! `cargo test -p device --test stress -- --ignored`.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+#[derive(Default)]
+struct Counts {
+    ok: AtomicUsize,
+    not_initialized: AtomicUsize,
+    rejected: AtomicUsize,
+}
+
+fn worker(id: usize, stop: &AtomicBool, counts: &Counts) {
+    let mut i = 0;
+    while !stop.load(Ordering::Relaxed) {
+        i += 1;
+        let result = if i % 2 == 0 {
+            let val = (id + i) as i32 % 7;
+            device::one(val).map(|seq| {
+                assert!(seq > 0);
+            })
+        } else {
+            let val = format!("w{id}");
+            device::two(&val).map(|path| {
+                assert!(path.starts_with(&format!("two?val={val}&seq=")), "{path}");
+            })
+        };
+        match result {
+            Ok(()) => counts.ok.fetch_add(1, Ordering::Relaxed),
+            Err(e) => match e.to_string().as_str() {
+                "call init first" => counts.not_initialized.fetch_add(1, Ordering::Relaxed),
+                "sorry, not that one" => counts.rejected.fetch_add(1, Ordering::Relaxed),
+                other => panic!("unexpected error: {other}"),
+            },
+        };
+    }
+}
+
+fn admin(id: usize, stop: &AtomicBool) {
+    let mut i = 0;
+    while !stop.load(Ordering::Relaxed) {
+        i += 1;
+        // Alternate between reconfiguring (init on an initialized
+        // device) and tearing down.
+        if (id + i) % 3 == 0 {
+            device::deinit();
+        } else {
+            device::init();
+        }
+        thread::sleep(Duration::from_micros(200));
+    }
+}
+
+fn hammer(workers: usize, admins: usize, duration: Duration) {
+    let stop = Arc::new(AtomicBool::new(false));
+    let counts = Arc::new(Counts::default());
+    let (tx, rx) = mpsc::channel();
+    {
+        let stop = stop.clone();
+        let counts = counts.clone();
+        thread::spawn(move || {
+            let mut handles = Vec::new();
+            for id in 0..workers {
+                let stop = stop.clone();
+                let counts = counts.clone();
+                handles.push(thread::spawn(move || worker(id, &stop, &counts)));
+            }
+            for id in 0..admins {
+                let stop = stop.clone();
+                handles.push(thread::spawn(move || admin(id, &stop)));
+            }
+            let panicked = handles.into_iter().any(|h| h.join().is_err());
+            tx.send(panicked).unwrap();
+        });
+    }
+    thread::sleep(duration);
+    stop.store(true, Ordering::Relaxed);
+    match rx.recv_timeout(Duration::from_secs(30)) {
+        Ok(panicked) => assert!(!panicked, "a thread panicked"),
+        Err(_) => panic!("threads did not finish; probable deadlock"),
+    }
+    // Make sure the test actually exercised every path.
+    assert!(counts.ok.load(Ordering::Relaxed) > 0);
+    assert!(counts.not_initialized.load(Ordering::Relaxed) > 0);
+    assert!(counts.rejected.load(Ordering::Relaxed) > 0);
+}
+
+#[test]
+fn test_stress() {
+    hammer(32, 4, Duration::from_secs(1));
+}
+
+#[test]
+#[ignore]
+fn test_stress_long() {
+    hammer(64, 8, Duration::from_secs(60));
+}
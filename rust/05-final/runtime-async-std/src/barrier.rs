@@ -0,0 +1,17 @@
+use async_lock::Barrier;
+use base::AsyncBarrier;
+
+pub struct AsyncStdBarrier(Barrier);
+
+impl AsyncBarrier for AsyncStdBarrier {
+    /// async-lock's barrier already treats zero tasks like one.
+    fn new(n: usize) -> Self {
+        AsyncStdBarrier(Barrier::new(n))
+    }
+
+    async fn wait(&self) -> bool {
+        base::trace_future!(self.0.wait(), "barrier.wait")
+            .await
+            .is_leader()
+    }
+}
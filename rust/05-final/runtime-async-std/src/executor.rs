@@ -0,0 +1,13 @@
+use base::Executor;
+use std::future::Future;
+
+/// Runs futures with [async_std::task::block_on]. Spawned tasks run on
+/// async-std's global executor, which keeps running in the background,
+/// so there is nothing to shut down.
+pub struct AsyncStdExecutor;
+
+impl Executor for AsyncStdExecutor {
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        async_std::task::block_on(future)
+    }
+}
@@ -0,0 +1,195 @@
+//! An implementation of the [base] runtime traits on top of async-std,
+//! so the same [Runtime]-generic code, such as `Controller`, runs
+//! without tokio. Locks, channels, and barriers come from async-lock and
+//! async-channel, which async-std uses itself, and broadcast channels
+//! from async-broadcast. async-std has no notify, watch, or cancel token,
+//! so those are the executor-independent ones from [base].
+use crate::barrier::AsyncStdBarrier;
+use crate::broadcast::{AsyncStdBroadcast, AsyncStdBroadcastReceiver};
+use crate::channel::AsyncStdChannel;
+use crate::executor::AsyncStdExecutor;
+use crate::fs::AsyncStdFile;
+use crate::mutex::AsyncStdMutexWrapper;
+use crate::net::{AsyncStdTcpListener, AsyncStdTcpStream, AsyncStdUdpSocket};
+use crate::rwlock::AsyncStdLockWrapper;
+use crate::semaphore::AsyncStdSemaphoreWrapper;
+use crate::task::AsyncStdJoinHandle;
+use crate::time::AsyncStdClock;
+use base::{
+    AsyncBarrier, AsyncChannel, AsyncFile, AsyncMutex, AsyncNotify, AsyncRwLock, AsyncSemaphore,
+    AsyncStream, AsyncTcpListener, AsyncTcpStream, AsyncUdpSocket, BarrierBox, BoxFuture,
+    Broadcast, BroadcastBox, BroadcastReceiver, BroadcastReceiverBox, CancelToken, CancelTokenBox,
+    ChannelBox, ChannelStream, Elapsed, FileBox, JoinHandle, JoinHandleBox, LocalBoxFuture,
+    LockBox, LockPolicy, Locker, MutexBox, NotifyBox, PolicyLockBox, Runtime, SemaphoreBox,
+    StdCancelToken, StdNotify, StdWatch, StdWatchReceiver, StreamBox, TcpListenerBox, TcpStreamBox,
+    UdpSocketBox, Watch, WatchBox, WatchReceiver, WatchReceiverBox,
+};
+use implbox::{ImplBox, ImplBoxShared};
+use implbox_macros::implbox_impls;
+use std::fs::OpenOptions;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::Duration;
+
+pub mod barrier;
+pub mod broadcast;
+pub mod channel;
+pub mod executor;
+pub mod fs;
+pub mod mutex;
+pub mod net;
+pub mod rwlock;
+pub mod semaphore;
+pub mod task;
+pub mod time;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Default, Clone)]
+pub struct AsyncStdRuntime;
+
+impl Locker for AsyncStdRuntime {
+    #[implbox_impls(LockBox<T>, AsyncStdLockWrapper<T>, downcast)]
+    fn new_lock<T: Sync + Send + 'static>(item: T) -> impl AsyncRwLock<T> {
+        AsyncStdLockWrapper::<T>::new(item)
+    }
+
+    /// async-lock's lock always prefers writers and has no reader
+    /// limit, so the policy is ignored.
+    #[implbox_impls(PolicyLockBox<T>, AsyncStdLockWrapper<T>, downcast)]
+    fn new_lock_with<T: Sync + Send + 'static>(
+        _policy: LockPolicy,
+        item: T,
+    ) -> impl AsyncRwLock<T> {
+        AsyncStdLockWrapper::<T>::new(item)
+    }
+
+    #[implbox_impls(MutexBox<T>, AsyncStdMutexWrapper<T>, downcast)]
+    fn new_mutex<T: Sync + Send + 'static>(item: T) -> impl AsyncMutex<T> {
+        AsyncStdMutexWrapper::<T>::new(item)
+    }
+
+    #[implbox_impls(SemaphoreBox, AsyncStdSemaphoreWrapper, downcast)]
+    fn new_semaphore(permits: usize) -> impl AsyncSemaphore {
+        AsyncStdSemaphoreWrapper::new(permits)
+    }
+
+    #[implbox_impls(NotifyBox, StdNotify, downcast)]
+    fn new_notify() -> impl AsyncNotify {
+        StdNotify::new()
+    }
+
+    #[implbox_impls(BarrierBox, AsyncStdBarrier, downcast)]
+    fn new_barrier(n: usize) -> impl AsyncBarrier {
+        AsyncStdBarrier::new(n)
+    }
+
+    #[implbox_impls(ChannelBox<T>, AsyncStdChannel<T>, downcast)]
+    fn new_channel<T: Send + 'static>(capacity: Option<usize>) -> impl AsyncChannel<T> {
+        AsyncStdChannel::<T>::new(capacity)
+    }
+
+    #[implbox_impls(BroadcastBox<T>, AsyncStdBroadcast<T>, downcast)]
+    fn new_broadcast<T: Clone + Sync + Send + 'static>(capacity: usize) -> impl Broadcast<T> {
+        AsyncStdBroadcast::<T>::new(capacity)
+    }
+
+    #[implbox_impls(BroadcastReceiverBox<T>, AsyncStdBroadcastReceiver<T>, downcast)]
+    fn new_broadcast_receiver<T: Clone + Sync + Send + 'static>(
+        broadcast: &ImplBox<BroadcastBox<T>>,
+    ) -> impl BroadcastReceiver<T> {
+        broadcast
+            .downcast_ref::<AsyncStdBroadcast<T>>()
+            .expect("broadcast was not created by AsyncStdRuntime")
+            .subscribe()
+    }
+
+    #[implbox_impls(WatchBox<T>, StdWatch<T>, downcast)]
+    fn new_watch<T: Clone + Sync + Send + 'static>(initial: T) -> impl Watch<T> {
+        StdWatch::<T>::new(initial)
+    }
+
+    #[implbox_impls(WatchReceiverBox<T>, StdWatchReceiver<T>, downcast)]
+    fn new_watch_receiver<T: Clone + Sync + Send + 'static>(
+        watch: &ImplBox<WatchBox<T>>,
+    ) -> impl WatchReceiver<T> {
+        watch
+            .downcast_ref::<StdWatch<T>>()
+            .expect("watch was not created by AsyncStdRuntime")
+            .subscribe()
+    }
+
+    #[implbox_impls(StreamBox<T>, ChannelStream<T, AsyncStdRuntime>, downcast)]
+    fn new_stream<T: Send + 'static>(channel: ImplBoxShared<ChannelBox<T>>) -> impl AsyncStream<T> {
+        ChannelStream::<T, Self>::new(channel)
+    }
+}
+
+impl Runtime for AsyncStdRuntime {
+    type Executor = AsyncStdExecutor;
+    type Clock = AsyncStdClock;
+
+    fn new_executor() -> io::Result<AsyncStdExecutor> {
+        Ok(AsyncStdExecutor)
+    }
+
+    fn clock() -> AsyncStdClock {
+        AsyncStdClock
+    }
+
+    #[implbox_impls(JoinHandleBox<T>, AsyncStdJoinHandle<T>, downcast, name = "task")]
+    fn spawn<T: Send + 'static>(future: BoxFuture<'static, T>) -> impl JoinHandle<T> {
+        let (future, abort) = base::abortable(future);
+        AsyncStdJoinHandle::new(async_std::task::spawn(future), abort)
+    }
+
+    /// Unlike tokio, async-std doesn't need any setup for this.
+    #[implbox_impls(JoinHandleBox<T>, AsyncStdJoinHandle<T>, downcast, name = "local_task")]
+    fn spawn_local<T: Send + 'static>(future: LocalBoxFuture<'static, T>) -> impl JoinHandle<T> {
+        let (future, abort) = base::abortable(future);
+        AsyncStdJoinHandle::new(async_std::task::spawn_local(future), abort)
+    }
+
+    #[implbox_impls(CancelTokenBox, StdCancelToken, downcast)]
+    fn new_cancel_token(parent: Option<&ImplBox<CancelTokenBox>>) -> impl CancelToken + Clone {
+        match parent {
+            Some(parent) => parent
+                .downcast_ref::<StdCancelToken>()
+                .expect("cancel token was not created by AsyncStdRuntime")
+                .child(),
+            None => StdCancelToken::default(),
+        }
+    }
+
+    #[implbox_impls(TcpStreamBox, AsyncStdTcpStream, downcast, name = "tcp_stream")]
+    async fn connect_tcp(addr: SocketAddr) -> Result<impl AsyncTcpStream, io::Error> {
+        AsyncStdTcpStream::connect(addr).await
+    }
+
+    #[implbox_impls(TcpListenerBox, AsyncStdTcpListener, downcast, name = "tcp_listener")]
+    async fn bind_tcp(addr: SocketAddr) -> Result<impl AsyncTcpListener, io::Error> {
+        AsyncStdTcpListener::bind(addr).await
+    }
+
+    #[implbox_impls(UdpSocketBox, AsyncStdUdpSocket, downcast, name = "udp_socket")]
+    async fn bind_udp(addr: SocketAddr) -> Result<impl AsyncUdpSocket, io::Error> {
+        AsyncStdUdpSocket::bind(addr).await
+    }
+
+    #[implbox_impls(FileBox, AsyncStdFile, downcast, name = "file")]
+    async fn open_file(path: &Path, options: &OpenOptions) -> Result<impl AsyncFile, io::Error> {
+        AsyncStdFile::open(path, options).await
+    }
+
+    async fn timeout<F: Future + Send>(
+        duration: Duration,
+        future: F,
+    ) -> Result<F::Output, Elapsed> {
+        async_std::future::timeout(duration, future)
+            .await
+            .map_err(|_| Elapsed)
+    }
+}
@@ -0,0 +1,79 @@
+use async_std::io::{ReadExt, WriteExt};
+use async_std::net::{TcpListener, TcpStream, UdpSocket};
+use base::{AsyncTcpListener, AsyncTcpStream, AsyncUdpSocket};
+use std::io;
+use std::net::{Shutdown, SocketAddr};
+
+pub struct AsyncStdTcpStream(TcpStream);
+
+impl AsyncStdTcpStream {
+    pub async fn connect(addr: SocketAddr) -> io::Result<Self> {
+        let stream = base::trace_future!(TcpStream::connect(addr), "tcp.connect").await?;
+        Ok(AsyncStdTcpStream(stream))
+    }
+}
+
+impl AsyncTcpStream for AsyncStdTcpStream {
+    async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        base::trace_future!(self.0.read(buf), "tcp.read").await
+    }
+
+    async fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        base::trace_future!(self.0.write(buf), "tcp.write").await
+    }
+
+    async fn shutdown(&mut self) -> io::Result<()> {
+        self.0.shutdown(Shutdown::Write)
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.0.local_addr()
+    }
+
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.0.peer_addr()
+    }
+}
+
+pub struct AsyncStdTcpListener(TcpListener);
+
+impl AsyncStdTcpListener {
+    pub async fn bind(addr: SocketAddr) -> io::Result<Self> {
+        Ok(AsyncStdTcpListener(TcpListener::bind(addr).await?))
+    }
+}
+
+impl AsyncTcpListener for AsyncStdTcpListener {
+    async fn accept(
+        &self,
+    ) -> io::Result<(impl AsyncTcpStream + Send + Sync + 'static, SocketAddr)> {
+        let (stream, addr) = base::trace_future!(self.0.accept(), "tcp.accept").await?;
+        Ok((AsyncStdTcpStream(stream), addr))
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.0.local_addr()
+    }
+}
+
+pub struct AsyncStdUdpSocket(UdpSocket);
+
+impl AsyncStdUdpSocket {
+    pub async fn bind(addr: SocketAddr) -> io::Result<Self> {
+        Ok(AsyncStdUdpSocket(UdpSocket::bind(addr).await?))
+    }
+}
+
+impl AsyncUdpSocket for AsyncStdUdpSocket {
+    async fn send_to(&self, buf: &[u8], target: SocketAddr) -> io::Result<usize> {
+        base::trace_future!(self.0.send_to(buf, target), "udp.send_to").await
+    }
+
+    async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        base::trace_future!(self.0.recv_from(buf), "udp.recv_from").await
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.0.local_addr()
+    }
+}
@@ -0,0 +1,32 @@
+use base::{AbortHandle, JoinError, JoinHandle};
+
+/// async-std's task handles can only be cancelled by value, and joining
+/// a task that panicked panics, so tasks are spawned wrapped with
+/// [base::abortable].
+pub struct AsyncStdJoinHandle<T> {
+    handle: async_std::task::JoinHandle<Result<T, JoinError>>,
+    abort: AbortHandle,
+}
+
+impl<T> AsyncStdJoinHandle<T> {
+    pub fn new(
+        handle: async_std::task::JoinHandle<Result<T, JoinError>>,
+        abort: AbortHandle,
+    ) -> Self {
+        Self { handle, abort }
+    }
+}
+
+impl<T: Send + 'static> JoinHandle<T> for AsyncStdJoinHandle<T> {
+    async fn join(&mut self) -> Result<T, JoinError> {
+        base::trace_future!(&mut self.handle, "task.join").await
+    }
+
+    fn abort(&self) {
+        self.abort.abort()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.abort.is_finished()
+    }
+}
@@ -0,0 +1,18 @@
+use base::Clock;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+/// The clock for [crate::AsyncStdRuntime]. async-std has no virtual time,
+/// so tests that need it should use [base::VirtualClock].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AsyncStdClock;
+
+impl Clock for AsyncStdClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send {
+        async_std::task::sleep(duration)
+    }
+}
@@ -0,0 +1,133 @@
+use crate::state::{self, State};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use base::{Broadcast, BroadcastReceiver, RecvError};
+use core::future::poll_fn;
+use core::task::{Poll, Waker};
+
+struct BroadcastState<T> {
+    /// The most recent items, oldest first
+    items: VecDeque<T>,
+    /// The number of items sent before the first one in `items`
+    first: u64,
+    capacity: usize,
+    receivers: usize,
+    closed: bool,
+    /// Receivers waiting for the next item
+    waiting: Vec<Waker>,
+}
+
+impl<T> BroadcastState<T> {
+    /// The number of items sent so far
+    fn end(&self) -> u64 {
+        self.first + self.items.len() as u64
+    }
+}
+
+/// A broadcast channel whose capacity and number of receivers are chosen
+/// at run time, unlike embassy-sync's `PubSubChannel`. Receivers are
+/// created with [EmbassyBroadcast::subscribe].
+pub struct EmbassyBroadcast<T>(Arc<State<BroadcastState<T>>>);
+
+impl<T> EmbassyBroadcast<T> {
+    pub fn subscribe(&self) -> EmbassyBroadcastReceiver<T> {
+        let next = self.0.with(|s| {
+            s.receivers += 1;
+            s.end()
+        });
+        EmbassyBroadcastReceiver {
+            state: self.0.clone(),
+            next,
+        }
+    }
+}
+
+impl<T> Drop for EmbassyBroadcast<T> {
+    fn drop(&mut self) {
+        self.0.with(|s| {
+            s.closed = true;
+            state::wake_all(&mut s.waiting);
+        })
+    }
+}
+
+impl<T: Clone> Broadcast<T> for EmbassyBroadcast<T> {
+    fn new(capacity: usize) -> Self {
+        EmbassyBroadcast(Arc::new(State::new(BroadcastState {
+            items: VecDeque::new(),
+            first: 0,
+            capacity: capacity.max(1),
+            receivers: 0,
+            closed: false,
+            waiting: Vec::new(),
+        })))
+    }
+
+    fn send(&self, item: T) -> usize {
+        self.0.with(|s| {
+            s.items.push_back(item);
+            if s.items.len() > s.capacity {
+                s.items.pop_front();
+                s.first += 1;
+            }
+            state::wake_all(&mut s.waiting);
+            s.receivers
+        })
+    }
+
+    fn receiver_count(&self) -> usize {
+        self.0.with(|s| s.receivers)
+    }
+}
+
+pub struct EmbassyBroadcastReceiver<T> {
+    state: Arc<State<BroadcastState<T>>>,
+    /// The number of the next item to receive
+    next: u64,
+}
+
+impl<T> Drop for EmbassyBroadcastReceiver<T> {
+    fn drop(&mut self) {
+        self.state.with(|s| s.receivers -= 1);
+    }
+}
+
+impl<T: Clone> EmbassyBroadcastReceiver<T> {
+    fn take(next: &mut u64, s: &BroadcastState<T>) -> Result<Option<T>, RecvError> {
+        if *next < s.first {
+            let lagged = s.first - *next;
+            *next = s.first;
+            return Err(RecvError::Lagged(lagged));
+        }
+        if *next < s.end() {
+            let item = s.items[(*next - s.first) as usize].clone();
+            *next += 1;
+            return Ok(Some(item));
+        }
+        if s.closed {
+            return Err(RecvError::Closed);
+        }
+        Ok(None)
+    }
+}
+
+impl<T: Clone + Send + Sync> BroadcastReceiver<T> for EmbassyBroadcastReceiver<T> {
+    async fn recv(&mut self) -> Result<T, RecvError> {
+        poll_fn(|cx| {
+            self.state.with(|s| match Self::take(&mut self.next, s) {
+                Ok(Some(item)) => Poll::Ready(Ok(item)),
+                Err(e) => Poll::Ready(Err(e)),
+                Ok(None) => {
+                    state::register(&mut s.waiting, cx.waker());
+                    Poll::Pending
+                }
+            })
+        })
+        .await
+    }
+
+    fn try_recv(&mut self) -> Result<Option<T>, RecvError> {
+        self.state.with(|s| Self::take(&mut self.next, s))
+    }
+}
@@ -0,0 +1,140 @@
+use crate::state::{self, State};
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use base::{AsyncChannel, SendError, TryRecvError};
+use core::future::poll_fn;
+use core::task::{Poll, Waker};
+
+struct ChannelState<T> {
+    items: VecDeque<T>,
+    capacity: Option<usize>,
+    closed: bool,
+    /// The number of tasks waiting in recv
+    receiving: usize,
+    /// Tasks waiting to send or receive. They are all woken whenever
+    /// anything changes and check again.
+    waiting: Vec<Waker>,
+}
+
+impl<T> ChannelState<T> {
+    fn is_full(&self) -> bool {
+        self.capacity.is_some_and(|c| self.items.len() >= c)
+    }
+}
+
+/// A channel whose length is chosen at run time. embassy-sync's channels
+/// have their capacity in their type, which [AsyncChannel::new] can't
+/// choose.
+pub struct EmbassyChannel<T> {
+    state: State<ChannelState<T>>,
+}
+
+/// Counts a task as receiving for as long as it waits in recv, including
+/// when the future is dropped before it finishes.
+struct Receiving<'a, T>(&'a State<ChannelState<T>>);
+
+impl<'a, T> Receiving<'a, T> {
+    fn new(state: &'a State<ChannelState<T>>) -> Self {
+        state.with(|s| s.receiving += 1);
+        Receiving(state)
+    }
+}
+
+impl<T> Drop for Receiving<'_, T> {
+    fn drop(&mut self) {
+        self.0.with(|s| s.receiving -= 1);
+    }
+}
+
+impl<T: Send> AsyncChannel<T> for EmbassyChannel<T> {
+    fn new(capacity: Option<usize>) -> Self {
+        EmbassyChannel {
+            state: State::new(ChannelState {
+                items: VecDeque::new(),
+                capacity,
+                closed: false,
+                receiving: 0,
+                waiting: Vec::new(),
+            }),
+        }
+    }
+
+    async fn send(&self, item: T) -> Result<(), SendError<T>> {
+        let mut item = Some(item);
+        poll_fn(|cx| {
+            self.state.with(|s| {
+                if s.closed {
+                    return Poll::Ready(Err(SendError::Closed(item.take().unwrap())));
+                }
+                if s.is_full() {
+                    state::register(&mut s.waiting, cx.waker());
+                    return Poll::Pending;
+                }
+                s.items.push_back(item.take().unwrap());
+                state::wake_all(&mut s.waiting);
+                Poll::Ready(Ok(()))
+            })
+        })
+        .await
+    }
+
+    fn try_send(&self, item: T) -> Result<(), SendError<T>> {
+        self.state.with(|s| {
+            if s.closed {
+                return Err(SendError::Closed(item));
+            }
+            if s.is_full() {
+                return Err(SendError::Full(item));
+            }
+            s.items.push_back(item);
+            state::wake_all(&mut s.waiting);
+            Ok(())
+        })
+    }
+
+    async fn recv(&self) -> Option<T> {
+        let _receiving = Receiving::new(&self.state);
+        poll_fn(|cx| {
+            self.state.with(|s| match s.items.pop_front() {
+                Some(item) => {
+                    // There is room for a sender.
+                    state::wake_all(&mut s.waiting);
+                    Poll::Ready(Some(item))
+                }
+                None if s.closed => Poll::Ready(None),
+                None => {
+                    state::register(&mut s.waiting, cx.waker());
+                    Poll::Pending
+                }
+            })
+        })
+        .await
+    }
+
+    fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.state.with(|s| {
+            if s.receiving > 0 {
+                return Err(TryRecvError::Empty);
+            }
+            match s.items.pop_front() {
+                Some(item) => {
+                    state::wake_all(&mut s.waiting);
+                    Ok(item)
+                }
+                None if s.closed => Err(TryRecvError::Closed),
+                None => Err(TryRecvError::Empty),
+            }
+        })
+    }
+
+    fn close(&self) {
+        self.state.with(|s| {
+            s.closed = true;
+            state::wake_all(&mut s.waiting);
+        })
+    }
+
+    fn is_closed(&self) -> bool {
+        self.state.with(|s| s.closed)
+    }
+}
@@ -0,0 +1,138 @@
+//! An implementation of the [base] lock and channel traits for embassy,
+//! so code that is generic over a [Locker] can run on microcontrollers.
+//! The crate is `no_std` and needs only `alloc`, a `critical-section`
+//! implementation, and an embassy-time driver, which an embassy program
+//! already has. It uses [base] without its `std` feature.
+//!
+//! Only [Locker] is implemented. [base::Runtime] needs std for sockets,
+//! files, and threads, and embassy can't spawn tasks whose futures aren't
+//! known at compile time, so code that uses a `Runtime`, such as
+//! `Controller`, still needs std. Waiting for a timeout is
+//! [EmbassyRuntime::timeout] instead.
+//!
+//! - mutexes and semaphores are embassy-sync's
+//! - locks, channels, and broadcast channels have their own state, since
+//!   embassy-sync's have sizes fixed at compile time or can't be
+//!   upgraded. The state is only touched inside critical sections.
+//! - timeouts use embassy-time
+//! - notify, watch, barrier, and cancel token are the
+//!   executor-independent ones from [base]
+//!
+//! An embassy executor runs its tasks on one thread, so the locks'
+//! `blocking_*` functions panic if the lock is held.
+#![no_std]
+extern crate alloc;
+
+use crate::broadcast::{EmbassyBroadcast, EmbassyBroadcastReceiver};
+use crate::channel::EmbassyChannel;
+use crate::rwlock::{EmbassyLockWrapper, EmbassyMutexWrapper};
+use crate::semaphore::EmbassySemaphoreWrapper;
+use base::{
+    AsyncBarrier, AsyncChannel, AsyncMutex, AsyncNotify, AsyncRwLock, AsyncSemaphore, AsyncStream,
+    BarrierBox, Broadcast, BroadcastBox, BroadcastReceiver, BroadcastReceiverBox, ChannelBox,
+    ChannelStream, Elapsed, LockBox, LockPolicy, Locker, MutexBox, NotifyBox, PolicyLockBox,
+    SemaphoreBox, StdBarrier, StdNotify, StdWatch, StdWatchReceiver, StreamBox, Watch, WatchBox,
+    WatchReceiver, WatchReceiverBox,
+};
+use core::future::Future;
+use core::time::Duration;
+use implbox::{ImplBox, ImplBoxShared};
+use implbox_macros::implbox_impls;
+
+pub mod broadcast;
+pub mod channel;
+pub mod rwlock;
+pub mod semaphore;
+mod state;
+mod time;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Default, Clone)]
+pub struct EmbassyRuntime;
+
+impl EmbassyRuntime {
+    /// Wait for `future` for at most `duration`, like
+    /// [base::Runtime::timeout], using embassy-time's timer.
+    pub async fn timeout<F: Future>(duration: Duration, future: F) -> Result<F::Output, Elapsed> {
+        time::timeout(duration, future).await
+    }
+}
+
+impl Locker for EmbassyRuntime {
+    #[implbox_impls(LockBox<T>, EmbassyLockWrapper<T>, downcast)]
+    fn new_lock<T: Sync + Send + 'static>(item: T) -> impl AsyncRwLock<T> {
+        EmbassyLockWrapper::<T>::new(item)
+    }
+
+    /// This lock always prefers readers and has no reader limit, so
+    /// the policy is ignored.
+    #[implbox_impls(PolicyLockBox<T>, EmbassyLockWrapper<T>, downcast)]
+    fn new_lock_with<T: Sync + Send + 'static>(
+        _policy: LockPolicy,
+        item: T,
+    ) -> impl AsyncRwLock<T> {
+        EmbassyLockWrapper::<T>::new(item)
+    }
+
+    #[implbox_impls(MutexBox<T>, EmbassyMutexWrapper<T>, downcast)]
+    fn new_mutex<T: Sync + Send + 'static>(item: T) -> impl AsyncMutex<T> {
+        EmbassyMutexWrapper::<T>::new(item)
+    }
+
+    #[implbox_impls(SemaphoreBox, EmbassySemaphoreWrapper, downcast)]
+    fn new_semaphore(permits: usize) -> impl AsyncSemaphore {
+        EmbassySemaphoreWrapper::new(permits)
+    }
+
+    #[implbox_impls(NotifyBox, StdNotify, downcast)]
+    fn new_notify() -> impl AsyncNotify {
+        StdNotify::new()
+    }
+
+    #[implbox_impls(BarrierBox, StdBarrier, downcast)]
+    fn new_barrier(n: usize) -> impl AsyncBarrier {
+        StdBarrier::new(n)
+    }
+
+    #[implbox_impls(ChannelBox<T>, EmbassyChannel<T>, downcast)]
+    fn new_channel<T: Send + 'static>(capacity: Option<usize>) -> impl AsyncChannel<T> {
+        EmbassyChannel::<T>::new(capacity)
+    }
+
+    #[implbox_impls(BroadcastBox<T>, EmbassyBroadcast<T>, downcast)]
+    fn new_broadcast<T: Clone + Sync + Send + 'static>(capacity: usize) -> impl Broadcast<T> {
+        EmbassyBroadcast::<T>::new(capacity)
+    }
+
+    #[implbox_impls(BroadcastReceiverBox<T>, EmbassyBroadcastReceiver<T>, downcast)]
+    fn new_broadcast_receiver<T: Clone + Sync + Send + 'static>(
+        broadcast: &ImplBox<BroadcastBox<T>>,
+    ) -> impl BroadcastReceiver<T> {
+        broadcast
+            .downcast_ref::<EmbassyBroadcast<T>>()
+            .expect("broadcast was not created by EmbassyRuntime")
+            .subscribe()
+    }
+
+    #[implbox_impls(WatchBox<T>, StdWatch<T>, downcast)]
+    fn new_watch<T: Clone + Sync + Send + 'static>(initial: T) -> impl Watch<T> {
+        StdWatch::<T>::new(initial)
+    }
+
+    #[implbox_impls(WatchReceiverBox<T>, StdWatchReceiver<T>, downcast)]
+    fn new_watch_receiver<T: Clone + Sync + Send + 'static>(
+        watch: &ImplBox<WatchBox<T>>,
+    ) -> impl WatchReceiver<T> {
+        watch
+            .downcast_ref::<StdWatch<T>>()
+            .expect("watch was not created by EmbassyRuntime")
+            .subscribe()
+    }
+
+    #[implbox_impls(StreamBox<T>, ChannelStream<T, EmbassyRuntime>, downcast)]
+    fn new_stream<T: Send + 'static>(channel: ImplBoxShared<ChannelBox<T>>) -> impl AsyncStream<T> {
+        ChannelStream::<T, Self>::new(channel)
+    }
+}
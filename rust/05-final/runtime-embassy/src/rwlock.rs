@@ -0,0 +1,386 @@
+use crate::state::{self, State};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use base::{AsyncMutex, AsyncRwLock, Elapsed, Upgradable};
+use core::cell::UnsafeCell;
+use core::future::poll_fn;
+use core::mem;
+use core::ops::{Deref, DerefMut};
+use core::task::{Poll, Waker};
+use core::time::Duration;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::{Mutex, MutexGuard};
+
+#[derive(Clone, Copy)]
+enum Access {
+    Read,
+    Write,
+    Upgradable,
+    /// Turn the upgradable read into a write
+    Upgrade,
+}
+
+#[derive(Default)]
+struct LockState {
+    readers: usize,
+    writer: bool,
+    upgradable: bool,
+    /// Tasks waiting for the lock. They are all woken whenever it is
+    /// released and check again.
+    waiting: Vec<Waker>,
+}
+
+impl LockState {
+    fn available(&self, access: Access) -> bool {
+        match access {
+            Access::Read => !self.writer,
+            Access::Write => !self.writer && !self.upgradable && self.readers == 0,
+            Access::Upgradable => !self.writer && !self.upgradable,
+            Access::Upgrade => self.readers == 0,
+        }
+    }
+
+    fn take(&mut self, access: Access) {
+        match access {
+            Access::Read => self.readers += 1,
+            Access::Write => self.writer = true,
+            Access::Upgradable => self.upgradable = true,
+            Access::Upgrade => {
+                self.upgradable = false;
+                self.writer = true;
+            }
+        }
+    }
+
+    fn release(&mut self, access: Access) {
+        match access {
+            Access::Read => self.readers -= 1,
+            Access::Write | Access::Upgrade => self.writer = false,
+            Access::Upgradable => self.upgradable = false,
+        }
+    }
+}
+
+/// The state of a lock and the item it protects. The state says which
+/// guards exist, and a guard only gives access to the item in the way
+/// the state allows.
+struct Inner<T> {
+    state: State<LockState>,
+    item: UnsafeCell<T>,
+}
+
+// SAFETY: Access to the item is controlled by the state, as for
+// std::sync::RwLock.
+unsafe impl<T: Send + Sync> Sync for Inner<T> {}
+
+impl<T> Inner<T> {
+    /// Wait until `access` is possible without blocking the thread.
+    async fn lock(&self, access: Access) {
+        poll_fn(|cx| {
+            self.state.with(|state| {
+                if state.available(access) {
+                    state.take(access);
+                    return Poll::Ready(());
+                }
+                state::register(&mut state.waiting, cx.waker());
+                Poll::Pending
+            })
+        })
+        .await
+    }
+
+    fn try_lock(&self, access: Access) -> bool {
+        self.state.with(|state| {
+            let available = state.available(access);
+            if available {
+                state.take(access);
+            }
+            available
+        })
+    }
+
+    fn unlock(&self, access: Access) {
+        self.state.with(|state| {
+            state.release(access);
+            state::wake_all(&mut state.waiting);
+        })
+    }
+
+    /// SAFETY: The caller must hold a guard that allows reading.
+    unsafe fn item(&self) -> &T {
+        &*self.item.get()
+    }
+
+    /// SAFETY: The caller must hold a guard that allows writing, and this
+    /// must be the only reference to the item.
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn item_mut(&self) -> &mut T {
+        &mut *self.item.get()
+    }
+}
+
+/// A reader-writer lock that never blocks the thread. embassy-sync has a
+/// reader-writer lock, but it can't be upgraded and has no owned guards,
+/// so this keeps its own state, which is only touched inside critical
+/// sections. It doesn't prefer writers, so a steady stream of readers
+/// can keep a writer waiting.
+pub struct EmbassyLockWrapper<T> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T: Default> Default for EmbassyLockWrapper<T> {
+    fn default() -> Self {
+        Self::with_item(T::default())
+    }
+}
+
+impl<T> EmbassyLockWrapper<T> {
+    fn with_item(item: T) -> Self {
+        EmbassyLockWrapper {
+            inner: Arc::new(Inner {
+                state: State::new(LockState::default()),
+                item: UnsafeCell::new(item),
+            }),
+        }
+    }
+
+    fn read_guard(&self) -> EmbassyReadGuard<'_, T> {
+        EmbassyReadGuard { inner: &self.inner }
+    }
+
+    fn write_guard(&self) -> EmbassyWriteGuard<'_, T> {
+        EmbassyWriteGuard { inner: &self.inner }
+    }
+}
+
+pub struct EmbassyReadGuard<'a, T> {
+    inner: &'a Inner<T>,
+}
+
+impl<T> Deref for EmbassyReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: This guard holds a read lock.
+        unsafe { self.inner.item() }
+    }
+}
+
+impl<T> Drop for EmbassyReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.inner.unlock(Access::Read);
+    }
+}
+
+pub struct EmbassyWriteGuard<'a, T> {
+    inner: &'a Inner<T>,
+}
+
+impl<T> Deref for EmbassyWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: This guard holds the write lock.
+        unsafe { self.inner.item() }
+    }
+}
+
+impl<T> DerefMut for EmbassyWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: This guard holds the write lock, and `&mut self` makes
+        // this the only reference.
+        unsafe { self.inner.item_mut() }
+    }
+}
+
+impl<T> Drop for EmbassyWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.inner.unlock(Access::Write);
+    }
+}
+
+/// The guard returned by [AsyncRwLock::upgradable_read]
+pub struct EmbassyUpgradableReadGuard<'a, T> {
+    inner: &'a Inner<T>,
+}
+
+impl<T> Deref for EmbassyUpgradableReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: This guard holds the upgradable read lock.
+        unsafe { self.inner.item() }
+    }
+}
+
+impl<T> Drop for EmbassyUpgradableReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.inner.unlock(Access::Upgradable);
+    }
+}
+
+impl<'a, T: Sync + Send + 'a> Upgradable<'a, T> for EmbassyUpgradableReadGuard<'a, T> {
+    type WriteGuard = EmbassyWriteGuard<'a, T>;
+
+    async fn upgrade(self) -> EmbassyWriteGuard<'a, T> {
+        let inner = self.inner;
+        // The upgradable lock becomes the write lock, so it must not be
+        // released.
+        mem::forget(self);
+        inner.lock(Access::Upgrade).await;
+        EmbassyWriteGuard { inner }
+    }
+}
+
+pub struct EmbassyOwnedReadGuard<T> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T> Deref for EmbassyOwnedReadGuard<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: This guard holds a read lock.
+        unsafe { self.inner.item() }
+    }
+}
+
+impl<T> Drop for EmbassyOwnedReadGuard<T> {
+    fn drop(&mut self) {
+        self.inner.unlock(Access::Read);
+    }
+}
+
+pub struct EmbassyOwnedWriteGuard<T> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T> Deref for EmbassyOwnedWriteGuard<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: This guard holds the write lock.
+        unsafe { self.inner.item() }
+    }
+}
+
+impl<T> DerefMut for EmbassyOwnedWriteGuard<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: As for EmbassyWriteGuard.
+        unsafe { self.inner.item_mut() }
+    }
+}
+
+impl<T> Drop for EmbassyOwnedWriteGuard<T> {
+    fn drop(&mut self) {
+        self.inner.unlock(Access::Write);
+    }
+}
+
+impl<T: Sync + Send + 'static> AsyncRwLock<T> for EmbassyLockWrapper<T> {
+    type ReadGuard<'a>
+        = EmbassyReadGuard<'a, T>
+    where
+        Self: 'a;
+    type WriteGuard<'a>
+        = EmbassyWriteGuard<'a, T>
+    where
+        Self: 'a;
+    type UpgradableReadGuard<'a>
+        = EmbassyUpgradableReadGuard<'a, T>
+    where
+        Self: 'a;
+    type OwnedReadGuard = EmbassyOwnedReadGuard<T>;
+    type OwnedWriteGuard = EmbassyOwnedWriteGuard<T>;
+
+    fn new(item: T) -> Self {
+        Self::with_item(item)
+    }
+
+    async fn read(&self) -> EmbassyReadGuard<'_, T> {
+        self.inner.lock(Access::Read).await;
+        self.read_guard()
+    }
+
+    async fn write(&self) -> EmbassyWriteGuard<'_, T> {
+        self.inner.lock(Access::Write).await;
+        self.write_guard()
+    }
+
+    async fn upgradable_read(&self) -> EmbassyUpgradableReadGuard<'_, T> {
+        self.inner.lock(Access::Upgradable).await;
+        EmbassyUpgradableReadGuard { inner: &self.inner }
+    }
+
+    async fn read_owned(&self) -> EmbassyOwnedReadGuard<T> {
+        self.inner.lock(Access::Read).await;
+        EmbassyOwnedReadGuard {
+            inner: self.inner.clone(),
+        }
+    }
+
+    async fn write_owned(&self) -> EmbassyOwnedWriteGuard<T> {
+        self.inner.lock(Access::Write).await;
+        EmbassyOwnedWriteGuard {
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// An embassy executor runs every task on one thread, so waiting for
+    /// the lock would wait forever, and this panics if the lock isn't
+    /// available.
+    fn blocking_read(&self) -> EmbassyReadGuard<'_, T> {
+        self.try_read()
+            .expect("blocking_read would block the executor")
+    }
+
+    /// Like [EmbassyLockWrapper::blocking_read], this panics if the lock
+    /// isn't available.
+    fn blocking_write(&self) -> EmbassyWriteGuard<'_, T> {
+        self.try_write()
+            .expect("blocking_write would block the executor")
+    }
+
+    fn try_read(&self) -> Option<EmbassyReadGuard<'_, T>> {
+        self.inner.try_lock(Access::Read).then(|| self.read_guard())
+    }
+
+    fn try_write(&self) -> Option<EmbassyWriteGuard<'_, T>> {
+        self.inner
+            .try_lock(Access::Write)
+            .then(|| self.write_guard())
+    }
+
+    async fn read_timeout(&self, duration: Duration) -> Result<EmbassyReadGuard<'_, T>, Elapsed> {
+        crate::time::timeout(duration, self.read()).await
+    }
+
+    async fn write_timeout(&self, duration: Duration) -> Result<EmbassyWriteGuard<'_, T>, Elapsed> {
+        crate::time::timeout(duration, self.write()).await
+    }
+}
+
+/// embassy-sync's async mutex. Its guard borrows the mutex, which is all
+/// [AsyncMutex] needs.
+pub struct EmbassyMutexWrapper<T>(Mutex<CriticalSectionRawMutex, T>);
+
+impl<T: Default> Default for EmbassyMutexWrapper<T> {
+    fn default() -> Self {
+        EmbassyMutexWrapper(Mutex::new(T::default()))
+    }
+}
+
+impl<T: Sync + Send + 'static> AsyncMutex<T> for EmbassyMutexWrapper<T> {
+    type Guard<'a>
+        = MutexGuard<'a, CriticalSectionRawMutex, T>
+    where
+        Self: 'a;
+
+    fn new(item: T) -> Self {
+        EmbassyMutexWrapper(Mutex::new(item))
+    }
+
+    async fn lock(&self) -> MutexGuard<'_, CriticalSectionRawMutex, T> {
+        self.0.lock().await
+    }
+}
@@ -0,0 +1,40 @@
+use base::AsyncSemaphore;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::semaphore::{GreedySemaphore, Semaphore, SemaphoreReleaser};
+
+type Inner = GreedySemaphore<CriticalSectionRawMutex>;
+
+pub struct EmbassySemaphoreWrapper(Inner);
+
+pub struct EmbassyPermit<'a> {
+    // Only held so the permit is released when this is dropped
+    _releaser: SemaphoreReleaser<'a, Inner>,
+}
+
+impl AsyncSemaphore for EmbassySemaphoreWrapper {
+    type Permit<'a> = EmbassyPermit<'a>;
+
+    fn new(permits: usize) -> Self {
+        EmbassySemaphoreWrapper(GreedySemaphore::new(permits))
+    }
+
+    async fn acquire(&self) -> EmbassyPermit<'_> {
+        let permit = self.0.acquire(1).await;
+        match permit {
+            Ok(permit) => EmbassyPermit { _releaser: permit },
+            Err(never) => match never {},
+        }
+    }
+
+    fn try_acquire(&self) -> Option<EmbassyPermit<'_>> {
+        self.0
+            .try_acquire(1)
+            .map(|permit| EmbassyPermit { _releaser: permit })
+    }
+
+    /// embassy-sync doesn't report how many permits are left, so this
+    /// takes all of them, counts them, and gives them back.
+    fn permits(&self) -> usize {
+        self.0.try_acquire_all(0).map_or(0, |all| all.permits())
+    }
+}
@@ -0,0 +1,33 @@
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::task::Waker;
+use embassy_sync::blocking_mutex::CriticalSectionMutex;
+
+/// The state of a lock or channel. It is only reached inside a critical
+/// section, so it can be shared with interrupt handlers and, on
+/// multi-core chips, with other cores.
+pub(crate) struct State<T>(CriticalSectionMutex<RefCell<T>>);
+
+impl<T> State<T> {
+    pub(crate) fn new(state: T) -> Self {
+        State(CriticalSectionMutex::new(RefCell::new(state)))
+    }
+
+    pub(crate) fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        self.0.lock(|state| f(&mut state.borrow_mut()))
+    }
+}
+
+/// Remember `waker` so it can be woken when the state changes, unless it
+/// is already there.
+pub(crate) fn register(waiting: &mut Vec<Waker>, waker: &Waker) {
+    if !waiting.iter().any(|w| w.will_wake(waker)) {
+        waiting.push(waker.clone());
+    }
+}
+
+pub(crate) fn wake_all(waiting: &mut Vec<Waker>) {
+    for waker in waiting.drain(..) {
+        waker.wake();
+    }
+}
@@ -0,0 +1,190 @@
+//! These run on the host, with std's critical sections and embassy-time's
+//! std driver, and use [base::StdExecutor] instead of an embassy
+//! executor.
+extern crate std;
+
+use super::*;
+use alloc::string::String;
+use base::{Executor, RecvError, SendError, StdExecutor, TryRecvError, Upgradable};
+use std::sync::Arc;
+use std::thread;
+
+type R = EmbassyRuntime;
+
+fn run<F: Future>(future: F) -> F::Output {
+    StdExecutor.block_on(future)
+}
+
+#[test]
+fn test_lock() {
+    run(async {
+        let l = R::box_lock(3);
+        let lock = R::unbox_lock(&l);
+        {
+            let r1 = lock.read().await;
+            let r2 = lock.try_read().unwrap();
+            assert_eq!(*r1 + *r2, 6);
+            assert!(lock.try_write().is_none());
+        }
+        *lock.write().await += 1;
+        let owned = lock.read_owned().await;
+        drop(l);
+        // The owned guard keeps the item alive.
+        assert_eq!(*owned, 4);
+    })
+}
+
+#[test]
+fn test_wait() {
+    // Waiting doesn't block the thread, and the waiter is woken when the
+    // lock is released.
+    let lock = Arc::new(R::new_lock(1));
+    let mut guard = run(lock.write_owned());
+    let reader = {
+        let lock = lock.clone();
+        thread::spawn(move || run(async { *lock.read().await }))
+    };
+    thread::sleep(Duration::from_millis(10));
+    *guard = 2;
+    drop(guard);
+    assert_eq!(reader.join().unwrap(), 2);
+}
+
+#[test]
+fn test_upgrade() {
+    run(async {
+        let lock = Arc::new(R::new_lock(1));
+        let upgradable = lock.upgradable_read().await;
+        // Plain readers can still share the lock.
+        assert_eq!(*lock.read().await, 1);
+        assert!(lock.try_write().is_none());
+        let mut w = upgradable.upgrade().await;
+        *w = 2;
+        drop(w);
+        assert_eq!(*lock.blocking_read(), 2);
+    })
+}
+
+#[test]
+fn test_timeout() {
+    run(async {
+        let lock = R::new_lock(1);
+        let w = lock.write().await;
+        let r = lock.read_timeout(Duration::from_millis(10)).await;
+        assert_eq!(r.err(), Some(Elapsed));
+        drop(w);
+        assert_eq!(
+            *lock.write_timeout(Duration::from_secs(1)).await.unwrap(),
+            1
+        );
+        let ok = R::timeout(Duration::from_secs(1), async { 5 }).await;
+        assert_eq!(ok, Ok(5));
+        // A duration embassy-time can't represent never ends.
+        let ok = R::timeout(Duration::MAX, async { 6 }).await;
+        assert_eq!(ok, Ok(6));
+    })
+}
+
+#[test]
+fn test_mutex_and_semaphore() {
+    run(async {
+        let m = R::new_mutex(String::new());
+        m.lock().await.push_str("potato");
+        assert_eq!(*m.lock().await, "potato");
+
+        let s = R::new_semaphore(2);
+        let p1 = s.acquire().await;
+        let p2 = s.try_acquire().unwrap();
+        assert_eq!(s.permits(), 0);
+        assert!(s.try_acquire().is_none());
+        drop(p1);
+        assert_eq!(s.permits(), 1);
+        drop(p2);
+        assert_eq!(s.permits(), 2);
+    })
+}
+
+#[test]
+fn test_channel() {
+    run(async {
+        let c = R::new_channel(Some(1));
+        c.send(1).await.unwrap();
+        assert_eq!(c.try_send(2), Err(SendError::Full(2)));
+        c.close();
+        assert!(c.is_closed());
+        assert_eq!(c.send(3).await, Err(SendError::Closed(3)));
+        assert_eq!(c.recv().await, Some(1));
+        assert_eq!(c.recv().await, None);
+        assert_eq!(c.try_recv(), Err(TryRecvError::Closed));
+    })
+}
+
+#[test]
+fn test_channel_wait() {
+    // A full channel makes the sender wait until there is room.
+    let c = Arc::new(R::new_channel(Some(1)));
+    run(c.send(1)).unwrap();
+    let sender = {
+        let c = c.clone();
+        thread::spawn(move || run(c.send(2)))
+    };
+    thread::sleep(Duration::from_millis(10));
+    assert_eq!(run(c.recv()), Some(1));
+    sender.join().unwrap().unwrap();
+    assert_eq!(run(c.recv()), Some(2));
+}
+
+#[test]
+fn test_stream() {
+    run(async {
+        let c = R::box_shared_channel(None);
+        let mut s = R::box_stream(c.clone());
+        let tx = R::unbox_channel(&c);
+        tx.send("potato").await.unwrap();
+        tx.send("salad").await.unwrap();
+        tx.close();
+        let s = R::unbox_mut_stream(&mut s);
+        assert_eq!(s.next().await, Some("potato"));
+        assert_eq!(s.next().await, Some("salad"));
+        assert_eq!(s.next().await, None);
+    })
+}
+
+#[test]
+fn test_broadcast() {
+    run(async {
+        let b = R::box_broadcast(2);
+        let tx = R::unbox_broadcast(&b);
+        // Sending without receivers is not an error.
+        assert_eq!(tx.send(0), 0);
+        let mut rx = R::box_broadcast_receiver(&b);
+        let rx = R::unbox_mut_broadcast_receiver(&mut rx);
+        assert_eq!(tx.receiver_count(), 1);
+        for i in 1..=3 {
+            assert_eq!(tx.send(i), 1);
+        }
+        assert_eq!(rx.recv().await, Err(RecvError::Lagged(1)));
+        assert_eq!(rx.recv().await, Ok(2));
+        assert_eq!(rx.try_recv(), Ok(Some(3)));
+        assert_eq!(rx.try_recv(), Ok(None));
+        drop(b);
+        assert_eq!(rx.recv().await, Err(RecvError::Closed));
+    })
+}
+
+#[test]
+fn test_watch() {
+    run(async {
+        let w = R::box_watch(1);
+        let mut boxed = R::box_watch_receiver(&w);
+        let rx = R::unbox_mut_watch_receiver(&mut boxed);
+        let tx = R::unbox_watch(&w);
+        tx.send(2);
+        tx.send(3);
+        // Only the latest value is seen.
+        rx.changed().await.unwrap();
+        assert_eq!(rx.get(), 3);
+        drop(w);
+        assert_eq!(rx.changed().await, Err(RecvError::Closed));
+    })
+}
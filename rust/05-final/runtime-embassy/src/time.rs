@@ -0,0 +1,22 @@
+use base::Elapsed;
+use core::future::Future;
+use core::time::Duration;
+use embassy_time::Instant;
+
+/// Wait for `future` for at most `duration`, using embassy-time's timer.
+/// A deadline too far away for embassy-time to represent is never
+/// reached.
+pub(crate) async fn timeout<F: Future>(
+    duration: Duration,
+    future: F,
+) -> Result<F::Output, Elapsed> {
+    let deadline = embassy_time::Duration::try_from(duration)
+        .ok()
+        .and_then(|d| Instant::now().checked_add(d));
+    match deadline {
+        Some(deadline) => embassy_time::with_deadline(deadline, future)
+            .await
+            .map_err(|_| Elapsed),
+        None => Ok(future.await),
+    }
+}
@@ -0,0 +1,139 @@
+use base::{AsyncChannel, SendError, TryRecvError};
+use futures::channel::mpsc;
+use futures::lock::Mutex;
+use futures::StreamExt;
+use std::future::poll_fn;
+
+/// A bounded sender needs `&mut` to send, so it is locked while a send
+/// waits for room, and waiting senders take turns. `closer` is a clone
+/// that never sends, so the channel can be closed without waiting for
+/// them.
+enum Sender<T> {
+    Bounded {
+        tx: Mutex<mpsc::Sender<T>>,
+        closer: std::sync::Mutex<mpsc::Sender<T>>,
+    },
+    Unbounded(mpsc::UnboundedSender<T>),
+}
+
+enum Receiver<T> {
+    Bounded(mpsc::Receiver<T>),
+    Unbounded(mpsc::UnboundedReceiver<T>),
+}
+
+/// A futures mpsc channel, with both of its halves. The receiver is
+/// locked while a task waits in [AsyncChannel::recv], so receivers take
+/// turns. Closing the channel stops sends, and the receiver returns
+/// `None` once the channel is drained.
+pub struct FuturesChannel<T> {
+    tx: Sender<T>,
+    rx: Mutex<Receiver<T>>,
+}
+
+impl<T: Send> AsyncChannel<T> for FuturesChannel<T> {
+    /// futures' channels hold one item more than they are created for,
+    /// so a bounded channel is created with one less. A capacity of 0
+    /// acts like 1.
+    fn new(capacity: Option<usize>) -> Self {
+        let (tx, rx) = match capacity {
+            Some(capacity) => {
+                let (tx, rx) = mpsc::channel(capacity.saturating_sub(1));
+                let tx = Sender::Bounded {
+                    closer: std::sync::Mutex::new(tx.clone()),
+                    tx: Mutex::new(tx),
+                };
+                (tx, Receiver::Bounded(rx))
+            }
+            None => {
+                let (tx, rx) = mpsc::unbounded();
+                (Sender::Unbounded(tx), Receiver::Unbounded(rx))
+            }
+        };
+        FuturesChannel {
+            tx,
+            rx: Mutex::new(rx),
+        }
+    }
+
+    async fn send(&self, item: T) -> Result<(), SendError<T>> {
+        let tx = match &self.tx {
+            Sender::Bounded { tx, .. } => tx,
+            Sender::Unbounded(tx) => {
+                return tx
+                    .unbounded_send(item)
+                    .map_err(|e| SendError::Closed(e.into_inner()));
+            }
+        };
+        let send = async {
+            let mut tx = tx.lock().await;
+            if poll_fn(|cx| tx.poll_ready(cx)).await.is_err() {
+                return Err(SendError::Closed(item));
+            }
+            // Only this sender sends, and it has room, so this can only
+            // fail if the channel was closed in between.
+            tx.try_send(item)
+                .map_err(|e| SendError::Closed(e.into_inner()))
+        };
+        base::trace_future!(send, "channel.send").await
+    }
+
+    /// A bounded channel is full while another task is waiting to send.
+    fn try_send(&self, item: T) -> Result<(), SendError<T>> {
+        match &self.tx {
+            Sender::Bounded { tx, .. } => {
+                let Some(mut tx) = tx.try_lock() else {
+                    return Err(SendError::Full(item));
+                };
+                tx.try_send(item).map_err(|e| {
+                    if e.is_full() {
+                        SendError::Full(e.into_inner())
+                    } else {
+                        SendError::Closed(e.into_inner())
+                    }
+                })
+            }
+            Sender::Unbounded(tx) => tx
+                .unbounded_send(item)
+                .map_err(|e| SendError::Closed(e.into_inner())),
+        }
+    }
+
+    async fn recv(&self) -> Option<T> {
+        let recv = async {
+            let mut rx = self.rx.lock().await;
+            match &mut *rx {
+                Receiver::Bounded(rx) => rx.next().await,
+                Receiver::Unbounded(rx) => rx.next().await,
+            }
+        };
+        base::trace_future!(recv, "channel.recv").await
+    }
+
+    fn try_recv(&self) -> Result<T, TryRecvError> {
+        let Some(mut rx) = self.rx.try_lock() else {
+            return Err(TryRecvError::Empty);
+        };
+        let result = match &mut *rx {
+            Receiver::Bounded(rx) => rx.try_recv(),
+            Receiver::Unbounded(rx) => rx.try_recv(),
+        };
+        result.map_err(|e| match e {
+            mpsc::TryRecvError::Empty => TryRecvError::Empty,
+            mpsc::TryRecvError::Closed => TryRecvError::Closed,
+        })
+    }
+
+    fn close(&self) {
+        match &self.tx {
+            Sender::Bounded { closer, .. } => closer.lock().unwrap().close_channel(),
+            Sender::Unbounded(tx) => tx.close_channel(),
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        match &self.tx {
+            Sender::Bounded { closer, .. } => closer.lock().unwrap().is_closed(),
+            Sender::Unbounded(tx) => tx.is_closed(),
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use base::{Executor, LocalBoxFuture};
+use futures::executor::{LocalPool, LocalSpawner};
+use futures::task::LocalSpawnExt;
+use std::cell::RefCell;
+use std::future::Future;
+
+thread_local! {
+    /// The spawner of the [FuturesExecutor::block_on] that is running on
+    /// this thread
+    static CURRENT: RefCell<Option<LocalSpawner>> = const { RefCell::new(None) };
+}
+
+/// Makes a pool's spawner current for as long as it exists. When it is
+/// dropped, tasks that haven't finished are dropped too.
+struct Enter(Option<LocalPool>);
+
+impl Enter {
+    fn new(pool: LocalPool) -> Self {
+        CURRENT.with(|current| {
+            let mut current = current.borrow_mut();
+            assert!(
+                current.is_none(),
+                "FuturesExecutor::block_on can't be called from a task"
+            );
+            *current = Some(pool.spawner());
+        });
+        Enter(Some(pool))
+    }
+
+    fn pool(&mut self) -> &mut LocalPool {
+        self.0
+            .as_mut()
+            .expect("the pool exists until Enter is dropped")
+    }
+}
+
+impl Drop for Enter {
+    fn drop(&mut self) {
+        // A task's destructor may spawn, so the spawner stays current
+        // until the tasks are gone.
+        drop(self.0.take());
+        CURRENT.with(|current| current.borrow_mut().take());
+    }
+}
+
+/// Start running `task` in the [FuturesExecutor::block_on] on this
+/// thread.
+pub(crate) fn spawn(task: LocalBoxFuture<'static, ()>) {
+    CURRENT.with(|current| {
+        let current = current.borrow();
+        let spawner = current
+            .as_ref()
+            .expect("FuturesRuntime tasks can only be spawned inside FuturesExecutor::block_on");
+        // This only fails while the pool is being dropped, and then the
+        // task would be dropped without running anyway.
+        let _ = spawner.spawn_local(task);
+    })
+}
+
+/// Runs futures on the current thread with a [LocalPool] that is
+/// created for each call to `block_on`. Spawned tasks run on the same
+/// thread, so they don't have to be `Send`, and only while `block_on` is
+/// running. Those that haven't finished when it returns are dropped.
+/// When nothing is ready, the thread parks until a task is woken.
+#[derive(Default)]
+pub struct FuturesExecutor;
+
+impl Executor for FuturesExecutor {
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        let mut enter = Enter::new(LocalPool::new());
+        enter.pool().run_until(future)
+    }
+}
@@ -0,0 +1,188 @@
+//! An implementation of the [base] runtime traits for projects that use
+//! only the `futures` crates, with no tokio or async-std. Everything runs
+//! on one thread under [FuturesExecutor], which runs a
+//! [futures::executor::LocalPool] for each call to `block_on`, so
+//! [Runtime::spawn_local] can run futures that aren't `Send`, and
+//! [Runtime::spawn] runs its tasks on the same thread. Tasks run only
+//! while `block_on` is running, and spawning from anywhere else panics.
+//!
+//! Mutexes and channels are futures' own. futures has no reader-writer
+//! lock, semaphore, or broadcast channel, so those come from async-lock
+//! and async-broadcast, which need no executor. Notify, watch, barrier,
+//! and cancel token are the executor-independent ones from [base], and
+//! the clock and timeouts are [base]'s defaults, which use a thread to
+//! wait. Sockets are runtime-std's, which block the thread, and files
+//! are [base::StdFile].
+use crate::broadcast::{FuturesBroadcast, FuturesBroadcastReceiver};
+use crate::channel::FuturesChannel;
+use crate::mutex::FuturesMutexWrapper;
+use crate::rwlock::FuturesLockWrapper;
+use crate::semaphore::FuturesSemaphoreWrapper;
+use crate::task::FuturesJoinHandle;
+use base::{
+    AsyncBarrier, AsyncChannel, AsyncFile, AsyncMutex, AsyncNotify, AsyncRwLock, AsyncSemaphore,
+    AsyncStream, AsyncTcpListener, AsyncTcpStream, AsyncUdpSocket, BarrierBox, BoxFuture,
+    Broadcast, BroadcastBox, BroadcastReceiver, BroadcastReceiverBox, CancelToken, CancelTokenBox,
+    ChannelBox, ChannelStream, FileBox, JoinHandle, JoinHandleBox, LocalBoxFuture, LockBox,
+    LockPolicy, Locker, MutexBox, NotifyBox, PolicyLockBox, Runtime, SemaphoreBox, StdBarrier,
+    StdCancelToken, StdClock, StdFile, StdNotify, StdWatch, StdWatchReceiver, StreamBox,
+    TcpListenerBox, TcpStreamBox, UdpSocketBox, Watch, WatchBox, WatchReceiver, WatchReceiverBox,
+};
+use implbox::{ImplBox, ImplBoxShared};
+use implbox_macros::implbox_impls;
+use runtime_std::net::{StdTcpListener, StdTcpStream, StdUdpSocket};
+use std::fs::OpenOptions;
+use std::io;
+use std::net::SocketAddr;
+use std::path::Path;
+
+pub mod broadcast;
+pub mod channel;
+pub mod executor;
+pub mod mutex;
+pub mod rwlock;
+pub mod semaphore;
+pub mod task;
+
+pub use executor::FuturesExecutor;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Default, Clone)]
+pub struct FuturesRuntime;
+
+impl Locker for FuturesRuntime {
+    #[implbox_impls(LockBox<T>, FuturesLockWrapper<T>, downcast)]
+    fn new_lock<T: Sync + Send + 'static>(item: T) -> impl AsyncRwLock<T> {
+        FuturesLockWrapper::<T>::new(item)
+    }
+
+    /// async-lock's lock always prefers writers and has no reader
+    /// limit, so the policy is ignored.
+    #[implbox_impls(PolicyLockBox<T>, FuturesLockWrapper<T>, downcast)]
+    fn new_lock_with<T: Sync + Send + 'static>(
+        _policy: LockPolicy,
+        item: T,
+    ) -> impl AsyncRwLock<T> {
+        FuturesLockWrapper::<T>::new(item)
+    }
+
+    #[implbox_impls(MutexBox<T>, FuturesMutexWrapper<T>, downcast)]
+    fn new_mutex<T: Sync + Send + 'static>(item: T) -> impl AsyncMutex<T> {
+        FuturesMutexWrapper::<T>::new(item)
+    }
+
+    #[implbox_impls(SemaphoreBox, FuturesSemaphoreWrapper, downcast)]
+    fn new_semaphore(permits: usize) -> impl AsyncSemaphore {
+        FuturesSemaphoreWrapper::new(permits)
+    }
+
+    #[implbox_impls(NotifyBox, StdNotify, downcast)]
+    fn new_notify() -> impl AsyncNotify {
+        StdNotify::new()
+    }
+
+    #[implbox_impls(BarrierBox, StdBarrier, downcast)]
+    fn new_barrier(n: usize) -> impl AsyncBarrier {
+        StdBarrier::new(n)
+    }
+
+    #[implbox_impls(ChannelBox<T>, FuturesChannel<T>, downcast)]
+    fn new_channel<T: Send + 'static>(capacity: Option<usize>) -> impl AsyncChannel<T> {
+        FuturesChannel::<T>::new(capacity)
+    }
+
+    #[implbox_impls(BroadcastBox<T>, FuturesBroadcast<T>, downcast)]
+    fn new_broadcast<T: Clone + Sync + Send + 'static>(capacity: usize) -> impl Broadcast<T> {
+        FuturesBroadcast::<T>::new(capacity)
+    }
+
+    #[implbox_impls(BroadcastReceiverBox<T>, FuturesBroadcastReceiver<T>, downcast)]
+    fn new_broadcast_receiver<T: Clone + Sync + Send + 'static>(
+        broadcast: &ImplBox<BroadcastBox<T>>,
+    ) -> impl BroadcastReceiver<T> {
+        broadcast
+            .downcast_ref::<FuturesBroadcast<T>>()
+            .expect("broadcast was not created by FuturesRuntime")
+            .subscribe()
+    }
+
+    #[implbox_impls(WatchBox<T>, StdWatch<T>, downcast)]
+    fn new_watch<T: Clone + Sync + Send + 'static>(initial: T) -> impl Watch<T> {
+        StdWatch::<T>::new(initial)
+    }
+
+    #[implbox_impls(WatchReceiverBox<T>, StdWatchReceiver<T>, downcast)]
+    fn new_watch_receiver<T: Clone + Sync + Send + 'static>(
+        watch: &ImplBox<WatchBox<T>>,
+    ) -> impl WatchReceiver<T> {
+        watch
+            .downcast_ref::<StdWatch<T>>()
+            .expect("watch was not created by FuturesRuntime")
+            .subscribe()
+    }
+
+    #[implbox_impls(StreamBox<T>, ChannelStream<T, FuturesRuntime>, downcast)]
+    fn new_stream<T: Send + 'static>(channel: ImplBoxShared<ChannelBox<T>>) -> impl AsyncStream<T> {
+        ChannelStream::<T, Self>::new(channel)
+    }
+}
+
+impl Runtime for FuturesRuntime {
+    type Executor = FuturesExecutor;
+    type Clock = StdClock;
+
+    fn new_executor() -> io::Result<FuturesExecutor> {
+        Ok(FuturesExecutor)
+    }
+
+    fn clock() -> StdClock {
+        StdClock
+    }
+
+    /// The task runs on this thread while [FuturesExecutor]'s `block_on`
+    /// is running, and this panics if it isn't.
+    #[implbox_impls(JoinHandleBox<T>, FuturesJoinHandle<T>, downcast, name = "task")]
+    fn spawn<T: Send + 'static>(future: BoxFuture<'static, T>) -> impl JoinHandle<T> {
+        FuturesJoinHandle::spawn(future)
+    }
+
+    /// Every task runs on the same thread, so this is the same as
+    /// [FuturesRuntime::spawn], but the future doesn't have to be `Send`.
+    #[implbox_impls(JoinHandleBox<T>, FuturesJoinHandle<T>, downcast, name = "local_task")]
+    fn spawn_local<T: Send + 'static>(future: LocalBoxFuture<'static, T>) -> impl JoinHandle<T> {
+        FuturesJoinHandle::spawn(future)
+    }
+
+    #[implbox_impls(CancelTokenBox, StdCancelToken, downcast)]
+    fn new_cancel_token(parent: Option<&ImplBox<CancelTokenBox>>) -> impl CancelToken + Clone {
+        match parent {
+            Some(parent) => parent
+                .downcast_ref::<StdCancelToken>()
+                .expect("cancel token was not created by FuturesRuntime")
+                .child(),
+            None => StdCancelToken::default(),
+        }
+    }
+
+    #[implbox_impls(TcpStreamBox, StdTcpStream, downcast, name = "tcp_stream")]
+    async fn connect_tcp(addr: SocketAddr) -> Result<impl AsyncTcpStream, io::Error> {
+        StdTcpStream::connect(addr)
+    }
+
+    #[implbox_impls(TcpListenerBox, StdTcpListener, downcast, name = "tcp_listener")]
+    async fn bind_tcp(addr: SocketAddr) -> Result<impl AsyncTcpListener, io::Error> {
+        StdTcpListener::bind(addr)
+    }
+
+    #[implbox_impls(UdpSocketBox, StdUdpSocket, downcast, name = "udp_socket")]
+    async fn bind_udp(addr: SocketAddr) -> Result<impl AsyncUdpSocket, io::Error> {
+        StdUdpSocket::bind(addr)
+    }
+
+    #[implbox_impls(FileBox, StdFile, downcast, name = "file")]
+    async fn open_file(path: &Path, options: &OpenOptions) -> Result<impl AsyncFile, io::Error> {
+        StdFile::open(path, options)
+    }
+}
@@ -0,0 +1,42 @@
+use base::AsyncMutex;
+use futures::lock::{Mutex, MutexGuard};
+
+/// futures' own mutex. It has no way to lock from synchronous code
+/// except [futures::executor::block_on], which
+/// [FuturesMutexWrapper::blocking_lock] uses.
+#[derive(Default)]
+pub struct FuturesMutexWrapper<T> {
+    mutex: Mutex<T>,
+}
+
+impl<T> FuturesMutexWrapper<T> {
+    /// Lock from synchronous code by blocking the thread. This must not
+    /// be called from a task, since the task holding the mutex can't run
+    /// while the thread is blocked. To get the wrapper from a boxed
+    /// mutex, use [implbox::ImplBox::downcast_ref].
+    pub fn blocking_lock(&self) -> MutexGuard<'_, T> {
+        futures::executor::block_on(self.mutex.lock())
+    }
+}
+
+impl<T: Sync + Send> AsyncMutex<T> for FuturesMutexWrapper<T> {
+    type Guard<'a>
+        = MutexGuard<'a, T>
+    where
+        Self: 'a;
+
+    fn new(item: T) -> Self {
+        FuturesMutexWrapper {
+            mutex: Mutex::new(item),
+        }
+    }
+
+    async fn lock(&self) -> MutexGuard<'_, T> {
+        base::trace_future!(
+            self.mutex.lock(),
+            "mutex.lock",
+            item = std::any::type_name::<T>()
+        )
+        .await
+    }
+}
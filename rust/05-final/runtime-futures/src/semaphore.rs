@@ -0,0 +1,58 @@
+use async_lock::{Semaphore, SemaphoreGuard};
+use base::AsyncSemaphore;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// futures has no semaphore, so this is async-lock's. It doesn't report
+/// how many permits are left, so the wrapper counts the permits it has
+/// given out.
+pub struct FuturesSemaphoreWrapper {
+    semaphore: Semaphore,
+    permits: usize,
+    acquired: AtomicUsize,
+}
+
+pub struct FuturesPermit<'a> {
+    _guard: SemaphoreGuard<'a>,
+    acquired: &'a AtomicUsize,
+}
+
+impl Drop for FuturesPermit<'_> {
+    fn drop(&mut self) {
+        self.acquired.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl FuturesSemaphoreWrapper {
+    fn permit<'a>(&'a self, guard: SemaphoreGuard<'a>) -> FuturesPermit<'a> {
+        self.acquired.fetch_add(1, Ordering::AcqRel);
+        FuturesPermit {
+            _guard: guard,
+            acquired: &self.acquired,
+        }
+    }
+}
+
+impl AsyncSemaphore for FuturesSemaphoreWrapper {
+    type Permit<'a> = FuturesPermit<'a>;
+
+    fn new(permits: usize) -> Self {
+        FuturesSemaphoreWrapper {
+            semaphore: Semaphore::new(permits),
+            permits,
+            acquired: AtomicUsize::new(0),
+        }
+    }
+
+    async fn acquire(&self) -> FuturesPermit<'_> {
+        let guard = base::trace_future!(self.semaphore.acquire(), "semaphore.acquire").await;
+        self.permit(guard)
+    }
+
+    fn try_acquire(&self) -> Option<FuturesPermit<'_>> {
+        self.semaphore.try_acquire().map(|guard| self.permit(guard))
+    }
+
+    fn permits(&self) -> usize {
+        self.permits - self.acquired.load(Ordering::Acquire)
+    }
+}
@@ -0,0 +1,84 @@
+use base::{AbortHandle, JoinError, JoinHandle};
+use std::future::{poll_fn, Future};
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, Waker};
+
+struct JoinState<T> {
+    output: Option<Result<T, JoinError>>,
+    finished: bool,
+    waker: Option<Waker>,
+}
+
+/// Owned by the task. It stores the task's output, or
+/// [JoinError::Cancelled] if the task is dropped before it finishes
+/// because the executor returned.
+struct Finish<T>(Arc<Mutex<JoinState<T>>>);
+
+impl<T> Finish<T> {
+    fn set(&self, output: Result<T, JoinError>) {
+        let mut state = self.0.lock().unwrap();
+        if state.finished {
+            return;
+        }
+        state.finished = true;
+        state.output = Some(output);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Drop for Finish<T> {
+    fn drop(&mut self) {
+        self.set(Err(JoinError::Cancelled));
+    }
+}
+
+/// A task run by [crate::executor::FuturesExecutor]. The task is wrapped
+/// with [base::abortable] and stores its output here when it finishes.
+/// Dropping the handle detaches the task. A task that is dropped because
+/// `block_on` returned is joined with [JoinError::Cancelled].
+pub struct FuturesJoinHandle<T> {
+    state: Arc<Mutex<JoinState<T>>>,
+    abort: AbortHandle,
+}
+
+impl<T: 'static> FuturesJoinHandle<T> {
+    pub(crate) fn spawn(future: impl Future<Output = T> + Unpin + 'static) -> Self {
+        let (future, abort) = base::abortable(future);
+        let state = Arc::new(Mutex::new(JoinState {
+            output: None,
+            finished: false,
+            waker: None,
+        }));
+        let finish = Finish(state.clone());
+        crate::executor::spawn(Box::pin(async move {
+            finish.set(future.await);
+        }));
+        Self { state, abort }
+    }
+}
+
+impl<T: Send + 'static> JoinHandle<T> for FuturesJoinHandle<T> {
+    async fn join(&mut self) -> Result<T, JoinError> {
+        let join = poll_fn(|cx| {
+            let mut state = self.state.lock().unwrap();
+            match state.output.take() {
+                Some(output) => Poll::Ready(output),
+                None => {
+                    state.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        });
+        base::trace_future!(join, "task.join").await
+    }
+
+    fn abort(&self) {
+        self.abort.abort()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.abort.is_finished()
+    }
+}
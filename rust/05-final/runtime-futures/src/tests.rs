@@ -0,0 +1,130 @@
+use super::*;
+use base::{Clock, Executor, JoinError, SendError, TryRecvError, Upgradable};
+use std::future::{pending, Future};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+type R = FuturesRuntime;
+
+fn run<F: Future>(future: F) -> F::Output {
+    R::new_executor().unwrap().block_on(future)
+}
+
+#[test]
+fn test_lock() {
+    run(async {
+        let l = R::box_lock(3);
+        let lock = R::unbox_lock(&l);
+        {
+            let r1 = lock.read().await;
+            let r2 = lock.try_read().unwrap();
+            assert_eq!(*r1 + *r2, 6);
+            assert!(lock.try_write().is_none());
+            assert_eq!(
+                lock.write_timeout(Duration::from_millis(10)).await.err(),
+                Some(base::Elapsed)
+            );
+        }
+        *lock.write().await += 1;
+        let upgradable = lock.upgradable_read().await;
+        assert_eq!(*lock.read().await, 4);
+        let mut w = upgradable.upgrade().await;
+        *w = 5;
+        drop(w);
+        let owned = lock.read_owned().await;
+        drop(l);
+        // The owned guard keeps the item alive.
+        assert_eq!(*owned, 5);
+    })
+}
+
+#[test]
+fn test_mutex_and_semaphore() {
+    let m = R::box_mutex(String::new());
+    run(async { R::unbox_mutex(&m).lock().await.push_str("potato") });
+    // Blocking is only allowed outside of the executor.
+    let wrapper = m.downcast_ref::<FuturesMutexWrapper<String>>().unwrap();
+    assert_eq!(*wrapper.blocking_lock(), "potato");
+    run(async {
+        let s = R::new_semaphore(2);
+        let p1 = s.acquire().await;
+        let p2 = s.try_acquire().unwrap();
+        assert!(s.try_acquire().is_none());
+        drop(p1);
+        drop(p2);
+        assert_eq!(s.permits(), 2);
+    })
+}
+
+#[test]
+fn test_channel() {
+    run(async {
+        let c = Arc::new(R::new_channel(Some(1)));
+        c.send(1).await.unwrap();
+        assert_eq!(c.try_send(2), Err(SendError::Full(2)));
+        // A waiting sender gets in once there is room.
+        let mut waiting = {
+            let c = c.clone();
+            R::spawn(Box::pin(async move { c.send(2).await }))
+        };
+        assert_eq!(c.recv().await, Some(1));
+        waiting.join().await.unwrap().unwrap();
+        c.close();
+        assert!(c.is_closed());
+        assert_eq!(c.send(3).await, Err(SendError::Closed(3)));
+        assert_eq!(c.recv().await, Some(2));
+        assert_eq!(c.recv().await, None);
+        assert_eq!(c.try_recv(), Err(TryRecvError::Closed));
+
+        let c = R::new_channel(None);
+        c.try_send("potato").unwrap();
+        assert_eq!(c.try_recv(), Ok("potato"));
+        assert_eq!(c.try_recv(), Err(TryRecvError::Empty));
+    })
+}
+
+#[test]
+fn test_spawn() {
+    run(async {
+        let mut h = R::box_task(Box::pin(async { 5 }));
+        assert_eq!(R::unbox_mut_task(&mut h).join().await, Ok(5));
+        assert!(R::unbox_task(&h).is_finished());
+
+        let mut h = R::spawn(Box::pin(pending::<()>()));
+        h.abort();
+        assert_eq!(h.join().await, Err(JoinError::Cancelled));
+
+        let mut h = R::spawn(Box::pin(async { panic!("potato") }));
+        assert_eq!(h.join().await, Err::<(), _>(JoinError::Panicked));
+
+        // Local tasks may hold values that aren't Send across awaits.
+        let value = Rc::new(6);
+        let mut h = R::spawn_local(Box::pin(async move {
+            R::clock().sleep(Duration::from_millis(1)).await;
+            *value
+        }));
+        assert_eq!(h.join().await, Ok(6));
+    })
+}
+
+#[test]
+fn test_timeout() {
+    run(async {
+        let d = Duration::from_millis(20);
+        assert_eq!(R::timeout(d, async { 5 }).await, Ok(5));
+        assert_eq!(R::timeout(d, pending::<()>()).await, Err(base::Elapsed));
+    })
+}
+
+#[test]
+fn test_executor() {
+    let e = R::new_executor().unwrap();
+    // Tasks only run inside block_on, and are dropped when it returns.
+    let mut h = e.block_on(async { R::spawn(Box::pin(pending::<()>())) });
+    assert!(!h.is_finished());
+    assert_eq!(e.block_on(h.join()), Err(JoinError::Cancelled));
+    assert!(std::panic::catch_unwind(|| R::spawn(Box::pin(async {}))).is_err());
+    // block_on can't be nested.
+    assert!(std::panic::catch_unwind(|| e.block_on(async { e.block_on(async {}) })).is_err());
+}
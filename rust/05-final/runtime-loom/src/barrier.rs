@@ -0,0 +1,34 @@
+use base::AsyncBarrier;
+use loom::sync::{Condvar, Mutex};
+
+/// The number of tasks waiting and the number of groups released
+pub struct LoomBarrier {
+    n: usize,
+    state: Mutex<(usize, u64)>,
+    changed: Condvar,
+}
+
+impl AsyncBarrier for LoomBarrier {
+    fn new(n: usize) -> Self {
+        LoomBarrier {
+            n: n.max(1),
+            state: Mutex::new((0, 0)),
+            changed: Condvar::new(),
+        }
+    }
+
+    async fn wait(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.0 += 1;
+        if state.0 == self.n {
+            *state = (0, state.1 + 1);
+            self.changed.notify_all();
+            return true;
+        }
+        let generation = state.1;
+        while state.1 == generation {
+            state = self.changed.wait(state).unwrap();
+        }
+        false
+    }
+}
@@ -0,0 +1,123 @@
+use base::{Broadcast, BroadcastReceiver, RecvError};
+use loom::sync::{Arc, Condvar, Mutex};
+use std::collections::VecDeque;
+
+struct BroadcastState<T> {
+    items: VecDeque<T>,
+    /// The sequence number of the next item to be sent
+    next: u64,
+    receivers: usize,
+    closed: bool,
+}
+
+struct BroadcastShared<T> {
+    state: Mutex<BroadcastState<T>>,
+    changed: Condvar,
+    capacity: usize,
+}
+
+/// A broadcast channel that keeps the last `capacity` items. Each
+/// receiver keeps the sequence number of the next item it will receive.
+pub struct LoomBroadcast<T> {
+    shared: Arc<BroadcastShared<T>>,
+}
+
+impl<T> LoomBroadcast<T> {
+    pub fn subscribe(&self) -> LoomBroadcastReceiver<T> {
+        let mut state = self.shared.state.lock().unwrap();
+        state.receivers += 1;
+        LoomBroadcastReceiver {
+            shared: self.shared.clone(),
+            next: state.next,
+        }
+    }
+}
+
+impl<T> Drop for LoomBroadcast<T> {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().closed = true;
+        self.shared.changed.notify_all();
+    }
+}
+
+impl<T: Clone> Broadcast<T> for LoomBroadcast<T> {
+    fn new(capacity: usize) -> Self {
+        LoomBroadcast {
+            shared: Arc::new(BroadcastShared {
+                state: Mutex::new(BroadcastState {
+                    items: VecDeque::new(),
+                    next: 0,
+                    receivers: 0,
+                    closed: false,
+                }),
+                changed: Condvar::new(),
+                capacity,
+            }),
+        }
+    }
+
+    fn send(&self, item: T) -> usize {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.receivers == 0 {
+            return 0;
+        }
+        if state.items.len() == self.shared.capacity {
+            state.items.pop_front();
+        }
+        state.items.push_back(item);
+        state.next += 1;
+        self.shared.changed.notify_all();
+        state.receivers
+    }
+
+    fn receiver_count(&self) -> usize {
+        self.shared.state.lock().unwrap().receivers
+    }
+}
+
+pub struct LoomBroadcastReceiver<T> {
+    shared: Arc<BroadcastShared<T>>,
+    next: u64,
+}
+
+impl<T: Clone> LoomBroadcastReceiver<T> {
+    fn take(&mut self, state: &BroadcastState<T>) -> Option<Result<T, RecvError>> {
+        let oldest = state.next - state.items.len() as u64;
+        if self.next < oldest {
+            let lagged = oldest - self.next;
+            self.next = oldest;
+            return Some(Err(RecvError::Lagged(lagged)));
+        }
+        if self.next < state.next {
+            let item = state.items[(self.next - oldest) as usize].clone();
+            self.next += 1;
+            return Some(Ok(item));
+        }
+        state.closed.then_some(Err(RecvError::Closed))
+    }
+}
+
+impl<T> Drop for LoomBroadcastReceiver<T> {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().receivers -= 1;
+    }
+}
+
+impl<T: Clone + Sync + Send> BroadcastReceiver<T> for LoomBroadcastReceiver<T> {
+    async fn recv(&mut self) -> Result<T, RecvError> {
+        let shared = self.shared.clone();
+        let mut state = shared.state.lock().unwrap();
+        loop {
+            if let Some(result) = self.take(&state) {
+                return result;
+            }
+            state = shared.changed.wait(state).unwrap();
+        }
+    }
+
+    fn try_recv(&mut self) -> Result<Option<T>, RecvError> {
+        let shared = self.shared.clone();
+        let state = shared.state.lock().unwrap();
+        self.take(&state).transpose()
+    }
+}
@@ -0,0 +1,64 @@
+use base::CancelToken;
+use loom::sync::{Arc, Condvar, Mutex};
+
+#[derive(Default)]
+struct CancelState {
+    cancelled: bool,
+    children: Vec<Arc<CancelNode>>,
+}
+
+#[derive(Default)]
+struct CancelNode {
+    state: Mutex<CancelState>,
+    changed: Condvar,
+}
+
+impl CancelNode {
+    fn cancel(&self) {
+        let children = {
+            let mut state = self.state.lock().unwrap();
+            if state.cancelled {
+                return;
+            }
+            state.cancelled = true;
+            std::mem::take(&mut state.children)
+        };
+        self.changed.notify_all();
+        for child in children {
+            child.cancel();
+        }
+    }
+}
+
+/// A tree of cancel tokens. Unlike the tokio version, parents keep
+/// their children alive, which doesn't matter for short tests.
+#[derive(Clone, Default)]
+pub struct LoomCancelToken(Arc<CancelNode>);
+
+impl CancelToken for LoomCancelToken {
+    fn cancel(&self) {
+        self.0.cancel()
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.0.state.lock().unwrap().cancelled
+    }
+
+    async fn cancelled(&self) {
+        let mut state = self.0.state.lock().unwrap();
+        while !state.cancelled {
+            state = self.0.changed.wait(state).unwrap();
+        }
+    }
+
+    fn child(&self) -> Self {
+        let child = Arc::new(CancelNode::default());
+        let mut state = self.0.state.lock().unwrap();
+        if state.cancelled {
+            child.state.lock().unwrap().cancelled = true;
+        } else {
+            state.children.push(child.clone());
+        }
+        LoomCancelToken(child)
+    }
+}
@@ -0,0 +1,83 @@
+use base::{AsyncChannel, SendError, TryRecvError};
+use loom::sync::{Condvar, Mutex};
+use std::collections::VecDeque;
+
+/// A queue that blocks on a condition variable, like [LoomSemaphore]
+pub struct LoomChannel<T> {
+    queue: Mutex<(VecDeque<T>, bool)>,
+    changed: Condvar,
+    capacity: Option<usize>,
+}
+
+impl<T> LoomChannel<T> {
+    fn is_full(&self, queue: &VecDeque<T>) -> bool {
+        self.capacity.is_some_and(|c| queue.len() >= c)
+    }
+}
+
+impl<T: Send> AsyncChannel<T> for LoomChannel<T> {
+    fn new(capacity: Option<usize>) -> Self {
+        LoomChannel {
+            queue: Mutex::new((VecDeque::new(), false)),
+            changed: Condvar::new(),
+            capacity,
+        }
+    }
+
+    async fn send(&self, item: T) -> Result<(), SendError<T>> {
+        let mut queue = self.queue.lock().unwrap();
+        while !queue.1 && self.is_full(&queue.0) {
+            queue = self.changed.wait(queue).unwrap();
+        }
+        if queue.1 {
+            return Err(SendError::Closed(item));
+        }
+        queue.0.push_back(item);
+        self.changed.notify_all();
+        Ok(())
+    }
+
+    fn try_send(&self, item: T) -> Result<(), SendError<T>> {
+        let mut queue = self.queue.lock().unwrap();
+        if queue.1 {
+            return Err(SendError::Closed(item));
+        }
+        if self.is_full(&queue.0) {
+            return Err(SendError::Full(item));
+        }
+        queue.0.push_back(item);
+        self.changed.notify_all();
+        Ok(())
+    }
+
+    async fn recv(&self) -> Option<T> {
+        let mut queue = self.queue.lock().unwrap();
+        while !queue.1 && queue.0.is_empty() {
+            queue = self.changed.wait(queue).unwrap();
+        }
+        let item = queue.0.pop_front();
+        self.changed.notify_all();
+        item
+    }
+
+    fn try_recv(&self) -> Result<T, TryRecvError> {
+        let mut queue = self.queue.lock().unwrap();
+        match queue.0.pop_front() {
+            Some(item) => {
+                self.changed.notify_all();
+                Ok(item)
+            }
+            None if queue.1 => Err(TryRecvError::Closed),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    fn close(&self) {
+        self.queue.lock().unwrap().1 = true;
+        self.changed.notify_all();
+    }
+
+    fn is_closed(&self) -> bool {
+        self.queue.lock().unwrap().1
+    }
+}
@@ -0,0 +1,212 @@
+//! An implementation of the [base] runtime traits on [loom]'s
+//! primitives, so that concurrent use of boxed items and of code built on
+//! the runtime, such as a controller shared between threads, can be
+//! model-checked. Inside `loom::model`, loom runs the closure once for
+//! every interleaving of the operations on its primitives, so a test
+//! fails if any interleaving breaks it.
+//!
+//! Every future is driven with [loom::future::block_on], and waiting
+//! blocks the loom thread on a condition variable, since loom doesn't
+//! model wakers that are called from other threads. Tasks from
+//! [Runtime::spawn] each get their own loom thread. Nothing times out,
+//! sockets are unsupported, and files are [base::StdFile].
+//!
+//! When the workspace is built with `RUSTFLAGS="--cfg loom"`, implbox
+//! uses loom's atomics and [loom::sync::Arc] too, so the tests also
+//! check how [ImplBox] and [ImplBoxShared] are shared between threads.
+//! Only crates that don't pull in tokio can be built that way, so run it
+//! as `RUSTFLAGS="--cfg loom" cargo test -p runtime-loom`.
+use crate::barrier::LoomBarrier;
+use crate::broadcast::{LoomBroadcast, LoomBroadcastReceiver};
+use crate::cancel::LoomCancelToken;
+use crate::channel::LoomChannel;
+use crate::net::{no_net, NoNet};
+use crate::notify::LoomNotify;
+use crate::rwlock::{LoomLockWrapper, LoomMutexWrapper};
+use crate::semaphore::LoomSemaphore;
+use crate::task::LoomJoinHandle;
+use crate::watch::{LoomWatch, LoomWatchReceiver};
+use base::{
+    AsyncBarrier, AsyncChannel, AsyncFile, AsyncMutex, AsyncNotify, AsyncRwLock, AsyncSemaphore,
+    AsyncStream, AsyncTcpListener, AsyncTcpStream, AsyncUdpSocket, BarrierBox, BoxFuture,
+    Broadcast, BroadcastBox, BroadcastReceiver, BroadcastReceiverBox, CancelToken, CancelTokenBox,
+    ChannelBox, ChannelStream, Elapsed, Executor, FileBox, JoinHandle, JoinHandleBox,
+    LocalBoxFuture, LockBox, LockPolicy, Locker, MutexBox, NotifyBox, PolicyLockBox, Runtime,
+    SemaphoreBox, StdClock, StdFile, StreamBox, TcpListenerBox, TcpStreamBox, UdpSocketBox, Watch,
+    WatchBox, WatchReceiver, WatchReceiverBox,
+};
+use implbox::{ImplBox, ImplBoxShared};
+use implbox_macros::implbox_impls;
+use loom::future::block_on;
+use std::fs::OpenOptions;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::Duration;
+
+pub mod barrier;
+pub mod broadcast;
+pub mod cancel;
+pub mod channel;
+pub mod net;
+pub mod notify;
+pub mod rwlock;
+pub mod semaphore;
+pub mod task;
+pub mod watch;
+
+#[cfg(test)]
+mod tests;
+
+pub struct LoomExecutor;
+
+impl Executor for LoomExecutor {
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        block_on(future)
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct LoomRuntime;
+
+impl Locker for LoomRuntime {
+    #[implbox_impls(LockBox<T>, LoomLockWrapper<T>)]
+    fn new_lock<T: Sync + Send + 'static>(item: T) -> impl AsyncRwLock<T> {
+        LoomLockWrapper::<T>::new(item)
+    }
+
+    /// loom's lock has no scheduling options, and loom explores every
+    /// order anyway, so the policy is ignored.
+    #[implbox_impls(PolicyLockBox<T>, LoomLockWrapper<T>)]
+    fn new_lock_with<T: Sync + Send + 'static>(
+        _policy: LockPolicy,
+        item: T,
+    ) -> impl AsyncRwLock<T> {
+        LoomLockWrapper::<T>::new(item)
+    }
+
+    #[implbox_impls(MutexBox<T>, LoomMutexWrapper<T>)]
+    fn new_mutex<T: Sync + Send + 'static>(item: T) -> impl AsyncMutex<T> {
+        LoomMutexWrapper::<T>::new(item)
+    }
+
+    #[implbox_impls(SemaphoreBox, LoomSemaphore)]
+    fn new_semaphore(permits: usize) -> impl AsyncSemaphore {
+        LoomSemaphore::new(permits)
+    }
+
+    #[implbox_impls(NotifyBox, LoomNotify)]
+    fn new_notify() -> impl AsyncNotify {
+        LoomNotify::new()
+    }
+
+    #[implbox_impls(BarrierBox, LoomBarrier)]
+    fn new_barrier(n: usize) -> impl AsyncBarrier {
+        LoomBarrier::new(n)
+    }
+
+    #[implbox_impls(ChannelBox<T>, LoomChannel<T>)]
+    fn new_channel<T: Send + 'static>(capacity: Option<usize>) -> impl AsyncChannel<T> {
+        LoomChannel::<T>::new(capacity)
+    }
+
+    #[implbox_impls(BroadcastBox<T>, LoomBroadcast<T>, downcast)]
+    fn new_broadcast<T: Clone + Sync + Send + 'static>(capacity: usize) -> impl Broadcast<T> {
+        LoomBroadcast::<T>::new(capacity)
+    }
+
+    #[implbox_impls(BroadcastReceiverBox<T>, LoomBroadcastReceiver<T>)]
+    fn new_broadcast_receiver<T: Clone + Sync + Send + 'static>(
+        broadcast: &ImplBox<BroadcastBox<T>>,
+    ) -> impl BroadcastReceiver<T> {
+        broadcast
+            .downcast_ref::<LoomBroadcast<T>>()
+            .expect("broadcast was not created by LoomRuntime")
+            .subscribe()
+    }
+
+    #[implbox_impls(WatchBox<T>, LoomWatch<T>, downcast)]
+    fn new_watch<T: Clone + Sync + Send + 'static>(initial: T) -> impl Watch<T> {
+        LoomWatch::<T>::new(initial)
+    }
+
+    #[implbox_impls(WatchReceiverBox<T>, LoomWatchReceiver<T>)]
+    fn new_watch_receiver<T: Clone + Sync + Send + 'static>(
+        watch: &ImplBox<WatchBox<T>>,
+    ) -> impl WatchReceiver<T> {
+        watch
+            .downcast_ref::<LoomWatch<T>>()
+            .expect("watch was not created by LoomRuntime")
+            .subscribe()
+    }
+
+    #[implbox_impls(StreamBox<T>, ChannelStream<T, LoomRuntime>)]
+    fn new_stream<T: Send + 'static>(channel: ImplBoxShared<ChannelBox<T>>) -> impl AsyncStream<T> {
+        ChannelStream::<T, Self>::new(channel)
+    }
+}
+
+impl Runtime for LoomRuntime {
+    type Executor = LoomExecutor;
+    // Timers aren't modeled, and nothing here sleeps.
+    type Clock = StdClock;
+
+    fn new_executor() -> io::Result<LoomExecutor> {
+        Ok(LoomExecutor)
+    }
+
+    fn clock() -> StdClock {
+        StdClock
+    }
+
+    #[implbox_impls(JoinHandleBox<T>, LoomJoinHandle<T>, name = "task")]
+    fn spawn<T: Send + 'static>(future: BoxFuture<'static, T>) -> impl JoinHandle<T> {
+        LoomJoinHandle::spawn(future)
+    }
+
+    #[implbox_impls(JoinHandleBox<T>, LoomJoinHandle<T>, name = "local_task")]
+    fn spawn_local<T: Send + 'static>(future: LocalBoxFuture<'static, T>) -> impl JoinHandle<T> {
+        LoomJoinHandle::spawn_local(future)
+    }
+
+    #[implbox_impls(CancelTokenBox, LoomCancelToken, downcast)]
+    fn new_cancel_token(parent: Option<&ImplBox<CancelTokenBox>>) -> impl CancelToken + Clone {
+        match parent {
+            Some(parent) => parent
+                .downcast_ref::<LoomCancelToken>()
+                .expect("cancel token was not created by LoomRuntime")
+                .child(),
+            None => LoomCancelToken::default(),
+        }
+    }
+
+    #[implbox_impls(TcpStreamBox, NoNet, name = "tcp_stream")]
+    async fn connect_tcp(_addr: SocketAddr) -> Result<impl AsyncTcpStream, io::Error> {
+        Err::<NoNet, _>(no_net())
+    }
+
+    #[implbox_impls(TcpListenerBox, NoNet, name = "tcp_listener")]
+    async fn bind_tcp(_addr: SocketAddr) -> Result<impl AsyncTcpListener, io::Error> {
+        Err::<NoNet, _>(no_net())
+    }
+
+    #[implbox_impls(UdpSocketBox, NoNet, name = "udp_socket")]
+    async fn bind_udp(_addr: SocketAddr) -> Result<impl AsyncUdpSocket, io::Error> {
+        Err::<NoNet, _>(no_net())
+    }
+
+    /// loom doesn't model files, but tests can still use real ones.
+    #[implbox_impls(FileBox, StdFile, name = "file")]
+    async fn open_file(path: &Path, options: &OpenOptions) -> Result<impl AsyncFile, io::Error> {
+        StdFile::open(path, options)
+    }
+
+    /// loom has no notion of time, so nothing times out.
+    async fn timeout<F: Future + Send>(
+        _duration: Duration,
+        future: F,
+    ) -> Result<F::Output, Elapsed> {
+        Ok(future.await)
+    }
+}
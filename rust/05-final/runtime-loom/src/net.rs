@@ -0,0 +1,59 @@
+use base::{AsyncTcpListener, AsyncTcpStream, AsyncUdpSocket};
+use std::io;
+use std::net::SocketAddr;
+
+/// loom can't model sockets, so connecting and binding fail. This is the
+/// type those calls would return.
+pub struct NoNet;
+
+pub(crate) fn no_net() -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, "loom has no network")
+}
+
+impl AsyncTcpStream for NoNet {
+    async fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Err(no_net())
+    }
+
+    async fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(no_net())
+    }
+
+    async fn shutdown(&mut self) -> io::Result<()> {
+        Err(no_net())
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Err(no_net())
+    }
+
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        Err(no_net())
+    }
+}
+
+impl AsyncUdpSocket for NoNet {
+    async fn send_to(&self, _buf: &[u8], _target: SocketAddr) -> io::Result<usize> {
+        Err(no_net())
+    }
+
+    async fn recv_from(&self, _buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        Err(no_net())
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Err(no_net())
+    }
+}
+
+impl AsyncTcpListener for NoNet {
+    async fn accept(
+        &self,
+    ) -> io::Result<(impl AsyncTcpStream + Send + Sync + 'static, SocketAddr)> {
+        Err::<(NoNet, SocketAddr), _>(no_net())
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Err(no_net())
+    }
+}
@@ -0,0 +1,42 @@
+use base::AsyncNotify;
+use loom::sync::{Condvar, Mutex};
+use std::future::Future;
+
+/// A stored permit and a count of calls to notify_waiters, so that a
+/// waiter can tell whether notify_waiters was called since it started
+pub struct LoomNotify {
+    state: Mutex<(bool, u64)>,
+    changed: Condvar,
+}
+
+impl AsyncNotify for LoomNotify {
+    fn new() -> Self {
+        LoomNotify {
+            state: Mutex::new((false, 0)),
+            changed: Condvar::new(),
+        }
+    }
+
+    fn notify_one(&self) {
+        self.state.lock().unwrap().0 = true;
+        self.changed.notify_all();
+    }
+
+    fn notify_waiters(&self) {
+        self.state.lock().unwrap().1 += 1;
+        self.changed.notify_all();
+    }
+
+    fn notified(&self) -> impl Future<Output = ()> + Send + '_ {
+        let generation = self.state.lock().unwrap().1;
+        async move {
+            let mut state = self.state.lock().unwrap();
+            while !state.0 && state.1 == generation {
+                state = self.changed.wait(state).unwrap();
+            }
+            if state.1 == generation {
+                state.0 = false;
+            }
+        }
+    }
+}
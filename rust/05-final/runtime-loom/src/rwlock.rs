@@ -0,0 +1,199 @@
+use base::{AsyncMutex, AsyncRwLock, Elapsed, Upgradable};
+use loom::sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::ops::{Deref, DerefMut};
+use std::time::Duration;
+
+/// loom's guards wrap std guards, which are not `Send`. Every future is
+/// driven to completion with [loom::future::block_on] on the thread that
+/// created it, so a guard never actually moves to another thread.
+pub struct Guard<G>(G);
+unsafe impl<G> Send for Guard<G> {}
+unsafe impl<G> Sync for Guard<G> {}
+impl<G: Deref> Deref for Guard<G> {
+    type Target = G::Target;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+impl<G: DerefMut> DerefMut for Guard<G> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// loom has no owned guards, so this holds the lock's [Arc] along with a
+/// guard whose borrow of it has been extended. `guard` is declared first
+/// so that it is dropped before the [Arc].
+pub struct OwnedGuard<G, T> {
+    guard: Guard<G>,
+    _lock: Arc<RwLock<T>>,
+}
+
+impl<G, T: 'static> OwnedGuard<G, T> {
+    fn new(lock: &Arc<RwLock<T>>, f: impl FnOnce(&'static RwLock<T>) -> G) -> Self {
+        let lock = lock.clone();
+        // SAFETY: The Arc keeps the lock alive for as long as the guard
+        // exists, and the guard is dropped first.
+        let r: &'static RwLock<T> = unsafe { &*Arc::as_ptr(&lock) };
+        Self {
+            guard: Guard(f(r)),
+            _lock: lock,
+        }
+    }
+}
+
+impl<G: Deref, T> Deref for OwnedGuard<G, T> {
+    type Target = G::Target;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<G: DerefMut, T> DerefMut for OwnedGuard<G, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+/// An upgradable guard emulated the same way as for TokioLockWrapper
+pub struct LoomUpgradableGuard<'a, T> {
+    lock: &'a RwLock<T>,
+    read: Guard<RwLockReadGuard<'a, T>>,
+    upgrade: Guard<MutexGuard<'a, ()>>,
+}
+
+impl<T> Deref for LoomUpgradableGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.read
+    }
+}
+
+impl<'a, T: Sync + Send> Upgradable<'a, T> for LoomUpgradableGuard<'a, T> {
+    type WriteGuard = Guard<RwLockWriteGuard<'a, T>>;
+
+    async fn upgrade(self) -> Guard<RwLockWriteGuard<'a, T>> {
+        let Self {
+            lock,
+            read,
+            upgrade,
+        } = self;
+        drop(read);
+        let write = Guard(lock.write().unwrap());
+        drop(upgrade);
+        write
+    }
+}
+
+pub struct LoomLockWrapper<T> {
+    lock: Arc<RwLock<T>>,
+    upgrade: Mutex<()>,
+}
+
+impl<T: Sync + Send + 'static> AsyncRwLock<T> for LoomLockWrapper<T> {
+    type ReadGuard<'a>
+        = Guard<RwLockReadGuard<'a, T>>
+    where
+        Self: 'a;
+    type WriteGuard<'a>
+        = Guard<RwLockWriteGuard<'a, T>>
+    where
+        Self: 'a;
+    type UpgradableReadGuard<'a>
+        = LoomUpgradableGuard<'a, T>
+    where
+        Self: 'a;
+    type OwnedReadGuard = OwnedGuard<RwLockReadGuard<'static, T>, T>;
+    type OwnedWriteGuard = OwnedGuard<RwLockWriteGuard<'static, T>, T>;
+
+    fn new(item: T) -> Self {
+        LoomLockWrapper {
+            lock: Arc::new(RwLock::new(item)),
+            upgrade: Mutex::new(()),
+        }
+    }
+
+    async fn read(&self) -> Guard<RwLockReadGuard<'_, T>> {
+        Guard(self.lock.read().unwrap())
+    }
+
+    async fn write(&self) -> Guard<RwLockWriteGuard<'_, T>> {
+        let _upgrade = self.upgrade.lock().unwrap();
+        Guard(self.lock.write().unwrap())
+    }
+
+    async fn upgradable_read(&self) -> LoomUpgradableGuard<'_, T> {
+        let upgrade = Guard(self.upgrade.lock().unwrap());
+        LoomUpgradableGuard {
+            lock: &self.lock,
+            read: Guard(self.lock.read().unwrap()),
+            upgrade,
+        }
+    }
+
+    async fn read_owned(&self) -> Self::OwnedReadGuard {
+        OwnedGuard::new(&self.lock, |lock| lock.read().unwrap())
+    }
+
+    async fn write_owned(&self) -> Self::OwnedWriteGuard {
+        let _upgrade = self.upgrade.lock().unwrap();
+        OwnedGuard::new(&self.lock, |lock| lock.write().unwrap())
+    }
+
+    fn blocking_read(&self) -> Guard<RwLockReadGuard<'_, T>> {
+        Guard(self.lock.read().unwrap())
+    }
+
+    fn blocking_write(&self) -> Guard<RwLockWriteGuard<'_, T>> {
+        let _upgrade = self.upgrade.lock().unwrap();
+        Guard(self.lock.write().unwrap())
+    }
+
+    fn try_read(&self) -> Option<Guard<RwLockReadGuard<'_, T>>> {
+        self.lock.try_read().ok().map(Guard)
+    }
+
+    fn try_write(&self) -> Option<Guard<RwLockWriteGuard<'_, T>>> {
+        let _upgrade = self.upgrade.try_lock().ok()?;
+        self.lock.try_write().ok().map(Guard)
+    }
+
+    // As with LoomRuntime::timeout, nothing times out.
+    async fn read_timeout(
+        &self,
+        _duration: Duration,
+    ) -> Result<Guard<RwLockReadGuard<'_, T>>, Elapsed> {
+        Ok(self.read().await)
+    }
+
+    async fn write_timeout(
+        &self,
+        _duration: Duration,
+    ) -> Result<Guard<RwLockWriteGuard<'_, T>>, Elapsed> {
+        Ok(self.write().await)
+    }
+}
+
+pub struct LoomMutexWrapper<T> {
+    mutex: Mutex<T>,
+}
+
+impl<T: Sync + Send> AsyncMutex<T> for LoomMutexWrapper<T> {
+    type Guard<'a>
+        = Guard<MutexGuard<'a, T>>
+    where
+        Self: 'a;
+
+    fn new(item: T) -> Self {
+        LoomMutexWrapper {
+            mutex: Mutex::new(item),
+        }
+    }
+
+    async fn lock(&self) -> Guard<MutexGuard<'_, T>> {
+        Guard(self.mutex.lock().unwrap())
+    }
+}
@@ -0,0 +1,51 @@
+use base::AsyncSemaphore;
+use loom::sync::{Condvar, Mutex};
+
+/// loom has no semaphore, so this blocks on a condition variable, which
+/// is fine since every future is driven with [loom::future::block_on].
+pub struct LoomSemaphore {
+    permits: Mutex<usize>,
+    available: Condvar,
+}
+
+pub struct LoomPermit<'a>(&'a LoomSemaphore);
+
+impl Drop for LoomPermit<'_> {
+    fn drop(&mut self) {
+        *self.0.permits.lock().unwrap() += 1;
+        self.0.available.notify_one();
+    }
+}
+
+impl AsyncSemaphore for LoomSemaphore {
+    type Permit<'a> = LoomPermit<'a>;
+
+    fn new(permits: usize) -> Self {
+        LoomSemaphore {
+            permits: Mutex::new(permits),
+            available: Condvar::new(),
+        }
+    }
+
+    async fn acquire(&self) -> LoomPermit<'_> {
+        let mut permits = self.permits.lock().unwrap();
+        while *permits == 0 {
+            permits = self.available.wait(permits).unwrap();
+        }
+        *permits -= 1;
+        LoomPermit(self)
+    }
+
+    fn try_acquire(&self) -> Option<LoomPermit<'_>> {
+        let mut permits = self.permits.lock().unwrap();
+        if *permits == 0 {
+            return None;
+        }
+        *permits -= 1;
+        Some(LoomPermit(self))
+    }
+
+    fn permits(&self) -> usize {
+        *self.permits.lock().unwrap()
+    }
+}
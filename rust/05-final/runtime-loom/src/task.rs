@@ -0,0 +1,52 @@
+use base::{BoxFuture, JoinError, JoinHandle, LocalBoxFuture};
+use loom::future::block_on;
+use loom::sync::atomic::{AtomicBool, Ordering};
+use loom::sync::Arc;
+use loom::thread;
+
+/// A task run on its own loom thread with [block_on]. loom threads can't
+/// be cancelled, so [JoinHandle::abort] does nothing. A local task can't
+/// move to another thread, so it runs to completion when it is spawned.
+pub enum LoomJoinHandle<T> {
+    Thread(Option<thread::JoinHandle<T>>, Arc<AtomicBool>),
+    Done(Option<T>),
+}
+
+impl<T: Send + 'static> LoomJoinHandle<T> {
+    pub(crate) fn spawn(future: BoxFuture<'static, T>) -> Self {
+        let finished = Arc::new(AtomicBool::new(false));
+        let f = finished.clone();
+        let handle = thread::spawn(move || {
+            let output = block_on(future);
+            f.store(true, Ordering::Release);
+            output
+        });
+        LoomJoinHandle::Thread(Some(handle), finished)
+    }
+
+    pub(crate) fn spawn_local(future: LocalBoxFuture<'static, T>) -> Self {
+        LoomJoinHandle::Done(Some(block_on(future)))
+    }
+}
+
+impl<T: Send> JoinHandle<T> for LoomJoinHandle<T> {
+    async fn join(&mut self) -> Result<T, JoinError> {
+        match self {
+            LoomJoinHandle::Thread(handle, _) => handle
+                .take()
+                .expect("task was already joined")
+                .join()
+                .map_err(|_| JoinError::Panicked),
+            LoomJoinHandle::Done(output) => Ok(output.take().expect("task was already joined")),
+        }
+    }
+
+    fn abort(&self) {}
+
+    fn is_finished(&self) -> bool {
+        match self {
+            LoomJoinHandle::Thread(_, finished) => finished.load(Ordering::Acquire),
+            LoomJoinHandle::Done(_) => true,
+        }
+    }
+}
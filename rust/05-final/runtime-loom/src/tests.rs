@@ -0,0 +1,156 @@
+use super::*;
+use base::{JoinHandle, RecvError, Upgradable};
+use loom::sync::atomic::{AtomicUsize, Ordering};
+use loom::sync::Arc;
+use loom::thread;
+
+type R = LoomRuntime;
+
+/// Counts how many times it has been dropped
+struct Counted(Arc<AtomicUsize>);
+
+impl Drop for Counted {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn loom_box_moved() {
+    // A box created on one thread is unboxed and dropped on another
+    // while the first thread still holds an owned guard.
+    loom::model(|| {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let b = R::box_lock(Counted(drops.clone()));
+        let r = block_on(R::unbox_lock(&b).read_owned());
+        let h = thread::spawn(move || {
+            let lock = R::unbox_lock(&b);
+            drop(block_on(lock.read()));
+            drop(b);
+        });
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        h.join().unwrap();
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        drop(r);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    });
+}
+
+#[test]
+fn loom_box_shared() {
+    // The item of a shared box is dropped exactly once, by whichever
+    // thread drops the last handle, and each thread's update is seen by
+    // the other.
+    loom::model(|| {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let b1 = ImplBoxShared::from(R::box_lock((0, Counted(drops.clone()))));
+        let b2 = b1.clone();
+        let h = thread::spawn(move || {
+            block_on(R::unbox_lock(&b2).write()).0 += 1;
+        });
+        block_on(R::unbox_lock(&b1).write()).0 += 1;
+        h.join().unwrap();
+        assert_eq!(block_on(R::unbox_lock(&b1).read()).0, 2);
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        drop(b1);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    });
+}
+
+#[test]
+fn loom_box_upgrade() {
+    // Boxed locks keep the upgradable read's guarantee between threads.
+    loom::model(|| {
+        let b1 = ImplBoxShared::from(R::box_lock(0));
+        let b2 = b1.clone();
+        let h = thread::spawn(move || {
+            *block_on(R::unbox_lock(&b2).write()) += 1;
+        });
+        let lock = R::unbox_lock(&b1);
+        let u = block_on(lock.upgradable_read());
+        let n = *u;
+        *block_on(u.upgrade()) = n + 1;
+        h.join().unwrap();
+        assert_eq!(*block_on(lock.read()), 2);
+    });
+}
+
+#[test]
+fn loom_channel_stream() {
+    // A stream reads a shared channel that is closed on another thread.
+    loom::model(|| {
+        let c = R::box_shared_channel(Some(1));
+        let mut s = R::box_stream(c.clone());
+        let h = thread::spawn(move || {
+            let tx = R::unbox_channel(&c);
+            block_on(tx.send(1)).unwrap();
+            block_on(tx.send(2)).unwrap();
+            tx.close();
+        });
+        let s = R::unbox_mut_stream(&mut s);
+        assert_eq!(block_on(s.next()), Some(1));
+        assert_eq!(block_on(s.next()), Some(2));
+        assert_eq!(block_on(s.next()), None);
+        h.join().unwrap();
+    });
+}
+
+#[test]
+fn loom_task_moved() {
+    // A boxed task is joined on a thread other than the one that
+    // spawned it.
+    loom::model(|| {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let counted = Counted(drops.clone());
+        let mut t = R::box_task(Box::pin(async move {
+            drop(counted);
+            5
+        }));
+        let h = thread::spawn(move || block_on(R::unbox_mut_task(&mut t).join()));
+        assert_eq!(h.join().unwrap(), Ok(5));
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    });
+}
+
+#[test]
+fn loom_cancel() {
+    // Cancelling a parent wakes a child that is waiting on another
+    // thread.
+    loom::model(|| {
+        let parent = R::box_cancel_token(None);
+        let child = R::box_cancel_token(Some(&parent));
+        let h = thread::spawn(move || {
+            block_on(R::unbox_cancel_token(&child).cancelled());
+            R::unbox_cancel_token(&child).is_cancelled()
+        });
+        R::unbox_cancel_token(&parent).cancel();
+        assert!(h.join().unwrap());
+    });
+}
+
+#[test]
+fn loom_broadcast() {
+    // A receiver on another thread sees every item or a lag, and then
+    // the close.
+    loom::model(|| {
+        let b = R::box_broadcast(1);
+        let mut rx = R::box_broadcast_receiver(&b);
+        let h = thread::spawn(move || {
+            let rx = R::unbox_mut_broadcast_receiver(&mut rx);
+            let mut seen = Vec::new();
+            loop {
+                match block_on(rx.recv()) {
+                    Ok(i) => seen.push(i),
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => return seen,
+                }
+            }
+        });
+        let tx = R::unbox_broadcast(&b);
+        tx.send(1);
+        tx.send(2);
+        drop(b);
+        let seen = h.join().unwrap();
+        assert!(seen == [1, 2] || seen == [2], "{seen:?}");
+    });
+}
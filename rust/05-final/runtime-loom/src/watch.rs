@@ -0,0 +1,100 @@
+use base::{RecvError, Watch, WatchReceiver};
+use loom::sync::{Arc, Condvar, Mutex};
+
+struct WatchState<T> {
+    value: T,
+    version: u64,
+    receivers: usize,
+    closed: bool,
+}
+
+struct WatchShared<T> {
+    state: Mutex<WatchState<T>>,
+    changed: Condvar,
+}
+
+/// A value with a version that is incremented on each change. Each
+/// receiver keeps the last version it saw.
+pub struct LoomWatch<T> {
+    shared: Arc<WatchShared<T>>,
+}
+
+impl<T> LoomWatch<T> {
+    pub fn subscribe(&self) -> LoomWatchReceiver<T> {
+        let mut state = self.shared.state.lock().unwrap();
+        state.receivers += 1;
+        LoomWatchReceiver {
+            shared: self.shared.clone(),
+            seen: state.version,
+        }
+    }
+}
+
+impl<T> Drop for LoomWatch<T> {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().closed = true;
+        self.shared.changed.notify_all();
+    }
+}
+
+impl<T: Clone> Watch<T> for LoomWatch<T> {
+    fn new(initial: T) -> Self {
+        LoomWatch {
+            shared: Arc::new(WatchShared {
+                state: Mutex::new(WatchState {
+                    value: initial,
+                    version: 0,
+                    receivers: 0,
+                    closed: false,
+                }),
+                changed: Condvar::new(),
+            }),
+        }
+    }
+
+    fn send(&self, value: T) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.value = value;
+        state.version += 1;
+        self.shared.changed.notify_all();
+    }
+
+    fn get(&self) -> T {
+        self.shared.state.lock().unwrap().value.clone()
+    }
+
+    fn receiver_count(&self) -> usize {
+        self.shared.state.lock().unwrap().receivers
+    }
+}
+
+pub struct LoomWatchReceiver<T> {
+    shared: Arc<WatchShared<T>>,
+    seen: u64,
+}
+
+impl<T> Drop for LoomWatchReceiver<T> {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().receivers -= 1;
+    }
+}
+
+impl<T: Clone + Sync + Send> WatchReceiver<T> for LoomWatchReceiver<T> {
+    async fn changed(&mut self) -> Result<(), RecvError> {
+        let mut state = self.shared.state.lock().unwrap();
+        while state.version == self.seen && !state.closed {
+            state = self.shared.changed.wait(state).unwrap();
+        }
+        if state.version == self.seen {
+            return Err(RecvError::Closed);
+        }
+        self.seen = state.version;
+        Ok(())
+    }
+
+    fn get(&mut self) -> T {
+        let state = self.shared.state.lock().unwrap();
+        self.seen = state.version;
+        state.value.clone()
+    }
+}
@@ -0,0 +1,116 @@
+//! An implementation of the [base] lock traits using parking_lot, for
+//! code whose critical sections are short and rarely contended, where a
+//! lock that is almost always free costs less to take with parking_lot
+//! than with an async lock. Locks, mutexes, and semaphores block the
+//! thread while they wait, so their futures are ready as soon as they are
+//! polled. While the lock is free, that costs one atomic operation.
+//!
+//! Only [Locker] is implemented; pair it with the [base::Runtime] of the
+//! executor the code already runs on. Since waiting blocks the thread, a
+//! task must not hold a guard across an `.await` that waits for another
+//! task on the same thread, and a lock that is held for long blocks every
+//! task waiting for it. Use the executor's own runtime for those.
+//!
+//! - locks, mutexes, and semaphores are parking_lot's, which are
+//!   eventually fair and have native upgradable reads
+//! - channels and broadcast channels are runtime-std's, which also block
+//! - notify, watch, and barrier are the executor-independent ones from
+//!   [base]
+use crate::rwlock::{ParkingLotLockWrapper, ParkingLotMutexWrapper};
+use crate::semaphore::ParkingLotSemaphoreWrapper;
+use base::{
+    AsyncBarrier, AsyncChannel, AsyncMutex, AsyncNotify, AsyncRwLock, AsyncSemaphore, AsyncStream,
+    BarrierBox, Broadcast, BroadcastBox, BroadcastReceiver, BroadcastReceiverBox, ChannelBox,
+    ChannelStream, LockBox, LockPolicy, Locker, MutexBox, NotifyBox, PolicyLockBox, SemaphoreBox,
+    StdBarrier, StdNotify, StdWatch, StdWatchReceiver, StreamBox, Watch, WatchBox, WatchReceiver,
+    WatchReceiverBox,
+};
+use implbox::{ImplBox, ImplBoxShared};
+use implbox_macros::implbox_impls;
+use runtime_std::channel::{StdBroadcast, StdBroadcastReceiver, StdChannel};
+
+pub mod rwlock;
+pub mod semaphore;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Default, Clone)]
+pub struct ParkingLotRuntime;
+
+impl Locker for ParkingLotRuntime {
+    #[implbox_impls(LockBox<T>, ParkingLotLockWrapper<T>, downcast)]
+    fn new_lock<T: Sync + Send + 'static>(item: T) -> impl AsyncRwLock<T> {
+        ParkingLotLockWrapper::<T>::new(item)
+    }
+
+    /// parking_lot's lock is eventually fair and has no reader limit,
+    /// so the policy is ignored.
+    #[implbox_impls(PolicyLockBox<T>, ParkingLotLockWrapper<T>, downcast)]
+    fn new_lock_with<T: Sync + Send + 'static>(
+        _policy: LockPolicy,
+        item: T,
+    ) -> impl AsyncRwLock<T> {
+        ParkingLotLockWrapper::<T>::new(item)
+    }
+
+    #[implbox_impls(MutexBox<T>, ParkingLotMutexWrapper<T>, downcast)]
+    fn new_mutex<T: Sync + Send + 'static>(item: T) -> impl AsyncMutex<T> {
+        ParkingLotMutexWrapper::<T>::new(item)
+    }
+
+    #[implbox_impls(SemaphoreBox, ParkingLotSemaphoreWrapper, downcast)]
+    fn new_semaphore(permits: usize) -> impl AsyncSemaphore {
+        ParkingLotSemaphoreWrapper::new(permits)
+    }
+
+    #[implbox_impls(NotifyBox, StdNotify, downcast)]
+    fn new_notify() -> impl AsyncNotify {
+        StdNotify::new()
+    }
+
+    #[implbox_impls(BarrierBox, StdBarrier, downcast)]
+    fn new_barrier(n: usize) -> impl AsyncBarrier {
+        StdBarrier::new(n)
+    }
+
+    #[implbox_impls(ChannelBox<T>, StdChannel<T>, downcast)]
+    fn new_channel<T: Send + 'static>(capacity: Option<usize>) -> impl AsyncChannel<T> {
+        StdChannel::<T>::new(capacity)
+    }
+
+    #[implbox_impls(BroadcastBox<T>, StdBroadcast<T>, downcast)]
+    fn new_broadcast<T: Clone + Sync + Send + 'static>(capacity: usize) -> impl Broadcast<T> {
+        StdBroadcast::<T>::new(capacity)
+    }
+
+    #[implbox_impls(BroadcastReceiverBox<T>, StdBroadcastReceiver<T>, downcast)]
+    fn new_broadcast_receiver<T: Clone + Sync + Send + 'static>(
+        broadcast: &ImplBox<BroadcastBox<T>>,
+    ) -> impl BroadcastReceiver<T> {
+        broadcast
+            .downcast_ref::<StdBroadcast<T>>()
+            .expect("broadcast was not created by ParkingLotRuntime")
+            .subscribe()
+    }
+
+    #[implbox_impls(WatchBox<T>, StdWatch<T>, downcast)]
+    fn new_watch<T: Clone + Sync + Send + 'static>(initial: T) -> impl Watch<T> {
+        StdWatch::<T>::new(initial)
+    }
+
+    #[implbox_impls(WatchReceiverBox<T>, StdWatchReceiver<T>, downcast)]
+    fn new_watch_receiver<T: Clone + Sync + Send + 'static>(
+        watch: &ImplBox<WatchBox<T>>,
+    ) -> impl WatchReceiver<T> {
+        watch
+            .downcast_ref::<StdWatch<T>>()
+            .expect("watch was not created by ParkingLotRuntime")
+            .subscribe()
+    }
+
+    #[implbox_impls(StreamBox<T>, ChannelStream<T, ParkingLotRuntime>, downcast)]
+    fn new_stream<T: Send + 'static>(channel: ImplBoxShared<ChannelBox<T>>) -> impl AsyncStream<T> {
+        ChannelStream::<T, Self>::new(channel)
+    }
+}
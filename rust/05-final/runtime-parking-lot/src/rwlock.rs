@@ -0,0 +1,132 @@
+use base::{AsyncMutex, AsyncRwLock, Elapsed, Upgradable};
+use parking_lot::{
+    ArcRwLockReadGuard, ArcRwLockWriteGuard, Mutex, MutexGuard, RawRwLock, RwLock, RwLockReadGuard,
+    RwLockUpgradableReadGuard, RwLockWriteGuard,
+};
+use std::ops::Deref;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A parking_lot RwLock whose futures lock it by blocking the thread, so
+/// they are always ready when first polled. parking_lot's locks are
+/// eventually fair, so a steady stream of readers can't keep a writer
+/// waiting forever, and its upgradable reads are native.
+pub struct ParkingLotLockWrapper<T> {
+    lock: Arc<RwLock<T>>,
+}
+
+impl<T: Default> Default for ParkingLotLockWrapper<T> {
+    fn default() -> Self {
+        Self {
+            lock: Default::default(),
+        }
+    }
+}
+
+/// The guard returned by [AsyncRwLock::upgradable_read]
+pub struct ParkingLotUpgradableReadGuard<'a, T>(RwLockUpgradableReadGuard<'a, T>);
+
+impl<T> Deref for ParkingLotUpgradableReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<'a, T: Sync + Send + 'a> Upgradable<'a, T> for ParkingLotUpgradableReadGuard<'a, T> {
+    type WriteGuard = RwLockWriteGuard<'a, T>;
+
+    async fn upgrade(self) -> RwLockWriteGuard<'a, T> {
+        RwLockUpgradableReadGuard::upgrade(self.0)
+    }
+}
+
+impl<T: Sync + Send + 'static> AsyncRwLock<T> for ParkingLotLockWrapper<T> {
+    type ReadGuard<'a>
+        = RwLockReadGuard<'a, T>
+    where
+        Self: 'a;
+    type WriteGuard<'a>
+        = RwLockWriteGuard<'a, T>
+    where
+        Self: 'a;
+    type UpgradableReadGuard<'a>
+        = ParkingLotUpgradableReadGuard<'a, T>
+    where
+        Self: 'a;
+    type OwnedReadGuard = ArcRwLockReadGuard<RawRwLock, T>;
+    type OwnedWriteGuard = ArcRwLockWriteGuard<RawRwLock, T>;
+
+    fn new(item: T) -> Self {
+        Self {
+            lock: Arc::new(RwLock::new(item)),
+        }
+    }
+
+    async fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.lock.read()
+    }
+
+    async fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.lock.write()
+    }
+
+    async fn upgradable_read(&self) -> ParkingLotUpgradableReadGuard<'_, T> {
+        ParkingLotUpgradableReadGuard(self.lock.upgradable_read())
+    }
+
+    async fn read_owned(&self) -> ArcRwLockReadGuard<RawRwLock, T> {
+        self.lock.read_arc()
+    }
+
+    async fn write_owned(&self) -> ArcRwLockWriteGuard<RawRwLock, T> {
+        self.lock.write_arc()
+    }
+
+    /// Every function of this lock blocks, so this is the same as
+    /// [AsyncRwLock::read], and is safe to call from async code.
+    fn blocking_read(&self) -> RwLockReadGuard<'_, T> {
+        self.lock.read()
+    }
+
+    fn blocking_write(&self) -> RwLockWriteGuard<'_, T> {
+        self.lock.write()
+    }
+
+    fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        self.lock.try_read()
+    }
+
+    fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        self.lock.try_write()
+    }
+
+    async fn read_timeout(&self, duration: Duration) -> Result<RwLockReadGuard<'_, T>, Elapsed> {
+        self.lock.try_read_for(duration).ok_or(Elapsed)
+    }
+
+    async fn write_timeout(&self, duration: Duration) -> Result<RwLockWriteGuard<'_, T>, Elapsed> {
+        self.lock.try_write_for(duration).ok_or(Elapsed)
+    }
+}
+
+/// A parking_lot Mutex that blocks the thread like
+/// [ParkingLotLockWrapper]
+#[derive(Default)]
+pub struct ParkingLotMutexWrapper<T>(Mutex<T>);
+
+impl<T: Sync + Send + 'static> AsyncMutex<T> for ParkingLotMutexWrapper<T> {
+    type Guard<'a>
+        = MutexGuard<'a, T>
+    where
+        Self: 'a;
+
+    fn new(item: T) -> Self {
+        ParkingLotMutexWrapper(Mutex::new(item))
+    }
+
+    async fn lock(&self) -> MutexGuard<'_, T> {
+        self.0.lock()
+    }
+}
@@ -0,0 +1,50 @@
+use base::AsyncSemaphore;
+use parking_lot::{Condvar, Mutex};
+
+/// A semaphore that blocks the thread until a permit is available
+pub struct ParkingLotSemaphoreWrapper {
+    permits: Mutex<usize>,
+    released: Condvar,
+}
+
+pub struct ParkingLotPermit<'a> {
+    semaphore: &'a ParkingLotSemaphoreWrapper,
+}
+
+impl Drop for ParkingLotPermit<'_> {
+    fn drop(&mut self) {
+        *self.semaphore.permits.lock() += 1;
+        self.semaphore.released.notify_one();
+    }
+}
+
+impl AsyncSemaphore for ParkingLotSemaphoreWrapper {
+    type Permit<'a> = ParkingLotPermit<'a>;
+
+    fn new(permits: usize) -> Self {
+        ParkingLotSemaphoreWrapper {
+            permits: Mutex::new(permits),
+            released: Condvar::new(),
+        }
+    }
+
+    async fn acquire(&self) -> ParkingLotPermit<'_> {
+        let mut permits = self.permits.lock();
+        self.released.wait_while(&mut permits, |p| *p == 0);
+        *permits -= 1;
+        ParkingLotPermit { semaphore: self }
+    }
+
+    fn try_acquire(&self) -> Option<ParkingLotPermit<'_>> {
+        let mut permits = self.permits.lock();
+        if *permits == 0 {
+            return None;
+        }
+        *permits -= 1;
+        Some(ParkingLotPermit { semaphore: self })
+    }
+
+    fn permits(&self) -> usize {
+        *self.permits.lock()
+    }
+}
@@ -0,0 +1,140 @@
+use super::*;
+use base::{Elapsed, Executor, SendError, StdExecutor, Upgradable};
+use std::future::Future;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+type R = ParkingLotRuntime;
+
+fn run<F: Future>(future: F) -> F::Output {
+    StdExecutor.block_on(future)
+}
+
+#[test]
+fn test_lock() {
+    run(async {
+        let l = R::box_lock(3);
+        let lock = R::unbox_lock(&l);
+        {
+            let r1 = lock.read().await;
+            let r2 = lock.try_read().unwrap();
+            assert_eq!(*r1 + *r2, 6);
+            assert!(lock.try_write().is_none());
+            assert_eq!(
+                lock.write_timeout(Duration::from_millis(10)).await.err(),
+                Some(Elapsed)
+            );
+        }
+        *lock.write().await += 1;
+        let owned = lock.read_owned().await;
+        drop(l);
+        // The owned guard keeps the item alive.
+        assert_eq!(*owned, 4);
+    })
+}
+
+#[test]
+fn test_wait() {
+    // Waiting blocks the thread until the lock is released.
+    let lock = Arc::new(R::new_lock(1));
+    let mut guard = run(lock.write_owned());
+    let reader = {
+        let lock = lock.clone();
+        thread::spawn(move || run(async { *lock.read().await }))
+    };
+    thread::sleep(Duration::from_millis(10));
+    *guard = 2;
+    drop(guard);
+    assert_eq!(reader.join().unwrap(), 2);
+}
+
+#[test]
+fn test_upgrade() {
+    run(async {
+        let lock = Arc::new(R::new_lock(1));
+        let upgradable = lock.upgradable_read().await;
+        // Plain readers can still share the lock.
+        assert_eq!(*lock.read().await, 1);
+        assert!(lock.try_write().is_none());
+        assert!(lock.try_read().is_some());
+        let mut w = upgradable.upgrade().await;
+        *w = 2;
+        drop(w);
+        assert_eq!(*lock.blocking_read(), 2);
+        *lock.blocking_write() = 3;
+        assert_eq!(*lock.read_timeout(Duration::from_secs(1)).await.unwrap(), 3);
+    })
+}
+
+#[test]
+fn test_mutex_and_semaphore() {
+    run(async {
+        let m = R::new_mutex(String::new());
+        m.lock().await.push_str("potato");
+        assert_eq!(*m.lock().await, "potato");
+
+        let s = R::new_semaphore(2);
+        let p1 = s.acquire().await;
+        let p2 = s.try_acquire().unwrap();
+        assert_eq!(s.permits(), 0);
+        assert!(s.try_acquire().is_none());
+        drop(p1);
+        assert_eq!(s.permits(), 1);
+        drop(p2);
+        assert_eq!(s.permits(), 2);
+    })
+}
+
+#[test]
+fn test_semaphore_wait() {
+    // A thread waiting for a permit gets the one that is released.
+    let s = Arc::new(R::new_semaphore(1));
+    let permit = s.try_acquire().unwrap();
+    let waiter = {
+        let s = s.clone();
+        thread::spawn(move || {
+            run(async {
+                let _permit = s.acquire().await;
+                s.permits()
+            })
+        })
+    };
+    thread::sleep(Duration::from_millis(10));
+    drop(permit);
+    assert_eq!(waiter.join().unwrap(), 0);
+    assert_eq!(s.permits(), 1);
+}
+
+#[test]
+fn test_channel() {
+    run(async {
+        let c = R::box_shared_channel(Some(1));
+        let mut s = R::box_stream(c.clone());
+        let tx = R::unbox_channel(&c);
+        tx.send("potato").await.unwrap();
+        assert_eq!(tx.try_send("salad"), Err(SendError::Full("salad")));
+        tx.close();
+        let s = R::unbox_mut_stream(&mut s);
+        assert_eq!(s.next().await, Some("potato"));
+        assert_eq!(s.next().await, None);
+    })
+}
+
+#[test]
+fn test_watch_and_broadcast() {
+    run(async {
+        let w = R::box_watch(1);
+        let mut boxed = R::box_watch_receiver(&w);
+        let rx = R::unbox_mut_watch_receiver(&mut boxed);
+        R::unbox_watch(&w).send(2);
+        rx.changed().await.unwrap();
+        assert_eq!(rx.get(), 2);
+
+        let b = R::box_broadcast(2);
+        let mut rx = R::box_broadcast_receiver(&b);
+        let rx = R::unbox_mut_broadcast_receiver(&mut rx);
+        assert_eq!(R::unbox_broadcast(&b).send(5), 1);
+        assert_eq!(rx.recv().await, Ok(5));
+    })
+}
@@ -0,0 +1,63 @@
+use async_broadcast::{InactiveReceiver, Receiver, Sender, TryRecvError};
+use base::{Broadcast, BroadcastReceiver, RecvError};
+
+/// The sending half of an async-broadcast channel. It keeps an inactive
+/// receiver so that the channel stays open while there are no
+/// receivers. Receivers are created with [SmolBroadcast::subscribe].
+pub struct SmolBroadcast<T> {
+    tx: Sender<T>,
+    keep_open: InactiveReceiver<T>,
+}
+
+impl<T> SmolBroadcast<T> {
+    pub fn subscribe(&self) -> SmolBroadcastReceiver<T> {
+        SmolBroadcastReceiver(self.keep_open.activate_cloned())
+    }
+}
+
+impl<T: Clone> Broadcast<T> for SmolBroadcast<T> {
+    fn new(capacity: usize) -> Self {
+        let (mut tx, rx) = async_broadcast::broadcast(capacity);
+        // Drop the oldest item instead of waiting when receivers fall
+        // behind.
+        tx.set_overflow(true);
+        SmolBroadcast {
+            tx,
+            keep_open: rx.deactivate(),
+        }
+    }
+
+    fn send(&self, item: T) -> usize {
+        // This only fails if there are no active receivers.
+        match self.tx.try_broadcast(item) {
+            Ok(_) => self.tx.receiver_count(),
+            Err(_) => 0,
+        }
+    }
+
+    fn receiver_count(&self) -> usize {
+        self.tx.receiver_count()
+    }
+}
+
+pub struct SmolBroadcastReceiver<T>(Receiver<T>);
+
+impl<T: Clone + Send + Sync> BroadcastReceiver<T> for SmolBroadcastReceiver<T> {
+    async fn recv(&mut self) -> Result<T, RecvError> {
+        base::trace_future!(self.0.recv_direct(), "broadcast.recv")
+            .await
+            .map_err(|e| match e {
+                async_broadcast::RecvError::Overflowed(n) => RecvError::Lagged(n),
+                async_broadcast::RecvError::Closed => RecvError::Closed,
+            })
+    }
+
+    fn try_recv(&mut self) -> Result<Option<T>, RecvError> {
+        match self.0.try_recv() {
+            Ok(item) => Ok(Some(item)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Overflowed(n)) => Err(RecvError::Lagged(n)),
+            Err(TryRecvError::Closed) => Err(RecvError::Closed),
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use base::Executor;
+use smol::LocalExecutor;
+use std::future::Future;
+
+thread_local! {
+    /// Tasks from [base::Runtime::spawn_local] on this thread. They run
+    /// while [SmolExecutor::block_on] is running on the thread.
+    pub(crate) static LOCAL: LocalExecutor<'static> = const { LocalExecutor::new() };
+}
+
+/// Runs futures with [smol::block_on]. Tasks from [base::Runtime::spawn]
+/// run on smol's global executor, which keeps running in the background,
+/// so there is nothing to shut down. Tasks from
+/// [base::Runtime::spawn_local] only run while `block_on` is running on
+/// the thread that spawned them.
+pub struct SmolExecutor;
+
+impl Executor for SmolExecutor {
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        LOCAL.with(|local| smol::block_on(local.run(future)))
+    }
+}
@@ -0,0 +1,36 @@
+use base::AsyncFile;
+use smol::fs::File;
+use smol::io::{AsyncReadExt, AsyncWriteExt};
+use std::fs::OpenOptions;
+use std::io;
+use std::path::Path;
+
+pub struct SmolFile(File);
+
+impl SmolFile {
+    /// smol's OpenOptions can't be built from std's, so the file is opened
+    /// with std on a blocking thread.
+    pub async fn open(path: &Path, options: &OpenOptions) -> io::Result<Self> {
+        let path = path.to_owned();
+        let options = options.clone();
+        let file = smol::unblock(move || options.open(path)).await?;
+        Ok(SmolFile(File::from(file)))
+    }
+}
+
+impl AsyncFile for SmolFile {
+    async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        base::trace_future!(self.0.read(buf), "file.read").await
+    }
+
+    async fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        base::trace_future!(self.0.write(buf), "file.write").await
+    }
+
+    async fn sync(&mut self) -> io::Result<()> {
+        // smol finishes writes in the background, so flush before syncing
+        // to report their errors here.
+        self.0.flush().await?;
+        base::trace_future!(self.0.sync_all(), "file.sync").await
+    }
+}
@@ -0,0 +1,195 @@
+//! An implementation of the [base] runtime traits on top of smol, for
+//! programs that embed smol and don't want to link tokio just to use
+//! [Runtime]-generic code such as `Controller`. Locks, channels, and
+//! barriers are smol's own, from async-lock and async-channel, and
+//! broadcast channels come from async-broadcast. smol has no notify, watch, or cancel token,
+//! so those are the executor-independent ones from [base].
+use crate::barrier::SmolBarrier;
+use crate::broadcast::{SmolBroadcast, SmolBroadcastReceiver};
+use crate::channel::SmolChannel;
+use crate::executor::SmolExecutor;
+use crate::fs::SmolFile;
+use crate::mutex::SmolMutexWrapper;
+use crate::net::{SmolTcpListener, SmolTcpStream, SmolUdpSocket};
+use crate::rwlock::SmolLockWrapper;
+use crate::semaphore::SmolSemaphoreWrapper;
+use crate::task::SmolJoinHandle;
+use crate::time::SmolClock;
+use base::{
+    AsyncBarrier, AsyncChannel, AsyncFile, AsyncMutex, AsyncNotify, AsyncRwLock, AsyncSemaphore,
+    AsyncStream, AsyncTcpListener, AsyncTcpStream, AsyncUdpSocket, BarrierBox, BoxFuture,
+    Broadcast, BroadcastBox, BroadcastReceiver, BroadcastReceiverBox, CancelToken, CancelTokenBox,
+    ChannelBox, ChannelStream, Elapsed, FileBox, JoinHandle, JoinHandleBox, LocalBoxFuture,
+    LockBox, LockPolicy, Locker, MutexBox, NotifyBox, PolicyLockBox, Runtime, SemaphoreBox,
+    StdCancelToken, StdNotify, StdWatch, StdWatchReceiver, StreamBox, TcpListenerBox, TcpStreamBox,
+    UdpSocketBox, Watch, WatchBox, WatchReceiver, WatchReceiverBox,
+};
+use implbox::{ImplBox, ImplBoxShared};
+use implbox_macros::implbox_impls;
+use std::fs::OpenOptions;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::Duration;
+
+pub mod barrier;
+pub mod broadcast;
+pub mod channel;
+pub mod executor;
+pub mod fs;
+pub mod mutex;
+pub mod net;
+pub mod rwlock;
+pub mod semaphore;
+pub mod task;
+pub mod time;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Default, Clone)]
+pub struct SmolRuntime;
+
+impl Locker for SmolRuntime {
+    #[implbox_impls(LockBox<T>, SmolLockWrapper<T>, downcast)]
+    fn new_lock<T: Sync + Send + 'static>(item: T) -> impl AsyncRwLock<T> {
+        SmolLockWrapper::<T>::new(item)
+    }
+
+    /// async-lock's lock always prefers writers and has no reader
+    /// limit, so the policy is ignored.
+    #[implbox_impls(PolicyLockBox<T>, SmolLockWrapper<T>, downcast)]
+    fn new_lock_with<T: Sync + Send + 'static>(
+        _policy: LockPolicy,
+        item: T,
+    ) -> impl AsyncRwLock<T> {
+        SmolLockWrapper::<T>::new(item)
+    }
+
+    #[implbox_impls(MutexBox<T>, SmolMutexWrapper<T>, downcast)]
+    fn new_mutex<T: Sync + Send + 'static>(item: T) -> impl AsyncMutex<T> {
+        SmolMutexWrapper::<T>::new(item)
+    }
+
+    #[implbox_impls(SemaphoreBox, SmolSemaphoreWrapper, downcast)]
+    fn new_semaphore(permits: usize) -> impl AsyncSemaphore {
+        SmolSemaphoreWrapper::new(permits)
+    }
+
+    #[implbox_impls(NotifyBox, StdNotify, downcast)]
+    fn new_notify() -> impl AsyncNotify {
+        StdNotify::new()
+    }
+
+    #[implbox_impls(BarrierBox, SmolBarrier, downcast)]
+    fn new_barrier(n: usize) -> impl AsyncBarrier {
+        SmolBarrier::new(n)
+    }
+
+    #[implbox_impls(ChannelBox<T>, SmolChannel<T>, downcast)]
+    fn new_channel<T: Send + 'static>(capacity: Option<usize>) -> impl AsyncChannel<T> {
+        SmolChannel::<T>::new(capacity)
+    }
+
+    #[implbox_impls(BroadcastBox<T>, SmolBroadcast<T>, downcast)]
+    fn new_broadcast<T: Clone + Sync + Send + 'static>(capacity: usize) -> impl Broadcast<T> {
+        SmolBroadcast::<T>::new(capacity)
+    }
+
+    #[implbox_impls(BroadcastReceiverBox<T>, SmolBroadcastReceiver<T>, downcast)]
+    fn new_broadcast_receiver<T: Clone + Sync + Send + 'static>(
+        broadcast: &ImplBox<BroadcastBox<T>>,
+    ) -> impl BroadcastReceiver<T> {
+        broadcast
+            .downcast_ref::<SmolBroadcast<T>>()
+            .expect("broadcast was not created by SmolRuntime")
+            .subscribe()
+    }
+
+    #[implbox_impls(WatchBox<T>, StdWatch<T>, downcast)]
+    fn new_watch<T: Clone + Sync + Send + 'static>(initial: T) -> impl Watch<T> {
+        StdWatch::<T>::new(initial)
+    }
+
+    #[implbox_impls(WatchReceiverBox<T>, StdWatchReceiver<T>, downcast)]
+    fn new_watch_receiver<T: Clone + Sync + Send + 'static>(
+        watch: &ImplBox<WatchBox<T>>,
+    ) -> impl WatchReceiver<T> {
+        watch
+            .downcast_ref::<StdWatch<T>>()
+            .expect("watch was not created by SmolRuntime")
+            .subscribe()
+    }
+
+    #[implbox_impls(StreamBox<T>, ChannelStream<T, SmolRuntime>, downcast)]
+    fn new_stream<T: Send + 'static>(channel: ImplBoxShared<ChannelBox<T>>) -> impl AsyncStream<T> {
+        ChannelStream::<T, Self>::new(channel)
+    }
+}
+
+impl Runtime for SmolRuntime {
+    type Executor = SmolExecutor;
+    type Clock = SmolClock;
+
+    fn new_executor() -> io::Result<SmolExecutor> {
+        Ok(SmolExecutor)
+    }
+
+    fn clock() -> SmolClock {
+        SmolClock
+    }
+
+    #[implbox_impls(JoinHandleBox<T>, SmolJoinHandle<T>, downcast, name = "task")]
+    fn spawn<T: Send + 'static>(future: BoxFuture<'static, T>) -> impl JoinHandle<T> {
+        let (future, abort) = base::abortable(future);
+        SmolJoinHandle::new(smol::spawn(future), abort)
+    }
+
+    /// The task runs while [SmolExecutor]'s `block_on` is running on this
+    /// thread.
+    #[implbox_impls(JoinHandleBox<T>, SmolJoinHandle<T>, downcast, name = "local_task")]
+    fn spawn_local<T: Send + 'static>(future: LocalBoxFuture<'static, T>) -> impl JoinHandle<T> {
+        let (future, abort) = base::abortable(future);
+        let task = executor::LOCAL.with(|local| local.spawn(future));
+        SmolJoinHandle::new(task, abort)
+    }
+
+    #[implbox_impls(CancelTokenBox, StdCancelToken, downcast)]
+    fn new_cancel_token(parent: Option<&ImplBox<CancelTokenBox>>) -> impl CancelToken + Clone {
+        match parent {
+            Some(parent) => parent
+                .downcast_ref::<StdCancelToken>()
+                .expect("cancel token was not created by SmolRuntime")
+                .child(),
+            None => StdCancelToken::default(),
+        }
+    }
+
+    #[implbox_impls(TcpStreamBox, SmolTcpStream, downcast, name = "tcp_stream")]
+    async fn connect_tcp(addr: SocketAddr) -> Result<impl AsyncTcpStream, io::Error> {
+        SmolTcpStream::connect(addr).await
+    }
+
+    #[implbox_impls(TcpListenerBox, SmolTcpListener, downcast, name = "tcp_listener")]
+    async fn bind_tcp(addr: SocketAddr) -> Result<impl AsyncTcpListener, io::Error> {
+        SmolTcpListener::bind(addr).await
+    }
+
+    #[implbox_impls(UdpSocketBox, SmolUdpSocket, downcast, name = "udp_socket")]
+    async fn bind_udp(addr: SocketAddr) -> Result<impl AsyncUdpSocket, io::Error> {
+        SmolUdpSocket::bind(addr).await
+    }
+
+    #[implbox_impls(FileBox, SmolFile, downcast, name = "file")]
+    async fn open_file(path: &Path, options: &OpenOptions) -> Result<impl AsyncFile, io::Error> {
+        SmolFile::open(path, options).await
+    }
+
+    async fn timeout<F: Future + Send>(
+        duration: Duration,
+        future: F,
+    ) -> Result<F::Output, Elapsed> {
+        time::timeout(duration, future).await
+    }
+}
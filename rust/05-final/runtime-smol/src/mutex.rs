@@ -0,0 +1,38 @@
+use async_lock::{Mutex, MutexGuard};
+use base::AsyncMutex;
+
+#[derive(Default)]
+pub struct SmolMutexWrapper<T> {
+    mutex: Mutex<T>,
+}
+
+impl<T> SmolMutexWrapper<T> {
+    /// Lock from synchronous code by blocking the thread. See
+    /// [async_lock::Mutex::lock_blocking]. To get the wrapper from a
+    /// boxed mutex, use [implbox::ImplBox::downcast_ref].
+    pub fn blocking_lock(&self) -> MutexGuard<'_, T> {
+        self.mutex.lock_blocking()
+    }
+}
+
+impl<T: Sync + Send> AsyncMutex<T> for SmolMutexWrapper<T> {
+    type Guard<'a>
+        = MutexGuard<'a, T>
+    where
+        Self: 'a;
+
+    fn new(item: T) -> Self {
+        SmolMutexWrapper {
+            mutex: Mutex::new(item),
+        }
+    }
+
+    async fn lock(&self) -> MutexGuard<'_, T> {
+        base::trace_future!(
+            self.mutex.lock(),
+            "mutex.lock",
+            item = std::any::type_name::<T>()
+        )
+        .await
+    }
+}
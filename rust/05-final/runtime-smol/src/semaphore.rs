@@ -0,0 +1,57 @@
+use async_lock::{Semaphore, SemaphoreGuard};
+use base::AsyncSemaphore;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// async-lock's semaphore doesn't report how many permits are left, so
+/// the wrapper counts the permits it has given out.
+pub struct SmolSemaphoreWrapper {
+    semaphore: Semaphore,
+    permits: usize,
+    acquired: AtomicUsize,
+}
+
+pub struct SmolPermit<'a> {
+    _guard: SemaphoreGuard<'a>,
+    acquired: &'a AtomicUsize,
+}
+
+impl Drop for SmolPermit<'_> {
+    fn drop(&mut self) {
+        self.acquired.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl SmolSemaphoreWrapper {
+    fn permit<'a>(&'a self, guard: SemaphoreGuard<'a>) -> SmolPermit<'a> {
+        self.acquired.fetch_add(1, Ordering::AcqRel);
+        SmolPermit {
+            _guard: guard,
+            acquired: &self.acquired,
+        }
+    }
+}
+
+impl AsyncSemaphore for SmolSemaphoreWrapper {
+    type Permit<'a> = SmolPermit<'a>;
+
+    fn new(permits: usize) -> Self {
+        SmolSemaphoreWrapper {
+            semaphore: Semaphore::new(permits),
+            permits,
+            acquired: AtomicUsize::new(0),
+        }
+    }
+
+    async fn acquire(&self) -> SmolPermit<'_> {
+        let guard = base::trace_future!(self.semaphore.acquire(), "semaphore.acquire").await;
+        self.permit(guard)
+    }
+
+    fn try_acquire(&self) -> Option<SmolPermit<'_>> {
+        self.semaphore.try_acquire().map(|guard| self.permit(guard))
+    }
+
+    fn permits(&self) -> usize {
+        self.permits - self.acquired.load(Ordering::Acquire)
+    }
+}
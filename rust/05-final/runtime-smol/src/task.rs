@@ -0,0 +1,42 @@
+use base::{AbortHandle, JoinError, JoinHandle};
+use smol::Task;
+
+/// smol cancels a task when its handle is dropped and propagates panics
+/// when it is joined, so tasks are spawned wrapped with [base::abortable]
+/// and detached when the handle is dropped, as with tokio.
+pub struct SmolJoinHandle<T> {
+    task: Option<Task<Result<T, JoinError>>>,
+    abort: AbortHandle,
+}
+
+impl<T> SmolJoinHandle<T> {
+    pub fn new(task: Task<Result<T, JoinError>>, abort: AbortHandle) -> Self {
+        Self {
+            task: Some(task),
+            abort,
+        }
+    }
+}
+
+impl<T> Drop for SmolJoinHandle<T> {
+    fn drop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.detach();
+        }
+    }
+}
+
+impl<T: Send + 'static> JoinHandle<T> for SmolJoinHandle<T> {
+    async fn join(&mut self) -> Result<T, JoinError> {
+        let task = self.task.as_mut().expect("task is present until drop");
+        base::trace_future!(task, "task.join").await
+    }
+
+    fn abort(&self) {
+        self.abort.abort()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.abort.is_finished()
+    }
+}
@@ -0,0 +1,32 @@
+use base::{Clock, Elapsed};
+use smol::Timer;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+/// The clock for [crate::SmolRuntime]. smol has no virtual time, so
+/// tests that need it should use [base::VirtualClock].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SmolClock;
+
+impl Clock for SmolClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        Timer::after(duration).await;
+    }
+}
+
+/// smol has no timeout, so race the future against a [Timer]. If both
+/// are ready, the future wins.
+pub(crate) async fn timeout<F: Future>(
+    duration: Duration,
+    future: F,
+) -> Result<F::Output, Elapsed> {
+    smol::future::or(async { Ok(future.await) }, async {
+        Timer::after(duration).await;
+        Err(Elapsed)
+    })
+    .await
+}
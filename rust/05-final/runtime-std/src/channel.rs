@@ -0,0 +1,228 @@
+use base::{AsyncChannel, Broadcast, BroadcastReceiver, RecvError, SendError, TryRecvError};
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+
+struct ChannelState<T> {
+    items: VecDeque<T>,
+    capacity: Option<usize>,
+    closed: bool,
+    /// The number of threads blocked in recv
+    receiving: usize,
+}
+
+impl<T> ChannelState<T> {
+    fn is_full(&self) -> bool {
+        self.capacity.is_some_and(|c| self.items.len() >= c)
+    }
+}
+
+/// A channel that blocks the thread when it has to wait. Receiving from
+/// an empty channel on the only thread that sends to it never returns.
+pub struct StdChannel<T> {
+    state: Mutex<ChannelState<T>>,
+    changed: Condvar,
+}
+
+impl<T: Send> AsyncChannel<T> for StdChannel<T> {
+    fn new(capacity: Option<usize>) -> Self {
+        StdChannel {
+            state: Mutex::new(ChannelState {
+                items: VecDeque::new(),
+                capacity,
+                closed: false,
+                receiving: 0,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    async fn send(&self, item: T) -> Result<(), SendError<T>> {
+        let state = self.state.lock().unwrap();
+        let mut state = self
+            .changed
+            .wait_while(state, |s| s.is_full() && !s.closed)
+            .unwrap();
+        if state.closed {
+            return Err(SendError::Closed(item));
+        }
+        state.items.push_back(item);
+        self.changed.notify_all();
+        Ok(())
+    }
+
+    fn try_send(&self, item: T) -> Result<(), SendError<T>> {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return Err(SendError::Closed(item));
+        }
+        if state.is_full() {
+            return Err(SendError::Full(item));
+        }
+        state.items.push_back(item);
+        self.changed.notify_all();
+        Ok(())
+    }
+
+    async fn recv(&self) -> Option<T> {
+        let mut state = self.state.lock().unwrap();
+        state.receiving += 1;
+        let mut state = self
+            .changed
+            .wait_while(state, |s| s.items.is_empty() && !s.closed)
+            .unwrap();
+        state.receiving -= 1;
+        let item = state.items.pop_front();
+        // There is room for a sender.
+        self.changed.notify_all();
+        item
+    }
+
+    fn try_recv(&self) -> Result<T, TryRecvError> {
+        let mut state = self.state.lock().unwrap();
+        if state.receiving > 0 {
+            return Err(TryRecvError::Empty);
+        }
+        match state.items.pop_front() {
+            Some(item) => {
+                self.changed.notify_all();
+                Ok(item)
+            }
+            None if state.closed => Err(TryRecvError::Closed),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.changed.notify_all();
+    }
+
+    fn is_closed(&self) -> bool {
+        self.state.lock().unwrap().closed
+    }
+}
+
+struct BroadcastState<T> {
+    /// The most recent items, oldest first
+    items: VecDeque<T>,
+    /// The number of items sent before the first one in `items`
+    first: u64,
+    capacity: usize,
+    receivers: usize,
+    closed: bool,
+}
+
+impl<T> BroadcastState<T> {
+    /// The number of items sent so far
+    fn end(&self) -> u64 {
+        self.first + self.items.len() as u64
+    }
+}
+
+struct BroadcastShared<T> {
+    state: Mutex<BroadcastState<T>>,
+    sent: Condvar,
+}
+
+/// A broadcast channel whose receivers block the thread while they wait.
+/// Receivers are created with [StdBroadcast::subscribe].
+pub struct StdBroadcast<T>(Arc<BroadcastShared<T>>);
+
+impl<T> StdBroadcast<T> {
+    pub fn subscribe(&self) -> StdBroadcastReceiver<T> {
+        let mut state = self.0.state.lock().unwrap();
+        state.receivers += 1;
+        StdBroadcastReceiver {
+            next: state.end(),
+            shared: self.0.clone(),
+        }
+    }
+}
+
+impl<T> Drop for StdBroadcast<T> {
+    fn drop(&mut self) {
+        self.0.state.lock().unwrap().closed = true;
+        self.0.sent.notify_all();
+    }
+}
+
+impl<T: Clone> Broadcast<T> for StdBroadcast<T> {
+    fn new(capacity: usize) -> Self {
+        StdBroadcast(Arc::new(BroadcastShared {
+            state: Mutex::new(BroadcastState {
+                items: VecDeque::new(),
+                first: 0,
+                capacity: capacity.max(1),
+                receivers: 0,
+                closed: false,
+            }),
+            sent: Condvar::new(),
+        }))
+    }
+
+    fn send(&self, item: T) -> usize {
+        let mut state = self.0.state.lock().unwrap();
+        state.items.push_back(item);
+        if state.items.len() > state.capacity {
+            state.items.pop_front();
+            state.first += 1;
+        }
+        self.0.sent.notify_all();
+        state.receivers
+    }
+
+    fn receiver_count(&self) -> usize {
+        self.0.state.lock().unwrap().receivers
+    }
+}
+
+pub struct StdBroadcastReceiver<T> {
+    shared: Arc<BroadcastShared<T>>,
+    /// The number of the next item to receive
+    next: u64,
+}
+
+impl<T> Drop for StdBroadcastReceiver<T> {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().receivers -= 1;
+    }
+}
+
+impl<T: Clone> StdBroadcastReceiver<T> {
+    fn take(&mut self, state: &BroadcastState<T>) -> Result<Option<T>, RecvError> {
+        if self.next < state.first {
+            let lagged = state.first - self.next;
+            self.next = state.first;
+            return Err(RecvError::Lagged(lagged));
+        }
+        if self.next < state.end() {
+            let item = state.items[(self.next - state.first) as usize].clone();
+            self.next += 1;
+            return Ok(Some(item));
+        }
+        if state.closed {
+            return Err(RecvError::Closed);
+        }
+        Ok(None)
+    }
+}
+
+impl<T: Clone + Send + Sync> BroadcastReceiver<T> for StdBroadcastReceiver<T> {
+    async fn recv(&mut self) -> Result<T, RecvError> {
+        let shared = self.shared.clone();
+        let state = shared.state.lock().unwrap();
+        let state = shared
+            .sent
+            .wait_while(state, |s| self.next == s.end() && !s.closed)
+            .unwrap();
+        Ok(self
+            .take(&state)?
+            .expect("an item is available after waiting"))
+    }
+
+    fn try_recv(&mut self) -> Result<Option<T>, RecvError> {
+        let shared = self.shared.clone();
+        let state = shared.state.lock().unwrap();
+        self.take(&state)
+    }
+}
@@ -0,0 +1,183 @@
+//! An implementation of the [base] runtime traits that needs no async
+//! executor, for programs such as command-line tools that make one call
+//! at a time and would rather not depend on tokio. Locks, semaphores, and
+//! channels block the thread while they wait, so their futures are ready
+//! as soon as they are polled, and [base::StdExecutor] is enough to run
+//! them. Sockets and files are std's blocking ones.
+//!
+//! Since waiting blocks the thread, anything that waits for another task
+//! on the same thread never finishes, and [Runtime::timeout] can't stop a
+//! future that is blocked. Tasks from [Runtime::spawn] each get their own
+//! thread, so they can wait for each other. Notify, watch, barrier, and
+//! cancel token are the executor-independent ones from [base], which
+//! park the thread rather than block it, so they work the same way.
+use crate::channel::{StdBroadcast, StdBroadcastReceiver, StdChannel};
+use crate::net::{StdTcpListener, StdTcpStream, StdUdpSocket};
+use crate::rwlock::{StdLockWrapper, StdMutexWrapper};
+use crate::semaphore::StdSemaphoreWrapper;
+use crate::task::StdJoinHandle;
+use base::{
+    AsyncBarrier, AsyncChannel, AsyncFile, AsyncMutex, AsyncNotify, AsyncRwLock, AsyncSemaphore,
+    AsyncStream, AsyncTcpListener, AsyncTcpStream, AsyncUdpSocket, BarrierBox, BoxFuture,
+    Broadcast, BroadcastBox, BroadcastReceiver, BroadcastReceiverBox, CancelToken, CancelTokenBox,
+    ChannelBox, ChannelStream, Executor, FileBox, JoinHandle, JoinHandleBox, LocalBoxFuture,
+    LockBox, LockPolicy, Locker, MutexBox, NotifyBox, PolicyLockBox, Runtime, SemaphoreBox,
+    StdBarrier, StdCancelToken, StdClock, StdExecutor, StdFile, StdNotify, StdWatch,
+    StdWatchReceiver, StreamBox, TcpListenerBox, TcpStreamBox, UdpSocketBox, Watch, WatchBox,
+    WatchReceiver, WatchReceiverBox,
+};
+use implbox::{ImplBox, ImplBoxShared};
+use implbox_macros::implbox_impls;
+use std::fs::OpenOptions;
+use std::io;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::thread;
+
+pub mod channel;
+pub mod net;
+pub mod rwlock;
+pub mod semaphore;
+pub mod task;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Default, Clone)]
+pub struct StdRuntime;
+
+impl Locker for StdRuntime {
+    #[implbox_impls(LockBox<T>, StdLockWrapper<T>, downcast)]
+    fn new_lock<T: Sync + Send + 'static>(item: T) -> impl AsyncRwLock<T> {
+        StdLockWrapper::<T>::new(item)
+    }
+
+    /// This lock always prefers readers and has no reader limit, so
+    /// the policy is ignored.
+    #[implbox_impls(PolicyLockBox<T>, StdLockWrapper<T>, downcast)]
+    fn new_lock_with<T: Sync + Send + 'static>(
+        _policy: LockPolicy,
+        item: T,
+    ) -> impl AsyncRwLock<T> {
+        StdLockWrapper::<T>::new(item)
+    }
+
+    #[implbox_impls(MutexBox<T>, StdMutexWrapper<T>, downcast)]
+    fn new_mutex<T: Sync + Send + 'static>(item: T) -> impl AsyncMutex<T> {
+        StdMutexWrapper::<T>::new(item)
+    }
+
+    #[implbox_impls(SemaphoreBox, StdSemaphoreWrapper, downcast)]
+    fn new_semaphore(permits: usize) -> impl AsyncSemaphore {
+        StdSemaphoreWrapper::new(permits)
+    }
+
+    #[implbox_impls(NotifyBox, StdNotify, downcast)]
+    fn new_notify() -> impl AsyncNotify {
+        StdNotify::new()
+    }
+
+    #[implbox_impls(BarrierBox, StdBarrier, downcast)]
+    fn new_barrier(n: usize) -> impl AsyncBarrier {
+        StdBarrier::new(n)
+    }
+
+    #[implbox_impls(ChannelBox<T>, StdChannel<T>, downcast)]
+    fn new_channel<T: Send + 'static>(capacity: Option<usize>) -> impl AsyncChannel<T> {
+        StdChannel::<T>::new(capacity)
+    }
+
+    #[implbox_impls(BroadcastBox<T>, StdBroadcast<T>, downcast)]
+    fn new_broadcast<T: Clone + Sync + Send + 'static>(capacity: usize) -> impl Broadcast<T> {
+        StdBroadcast::<T>::new(capacity)
+    }
+
+    #[implbox_impls(BroadcastReceiverBox<T>, StdBroadcastReceiver<T>, downcast)]
+    fn new_broadcast_receiver<T: Clone + Sync + Send + 'static>(
+        broadcast: &ImplBox<BroadcastBox<T>>,
+    ) -> impl BroadcastReceiver<T> {
+        broadcast
+            .downcast_ref::<StdBroadcast<T>>()
+            .expect("broadcast was not created by StdRuntime")
+            .subscribe()
+    }
+
+    #[implbox_impls(WatchBox<T>, StdWatch<T>, downcast)]
+    fn new_watch<T: Clone + Sync + Send + 'static>(initial: T) -> impl Watch<T> {
+        StdWatch::<T>::new(initial)
+    }
+
+    #[implbox_impls(WatchReceiverBox<T>, StdWatchReceiver<T>, downcast)]
+    fn new_watch_receiver<T: Clone + Sync + Send + 'static>(
+        watch: &ImplBox<WatchBox<T>>,
+    ) -> impl WatchReceiver<T> {
+        watch
+            .downcast_ref::<StdWatch<T>>()
+            .expect("watch was not created by StdRuntime")
+            .subscribe()
+    }
+
+    #[implbox_impls(StreamBox<T>, ChannelStream<T, StdRuntime>, downcast)]
+    fn new_stream<T: Send + 'static>(channel: ImplBoxShared<ChannelBox<T>>) -> impl AsyncStream<T> {
+        ChannelStream::<T, Self>::new(channel)
+    }
+}
+
+impl Runtime for StdRuntime {
+    type Executor = StdExecutor;
+    type Clock = StdClock;
+
+    fn new_executor() -> io::Result<StdExecutor> {
+        Ok(StdExecutor)
+    }
+
+    fn clock() -> StdClock {
+        StdClock
+    }
+
+    /// The task runs on a new thread.
+    #[implbox_impls(JoinHandleBox<T>, StdJoinHandle<T>, downcast, name = "task")]
+    fn spawn<T: Send + 'static>(future: BoxFuture<'static, T>) -> impl JoinHandle<T> {
+        let (future, abort) = base::abortable(future);
+        StdJoinHandle::new(thread::spawn(move || StdExecutor.block_on(future)), abort)
+    }
+
+    /// The future can't leave this thread, so it runs to completion before
+    /// this returns.
+    #[implbox_impls(JoinHandleBox<T>, StdJoinHandle<T>, downcast, name = "local_task")]
+    fn spawn_local<T: Send + 'static>(future: LocalBoxFuture<'static, T>) -> impl JoinHandle<T> {
+        let (future, abort) = base::abortable(future);
+        StdJoinHandle::finished(StdExecutor.block_on(future), abort)
+    }
+
+    #[implbox_impls(CancelTokenBox, StdCancelToken, downcast)]
+    fn new_cancel_token(parent: Option<&ImplBox<CancelTokenBox>>) -> impl CancelToken + Clone {
+        match parent {
+            Some(parent) => parent
+                .downcast_ref::<StdCancelToken>()
+                .expect("cancel token was not created by StdRuntime")
+                .child(),
+            None => StdCancelToken::default(),
+        }
+    }
+
+    #[implbox_impls(TcpStreamBox, StdTcpStream, downcast, name = "tcp_stream")]
+    async fn connect_tcp(addr: SocketAddr) -> Result<impl AsyncTcpStream, io::Error> {
+        StdTcpStream::connect(addr)
+    }
+
+    #[implbox_impls(TcpListenerBox, StdTcpListener, downcast, name = "tcp_listener")]
+    async fn bind_tcp(addr: SocketAddr) -> Result<impl AsyncTcpListener, io::Error> {
+        StdTcpListener::bind(addr)
+    }
+
+    #[implbox_impls(UdpSocketBox, StdUdpSocket, downcast, name = "udp_socket")]
+    async fn bind_udp(addr: SocketAddr) -> Result<impl AsyncUdpSocket, io::Error> {
+        StdUdpSocket::bind(addr)
+    }
+
+    #[implbox_impls(FileBox, StdFile, downcast, name = "file")]
+    async fn open_file(path: &Path, options: &OpenOptions) -> Result<impl AsyncFile, io::Error> {
+        StdFile::open(path, options)
+    }
+}
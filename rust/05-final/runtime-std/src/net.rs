@@ -0,0 +1,78 @@
+use base::{AsyncTcpListener, AsyncTcpStream, AsyncUdpSocket};
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, UdpSocket};
+
+/// std's blocking sockets. Each call blocks the thread until the system
+/// call returns.
+pub struct StdTcpStream(TcpStream);
+
+impl StdTcpStream {
+    pub fn connect(addr: SocketAddr) -> io::Result<Self> {
+        Ok(StdTcpStream(TcpStream::connect(addr)?))
+    }
+}
+
+impl AsyncTcpStream for StdTcpStream {
+    async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+
+    async fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    async fn shutdown(&mut self) -> io::Result<()> {
+        self.0.shutdown(Shutdown::Write)
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.0.local_addr()
+    }
+
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.0.peer_addr()
+    }
+}
+
+pub struct StdTcpListener(TcpListener);
+
+impl StdTcpListener {
+    pub fn bind(addr: SocketAddr) -> io::Result<Self> {
+        Ok(StdTcpListener(TcpListener::bind(addr)?))
+    }
+}
+
+impl AsyncTcpListener for StdTcpListener {
+    async fn accept(
+        &self,
+    ) -> io::Result<(impl AsyncTcpStream + Send + Sync + 'static, SocketAddr)> {
+        let (stream, addr) = self.0.accept()?;
+        Ok((StdTcpStream(stream), addr))
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.0.local_addr()
+    }
+}
+
+pub struct StdUdpSocket(UdpSocket);
+
+impl StdUdpSocket {
+    pub fn bind(addr: SocketAddr) -> io::Result<Self> {
+        Ok(StdUdpSocket(UdpSocket::bind(addr)?))
+    }
+}
+
+impl AsyncUdpSocket for StdUdpSocket {
+    async fn send_to(&self, buf: &[u8], target: SocketAddr) -> io::Result<usize> {
+        self.0.send_to(buf, target)
+    }
+
+    async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.0.recv_from(buf)
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.0.local_addr()
+    }
+}
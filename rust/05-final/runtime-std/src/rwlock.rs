@@ -0,0 +1,374 @@
+use base::{AsyncMutex, AsyncRwLock, Elapsed, Upgradable};
+use std::cell::UnsafeCell;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+#[derive(Clone, Copy)]
+enum Access {
+    Read,
+    Write,
+    Upgradable,
+    /// Turn the upgradable read into a write
+    Upgrade,
+}
+
+#[derive(Default)]
+struct LockState {
+    readers: usize,
+    writer: bool,
+    upgradable: bool,
+}
+
+impl LockState {
+    fn available(&self, access: Access) -> bool {
+        match access {
+            Access::Read => !self.writer,
+            Access::Write => !self.writer && !self.upgradable && self.readers == 0,
+            Access::Upgradable => !self.writer && !self.upgradable,
+            Access::Upgrade => self.readers == 0,
+        }
+    }
+
+    fn take(&mut self, access: Access) {
+        match access {
+            Access::Read => self.readers += 1,
+            Access::Write => self.writer = true,
+            Access::Upgradable => self.upgradable = true,
+            Access::Upgrade => {
+                self.upgradable = false;
+                self.writer = true;
+            }
+        }
+    }
+
+    fn release(&mut self, access: Access) {
+        match access {
+            Access::Read => self.readers -= 1,
+            Access::Write | Access::Upgrade => self.writer = false,
+            Access::Upgradable => self.upgradable = false,
+        }
+    }
+}
+
+/// The state of a lock and the item it protects. The state says which
+/// guards exist, and a guard only gives access to the item in the way
+/// the state allows.
+struct Inner<T> {
+    state: Mutex<LockState>,
+    released: Condvar,
+    item: UnsafeCell<T>,
+}
+
+// SAFETY: Access to the item is controlled by the state, as for
+// std::sync::RwLock.
+unsafe impl<T: Send + Sync> Sync for Inner<T> {}
+
+impl<T> Inner<T> {
+    /// Block until `access` is possible, or until `timeout` has passed.
+    fn lock(&self, access: Access, timeout: Option<Duration>) -> Result<(), Elapsed> {
+        let state = self.state.lock().unwrap();
+        let blocked = |s: &mut LockState| !s.available(access);
+        let mut state = match timeout {
+            None => self.released.wait_while(state, blocked).unwrap(),
+            Some(timeout) => {
+                let (state, result) = self
+                    .released
+                    .wait_timeout_while(state, timeout, blocked)
+                    .unwrap();
+                if result.timed_out() {
+                    return Err(Elapsed);
+                }
+                state
+            }
+        };
+        state.take(access);
+        Ok(())
+    }
+
+    fn try_lock(&self, access: Access) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let available = state.available(access);
+        if available {
+            state.take(access);
+        }
+        available
+    }
+
+    fn unlock(&self, access: Access) {
+        self.state.lock().unwrap().release(access);
+        self.released.notify_all();
+    }
+
+    /// SAFETY: The caller must hold a guard that allows reading.
+    unsafe fn item(&self) -> &T {
+        &*self.item.get()
+    }
+
+    /// SAFETY: The caller must hold a guard that allows writing, and this
+    /// must be the only reference to the item.
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn item_mut(&self) -> &mut T {
+        &mut *self.item.get()
+    }
+}
+
+/// A reader-writer lock that blocks the thread while it waits, so its
+/// futures are always ready when first polled. std's RwLock can't be used
+/// directly since its guards can't be sent between threads, which
+/// [AsyncRwLock] requires, so this is built from std's Mutex and Condvar.
+/// It doesn't prefer writers, so a steady stream of readers can keep a
+/// writer waiting.
+pub struct StdLockWrapper<T> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T: Default> Default for StdLockWrapper<T> {
+    fn default() -> Self {
+        Self::with_item(T::default())
+    }
+}
+
+impl<T> StdLockWrapper<T> {
+    fn with_item(item: T) -> Self {
+        StdLockWrapper {
+            inner: Arc::new(Inner {
+                state: Default::default(),
+                released: Condvar::new(),
+                item: UnsafeCell::new(item),
+            }),
+        }
+    }
+
+    fn read_guard(&self) -> StdReadGuard<'_, T> {
+        StdReadGuard { inner: &self.inner }
+    }
+
+    fn write_guard(&self) -> StdWriteGuard<'_, T> {
+        StdWriteGuard { inner: &self.inner }
+    }
+}
+
+pub struct StdReadGuard<'a, T> {
+    inner: &'a Inner<T>,
+}
+
+impl<T> Deref for StdReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: This guard holds a read lock.
+        unsafe { self.inner.item() }
+    }
+}
+
+impl<T> Drop for StdReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.inner.unlock(Access::Read);
+    }
+}
+
+pub struct StdWriteGuard<'a, T> {
+    inner: &'a Inner<T>,
+}
+
+impl<T> Deref for StdWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: This guard holds the write lock.
+        unsafe { self.inner.item() }
+    }
+}
+
+impl<T> DerefMut for StdWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: This guard holds the write lock, and `&mut self` makes
+        // this the only reference.
+        unsafe { self.inner.item_mut() }
+    }
+}
+
+impl<T> Drop for StdWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.inner.unlock(Access::Write);
+    }
+}
+
+/// The guard returned by [AsyncRwLock::upgradable_read]
+pub struct StdUpgradableReadGuard<'a, T> {
+    inner: &'a Inner<T>,
+}
+
+impl<T> Deref for StdUpgradableReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: This guard holds the upgradable read lock.
+        unsafe { self.inner.item() }
+    }
+}
+
+impl<T> Drop for StdUpgradableReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.inner.unlock(Access::Upgradable);
+    }
+}
+
+impl<'a, T: Sync + Send + 'a> Upgradable<'a, T> for StdUpgradableReadGuard<'a, T> {
+    type WriteGuard = StdWriteGuard<'a, T>;
+
+    async fn upgrade(self) -> StdWriteGuard<'a, T> {
+        let inner = self.inner;
+        // The upgradable lock becomes the write lock, so it must not be
+        // released.
+        mem::forget(self);
+        inner.lock(Access::Upgrade, None).unwrap();
+        StdWriteGuard { inner }
+    }
+}
+
+pub struct StdOwnedReadGuard<T> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T> Deref for StdOwnedReadGuard<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: This guard holds a read lock.
+        unsafe { self.inner.item() }
+    }
+}
+
+impl<T> Drop for StdOwnedReadGuard<T> {
+    fn drop(&mut self) {
+        self.inner.unlock(Access::Read);
+    }
+}
+
+pub struct StdOwnedWriteGuard<T> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T> Deref for StdOwnedWriteGuard<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: This guard holds the write lock.
+        unsafe { self.inner.item() }
+    }
+}
+
+impl<T> DerefMut for StdOwnedWriteGuard<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: As for StdWriteGuard.
+        unsafe { self.inner.item_mut() }
+    }
+}
+
+impl<T> Drop for StdOwnedWriteGuard<T> {
+    fn drop(&mut self) {
+        self.inner.unlock(Access::Write);
+    }
+}
+
+impl<T: Sync + Send + 'static> AsyncRwLock<T> for StdLockWrapper<T> {
+    type ReadGuard<'a>
+        = StdReadGuard<'a, T>
+    where
+        Self: 'a;
+    type WriteGuard<'a>
+        = StdWriteGuard<'a, T>
+    where
+        Self: 'a;
+    type UpgradableReadGuard<'a>
+        = StdUpgradableReadGuard<'a, T>
+    where
+        Self: 'a;
+    type OwnedReadGuard = StdOwnedReadGuard<T>;
+    type OwnedWriteGuard = StdOwnedWriteGuard<T>;
+
+    fn new(item: T) -> Self {
+        Self::with_item(item)
+    }
+
+    async fn read(&self) -> StdReadGuard<'_, T> {
+        self.blocking_read()
+    }
+
+    async fn write(&self) -> StdWriteGuard<'_, T> {
+        self.blocking_write()
+    }
+
+    async fn upgradable_read(&self) -> StdUpgradableReadGuard<'_, T> {
+        self.inner.lock(Access::Upgradable, None).unwrap();
+        StdUpgradableReadGuard { inner: &self.inner }
+    }
+
+    async fn read_owned(&self) -> StdOwnedReadGuard<T> {
+        self.inner.lock(Access::Read, None).unwrap();
+        StdOwnedReadGuard {
+            inner: self.inner.clone(),
+        }
+    }
+
+    async fn write_owned(&self) -> StdOwnedWriteGuard<T> {
+        self.inner.lock(Access::Write, None).unwrap();
+        StdOwnedWriteGuard {
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// Every function of this lock blocks, so this is the same as
+    /// [AsyncRwLock::read], and is safe to call from async code.
+    fn blocking_read(&self) -> StdReadGuard<'_, T> {
+        self.inner.lock(Access::Read, None).unwrap();
+        self.read_guard()
+    }
+
+    fn blocking_write(&self) -> StdWriteGuard<'_, T> {
+        self.inner.lock(Access::Write, None).unwrap();
+        self.write_guard()
+    }
+
+    fn try_read(&self) -> Option<StdReadGuard<'_, T>> {
+        self.inner.try_lock(Access::Read).then(|| self.read_guard())
+    }
+
+    fn try_write(&self) -> Option<StdWriteGuard<'_, T>> {
+        self.inner
+            .try_lock(Access::Write)
+            .then(|| self.write_guard())
+    }
+
+    async fn read_timeout(&self, duration: Duration) -> Result<StdReadGuard<'_, T>, Elapsed> {
+        self.inner.lock(Access::Read, Some(duration))?;
+        Ok(self.read_guard())
+    }
+
+    async fn write_timeout(&self, duration: Duration) -> Result<StdWriteGuard<'_, T>, Elapsed> {
+        self.inner.lock(Access::Write, Some(duration))?;
+        Ok(self.write_guard())
+    }
+}
+
+/// A mutex is a lock that is only ever locked for writing.
+#[derive(Default)]
+pub struct StdMutexWrapper<T>(StdLockWrapper<T>);
+
+impl<T: Sync + Send + 'static> AsyncMutex<T> for StdMutexWrapper<T> {
+    type Guard<'a>
+        = StdWriteGuard<'a, T>
+    where
+        Self: 'a;
+
+    fn new(item: T) -> Self {
+        StdMutexWrapper(StdLockWrapper::with_item(item))
+    }
+
+    async fn lock(&self) -> StdWriteGuard<'_, T> {
+        self.0.blocking_write()
+    }
+}
@@ -0,0 +1,50 @@
+use base::AsyncSemaphore;
+use std::sync::{Condvar, Mutex};
+
+/// A semaphore that blocks the thread until a permit is available
+pub struct StdSemaphoreWrapper {
+    permits: Mutex<usize>,
+    released: Condvar,
+}
+
+pub struct StdPermit<'a> {
+    semaphore: &'a StdSemaphoreWrapper,
+}
+
+impl Drop for StdPermit<'_> {
+    fn drop(&mut self) {
+        *self.semaphore.permits.lock().unwrap() += 1;
+        self.semaphore.released.notify_one();
+    }
+}
+
+impl AsyncSemaphore for StdSemaphoreWrapper {
+    type Permit<'a> = StdPermit<'a>;
+
+    fn new(permits: usize) -> Self {
+        StdSemaphoreWrapper {
+            permits: Mutex::new(permits),
+            released: Condvar::new(),
+        }
+    }
+
+    async fn acquire(&self) -> StdPermit<'_> {
+        let permits = self.permits.lock().unwrap();
+        let mut permits = self.released.wait_while(permits, |p| *p == 0).unwrap();
+        *permits -= 1;
+        StdPermit { semaphore: self }
+    }
+
+    fn try_acquire(&self) -> Option<StdPermit<'_>> {
+        let mut permits = self.permits.lock().unwrap();
+        if *permits == 0 {
+            return None;
+        }
+        *permits -= 1;
+        Some(StdPermit { semaphore: self })
+    }
+
+    fn permits(&self) -> usize {
+        *self.permits.lock().unwrap()
+    }
+}
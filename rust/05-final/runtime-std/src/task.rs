@@ -0,0 +1,53 @@
+use base::{AbortHandle, JoinError, JoinHandle};
+use std::mem;
+use std::thread;
+
+enum TaskState<T> {
+    Running(thread::JoinHandle<Result<T, JoinError>>),
+    Finished(Result<T, JoinError>),
+    Joined,
+}
+
+/// A handle to a task that runs on its own thread, or, for a local task,
+/// that already ran to completion
+pub struct StdJoinHandle<T> {
+    state: TaskState<T>,
+    abort: AbortHandle,
+}
+
+impl<T> StdJoinHandle<T> {
+    pub fn new(thread: thread::JoinHandle<Result<T, JoinError>>, abort: AbortHandle) -> Self {
+        Self {
+            state: TaskState::Running(thread),
+            abort,
+        }
+    }
+
+    pub fn finished(output: Result<T, JoinError>, abort: AbortHandle) -> Self {
+        Self {
+            state: TaskState::Finished(output),
+            abort,
+        }
+    }
+}
+
+impl<T: Send + 'static> JoinHandle<T> for StdJoinHandle<T> {
+    /// This blocks the thread until the task's thread exits.
+    async fn join(&mut self) -> Result<T, JoinError> {
+        match mem::replace(&mut self.state, TaskState::Joined) {
+            // The task's panics are caught by base::abortable, so the
+            // thread itself only panics if something outside the task did.
+            TaskState::Running(thread) => thread.join().unwrap_or(Err(JoinError::Panicked)),
+            TaskState::Finished(output) => output,
+            TaskState::Joined => panic!("task was already joined"),
+        }
+    }
+
+    fn abort(&self) {
+        self.abort.abort()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.abort.is_finished()
+    }
+}
@@ -0,0 +1,341 @@
+use super::*;
+use base::{Clock, Elapsed, Executor, JoinError, RecvError, SendError, TryRecvError, Upgradable};
+use std::future::{pending, Future};
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::sync::Arc;
+use std::time::Duration;
+
+type R = StdRuntime;
+
+fn run<F: Future>(future: F) -> F::Output {
+    R::new_executor().unwrap().block_on(future)
+}
+
+fn localhost() -> SocketAddr {
+    SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0).into()
+}
+
+#[test]
+fn test_lock() {
+    run(async {
+        let l = R::box_lock(3);
+        let lock = R::unbox_lock(&l);
+        {
+            let r1 = lock.read().await;
+            let r2 = lock.try_read().unwrap();
+            assert_eq!(*r1 + *r2, 6);
+            assert!(lock.try_write().is_none());
+            assert_eq!(
+                lock.write_timeout(Duration::from_millis(10)).await.err(),
+                Some(Elapsed)
+            );
+        }
+        *lock.write().await += 1;
+        let owned = lock.read_owned().await;
+        drop(l);
+        // The owned guard keeps the item alive.
+        assert_eq!(*owned, 4);
+    })
+}
+
+#[test]
+fn test_upgrade() {
+    run(async {
+        let lock = Arc::new(R::new_lock(1));
+        let upgradable = lock.upgradable_read().await;
+        // Plain readers can still share the lock.
+        assert_eq!(*lock.read().await, 1);
+        assert!(lock.try_write().is_none());
+        let mut w = upgradable.upgrade().await;
+        *w = 2;
+        drop(w);
+        assert_eq!(*lock.blocking_read(), 2);
+        let mut w = lock.write_owned().await;
+        *w = 3;
+        drop(w);
+        assert_eq!(*lock.read_timeout(Duration::from_secs(1)).await.unwrap(), 3);
+    })
+}
+
+#[test]
+fn test_mutex_and_semaphore() {
+    run(async {
+        let m = R::new_mutex(String::new());
+        m.lock().await.push_str("potato");
+        assert_eq!(*m.lock().await, "potato");
+
+        let s = R::new_semaphore(2);
+        let p1 = s.acquire().await;
+        let p2 = s.try_acquire().unwrap();
+        assert_eq!(s.permits(), 0);
+        assert!(s.try_acquire().is_none());
+        drop(p1);
+        assert_eq!(s.permits(), 1);
+        drop(p2);
+        assert_eq!(s.permits(), 2);
+    })
+}
+
+#[test]
+fn test_notify() {
+    run(async {
+        let n = Arc::new(R::new_notify());
+        // A notification with no waiter is kept for the next one.
+        n.notify_one();
+        n.notified().await;
+        let mut waiting = {
+            let n = n.clone();
+            R::spawn(Box::pin(async move { n.notified().await }))
+        };
+        std::thread::sleep(Duration::from_millis(10));
+        n.notify_one();
+        waiting.join().await.unwrap();
+        // Waiting starts when the future is created.
+        let notified = n.notified();
+        n.notify_waiters();
+        notified.await;
+    })
+}
+
+#[test]
+fn test_barrier() {
+    run(async {
+        let b = Arc::new(R::new_barrier(3));
+        let handles: Vec<_> = (0..3)
+            .map(|_| {
+                let b = b.clone();
+                R::spawn(Box::pin(async move { b.wait().await }))
+            })
+            .collect();
+        let mut leaders = 0;
+        for mut h in handles {
+            leaders += h.join().await.unwrap() as usize;
+        }
+        assert_eq!(leaders, 1);
+    })
+}
+
+#[test]
+fn test_channel() {
+    run(async {
+        let c = R::new_channel(Some(1));
+        c.send(1).await.unwrap();
+        assert_eq!(c.try_send(2), Err(SendError::Full(2)));
+        c.close();
+        assert!(c.is_closed());
+        assert_eq!(c.send(3).await, Err(SendError::Closed(3)));
+        assert_eq!(c.recv().await, Some(1));
+        assert_eq!(c.recv().await, None);
+        assert_eq!(c.try_recv(), Err(TryRecvError::Closed));
+    })
+}
+
+#[test]
+fn test_blocking() {
+    run(async {
+        // Waiting blocks the thread, so the other side must be on another
+        // thread.
+        let c = Arc::new(R::new_channel(Some(1)));
+        let lock = Arc::new(R::new_lock(0));
+        let guard = lock.write().await;
+        let mut producer = {
+            let c = c.clone();
+            let lock = lock.clone();
+            R::spawn(Box::pin(async move {
+                for i in 0..3 {
+                    c.send(i).await.unwrap();
+                }
+                c.close();
+                *lock.read().await
+            }))
+        };
+        assert_eq!(c.recv().await, Some(0));
+        assert_eq!(c.recv().await, Some(1));
+        assert_eq!(c.recv().await, Some(2));
+        assert_eq!(c.recv().await, None);
+        assert!(!producer.is_finished());
+        drop(guard);
+        assert_eq!(producer.join().await, Ok(0));
+    })
+}
+
+#[test]
+fn test_stream() {
+    run(async {
+        let c = R::box_shared_channel(None);
+        let mut s = R::box_stream(c.clone());
+        let tx = R::unbox_channel(&c);
+        tx.send("potato").await.unwrap();
+        tx.send("salad").await.unwrap();
+        tx.close();
+        let s = R::unbox_mut_stream(&mut s);
+        assert_eq!(s.next().await, Some("potato"));
+        assert_eq!(s.next().await, Some("salad"));
+        assert_eq!(s.next().await, None);
+    })
+}
+
+#[test]
+fn test_broadcast() {
+    run(async {
+        let b = R::box_broadcast(2);
+        let tx = R::unbox_broadcast(&b);
+        // Sending without receivers is not an error.
+        assert_eq!(tx.send(0), 0);
+        let mut rx = R::box_broadcast_receiver(&b);
+        let rx = R::unbox_mut_broadcast_receiver(&mut rx);
+        assert_eq!(tx.receiver_count(), 1);
+        for i in 1..=3 {
+            assert_eq!(tx.send(i), 1);
+        }
+        assert_eq!(rx.recv().await, Err(RecvError::Lagged(1)));
+        assert_eq!(rx.recv().await, Ok(2));
+        assert_eq!(rx.try_recv(), Ok(Some(3)));
+        assert_eq!(rx.try_recv(), Ok(None));
+        drop(b);
+        assert_eq!(rx.recv().await, Err(RecvError::Closed));
+    })
+}
+
+#[test]
+fn test_watch() {
+    run(async {
+        let w = R::box_watch(1);
+        let mut boxed = R::box_watch_receiver(&w);
+        let rx = R::unbox_mut_watch_receiver(&mut boxed);
+        let tx = R::unbox_watch(&w);
+        assert_eq!(tx.receiver_count(), 1);
+        tx.send(2);
+        tx.send(3);
+        // Only the latest value is seen.
+        rx.changed().await.unwrap();
+        assert_eq!(rx.get(), 3);
+        tx.send(4);
+        assert_eq!(tx.get(), 4);
+        drop(w);
+        rx.changed().await.unwrap();
+        assert_eq!(rx.get(), 4);
+        assert_eq!(rx.changed().await, Err(RecvError::Closed));
+    })
+}
+
+#[test]
+fn test_cancel() {
+    run(async {
+        let parent = R::box_cancel_token(None);
+        let child = R::box_cancel_token(Some(&parent));
+        let mut waiting = {
+            let child = child.try_clone().unwrap();
+            R::spawn(Box::pin(async move {
+                R::unbox_cancel_token(&child).cancelled().await
+            }))
+        };
+        assert!(!R::unbox_cancel_token(&child).is_cancelled());
+        R::unbox_cancel_token(&parent).cancel();
+        waiting.join().await.unwrap();
+        assert!(R::unbox_cancel_token(&child).is_cancelled());
+        // A child of a cancelled token starts out cancelled.
+        let grandchild = R::new_cancel_token(Some(&child));
+        assert!(grandchild.is_cancelled());
+    })
+}
+
+#[test]
+fn test_spawn() {
+    run(async {
+        let mut h = R::box_task(Box::pin(async { 5 }));
+        assert_eq!(R::unbox_mut_task(&mut h).join().await, Ok(5));
+        assert!(R::unbox_task(&h).is_finished());
+
+        let mut h = R::spawn(Box::pin(pending::<()>()));
+        h.abort();
+        assert_eq!(h.join().await, Err(JoinError::Cancelled));
+
+        let mut h = R::spawn(Box::pin(async { panic!("potato") }));
+        assert_eq!(h.join().await, Err::<(), _>(JoinError::Panicked));
+
+        let value = std::rc::Rc::new(6);
+        let mut h = R::spawn_local(Box::pin(async move { *value }));
+        assert_eq!(h.join().await, Ok(6));
+    })
+}
+
+#[test]
+fn test_timeout() {
+    run(async {
+        let d = Duration::from_millis(20);
+        assert_eq!(R::timeout(d, async { 5 }).await, Ok(5));
+        assert_eq!(R::timeout(d, pending::<()>()).await, Err(Elapsed));
+        let clock = R::clock();
+        let start = clock.now();
+        clock.sleep(d).await;
+        assert!(clock.now() - start >= d);
+    })
+}
+
+#[test]
+fn test_tcp() {
+    run(async {
+        let listener = R::bind_tcp(localhost()).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut server = R::spawn(Box::pin(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 6];
+            let n = stream.read(&mut buf).await.unwrap();
+            stream.write(&buf[..n]).await.unwrap();
+            stream.shutdown().await.unwrap();
+        }));
+        let mut client = R::connect_tcp(addr).await.unwrap();
+        assert_eq!(client.peer_addr().unwrap(), addr);
+        client.write(b"potato").await.unwrap();
+        let mut buf = [0; 6];
+        let mut n = 0;
+        while n < buf.len() {
+            n += client.read(&mut buf[n..]).await.unwrap();
+        }
+        assert_eq!(&buf, b"potato");
+        assert_eq!(client.read(&mut buf).await.unwrap(), 0);
+        server.join().await.unwrap();
+    })
+}
+
+#[test]
+fn test_udp() {
+    run(async {
+        let a = R::bind_udp(localhost()).await.unwrap();
+        let b = R::bind_udp(localhost()).await.unwrap();
+        a.send_to(b"salad", b.local_addr().unwrap()).await.unwrap();
+        let mut buf = [0; 16];
+        let (n, from) = b.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"salad");
+        assert_eq!(from, a.local_addr().unwrap());
+    })
+}
+
+#[test]
+fn test_file() {
+    run(async {
+        let path = std::env::temp_dir().join(format!("runtime-std-{}", std::process::id()));
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        let mut f = R::open_file(&path, &options).await.unwrap();
+        f.write(b"potato salad").await.unwrap();
+        f.sync().await.unwrap();
+        drop(f);
+        let mut options = OpenOptions::new();
+        options.read(true);
+        let mut f = R::open_file(&path, &options).await.unwrap();
+        let mut buf = [0; 32];
+        let n = f.read(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"potato salad");
+        drop(f);
+        std::fs::remove_file(&path).unwrap();
+    })
+}
+
+#[test]
+fn test_executor() {
+    let e = R::new_executor().unwrap();
+    let mut h = e.block_on(async { R::spawn(Box::pin(async { 7 })) });
+    assert_eq!(e.block_on(h.join()), Ok(7));
+}
@@ -0,0 +1,187 @@
+use crate::time::TestRuntimeClock;
+use base::{Executor, LocalBoxFuture};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet};
+use std::future::Future;
+use std::pin::pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+/// The id of the future passed to [TestExecutor::block_on]
+const MAIN: u64 = 0;
+
+/// The ids of the tasks that have been woken. Wakers may be called from
+/// any thread, so this is shared with them.
+struct Ready {
+    ids: Mutex<BTreeSet<u64>>,
+    thread: Thread,
+}
+
+impl Ready {
+    fn insert(&self, id: u64) {
+        self.ids.lock().unwrap().insert(id);
+        self.thread.unpark();
+    }
+}
+
+struct TaskWaker {
+    id: u64,
+    ready: Arc<Ready>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.ready.insert(self.id);
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.ready.insert(self.id);
+    }
+}
+
+struct Scheduler {
+    tasks: RefCell<BTreeMap<u64, LocalBoxFuture<'static, ()>>>,
+    next_id: Cell<u64>,
+    ready: Arc<Ready>,
+}
+
+thread_local! {
+    /// The scheduler of the [TestExecutor::block_on] that is running on
+    /// this thread
+    static CURRENT: RefCell<Option<Rc<Scheduler>>> = const { RefCell::new(None) };
+}
+
+/// Makes a scheduler current for as long as it exists. When it is
+/// dropped, tasks that haven't finished are dropped too.
+struct Enter(Rc<Scheduler>);
+
+impl Enter {
+    fn new(scheduler: Rc<Scheduler>) -> Self {
+        CURRENT.with(|current| {
+            let mut current = current.borrow_mut();
+            assert!(
+                current.is_none(),
+                "TestExecutor::block_on can't be called from a task"
+            );
+            *current = Some(scheduler.clone());
+        });
+        Enter(scheduler)
+    }
+}
+
+impl Drop for Enter {
+    fn drop(&mut self) {
+        // A task's destructor may spawn, so the scheduler stays current
+        // until the tasks are gone.
+        drop(std::mem::take(&mut *self.0.tasks.borrow_mut()));
+        CURRENT.with(|current| current.borrow_mut().take());
+    }
+}
+
+/// Start running `task` in the [TestExecutor::block_on] on this thread.
+pub(crate) fn spawn(task: LocalBoxFuture<'static, ()>) {
+    CURRENT.with(|current| {
+        let current = current.borrow();
+        let scheduler = current
+            .as_ref()
+            .expect("TestRuntime tasks can only be spawned inside TestExecutor::block_on");
+        let id = scheduler.next_id.get();
+        scheduler.next_id.set(id + 1);
+        scheduler.tasks.borrow_mut().insert(id, task);
+        scheduler.ready.insert(id);
+    })
+}
+
+/// SplitMix64, which is enough to pick tasks and keeps the sequence the
+/// same on every platform and release
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// Runs futures on the current thread with a deterministic scheduler.
+/// Whenever more than one task is ready, the seed decides which one is
+/// polled next, and only one is polled at a time, so a seed always
+/// produces the same interleaving of tasks that don't wait for anything
+/// outside the executor. When no task is ready, the thread's
+/// [TestRuntimeClock] jumps to the next deadline. Tasks run only while
+/// `block_on` is running, and those that haven't finished when it
+/// returns are dropped.
+pub struct TestExecutor {
+    seed: u64,
+}
+
+impl TestExecutor {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl Executor for TestExecutor {
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        let scheduler = Rc::new(Scheduler {
+            tasks: RefCell::new(BTreeMap::new()),
+            next_id: Cell::new(MAIN + 1),
+            ready: Arc::new(Ready {
+                ids: Mutex::new(BTreeSet::from([MAIN])),
+                thread: thread::current(),
+            }),
+        });
+        let _enter = Enter::new(scheduler.clone());
+        let clock = TestRuntimeClock::current();
+        let mut rng = Rng(self.seed);
+        let mut future = pin!(future);
+        loop {
+            let next = {
+                let mut ids = scheduler.ready.ids.lock().unwrap();
+                match ids.len() as u64 {
+                    0 => None,
+                    n => {
+                        let id = *ids.iter().nth((rng.next() % n) as usize).unwrap();
+                        ids.remove(&id);
+                        Some(id)
+                    }
+                }
+            };
+            let Some(id) = next else {
+                if !clock.advance_to_next() {
+                    // Only something outside the executor, such as
+                    // blocking I/O on another thread, can wake a task.
+                    thread::park();
+                }
+                continue;
+            };
+            let waker = Waker::from(Arc::new(TaskWaker {
+                id,
+                ready: scheduler.ready.clone(),
+            }));
+            let mut cx = Context::from_waker(&waker);
+            if id == MAIN {
+                if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                    return output;
+                }
+                continue;
+            }
+            // The task is taken out while it runs so that it can spawn.
+            let task = scheduler.tasks.borrow_mut().remove(&id);
+            if let Some(mut task) = task {
+                if task.as_mut().poll(&mut cx).is_pending() {
+                    scheduler.tasks.borrow_mut().insert(id, task);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,210 @@
+//! An implementation of the [base] runtime traits for tests that need to
+//! reproduce a race exactly. Everything runs on one thread under
+//! [TestExecutor], which picks the next task to poll with a seeded random
+//! number generator, so a seed always gives the same interleaving, and
+//! different seeds explore different ones. Time is virtual; see
+//! [TestRuntimeClock]. A failing seed can be replayed by passing it to
+//! [TestExecutor::new], or to [Runtime::new_executor] through the
+//! `RUNTIME_TEST_SEED` environment variable.
+//!
+//! Locks, semaphores, barriers, and channels come from async-lock,
+//! async-channel, and async-broadcast, which need no executor, with
+//! timeouts on the virtual clock. Notify, watch, and cancel token are the
+//! executor-independent ones from [base]. Sockets are runtime-std's,
+//! which block the thread, and files are [base::StdFile], so I/O is
+//! deterministic only as far as the other end is.
+use crate::barrier::TestBarrier;
+use crate::broadcast::{TestBroadcast, TestBroadcastReceiver};
+use crate::channel::TestChannel;
+use crate::mutex::TestMutexWrapper;
+use crate::rwlock::TestLockWrapper;
+use crate::semaphore::TestSemaphoreWrapper;
+use crate::task::TestJoinHandle;
+use base::{
+    AsyncBarrier, AsyncChannel, AsyncFile, AsyncMutex, AsyncNotify, AsyncRwLock, AsyncSemaphore,
+    AsyncStream, AsyncTcpListener, AsyncTcpStream, AsyncUdpSocket, BarrierBox, BoxFuture,
+    Broadcast, BroadcastBox, BroadcastReceiver, BroadcastReceiverBox, CancelToken, CancelTokenBox,
+    ChannelBox, ChannelStream, Elapsed, FileBox, JoinHandle, JoinHandleBox, LocalBoxFuture,
+    LockBox, LockPolicy, Locker, MutexBox, NotifyBox, PolicyLockBox, Runtime, SemaphoreBox,
+    StdCancelToken, StdFile, StdNotify, StdWatch, StdWatchReceiver, StreamBox, TcpListenerBox,
+    TcpStreamBox, UdpSocketBox, Watch, WatchBox, WatchReceiver, WatchReceiverBox,
+};
+use implbox::{ImplBox, ImplBoxShared};
+use implbox_macros::implbox_impls;
+use runtime_std::net::{StdTcpListener, StdTcpStream, StdUdpSocket};
+use std::fs::OpenOptions;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::Duration;
+
+pub mod barrier;
+pub mod broadcast;
+pub mod channel;
+pub mod executor;
+pub mod mutex;
+pub mod rwlock;
+pub mod semaphore;
+pub mod task;
+pub mod time;
+
+pub use executor::TestExecutor;
+pub use time::TestRuntimeClock;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Default, Clone)]
+pub struct TestRuntime;
+
+impl Locker for TestRuntime {
+    #[implbox_impls(LockBox<T>, TestLockWrapper<T>, downcast)]
+    fn new_lock<T: Sync + Send + 'static>(item: T) -> impl AsyncRwLock<T> {
+        TestLockWrapper::<T>::new(item)
+    }
+
+    /// async-lock's lock always prefers writers and has no reader
+    /// limit, so the policy is ignored.
+    #[implbox_impls(PolicyLockBox<T>, TestLockWrapper<T>, downcast)]
+    fn new_lock_with<T: Sync + Send + 'static>(
+        _policy: LockPolicy,
+        item: T,
+    ) -> impl AsyncRwLock<T> {
+        TestLockWrapper::<T>::new(item)
+    }
+
+    #[implbox_impls(MutexBox<T>, TestMutexWrapper<T>, downcast)]
+    fn new_mutex<T: Sync + Send + 'static>(item: T) -> impl AsyncMutex<T> {
+        TestMutexWrapper::<T>::new(item)
+    }
+
+    #[implbox_impls(SemaphoreBox, TestSemaphoreWrapper, downcast)]
+    fn new_semaphore(permits: usize) -> impl AsyncSemaphore {
+        TestSemaphoreWrapper::new(permits)
+    }
+
+    #[implbox_impls(NotifyBox, StdNotify, downcast)]
+    fn new_notify() -> impl AsyncNotify {
+        StdNotify::new()
+    }
+
+    #[implbox_impls(BarrierBox, TestBarrier, downcast)]
+    fn new_barrier(n: usize) -> impl AsyncBarrier {
+        TestBarrier::new(n)
+    }
+
+    #[implbox_impls(ChannelBox<T>, TestChannel<T>, downcast)]
+    fn new_channel<T: Send + 'static>(capacity: Option<usize>) -> impl AsyncChannel<T> {
+        TestChannel::<T>::new(capacity)
+    }
+
+    #[implbox_impls(BroadcastBox<T>, TestBroadcast<T>, downcast)]
+    fn new_broadcast<T: Clone + Sync + Send + 'static>(capacity: usize) -> impl Broadcast<T> {
+        TestBroadcast::<T>::new(capacity)
+    }
+
+    #[implbox_impls(BroadcastReceiverBox<T>, TestBroadcastReceiver<T>, downcast)]
+    fn new_broadcast_receiver<T: Clone + Sync + Send + 'static>(
+        broadcast: &ImplBox<BroadcastBox<T>>,
+    ) -> impl BroadcastReceiver<T> {
+        broadcast
+            .downcast_ref::<TestBroadcast<T>>()
+            .expect("broadcast was not created by TestRuntime")
+            .subscribe()
+    }
+
+    #[implbox_impls(WatchBox<T>, StdWatch<T>, downcast)]
+    fn new_watch<T: Clone + Sync + Send + 'static>(initial: T) -> impl Watch<T> {
+        StdWatch::<T>::new(initial)
+    }
+
+    #[implbox_impls(WatchReceiverBox<T>, StdWatchReceiver<T>, downcast)]
+    fn new_watch_receiver<T: Clone + Sync + Send + 'static>(
+        watch: &ImplBox<WatchBox<T>>,
+    ) -> impl WatchReceiver<T> {
+        watch
+            .downcast_ref::<StdWatch<T>>()
+            .expect("watch was not created by TestRuntime")
+            .subscribe()
+    }
+
+    #[implbox_impls(StreamBox<T>, ChannelStream<T, TestRuntime>, downcast)]
+    fn new_stream<T: Send + 'static>(channel: ImplBoxShared<ChannelBox<T>>) -> impl AsyncStream<T> {
+        ChannelStream::<T, Self>::new(channel)
+    }
+}
+
+impl Runtime for TestRuntime {
+    type Executor = TestExecutor;
+    type Clock = TestRuntimeClock;
+
+    /// The seed is taken from the `RUNTIME_TEST_SEED` environment
+    /// variable, or is 0 if it isn't set.
+    fn new_executor() -> io::Result<TestExecutor> {
+        let seed = match std::env::var("RUNTIME_TEST_SEED") {
+            Ok(seed) => seed
+                .parse()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?,
+            Err(_) => 0,
+        };
+        Ok(TestExecutor::new(seed))
+    }
+
+    /// Return the current thread's clock.
+    fn clock() -> TestRuntimeClock {
+        TestRuntimeClock::current()
+    }
+
+    /// The task runs on this thread while [TestExecutor]'s `block_on` is
+    /// running, and this panics if it isn't.
+    #[implbox_impls(JoinHandleBox<T>, TestJoinHandle<T>, downcast, name = "task")]
+    fn spawn<T: Send + 'static>(future: BoxFuture<'static, T>) -> impl JoinHandle<T> {
+        TestJoinHandle::spawn(future)
+    }
+
+    /// Every task runs on the same thread, so this is the same as
+    /// [TestRuntime::spawn].
+    #[implbox_impls(JoinHandleBox<T>, TestJoinHandle<T>, downcast, name = "local_task")]
+    fn spawn_local<T: Send + 'static>(future: LocalBoxFuture<'static, T>) -> impl JoinHandle<T> {
+        TestJoinHandle::spawn(future)
+    }
+
+    #[implbox_impls(CancelTokenBox, StdCancelToken, downcast)]
+    fn new_cancel_token(parent: Option<&ImplBox<CancelTokenBox>>) -> impl CancelToken + Clone {
+        match parent {
+            Some(parent) => parent
+                .downcast_ref::<StdCancelToken>()
+                .expect("cancel token was not created by TestRuntime")
+                .child(),
+            None => StdCancelToken::default(),
+        }
+    }
+
+    #[implbox_impls(TcpStreamBox, StdTcpStream, downcast, name = "tcp_stream")]
+    async fn connect_tcp(addr: SocketAddr) -> Result<impl AsyncTcpStream, io::Error> {
+        StdTcpStream::connect(addr)
+    }
+
+    #[implbox_impls(TcpListenerBox, StdTcpListener, downcast, name = "tcp_listener")]
+    async fn bind_tcp(addr: SocketAddr) -> Result<impl AsyncTcpListener, io::Error> {
+        StdTcpListener::bind(addr)
+    }
+
+    #[implbox_impls(UdpSocketBox, StdUdpSocket, downcast, name = "udp_socket")]
+    async fn bind_udp(addr: SocketAddr) -> Result<impl AsyncUdpSocket, io::Error> {
+        StdUdpSocket::bind(addr)
+    }
+
+    #[implbox_impls(FileBox, StdFile, downcast, name = "file")]
+    async fn open_file(path: &Path, options: &OpenOptions) -> Result<impl AsyncFile, io::Error> {
+        StdFile::open(path, options)
+    }
+
+    async fn timeout<F: Future + Send>(
+        duration: Duration,
+        future: F,
+    ) -> Result<F::Output, Elapsed> {
+        time::timeout(duration, future).await
+    }
+}
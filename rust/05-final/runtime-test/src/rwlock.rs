@@ -0,0 +1,134 @@
+use async_lock::{
+    RwLock, RwLockReadGuard, RwLockReadGuardArc, RwLockUpgradableReadGuard, RwLockWriteGuard,
+    RwLockWriteGuardArc,
+};
+use base::{AsyncRwLock, Elapsed, Upgradable};
+use std::ops::Deref;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// async-lock's RwLock supports everything [AsyncRwLock] needs directly.
+/// It is in an [Arc] so that owned guards can share it.
+#[derive(Default)]
+pub struct TestLockWrapper<T> {
+    lock: Arc<RwLock<T>>,
+}
+
+/// The guard returned by [AsyncRwLock::upgradable_read]
+pub struct TestUpgradableReadGuard<'a, T>(RwLockUpgradableReadGuard<'a, T>);
+
+impl<T> Deref for TestUpgradableReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<'a, T: Sync + Send + 'a> Upgradable<'a, T> for TestUpgradableReadGuard<'a, T> {
+    type WriteGuard = RwLockWriteGuard<'a, T>;
+
+    async fn upgrade(self) -> RwLockWriteGuard<'a, T> {
+        base::trace_future!(
+            RwLockUpgradableReadGuard::upgrade(self.0),
+            "lock.upgrade",
+            item = std::any::type_name::<T>()
+        )
+        .await
+    }
+}
+
+impl<T: Sync + Send + 'static> AsyncRwLock<T> for TestLockWrapper<T> {
+    type ReadGuard<'a>
+        = RwLockReadGuard<'a, T>
+    where
+        Self: 'a;
+    type WriteGuard<'a>
+        = RwLockWriteGuard<'a, T>
+    where
+        Self: 'a;
+    type UpgradableReadGuard<'a>
+        = TestUpgradableReadGuard<'a, T>
+    where
+        Self: 'a;
+    type OwnedReadGuard = RwLockReadGuardArc<T>;
+    type OwnedWriteGuard = RwLockWriteGuardArc<T>;
+
+    fn new(item: T) -> Self {
+        TestLockWrapper {
+            lock: Arc::new(RwLock::new(item)),
+        }
+    }
+
+    async fn read(&self) -> RwLockReadGuard<'_, T> {
+        base::trace_future!(
+            self.lock.read(),
+            "lock.read",
+            item = std::any::type_name::<T>()
+        )
+        .await
+    }
+
+    async fn write(&self) -> RwLockWriteGuard<'_, T> {
+        base::trace_future!(
+            self.lock.write(),
+            "lock.write",
+            item = std::any::type_name::<T>()
+        )
+        .await
+    }
+
+    async fn upgradable_read(&self) -> TestUpgradableReadGuard<'_, T> {
+        let guard = base::trace_future!(
+            self.lock.upgradable_read(),
+            "lock.upgradable_read",
+            item = std::any::type_name::<T>()
+        )
+        .await;
+        TestUpgradableReadGuard(guard)
+    }
+
+    async fn read_owned(&self) -> RwLockReadGuardArc<T> {
+        base::trace_future!(
+            self.lock.read_arc(),
+            "lock.read_owned",
+            item = std::any::type_name::<T>()
+        )
+        .await
+    }
+
+    async fn write_owned(&self) -> RwLockWriteGuardArc<T> {
+        base::trace_future!(
+            self.lock.write_arc(),
+            "lock.write_owned",
+            item = std::any::type_name::<T>()
+        )
+        .await
+    }
+
+    /// This blocks the thread, so calling it from a task can deadlock
+    /// the executor. See [async_lock::RwLock::read_blocking].
+    fn blocking_read(&self) -> RwLockReadGuard<'_, T> {
+        self.lock.read_blocking()
+    }
+
+    fn blocking_write(&self) -> RwLockWriteGuard<'_, T> {
+        self.lock.write_blocking()
+    }
+
+    fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        self.lock.try_read()
+    }
+
+    fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        self.lock.try_write()
+    }
+
+    async fn read_timeout(&self, duration: Duration) -> Result<RwLockReadGuard<'_, T>, Elapsed> {
+        crate::time::timeout(duration, self.read()).await
+    }
+
+    async fn write_timeout(&self, duration: Duration) -> Result<RwLockWriteGuard<'_, T>, Elapsed> {
+        crate::time::timeout(duration, self.write()).await
+    }
+}
@@ -0,0 +1,57 @@
+use async_lock::{Semaphore, SemaphoreGuard};
+use base::AsyncSemaphore;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// async-lock's semaphore doesn't report how many permits are left, so
+/// the wrapper counts the permits it has given out.
+pub struct TestSemaphoreWrapper {
+    semaphore: Semaphore,
+    permits: usize,
+    acquired: AtomicUsize,
+}
+
+pub struct TestPermit<'a> {
+    _guard: SemaphoreGuard<'a>,
+    acquired: &'a AtomicUsize,
+}
+
+impl Drop for TestPermit<'_> {
+    fn drop(&mut self) {
+        self.acquired.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl TestSemaphoreWrapper {
+    fn permit<'a>(&'a self, guard: SemaphoreGuard<'a>) -> TestPermit<'a> {
+        self.acquired.fetch_add(1, Ordering::AcqRel);
+        TestPermit {
+            _guard: guard,
+            acquired: &self.acquired,
+        }
+    }
+}
+
+impl AsyncSemaphore for TestSemaphoreWrapper {
+    type Permit<'a> = TestPermit<'a>;
+
+    fn new(permits: usize) -> Self {
+        TestSemaphoreWrapper {
+            semaphore: Semaphore::new(permits),
+            permits,
+            acquired: AtomicUsize::new(0),
+        }
+    }
+
+    async fn acquire(&self) -> TestPermit<'_> {
+        let guard = base::trace_future!(self.semaphore.acquire(), "semaphore.acquire").await;
+        self.permit(guard)
+    }
+
+    fn try_acquire(&self) -> Option<TestPermit<'_>> {
+        self.semaphore.try_acquire().map(|guard| self.permit(guard))
+    }
+
+    fn permits(&self) -> usize {
+        self.permits - self.acquired.load(Ordering::Acquire)
+    }
+}
@@ -0,0 +1,135 @@
+use base::{Clock, Elapsed, TestClock};
+use std::collections::BTreeMap;
+use std::future::{poll_fn, Future};
+use std::pin::{pin, Pin};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+struct ClockState {
+    now: Instant,
+    next_id: u64,
+    /// Waiting sleeps by deadline, and then by when they started, so
+    /// they are always woken in the same order
+    sleepers: BTreeMap<(Instant, u64), Waker>,
+}
+
+impl ClockState {
+    /// Wake every sleep that is due.
+    fn wake_due(&mut self) {
+        let waiting = self.sleepers.split_off(&(self.now, u64::MAX));
+        for waker in std::mem::replace(&mut self.sleepers, waiting).into_values() {
+            waker.wake();
+        }
+    }
+}
+
+thread_local! {
+    static CLOCK: TestRuntimeClock = TestRuntimeClock::new();
+}
+
+/// The clock for [crate::TestRuntime]. Time is virtual: it starts when
+/// the clock is first used and only moves when [TestClock::advance] is
+/// called or when every task of a [crate::executor::TestExecutor] is
+/// waiting, in which case it jumps to the next deadline. A test that
+/// sleeps for an hour finishes immediately and sees the same times on
+/// every run. Each thread has its own clock, so tests that run in
+/// parallel don't move each other's time. Clones share the same time.
+#[derive(Clone)]
+pub struct TestRuntimeClock(Arc<Mutex<ClockState>>);
+
+impl TestRuntimeClock {
+    fn new() -> Self {
+        Self(Arc::new(Mutex::new(ClockState {
+            now: Instant::now(),
+            next_id: 0,
+            sleepers: BTreeMap::new(),
+        })))
+    }
+
+    /// Return the current thread's clock.
+    pub fn current() -> Self {
+        CLOCK.with(Clone::clone)
+    }
+
+    /// Jump to the earliest deadline of any sleep and wake the sleeps
+    /// that are due. Return false if nothing is sleeping.
+    pub(crate) fn advance_to_next(&self) -> bool {
+        let mut state = self.0.lock().unwrap();
+        let Some(&(deadline, _)) = state.sleepers.keys().next() else {
+            return false;
+        };
+        state.now = state.now.max(deadline);
+        state.wake_due();
+        true
+    }
+}
+
+/// The future returned by [Clock::sleep]. It stops waiting when it is
+/// dropped, so an abandoned sleep doesn't make the clock jump.
+struct Sleep {
+    clock: Arc<Mutex<ClockState>>,
+    key: (Instant, u64),
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.clock.lock().unwrap();
+        if state.now >= self.key.0 {
+            state.sleepers.remove(&self.key);
+            return Poll::Ready(());
+        }
+        state.sleepers.insert(self.key, cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        self.clock.lock().unwrap().sleepers.remove(&self.key);
+    }
+}
+
+impl Clock for TestRuntimeClock {
+    fn now(&self) -> Instant {
+        self.0.lock().unwrap().now
+    }
+
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send {
+        let mut state = self.0.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        Sleep {
+            clock: self.0.clone(),
+            key: (state.now + duration, id),
+        }
+    }
+}
+
+impl TestClock for TestRuntimeClock {
+    async fn advance(&self, duration: Duration) {
+        let mut state = self.0.lock().unwrap();
+        state.now += duration;
+        state.wake_due();
+    }
+}
+
+/// Race the future against a sleep on the current thread's clock. If
+/// both are ready, the future wins.
+pub(crate) async fn timeout<F: Future>(
+    duration: Duration,
+    future: F,
+) -> Result<F::Output, Elapsed> {
+    let clock = TestRuntimeClock::current();
+    let mut future = pin!(future);
+    let mut sleep = pin!(clock.sleep(duration));
+    poll_fn(|cx| {
+        if let Poll::Ready(output) = future.as_mut().poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        sleep.as_mut().poll(cx).map(|()| Err(Elapsed))
+    })
+    .await
+}
@@ -0,0 +1,19 @@
+use base::AsyncBarrier;
+use tokio::sync::Barrier;
+
+pub struct TokioBarrier(Barrier);
+
+impl AsyncBarrier for TokioBarrier {
+    fn new(n: usize) -> Self {
+        TokioBarrier(Barrier::new(n))
+    }
+
+    async fn wait(&self) -> bool {
+        base::trace_future!(self.0.wait(), "barrier.wait")
+            .await
+            .is_leader()
+    }
+}
+
+#[cfg(test)]
+mod tests;
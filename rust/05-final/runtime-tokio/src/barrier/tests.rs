@@ -0,0 +1,54 @@
+use super::*;
+use crate::TokioRuntime;
+use base::{BarrierBox, Locker, StdBarrier};
+use implbox::ImplBoxShared;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::task;
+
+/// Run three rounds of four tasks through `barrier`, and check that no
+/// task gets ahead of the others and that each round has one leader.
+async fn rounds<B: AsyncBarrier + Send + Sync + 'static>(barrier: Arc<B>) {
+    let arrived = Arc::new(AtomicUsize::new(0));
+    let leaders = Arc::new(AtomicUsize::new(0));
+    let mut handles = Vec::new();
+    for _ in 0..4 {
+        let barrier = barrier.clone();
+        let arrived = arrived.clone();
+        let leaders = leaders.clone();
+        handles.push(task::spawn(async move {
+            for round in 0..3 {
+                arrived.fetch_add(1, Ordering::SeqCst);
+                if barrier.wait().await {
+                    leaders.fetch_add(1, Ordering::SeqCst);
+                }
+                assert!(arrived.load(Ordering::SeqCst) >= (round + 1) * 4);
+                // Make sure everyone has checked before the next round.
+                barrier.wait().await;
+            }
+        }));
+    }
+    for h in handles {
+        h.await.unwrap();
+    }
+    assert_eq!(leaders.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_tokio() {
+    rounds(Arc::new(TokioBarrier::new(4))).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_std() {
+    rounds(Arc::new(StdBarrier::new(4))).await;
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_boxed() {
+    let b: ImplBoxShared<BarrierBox> = TokioRuntime::box_shared_barrier(2);
+    let b2 = b.clone();
+    let h = task::spawn(async move { TokioRuntime::unbox_barrier(&b2).wait().await });
+    let leader = TokioRuntime::unbox_barrier(&b).wait().await;
+    assert_ne!(leader, h.await.unwrap());
+}
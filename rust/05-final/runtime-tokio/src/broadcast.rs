@@ -0,0 +1,55 @@
+use base::{Broadcast, BroadcastReceiver, RecvError};
+use tokio::sync::broadcast;
+
+/// The sending half of a tokio broadcast channel. Receivers are created
+/// with [TokioBroadcast::subscribe].
+pub struct TokioBroadcast<T> {
+    tx: broadcast::Sender<T>,
+}
+
+impl<T> TokioBroadcast<T> {
+    pub fn subscribe(&self) -> TokioBroadcastReceiver<T> {
+        TokioBroadcastReceiver(self.tx.subscribe())
+    }
+}
+
+impl<T: Clone> Broadcast<T> for TokioBroadcast<T> {
+    fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity);
+        TokioBroadcast { tx }
+    }
+
+    fn send(&self, item: T) -> usize {
+        // This only fails if there are no receivers.
+        self.tx.send(item).unwrap_or(0)
+    }
+
+    fn receiver_count(&self) -> usize {
+        self.tx.receiver_count()
+    }
+}
+
+pub struct TokioBroadcastReceiver<T>(broadcast::Receiver<T>);
+
+impl<T: Clone + Send> BroadcastReceiver<T> for TokioBroadcastReceiver<T> {
+    async fn recv(&mut self) -> Result<T, RecvError> {
+        base::trace_future!(self.0.recv(), "broadcast.recv")
+            .await
+            .map_err(|e| match e {
+                broadcast::error::RecvError::Lagged(n) => RecvError::Lagged(n),
+                broadcast::error::RecvError::Closed => RecvError::Closed,
+            })
+    }
+
+    fn try_recv(&mut self) -> Result<Option<T>, RecvError> {
+        match self.0.try_recv() {
+            Ok(item) => Ok(Some(item)),
+            Err(broadcast::error::TryRecvError::Empty) => Ok(None),
+            Err(broadcast::error::TryRecvError::Lagged(n)) => Err(RecvError::Lagged(n)),
+            Err(broadcast::error::TryRecvError::Closed) => Err(RecvError::Closed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
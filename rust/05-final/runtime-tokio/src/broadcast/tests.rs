@@ -0,0 +1,56 @@
+use crate::TokioRuntime;
+use base::{Broadcast, BroadcastBox, BroadcastReceiver, Locker, RecvError};
+use implbox::ImplBox;
+use tokio::task;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_broadcast() {
+    let b: ImplBox<BroadcastBox<usize>> = TokioRuntime::box_broadcast(16);
+    // There are no receivers yet, so this is dropped.
+    assert_eq!(TokioRuntime::unbox_broadcast(&b).send(0), 0);
+    let mut handles = Vec::new();
+    for _ in 0..3 {
+        let mut rx = TokioRuntime::box_broadcast_receiver(&b);
+        handles.push(task::spawn(async move {
+            let rx = TokioRuntime::unbox_mut_broadcast_receiver(&mut rx);
+            let mut received = Vec::new();
+            loop {
+                match rx.recv().await {
+                    Ok(item) => received.push(item),
+                    Err(e) => {
+                        assert_eq!(e, RecvError::Closed);
+                        break;
+                    }
+                }
+            }
+            received
+        }));
+    }
+    let tx = TokioRuntime::unbox_broadcast(&b);
+    assert_eq!(tx.receiver_count(), 3);
+    for i in 1..=10 {
+        assert_eq!(tx.send(i), 3);
+    }
+    drop(b);
+    for h in handles {
+        assert_eq!(h.await.unwrap(), (1..=10).collect::<Vec<_>>());
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_lagged() {
+    let b = TokioRuntime::box_broadcast(2);
+    let mut boxed = TokioRuntime::box_broadcast_receiver(&b);
+    let rx = TokioRuntime::unbox_mut_broadcast_receiver(&mut boxed);
+    assert_eq!(rx.try_recv(), Ok(None));
+    let tx = TokioRuntime::unbox_broadcast(&b);
+    for i in 1..=5 {
+        tx.send(i);
+    }
+    assert_eq!(rx.recv().await, Err(RecvError::Lagged(3)));
+    assert_eq!(rx.recv().await, Ok(4));
+    assert_eq!(rx.try_recv(), Ok(Some(5)));
+    assert_eq!(rx.try_recv(), Ok(None));
+    drop(b);
+    assert_eq!(rx.try_recv(), Err(RecvError::Closed));
+}
@@ -0,0 +1,70 @@
+use base::CancelToken;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use tokio::sync::Notify;
+
+#[derive(Default)]
+struct Node {
+    cancelled: AtomicBool,
+    notify: Notify,
+    /// Only weak references are kept so that dropping a child frees it.
+    children: Mutex<Vec<Weak<Node>>>,
+}
+
+impl Node {
+    fn cancel(&self) {
+        // Setting the flag while holding the lock on the children ensures
+        // that a child is either in the list or is created cancelled.
+        let children = {
+            let mut children = self.children.lock().unwrap();
+            if self.cancelled.swap(true, Ordering::AcqRel) {
+                return;
+            }
+            std::mem::take(&mut *children)
+        };
+        self.notify.notify_waiters();
+        for child in children.iter().filter_map(Weak::upgrade) {
+            child.cancel();
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct TokioCancelToken(Arc<Node>);
+
+impl CancelToken for TokioCancelToken {
+    fn cancel(&self) {
+        self.0.cancel()
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::Acquire)
+    }
+
+    async fn cancelled(&self) {
+        loop {
+            // A Notified future receives notify_waiters as soon as it is
+            // created, so the flag has to be checked after creating it.
+            let notified = self.0.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            base::trace_future!(notified, "cancel.cancelled").await;
+        }
+    }
+
+    fn child(&self) -> Self {
+        let child = Arc::new(Node::default());
+        let mut children = self.0.children.lock().unwrap();
+        if self.is_cancelled() {
+            child.cancelled.store(true, Ordering::Release);
+        } else {
+            children.retain(|c| c.strong_count() > 0);
+            children.push(Arc::downgrade(&child));
+        }
+        TokioCancelToken(child)
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,50 @@
+use super::*;
+use crate::TokioRuntime;
+use base::{run_until_cancelled, Runtime};
+use std::future::pending;
+use tokio::task;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_tree() {
+    let root = TokioRuntime::box_cancel_token(None);
+    let child = TokioRuntime::box_cancel_token(Some(&root));
+    let grandchild = TokioRuntime::box_cancel_token(Some(&child));
+    let sibling = TokioRuntime::box_cancel_token(Some(&root));
+    let waiter = grandchild.try_clone().unwrap();
+    let h = task::spawn(async move {
+        TokioRuntime::unbox_cancel_token(&waiter).cancelled().await;
+    });
+    TokioRuntime::unbox_cancel_token(&child).cancel();
+    h.await.unwrap();
+    assert!(TokioRuntime::unbox_cancel_token(&grandchild).is_cancelled());
+    // Cancellation doesn't propagate up or sideways.
+    assert!(!TokioRuntime::unbox_cancel_token(&root).is_cancelled());
+    assert!(!TokioRuntime::unbox_cancel_token(&sibling).is_cancelled());
+    TokioRuntime::unbox_cancel_token(&root).cancel();
+    assert!(TokioRuntime::unbox_cancel_token(&sibling).is_cancelled());
+    // A child of a cancelled token starts out cancelled.
+    let late = TokioRuntime::box_cancel_token(Some(&root));
+    assert!(TokioRuntime::unbox_cancel_token(&late).is_cancelled());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_run_until_cancelled() {
+    let token = TokioCancelToken::default();
+    assert_eq!(run_until_cancelled(&token, async { 5 }).await, Some(5));
+    let t = token.clone();
+    let h = task::spawn(async move { run_until_cancelled(&t, pending::<()>()).await });
+    task::yield_now().await;
+    token.cancel();
+    assert_eq!(h.await.unwrap(), None);
+    assert_eq!(run_until_cancelled(&token, async { 5 }).await, None);
+}
+
+#[test]
+fn test_dropped_children() {
+    let token = TokioCancelToken::default();
+    for _ in 0..10 {
+        drop(token.child());
+    }
+    let _child = token.child();
+    assert_eq!(token.0.children.lock().unwrap().len(), 1);
+}
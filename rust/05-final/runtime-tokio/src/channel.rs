@@ -0,0 +1,147 @@
+#[cfg(feature = "metrics")]
+use crate::metrics::ChannelStats;
+use base::{AsyncChannel, SendError, TryRecvError};
+#[cfg(feature = "metrics")]
+use std::sync::Arc;
+use std::sync::Mutex;
+use tokio::sync::{self, mpsc};
+
+enum Sender<T> {
+    Bounded(mpsc::Sender<T>),
+    Unbounded(mpsc::UnboundedSender<T>),
+}
+
+// Derived Clone would require T: Clone.
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        match self {
+            Sender::Bounded(tx) => Sender::Bounded(tx.clone()),
+            Sender::Unbounded(tx) => Sender::Unbounded(tx.clone()),
+        }
+    }
+}
+
+enum Receiver<T> {
+    Bounded(mpsc::Receiver<T>),
+    Unbounded(mpsc::UnboundedReceiver<T>),
+}
+
+/// A tokio mpsc channel with both of its halves. Closing the channel
+/// drops the sender, so the receiver returns `None` once every send
+/// that is in progress finishes and the channel is drained.
+pub struct TokioChannel<T> {
+    tx: Mutex<Option<Sender<T>>>,
+    rx: sync::Mutex<Receiver<T>>,
+    #[cfg(feature = "metrics")]
+    stats: Arc<ChannelStats>,
+}
+
+impl<T> TokioChannel<T> {
+    fn sender(&self) -> Option<Sender<T>> {
+        self.tx.lock().unwrap().clone()
+    }
+
+    /// Count `result` with the `metrics` feature.
+    fn sent<E>(&self, result: Result<(), SendError<E>>) -> Result<(), SendError<E>> {
+        #[cfg(feature = "metrics")]
+        match &result {
+            Ok(()) => self.stats.sent(),
+            Err(SendError::Full(_)) => self.stats.full(),
+            Err(SendError::Closed(_)) => {}
+        }
+        result
+    }
+
+    /// Count a received item with the `metrics` feature.
+    fn received(&self, item: T) -> T {
+        #[cfg(feature = "metrics")]
+        self.stats.received();
+        item
+    }
+}
+
+impl<T: Send> AsyncChannel<T> for TokioChannel<T> {
+    fn new(capacity: Option<usize>) -> Self {
+        let (tx, rx) = match capacity {
+            Some(capacity) => {
+                let (tx, rx) = mpsc::channel(capacity);
+                (Sender::Bounded(tx), Receiver::Bounded(rx))
+            }
+            None => {
+                let (tx, rx) = mpsc::unbounded_channel();
+                (Sender::Unbounded(tx), Receiver::Unbounded(rx))
+            }
+        };
+        TokioChannel {
+            tx: Mutex::new(Some(tx)),
+            rx: sync::Mutex::new(rx),
+            #[cfg(feature = "metrics")]
+            stats: crate::metrics::channel_stats::<T>(),
+        }
+    }
+
+    async fn send(&self, item: T) -> Result<(), SendError<T>> {
+        let result = match self.sender() {
+            None => Err(SendError::Closed(item)),
+            Some(Sender::Bounded(tx)) => {
+                let send = tx.send(item);
+                #[cfg(feature = "metrics")]
+                let send = self.stats.send(send);
+                base::trace_future!(send, "channel.send")
+                    .await
+                    .map_err(|e| SendError::Closed(e.0))
+            }
+            Some(Sender::Unbounded(tx)) => tx.send(item).map_err(|e| SendError::Closed(e.0)),
+        };
+        self.sent(result)
+    }
+
+    fn try_send(&self, item: T) -> Result<(), SendError<T>> {
+        let result = match self.sender() {
+            None => Err(SendError::Closed(item)),
+            Some(Sender::Bounded(tx)) => tx.try_send(item).map_err(|e| match e {
+                mpsc::error::TrySendError::Full(item) => SendError::Full(item),
+                mpsc::error::TrySendError::Closed(item) => SendError::Closed(item),
+            }),
+            Some(Sender::Unbounded(tx)) => tx.send(item).map_err(|e| SendError::Closed(e.0)),
+        };
+        self.sent(result)
+    }
+
+    async fn recv(&self) -> Option<T> {
+        let mut rx = self.rx.lock().await;
+        let recv = async {
+            match &mut *rx {
+                Receiver::Bounded(rx) => rx.recv().await,
+                Receiver::Unbounded(rx) => rx.recv().await,
+            }
+        };
+        let item = base::trace_future!(recv, "channel.recv").await;
+        item.map(|item| self.received(item))
+    }
+
+    fn try_recv(&self) -> Result<T, TryRecvError> {
+        let Ok(mut rx) = self.rx.try_lock() else {
+            return Err(TryRecvError::Empty);
+        };
+        let result = match &mut *rx {
+            Receiver::Bounded(rx) => rx.try_recv(),
+            Receiver::Unbounded(rx) => rx.try_recv(),
+        };
+        result.map(|item| self.received(item)).map_err(|e| match e {
+            mpsc::error::TryRecvError::Empty => TryRecvError::Empty,
+            mpsc::error::TryRecvError::Disconnected => TryRecvError::Closed,
+        })
+    }
+
+    fn close(&self) {
+        self.tx.lock().unwrap().take();
+    }
+
+    fn is_closed(&self) -> bool {
+        self.tx.lock().unwrap().is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,59 @@
+use super::*;
+use crate::TokioRuntime;
+use base::{ChannelBox, Locker};
+use implbox::ImplBoxShared;
+use std::cell::Cell;
+use tokio::task;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_producers() {
+    let c: ImplBoxShared<ChannelBox<usize>> = TokioRuntime::box_shared_channel(Some(2));
+    let mut handles = Vec::new();
+    for i in 0..4 {
+        let c = c.clone();
+        handles.push(task::spawn(async move {
+            let c = TokioRuntime::unbox_channel(&c);
+            for j in 0..10 {
+                c.send(i * 10 + j).await.unwrap();
+            }
+        }));
+    }
+    let mut received = Vec::new();
+    let rx = TokioRuntime::unbox_channel(&c);
+    while received.len() < 40 {
+        received.push(rx.recv().await.unwrap());
+    }
+    for h in handles {
+        h.await.unwrap();
+    }
+    received.sort();
+    assert_eq!(received, (0..40).collect::<Vec<_>>());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_close() {
+    let c = TokioRuntime::new_channel(Some(1));
+    c.send(1).await.unwrap();
+    assert_eq!(c.try_send(2), Err(SendError::Full(2)));
+    c.close();
+    assert!(c.is_closed());
+    assert_eq!(c.send(3).await, Err(SendError::Closed(3)));
+    // Items sent before closing are still received.
+    assert_eq!(c.recv().await, Some(1));
+    assert_eq!(c.recv().await, None);
+    assert_eq!(c.try_recv(), Err(TryRecvError::Closed));
+}
+
+#[test]
+fn test_unbounded() {
+    // Items only have to be Send, not Sync.
+    let b = TokioRuntime::box_channel::<Cell<i32>>(None);
+    let c = TokioRuntime::unbox_channel(&b);
+    assert_eq!(c.try_recv(), Err(TryRecvError::Empty));
+    for i in 0..100 {
+        c.try_send(Cell::new(i)).unwrap();
+    }
+    assert_eq!(c.try_recv().unwrap().get(), 0);
+    // The box can move to another thread even though the items aren't Sync.
+    std::thread::spawn(move || drop(b)).join().unwrap();
+}
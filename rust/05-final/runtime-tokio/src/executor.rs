@@ -0,0 +1,44 @@
+use base::Executor;
+use std::future::Future;
+use std::io;
+use std::time::Duration;
+use tokio::runtime::{Builder, Runtime};
+
+/// A tokio runtime. The one from [TokioExecutor::new] is a
+/// current-thread runtime, whose spawned tasks only run while a call to
+/// [Executor::block_on] is in progress.
+pub struct TokioExecutor(Runtime);
+
+impl TokioExecutor {
+    pub fn new() -> io::Result<Self> {
+        Ok(TokioExecutor(
+            Builder::new_current_thread().enable_all().build()?,
+        ))
+    }
+
+    /// Use a runtime built by the caller, such as a multi-threaded one,
+    /// whose spawned tasks run on its worker threads whether or not
+    /// anything is blocked on it. It needs the time and I/O drivers.
+    pub fn with_runtime(runtime: Runtime) -> Self {
+        TokioExecutor(runtime)
+    }
+
+    /// Return the underlying tokio runtime, for example to read its
+    /// metrics.
+    pub fn runtime(&self) -> &Runtime {
+        &self.0
+    }
+}
+
+impl Executor for TokioExecutor {
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.0.block_on(future)
+    }
+
+    fn shutdown(self, timeout: Duration) {
+        self.0.shutdown_timeout(timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests;
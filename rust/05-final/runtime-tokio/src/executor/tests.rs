@@ -0,0 +1,61 @@
+use super::*;
+use crate::TokioRuntime;
+use base::{AsyncBarrier, JoinHandle, Runtime, StdBarrier, StdExecutor};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_tokio() {
+    let rt = TokioRuntime::new_executor().unwrap();
+    let n = rt.block_on(async {
+        // Timers and spawned tasks need the runtime to be running.
+        let mut h = TokioRuntime::spawn(Box::pin(async {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+            21
+        }));
+        h.join().await.unwrap() * 2
+    });
+    assert_eq!(n, 42);
+}
+
+#[test]
+fn test_std() {
+    let barrier = Arc::new(StdBarrier::new(2));
+    let b2 = barrier.clone();
+    let h = thread::spawn(move || StdExecutor.block_on(b2.wait()));
+    let leader = StdExecutor.block_on(barrier.wait());
+    // Exactly one side leads.
+    assert_ne!(leader, h.join().unwrap());
+}
+
+#[test]
+fn test_with_runtime() {
+    let rt = TokioExecutor::with_runtime(
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .enable_all()
+            .build()
+            .unwrap(),
+    );
+    // The task runs on a worker thread without block_on.
+    let (tx, rx) = std::sync::mpsc::channel();
+    rt.runtime()
+        .spawn(async move { tx.send(thread::current().id()).unwrap() });
+    let id = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+    assert_ne!(id, thread::current().id());
+    assert_eq!(rt.runtime().metrics().num_workers(), 2);
+}
+
+#[test]
+fn test_shutdown() {
+    let rt = TokioRuntime::new_executor().unwrap();
+    rt.block_on(async {
+        // This never finishes on its own.
+        TokioRuntime::spawn(Box::pin(std::future::pending::<()>()));
+    });
+    let start = std::time::Instant::now();
+    rt.shutdown(Duration::from_secs(10));
+    // The task is dropped rather than waited for.
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
@@ -0,0 +1,35 @@
+use base::AsyncFile;
+use std::fs::OpenOptions;
+use std::io;
+use std::path::Path;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+pub struct TokioFile(File);
+
+impl TokioFile {
+    pub async fn open(path: &Path, options: &OpenOptions) -> io::Result<Self> {
+        let options = tokio::fs::OpenOptions::from(options.clone());
+        Ok(TokioFile(options.open(path).await?))
+    }
+}
+
+impl AsyncFile for TokioFile {
+    async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        base::trace_future!(self.0.read(buf), "file.read").await
+    }
+
+    async fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        base::trace_future!(self.0.write(buf), "file.write").await
+    }
+
+    async fn sync(&mut self) -> io::Result<()> {
+        // tokio finishes writes in the background, so flush before
+        // syncing to report their errors here.
+        self.0.flush().await?;
+        base::trace_future!(self.0.sync_all(), "file.sync").await
+    }
+}
+
+#[cfg(test)]
+mod tests;
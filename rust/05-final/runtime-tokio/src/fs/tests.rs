@@ -0,0 +1,86 @@
+use super::*;
+use crate::TokioRuntime;
+use base::{Runtime, StdFile};
+use std::path::PathBuf;
+
+/// A path in the temporary directory whose file is removed when dropped
+struct TempPath(PathBuf);
+
+impl TempPath {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("{name}-{}", std::process::id()));
+        _ = std::fs::remove_file(&path);
+        TempPath(path)
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        _ = std::fs::remove_file(&self.0);
+    }
+}
+
+fn create() -> OpenOptions {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    options
+}
+
+fn read_only() -> OpenOptions {
+    let mut options = OpenOptions::new();
+    options.read(true);
+    options
+}
+
+async fn write_all(f: &mut impl AsyncFile, mut data: &[u8]) {
+    while !data.is_empty() {
+        let n = f.write(data).await.unwrap();
+        data = &data[n..];
+    }
+    f.sync().await.unwrap();
+}
+
+async fn read_to_end(f: &mut impl AsyncFile) -> Vec<u8> {
+    let mut buf = Vec::new();
+    let mut chunk = [0; 5];
+    loop {
+        let n = f.read(&mut chunk).await.unwrap();
+        if n == 0 {
+            return buf;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+}
+
+#[tokio::test]
+async fn test_tokio() {
+    let path = TempPath::new("runtime-tokio-fs-tokio");
+    let mut f = TokioFile::open(&path.0, &create()).await.unwrap();
+    write_all(&mut f, b"potatoes and salad").await;
+    assert_eq!(std::fs::read(&path.0).unwrap(), b"potatoes and salad");
+    let mut f = TokioFile::open(&path.0, &read_only()).await.unwrap();
+    assert_eq!(read_to_end(&mut f).await, b"potatoes and salad");
+    // create_new refuses to replace the file.
+    assert!(TokioFile::open(&path.0, &create()).await.is_err());
+}
+
+#[tokio::test]
+async fn test_std() {
+    let path = TempPath::new("runtime-tokio-fs-std");
+    let mut f = StdFile::open(&path.0, &create()).unwrap();
+    write_all(&mut f, b"potatoes and salad").await;
+    let mut f = StdFile::open(&path.0, &read_only()).unwrap();
+    assert_eq!(read_to_end(&mut f).await, b"potatoes and salad");
+}
+
+#[tokio::test]
+async fn test_boxed() {
+    let path = TempPath::new("runtime-tokio-fs-boxed");
+    let mut f = TokioRuntime::box_file(&path.0, &create()).await.unwrap();
+    write_all(TokioRuntime::unbox_mut_file(&mut f), b"salad").await;
+    let mut f = TokioRuntime::box_file(&path.0, &read_only()).await.unwrap();
+    assert_eq!(
+        read_to_end(TokioRuntime::unbox_mut_file(&mut f)).await,
+        b"salad"
+    );
+}
@@ -1,18 +1,229 @@
+use crate::barrier::TokioBarrier;
+use crate::broadcast::{TokioBroadcast, TokioBroadcastReceiver};
+use crate::cancel::TokioCancelToken;
+use crate::channel::TokioChannel;
+use crate::executor::TokioExecutor;
+use crate::fs::TokioFile;
+use crate::mutex::TokioMutexWrapper;
+use crate::net::{TokioTcpListener, TokioTcpStream, TokioUdpSocket};
+use crate::notify::TokioNotify;
 use crate::rwlock::TokioLockWrapper;
-use base::{AsyncRwLock, LockBox, Locker, Runtime};
-use implbox::ImplBox;
+use crate::semaphore::TokioSemaphoreWrapper;
+use crate::task::TokioJoinHandle;
+use crate::time::TokioClock;
+use crate::watch::{TokioWatch, TokioWatchReceiver};
+use base::{
+    AsyncBarrier, AsyncChannel, AsyncFile, AsyncMutex, AsyncNotify, AsyncRwLock, AsyncSemaphore,
+    AsyncStream, AsyncTcpListener, AsyncTcpStream, AsyncUdpSocket, BarrierBox, BoxFuture,
+    Broadcast, BroadcastBox, BroadcastReceiver, BroadcastReceiverBox, CancelToken, CancelTokenBox,
+    ChannelBox, ChannelStream, Elapsed, FileBox, JoinHandle, JoinHandleBox, LocalBoxFuture,
+    LockBox, LockPolicy, Locker, MutexBox, NotifyBox, PolicyLockBox, Runtime, SemaphoreBox,
+    StreamBox, TcpListenerBox, TcpStreamBox, UdpSocketBox, Watch, WatchBox, WatchReceiver,
+    WatchReceiverBox,
+};
+use implbox::{ImplBox, ImplBoxShared};
 use implbox_macros::implbox_impls;
+use std::fs::OpenOptions;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::Duration;
 
+pub mod barrier;
+pub mod broadcast;
+pub mod cancel;
+pub mod channel;
+pub mod executor;
+pub mod fs;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod mutex;
+pub mod net;
+pub mod notify;
 pub mod rwlock;
+pub mod semaphore;
+pub mod task;
+pub mod time;
+pub mod watch;
+
+#[cfg(test)]
+mod tests;
 
 #[derive(Default, Clone)]
 pub struct TokioRuntime;
 
+#[cfg(feature = "metrics")]
+impl TokioRuntime {
+    /// Return a snapshot of how locks, channels, and tasks have been
+    /// used. See [metrics].
+    pub fn metrics() -> metrics::RuntimeMetrics {
+        metrics::snapshot()
+    }
+}
+
 impl Locker for TokioRuntime {
-    #[implbox_impls(LockBox<T>, TokioLockWrapper<T>)]
-    fn new_lock<T: Sync + Send>(item: T) -> impl AsyncRwLock<T> {
+    #[implbox_impls(LockBox<T>, TokioLockWrapper<T>, downcast)]
+    fn new_lock<T: Sync + Send + 'static>(item: T) -> impl AsyncRwLock<T> {
         TokioLockWrapper::<T>::new(item)
     }
+
+    #[implbox_impls(PolicyLockBox<T>, TokioLockWrapper<T>, downcast)]
+    fn new_lock_with<T: Sync + Send + 'static>(policy: LockPolicy, item: T) -> impl AsyncRwLock<T> {
+        TokioLockWrapper::<T>::with_policy(policy, item)
+    }
+
+    #[implbox_impls(MutexBox<T>, TokioMutexWrapper<T>, downcast)]
+    fn new_mutex<T: Sync + Send + 'static>(item: T) -> impl AsyncMutex<T> {
+        TokioMutexWrapper::<T>::new(item)
+    }
+
+    #[implbox_impls(SemaphoreBox, TokioSemaphoreWrapper, downcast)]
+    fn new_semaphore(permits: usize) -> impl AsyncSemaphore {
+        TokioSemaphoreWrapper::new(permits)
+    }
+
+    #[implbox_impls(NotifyBox, TokioNotify, downcast)]
+    fn new_notify() -> impl AsyncNotify {
+        TokioNotify::new()
+    }
+
+    #[implbox_impls(BarrierBox, TokioBarrier, downcast)]
+    fn new_barrier(n: usize) -> impl AsyncBarrier {
+        TokioBarrier::new(n)
+    }
+
+    #[implbox_impls(ChannelBox<T>, TokioChannel<T>, downcast)]
+    fn new_channel<T: Send + 'static>(capacity: Option<usize>) -> impl AsyncChannel<T> {
+        TokioChannel::<T>::new(capacity)
+    }
+
+    #[implbox_impls(BroadcastBox<T>, TokioBroadcast<T>, downcast)]
+    fn new_broadcast<T: Clone + Sync + Send + 'static>(capacity: usize) -> impl Broadcast<T> {
+        TokioBroadcast::<T>::new(capacity)
+    }
+
+    #[implbox_impls(BroadcastReceiverBox<T>, TokioBroadcastReceiver<T>, downcast)]
+    fn new_broadcast_receiver<T: Clone + Sync + Send + 'static>(
+        broadcast: &ImplBox<BroadcastBox<T>>,
+    ) -> impl BroadcastReceiver<T> {
+        broadcast
+            .downcast_ref::<TokioBroadcast<T>>()
+            .expect("broadcast was not created by TokioRuntime")
+            .subscribe()
+    }
+
+    #[implbox_impls(WatchBox<T>, TokioWatch<T>, downcast)]
+    fn new_watch<T: Clone + Sync + Send + 'static>(initial: T) -> impl Watch<T> {
+        TokioWatch::<T>::new(initial)
+    }
+
+    #[implbox_impls(WatchReceiverBox<T>, TokioWatchReceiver<T>, downcast)]
+    fn new_watch_receiver<T: Clone + Sync + Send + 'static>(
+        watch: &ImplBox<WatchBox<T>>,
+    ) -> impl WatchReceiver<T> {
+        watch
+            .downcast_ref::<TokioWatch<T>>()
+            .expect("watch was not created by TokioRuntime")
+            .subscribe()
+    }
+
+    #[implbox_impls(StreamBox<T>, ChannelStream<T, TokioRuntime>, downcast)]
+    fn new_stream<T: Send + 'static>(channel: ImplBoxShared<ChannelBox<T>>) -> impl AsyncStream<T> {
+        ChannelStream::<T, Self>::new(channel)
+    }
+}
+
+/// Count the task as spawned now and as finished when `future` is
+/// dropped.
+#[cfg(feature = "metrics")]
+fn counted<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    let finished = metrics::spawned();
+    async move {
+        let _finished = finished;
+        future.await
+    }
+}
+
+/// Without the `metrics` feature, tasks aren't counted.
+#[cfg(not(feature = "metrics"))]
+fn counted<F: Future>(future: F) -> F {
+    future
 }
 
-impl Runtime for TokioRuntime {}
+impl Runtime for TokioRuntime {
+    type Executor = TokioExecutor;
+    type Clock = TokioClock;
+
+    fn new_executor() -> io::Result<TokioExecutor> {
+        TokioExecutor::new()
+    }
+
+    fn clock() -> TokioClock {
+        TokioClock
+    }
+
+    #[implbox_impls(JoinHandleBox<T>, TokioJoinHandle<T>, downcast, name = "task")]
+    fn spawn<T: Send + 'static>(future: BoxFuture<'static, T>) -> impl JoinHandle<T> {
+        TokioJoinHandle::new(tokio::task::spawn(counted(future)))
+    }
+
+    #[implbox_impls(JoinHandleBox<T>, TokioJoinHandle<T>, downcast, name = "local_task")]
+    fn spawn_local<T: Send + 'static>(future: LocalBoxFuture<'static, T>) -> impl JoinHandle<T> {
+        TokioJoinHandle::new(tokio::task::spawn_local(counted(future)))
+    }
+
+    #[implbox_impls(CancelTokenBox, TokioCancelToken, downcast)]
+    fn new_cancel_token(parent: Option<&ImplBox<CancelTokenBox>>) -> impl CancelToken + Clone {
+        match parent {
+            Some(parent) => parent
+                .downcast_ref::<TokioCancelToken>()
+                .expect("cancel token was not created by TokioRuntime")
+                .child(),
+            None => TokioCancelToken::default(),
+        }
+    }
+
+    #[implbox_impls(TcpStreamBox, TokioTcpStream, downcast, name = "tcp_stream")]
+    async fn connect_tcp(addr: SocketAddr) -> Result<impl AsyncTcpStream, io::Error> {
+        TokioTcpStream::connect(addr).await
+    }
+
+    #[implbox_impls(TcpListenerBox, TokioTcpListener, downcast, name = "tcp_listener")]
+    async fn bind_tcp(addr: SocketAddr) -> Result<impl AsyncTcpListener, io::Error> {
+        TokioTcpListener::bind(addr).await
+    }
+
+    #[implbox_impls(UdpSocketBox, TokioUdpSocket, downcast, name = "udp_socket")]
+    async fn bind_udp(addr: SocketAddr) -> Result<impl AsyncUdpSocket, io::Error> {
+        TokioUdpSocket::bind(addr).await
+    }
+
+    #[implbox_impls(FileBox, TokioFile, downcast, name = "file")]
+    async fn open_file(path: &Path, options: &OpenOptions) -> Result<impl AsyncFile, io::Error> {
+        TokioFile::open(path, options).await
+    }
+
+    async fn timeout<F: Future + Send>(
+        duration: Duration,
+        future: F,
+    ) -> Result<F::Output, Elapsed> {
+        tokio::time::timeout(duration, future)
+            .await
+            .map_err(|_| Elapsed)
+    }
+
+    async fn shutdown_signal() -> io::Result<()> {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            let mut term = signal(SignalKind::terminate())?;
+            tokio::select! {
+                r = tokio::signal::ctrl_c() => r,
+                _ = term.recv() => Ok(()),
+            }
+        }
+        #[cfg(not(unix))]
+        tokio::signal::ctrl_c().await
+    }
+}
@@ -0,0 +1,359 @@
+//! Counters and histograms of how the runtime's locks, channels, and
+//! tasks are used, enabled by the `metrics` feature. Locks and channels
+//! are keyed by the name of their item type, so the lock around a
+//! particular struct, such as a controller's request data, can be found
+//! by name, and every lock of the same type adds to the same entry. Take
+//! a snapshot with [TokioRuntime::metrics]. Counts are process-wide and
+//! are never reset, so compare two snapshots to see what happened in
+//! between.
+//!
+//! A lock acquisition is contended if it couldn't be granted the first
+//! time it was polled. Wait time runs from the first poll until the
+//! lock is granted, and hold time from then until the guard is dropped.
+//! Acquisitions that time out or are abandoned aren't counted.
+//!
+//! [TokioRuntime::metrics]: crate::TokioRuntime::metrics
+use crate::rwlock::Access;
+use std::collections::BTreeMap;
+use std::future::{poll_fn, Future};
+use std::ops::{Deref, DerefMut};
+use std::pin::pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The number of buckets in a [Histogram]
+pub const BUCKETS: usize = 32;
+
+/// A histogram of durations with power-of-two buckets. Bucket 0 counts
+/// durations under 1µs, bucket `i` counts those from `2^(i-1)` up to
+/// `2^i` µs, and the last bucket also counts everything longer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Histogram {
+    pub buckets: [u64; BUCKETS],
+    pub count: u64,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+impl Histogram {
+    fn bucket(d: Duration) -> usize {
+        let micros = d.as_micros();
+        let bits = (u128::BITS - micros.leading_zeros()) as usize;
+        bits.min(BUCKETS - 1)
+    }
+
+    pub fn mean(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            n => Duration::from_nanos((self.total.as_nanos() / n as u128) as u64),
+        }
+    }
+
+    /// Estimate the duration that the fraction `q`, from 0.0 to 1.0, of
+    /// the samples don't exceed. This is the upper bound of the bucket
+    /// the sample falls in, but never more than [Histogram::max], so it
+    /// is at most twice the real value.
+    pub fn quantile(&self, q: f64) -> Duration {
+        let rank = ((q.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (i, n) in self.buckets.iter().enumerate() {
+            seen += n;
+            if seen >= rank {
+                return Duration::from_micros(1 << i).min(self.max);
+            }
+        }
+        self.max
+    }
+}
+
+#[derive(Default)]
+struct AtomicHistogram {
+    buckets: [AtomicU64; BUCKETS],
+    count: AtomicU64,
+    total_nanos: AtomicU64,
+    max_nanos: AtomicU64,
+}
+
+impl AtomicHistogram {
+    fn record(&self, d: Duration) {
+        let nanos = d.as_nanos().min(u64::MAX as u128) as u64;
+        self.buckets[Histogram::bucket(d)].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.total_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.max_nanos.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> Histogram {
+        let mut buckets = [0; BUCKETS];
+        for (b, a) in buckets.iter_mut().zip(&self.buckets) {
+            *b = a.load(Ordering::Relaxed);
+        }
+        Histogram {
+            buckets,
+            count: self.count.load(Ordering::Relaxed),
+            total: Duration::from_nanos(self.total_nanos.load(Ordering::Relaxed)),
+            max: Duration::from_nanos(self.max_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// How the locks of one item type have been used
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LockMetrics {
+    /// Read and upgradable read acquisitions
+    pub reads: u64,
+    /// Write acquisitions, including upgrades
+    pub writes: u64,
+    /// Acquisitions that had to wait
+    pub contended: u64,
+    pub wait: Histogram,
+    pub hold: Histogram,
+}
+
+/// How the channels of one item type have been used
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChannelMetrics {
+    pub sent: u64,
+    pub received: u64,
+    /// Sends that found a bounded channel full, whether they waited or
+    /// failed
+    pub full: u64,
+    /// How long sends to a bounded channel waited for room
+    pub send_wait: Histogram,
+}
+
+/// Tasks started by [base::Runtime::spawn] and
+/// [base::Runtime::spawn_local]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TaskMetrics {
+    pub spawned: u64,
+    /// Tasks that finished, panicked, or were aborted
+    pub finished: u64,
+}
+
+impl TaskMetrics {
+    pub fn alive(&self) -> u64 {
+        self.spawned - self.finished
+    }
+}
+
+/// A snapshot of every metric, returned by
+/// [TokioRuntime::metrics](crate::TokioRuntime::metrics)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuntimeMetrics {
+    /// Keyed by the name of the locked type
+    pub locks: BTreeMap<&'static str, LockMetrics>,
+    /// Keyed by the name of the item type
+    pub channels: BTreeMap<&'static str, ChannelMetrics>,
+    pub tasks: TaskMetrics,
+}
+
+#[derive(Default)]
+pub(crate) struct LockStats {
+    reads: AtomicU64,
+    writes: AtomicU64,
+    contended: AtomicU64,
+    wait: AtomicHistogram,
+    hold: AtomicHistogram,
+}
+
+#[derive(Default)]
+pub(crate) struct ChannelStats {
+    sent: AtomicU64,
+    received: AtomicU64,
+    full: AtomicU64,
+    send_wait: AtomicHistogram,
+}
+
+impl ChannelStats {
+    pub(crate) fn sent(&self) {
+        self.sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn received(&self) {
+        self.received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn full(&self) {
+        self.full.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Send with `send`, which waits while the channel is full.
+    pub(crate) async fn send<F: Future>(&self, send: F) -> F::Output {
+        let (result, waited) = timed(send).await;
+        if let Some(wait) = waited {
+            self.full();
+            self.send_wait.record(wait);
+        }
+        result
+    }
+}
+
+static LOCKS: Mutex<BTreeMap<&'static str, Arc<LockStats>>> = Mutex::new(BTreeMap::new());
+static CHANNELS: Mutex<BTreeMap<&'static str, Arc<ChannelStats>>> = Mutex::new(BTreeMap::new());
+static SPAWNED: AtomicU64 = AtomicU64::new(0);
+static FINISHED: AtomicU64 = AtomicU64::new(0);
+
+/// Return the stats shared by every lock of `T`.
+pub(crate) fn lock_stats<T>() -> Arc<LockStats> {
+    let mut locks = LOCKS.lock().unwrap();
+    locks.entry(std::any::type_name::<T>()).or_default().clone()
+}
+
+/// Return the stats shared by every channel of `T`.
+pub(crate) fn channel_stats<T>() -> Arc<ChannelStats> {
+    let mut channels = CHANNELS.lock().unwrap();
+    channels
+        .entry(std::any::type_name::<T>())
+        .or_default()
+        .clone()
+}
+
+pub(crate) fn snapshot() -> RuntimeMetrics {
+    let locks = LOCKS.lock().unwrap();
+    let channels = CHANNELS.lock().unwrap();
+    RuntimeMetrics {
+        locks: locks
+            .iter()
+            .map(|(name, s)| {
+                let metrics = LockMetrics {
+                    reads: s.reads.load(Ordering::Relaxed),
+                    writes: s.writes.load(Ordering::Relaxed),
+                    contended: s.contended.load(Ordering::Relaxed),
+                    wait: s.wait.snapshot(),
+                    hold: s.hold.snapshot(),
+                };
+                (*name, metrics)
+            })
+            .collect(),
+        channels: channels
+            .iter()
+            .map(|(name, s)| {
+                let metrics = ChannelMetrics {
+                    sent: s.sent.load(Ordering::Relaxed),
+                    received: s.received.load(Ordering::Relaxed),
+                    full: s.full.load(Ordering::Relaxed),
+                    send_wait: s.send_wait.snapshot(),
+                };
+                (*name, metrics)
+            })
+            .collect(),
+        tasks: TaskMetrics {
+            spawned: SPAWNED.load(Ordering::Relaxed),
+            finished: FINISHED.load(Ordering::Relaxed),
+        },
+    }
+}
+
+/// Run `future`, and if it wasn't ready the first time it was polled,
+/// also return how long it took from then.
+async fn timed<F: Future>(future: F) -> (F::Output, Option<Duration>) {
+    let mut future = pin!(future);
+    let mut start = None;
+    let output = poll_fn(|cx| {
+        let poll = future.as_mut().poll(cx);
+        if poll.is_pending() {
+            start.get_or_insert_with(Instant::now);
+        }
+        poll
+    })
+    .await;
+    (output, start.map(|start| start.elapsed()))
+}
+
+/// Records how long a lock was held when it is dropped
+pub(crate) struct Hold {
+    stats: Arc<LockStats>,
+    since: Instant,
+}
+
+impl Hold {
+    fn acquired(stats: &Arc<LockStats>, access: Access, waited: Option<Duration>) -> Self {
+        match access {
+            Access::Read => stats.reads.fetch_add(1, Ordering::Relaxed),
+            Access::Write => stats.writes.fetch_add(1, Ordering::Relaxed),
+        };
+        if waited.is_some() {
+            stats.contended.fetch_add(1, Ordering::Relaxed);
+        }
+        stats.wait.record(waited.unwrap_or_default());
+        Hold {
+            stats: stats.clone(),
+            since: Instant::now(),
+        }
+    }
+
+    /// Record the hold time, and return the stats so that a following
+    /// acquisition can be recorded.
+    pub(crate) fn release(self) -> Arc<LockStats> {
+        self.stats.clone()
+    }
+}
+
+impl Drop for Hold {
+    fn drop(&mut self) {
+        self.stats.hold.record(self.since.elapsed());
+    }
+}
+
+/// A lock guard that records how long it was held when it is dropped
+pub struct Held<G> {
+    pub(crate) guard: G,
+    pub(crate) hold: Hold,
+}
+
+impl<G> Held<G> {
+    /// Record an acquisition that didn't wait.
+    pub(crate) fn new(stats: &Arc<LockStats>, access: Access, guard: G) -> Self {
+        Held {
+            guard,
+            hold: Hold::acquired(stats, access, None),
+        }
+    }
+
+    /// Acquire the lock with `acquire`, recording how long it waited.
+    pub(crate) async fn acquire(
+        stats: &Arc<LockStats>,
+        access: Access,
+        acquire: impl Future<Output = G>,
+    ) -> Self {
+        let (guard, waited) = timed(acquire).await;
+        Held {
+            guard,
+            hold: Hold::acquired(stats, access, waited),
+        }
+    }
+}
+
+impl<G: Deref> Deref for Held<G> {
+    type Target = G::Target;
+
+    fn deref(&self) -> &G::Target {
+        &self.guard
+    }
+}
+
+impl<G: DerefMut> DerefMut for Held<G> {
+    fn deref_mut(&mut self) -> &mut G::Target {
+        &mut self.guard
+    }
+}
+
+/// Count a task that was spawned, and return a guard that counts it as
+/// finished when the task's future is dropped.
+pub(crate) fn spawned() -> TaskFinished {
+    SPAWNED.fetch_add(1, Ordering::Relaxed);
+    TaskFinished
+}
+
+pub(crate) struct TaskFinished;
+
+impl Drop for TaskFinished {
+    fn drop(&mut self) {
+        FINISHED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests;
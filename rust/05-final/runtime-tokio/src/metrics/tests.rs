@@ -0,0 +1,92 @@
+use super::*;
+use crate::TokioRuntime;
+use base::{AsyncChannel, AsyncRwLock, JoinHandle, Locker, Runtime, SendError, Upgradable};
+use tokio::sync::oneshot;
+
+// Each test locks its own type so that tests running in parallel don't
+// add to each other's counts.
+
+#[test]
+fn test_histogram() {
+    let h = AtomicHistogram::default();
+    for micros in [0, 1, 3, 100, 100, 5000] {
+        h.record(Duration::from_micros(micros));
+    }
+    let h = h.snapshot();
+    assert_eq!(h.count, 6);
+    assert_eq!(h.buckets[0], 1);
+    assert_eq!(h.buckets[1], 1);
+    assert_eq!(h.buckets[2], 1);
+    assert_eq!(h.buckets[7], 2);
+    assert_eq!(h.max, Duration::from_micros(5000));
+    assert_eq!(h.mean(), Duration::from_nanos(5204000 / 6));
+    assert_eq!(h.quantile(0.5), Duration::from_micros(4));
+    assert_eq!(h.quantile(0.8), Duration::from_micros(128));
+    // The estimate never exceeds the largest sample.
+    assert_eq!(h.quantile(1.0), Duration::from_micros(5000));
+    assert_eq!(Histogram::default().quantile(0.5), Duration::ZERO);
+}
+
+#[tokio::test]
+async fn test_lock() {
+    #[derive(Default)]
+    struct Data(i32);
+    let name = std::any::type_name::<Data>();
+
+    let lock = TokioRuntime::new_lock(Data::default());
+    assert_eq!(lock.read().await.0, 0);
+    lock.write().await.0 += 1;
+    let upgradable = lock.upgradable_read().await;
+    upgradable.upgrade().await.0 += 1;
+    assert!(lock.try_read().is_some());
+    let m = &TokioRuntime::metrics().locks[name];
+    assert_eq!((m.reads, m.writes, m.contended), (3, 2, 0));
+    assert_eq!(m.wait.count, 5);
+    assert_eq!(m.hold.count, 5);
+
+    // A writer that waits for a reader is contended, and the reader's
+    // hold time covers the wait.
+    let r = lock.read_owned().await;
+    let (tx, rx) = oneshot::channel();
+    let writer = tokio::spawn(async move {
+        tx.send(()).unwrap();
+        lock.write().await.0
+    });
+    rx.await.unwrap();
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    drop(r);
+    assert_eq!(writer.await.unwrap(), 2);
+    let m = &TokioRuntime::metrics().locks[name];
+    assert_eq!((m.reads, m.writes, m.contended), (4, 3, 1));
+    assert!(m.wait.max >= Duration::from_millis(10));
+    assert!(m.hold.max >= Duration::from_millis(10));
+}
+
+#[tokio::test]
+async fn test_channel() {
+    #[derive(Debug)]
+    struct Item;
+    let name = std::any::type_name::<Item>();
+
+    let c = TokioRuntime::new_channel(Some(1));
+    c.send(Item).await.unwrap();
+    assert!(matches!(c.try_send(Item), Err(SendError::Full(_))));
+    assert!(c.recv().await.is_some());
+    assert!(c.try_recv().is_err());
+    c.close();
+    assert!(matches!(c.send(Item).await, Err(SendError::Closed(_))));
+    let m = &TokioRuntime::metrics().channels[name];
+    assert_eq!((m.sent, m.received, m.full), (1, 1, 1));
+    assert_eq!(m.send_wait.count, 0);
+}
+
+#[tokio::test]
+async fn test_tasks() {
+    let before = TokioRuntime::metrics().tasks;
+    let mut h = TokioRuntime::box_task(Box::pin(async { 5 }));
+    assert_eq!(TokioRuntime::unbox_mut_task(&mut h).join().await, Ok(5));
+    let after = TokioRuntime::metrics().tasks;
+    // Other tests may spawn tasks at the same time.
+    assert!(after.spawned > before.spawned);
+    assert!(after.finished > before.finished);
+}
@@ -0,0 +1,41 @@
+use base::AsyncMutex;
+use tokio::sync;
+
+#[derive(Default)]
+pub struct TokioMutexWrapper<T> {
+    mutex: sync::Mutex<T>,
+}
+
+impl<T> TokioMutexWrapper<T> {
+    /// Lock from synchronous code. This panics if called from within an
+    /// async context. See [tokio::sync::Mutex::blocking_lock]. To get the
+    /// wrapper from a boxed mutex, use [implbox::ImplBox::downcast_ref].
+    pub fn blocking_lock(&self) -> sync::MutexGuard<'_, T> {
+        self.mutex.blocking_lock()
+    }
+}
+
+impl<T: Sync + Send> AsyncMutex<T> for TokioMutexWrapper<T> {
+    type Guard<'a>
+        = sync::MutexGuard<'a, T>
+    where
+        Self: 'a;
+
+    fn new(item: T) -> Self {
+        TokioMutexWrapper {
+            mutex: sync::Mutex::new(item),
+        }
+    }
+
+    async fn lock(&self) -> sync::MutexGuard<'_, T> {
+        base::trace_future!(
+            self.mutex.lock(),
+            "mutex.lock",
+            item = std::any::type_name::<T>()
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,58 @@
+use super::*;
+use crate::TokioRuntime;
+use base::{Locker, MutexBox};
+use implbox::ImplBox;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::task;
+
+async fn generic_incr<M: AsyncMutex<i32>>(m: &M) -> i32 {
+    let mut guard = m.lock().await;
+    // non-Send Future
+    async move { std::ptr::null::<*const ()>() }.await;
+    *guard += 1;
+    *guard
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_basic() {
+    let b: Arc<ImplBox<MutexBox<i32>>> = Arc::new(TokioRuntime::box_mutex(1));
+    assert_eq!(generic_incr(TokioRuntime::unbox_mutex(&b)).await, 2);
+    let b2 = b.clone();
+    let h = task::spawn(async move { generic_incr(TokioRuntime::unbox_mutex(&b2)).await });
+    assert_eq!(h.await.unwrap(), 3);
+    assert_eq!(*TokioRuntime::unbox_mutex(&b).lock().await, 3);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_lock() {
+    // Exercise non-trivial case of waiting for the mutex.
+    let m1 = Arc::new(TokioRuntime::new_mutex(5));
+    let (tx, rx) = oneshot::channel::<()>();
+    let m2 = m1.clone();
+    let h1 = task::spawn(async move {
+        let mut guard = m2.lock().await;
+        tx.send(()).unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        *guard = 10;
+    });
+    let m2 = m1.clone();
+    let h2 = task::spawn(async move {
+        rx.await.unwrap();
+        let mut guard = m2.lock().await;
+        assert_eq!(*guard, 10);
+        *guard = 11;
+    });
+    h1.await.unwrap();
+    h2.await.unwrap();
+    assert_eq!(*m1.lock().await, 11);
+}
+
+#[test]
+fn test_downcast() {
+    let b = TokioRuntime::box_mutex(5);
+    let m = b.downcast_ref::<TokioMutexWrapper<i32>>().unwrap();
+    *m.blocking_lock() += 1;
+    assert_eq!(*m.blocking_lock(), 6);
+}
@@ -0,0 +1,82 @@
+use base::{AsyncTcpListener, AsyncTcpStream, AsyncUdpSocket};
+use std::io;
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+
+pub struct TokioTcpStream(TcpStream);
+
+impl TokioTcpStream {
+    pub async fn connect(addr: SocketAddr) -> io::Result<Self> {
+        let stream = base::trace_future!(TcpStream::connect(addr), "tcp.connect").await?;
+        Ok(TokioTcpStream(stream))
+    }
+}
+
+impl AsyncTcpStream for TokioTcpStream {
+    async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        base::trace_future!(self.0.read(buf), "tcp.read").await
+    }
+
+    async fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        base::trace_future!(self.0.write(buf), "tcp.write").await
+    }
+
+    async fn shutdown(&mut self) -> io::Result<()> {
+        self.0.shutdown().await
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.0.local_addr()
+    }
+
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.0.peer_addr()
+    }
+}
+
+pub struct TokioTcpListener(TcpListener);
+
+impl TokioTcpListener {
+    pub async fn bind(addr: SocketAddr) -> io::Result<Self> {
+        Ok(TokioTcpListener(TcpListener::bind(addr).await?))
+    }
+}
+
+impl AsyncTcpListener for TokioTcpListener {
+    async fn accept(
+        &self,
+    ) -> io::Result<(impl AsyncTcpStream + Send + Sync + 'static, SocketAddr)> {
+        let (stream, addr) = base::trace_future!(self.0.accept(), "tcp.accept").await?;
+        Ok((TokioTcpStream(stream), addr))
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.0.local_addr()
+    }
+}
+
+pub struct TokioUdpSocket(UdpSocket);
+
+impl TokioUdpSocket {
+    pub async fn bind(addr: SocketAddr) -> io::Result<Self> {
+        Ok(TokioUdpSocket(UdpSocket::bind(addr).await?))
+    }
+}
+
+impl AsyncUdpSocket for TokioUdpSocket {
+    async fn send_to(&self, buf: &[u8], target: SocketAddr) -> io::Result<usize> {
+        base::trace_future!(self.0.send_to(buf, target), "udp.send_to").await
+    }
+
+    async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        base::trace_future!(self.0.recv_from(buf), "udp.recv_from").await
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.0.local_addr()
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,73 @@
+use super::*;
+use crate::TokioRuntime;
+use base::Runtime;
+
+#[tokio::test]
+async fn test_echo() {
+    let listener = TokioRuntime::box_tcp_listener("127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap();
+    let addr = TokioRuntime::unbox_tcp_listener(&listener)
+        .local_addr()
+        .unwrap();
+    let server = tokio::spawn(async move {
+        let (mut stream, peer) = TokioRuntime::unbox_tcp_listener(&listener)
+            .accept()
+            .await
+            .unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), peer);
+        let mut buf = [0; 16];
+        loop {
+            let n = stream.read(&mut buf).await.unwrap();
+            if n == 0 {
+                break;
+            }
+            stream.write(&buf[..n]).await.unwrap();
+        }
+        stream.shutdown().await.unwrap();
+    });
+
+    let mut client = TokioRuntime::box_tcp_stream(addr).await.unwrap();
+    let stream = TokioRuntime::unbox_mut_tcp_stream(&mut client);
+    assert_eq!(stream.peer_addr().unwrap(), addr);
+    assert_eq!(stream.write(b"potato").await.unwrap(), 6);
+    let mut buf = [0; 6];
+    let mut got = 0;
+    while got < buf.len() {
+        got += stream.read(&mut buf[got..]).await.unwrap();
+    }
+    assert_eq!(&buf, b"potato");
+    stream.shutdown().await.unwrap();
+    // The server shuts down once it sees the end of our stream.
+    assert_eq!(stream.read(&mut buf).await.unwrap(), 0);
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_connect_refused() {
+    // Bind and drop a listener to find a port with nothing listening.
+    let addr = TokioTcpListener::bind("127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    assert!(TokioRuntime::box_tcp_stream(addr).await.is_err());
+}
+
+#[tokio::test]
+async fn test_udp() {
+    let any = "127.0.0.1:0".parse().unwrap();
+    let a = TokioRuntime::box_udp_socket(any).await.unwrap();
+    let b = TokioRuntime::box_udp_socket(any).await.unwrap();
+    let a = TokioRuntime::unbox_udp_socket(&a);
+    let b = TokioRuntime::unbox_udp_socket(&b);
+    let a_addr = a.local_addr().unwrap();
+    let b_addr = b.local_addr().unwrap();
+    assert_eq!(a.send_to(b"ping", b_addr).await.unwrap(), 4);
+    let mut buf = [0; 16];
+    let (n, from) = b.recv_from(&mut buf).await.unwrap();
+    assert_eq!((&buf[..n], from), (&b"ping"[..], a_addr));
+    b.send_to(b"pong", from).await.unwrap();
+    let (n, from) = a.recv_from(&mut buf).await.unwrap();
+    assert_eq!((&buf[..n], from), (&b"pong"[..], b_addr));
+}
@@ -0,0 +1,28 @@
+use base::AsyncNotify;
+use std::future::Future;
+use tokio::sync::Notify;
+
+pub struct TokioNotify(Notify);
+
+impl AsyncNotify for TokioNotify {
+    fn new() -> Self {
+        TokioNotify(Notify::new())
+    }
+
+    fn notify_one(&self) {
+        self.0.notify_one()
+    }
+
+    fn notify_waiters(&self) {
+        self.0.notify_waiters()
+    }
+
+    // This isn't an async fn since the Notified future has to be created
+    // right away to receive notify_waiters.
+    fn notified(&self) -> impl Future<Output = ()> + Send + '_ {
+        base::trace_future!(self.0.notified(), "notify.notified")
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,54 @@
+use super::*;
+use crate::TokioRuntime;
+use base::{Locker, NotifyBox};
+use implbox::ImplBoxShared;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::task;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_worker() {
+    let n: ImplBoxShared<NotifyBox> = TokioRuntime::box_shared_notify();
+    let queue = Arc::new(Mutex::new(VecDeque::new()));
+    let worker = {
+        let n = n.clone();
+        let queue = queue.clone();
+        task::spawn(async move {
+            let n = TokioRuntime::unbox_notify(&n);
+            let mut done = Vec::new();
+            loop {
+                let notified = n.notified();
+                let item = queue.lock().unwrap().pop_front();
+                match item {
+                    Some(None) => return done,
+                    Some(Some(item)) => done.push(item),
+                    None => notified.await,
+                }
+            }
+        })
+    };
+    let n = TokioRuntime::unbox_notify(&n);
+    for i in 0..10 {
+        queue.lock().unwrap().push_back(Some(i));
+        n.notify_one();
+        task::yield_now().await;
+    }
+    queue.lock().unwrap().push_back(None);
+    n.notify_one();
+    assert_eq!(worker.await.unwrap(), (0..10).collect::<Vec<_>>());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_notify_waiters() {
+    let n = TokioNotify::new();
+    // Nothing is stored for waiters that don't exist yet.
+    n.notify_waiters();
+    let a = n.notified();
+    let b = n.notified();
+    n.notify_waiters();
+    a.await;
+    b.await;
+    // A permit is stored for the next waiter.
+    n.notify_one();
+    n.notified().await;
+}
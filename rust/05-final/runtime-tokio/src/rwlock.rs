@@ -1,25 +1,275 @@
-use base::AsyncRwLock;
-use std::ops::{Deref, DerefMut};
+#[cfg(feature = "metrics")]
+use crate::metrics::{Held, Hold, LockStats};
+use base::{AsyncRwLock, Elapsed, LockPolicy, Upgradable};
+use std::future::Future;
+use std::ops::Deref;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync;
 
-#[derive(Default)]
+/// Without the `metrics` feature, guards are tokio's own. With it, they
+/// are wrapped so that dropping them records the hold time.
+#[cfg(not(feature = "metrics"))]
+type Held<G> = G;
+
+/// The most readers tokio allows. It panics if asked for more.
+const MAX_READERS: u32 = u32::MAX >> 3;
+
+/// Whether an acquisition is for reading or writing
+#[derive(Clone, Copy)]
+pub(crate) enum Access {
+    Read,
+    Write,
+}
+
+/// The lock is in an [Arc] so that owned guards can share it. tokio has no
+/// upgradable reads, so the `upgrade` mutex emulates them. Writers and
+/// upgradable readers hold it while acquiring the lock, so while an
+/// upgradable reader has the lock, no writer can be waiting for it, and
+/// the upgrade is next in line once the plain readers leave.
+///
+/// tokio's lock is fair: waiters get it in the order they asked for it,
+/// so a waiting writer keeps later readers out. A [LockPolicy] that
+/// prefers readers can't change that, but its reader limit is honored.
 pub struct TokioLockWrapper<T> {
-    lock: sync::RwLock<T>,
+    lock: Arc<sync::RwLock<T>>,
+    upgrade: sync::Mutex<()>,
+    #[cfg(feature = "metrics")]
+    stats: Arc<LockStats>,
 }
 
-impl<T: Sync + Send> AsyncRwLock<T> for TokioLockWrapper<T> {
-    fn new(item: T) -> Self {
+impl<T: Default> Default for TokioLockWrapper<T> {
+    fn default() -> Self {
+        Self::with_item(T::default())
+    }
+}
+
+impl<T> TokioLockWrapper<T> {
+    fn with_item(item: T) -> Self {
+        Self::with_lock(sync::RwLock::new(item))
+    }
+
+    /// Create a lock that allows at most `policy.max_readers` readers,
+    /// but at least one and no more than tokio supports. The preference
+    /// is ignored.
+    pub fn with_policy(policy: LockPolicy, item: T) -> Self {
+        let lock = match policy.max_readers {
+            None => sync::RwLock::new(item),
+            Some(n) => sync::RwLock::with_max_readers(item, n.clamp(1, MAX_READERS)),
+        };
+        Self::with_lock(lock)
+    }
+
+    fn with_lock(lock: sync::RwLock<T>) -> Self {
         TokioLockWrapper {
-            lock: sync::RwLock::new(item),
+            lock: Arc::new(lock),
+            upgrade: sync::Mutex::new(()),
+            #[cfg(feature = "metrics")]
+            stats: crate::metrics::lock_stats::<T>(),
         }
     }
 
-    async fn read(&self) -> impl Deref<Target = T> + Sync + Send {
-        self.lock.read().await
+    /// Run `acquire`, which locks for writing, while holding `upgrade`.
+    async fn exclusive<G>(&self, acquire: impl Future<Output = G>) -> G {
+        let _upgrade = self.upgrade.lock().await;
+        acquire.await
+    }
+
+    /// Run `acquire`, recording how long it waits with the `metrics`
+    /// feature.
+    async fn metered<G>(&self, access: Access, acquire: impl Future<Output = G>) -> Held<G> {
+        #[cfg(feature = "metrics")]
+        return Held::acquire(&self.stats, access, acquire).await;
+        #[cfg(not(feature = "metrics"))]
+        {
+            let _ = access;
+            acquire.await
+        }
+    }
+
+    /// Record an acquisition that didn't wait with the `metrics` feature.
+    fn held<G>(&self, access: Access, guard: G) -> Held<G> {
+        #[cfg(feature = "metrics")]
+        return Held::new(&self.stats, access, guard);
+        #[cfg(not(feature = "metrics"))]
+        {
+            let _ = access;
+            guard
+        }
+    }
+}
+
+/// The guard returned by [AsyncRwLock::upgradable_read]. It keeps holding
+/// the wrapper's upgrade mutex until the upgrade has the write lock.
+pub struct TokioUpgradableReadGuard<'a, T> {
+    lock: &'a sync::RwLock<T>,
+    read: sync::RwLockReadGuard<'a, T>,
+    upgrade: sync::MutexGuard<'a, ()>,
+    #[cfg(feature = "metrics")]
+    hold: Hold,
+}
+
+impl<T> Deref for TokioUpgradableReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.read
+    }
+}
+
+impl<'a, T: Sync + Send> Upgradable<'a, T> for TokioUpgradableReadGuard<'a, T> {
+    type WriteGuard = Held<sync::RwLockWriteGuard<'a, T>>;
+
+    async fn upgrade(self) -> Held<sync::RwLockWriteGuard<'a, T>> {
+        let Self {
+            lock,
+            read,
+            upgrade,
+            #[cfg(feature = "metrics")]
+            hold,
+        } = self;
+        drop(read);
+        #[cfg(feature = "metrics")]
+        let stats = hold.release();
+        #[cfg(feature = "metrics")]
+        let write = Held::acquire(&stats, Access::Write, lock.write());
+        #[cfg(not(feature = "metrics"))]
+        let write = lock.write();
+        let write =
+            base::trace_future!(write, "lock.upgrade", item = std::any::type_name::<T>()).await;
+        drop(upgrade);
+        write
+    }
+}
+
+impl<T: Sync + Send + 'static> AsyncRwLock<T> for TokioLockWrapper<T> {
+    type ReadGuard<'a>
+        = Held<sync::RwLockReadGuard<'a, T>>
+    where
+        Self: 'a;
+    type WriteGuard<'a>
+        = Held<sync::RwLockWriteGuard<'a, T>>
+    where
+        Self: 'a;
+    type UpgradableReadGuard<'a>
+        = TokioUpgradableReadGuard<'a, T>
+    where
+        Self: 'a;
+    type OwnedReadGuard = Held<sync::OwnedRwLockReadGuard<T>>;
+    type OwnedWriteGuard = Held<sync::OwnedRwLockWriteGuard<T>>;
+
+    fn new(item: T) -> Self {
+        Self::with_item(item)
+    }
+
+    async fn read(&self) -> Held<sync::RwLockReadGuard<'_, T>> {
+        base::trace_future!(
+            self.metered(Access::Read, self.lock.read()),
+            "lock.read",
+            item = std::any::type_name::<T>()
+        )
+        .await
+    }
+
+    async fn write(&self) -> Held<sync::RwLockWriteGuard<'_, T>> {
+        base::trace_future!(
+            self.metered(Access::Write, self.exclusive(self.lock.write())),
+            "lock.write",
+            item = std::any::type_name::<T>()
+        )
+        .await
+    }
+
+    async fn upgradable_read(&self) -> TokioUpgradableReadGuard<'_, T> {
+        let acquire = async {
+            let upgrade = self.upgrade.lock().await;
+            let read = self.lock.read().await;
+            (upgrade, read)
+        };
+        let acquire = async {
+            #[cfg(feature = "metrics")]
+            let Held {
+                guard: (upgrade, read),
+                hold,
+            } = self.metered(Access::Read, acquire).await;
+            #[cfg(not(feature = "metrics"))]
+            let (upgrade, read) = self.metered(Access::Read, acquire).await;
+            TokioUpgradableReadGuard {
+                lock: &self.lock,
+                read,
+                upgrade,
+                #[cfg(feature = "metrics")]
+                hold,
+            }
+        };
+        base::trace_future!(
+            acquire,
+            "lock.upgradable_read",
+            item = std::any::type_name::<T>()
+        )
+        .await
+    }
+
+    async fn read_owned(&self) -> Held<sync::OwnedRwLockReadGuard<T>> {
+        base::trace_future!(
+            self.metered(Access::Read, self.lock.clone().read_owned()),
+            "lock.read_owned",
+            item = std::any::type_name::<T>()
+        )
+        .await
+    }
+
+    async fn write_owned(&self) -> Held<sync::OwnedRwLockWriteGuard<T>> {
+        base::trace_future!(
+            self.metered(
+                Access::Write,
+                self.exclusive(self.lock.clone().write_owned())
+            ),
+            "lock.write_owned",
+            item = std::any::type_name::<T>()
+        )
+        .await
+    }
+
+    /// This panics if called from within an async context. See
+    /// [tokio::sync::RwLock::blocking_read]. With the `metrics` feature,
+    /// blocking acquisitions are counted as not waiting.
+    fn blocking_read(&self) -> Held<sync::RwLockReadGuard<'_, T>> {
+        self.held(Access::Read, self.lock.blocking_read())
+    }
+
+    fn blocking_write(&self) -> Held<sync::RwLockWriteGuard<'_, T>> {
+        let _upgrade = self.upgrade.blocking_lock();
+        self.held(Access::Write, self.lock.blocking_write())
+    }
+
+    fn try_read(&self) -> Option<Held<sync::RwLockReadGuard<'_, T>>> {
+        let guard = self.lock.try_read().ok()?;
+        Some(self.held(Access::Read, guard))
+    }
+
+    fn try_write(&self) -> Option<Held<sync::RwLockWriteGuard<'_, T>>> {
+        let _upgrade = self.upgrade.try_lock().ok()?;
+        let guard = self.lock.try_write().ok()?;
+        Some(self.held(Access::Write, guard))
+    }
+
+    async fn read_timeout(
+        &self,
+        duration: Duration,
+    ) -> Result<Held<sync::RwLockReadGuard<'_, T>>, Elapsed> {
+        tokio::time::timeout(duration, self.read())
+            .await
+            .map_err(|_| Elapsed)
     }
 
-    async fn write(&self) -> impl DerefMut<Target = T> + Sync + Send {
-        self.lock.write().await
+    async fn write_timeout(
+        &self,
+        duration: Duration,
+    ) -> Result<Held<sync::RwLockWriteGuard<'_, T>>, Elapsed> {
+        tokio::time::timeout(duration, self.write())
+            .await
+            .map_err(|_| Elapsed)
     }
 }
 
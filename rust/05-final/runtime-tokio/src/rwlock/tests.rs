@@ -1,8 +1,9 @@
 use super::*;
 use crate::TokioRuntime;
-use base::{LockBox, Locker};
+use base::{Elapsed, LockBox, LockPolicy, Locker, MappedReadGuard, MappedWriteGuard, Upgradable};
 use implbox::ImplBox;
 use std::marker::PhantomData;
+use std::pin::pin;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::oneshot;
@@ -119,3 +120,212 @@ async fn test_locker() {
     async {}.await;
     assert_eq!(th.do_thing().await, 6);
 }
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_try() {
+    let b = TokioRuntime::box_lock(1);
+    let lock = TokioRuntime::unbox_lock(&b);
+    {
+        let r1 = lock.try_read().unwrap();
+        let r2 = lock.try_read().unwrap();
+        assert_eq!(*r1 + *r2, 2);
+        assert!(lock.try_write().is_none());
+    }
+    {
+        let mut w = lock.try_write().unwrap();
+        *w = 2;
+        assert!(lock.try_read().is_none());
+        assert!(lock.try_write().is_none());
+    }
+    assert_eq!(*lock.try_read().unwrap(), 2);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_policy() {
+    let policy = LockPolicy {
+        max_readers: Some(2),
+        ..Default::default()
+    };
+    let b = TokioRuntime::box_lock_with(policy, 1);
+    let lock = TokioRuntime::unbox_lock_with(&b);
+    let r1 = lock.read().await;
+    let r2 = lock.try_read().unwrap();
+    assert!(lock.try_read().is_none());
+    assert_eq!(*r1 + *r2, 2);
+    drop(r1);
+    assert!(lock.try_read().is_some());
+    drop(r2);
+    *lock.write().await += 1;
+    assert_eq!(*lock.read().await, 2);
+
+    // A limit of zero would keep every reader out, so it means one.
+    let policy = LockPolicy {
+        max_readers: Some(0),
+        ..Default::default()
+    };
+    let lock = TokioRuntime::new_lock_with(policy, 1);
+    let _r = lock.read().await;
+    assert!(lock.try_read().is_none());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_owned() {
+    let b = TokioRuntime::box_lock(1);
+    let lock = TokioRuntime::unbox_lock(&b);
+    let mut w = lock.write_owned().await;
+    let (tx, rx) = oneshot::channel::<()>();
+    // The guard moves into the task, which releases the lock when it is
+    // told to.
+    let h = task::spawn(async move {
+        rx.await.unwrap();
+        *w += 1;
+    });
+    assert!(lock.try_read().is_none());
+    tx.send(()).unwrap();
+    h.await.unwrap();
+    let r = lock.read_owned().await;
+    // The guard keeps the item alive after the lock is dropped.
+    drop(b);
+    assert_eq!(*r, 2);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_upgradable() {
+    let lock = TokioRuntime::new_lock(1);
+    let short = Duration::from_millis(10);
+    let u = lock.upgradable_read().await;
+    // Plain readers share the lock, but writers and other upgradable
+    // readers wait.
+    let r = lock.try_read().unwrap();
+    assert!(lock.try_write().is_none());
+    assert!(lock.write_timeout(short).await.is_err());
+    assert!(tokio::time::timeout(short, lock.upgradable_read())
+        .await
+        .is_err());
+    // The upgrade waits for the plain reader.
+    let mut upgrade = pin!(u.upgrade());
+    assert!(tokio::time::timeout(short, upgrade.as_mut()).await.is_err());
+    drop(r);
+    let mut w = upgrade.await;
+    *w += 1;
+    drop(w);
+    assert_eq!(*lock.read().await, 2);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_map() {
+    let b = TokioRuntime::box_lock((1, String::from("potato")));
+    let lock = TokioRuntime::unbox_lock(&b);
+    {
+        let mut w = MappedWriteGuard::map(lock.write().await, |t| &mut t.1);
+        w.push_str("es");
+        // A mapped guard is still a lock guard.
+        assert!(lock.try_read().is_none());
+    }
+    let name = MappedReadGuard::map(lock.read().await, |t| t.1.as_str());
+    let r = match MappedReadGuard::try_map(lock.read().await, |t| t.1.strip_prefix("tomato")) {
+        Ok(_) => panic!("wrong prefix"),
+        Err(r) => r,
+    };
+    assert_eq!(r.0, 1);
+    // Owned guards can be mapped and moved into tasks.
+    let n = MappedReadGuard::map(lock.read_owned().await, |t| &t.0);
+    let h = task::spawn(async move { *n + 1 });
+    assert_eq!(h.await.unwrap(), 2);
+    assert_eq!(&*name, "potatoes");
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_timeout() {
+    let lock = TokioRuntime::new_lock(1);
+    let short = Duration::from_millis(10);
+    {
+        let _r = lock.read().await;
+        assert!(lock.read_timeout(short).await.is_ok());
+        assert_eq!(lock.write_timeout(short).await.err(), Some(Elapsed));
+    }
+    {
+        let _w = lock.write().await;
+        assert_eq!(lock.read_timeout(short).await.err(), Some(Elapsed));
+    }
+    *lock.write_timeout(short).await.unwrap() = 2;
+    assert_eq!(*lock.read_timeout(short).await.unwrap(), 2);
+}
+
+#[test]
+fn test_blocking() {
+    let b = TokioRuntime::box_lock(5);
+    let lock = TokioRuntime::unbox_lock(&b);
+    *lock.blocking_write() += 1;
+    assert_eq!(*lock.blocking_read(), 6);
+    // The upgrade mutex is held only while acquiring.
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    assert_eq!(*rt.block_on(lock.write()), 6);
+}
+
+#[test]
+fn test_downcast() {
+    let b = TokioRuntime::box_lock(5);
+    let lock = b.downcast_ref::<TokioLockWrapper<i32>>().unwrap();
+    *lock.blocking_write() += 1;
+    assert_eq!(*lock.blocking_read(), 6);
+    assert!(b.downcast_ref::<TokioLockWrapper<u32>>().is_none());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_dyn() {
+    // Importing both traits would make `read` and `write` on the
+    // concrete locks in the other tests ambiguous.
+    use base::DynAsyncRwLock;
+    let locks: Arc<Vec<Box<dyn DynAsyncRwLock<i32> + Send + Sync>>> = Arc::new(vec![
+        Box::new(TokioRuntime::new_lock(1)),
+        Box::new(TokioLockWrapper::new(2)),
+    ]);
+    let l2 = locks.clone();
+    task::spawn(async move {
+        for l in l2.iter() {
+            let mut lock = l.write().await;
+            async {}.await;
+            *lock *= 10;
+        }
+    })
+    .await
+    .unwrap();
+    let mut total = 0;
+    for l in locks.iter() {
+        total += *l.read().await;
+    }
+    assert_eq!(total, 30);
+}
+
+/// Holds a read guard in a field, which requires naming its type
+struct Snapshot<'a, L: AsyncRwLock<i32> + 'a> {
+    guard: L::ReadGuard<'a>,
+}
+
+impl<'a, L: AsyncRwLock<i32> + 'a> Snapshot<'a, L> {
+    async fn new(lock: &'a L) -> Self {
+        Self {
+            guard: lock.read().await,
+        }
+    }
+
+    fn value(&self) -> i32 {
+        *self.guard
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_named_guard() {
+    let lock = TokioLockWrapper::new(7);
+    let s = Snapshot::new(&lock).await;
+    assert_eq!(s.value(), 7);
+    drop(s);
+    *lock.write().await += 1;
+
+    let b = TokioRuntime::box_lock(8);
+    let s = Snapshot::new(TokioRuntime::unbox_lock(&b)).await;
+    assert_eq!(s.value(), 8);
+}
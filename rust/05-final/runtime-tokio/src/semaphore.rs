@@ -0,0 +1,36 @@
+use base::AsyncSemaphore;
+use tokio::sync;
+
+pub struct TokioSemaphoreWrapper {
+    semaphore: sync::Semaphore,
+}
+
+impl AsyncSemaphore for TokioSemaphoreWrapper {
+    type Permit<'a> = sync::SemaphorePermit<'a>;
+
+    /// Create a semaphore with `permits` permits. This panics if `permits`
+    /// is more than [tokio::sync::Semaphore::MAX_PERMITS].
+    fn new(permits: usize) -> Self {
+        TokioSemaphoreWrapper {
+            semaphore: sync::Semaphore::new(permits),
+        }
+    }
+
+    async fn acquire(&self) -> sync::SemaphorePermit<'_> {
+        base::trace_future!(self.semaphore.acquire(), "semaphore.acquire")
+            .await
+            // The semaphore is never closed.
+            .expect("semaphore closed")
+    }
+
+    fn try_acquire(&self) -> Option<sync::SemaphorePermit<'_>> {
+        self.semaphore.try_acquire().ok()
+    }
+
+    fn permits(&self) -> usize {
+        self.semaphore.available_permits()
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,44 @@
+use super::*;
+use crate::TokioRuntime;
+use base::Locker;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task;
+
+#[test]
+fn test_try_acquire() {
+    let b = TokioRuntime::box_semaphore(2);
+    let s = TokioRuntime::unbox_semaphore(&b);
+    let p1 = s.try_acquire().unwrap();
+    let _p2 = s.try_acquire().unwrap();
+    assert_eq!(s.permits(), 0);
+    assert!(s.try_acquire().is_none());
+    drop(p1);
+    assert_eq!(s.permits(), 1);
+    assert!(b.downcast_ref::<TokioSemaphoreWrapper>().is_some());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_limit() {
+    // No more than two tasks hold a permit at once.
+    let s = Arc::new(TokioRuntime::new_semaphore(2));
+    let active = Arc::new(AtomicUsize::new(0));
+    let max = Arc::new(AtomicUsize::new(0));
+    let mut handles = Vec::new();
+    for _ in 0..8 {
+        let (s, active, max) = (s.clone(), active.clone(), max.clone());
+        handles.push(task::spawn(async move {
+            let _permit = s.acquire().await;
+            let n = active.fetch_add(1, Ordering::SeqCst) + 1;
+            max.fetch_max(n, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            active.fetch_sub(1, Ordering::SeqCst);
+        }));
+    }
+    for h in handles {
+        h.await.unwrap();
+    }
+    assert_eq!(max.load(Ordering::SeqCst), 2);
+    assert_eq!(s.permits(), 2);
+}
@@ -0,0 +1,35 @@
+use base::{JoinError, JoinHandle};
+use tokio::task;
+
+pub struct TokioJoinHandle<T>(task::JoinHandle<T>);
+
+impl<T> TokioJoinHandle<T> {
+    pub fn new(handle: task::JoinHandle<T>) -> Self {
+        Self(handle)
+    }
+}
+
+impl<T: Send> JoinHandle<T> for TokioJoinHandle<T> {
+    async fn join(&mut self) -> Result<T, JoinError> {
+        base::trace_future!(&mut self.0, "task.join")
+            .await
+            .map_err(|e| {
+                if e.is_cancelled() {
+                    JoinError::Cancelled
+                } else {
+                    JoinError::Panicked
+                }
+            })
+    }
+
+    fn abort(&self) {
+        self.0.abort()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.0.is_finished()
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,59 @@
+use crate::TokioRuntime;
+use base::{JoinError, JoinHandle, JoinHandleBox, Runtime};
+use implbox::ImplBox;
+use std::marker::PhantomData;
+use std::rc::Rc;
+use std::time::Duration;
+use tokio::task::LocalSet;
+
+/// Something that holds background work without knowing its runtime
+struct Refresher<R: Runtime> {
+    task: ImplBox<JoinHandleBox<usize>>,
+    _r: PhantomData<R>,
+}
+
+impl<R: Runtime> Refresher<R> {
+    fn new(n: usize) -> Self {
+        Self {
+            task: R::box_task(Box::pin(async move { n * 2 })),
+            _r: PhantomData,
+        }
+    }
+
+    async fn result(&mut self) -> Result<usize, JoinError> {
+        R::unbox_mut_task(&mut self.task).join().await
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_spawn() {
+    let mut r = Refresher::<TokioRuntime>::new(21);
+    assert_eq!(r.result().await, Ok(42));
+
+    let mut h = TokioRuntime::spawn(Box::pin(async { panic!("potato") }));
+    assert_eq!(h.join().await, Err::<(), _>(JoinError::Panicked));
+    assert!(h.is_finished());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_abort() {
+    let mut h = TokioRuntime::spawn(Box::pin(async {
+        tokio::time::sleep(Duration::from_secs(60)).await;
+    }));
+    assert!(!h.is_finished());
+    h.abort();
+    assert_eq!(h.join().await, Err(JoinError::Cancelled));
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_spawn_local() {
+    LocalSet::new()
+        .run_until(async {
+            // Rc can't be sent between threads.
+            let rc = Rc::new(5);
+            let mut h = TokioRuntime::box_local_task(Box::pin(async move { *rc + 1 }));
+            let h = TokioRuntime::unbox_mut_local_task(&mut h);
+            assert_eq!(h.join().await, Ok(6));
+        })
+        .await;
+}
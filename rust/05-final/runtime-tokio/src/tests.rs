@@ -0,0 +1,58 @@
+use super::*;
+use std::future::pending;
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_timeout() {
+    let d = Duration::from_millis(20);
+    assert_eq!(TokioRuntime::timeout(d, async { 5 }).await, Ok(5));
+    assert_eq!(
+        TokioRuntime::timeout(d, pending::<()>()).await,
+        Err(Elapsed)
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "current_thread")]
+async fn test_shutdown_signal() {
+    use std::future::poll_fn;
+    use std::pin::pin;
+    use std::task::Poll;
+    let mut tokio_wait = pin!(TokioRuntime::shutdown_signal());
+    let mut std_wait = pin!(base::std_shutdown_signal());
+    // Both must be waiting before the signal is raised, or it stops the
+    // test process. Polling once registers them.
+    poll_fn(|cx| {
+        assert!(tokio_wait.as_mut().poll(cx).is_pending());
+        assert!(std_wait.as_mut().poll(cx).is_pending());
+        Poll::Ready(())
+    })
+    .await;
+    signal_hook::low_level::raise(signal_hook::consts::SIGTERM).unwrap();
+    let d = Duration::from_secs(5);
+    TokioRuntime::timeout(d, tokio_wait).await.unwrap().unwrap();
+    TokioRuntime::timeout(d, std_wait).await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_stream() {
+    let c = TokioRuntime::box_shared_channel(Some(2));
+    let mut s = TokioRuntime::box_stream(c.clone());
+    let h = tokio::spawn(async move {
+        let c = TokioRuntime::unbox_channel(&c);
+        for i in 0..5 {
+            c.send(i).await.unwrap();
+        }
+        c.close();
+    });
+    let s = TokioRuntime::unbox_mut_stream(&mut s);
+    let mut items = Vec::new();
+    while let Some(i) = s.next().await {
+        items.push(i);
+    }
+    h.await.unwrap();
+    assert_eq!(items, vec![0, 1, 2, 3, 4]);
+    assert_eq!(s.next().await, None);
+    let mut s = base::IterStream::new(["potato"]);
+    assert_eq!(s.next().await, Some("potato"));
+    assert_eq!(s.next().await, None);
+}
@@ -0,0 +1,33 @@
+use base::Clock;
+#[cfg(any(test, feature = "test-util"))]
+use base::TestClock;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+/// The clock for [crate::TokioRuntime]. It follows tokio's time, so when
+/// time is paused, as with `#[tokio::test(start_paused = true)]`, it only
+/// moves when tokio auto-advances it or [base::TestClock::advance] is called.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TokioClock;
+
+impl Clock for TokioClock {
+    fn now(&self) -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send {
+        tokio::time::sleep(duration)
+    }
+}
+
+/// This requires the `test-util` feature. `advance` panics unless time is
+/// paused. See [tokio::time::advance].
+#[cfg(any(test, feature = "test-util"))]
+impl TestClock for TokioClock {
+    fn advance(&self, duration: Duration) -> impl Future<Output = ()> + Send {
+        tokio::time::advance(duration)
+    }
+}
+
+#[cfg(test)]
+mod tests;
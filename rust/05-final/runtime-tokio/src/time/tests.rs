@@ -0,0 +1,54 @@
+use super::*;
+use crate::TokioRuntime;
+use base::{Runtime, StdClock, VirtualClock};
+use std::future::poll_fn;
+use std::pin::{pin, Pin};
+use std::task::Poll;
+
+/// Poll `f` once and return the result whether or not it is ready.
+async fn poll_once<F: Future>(mut f: Pin<&mut F>) -> Poll<F::Output> {
+    poll_fn(|cx| Poll::Ready(f.as_mut().poll(cx))).await
+}
+
+#[tokio::test(flavor = "current_thread", start_paused = true)]
+async fn test_tokio() {
+    let clock = TokioRuntime::clock();
+    let start = clock.now();
+    let mut sleep = pin!(clock.sleep(Duration::from_secs(60)));
+    assert!(poll_once(sleep.as_mut()).await.is_pending());
+    clock.advance(Duration::from_secs(59)).await;
+    assert!(poll_once(sleep.as_mut()).await.is_pending());
+    clock.advance(Duration::from_secs(1)).await;
+    sleep.await;
+    assert_eq!(clock.now() - start, Duration::from_secs(60));
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_virtual() {
+    let clock = VirtualClock::new();
+    let start = clock.now();
+    let c2 = clock.clone();
+    let mut short = pin!(clock.sleep(Duration::from_secs(1)));
+    let mut long = pin!(clock.sleep(Duration::from_secs(10)));
+    assert!(poll_once(short.as_mut()).await.is_pending());
+    assert!(poll_once(long.as_mut()).await.is_pending());
+    // Clones share the time.
+    c2.advance(Duration::from_secs(5)).await;
+    assert_eq!(clock.now() - start, Duration::from_secs(5));
+    assert!(poll_once(short.as_mut()).await.is_ready());
+    assert!(poll_once(long.as_mut()).await.is_pending());
+    // Sleeps start at the current virtual time.
+    let mut again = pin!(clock.sleep(Duration::from_secs(5)));
+    assert!(poll_once(again.as_mut()).await.is_pending());
+    clock.advance(Duration::from_secs(5)).await;
+    assert!(poll_once(long.as_mut()).await.is_ready());
+    assert!(poll_once(again.as_mut()).await.is_ready());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_std() {
+    let clock = StdClock;
+    let start = clock.now();
+    clock.sleep(Duration::from_millis(10)).await;
+    assert!(clock.now() - start >= Duration::from_millis(10));
+}
@@ -0,0 +1,51 @@
+use base::{RecvError, Watch, WatchReceiver};
+use tokio::sync::watch;
+
+/// The sending half of a tokio watch channel. Receivers are created with
+/// [TokioWatch::subscribe].
+pub struct TokioWatch<T> {
+    tx: watch::Sender<T>,
+}
+
+impl<T> TokioWatch<T> {
+    pub fn subscribe(&self) -> TokioWatchReceiver<T> {
+        TokioWatchReceiver(self.tx.subscribe())
+    }
+}
+
+impl<T: Clone> Watch<T> for TokioWatch<T> {
+    fn new(initial: T) -> Self {
+        let (tx, _) = watch::channel(initial);
+        TokioWatch { tx }
+    }
+
+    fn send(&self, value: T) {
+        // Unlike `send`, this works when there are no receivers.
+        self.tx.send_replace(value);
+    }
+
+    fn get(&self) -> T {
+        self.tx.borrow().clone()
+    }
+
+    fn receiver_count(&self) -> usize {
+        self.tx.receiver_count()
+    }
+}
+
+pub struct TokioWatchReceiver<T>(watch::Receiver<T>);
+
+impl<T: Clone + Send + Sync> WatchReceiver<T> for TokioWatchReceiver<T> {
+    async fn changed(&mut self) -> Result<(), RecvError> {
+        base::trace_future!(self.0.changed(), "watch.changed")
+            .await
+            .map_err(|_| RecvError::Closed)
+    }
+
+    fn get(&mut self) -> T {
+        self.0.borrow_and_update().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests;
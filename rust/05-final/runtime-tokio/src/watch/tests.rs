@@ -0,0 +1,50 @@
+use crate::TokioRuntime;
+use base::{Locker, RecvError, Watch, WatchBox, WatchReceiver};
+use implbox::ImplBox;
+use tokio::task;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_watch() {
+    let w: ImplBox<WatchBox<String>> = TokioRuntime::box_watch("potato".to_string());
+    // Sending works without receivers.
+    TokioRuntime::unbox_watch(&w).send("salad".to_string());
+    let mut rx = TokioRuntime::box_watch_receiver(&w);
+    // A new receiver has already seen the current value.
+    assert_eq!(
+        TokioRuntime::unbox_mut_watch_receiver(&mut rx).get(),
+        "salad"
+    );
+    let h = task::spawn(async move {
+        let rx = TokioRuntime::unbox_mut_watch_receiver(&mut rx);
+        let mut seen = Vec::new();
+        while rx.changed().await.is_ok() {
+            seen.push(rx.get());
+        }
+        seen
+    });
+    let tx = TokioRuntime::unbox_watch(&w);
+    assert_eq!(tx.receiver_count(), 1);
+    tx.send("soup".to_string());
+    assert_eq!(tx.get(), "soup");
+    drop(w);
+    // The receiver may or may not have seen the change before the watch
+    // was dropped, but it must see the final value.
+    assert_eq!(h.await.unwrap(), vec!["soup".to_string()]);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_changed() {
+    let w = TokioRuntime::box_watch(1);
+    let mut boxed = TokioRuntime::box_watch_receiver(&w);
+    let rx = TokioRuntime::unbox_mut_watch_receiver(&mut boxed);
+    let tx = TokioRuntime::unbox_watch(&w);
+    tx.send(2);
+    tx.send(3);
+    // Only the latest value is seen.
+    rx.changed().await.unwrap();
+    assert_eq!(rx.get(), 3);
+    tx.send(4);
+    assert_eq!(rx.get(), 4);
+    drop(w);
+    assert_eq!(rx.changed().await, Err(RecvError::Closed));
+}
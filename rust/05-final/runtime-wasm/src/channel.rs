@@ -0,0 +1,56 @@
+use async_channel::{Receiver, Sender};
+use base::{AsyncChannel, SendError, TryRecvError};
+
+/// An async-channel channel, which needs no threads, with both of
+/// its halves. Closing the sender closes the channel for both, and the
+/// receiver returns `None` once the channel is drained. Receivers take
+/// turns, so [AsyncChannel::try_recv] can take an item that a waiting
+/// receiver would otherwise have gotten.
+pub struct WasmChannel<T> {
+    tx: Sender<T>,
+    rx: Receiver<T>,
+}
+
+impl<T: Send> AsyncChannel<T> for WasmChannel<T> {
+    fn new(capacity: Option<usize>) -> Self {
+        let (tx, rx) = match capacity {
+            Some(capacity) => async_channel::bounded(capacity),
+            None => async_channel::unbounded(),
+        };
+        WasmChannel { tx, rx }
+    }
+
+    async fn send(&self, item: T) -> Result<(), SendError<T>> {
+        base::trace_future!(self.tx.send(item), "channel.send")
+            .await
+            .map_err(|e| SendError::Closed(e.0))
+    }
+
+    fn try_send(&self, item: T) -> Result<(), SendError<T>> {
+        self.tx.try_send(item).map_err(|e| match e {
+            async_channel::TrySendError::Full(item) => SendError::Full(item),
+            async_channel::TrySendError::Closed(item) => SendError::Closed(item),
+        })
+    }
+
+    async fn recv(&self) -> Option<T> {
+        base::trace_future!(self.rx.recv(), "channel.recv")
+            .await
+            .ok()
+    }
+
+    fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.rx.try_recv().map_err(|e| match e {
+            async_channel::TryRecvError::Empty => TryRecvError::Empty,
+            async_channel::TryRecvError::Closed => TryRecvError::Closed,
+        })
+    }
+
+    fn close(&self) {
+        self.tx.close();
+    }
+
+    fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+}
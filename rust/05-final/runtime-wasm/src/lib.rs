@@ -0,0 +1,209 @@
+//! An implementation of the [base] runtime traits for
+//! wasm32-unknown-unknown, so [Runtime]-generic code such as
+//! `Controller` can run in the browser or in Node and be called from
+//! JavaScript, or from any other language whose WASM host can await a
+//! promise. Wrap a call with `wasm_bindgen_futures::future_to_promise`
+//! to hand it to JavaScript.
+//!
+//! There is only one thread, and it must not block, so:
+//! - tasks run on the JavaScript event loop with
+//!   `wasm_bindgen_futures::spawn_local`, and [Runtime::spawn] and
+//!   [Runtime::spawn_local] are the same
+//! - locks are single-threaded and never block. Their `blocking_*`
+//!   functions panic if the lock is held.
+//! - there is no executor, so [Runtime::new_executor] fails
+//! - timers are the browser's, but std has no clock, so
+//!   [base::Clock::now] panics
+//! - there are no sockets or files, so creating them fails. See
+//!   [unsupported::Unsupported].
+//!
+//! Semaphores and channels come from async-lock, async-channel, and
+//! async-broadcast, which need no threads. Notify, watch, barrier, and
+//! cancel token are the executor-independent ones from [base].
+use crate::broadcast::{WasmBroadcast, WasmBroadcastReceiver};
+use crate::channel::WasmChannel;
+use crate::rwlock::{WasmLockWrapper, WasmMutexWrapper};
+use crate::semaphore::WasmSemaphoreWrapper;
+use crate::task::WasmJoinHandle;
+use crate::time::WasmClock;
+use crate::unsupported::Unsupported;
+use base::{
+    AsyncBarrier, AsyncChannel, AsyncFile, AsyncMutex, AsyncNotify, AsyncRwLock, AsyncSemaphore,
+    AsyncStream, AsyncTcpListener, AsyncTcpStream, AsyncUdpSocket, BarrierBox, BoxFuture,
+    Broadcast, BroadcastBox, BroadcastReceiver, BroadcastReceiverBox, CancelToken, CancelTokenBox,
+    ChannelBox, ChannelStream, Elapsed, FileBox, JoinHandle, JoinHandleBox, LocalBoxFuture,
+    LockBox, LockPolicy, Locker, MutexBox, NotifyBox, PolicyLockBox, Runtime, SemaphoreBox,
+    StdBarrier, StdCancelToken, StdExecutor, StdNotify, StdWatch, StdWatchReceiver, StreamBox,
+    TcpListenerBox, TcpStreamBox, UdpSocketBox, Watch, WatchBox, WatchReceiver, WatchReceiverBox,
+};
+use implbox::{ImplBox, ImplBoxShared};
+use implbox_macros::implbox_impls;
+use std::fs::OpenOptions;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::Duration;
+
+pub mod broadcast;
+pub mod channel;
+pub mod rwlock;
+pub mod semaphore;
+pub mod task;
+pub mod time;
+pub mod unsupported;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Default, Clone)]
+pub struct WasmRuntime;
+
+impl Locker for WasmRuntime {
+    #[implbox_impls(LockBox<T>, WasmLockWrapper<T>, downcast)]
+    fn new_lock<T: Sync + Send + 'static>(item: T) -> impl AsyncRwLock<T> {
+        WasmLockWrapper::<T>::new(item)
+    }
+
+    /// This lock always prefers readers and has no reader limit, so
+    /// the policy is ignored.
+    #[implbox_impls(PolicyLockBox<T>, WasmLockWrapper<T>, downcast)]
+    fn new_lock_with<T: Sync + Send + 'static>(
+        _policy: LockPolicy,
+        item: T,
+    ) -> impl AsyncRwLock<T> {
+        WasmLockWrapper::<T>::new(item)
+    }
+
+    #[implbox_impls(MutexBox<T>, WasmMutexWrapper<T>, downcast)]
+    fn new_mutex<T: Sync + Send + 'static>(item: T) -> impl AsyncMutex<T> {
+        WasmMutexWrapper::<T>::new(item)
+    }
+
+    #[implbox_impls(SemaphoreBox, WasmSemaphoreWrapper, downcast)]
+    fn new_semaphore(permits: usize) -> impl AsyncSemaphore {
+        WasmSemaphoreWrapper::new(permits)
+    }
+
+    #[implbox_impls(NotifyBox, StdNotify, downcast)]
+    fn new_notify() -> impl AsyncNotify {
+        StdNotify::new()
+    }
+
+    #[implbox_impls(BarrierBox, StdBarrier, downcast)]
+    fn new_barrier(n: usize) -> impl AsyncBarrier {
+        StdBarrier::new(n)
+    }
+
+    #[implbox_impls(ChannelBox<T>, WasmChannel<T>, downcast)]
+    fn new_channel<T: Send + 'static>(capacity: Option<usize>) -> impl AsyncChannel<T> {
+        WasmChannel::<T>::new(capacity)
+    }
+
+    #[implbox_impls(BroadcastBox<T>, WasmBroadcast<T>, downcast)]
+    fn new_broadcast<T: Clone + Sync + Send + 'static>(capacity: usize) -> impl Broadcast<T> {
+        WasmBroadcast::<T>::new(capacity)
+    }
+
+    #[implbox_impls(BroadcastReceiverBox<T>, WasmBroadcastReceiver<T>, downcast)]
+    fn new_broadcast_receiver<T: Clone + Sync + Send + 'static>(
+        broadcast: &ImplBox<BroadcastBox<T>>,
+    ) -> impl BroadcastReceiver<T> {
+        broadcast
+            .downcast_ref::<WasmBroadcast<T>>()
+            .expect("broadcast was not created by WasmRuntime")
+            .subscribe()
+    }
+
+    #[implbox_impls(WatchBox<T>, StdWatch<T>, downcast)]
+    fn new_watch<T: Clone + Sync + Send + 'static>(initial: T) -> impl Watch<T> {
+        StdWatch::<T>::new(initial)
+    }
+
+    #[implbox_impls(WatchReceiverBox<T>, StdWatchReceiver<T>, downcast)]
+    fn new_watch_receiver<T: Clone + Sync + Send + 'static>(
+        watch: &ImplBox<WatchBox<T>>,
+    ) -> impl WatchReceiver<T> {
+        watch
+            .downcast_ref::<StdWatch<T>>()
+            .expect("watch was not created by WasmRuntime")
+            .subscribe()
+    }
+
+    #[implbox_impls(StreamBox<T>, ChannelStream<T, WasmRuntime>, downcast)]
+    fn new_stream<T: Send + 'static>(channel: ImplBoxShared<ChannelBox<T>>) -> impl AsyncStream<T> {
+        ChannelStream::<T, Self>::new(channel)
+    }
+}
+
+impl Runtime for WasmRuntime {
+    /// Only named so the trait is satisfied. See [WasmRuntime::new_executor].
+    type Executor = StdExecutor;
+    type Clock = WasmClock;
+
+    /// The browser runs futures on its event loop, and its thread must
+    /// not block, so this always fails.
+    fn new_executor() -> io::Result<StdExecutor> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the browser's thread can't block; use wasm_bindgen_futures instead",
+        ))
+    }
+
+    fn clock() -> WasmClock {
+        WasmClock
+    }
+
+    #[implbox_impls(JoinHandleBox<T>, WasmJoinHandle<T>, downcast, name = "task")]
+    fn spawn<T: Send + 'static>(future: BoxFuture<'static, T>) -> impl JoinHandle<T> {
+        WasmJoinHandle::spawn(future)
+    }
+
+    #[implbox_impls(JoinHandleBox<T>, WasmJoinHandle<T>, downcast, name = "local_task")]
+    fn spawn_local<T: Send + 'static>(future: LocalBoxFuture<'static, T>) -> impl JoinHandle<T> {
+        WasmJoinHandle::spawn(future)
+    }
+
+    #[implbox_impls(CancelTokenBox, StdCancelToken, downcast)]
+    fn new_cancel_token(parent: Option<&ImplBox<CancelTokenBox>>) -> impl CancelToken + Clone {
+        match parent {
+            Some(parent) => parent
+                .downcast_ref::<StdCancelToken>()
+                .expect("cancel token was not created by WasmRuntime")
+                .child(),
+            None => StdCancelToken::default(),
+        }
+    }
+
+    #[implbox_impls(TcpStreamBox, Unsupported, downcast, name = "tcp_stream")]
+    async fn connect_tcp(_addr: SocketAddr) -> Result<impl AsyncTcpStream, io::Error> {
+        Err::<Unsupported, _>(Unsupported::error("TCP"))
+    }
+
+    #[implbox_impls(TcpListenerBox, Unsupported, downcast, name = "tcp_listener")]
+    async fn bind_tcp(_addr: SocketAddr) -> Result<impl AsyncTcpListener, io::Error> {
+        Err::<Unsupported, _>(Unsupported::error("TCP"))
+    }
+
+    #[implbox_impls(UdpSocketBox, Unsupported, downcast, name = "udp_socket")]
+    async fn bind_udp(_addr: SocketAddr) -> Result<impl AsyncUdpSocket, io::Error> {
+        Err::<Unsupported, _>(Unsupported::error("UDP"))
+    }
+
+    #[implbox_impls(FileBox, Unsupported, downcast, name = "file")]
+    async fn open_file(_path: &Path, _options: &OpenOptions) -> Result<impl AsyncFile, io::Error> {
+        Err::<Unsupported, _>(Unsupported::error("the file system"))
+    }
+
+    async fn timeout<F: Future + Send>(
+        duration: Duration,
+        future: F,
+    ) -> Result<F::Output, Elapsed> {
+        time::timeout(duration, future).await
+    }
+
+    /// A page has no signals, so this fails.
+    async fn shutdown_signal() -> io::Result<()> {
+        Err(Unsupported::error("signal handling"))
+    }
+}
@@ -0,0 +1,380 @@
+use base::{AsyncMutex, AsyncRwLock, Elapsed, Upgradable};
+use std::cell::UnsafeCell;
+use std::future::poll_fn;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, Waker};
+use std::time::Duration;
+
+#[derive(Clone, Copy)]
+enum Access {
+    Read,
+    Write,
+    Upgradable,
+    /// Turn the upgradable read into a write
+    Upgrade,
+}
+
+#[derive(Default)]
+struct LockState {
+    readers: usize,
+    writer: bool,
+    upgradable: bool,
+    /// Tasks waiting for the lock. They are all woken whenever it is
+    /// released and check again.
+    waiting: Vec<Waker>,
+}
+
+impl LockState {
+    fn available(&self, access: Access) -> bool {
+        match access {
+            Access::Read => !self.writer,
+            Access::Write => !self.writer && !self.upgradable && self.readers == 0,
+            Access::Upgradable => !self.writer && !self.upgradable,
+            Access::Upgrade => self.readers == 0,
+        }
+    }
+
+    fn take(&mut self, access: Access) {
+        match access {
+            Access::Read => self.readers += 1,
+            Access::Write => self.writer = true,
+            Access::Upgradable => self.upgradable = true,
+            Access::Upgrade => {
+                self.upgradable = false;
+                self.writer = true;
+            }
+        }
+    }
+
+    fn release(&mut self, access: Access) {
+        match access {
+            Access::Read => self.readers -= 1,
+            Access::Write | Access::Upgrade => self.writer = false,
+            Access::Upgradable => self.upgradable = false,
+        }
+    }
+}
+
+/// The state of a lock and the item it protects. The state says which
+/// guards exist, and a guard only gives access to the item in the way
+/// the state allows.
+struct Inner<T> {
+    state: Mutex<LockState>,
+    item: UnsafeCell<T>,
+}
+
+// SAFETY: Access to the item is controlled by the state, as for
+// std::sync::RwLock.
+unsafe impl<T: Send + Sync> Sync for Inner<T> {}
+
+impl<T> Inner<T> {
+    /// Wait until `access` is possible without blocking the thread.
+    async fn lock(&self, access: Access) {
+        poll_fn(|cx| {
+            let mut state = self.state.lock().unwrap();
+            if state.available(access) {
+                state.take(access);
+                return Poll::Ready(());
+            }
+            if !state.waiting.iter().any(|w| w.will_wake(cx.waker())) {
+                state.waiting.push(cx.waker().clone());
+            }
+            Poll::Pending
+        })
+        .await
+    }
+
+    fn try_lock(&self, access: Access) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let available = state.available(access);
+        if available {
+            state.take(access);
+        }
+        available
+    }
+
+    fn unlock(&self, access: Access) {
+        let waiting = {
+            let mut state = self.state.lock().unwrap();
+            state.release(access);
+            mem::take(&mut state.waiting)
+        };
+        for waker in waiting {
+            waker.wake();
+        }
+    }
+
+    /// SAFETY: The caller must hold a guard that allows reading.
+    unsafe fn item(&self) -> &T {
+        &*self.item.get()
+    }
+
+    /// SAFETY: The caller must hold a guard that allows writing, and this
+    /// must be the only reference to the item.
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn item_mut(&self) -> &mut T {
+        &mut *self.item.get()
+    }
+}
+
+/// A reader-writer lock for a single thread. Waiting never blocks the
+/// thread, since the browser's main thread must not block, and with only
+/// one thread, the std Mutex that holds the lock's state is never
+/// contended. It is still correct with more than one thread, so it can
+/// be tested natively. It doesn't prefer writers, so a steady stream of
+/// readers can keep a writer waiting.
+pub struct WasmLockWrapper<T> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T: Default> Default for WasmLockWrapper<T> {
+    fn default() -> Self {
+        Self::with_item(T::default())
+    }
+}
+
+impl<T> WasmLockWrapper<T> {
+    fn with_item(item: T) -> Self {
+        WasmLockWrapper {
+            inner: Arc::new(Inner {
+                state: Default::default(),
+                item: UnsafeCell::new(item),
+            }),
+        }
+    }
+
+    fn read_guard(&self) -> WasmReadGuard<'_, T> {
+        WasmReadGuard { inner: &self.inner }
+    }
+
+    fn write_guard(&self) -> WasmWriteGuard<'_, T> {
+        WasmWriteGuard { inner: &self.inner }
+    }
+}
+
+pub struct WasmReadGuard<'a, T> {
+    inner: &'a Inner<T>,
+}
+
+impl<T> Deref for WasmReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: This guard holds a read lock.
+        unsafe { self.inner.item() }
+    }
+}
+
+impl<T> Drop for WasmReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.inner.unlock(Access::Read);
+    }
+}
+
+pub struct WasmWriteGuard<'a, T> {
+    inner: &'a Inner<T>,
+}
+
+impl<T> Deref for WasmWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: This guard holds the write lock.
+        unsafe { self.inner.item() }
+    }
+}
+
+impl<T> DerefMut for WasmWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: This guard holds the write lock, and `&mut self` makes
+        // this the only reference.
+        unsafe { self.inner.item_mut() }
+    }
+}
+
+impl<T> Drop for WasmWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.inner.unlock(Access::Write);
+    }
+}
+
+/// The guard returned by [AsyncRwLock::upgradable_read]
+pub struct WasmUpgradableReadGuard<'a, T> {
+    inner: &'a Inner<T>,
+}
+
+impl<T> Deref for WasmUpgradableReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: This guard holds the upgradable read lock.
+        unsafe { self.inner.item() }
+    }
+}
+
+impl<T> Drop for WasmUpgradableReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.inner.unlock(Access::Upgradable);
+    }
+}
+
+impl<'a, T: Sync + Send + 'a> Upgradable<'a, T> for WasmUpgradableReadGuard<'a, T> {
+    type WriteGuard = WasmWriteGuard<'a, T>;
+
+    async fn upgrade(self) -> WasmWriteGuard<'a, T> {
+        let inner = self.inner;
+        // The upgradable lock becomes the write lock, so it must not be
+        // released.
+        mem::forget(self);
+        inner.lock(Access::Upgrade).await;
+        WasmWriteGuard { inner }
+    }
+}
+
+pub struct WasmOwnedReadGuard<T> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T> Deref for WasmOwnedReadGuard<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: This guard holds a read lock.
+        unsafe { self.inner.item() }
+    }
+}
+
+impl<T> Drop for WasmOwnedReadGuard<T> {
+    fn drop(&mut self) {
+        self.inner.unlock(Access::Read);
+    }
+}
+
+pub struct WasmOwnedWriteGuard<T> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T> Deref for WasmOwnedWriteGuard<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: This guard holds the write lock.
+        unsafe { self.inner.item() }
+    }
+}
+
+impl<T> DerefMut for WasmOwnedWriteGuard<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: As for WasmWriteGuard.
+        unsafe { self.inner.item_mut() }
+    }
+}
+
+impl<T> Drop for WasmOwnedWriteGuard<T> {
+    fn drop(&mut self) {
+        self.inner.unlock(Access::Write);
+    }
+}
+
+impl<T: Sync + Send + 'static> AsyncRwLock<T> for WasmLockWrapper<T> {
+    type ReadGuard<'a>
+        = WasmReadGuard<'a, T>
+    where
+        Self: 'a;
+    type WriteGuard<'a>
+        = WasmWriteGuard<'a, T>
+    where
+        Self: 'a;
+    type UpgradableReadGuard<'a>
+        = WasmUpgradableReadGuard<'a, T>
+    where
+        Self: 'a;
+    type OwnedReadGuard = WasmOwnedReadGuard<T>;
+    type OwnedWriteGuard = WasmOwnedWriteGuard<T>;
+
+    fn new(item: T) -> Self {
+        Self::with_item(item)
+    }
+
+    async fn read(&self) -> WasmReadGuard<'_, T> {
+        self.inner.lock(Access::Read).await;
+        self.read_guard()
+    }
+
+    async fn write(&self) -> WasmWriteGuard<'_, T> {
+        self.inner.lock(Access::Write).await;
+        self.write_guard()
+    }
+
+    async fn upgradable_read(&self) -> WasmUpgradableReadGuard<'_, T> {
+        self.inner.lock(Access::Upgradable).await;
+        WasmUpgradableReadGuard { inner: &self.inner }
+    }
+
+    async fn read_owned(&self) -> WasmOwnedReadGuard<T> {
+        self.inner.lock(Access::Read).await;
+        WasmOwnedReadGuard {
+            inner: self.inner.clone(),
+        }
+    }
+
+    async fn write_owned(&self) -> WasmOwnedWriteGuard<T> {
+        self.inner.lock(Access::Write).await;
+        WasmOwnedWriteGuard {
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// With only one thread, waiting for the lock would wait forever, so
+    /// this panics if the lock isn't available.
+    fn blocking_read(&self) -> WasmReadGuard<'_, T> {
+        self.try_read()
+            .expect("blocking_read would block the only thread")
+    }
+
+    /// Like [WasmLockWrapper::blocking_read], this panics if the lock
+    /// isn't available.
+    fn blocking_write(&self) -> WasmWriteGuard<'_, T> {
+        self.try_write()
+            .expect("blocking_write would block the only thread")
+    }
+
+    fn try_read(&self) -> Option<WasmReadGuard<'_, T>> {
+        self.inner.try_lock(Access::Read).then(|| self.read_guard())
+    }
+
+    fn try_write(&self) -> Option<WasmWriteGuard<'_, T>> {
+        self.inner
+            .try_lock(Access::Write)
+            .then(|| self.write_guard())
+    }
+
+    async fn read_timeout(&self, duration: Duration) -> Result<WasmReadGuard<'_, T>, Elapsed> {
+        crate::time::timeout(duration, self.read()).await
+    }
+
+    async fn write_timeout(&self, duration: Duration) -> Result<WasmWriteGuard<'_, T>, Elapsed> {
+        crate::time::timeout(duration, self.write()).await
+    }
+}
+
+/// A mutex is a lock that is only ever locked for writing.
+#[derive(Default)]
+pub struct WasmMutexWrapper<T>(WasmLockWrapper<T>);
+
+impl<T: Sync + Send + 'static> AsyncMutex<T> for WasmMutexWrapper<T> {
+    type Guard<'a>
+        = WasmWriteGuard<'a, T>
+    where
+        Self: 'a;
+
+    fn new(item: T) -> Self {
+        WasmMutexWrapper(WasmLockWrapper::with_item(item))
+    }
+
+    async fn lock(&self) -> WasmWriteGuard<'_, T> {
+        self.0.write().await
+    }
+}
@@ -0,0 +1,40 @@
+use async_channel::Receiver;
+use base::{AbortHandle, JoinError, JoinHandle};
+use std::future::Future;
+
+/// The browser's event loop runs tasks on the one thread, so every task
+/// is a local task. The output comes back over a channel since
+/// wasm-bindgen-futures doesn't return a handle.
+pub struct WasmJoinHandle<T> {
+    output: Receiver<Result<T, JoinError>>,
+    abort: AbortHandle,
+}
+
+impl<T: 'static> WasmJoinHandle<T> {
+    pub fn spawn(future: impl Future<Output = T> + Unpin + 'static) -> Self {
+        let (future, abort) = base::abortable(future);
+        let (tx, output) = async_channel::bounded(1);
+        wasm_bindgen_futures::spawn_local(async move {
+            // Nobody is waiting if the handle was dropped.
+            let _ = tx.send(future.await).await;
+        });
+        Self { output, abort }
+    }
+}
+
+impl<T: Send + 'static> JoinHandle<T> for WasmJoinHandle<T> {
+    async fn join(&mut self) -> Result<T, JoinError> {
+        let output = base::trace_future!(self.output.recv(), "task.join").await;
+        // The task can only go away without sending if the event loop
+        // dropped it.
+        output.unwrap_or(Err(JoinError::Cancelled))
+    }
+
+    fn abort(&self) {
+        self.abort.abort()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.abort.is_finished()
+    }
+}
@@ -0,0 +1,198 @@
+//! These run natively, so they leave out what needs JavaScript: tasks
+//! and timers.
+use super::*;
+use base::{Executor, RecvError, SendError, TryRecvError, Upgradable};
+use std::future::Future;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::sync::Arc;
+use std::time::Duration;
+
+type R = WasmRuntime;
+
+fn run<F: Future>(future: F) -> F::Output {
+    StdExecutor.block_on(future)
+}
+
+#[test]
+fn test_lock() {
+    run(async {
+        let l = R::box_lock(3);
+        let lock = R::unbox_lock(&l);
+        {
+            let r1 = lock.read().await;
+            let r2 = lock.try_read().unwrap();
+            assert_eq!(*r1 + *r2, 6);
+            assert!(lock.try_write().is_none());
+        }
+        *lock.write().await += 1;
+        let owned = lock.read_owned().await;
+        drop(l);
+        // The owned guard keeps the item alive.
+        assert_eq!(*owned, 4);
+    })
+}
+
+#[test]
+fn test_wait() {
+    // Waiting doesn't block the thread, and the waiter is woken when the
+    // lock is released.
+    let lock = Arc::new(R::new_lock(1));
+    let mut guard = run(lock.write_owned());
+    let reader = {
+        let lock = lock.clone();
+        std::thread::spawn(move || run(async { *lock.read().await }))
+    };
+    std::thread::sleep(Duration::from_millis(10));
+    *guard = 2;
+    drop(guard);
+    assert_eq!(reader.join().unwrap(), 2);
+}
+
+#[test]
+fn test_upgrade() {
+    run(async {
+        let lock = Arc::new(R::new_lock(1));
+        let upgradable = lock.upgradable_read().await;
+        // Plain readers can still share the lock.
+        assert_eq!(*lock.read().await, 1);
+        assert!(lock.try_write().is_none());
+        let mut w = upgradable.upgrade().await;
+        *w = 2;
+        drop(w);
+        assert_eq!(*lock.blocking_read(), 2);
+        let mut w = lock.write_owned().await;
+        *w = 3;
+        drop(w);
+        assert_eq!(*lock.read().await, 3);
+    })
+}
+
+#[test]
+fn test_mutex_and_semaphore() {
+    run(async {
+        let m = R::new_mutex(String::new());
+        m.lock().await.push_str("potato");
+        assert_eq!(*m.lock().await, "potato");
+
+        let s = R::new_semaphore(2);
+        let p1 = s.acquire().await;
+        let p2 = s.try_acquire().unwrap();
+        assert_eq!(s.permits(), 0);
+        assert!(s.try_acquire().is_none());
+        drop(p1);
+        assert_eq!(s.permits(), 1);
+        drop(p2);
+        assert_eq!(s.permits(), 2);
+    })
+}
+
+#[test]
+fn test_channel() {
+    run(async {
+        let c = R::new_channel(Some(1));
+        c.send(1).await.unwrap();
+        assert_eq!(c.try_send(2), Err(SendError::Full(2)));
+        c.close();
+        assert!(c.is_closed());
+        assert_eq!(c.send(3).await, Err(SendError::Closed(3)));
+        assert_eq!(c.recv().await, Some(1));
+        assert_eq!(c.recv().await, None);
+        assert_eq!(c.try_recv(), Err(TryRecvError::Closed));
+    })
+}
+
+#[test]
+fn test_stream() {
+    run(async {
+        let c = R::box_shared_channel(None);
+        let mut s = R::box_stream(c.clone());
+        let tx = R::unbox_channel(&c);
+        tx.send("potato").await.unwrap();
+        tx.send("salad").await.unwrap();
+        tx.close();
+        let s = R::unbox_mut_stream(&mut s);
+        assert_eq!(s.next().await, Some("potato"));
+        assert_eq!(s.next().await, Some("salad"));
+        assert_eq!(s.next().await, None);
+    })
+}
+
+#[test]
+fn test_broadcast() {
+    run(async {
+        let b = R::box_broadcast(2);
+        let tx = R::unbox_broadcast(&b);
+        // Sending without receivers is not an error.
+        assert_eq!(tx.send(0), 0);
+        let mut rx = R::box_broadcast_receiver(&b);
+        let rx = R::unbox_mut_broadcast_receiver(&mut rx);
+        assert_eq!(tx.receiver_count(), 1);
+        for i in 1..=3 {
+            assert_eq!(tx.send(i), 1);
+        }
+        assert_eq!(rx.recv().await, Err(RecvError::Lagged(1)));
+        assert_eq!(rx.recv().await, Ok(2));
+        assert_eq!(rx.try_recv(), Ok(Some(3)));
+        assert_eq!(rx.try_recv(), Ok(None));
+        drop(b);
+        assert_eq!(rx.recv().await, Err(RecvError::Closed));
+    })
+}
+
+#[test]
+fn test_watch() {
+    run(async {
+        let w = R::box_watch(1);
+        let mut boxed = R::box_watch_receiver(&w);
+        let rx = R::unbox_mut_watch_receiver(&mut boxed);
+        let tx = R::unbox_watch(&w);
+        assert_eq!(tx.receiver_count(), 1);
+        tx.send(2);
+        tx.send(3);
+        // Only the latest value is seen.
+        rx.changed().await.unwrap();
+        assert_eq!(rx.get(), 3);
+        tx.send(4);
+        assert_eq!(tx.get(), 4);
+        drop(w);
+        rx.changed().await.unwrap();
+        assert_eq!(rx.get(), 4);
+        assert_eq!(rx.changed().await, Err(RecvError::Closed));
+    })
+}
+
+#[test]
+fn test_cancel() {
+    run(async {
+        let parent = R::box_cancel_token(None);
+        let child = R::box_cancel_token(Some(&parent));
+        let cancelled = R::unbox_cancel_token(&child).cancelled();
+        assert!(!R::unbox_cancel_token(&child).is_cancelled());
+        R::unbox_cancel_token(&parent).cancel();
+        cancelled.await;
+        assert!(R::unbox_cancel_token(&child).is_cancelled());
+        // A child of a cancelled token starts out cancelled.
+        let grandchild = R::new_cancel_token(Some(&child));
+        assert!(grandchild.is_cancelled());
+    })
+}
+
+#[test]
+fn test_unsupported() {
+    run(async {
+        assert!(R::new_executor().is_err());
+        let addr = SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0).into();
+        let err = R::connect_tcp(addr).await.err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        assert!(R::bind_tcp(addr).await.is_err());
+        assert!(R::bind_udp(addr).await.is_err());
+        let err = R::open_file(Path::new("potato"), &OpenOptions::new())
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "the file system is not available in the browser"
+        );
+    })
+}
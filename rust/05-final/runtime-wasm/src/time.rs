@@ -0,0 +1,51 @@
+use base::{Clock, Elapsed};
+use std::future::{poll_fn, Future};
+use std::pin::pin;
+use std::task::Poll;
+use std::time::{Duration, Instant};
+
+/// The clock for [crate::WasmRuntime]. std has no clock on
+/// wasm32-unknown-unknown, so [Clock::now] panics there. Sleeping uses
+/// the browser's timers and works. Code that needs the time should take
+/// its clock as a parameter so it can be given another one.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WasmClock;
+
+impl Clock for WasmClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send {
+        sleep(duration)
+    }
+}
+
+/// Wait for `duration` using a browser timer. The timer holds a
+/// JavaScript callback, which can't be sent between threads, so it runs
+/// in a local task, and the returned future waits for that task.
+pub(crate) fn sleep(duration: Duration) -> impl Future<Output = ()> + Send {
+    let (tx, rx) = async_channel::bounded(1);
+    wasm_bindgen_futures::spawn_local(async move {
+        gloo_timers::future::sleep(duration).await;
+        let _ = tx.send(()).await;
+    });
+    async move {
+        let _ = rx.recv().await;
+    }
+}
+
+pub(crate) async fn timeout<F: Future>(
+    duration: Duration,
+    future: F,
+) -> Result<F::Output, Elapsed> {
+    let mut future = pin!(future);
+    let mut timer = pin!(sleep(duration));
+    poll_fn(|cx| {
+        if let Poll::Ready(output) = future.as_mut().poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        timer.as_mut().poll(cx).map(|()| Err(Elapsed))
+    })
+    .await
+}
@@ -0,0 +1,84 @@
+use base::{AsyncFile, AsyncTcpListener, AsyncTcpStream, AsyncUdpSocket};
+use std::io;
+use std::net::SocketAddr;
+
+/// The browser has no sockets or files, so [base::Runtime::connect_tcp],
+/// [base::Runtime::bind_tcp], [base::Runtime::bind_udp], and
+/// [base::Runtime::open_file] fail with [io::ErrorKind::Unsupported].
+/// This is the type they would have returned. It has no values.
+pub enum Unsupported {}
+
+impl Unsupported {
+    pub(crate) fn error(what: &str) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("{what} is not available in the browser"),
+        )
+    }
+
+    fn never<T>(&self) -> T {
+        match *self {}
+    }
+}
+
+impl AsyncTcpStream for Unsupported {
+    async fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        self.never()
+    }
+
+    async fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        self.never()
+    }
+
+    async fn shutdown(&mut self) -> io::Result<()> {
+        self.never()
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.never()
+    }
+
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.never()
+    }
+}
+
+impl AsyncTcpListener for Unsupported {
+    async fn accept(
+        &self,
+    ) -> io::Result<(impl AsyncTcpStream + Send + Sync + 'static, SocketAddr)> {
+        self.never::<io::Result<(Unsupported, SocketAddr)>>()
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.never()
+    }
+}
+
+impl AsyncUdpSocket for Unsupported {
+    async fn send_to(&self, _buf: &[u8], _target: SocketAddr) -> io::Result<usize> {
+        self.never()
+    }
+
+    async fn recv_from(&self, _buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.never()
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.never()
+    }
+}
+
+impl AsyncFile for Unsupported {
+    async fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        self.never()
+    }
+
+    async fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        self.never()
+    }
+
+    async fn sync(&mut self) -> io::Result<()> {
+        self.never()
+    }
+}
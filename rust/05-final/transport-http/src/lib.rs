@@ -0,0 +1,236 @@
+//! A [Transport] that sends each request as an HTTP request with hyper,
+//! so a `Controller` can talk to a real server. Requests are GETs
+//! unless they are sent with [Transport::send_with]:
+//!
+//! ```no_run
+//! use controller::Controller;
+//! use runtime_tokio::TokioRuntime;
+//! use transport_http::{HttpConfig, HttpTransport};
+//!
+//! # async fn f() -> Result<(), Box<dyn std::error::Error + Sync + Send>> {
+//! let config = HttpConfig::new("http://localhost:8080/api")?;
+//! let c = Controller::<TokioRuntime, HttpTransport>::new_with(config);
+//! let seq = c.one(5).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The base URL and user agent can also be set through
+//! [TransportConfig], as `Controller::builder` does.
+//!
+//! Connections are kept open and reused between requests. hyper runs
+//! them on tokio, so requests must be sent from inside a tokio runtime,
+//! whichever runtime the controller itself uses. Only plain `http` is
+//! supported.
+use base::{AsyncStream, Connector, Method, Transport, TransportBox, TransportConfig};
+use http_body_util::{BodyExt, Full};
+use hyper::body::{Bytes, Incoming};
+use hyper::header::{HeaderValue, USER_AGENT};
+use hyper::{Request, Response, StatusCode, Uri};
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use implbox::ImplBox;
+use implbox_macros::implbox_impls;
+use std::error::Error;
+use std::fmt;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HttpError {
+    /// The base URL given to [HttpConfig::new] can't be used.
+    InvalidUrl(String),
+    /// The user agent can't be sent in a header.
+    InvalidUserAgent(String),
+    /// No base URL was set.
+    NoBaseUrl,
+    /// The server responded with something other than success.
+    Status(StatusCode),
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpError::InvalidUrl(url) => write!(f, "invalid base URL: {url}"),
+            HttpError::InvalidUserAgent(agent) => write!(f, "invalid user agent: {agent}"),
+            HttpError::NoBaseUrl => write!(f, "no base URL was configured"),
+            HttpError::Status(status) => write!(f, "server responded with {status}"),
+        }
+    }
+}
+
+impl Error for HttpError {}
+
+/// Where an [HttpTransport] sends requests, and how it identifies
+/// itself. The default configuration has no base URL, so every request
+/// fails with [HttpError::NoBaseUrl].
+#[derive(Debug, Clone, Default)]
+pub struct HttpConfig {
+    base_url: Option<String>,
+    user_agent: Option<HeaderValue>,
+}
+
+impl HttpConfig {
+    /// Send requests to paths under `base_url`, which must be an `http`
+    /// URL with a host. A request for `one?val=5` is sent to
+    /// `{base_url}/one?val=5`.
+    pub fn new(base_url: &str) -> Result<Self, HttpError> {
+        let invalid = || HttpError::InvalidUrl(base_url.to_string());
+        let uri: Uri = base_url.parse().map_err(|_| invalid())?;
+        if uri.scheme_str() != Some("http") || uri.host().is_none() || uri.query().is_some() {
+            return Err(invalid());
+        }
+        Ok(HttpConfig {
+            base_url: Some(base_url.trim_end_matches('/').to_string()),
+            user_agent: None,
+        })
+    }
+
+    /// Send `user_agent` in the `User-Agent` header of every request.
+    pub fn with_user_agent(mut self, user_agent: &str) -> Result<Self, HttpError> {
+        let value = HeaderValue::from_str(user_agent)
+            .map_err(|_| HttpError::InvalidUserAgent(user_agent.to_string()))?;
+        self.user_agent = Some(value);
+        Ok(self)
+    }
+
+    pub fn base_url(&self) -> Option<&str> {
+        self.base_url.as_deref()
+    }
+
+    pub fn user_agent(&self) -> Option<&str> {
+        self.user_agent.as_ref().and_then(|v| v.to_str().ok())
+    }
+}
+
+/// A user agent that can't be sent is left out rather than failing
+/// every request.
+impl TransportConfig for HttpConfig {
+    fn set_base_url(&mut self, base_url: &str) -> Result<(), Box<dyn Error + Sync + Send>> {
+        self.base_url = HttpConfig::new(base_url)?.base_url;
+        Ok(())
+    }
+
+    fn set_user_agent(&mut self, user_agent: &str) {
+        self.user_agent = HeaderValue::from_str(user_agent).ok();
+    }
+}
+
+pub struct HttpTransport {
+    client: Client<HttpConnector, Full<Bytes>>,
+    config: HttpConfig,
+}
+
+impl HttpTransport {
+    pub fn new(config: HttpConfig) -> Self {
+        HttpTransport {
+            client: Client::builder(TokioExecutor::new()).build_http(),
+            config,
+        }
+    }
+
+    /// Send a GET for `path` and return the response once its headers
+    /// have arrived.
+    async fn get(&self, path: &str) -> Result<Response<Incoming>, Box<dyn Error + Sync + Send>> {
+        self.request(Method::Get, path, None).await
+    }
+
+    /// Send a request for `path` and return the response once its
+    /// headers have arrived.
+    async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&str>,
+    ) -> Result<Response<Incoming>, Box<dyn Error + Sync + Send>> {
+        let base_url = self.config.base_url.as_ref().ok_or(HttpError::NoBaseUrl)?;
+        let uri: Uri = format!("{base_url}/{path}").parse()?;
+        let body = Full::new(Bytes::from(body.unwrap_or_default().to_string()));
+        let mut request = Request::builder()
+            .method(method.as_str())
+            .uri(uri)
+            .body(body)?;
+        if let Some(user_agent) = &self.config.user_agent {
+            request.headers_mut().insert(USER_AGENT, user_agent.clone());
+        }
+        Ok(self.client.request(request).await?)
+    }
+}
+
+/// The body of a response, returned by [HttpTransport]'s
+/// [Transport::stream]. Each chunk is one data frame as hyper received
+/// it.
+pub struct BodyStream(Incoming);
+
+impl AsyncStream<Result<Vec<u8>, Box<dyn Error + Sync + Send>>> for BodyStream {
+    async fn next(&mut self) -> Option<Result<Vec<u8>, Box<dyn Error + Sync + Send>>> {
+        loop {
+            match self.0.frame().await? {
+                // Trailers aren't part of the body.
+                Ok(frame) => match frame.into_data() {
+                    Ok(data) => return Some(Ok(data.to_vec())),
+                    Err(_) => continue,
+                },
+                Err(e) => return Some(Err(e.into())),
+            }
+        }
+    }
+}
+
+impl Transport for HttpTransport {
+    async fn send(&self, path: &str) -> Result<String, Box<dyn Error + Sync + Send>> {
+        self.send_with(Method::Get, path, None).await
+    }
+
+    async fn send_with(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&str>,
+    ) -> Result<String, Box<dyn Error + Sync + Send>> {
+        let response = self.request(method, path, body).await?;
+        let status = response.status();
+        let body = response.into_body().collect().await?.to_bytes();
+        if !status.is_success() {
+            return Err(HttpError::Status(status).into());
+        }
+        Ok(String::from_utf8(body.to_vec())?)
+    }
+
+    /// A response with an error status fails without reading the body.
+    /// This names the stream type for callers that use the transport
+    /// directly.
+    #[allow(refining_impl_trait)]
+    async fn stream(&self, path: String) -> Result<BodyStream, Box<dyn Error + Sync + Send>> {
+        let response = self.get(&path).await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(HttpError::Status(status).into());
+        }
+        Ok(BodyStream(response.into_body()))
+    }
+
+    /// Send a GET for the base URL. Any response other than a server
+    /// error means the server is up, even if there is nothing there.
+    /// The body is read and discarded so the connection can be reused.
+    async fn ping(&self) -> Result<(), Box<dyn Error + Sync + Send>> {
+        let response = self.get("").await?;
+        let status = response.status();
+        response.into_body().collect().await?;
+        if status.is_server_error() {
+            return Err(HttpError::Status(status).into());
+        }
+        Ok(())
+    }
+}
+
+impl Connector for HttpTransport {
+    type Config = HttpConfig;
+
+    #[implbox_impls(TransportBox, HttpTransport, downcast)]
+    fn new_transport(config: HttpConfig) -> impl Transport + Sync + Send {
+        HttpTransport::new(config)
+    }
+}
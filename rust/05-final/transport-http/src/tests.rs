@@ -0,0 +1,188 @@
+use super::*;
+use base::fault::{Fault, FaultTransport, Latency, Scenario};
+use base::{AsyncStream, TransportConfig};
+use controller::Controller;
+use runtime_tokio::TokioRuntime;
+use std::future::Future;
+use std::sync::Arc;
+use std::task::{Context, Wake, Waker};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+/// Answer each request with the path it asked for, or with 404 if the
+/// path contains "missing", and return the port and the server task,
+/// which returns the requests, including their bodies.
+async fn serve(requests: usize) -> (u16, JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut received = Vec::new();
+        for _ in 0..requests {
+            // Read the head a byte at a time so that none of the body is
+            // read with it.
+            let mut request = Vec::new();
+            while !request.ends_with(b"\r\n\r\n") {
+                request.push(stream.read_u8().await.unwrap());
+            }
+            let mut request = String::from_utf8(request).unwrap();
+            let length = request
+                .lines()
+                .find_map(|line| line.strip_prefix("content-length: "))
+                .map_or(0, |n| n.parse().unwrap());
+            let mut body = vec![0; length];
+            stream.read_exact(&mut body).await.unwrap();
+            request += std::str::from_utf8(&body).unwrap();
+            let path = request.split(' ').nth(1).unwrap();
+            let status = if path.contains("missing") {
+                "404 Not Found"
+            } else {
+                "200 OK"
+            };
+            let response = format!(
+                "HTTP/1.1 {status}\r\ncontent-length: {}\r\n\r\n{path}",
+                path.len()
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            received.push(request);
+        }
+        received
+    });
+    (port, server)
+}
+
+#[test]
+fn test_config() {
+    let c = HttpConfig::new("http://localhost:8080/api/")
+        .unwrap()
+        .with_user_agent("potato")
+        .unwrap();
+    assert_eq!(c.base_url(), Some("http://localhost:8080/api"));
+    assert_eq!(c.user_agent(), Some("potato"));
+    assert_eq!(
+        c.with_user_agent("\n").err(),
+        Some(HttpError::InvalidUserAgent("\n".to_string()))
+    );
+    let mut c = HttpConfig::default();
+    assert!(c.set_base_url("https://localhost").is_err());
+    c.set_base_url("http://localhost").unwrap();
+    assert_eq!(c.base_url(), Some("http://localhost"));
+    for url in ["localhost", "https://localhost", "http://localhost/?a=b"] {
+        assert_eq!(
+            HttpConfig::new(url).err(),
+            Some(HttpError::InvalidUrl(url.to_string()))
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_send() {
+    let (port, server) = serve(5).await;
+    let config = HttpConfig::new(&format!("http://127.0.0.1:{port}/api")).unwrap();
+    let t = HttpTransport::new(config);
+    assert_eq!(t.send("one?val=5").await.unwrap(), "/api/one?val=5");
+    let e = t.send("missing").await.unwrap_err();
+    assert_eq!(
+        e.downcast_ref::<HttpError>(),
+        Some(&HttpError::Status(StatusCode::NOT_FOUND))
+    );
+    t.ping().await.unwrap();
+    let response = t.send_with(Method::Post, "items", Some("salad")).await;
+    assert_eq!(response.unwrap(), "/api/items");
+    // A ping only fails on a server error.
+    let config = HttpConfig::new(&format!("http://127.0.0.1:{port}/missing")).unwrap();
+    let missing = HttpTransport {
+        client: t.client.clone(),
+        config,
+    };
+    missing.ping().await.unwrap();
+    // All of the requests used the same connection.
+    let requests = server.await.unwrap();
+    assert!(requests[2].starts_with("GET /api/ "));
+    assert!(requests[3].starts_with("POST /api/items "));
+    assert!(requests[3].ends_with("\r\n\r\nsalad"));
+    let t = HttpTransport::new(HttpConfig::default());
+    let e = t.send("one").await.unwrap_err();
+    assert_eq!(e.downcast_ref::<HttpError>(), Some(&HttpError::NoBaseUrl));
+    let e = t.ping().await.unwrap_err();
+    assert_eq!(e.downcast_ref::<HttpError>(), Some(&HttpError::NoBaseUrl));
+}
+
+#[tokio::test]
+async fn test_faults() {
+    struct Noop;
+    impl Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    let (port, server) = serve(2).await;
+    let config = HttpConfig::new(&format!("http://127.0.0.1:{port}")).unwrap();
+    let t = FaultTransport::new(
+        HttpTransport::new(config),
+        Scenario {
+            latency: Latency::Fixed(Duration::from_millis(20)),
+            drop_on: Some(2),
+            ..Default::default()
+        },
+    );
+    // The first poll's waker is replaced by the runtime's, which must be
+    // the one woken when the latency is over.
+    let mut send = Box::pin(t.send("one"));
+    let waker = Waker::from(Arc::new(Noop));
+    assert!(send
+        .as_mut()
+        .poll(&mut Context::from_waker(&waker))
+        .is_pending());
+    let body = tokio::time::timeout(Duration::from_secs(5), send).await;
+    assert_eq!(body.unwrap().unwrap(), "/one");
+    // The dropped request never reaches the server.
+    let e = t.send("two").await.unwrap_err();
+    assert_eq!(e.downcast_ref::<Fault>(), Some(&Fault::Dropped));
+    let body = t.send_with(Method::Post, "three", Some("salad")).await;
+    assert_eq!(body.unwrap(), "/three");
+    assert_eq!(t.faults().count(), 3);
+    let requests = server.await.unwrap();
+    assert!(requests[1].starts_with("POST /three "));
+}
+
+#[tokio::test]
+async fn test_controller() {
+    let (port, server) = serve(2).await;
+    let c = Controller::<TokioRuntime, HttpTransport>::builder()
+        .base_url(&format!("http://127.0.0.1:{port}"))
+        .user_agent("salad/1.0")
+        .build()
+        .unwrap();
+    assert_eq!(c.one(5).await.unwrap(), 1);
+    assert_eq!(c.two("potato").await.unwrap(), "/two?val=potato&seq=2");
+    assert_eq!(&*c.last_path().await, "two?val=potato&seq=2");
+    let requests = server.await.unwrap();
+    assert!(requests[0].contains("user-agent: salad/1.0\r\n"));
+    let e = Controller::<TokioRuntime, HttpTransport>::builder()
+        .base_url("ftp://localhost")
+        .build()
+        .err()
+        .unwrap();
+    assert_eq!(e.to_string(), "invalid base URL: ftp://localhost");
+}
+
+#[tokio::test]
+async fn test_stream() {
+    let (port, server) = serve(2).await;
+    let config = HttpConfig::new(&format!("http://127.0.0.1:{port}")).unwrap();
+    let c = Controller::<TokioRuntime, HttpTransport>::new_with(config);
+    let mut stream = c.stream("events").await.unwrap();
+    let mut body = Vec::new();
+    while let Some(chunk) = stream.next().await {
+        body.extend(chunk.unwrap());
+    }
+    assert_eq!(body, b"/events?seq=1");
+    let e = c.stream("missing").await.err().unwrap();
+    assert_eq!(
+        e.to_string(),
+        "transport error: server responded with 404 Not Found"
+    );
+    server.await.unwrap();
+}
@@ -0,0 +1,165 @@
+//! Blocking dispatch of async methods. This packages the pattern
+//! explained in the device crate of `05-final`: to pass an async
+//! method such as `Controller::one` to a generic dispatcher, the
+//! lifetime of the object and the lifetime of the returned future must
+//! be tied together and separated from the caller's lifetime. There is
+//! no syntax for a higher-ranked trait bound that covers two trait
+//! bounds at once, so [AsyncMethod] combines them into a single trait
+//! with a lifetime, and the higher-ranked bound is placed on that.
+//!
+//! ```
+//! use hrtb::{dispatch_blocking, BlockOn};
+//! use std::future::Future;
+//!
+//! struct Counter(i32);
+//! impl Counter {
+//!     async fn add(&self, n: i32) -> i32 {
+//!         self.0 + n
+//!     }
+//! }
+//!
+//! // Any executor can be used. This one only handles futures that
+//! // are immediately ready.
+//! struct Ready;
+//! impl BlockOn for Ready {
+//!     fn block_on<F: Future>(&self, f: F) -> F::Output {
+//!         let mut f = std::pin::pin!(f);
+//!         let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+//!         match f.as_mut().poll(&mut cx) {
+//!             std::task::Poll::Ready(v) => v,
+//!             std::task::Poll::Pending => unimplemented!(),
+//!         }
+//!     }
+//! }
+//!
+//! let c = Counter(1);
+//! assert_eq!(dispatch_blocking(&Ready, &c, Counter::add, 2), 3);
+//! ```
+use std::future::Future;
+
+/// An async function or method taking `&'a Obj` and `Arg` and
+/// returning a future whose output is `Out`. The future may borrow
+/// from the object for `'a`. This is implemented for every suitable
+/// `FnOnce`. Use it with a higher-ranked bound:
+/// `for<'a> F: AsyncMethod<'a, Obj, Arg, Out>`.
+pub trait AsyncMethod<'a, Obj: ?Sized + 'a, Arg, Out>: FnOnce(&'a Obj, Arg) -> Self::Fut {
+    type Fut: Future<Output = Out>;
+}
+
+impl<'a, Obj, Arg, Out, F, Fut> AsyncMethod<'a, Obj, Arg, Out> for F
+where
+    Obj: ?Sized + 'a,
+    F: FnOnce(&'a Obj, Arg) -> Fut,
+    Fut: Future<Output = Out>,
+{
+    type Fut = Fut;
+}
+
+/// An executor that can run a future to completion on the current
+/// thread
+pub trait BlockOn {
+    fn block_on<F: Future>(&self, f: F) -> F::Output;
+}
+
+#[cfg(feature = "tokio")]
+impl BlockOn for tokio::runtime::Runtime {
+    fn block_on<F: Future>(&self, f: F) -> F::Output {
+        tokio::runtime::Runtime::block_on(self, f)
+    }
+}
+
+/// Call `f(obj, arg)` and block on the result using `rt`.
+pub fn dispatch_blocking<Rt, Obj, Arg, Out, F>(rt: &Rt, obj: &Obj, f: F, arg: Arg) -> Out
+where
+    Rt: BlockOn + ?Sized,
+    Obj: ?Sized,
+    for<'a> F: AsyncMethod<'a, Obj, Arg, Out>,
+{
+    rt.block_on(f(obj, arg))
+}
+
+/// Define blocking functions that forward to async methods through a
+/// dispatcher. The dispatcher is any function that can be called as
+/// `dispatch(method, arg)`, typically one that finds the object and
+/// calls [dispatch_blocking]. Each function takes a single argument.
+///
+/// ```ignore
+/// hrtb::sync_facade! {
+///     dispatch = run_method;
+///     /// Send a request and return its sequence number.
+///     pub fn one(val: i32) -> Result<i32, Error> => Controller::one;
+///     pub fn two(val: &str) -> Result<String, Error> => Controller::two;
+/// }
+/// ```
+#[macro_export]
+macro_rules! sync_facade {
+    (
+        dispatch = $dispatch:path;
+        $(
+            $(#[$attr:meta])*
+            $vis:vis fn $name:ident($arg:ident: $arg_t:ty) -> $ret:ty => $method:path;
+        )*
+    ) => {
+        $(
+            $(#[$attr])*
+            $vis fn $name($arg: $arg_t) -> $ret {
+                $dispatch($method, $arg)
+            }
+        )*
+    };
+}
+
+// The tests use tokio's runtime as the executor.
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::*;
+    use std::sync::{Mutex, RwLock};
+
+    struct Store {
+        items: Mutex<Vec<String>>,
+    }
+
+    impl Store {
+        async fn push(&self, item: &str) -> usize {
+            tokio::task::yield_now().await;
+            let mut items = self.items.lock().unwrap();
+            items.push(item.to_string());
+            items.len()
+        }
+
+        async fn get(&self, i: usize) -> Option<String> {
+            self.items.lock().unwrap().get(i).cloned()
+        }
+    }
+
+    static STORE: RwLock<Store> = RwLock::new(Store {
+        items: Mutex::new(Vec::new()),
+    });
+
+    fn run<Arg, Out, F>(f: F, arg: Arg) -> Out
+    where
+        for<'a> F: AsyncMethod<'a, Store, Arg, Out>,
+    {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        // The store is borrowed from a lock guard, so it doesn't live
+        // as long as the caller's lifetimes.
+        let store = STORE.read().unwrap();
+        dispatch_blocking(&rt, &*store, f, arg)
+    }
+
+    sync_facade! {
+        dispatch = run;
+        fn push(item: &str) -> usize => Store::push;
+        fn get(i: usize) -> Option<String> => Store::get;
+    }
+
+    #[test]
+    fn test_facade() {
+        assert_eq!(push("quack"), 1);
+        assert_eq!(push("moo"), 2);
+        assert_eq!(get(1).as_deref(), Some("moo"));
+        assert_eq!(get(2), None);
+    }
+}
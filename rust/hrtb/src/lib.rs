@@ -1,5 +1,12 @@
 // Higher-ranked trait bounds (HRTB)
 
+// This crate explains higher-ranked trait bounds and provides, in the
+// dispatch module, a utility for calling async methods from blocking
+// code that is built on them.
+
+pub mod dispatch;
+pub use dispatch::{dispatch_blocking, AsyncMethod, BlockOn};
+
 // Concepts
 
 // - Monomorphic Function -- a function whose arguments are specified static